@@ -18,5 +18,17 @@ fn main() -> Result<()> {
         .add_instructions(&si)?
         .emit()?;
 
+    #[cfg(feature = "grpc")]
+    compile_protos()?;
+
+    Ok(())
+}
+
+/// 使用protox编译proto文件，不依赖系统安装的protoc
+#[cfg(feature = "grpc")]
+fn compile_protos() -> Result<()> {
+    println!("cargo:rerun-if-changed=proto/berry.proto");
+    let file_descriptors = protox::compile(["proto/berry.proto"], ["proto"])?;
+    tonic_prost_build::configure().compile_fds(file_descriptors)?;
     Ok(())
 }
@@ -1,20 +1,23 @@
-use crate::config::loader::load_config;
+use crate::config::loader::{config_path, load_config};
+use crate::config::watcher::spawn_config_watcher;
 use crate::loadbalance::LoadBalanceService;
+use crate::middleware::{MiddlewareStack, run_middlewares};
 use crate::relay::handler::LoadBalancedHandler;
 use crate::router::router::create_app_router;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use axum::Router;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::{error, info};
-use tracing_subscriber::EnvFilter;
 
 /// 应用状态，包含负载均衡服务
 #[derive(Clone)]
 pub struct AppState {
     pub load_balancer: Arc<LoadBalanceService>,
     pub handler: Arc<LoadBalancedHandler>,
-    pub config: Arc<crate::config::model::Config>,
+    /// 通过管理接口创建的运行时API密钥（配置了keys_file时持久化到该文件，否则重启后失效）
+    pub runtime_users: Arc<std::sync::RwLock<HashMap<String, crate::config::model::UserToken>>>,
 }
 
 impl AppState {
@@ -25,22 +28,96 @@ impl AppState {
         info!("Configuration loaded successfully");
 
         // 创建负载均衡服务
-        let load_balancer = Arc::new(LoadBalanceService::new(config.clone())?);
+        let load_balancer = Arc::new(LoadBalanceService::new(config)?);
 
         // 启动负载均衡服务
         load_balancer.start().await?;
         info!("Load balance service started");
 
+        // 加载持久化的运行时密钥
+        let runtime_users = match &load_balancer.get_config().settings.keys_file {
+            Some(path) => {
+                let keys = crate::auth::keys::load_keys_file(path)?;
+                info!("Loaded {} runtime keys from {}", keys.len(), path);
+                keys
+            }
+            None => HashMap::new(),
+        };
+
         // 创建负载均衡处理器
         let handler = Arc::new(LoadBalancedHandler::new(load_balancer.clone()));
 
         Ok(Self {
             load_balancer,
             handler,
-            config: Arc::new(config),
+            runtime_users: Arc::new(std::sync::RwLock::new(runtime_users)),
         })
     }
 
+    /// 获取当前生效的配置（随热加载更新）
+    pub fn config(&self) -> Arc<crate::config::model::Config> {
+        self.load_balancer.get_config()
+    }
+
+    /// 验证API密钥，依次查找配置文件中的用户和运行时创建的密钥，再校验argon2哈希的密钥，最后按JWT验证
+    pub async fn authenticate(&self, token: &str) -> Option<crate::config::model::UserToken> {
+        let config = self.config();
+        if let Some(user) = config.validate_user_token(token) {
+            return Some(user.clone());
+        }
+
+        let (runtime_user, mut hashes) = {
+            let runtime_users = self.runtime_users.read().ok()?;
+            let runtime_user = runtime_users
+                .values()
+                .find(|user| user.enabled && user.matches_token(token))
+                .cloned();
+            let hashes: Vec<String> = runtime_users
+                .values()
+                .filter(|user| user.enabled)
+                .filter_map(|user| user.argon2_token_hash().map(str::to_string))
+                .collect();
+            (runtime_user, hashes)
+        };
+        if runtime_user.is_some() {
+            return runtime_user;
+        }
+
+        // argon2哈希只在缓存未命中时在阻塞线程池中校验一次
+        hashes.extend(config.argon2_token_hashes());
+        if let Some(hash) = self.load_balancer.get_token_verifier().verify(hashes, token).await {
+            if let Some(user) = config.user_by_token_hash(&hash) {
+                return Some(user.clone());
+            }
+            return self
+                .runtime_users
+                .read()
+                .ok()?
+                .values()
+                .find(|user| user.enabled && user.argon2_token_hash() == Some(hash.as_str()))
+                .cloned();
+        }
+
+        // 最后尝试按JWT验证
+        self.load_balancer
+            .get_jwt_authenticator()
+            .authenticate(&config, token)
+    }
+
+    /// 将运行时密钥写入keys_file，未配置时返回false
+    pub fn persist_runtime_users(&self) -> Result<bool> {
+        let Some(path) = self.config().settings.keys_file.clone() else {
+            return Ok(false);
+        };
+        let users = self
+            .runtime_users
+            .read()
+            .map_err(|_| anyhow::anyhow!("Key store unavailable"))?
+            .clone();
+        crate::auth::keys::save_keys_file(&path, &users)?;
+        Ok(true)
+    }
+
     /// 停止应用
     pub async fn shutdown(&self) {
         info!("Shutting down application...");
@@ -51,17 +128,48 @@ impl AppState {
 
 /// 创建应用路由
 pub fn create_app(state: AppState) -> Router {
-    create_app_router().with_state(state)
+    create_app_with_middleware(state, MiddlewareStack::new())
+}
+
+/// 创建注册了自定义中间件的应用，中间件在请求体大小限制之后、路由处理之前执行
+pub fn create_app_with_middleware(state: AppState, middleware: MiddlewareStack) -> Router {
+    let router = create_app_router();
+    let router = if middleware.is_empty() {
+        router
+    } else {
+        router.layer(axum::middleware::from_fn_with_state(middleware, run_middlewares))
+    };
+    router
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::router::body_limit::limit_request_body,
+        ))
+        // 预检请求不需要认证，在读取请求体之前直接响应
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::router::cors::handle_cors,
+        ))
+        // 在读取请求体之前按来源IP拒绝请求
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::router::network_acl::enforce_network_acl,
+        ))
+        // 最外层分配请求ID，使所有响应（包括413等）都带有该ID
+        .layer(axum::middleware::from_fn(
+            crate::router::request_id::assign_request_id,
+        ))
+        .with_state(state)
 }
 
 /// 启动应用服务器
 pub async fn start_server() -> Result<()> {
-    // 初始化日志 - 完全依赖RUST_LOG环境变量
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .with_file(true)
-        .with_line_number(true)
-        .init();
+    start_server_with_middleware(MiddlewareStack::new()).await
+}
+
+/// 启动注册了自定义中间件的应用服务器
+pub async fn start_server_with_middleware(middleware: MiddlewareStack) -> Result<()> {
+    // 初始化日志 - 完全依赖RUST_LOG环境变量，配置了OTLP地址时同时导出链路追踪
+    let tracer_provider = crate::telemetry::init_tracing();
 
     info!("Starting Berry API server...");
     info!("Build Time: {}", env!("VERGEN_BUILD_TIMESTAMP"));
@@ -76,8 +184,22 @@ pub async fn start_server() -> Result<()> {
         }
     };
 
+    // 监听配置文件变化，自动热加载
+    let _config_watcher = if app_state.config().settings.config_hot_reload {
+        match spawn_config_watcher(&config_path(), app_state.load_balancer.clone()) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                error!("Failed to start config watcher, hot reload disabled: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     // 创建应用
-    let app = create_app(app_state.clone());
+    let app = create_app_with_middleware(app_state.clone(), middleware);
+    let grpc_server = spawn_grpc_server(&app_state);
 
     // 启动服务器
     let bind_addr = std::env::var("BIND_ADDRESS").unwrap_or_else(|_| "127.0.0.1:3000".to_string());
@@ -90,6 +212,9 @@ pub async fn start_server() -> Result<()> {
     info!("  GET  /health        - Health check");
     info!("  GET  /status        - Service status page");
     info!("  GET  /metrics       - Service metrics");
+    info!("  GET  /healthz       - Liveness check");
+    info!("  GET  /readyz        - Readiness check");
+    info!("  *    /admin/*       - Admin API (admin token required)");
     info!("  GET  /models        - List available models");
     info!("  POST /v1/chat/completions - Chat completions (OpenAI compatible)");
     info!("  GET  /v1/models     - List models (OpenAI compatible)");
@@ -104,18 +229,84 @@ pub async fn start_server() -> Result<()> {
     };
 
     // 启动服务器
-    let server = axum::serve(listener, app).with_graceful_shutdown(shutdown_signal);
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal);
 
-    if let Err(e) = server.await {
+    let result = server.await;
+    if let Err(e) = &result {
         error!("Server error: {}", e);
-        app_state.shutdown().await;
-        return Err(e.into());
     }
 
+    if let Some(grpc_server) = grpc_server {
+        grpc_server.abort();
+    }
     app_state.shutdown().await;
+    if let Some(provider) = tracer_provider {
+        crate::telemetry::shutdown_tracing(provider).await;
+    }
+    result.map_err(Into::into)
+}
+
+/// 检查服务器将要使用的配置（CONFIG_PATH或config.toml）后退出，不启动服务
+/// 打印各provider的检查结果，配置无效或任一provider检查失败时返回错误
+pub async fn check_server_config(probe: bool) -> Result<()> {
+    let path = config_path();
+    let config = load_config().with_context(|| format!("Failed to load {}", path))?;
+    let report = crate::config::check::check_config(&config, probe)
+        .await
+        .with_context(|| format!("Invalid configuration in {}", path))?;
+
+    for provider in &report.providers {
+        let probe = match &provider.probe {
+            Some(probe) => format!(
+                ", probe {} ({}ms)",
+                probe.status.map_or("-".to_string(), |s| s.to_string()),
+                probe.latency.as_millis()
+            ),
+            None => String::new(),
+        };
+        match &provider.error {
+            None => println!("{}: ok (key: {}{})", provider.provider_id, provider.key_source, probe),
+            Some(e) => println!("{}: FAIL: {}", provider.provider_id, e),
+        }
+    }
+    println!(
+        "{}: {} providers, {} models, {} users",
+        path,
+        report.providers.len(),
+        report.models,
+        report.users
+    );
+
+    if !report.is_ok() {
+        anyhow::bail!("Configuration check failed for {}", path);
+    }
     Ok(())
 }
 
+/// 配置了gRPC时在后台启动gRPC服务
+fn spawn_grpc_server(app_state: &AppState) -> Option<tokio::task::JoinHandle<()>> {
+    let settings = app_state.config().settings.grpc.clone()?;
+    #[cfg(feature = "grpc")]
+    {
+        let state = app_state.clone();
+        Some(tokio::spawn(async move {
+            if let Err(e) = crate::grpc::serve(state, &settings.listen).await {
+                error!("gRPC server error: {}", e);
+            }
+        }))
+    }
+    #[cfg(not(feature = "grpc"))]
+    {
+        tracing::warn!("settings.grpc is configured but berry was built without the grpc feature, gRPC server disabled");
+        let _ = settings;
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,268 @@
+use crate::config::model::{Config, JwtSettings, UserToken};
+use anyhow::{Context, Result};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// 遇到未知kid时提前拉取JWKS的最小间隔（秒），避免伪造的kid造成大量请求
+const MIN_REFETCH_INTERVAL_SECONDS: i64 = 30;
+
+/// 已拉取的JWKS及其对应的签发者
+struct CachedJwks {
+    issuer: String,
+    keys: JwkSet,
+}
+
+/// JWT认证器：缓存身份提供方的JWKS，把验证通过的令牌映射为用户
+pub struct JwtAuthenticator {
+    client: reqwest::Client,
+    jwks: RwLock<Option<CachedJwks>>,
+    /// 上次拉取JWKS的时间（Unix秒）
+    last_fetch: AtomicI64,
+}
+
+impl Default for JwtAuthenticator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JwtAuthenticator {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(FETCH_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            jwks: RwLock::new(None),
+            last_fetch: AtomicI64::new(0),
+        }
+    }
+
+    /// 拉取JWKS，返回其中的公钥数量；失败时保留之前的公钥
+    pub async fn refresh(&self, settings: &JwtSettings) -> Result<usize> {
+        self.last_fetch.store(chrono::Utc::now().timestamp(), Ordering::Relaxed);
+        let jwks_url = match &settings.jwks_url {
+            Some(url) => url.clone(),
+            None => self.discover(settings).await?,
+        };
+        let keys: JwkSet = self
+            .client
+            .get(&jwks_url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to fetch JWKS from {}", jwks_url))?
+            .json()
+            .await
+            .with_context(|| format!("Invalid JWKS at {}", jwks_url))?;
+
+        let count = keys.keys.len();
+        debug!("Fetched {} JWT signing keys from {}", count, jwks_url);
+        if let Ok(mut jwks) = self.jwks.write() {
+            *jwks = Some(CachedJwks {
+                issuer: settings.issuer.clone(),
+                keys,
+            });
+        }
+        Ok(count)
+    }
+
+    /// 通过OIDC发现文档获取JWKS地址
+    async fn discover(&self, settings: &JwtSettings) -> Result<String> {
+        let url = format!("{}/.well-known/openid-configuration", settings.issuer.trim_end_matches('/'));
+        let document: Value = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to fetch OIDC discovery document from {}", url))?
+            .json()
+            .await
+            .with_context(|| format!("Invalid OIDC discovery document at {}", url))?;
+        document["jwks_uri"]
+            .as_str()
+            .map(str::to_string)
+            .with_context(|| format!("OIDC discovery document at {} has no jwks_uri", url))
+    }
+
+    /// 验证JWT并映射为用户；未启用JWT认证、不是JWT、验证失败或没有匹配的权限时返回None
+    pub fn authenticate(self: &Arc<Self>, config: &Config, token: &str) -> Option<UserToken> {
+        let settings = config.settings.jwt.as_ref()?;
+        if token.split('.').count() != 3 {
+            return None;
+        }
+        let header = jsonwebtoken::decode_header(token).ok()?;
+        // 只接受非对称签名，JWKS中的公钥不能用于验证HMAC
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            debug!("Rejected JWT signed with symmetric algorithm {:?}", header.alg);
+            return None;
+        }
+        let Some(key) = self.find_key(settings, header.kid.as_deref()) else {
+            debug!("No JWKS key matches JWT kid {:?}", header.kid);
+            self.refetch(settings);
+            return None;
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&settings.issuer]);
+        if settings.audience.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&settings.audience);
+        }
+        validation.leeway = settings.leeway_seconds;
+
+        match jsonwebtoken::decode::<Value>(token, &key, &validation) {
+            Ok(data) => user_from_claims(settings, &data.claims),
+            Err(e) => {
+                debug!("Rejected JWT: {}", e);
+                None
+            }
+        }
+    }
+
+    /// 按kid查找公钥，令牌没有kid时只在JWKS中只有一个公钥时使用它
+    fn find_key(&self, settings: &JwtSettings, kid: Option<&str>) -> Option<DecodingKey> {
+        let jwks = self.jwks.read().ok()?;
+        let cached = jwks.as_ref().filter(|cached| cached.issuer == settings.issuer)?;
+        let jwk = match kid {
+            Some(kid) => cached.keys.find(kid)?,
+            None if cached.keys.keys.len() == 1 => &cached.keys.keys[0],
+            None => return None,
+        };
+        DecodingKey::from_jwk(jwk).ok()
+    }
+
+    /// 身份提供方轮换密钥后，在后台提前拉取JWKS
+    fn refetch(self: &Arc<Self>, settings: &JwtSettings) {
+        let now = chrono::Utc::now().timestamp();
+        let last = self.last_fetch.load(Ordering::Relaxed);
+        if now - last < MIN_REFETCH_INTERVAL_SECONDS
+            || self
+                .last_fetch
+                .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+        {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let authenticator = self.clone();
+        let settings = settings.clone();
+        runtime.spawn(async move {
+            if let Err(e) = authenticator.refresh(&settings).await {
+                warn!("Failed to refresh JWKS: {:#}", e);
+            }
+        });
+    }
+}
+
+/// 按点分路径读取claim，如 realm_access.roles
+fn claim<'a>(claims: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(claims, |value, key| value.get(key))
+}
+
+/// 按claims映射用户：用户名取自user_claim，权限使用第一个匹配的角色，没有匹配时使用default_profile
+pub fn user_from_claims(settings: &JwtSettings, claims: &Value) -> Option<UserToken> {
+    let name = match claim(claims, &settings.user_claim)? {
+        Value::String(name) if !name.is_empty() => name.clone(),
+        Value::Number(number) => number.to_string(),
+        _ => return None,
+    };
+    let roles: Vec<&str> = match claim(claims, &settings.roles_claim) {
+        Some(Value::Array(values)) => values.iter().filter_map(Value::as_str).collect(),
+        Some(Value::String(value)) => value.split_whitespace().collect(),
+        _ => Vec::new(),
+    };
+    let Some(profile) = settings
+        .roles
+        .iter()
+        .find(|mapping| roles.contains(&mapping.role.as_str()))
+        .map(|mapping| &mapping.profile)
+        .or(settings.default_profile.as_ref())
+    else {
+        debug!("JWT user '{}' has no matching role", name);
+        return None;
+    };
+
+    Some(UserToken {
+        name,
+        token: String::new(),
+        token_hash: None,
+        allowed_models: profile.allowed_models.clone(),
+        model_aliases: HashMap::new(),
+        enabled: true,
+        rate_limit: profile.rate_limit.clone(),
+        tags: profile.tags.clone(),
+        budget: profile.budget,
+        priority: profile.priority,
+        region: None,
+        content_filters: Vec::new(),
+        allowed_ips: Vec::new(),
+        tenant: None,
+        limits: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::model::{JwtProfile, JwtRole};
+    use serde_json::json;
+
+    fn settings() -> JwtSettings {
+        serde_json::from_value(json!({
+            "issuer": "https://sso.example.com",
+            "user_claim": "email",
+            "roles_claim": "realm_access.roles",
+            "roles": [
+                { "role": "ml", "allowed_models": [], "tags": ["premium"] },
+                { "role": "staff", "allowed_models": ["gpt-4o-mini"] }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_user_from_claims_uses_first_matching_role() {
+        let mut settings = settings();
+        let claims = json!({ "email": "a@example.com", "realm_access": { "roles": ["staff", "ml"] } });
+        let user = user_from_claims(&settings, &claims).unwrap();
+        assert_eq!(user.name, "a@example.com");
+        assert_eq!(user.tags, vec!["premium".to_string()]);
+        assert!(user.allowed_models.is_empty());
+
+        // 没有匹配的角色时使用default_profile，未配置时拒绝
+        let claims = json!({ "email": "b@example.com", "realm_access": { "roles": ["guest"] } });
+        assert!(user_from_claims(&settings, &claims).is_none());
+        settings.default_profile = Some(JwtProfile {
+            allowed_models: vec!["gpt-4o-mini".to_string()],
+            ..Default::default()
+        });
+        let user = user_from_claims(&settings, &claims).unwrap();
+        assert_eq!(user.allowed_models, vec!["gpt-4o-mini".to_string()]);
+
+        assert!(user_from_claims(&settings, &json!({ "realm_access": { "roles": ["ml"] } })).is_none());
+    }
+
+    #[test]
+    fn test_roles_claim_accepts_space_separated_string() {
+        let mut settings = settings();
+        settings.roles_claim = "scope".to_string();
+        settings.roles = vec![JwtRole {
+            role: "berry:chat".to_string(),
+            profile: JwtProfile::default(),
+        }];
+        let claims = json!({ "email": "c@example.com", "scope": "openid berry:chat" });
+        assert!(user_from_claims(&settings, &claims).is_some());
+    }
+}
@@ -0,0 +1,255 @@
+use crate::config::model::UserToken;
+use crate::relay::cache::sha256_hex;
+use anyhow::{Context, Result};
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// SHA-256哈希密钥的前缀，格式为 sha256:<十六进制摘要>
+pub const SHA256_PREFIX: &str = "sha256:";
+
+/// 计算密钥的SHA-256哈希
+pub fn hash_token(token: &str) -> String {
+    format!("{}{}", SHA256_PREFIX, sha256_hex(token.as_bytes()))
+}
+
+/// 校验密钥哈希格式：sha256:<64位十六进制> 或 argon2 PHC字符串
+pub fn validate_token_hash(hash: &str) -> Result<()> {
+    if let Some(digest) = hash.strip_prefix(SHA256_PREFIX) {
+        if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("sha256 token hash must be 64 hex characters");
+        }
+        return Ok(());
+    }
+    if is_argon2_hash(hash) {
+        PasswordHash::new(hash).map_err(|e| anyhow::anyhow!("invalid argon2 hash: {}", e))?;
+        return Ok(());
+    }
+    anyhow::bail!("unsupported token hash format, expected 'sha256:<hex>' or an argon2 PHC string")
+}
+
+/// 校验密钥是否与哈希匹配
+pub fn verify_token_hash(hash: &str, token: &str) -> bool {
+    if let Some(digest) = hash.strip_prefix(SHA256_PREFIX) {
+        return sha256_hex(token.as_bytes()).eq_ignore_ascii_case(digest);
+    }
+    match PasswordHash::new(hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(token.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// 是否为argon2 PHC格式的密钥哈希
+pub fn is_argon2_hash(hash: &str) -> bool {
+    hash.starts_with("$argon2")
+}
+
+/// argon2校验结果缓存的最大条目数，超过时清空重新积累
+const MAX_VERIFIED_TOKENS: usize = 10_000;
+
+/// argon2密钥校验器
+///
+/// argon2校验刻意做得很慢，每个请求都逐个用户校验会被随意构造的密钥耗尽CPU。
+/// 校验器按密钥的SHA-256摘要缓存校验结果（包括没有匹配的结果），同一个密钥只在第一次出现时校验一轮，
+/// 校验在阻塞线程池中执行，不占用异步运行时的工作线程。候选哈希集合变化（热加载、增删密钥）时缓存自动失效。
+#[derive(Default)]
+pub struct TokenVerifier {
+    cache: Mutex<VerifiedTokens>,
+}
+
+#[derive(Default)]
+struct VerifiedTokens {
+    /// 写入缓存时候选哈希集合的指纹
+    fingerprint: u64,
+    /// 密钥摘要 -> 匹配的argon2哈希，None表示没有匹配
+    entries: HashMap<String, Option<String>>,
+}
+
+impl TokenVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 在候选argon2哈希中查找与密钥匹配的一个，没有匹配时返回None
+    pub async fn verify(&self, hashes: Vec<String>, token: &str) -> Option<String> {
+        if hashes.is_empty() {
+            return None;
+        }
+        let fingerprint = fingerprint(&hashes);
+        let digest = sha256_hex(token.as_bytes());
+        if let Some(cached) = self.cached(fingerprint, &digest) {
+            return cached;
+        }
+
+        let token = token.to_string();
+        let matched = tokio::task::spawn_blocking(move || {
+            hashes.into_iter().find(|hash| verify_token_hash(hash, &token))
+        })
+        .await
+        .ok()?;
+        self.store(fingerprint, digest, matched.clone());
+        matched
+    }
+
+    fn cached(&self, fingerprint: u64, digest: &str) -> Option<Option<String>> {
+        let cache = self.cache.lock().ok()?;
+        if cache.fingerprint != fingerprint {
+            return None;
+        }
+        cache.entries.get(digest).cloned()
+    }
+
+    fn store(&self, fingerprint: u64, digest: String, matched: Option<String>) {
+        let Ok(mut cache) = self.cache.lock() else {
+            return;
+        };
+        if cache.fingerprint != fingerprint || cache.entries.len() >= MAX_VERIFIED_TOKENS {
+            cache.entries.clear();
+            cache.fingerprint = fingerprint;
+        }
+        cache.entries.insert(digest, matched);
+    }
+}
+
+/// 候选哈希集合的指纹，与顺序无关
+fn fingerprint(hashes: &[String]) -> u64 {
+    hashes.iter().fold(0, |acc, hash| {
+        let mut hasher = DefaultHasher::new();
+        hash.hash(&mut hasher);
+        acc.wrapping_add(hasher.finish())
+    })
+}
+
+/// 密钥文件内容，与主配置文件分开保存运行时创建的密钥
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct KeysFile {
+    #[serde(default)]
+    keys: HashMap<String, UserToken>,
+}
+
+/// 加载密钥文件，文件不存在时返回空集合
+pub fn load_keys_file(path: &str) -> Result<HashMap<String, UserToken>> {
+    if !Path::new(path).exists() {
+        return Ok(HashMap::new());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read keys file: {}", path))?;
+    let file: KeysFile = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse keys file: {}", path))?;
+
+    for (key_id, user) in &file.keys {
+        match &user.token_hash {
+            Some(hash) => validate_token_hash(hash)
+                .with_context(|| format!("Key '{}' in {} has an invalid token_hash", key_id, path))?,
+            None if user.token.is_empty() => {
+                anyhow::bail!("Key '{}' in {} has neither token nor token_hash", key_id, path)
+            }
+            None => {}
+        }
+    }
+    Ok(file.keys)
+}
+
+/// 保存密钥文件，先写临时文件再重命名，避免写入中断导致文件损坏
+pub fn save_keys_file(path: &str, keys: &HashMap<String, UserToken>) -> Result<()> {
+    let content = toml::to_string_pretty(&KeysFile { keys: keys.clone() })?;
+    let temp_path = format!("{}.tmp", path);
+    std::fs::write(&temp_path, content)
+        .with_context(|| format!("Failed to write keys file: {}", temp_path))?;
+    std::fs::rename(&temp_path, path)
+        .with_context(|| format!("Failed to replace keys file: {}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use argon2::PasswordHasher;
+    use argon2::password_hash::SaltString;
+
+    #[test]
+    fn test_verify_token_hash() {
+        let hash = hash_token("berry-secret");
+        assert!(validate_token_hash(&hash).is_ok());
+        assert!(verify_token_hash(&hash, "berry-secret"));
+        assert!(!verify_token_hash(&hash, "berry-other"));
+
+        let salt = SaltString::encode_b64(b"berry-test-salt").unwrap();
+        let argon2_hash = Argon2::default()
+            .hash_password(b"berry-secret", &salt)
+            .unwrap()
+            .to_string();
+        assert!(validate_token_hash(&argon2_hash).is_ok());
+        assert!(verify_token_hash(&argon2_hash, "berry-secret"));
+        assert!(!verify_token_hash(&argon2_hash, "berry-other"));
+
+        assert!(validate_token_hash("md5:abc").is_err());
+        assert!(validate_token_hash("sha256:xyz").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_token_verifier_caches_results() {
+        let salt = SaltString::encode_b64(b"berry-test-salt").unwrap();
+        let argon2_hash = Argon2::default()
+            .hash_password(b"berry-secret", &salt)
+            .unwrap()
+            .to_string();
+        let hashes = vec![argon2_hash.clone()];
+        let verifier = TokenVerifier::new();
+
+        assert_eq!(verifier.verify(hashes.clone(), "berry-secret").await, Some(argon2_hash.clone()));
+        assert_eq!(verifier.verify(hashes.clone(), "berry-other").await, None);
+        // 匹配和未匹配的结果都按摘要缓存，不再重复argon2校验
+        let digest = sha256_hex(b"berry-secret");
+        assert_eq!(verifier.cached(fingerprint(&hashes), &digest), Some(Some(argon2_hash)));
+        assert_eq!(verifier.cached(fingerprint(&hashes), &sha256_hex(b"berry-other")), Some(None));
+        // 候选哈希变化后缓存失效
+        let changed = vec![hash_token("berry-other")];
+        assert_eq!(verifier.cached(fingerprint(&changed), &digest), None);
+        assert_eq!(verifier.verify(Vec::new(), "berry-secret").await, None);
+    }
+
+    #[test]
+    fn test_keys_file_roundtrip() {
+        let path = std::env::temp_dir()
+            .join(format!("berry-keys-{}.toml", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let mut keys = HashMap::new();
+        keys.insert(
+            "bot".to_string(),
+            UserToken {
+                name: "bot".to_string(),
+                token: String::new(),
+                token_hash: Some(hash_token("berry-secret")),
+                model_aliases: HashMap::new(),
+                allowed_models: vec![],
+                enabled: true,
+                rate_limit: None,
+                tags: vec![],
+                budget: Some(5.0),
+                priority: Default::default(),
+                region: None,
+                content_filters: Vec::new(),
+                allowed_ips: Vec::new(),
+                tenant: None,
+                limits: None,
+            },
+        );
+
+        save_keys_file(&path, &keys).unwrap();
+        let loaded = load_keys_file(&path).unwrap();
+        assert!(loaded["bot"].matches_token("berry-secret"));
+        assert_eq!(loaded["bot"].budget, Some(5.0));
+        // 明文密钥不会被写入文件
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("berry-secret"));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(load_keys_file(&path).unwrap().is_empty());
+    }
+}
@@ -1,14 +1,13 @@
 use axum::{
     extract::{Request, State},
-    http::StatusCode,
     middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
 use std::sync::Arc;
 
 use crate::config::model::Config;
+use crate::relay::handler::types::{ErrorType, error_json};
 use super::types::{AuthenticatedUser, AuthError};
 
 /// 认证中间件
@@ -27,14 +26,9 @@ impl AuthMiddleware {
             .get("authorization")
             .and_then(|h| h.to_str().ok());
 
-        let token = match auth_header {
-            Some(header) => {
-                if header.starts_with("Bearer ") {
-                    &header[7..] // 移除 "Bearer " 前缀
-                } else {
-                    return Err(create_auth_error_response(AuthError::missing_token()));
-                }
-            }
+        // 移除 "Bearer " 前缀
+        let token = match auth_header.and_then(|header| header.strip_prefix("Bearer ")) {
+            Some(token) => token,
             None => {
                 return Err(create_auth_error_response(AuthError::missing_token()));
             }
@@ -89,22 +83,16 @@ impl AuthMiddleware {
 
 /// 创建认证错误响应
 fn create_auth_error_response(error: AuthError) -> Response {
-    let status_code = match error.status {
-        401 => StatusCode::UNAUTHORIZED,
-        403 => StatusCode::FORBIDDEN,
-        429 => StatusCode::TOO_MANY_REQUESTS,
-        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    let error_type = match error.status {
+        401 => ErrorType::Unauthorized,
+        403 => ErrorType::Forbidden,
+        429 => ErrorType::TooManyRequests,
+        _ => ErrorType::InternalServerError,
     };
 
     (
-        status_code,
-        Json(json!({
-            "error": {
-                "type": error.error,
-                "message": error.message,
-                "code": error.status
-            }
-        })),
+        error_type.status_code(),
+        Json(error_json(&error_type, &error.error, &error.message, None)),
     )
         .into_response()
 }
@@ -126,7 +114,7 @@ pub fn validate_request_token<'a>(config: &'a Config, token: &str) -> Result<&'a
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::model::{UserToken, RateLimit};
+    use crate::config::model::{ModelMapping, UserToken, RateLimit};
     use std::collections::HashMap;
 
     fn create_test_config() -> Config {
@@ -134,29 +122,73 @@ mod tests {
         users.insert("test-user".to_string(), UserToken {
             name: "Test User".to_string(),
             token: "test-token-123".to_string(),
+            token_hash: None,
+            model_aliases: HashMap::new(),
             allowed_models: vec!["gpt-4".to_string()],
             enabled: true,
             rate_limit: Some(RateLimit {
                 requests_per_minute: 60,
                 requests_per_hour: 1000,
                 requests_per_day: 10000,
+                tokens_per_minute: None,
             }),
             tags: vec!["test".to_string()],
+            budget: None,
+            priority: Default::default(),
+            region: None,
+            content_filters: Vec::new(),
+            allowed_ips: Vec::new(),
+            tenant: None,
+            limits: None,
         });
 
         users.insert("admin-user".to_string(), UserToken {
             name: "Admin User".to_string(),
             token: "admin-token-456".to_string(),
+            token_hash: None,
+            model_aliases: HashMap::new(),
             allowed_models: vec![], // 允许所有模型
             enabled: true,
             rate_limit: None,
             tags: vec!["admin".to_string()],
+            budget: None,
+            priority: Default::default(),
+            region: None,
+            content_filters: Vec::new(),
+            allowed_ips: Vec::new(),
+            tenant: None,
+            limits: None,
+        });
+
+        let mut models = HashMap::new();
+        models.insert("gpt-4".to_string(), ModelMapping {
+            name: "gpt-4".to_string(),
+            backends: vec![],
+            strategy: Default::default(),
+            enabled: true,
+            virtual_nodes: 100,
+            semantic_cache: true,
+            budget: None,
+            fallback_models: Vec::new(),
+            transforms: Vec::new(),
+            hedging: None,
+            max_in_flight_requests: None,
+            mirror: None,
+            experiment: None,
+            retry: None,
+            latency_slo_ms: None,
+            structured_output: None,
+            fair_queue: None,
+            timeouts: None,
+            content_filters: Vec::new(),
         });
 
         Config {
             providers: HashMap::new(),
-            models: HashMap::new(),
+            models,
             users,
+            peers: HashMap::new(),
+            tenants: HashMap::new(),
             settings: Default::default(),
         }
     }
@@ -1,3 +1,5 @@
+pub mod jwt;
+pub mod keys;
 pub mod middleware;
 pub mod types;
 
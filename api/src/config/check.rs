@@ -0,0 +1,207 @@
+use crate::config::model::Config;
+use crate::config::secrets::{SecretReference, SecretResolver};
+use crate::loadbalance::MetricsCollector;
+use crate::relay::client::openai::OpenAIClient;
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 单个provider的检查结果
+#[derive(Debug, Clone)]
+pub struct ProviderCheck {
+    pub provider_id: String,
+    pub enabled: bool,
+    pub models: usize,
+    /// api_key的来源：inline、vault、aws
+    pub key_source: &'static str,
+    /// 连通性探测结果，未探测时为None
+    pub probe: Option<ProbeResult>,
+    /// 密钥解析或探测失败的原因
+    pub error: Option<String>,
+}
+
+/// models接口探测结果
+#[derive(Debug, Clone)]
+pub struct ProbeResult {
+    pub status: Option<u16>,
+    pub latency: Duration,
+}
+
+/// 配置检查报告
+#[derive(Debug, Clone)]
+pub struct ConfigCheckReport {
+    pub providers: Vec<ProviderCheck>,
+    pub models: usize,
+    pub users: usize,
+}
+
+impl ConfigCheckReport {
+    /// 所有provider都通过检查
+    pub fn is_ok(&self) -> bool {
+        self.providers.iter().all(|provider| provider.error.is_none())
+    }
+}
+
+/// 校验配置并解析外部密钥引用，probe为true时请求每个启用provider的models接口检查连通性和密钥
+/// 配置本身无效时返回错误，单个provider的问题记录在报告中
+pub async fn check_config(config: &Config, probe: bool) -> Result<ConfigCheckReport> {
+    config.validate()?;
+    let config = &config.flatten_tenants();
+
+    let resolver = SecretResolver::new();
+    let metrics = Arc::new(MetricsCollector::new());
+    let mut provider_ids: Vec<&String> = config.providers.keys().collect();
+    provider_ids.sort();
+
+    let mut providers = Vec::new();
+    for provider_id in provider_ids {
+        let provider = &config.providers[provider_id];
+        let reference = SecretReference::parse(&provider.api_key)?;
+        let mut check = ProviderCheck {
+            provider_id: provider_id.clone(),
+            enabled: provider.enabled,
+            models: provider.models.len(),
+            key_source: reference.as_ref().map_or("inline", SecretReference::backend_name),
+            probe: None,
+            error: None,
+        };
+
+        let api_key = match (&reference, &config.settings.secrets) {
+            (Some(reference), Some(settings)) => match resolver.fetch(settings, reference).await {
+                Ok(value) => value,
+                Err(e) => {
+                    check.error = Some(format!("Failed to resolve api_key: {:#}", e));
+                    providers.push(check);
+                    continue;
+                }
+            },
+            _ => provider.api_key.clone(),
+        };
+
+        if probe && provider.enabled {
+            let start = Instant::now();
+            let result = match OpenAIClient::for_provider(
+                provider_id,
+                provider,
+                provider.probe_timeout(&config.settings),
+                metrics.clone(),
+            ) {
+                Ok(client) => client.models(&api_key).await.map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            let latency = start.elapsed();
+            match result {
+                Ok(response) => {
+                    if !response.is_success {
+                        check.error = Some(format!("models API returned {}", response.status));
+                    }
+                    check.probe = Some(ProbeResult { status: Some(response.status), latency });
+                }
+                Err(e) => {
+                    check.error = Some(format!("models API request failed: {}", e));
+                    check.probe = Some(ProbeResult { status: None, latency });
+                }
+            }
+        }
+        providers.push(check);
+    }
+
+    Ok(ConfigCheckReport {
+        providers,
+        models: config.models.len(),
+        users: config.users.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::{ConfigFormat, parse_config};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::get;
+
+    async fn spawn_upstream() -> String {
+        let app = axum::Router::new().route(
+            "/v1/models",
+            get(|headers: HeaderMap| async move {
+                match headers.get("authorization").and_then(|v| v.to_str().ok()) {
+                    Some("Bearer good-key") => (StatusCode::OK, r#"{"data":[]}"#),
+                    _ => (StatusCode::UNAUTHORIZED, r#"{"error":"invalid key"}"#),
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/v1", addr)
+    }
+
+    fn config(base_url: &str) -> Config {
+        parse_config(
+            &format!(
+                r#"
+[providers.good]
+name = "Good"
+base_url = "{base_url}"
+api_key = "good-key"
+models = ["m"]
+
+[providers.bad]
+name = "Bad"
+base_url = "{base_url}"
+api_key = "bad-key"
+models = ["m"]
+
+[providers.off]
+name = "Off"
+base_url = "{base_url}"
+api_key = "bad-key"
+models = ["m"]
+enabled = false
+
+[models.m]
+name = "m"
+backends = [{{ provider = "good", model = "m" }}, {{ provider = "bad", model = "m" }}]
+
+[users.admin]
+name = "Admin"
+token = "admin-token"
+"#
+            ),
+            ConfigFormat::Toml,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_check_without_probe_passes() {
+        let report = check_config(&config("http://127.0.0.1:9"), false).await.unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.providers.len(), 3);
+        assert_eq!((report.models, report.users), (1, 1));
+        assert!(report.providers.iter().all(|p| p.probe.is_none() && p.key_source == "inline"));
+    }
+
+    #[tokio::test]
+    async fn test_probe_reports_rejected_keys() {
+        let report = check_config(&config(&spawn_upstream().await), true).await.unwrap();
+        assert!(!report.is_ok());
+
+        let by_id = |id: &str| report.providers.iter().find(|p| p.provider_id == id).unwrap();
+        assert_eq!(by_id("good").probe.as_ref().unwrap().status, Some(200));
+        assert!(by_id("good").error.is_none());
+        assert_eq!(by_id("bad").probe.as_ref().unwrap().status, Some(401));
+        assert!(by_id("bad").error.as_ref().unwrap().contains("401"));
+        // 禁用的provider不探测
+        assert!(by_id("off").probe.is_none() && by_id("off").error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_config_is_an_error() {
+        let mut config = config("http://127.0.0.1:9");
+        config.providers.get_mut("good").unwrap().api_key.clear();
+        assert!(check_config(&config, false).await.is_err());
+    }
+}
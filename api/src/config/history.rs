@@ -0,0 +1,217 @@
+use crate::config::model::{Config, ConfigHistorySettings};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+
+/// 一个已应用的配置版本（外部密钥引用未解析）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigVersion {
+    pub version: u64,
+    pub applied_at: DateTime<Utc>,
+    /// 配置来源：startup、reload、patch，回滚时为 rollback:目标版本
+    pub source: String,
+    pub config: Config,
+}
+
+/// 历史版本摘要
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigVersionSummary {
+    pub version: u64,
+    pub applied_at: DateTime<Utc>,
+    pub source: String,
+    pub providers: usize,
+    pub models: usize,
+    pub users: usize,
+    /// 是否为当前生效的版本
+    pub current: bool,
+}
+
+/// 配置历史：保留最近应用的若干个配置版本，配置了目录时同时写入磁盘，重启后继续编号
+pub struct ConfigHistory {
+    versions: VecDeque<Arc<ConfigVersion>>,
+    next_version: u64,
+}
+
+impl ConfigHistory {
+    /// 创建配置历史，配置了目录时从目录中加载已保存的版本
+    pub fn load(settings: &ConfigHistorySettings) -> Self {
+        let mut versions: Vec<ConfigVersion> = settings
+            .directory
+            .as_deref()
+            .map(Self::read_directory)
+            .unwrap_or_default();
+        versions.sort_by_key(|v| v.version);
+        let next_version = versions.last().map_or(1, |v| v.version + 1);
+
+        let mut history = Self {
+            versions: versions.into_iter().map(Arc::new).collect(),
+            next_version,
+        };
+        history.prune(settings);
+        history
+    }
+
+    fn read_directory(directory: &str) -> Vec<ConfigVersion> {
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| Self::version_of(path).is_some())
+            .filter_map(|path| {
+                let parsed = std::fs::read_to_string(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|content| Ok(serde_json::from_str::<ConfigVersion>(&content)?));
+                parsed
+                    .inspect_err(|e| warn!("Ignoring unreadable config history file {}: {}", path.display(), e))
+                    .ok()
+            })
+            .collect()
+    }
+
+    fn file_path(directory: &str, version: u64) -> PathBuf {
+        Path::new(directory).join(format!("config-{:06}.json", version))
+    }
+
+    fn version_of(path: &Path) -> Option<u64> {
+        path.file_name()?
+            .to_str()?
+            .strip_prefix("config-")?
+            .strip_suffix(".json")?
+            .parse()
+            .ok()
+    }
+
+    /// 记录一次已应用的配置并返回版本号，与当前版本相同时不产生新版本
+    pub fn record(&mut self, config: &Config, source: &str) -> u64 {
+        if let Some(current) = self.versions.back()
+            && serde_json::to_value(&current.config).ok() == serde_json::to_value(config).ok()
+        {
+            return current.version;
+        }
+
+        let entry = ConfigVersion {
+            version: self.next_version,
+            applied_at: Utc::now(),
+            source: source.to_string(),
+            config: config.clone(),
+        };
+        self.next_version += 1;
+
+        let settings = &config.settings.config_history;
+        if let Some(directory) = &settings.directory
+            && let Err(e) = Self::write_version(directory, &entry)
+        {
+            warn!("Failed to save config version {} to {}: {}", entry.version, directory, e);
+        }
+
+        let version = entry.version;
+        self.versions.push_back(Arc::new(entry));
+        self.prune(settings);
+        version
+    }
+
+    fn write_version(directory: &str, entry: &ConfigVersion) -> anyhow::Result<()> {
+        std::fs::create_dir_all(directory)?;
+        std::fs::write(Self::file_path(directory, entry.version), serde_json::to_string_pretty(entry)?)?;
+        Ok(())
+    }
+
+    /// 只保留最近的max_versions个版本，同时删除目录中多余的文件
+    fn prune(&mut self, settings: &ConfigHistorySettings) {
+        while self.versions.len() > settings.max_versions.max(1) {
+            let Some(removed) = self.versions.pop_front() else {
+                break;
+            };
+            if let Some(directory) = &settings.directory {
+                let _ = std::fs::remove_file(Self::file_path(directory, removed.version));
+            }
+        }
+    }
+
+    /// 所有保留的版本摘要，最新的在前
+    pub fn list(&self) -> Vec<ConfigVersionSummary> {
+        let current = self.versions.back().map(|v| v.version);
+        self.versions
+            .iter()
+            .rev()
+            .map(|v| ConfigVersionSummary {
+                version: v.version,
+                applied_at: v.applied_at,
+                source: v.source.clone(),
+                providers: v.config.providers.len(),
+                models: v.config.models.len(),
+                users: v.config.users.len(),
+                current: Some(v.version) == current,
+            })
+            .collect()
+    }
+
+    pub fn get(&self, version: u64) -> Option<Arc<ConfigVersion>> {
+        self.versions.iter().find(|v| v.version == version).cloned()
+    }
+
+    /// 当前版本之前的一个版本
+    pub fn previous(&self) -> Option<Arc<ConfigVersion>> {
+        self.versions.iter().rev().nth(1).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_retries: u32, settings: &ConfigHistorySettings) -> Config {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../config_example.toml");
+        let mut config = crate::config::loader::load_config_from(path).unwrap();
+        config.settings.max_retries = max_retries;
+        config.settings.config_history = settings.clone();
+        config
+    }
+
+    #[test]
+    fn test_records_versions_and_prunes() {
+        let settings = ConfigHistorySettings {
+            max_versions: 2,
+            directory: None,
+        };
+        let mut history = ConfigHistory::load(&settings);
+        assert_eq!(history.record(&config(1, &settings), "startup"), 1);
+        // 内容不变时不产生新版本
+        assert_eq!(history.record(&config(1, &settings), "reload"), 1);
+        assert_eq!(history.record(&config(2, &settings), "reload"), 2);
+        assert_eq!(history.record(&config(3, &settings), "patch"), 3);
+
+        let list = history.list();
+        assert_eq!(list.iter().map(|v| v.version).collect::<Vec<_>>(), vec![3, 2]);
+        assert!(list[0].current);
+        assert_eq!(history.previous().unwrap().config.settings.max_retries, 2);
+        assert!(history.get(1).is_none());
+    }
+
+    #[test]
+    fn test_versions_survive_restart_on_disk() {
+        let directory = std::env::temp_dir().join(format!("berry-config-history-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let settings = ConfigHistorySettings {
+            max_versions: 2,
+            directory: Some(directory.to_str().unwrap().to_string()),
+        };
+
+        let mut history = ConfigHistory::load(&settings);
+        for retries in 1..=3 {
+            history.record(&config(retries, &settings), "reload");
+        }
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 2);
+
+        let mut restarted = ConfigHistory::load(&settings);
+        assert_eq!(restarted.list().iter().map(|v| v.version).collect::<Vec<_>>(), vec![3, 2]);
+        assert_eq!(restarted.record(&config(3, &settings), "startup"), 3);
+        assert_eq!(restarted.record(&config(4, &settings), "startup"), 4);
+
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
@@ -1,8 +1,339 @@
 use crate::config::model::Config;
+use anyhow::Context;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// 主配置文件中列出需要合并的文件或目录的字段
+const INCLUDE_KEY: &str = "include";
+/// 被合并的文件可以定义的配置段
+const MERGEABLE_SECTIONS: &[&str] = &["providers", "models", "users", "peers", "tenants"];
+
+/// 配置文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// 按格式名称解析（toml、json）
+    pub fn from_name(name: &str) -> Result<Self, anyhow::Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "toml" => Ok(ConfigFormat::Toml),
+            "json" => Ok(ConfigFormat::Json),
+            other => anyhow::bail!("Unsupported config format '{}': expected toml or json", other),
+        }
+    }
+
+    /// 按文件扩展名判断格式，.json为JSON，其余按TOML处理
+    pub fn from_path(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    /// 配置文件使用的格式：CONFIG_FORMAT环境变量优先，未设置时按扩展名判断
+    pub fn for_path(path: &str) -> Result<Self, anyhow::Error> {
+        match std::env::var("CONFIG_FORMAT") {
+            Ok(name) if !name.is_empty() => Self::from_name(&name),
+            _ => Ok(Self::from_path(path)),
+        }
+    }
+}
+
+/// 获取配置文件路径（由CONFIG_PATH环境变量指定，默认config.toml）
+pub fn config_path() -> String {
+    std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.toml".to_string())
+}
 
 pub fn load_config() -> Result<Config, anyhow::Error> {
-    let config_path = std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.toml".to_string());
-    let config_str = std::fs::read_to_string(config_path)?;
-    let config: Config = toml::from_str(&config_str)?;
+    load_config_from(&config_path())
+}
+
+/// 从指定路径加载配置
+pub fn load_config_from(path: &str) -> Result<Config, anyhow::Error> {
+    load_config_with_format(path, ConfigFormat::for_path(path)?)
+}
+
+/// 按指定格式加载配置，并合并 include 中列出的文件
+/// include 的每一项是相对主配置文件的路径，目录会按文件名顺序合并其中所有 .toml 和 .json 文件；
+/// 被合并的文件只能定义 providers、models、users、peers 和 tenants，同一项在多个文件中重复定义时报错
+pub fn load_config_with_format(path: &str, format: ConfigFormat) -> Result<Config, anyhow::Error> {
+    let config_str = std::fs::read_to_string(path)?;
+    let mut root = parse_value(&config_str, format)?;
+    let includes = take_includes(&mut root)?;
+    if includes.is_empty() {
+        return parse_config(&config_str, format);
+    }
+
+    let base = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+    let mut origins: HashMap<(String, String), String> = HashMap::new();
+    for section in MERGEABLE_SECTIONS {
+        if let Some(entries) = root.get(*section).and_then(Value::as_object) {
+            for key in entries.keys() {
+                origins.insert((section.to_string(), key.clone()), path.to_string());
+            }
+        }
+    }
+
+    for file in expand_includes(base, &includes)? {
+        let file_name = file.display().to_string();
+        let content = std::fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read included config {}", file_name))?;
+        let fragment = parse_value(&content, ConfigFormat::from_path(&file_name))
+            .with_context(|| format!("Failed to parse included config {}", file_name))?;
+        merge_fragment(&mut root, fragment, &file_name, &mut origins)?;
+    }
+
+    serde_json::from_value(root).context("Invalid configuration after merging included files")
+}
+
+fn parse_value(content: &str, format: ConfigFormat) -> Result<Value, anyhow::Error> {
+    let value = match format {
+        ConfigFormat::Toml => toml::from_str(content)?,
+        ConfigFormat::Json => serde_json::from_str(content)?,
+    };
+    Ok(value)
+}
+
+/// 取出主配置中的 include 列表
+fn take_includes(root: &mut Value) -> Result<Vec<String>, anyhow::Error> {
+    let Some(include) = root.as_object_mut().and_then(|root| root.remove(INCLUDE_KEY)) else {
+        return Ok(Vec::new());
+    };
+    serde_json::from_value(include).context("include must be a list of file or directory paths")
+}
+
+/// 主配置文件 include 中列出的文件和目录（相对主配置文件解析，目录不展开），用于监听被合并文件的变化
+pub fn include_paths(path: &str) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut root = parse_value(&std::fs::read_to_string(path)?, ConfigFormat::for_path(path)?)?;
+    let base = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+    Ok(take_includes(&mut root)?.iter().map(|include| base.join(include)).collect())
+}
+
+/// 是否是 include 目录中会被合并的配置文件
+pub fn is_config_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml") || ext.eq_ignore_ascii_case("json"))
+}
+
+/// 展开 include 列表，目录按文件名排序后返回其中的配置文件
+fn expand_includes(base: &Path, includes: &[String]) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut files = Vec::new();
+    for include in includes {
+        let path = base.join(include);
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(&path)
+                .with_context(|| format!("Failed to read include directory {}", path.display()))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|entry| entry.is_file() && is_config_file(entry))
+                .collect();
+            entries.sort();
+            files.extend(entries);
+        } else if path.is_file() {
+            files.push(path);
+        } else {
+            anyhow::bail!("Included config {} does not exist", path.display());
+        }
+    }
+    Ok(files)
+}
+
+/// 把被合并文件中的配置段合并到主配置，记录每一项的来源以便报告重复定义
+fn merge_fragment(
+    root: &mut Value,
+    fragment: Value,
+    file_name: &str,
+    origins: &mut HashMap<(String, String), String>,
+) -> Result<(), anyhow::Error> {
+    let Value::Object(fragment) = fragment else {
+        anyhow::bail!("Included config {} must be a table", file_name);
+    };
+    let root = root
+        .as_object_mut()
+        .ok_or_else(|| anyhow::anyhow!("Config root must be a table"))?;
+
+    for (section, entries) in fragment {
+        if !MERGEABLE_SECTIONS.contains(&section.as_str()) {
+            anyhow::bail!(
+                "Included config {} may only define {}, found '{}'",
+                file_name,
+                MERGEABLE_SECTIONS.join(", "),
+                section
+            );
+        }
+        let Value::Object(entries) = entries else {
+            anyhow::bail!("'{}' in included config {} must be a table", section, file_name);
+        };
+        let target = root
+            .entry(section.clone())
+            .or_insert_with(|| Value::Object(Default::default()))
+            .as_object_mut()
+            .ok_or_else(|| anyhow::anyhow!("'{}' in main config must be a table", section))?;
+
+        for (key, entry) in entries {
+            if let Some(previous) = origins.insert((section.clone(), key.clone()), file_name.to_string()) {
+                anyhow::bail!(
+                    "{} '{}' is defined in both {} and {}",
+                    section,
+                    key,
+                    previous,
+                    file_name
+                );
+            }
+            target.insert(key, entry);
+        }
+    }
+    Ok(())
+}
+
+/// 按指定格式解析配置
+pub fn parse_config(content: &str, format: ConfigFormat) -> Result<Config, anyhow::Error> {
+    let config = match format {
+        ConfigFormat::Toml => toml::from_str(content)?,
+        ConfigFormat::Json => serde_json::from_str(content)?,
+    };
     Ok(config)
 }
+
+/// 按指定格式输出配置，可用于在格式之间转换
+pub fn serialize_config(config: &Config, format: ConfigFormat) -> Result<String, anyhow::Error> {
+    match format {
+        ConfigFormat::Toml => toml::to_string_pretty(config).context("Failed to serialize config as TOML"),
+        ConfigFormat::Json => serde_json::to_string_pretty(config).context("Failed to serialize config as JSON"),
+    }
+}
+
+/// 把配置写回指定文件，格式与加载时一致；先写入临时文件再替换，避免写到一半时被热加载读取
+/// 使用 include 拆分的配置无法原样写回，直接报错
+pub fn save_config_to(path: &str, config: &Config) -> Result<(), anyhow::Error> {
+    let format = ConfigFormat::for_path(path)?;
+    if let Ok(existing) = std::fs::read_to_string(path)
+        && parse_value(&existing, format)?.get(INCLUDE_KEY).is_some()
+    {
+        anyhow::bail!("{} uses include and cannot be rewritten as a single file", path);
+    }
+
+    let content = serialize_config(config, format)?;
+    let temp_path = format!("{}.tmp", path);
+    std::fs::write(&temp_path, content).with_context(|| format!("Failed to write {}", temp_path))?;
+    std::fs::rename(&temp_path, path).with_context(|| format!("Failed to replace {}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_detection() {
+        assert_eq!(ConfigFormat::from_path("config.toml"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path("/etc/berry/config.JSON"), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::from_path("config"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_name("json").unwrap(), ConfigFormat::Json);
+        assert!(ConfigFormat::from_name("yaml").is_err());
+    }
+
+    fn write(dir: &Path, name: &str, content: &str) {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+
+    fn include_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("berry-include-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("providers")).unwrap();
+        write(&dir, "config.toml", r#"
+include = ["providers", "models.json"]
+
+[users.admin]
+name = "Admin"
+token = "admin-token"
+"#);
+        write(&dir.join("providers"), "a.toml", r#"
+[providers.a]
+name = "A"
+base_url = "https://a.example.com"
+api_key = "key-a"
+models = ["m"]
+"#);
+        write(&dir.join("providers"), "notes.txt", "ignored");
+        write(&dir, "models.json", r#"{
+  "models": {
+    "m": { "name": "m", "backends": [{ "provider": "a", "model": "m" }] }
+  }
+}"#);
+        dir
+    }
+
+    #[test]
+    fn test_include_merges_files_and_directories() {
+        let dir = include_dir("merge");
+        write(&dir.join("providers"), "b.toml", r#"
+[providers.b]
+name = "B"
+base_url = "https://b.example.com"
+api_key = "key-b"
+models = ["m"]
+"#);
+
+        let config = load_config_from(dir.join("config.toml").to_str().unwrap()).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.providers.len(), 2);
+        assert!(config.models.contains_key("m"));
+        assert!(config.users.contains_key("admin"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_include_rejects_conflicts_and_settings() {
+        let dir = include_dir("conflict");
+        let path = dir.join("config.toml");
+        write(&dir.join("providers"), "b.toml", "[providers.a]\nname = \"Duplicate\"\n");
+        let error = load_config_from(path.to_str().unwrap()).unwrap_err().to_string();
+        assert!(error.contains("providers 'a' is defined in both"), "{}", error);
+
+        write(&dir.join("providers"), "b.toml", "[settings]\nmax_retries = 1\n");
+        let error = load_config_from(path.to_str().unwrap()).unwrap_err().to_string();
+        assert!(error.contains("found 'settings'"), "{}", error);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_save_config_round_trips_and_refuses_includes() {
+        let dir = include_dir("save");
+        let path = dir.join("config.toml");
+        let path = path.to_str().unwrap();
+        let config = load_config_from(path).unwrap();
+        assert!(save_config_to(path, &config).unwrap_err().to_string().contains("uses include"));
+
+        let flat = dir.join("flat.json");
+        let flat = flat.to_str().unwrap();
+        save_config_to(flat, &config).unwrap();
+        let saved = load_config_from(flat).unwrap();
+        assert_eq!(serde_json::to_value(&saved).unwrap(), serde_json::to_value(&config).unwrap());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_round_trip_between_formats() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../config_example.toml");
+        let original = load_config_from(path).unwrap();
+
+        let json = serialize_config(&original, ConfigFormat::Json).unwrap();
+        let from_json = parse_config(&json, ConfigFormat::Json).unwrap();
+        let toml = serialize_config(&from_json, ConfigFormat::Toml).unwrap();
+        let from_toml = parse_config(&toml, ConfigFormat::Toml).unwrap();
+
+        from_toml.validate().unwrap();
+        assert_eq!(
+            serde_json::to_value(&original).unwrap(),
+            serde_json::to_value(&from_toml).unwrap()
+        );
+    }
+}
@@ -1,2 +1,8 @@
 pub mod model;
-pub mod loader;
\ No newline at end of file
+pub mod check;
+pub mod history;
+pub mod loader;
+pub mod patch;
+pub mod schedule;
+pub mod secrets;
+pub mod watcher;
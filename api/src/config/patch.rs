@@ -0,0 +1,133 @@
+use crate::config::model::Config;
+use anyhow::Context;
+use serde_json::{Map, Value};
+
+/// 按JSON Merge Patch（RFC 7386）规则修改配置：对象逐字段合并，null表示删除，其他值直接替换
+/// 模型（包括租户的模型）的backends除了可以整体替换为数组，也可以传入以"provider:model"为键的对象，只修改对应的后端
+pub fn apply_patch(config: &Config, patch: &Value) -> Result<Config, anyhow::Error> {
+    if !patch.is_object() {
+        anyhow::bail!("Config patch must be a JSON object");
+    }
+    let mut value = serde_json::to_value(config).context("Failed to serialize current config")?;
+    merge(&mut value, patch, &[])?;
+    serde_json::from_value(value).context("Patched configuration is invalid")
+}
+
+fn merge(target: &mut Value, patch: &Value, path: &[&str]) -> Result<(), anyhow::Error> {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return Ok(());
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else {
+        unreachable!();
+    };
+
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+            continue;
+        }
+        let is_backends = matches!(path, ["models", _] | ["tenants", _, "models", _]) && key == "backends";
+        match target.get_mut(key) {
+            Some(Value::Array(backends)) if is_backends && value.is_object() => {
+                patch_backends(backends, value, path[path.len() - 1])?;
+            }
+            Some(existing) => {
+                let mut child_path = path.to_vec();
+                child_path.push(key);
+                merge(existing, value, &child_path)?;
+            }
+            None => {
+                let mut entry = Value::Null;
+                merge(&mut entry, value, &[])?;
+                target.insert(key.clone(), entry);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 按"provider:model"修改模型中的单个后端，null表示移除该后端
+fn patch_backends(backends: &mut Vec<Value>, patch: &Value, model: &str) -> Result<(), anyhow::Error> {
+    let Value::Object(patch) = patch else {
+        unreachable!();
+    };
+    for (backend_key, value) in patch {
+        let index = backends.iter().position(|backend| {
+            let provider = backend.get("provider").and_then(Value::as_str).unwrap_or_default();
+            let name = backend.get("model").and_then(Value::as_str).unwrap_or_default();
+            format!("{}:{}", provider, name) == *backend_key
+        });
+        match (index, value.is_null()) {
+            (Some(index), true) => {
+                backends.remove(index);
+            }
+            (Some(index), false) => merge(&mut backends[index], value, &[])?,
+            (None, _) => anyhow::bail!("Backend '{}' not found in model '{}'", backend_key, model),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> Config {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../config_example.toml");
+        crate::config::loader::load_config_from(path).unwrap()
+    }
+
+    #[test]
+    fn test_merge_patch_adds_changes_and_removes() {
+        let original = config();
+        let (model_id, mapping) = original.models.iter().next().unwrap();
+        let provider = mapping.backends[0].provider.clone();
+        let backend_key = format!("{}:{}", provider, mapping.backends[0].model);
+
+        let patched = apply_patch(&original, &json!({
+            "providers": {
+                "extra": {
+                    "name": "Extra",
+                    "base_url": "https://extra.example.com",
+                    "api_key": "key",
+                    "models": ["m"]
+                }
+            },
+            "models": {
+                model_id: {
+                    "enabled": false,
+                    "backends": { backend_key: { "weight": 7.5 } }
+                }
+            },
+            "settings": { "max_retries": 9 }
+        }))
+        .unwrap();
+
+        assert!(patched.providers.contains_key("extra"));
+        assert!(!patched.models[model_id].enabled);
+        assert_eq!(patched.models[model_id].backends[0].weight, 7.5);
+        assert_eq!(patched.models[model_id].backends.len(), mapping.backends.len());
+        assert_eq!(patched.settings.max_retries, 9);
+        assert_eq!(patched.users.len(), original.users.len());
+
+        let removed = apply_patch(&patched, &json!({ "providers": { "extra": null } })).unwrap();
+        assert!(!removed.providers.contains_key("extra"));
+    }
+
+    #[test]
+    fn test_unknown_backend_is_rejected() {
+        let original = config();
+        let model_id = original.models.keys().next().unwrap().clone();
+        let error = apply_patch(&original, &json!({
+            "models": { model_id: { "backends": { "missing:model": { "weight": 1.0 } } } }
+        }))
+        .unwrap_err();
+        assert!(error.to_string().contains("Backend 'missing:model' not found"));
+        assert!(apply_patch(&original, &json!([])).is_err());
+    }
+}
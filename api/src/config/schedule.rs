@@ -0,0 +1,194 @@
+use crate::config::model::BackendSchedule;
+use anyhow::Context;
+use chrono::{DateTime, Datelike, FixedOffset, Timelike, Utc};
+use std::str::FromStr;
+
+const DAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// 时间表中的一个时间窗口，如"mon-fri 09:00-18:00"
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleWindow {
+    /// 生效的星期，下标0为周一
+    days: [bool; 7],
+    /// 开始和结束时间（当天的分钟数），结束早于开始时窗口跨越午夜
+    start: u32,
+    end: u32,
+}
+
+impl ScheduleWindow {
+    /// 指定星期（0为周一）和当天分钟数是否在窗口内，跨越午夜的部分属于开始的那一天
+    pub fn contains(&self, weekday: usize, minute: u32) -> bool {
+        if self.start < self.end {
+            self.days[weekday] && (self.start..self.end).contains(&minute)
+        } else {
+            let previous = (weekday + 6) % 7;
+            (self.days[weekday] && minute >= self.start) || (self.days[previous] && minute < self.end)
+        }
+    }
+
+    fn parse_days(spec: &str) -> anyhow::Result<[bool; 7]> {
+        let mut days = [false; 7];
+        if spec == "*" {
+            return Ok([true; 7]);
+        }
+        for part in spec.split(',') {
+            let day = |name: &str| {
+                DAY_NAMES
+                    .iter()
+                    .position(|d| d.eq_ignore_ascii_case(name))
+                    .with_context(|| format!("unknown day '{}'", name))
+            };
+            match part.split_once('-') {
+                Some((from, to)) => {
+                    let (from, to) = (day(from)?, day(to)?);
+                    // 范围可以跨越周末，如"fri-mon"
+                    let mut current = from;
+                    loop {
+                        days[current] = true;
+                        if current == to {
+                            break;
+                        }
+                        current = (current + 1) % 7;
+                    }
+                }
+                None => days[day(part)?] = true,
+            }
+        }
+        Ok(days)
+    }
+
+    fn parse_time(spec: &str) -> anyhow::Result<u32> {
+        let (hour, minute) = spec
+            .split_once(':')
+            .with_context(|| format!("invalid time '{}', expected HH:MM", spec))?;
+        let (hour, minute): (u32, u32) = (
+            hour.parse().with_context(|| format!("invalid hour in '{}'", spec))?,
+            minute.parse().with_context(|| format!("invalid minute in '{}'", spec))?,
+        );
+        let total = hour * 60 + minute;
+        if minute >= 60 || total > MINUTES_PER_DAY {
+            anyhow::bail!("time '{}' is out of range", spec);
+        }
+        Ok(total)
+    }
+}
+
+impl FromStr for ScheduleWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (days, times) = spec
+            .trim()
+            .split_once(char::is_whitespace)
+            .with_context(|| format!("invalid window '{}', expected '<days> <HH:MM>-<HH:MM>'", spec))?;
+        let (start, end) = times
+            .trim()
+            .split_once('-')
+            .with_context(|| format!("invalid time range '{}'", times.trim()))?;
+        let window = Self {
+            days: Self::parse_days(days)?,
+            start: Self::parse_time(start)?,
+            end: Self::parse_time(end)?,
+        };
+        if window.start == window.end || window.start == MINUTES_PER_DAY {
+            anyhow::bail!("window '{}' is empty", spec);
+        }
+        Ok(window)
+    }
+}
+
+/// 解析"+08:00"形式的UTC偏移，"UTC"和"Z"表示UTC
+fn parse_offset(spec: &str) -> anyhow::Result<FixedOffset> {
+    if spec.eq_ignore_ascii_case("utc") || spec == "Z" {
+        return Ok(FixedOffset::east_opt(0).unwrap());
+    }
+    let (sign, rest) = match spec.split_at_checked(1) {
+        Some(("+", rest)) => (1, rest),
+        Some(("-", rest)) => (-1, rest),
+        _ => anyhow::bail!("invalid timezone '{}', expected an offset like +08:00", spec),
+    };
+    let minutes = ScheduleWindow::parse_time(rest)
+        .with_context(|| format!("invalid timezone '{}'", spec))? as i32;
+    FixedOffset::east_opt(sign * minutes * 60).with_context(|| format!("invalid timezone '{}'", spec))
+}
+
+impl BackendSchedule {
+    /// 检查时区和时间窗口的格式
+    pub fn validate(&self) -> anyhow::Result<()> {
+        parse_offset(&self.timezone)?;
+        if self.windows.is_empty() {
+            anyhow::bail!("schedule must contain at least one window");
+        }
+        for window in &self.windows {
+            window.parse::<ScheduleWindow>()?;
+        }
+        Ok(())
+    }
+
+    /// 指定时刻是否在任一时间窗口内，格式错误的窗口视为不生效
+    pub fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        let Ok(offset) = parse_offset(&self.timezone) else {
+            return false;
+        };
+        let local = now.with_timezone(&offset);
+        let weekday = local.weekday().num_days_from_monday() as usize;
+        let minute = local.hour() * 60 + local.minute();
+        self.windows
+            .iter()
+            .filter_map(|window| window.parse::<ScheduleWindow>().ok())
+            .any(|window| window.contains(weekday, minute))
+    }
+
+    /// 当前是否在时间窗口内
+    pub fn is_active(&self) -> bool {
+        self.is_active_at(Utc::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn schedule(timezone: &str, windows: &[&str]) -> BackendSchedule {
+        BackendSchedule {
+            timezone: timezone.to_string(),
+            windows: windows.iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_business_hours_in_local_timezone() {
+        let business = schedule("+08:00", &["mon-fri 09:00-18:00"]);
+        // 2026-10-16是周五，UTC 02:00即北京时间10:00
+        assert!(business.is_active_at(Utc.with_ymd_and_hms(2026, 10, 16, 2, 0, 0).unwrap()));
+        assert!(!business.is_active_at(Utc.with_ymd_and_hms(2026, 10, 16, 10, 0, 0).unwrap()));
+        // 周六
+        assert!(!business.is_active_at(Utc.with_ymd_and_hms(2026, 10, 17, 2, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_overnight_and_weekend_windows() {
+        let nights = schedule("UTC", &["mon-fri 18:00-09:00", "sat,sun 00:00-24:00"]);
+        // 周五晚上和周六凌晨（属于周五的夜间窗口）
+        assert!(nights.is_active_at(Utc.with_ymd_and_hms(2026, 10, 16, 22, 0, 0).unwrap()));
+        assert!(nights.is_active_at(Utc.with_ymd_and_hms(2026, 10, 17, 3, 0, 0).unwrap()));
+        // 周日没有夜间窗口，周一凌晨和白天都不生效
+        assert!(!nights.is_active_at(Utc.with_ymd_and_hms(2026, 10, 19, 3, 0, 0).unwrap()));
+        assert!(!nights.is_active_at(Utc.with_ymd_and_hms(2026, 10, 19, 12, 0, 0).unwrap()));
+        // 周二凌晨属于周一的夜间窗口
+        assert!(nights.is_active_at(Utc.with_ymd_and_hms(2026, 10, 20, 3, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_invalid_schedules_are_rejected() {
+        assert!(schedule("+08:00", &["* 00:00-24:00"]).validate().is_ok());
+        assert!(schedule("+08:00", &["fri-mon 20:00-06:00"]).validate().is_ok());
+        assert!(schedule("Asia/Shanghai", &["* 09:00-18:00"]).validate().is_err());
+        assert!(schedule("UTC", &["weekdays 09:00-18:00"]).validate().is_err());
+        assert!(schedule("UTC", &["mon 09:00-09:00"]).validate().is_err());
+        assert!(schedule("UTC", &["mon 25:00-26:00"]).validate().is_err());
+        assert!(schedule("UTC", &[]).validate().is_err());
+    }
+}
@@ -0,0 +1,381 @@
+use crate::config::model::{AwsSecretsSettings, Config, SecretsSettings, VaultSettings};
+use crate::relay::cache::sha256_hex;
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::debug;
+
+const VAULT_PREFIX: &str = "vault://";
+const AWS_PREFIX: &str = "aws-sm://";
+/// Vault引用未指定字段时读取的字段名
+const DEFAULT_VAULT_FIELD: &str = "api_key";
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// provider的api_key中引用的外部密钥
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SecretReference {
+    /// vault://路径#字段，字段默认为api_key
+    Vault { path: String, field: String },
+    /// aws-sm://密钥ID#字段，未指定字段时使用整个SecretString
+    AwsSecretsManager { secret_id: String, field: Option<String> },
+}
+
+impl SecretReference {
+    /// 解析密钥引用，普通的api_key返回None
+    pub fn parse(value: &str) -> Result<Option<Self>> {
+        let (target, rest) = if let Some(rest) = value.strip_prefix(VAULT_PREFIX) {
+            ("vault", rest)
+        } else if let Some(rest) = value.strip_prefix(AWS_PREFIX) {
+            ("aws", rest)
+        } else {
+            return Ok(None);
+        };
+
+        let (path, field) = match rest.split_once('#') {
+            Some((path, field)) => (path, Some(field)),
+            None => (rest, None),
+        };
+        if path.is_empty() {
+            anyhow::bail!("secret path is empty in '{}'", value);
+        }
+        if field.is_some_and(str::is_empty) {
+            anyhow::bail!("secret field is empty in '{}'", value);
+        }
+
+        Ok(Some(match target {
+            "vault" => SecretReference::Vault {
+                path: path.trim_matches('/').to_string(),
+                field: field.unwrap_or(DEFAULT_VAULT_FIELD).to_string(),
+            },
+            _ => SecretReference::AwsSecretsManager {
+                secret_id: path.to_string(),
+                field: field.map(str::to_string),
+            },
+        }))
+    }
+
+    /// 对应的settings.secrets配置项名称
+    pub fn backend_name(&self) -> &'static str {
+        match self {
+            SecretReference::Vault { .. } => "vault",
+            SecretReference::AwsSecretsManager { .. } => "aws",
+        }
+    }
+}
+
+/// 配置中是否有provider引用了外部密钥
+pub fn has_secret_references(config: &Config) -> bool {
+    config
+        .providers
+        .values()
+        .any(|provider| matches!(SecretReference::parse(&provider.api_key), Ok(Some(_))))
+}
+
+/// 外部密钥解析器：拉取配置中引用的密钥，生成替换为实际密钥的配置
+pub struct SecretResolver {
+    client: reqwest::Client,
+}
+
+impl SecretResolver {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(FETCH_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// 拉取所有引用的密钥，返回api_key替换为实际值的配置
+    /// 任一密钥拉取失败时返回错误，调用方应继续使用之前的配置
+    pub async fn resolve(&self, config: &Config) -> Result<Config> {
+        let mut resolved = config.clone();
+        let Some(settings) = &config.settings.secrets else {
+            return Ok(resolved);
+        };
+
+        let mut fetched: HashMap<SecretReference, String> = HashMap::new();
+        for (provider_id, provider) in resolved.providers.iter_mut() {
+            let Some(reference) = SecretReference::parse(&provider.api_key)? else {
+                continue;
+            };
+            let value = match fetched.get(&reference) {
+                Some(value) => value.clone(),
+                None => {
+                    let value = self
+                        .fetch(settings, &reference)
+                        .await
+                        .with_context(|| format!("Failed to fetch secret '{}' for provider '{}'", provider.api_key, provider_id))?;
+                    fetched.insert(reference, value.clone());
+                    value
+                }
+            };
+            provider.api_key = value;
+        }
+        debug!("Resolved {} secret references", fetched.len());
+        Ok(resolved)
+    }
+
+    /// 拉取单个密钥
+    pub async fn fetch(&self, settings: &SecretsSettings, reference: &SecretReference) -> Result<String> {
+        let value = match reference {
+            SecretReference::Vault { path, field } => {
+                let vault = settings
+                    .vault
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("settings.secrets.vault is not configured"))?;
+                self.fetch_vault(vault, path, field).await?
+            }
+            SecretReference::AwsSecretsManager { secret_id, field } => {
+                let aws = settings
+                    .aws
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("settings.secrets.aws is not configured"))?;
+                self.fetch_aws(aws, secret_id, field.as_deref()).await?
+            }
+        };
+        if value.is_empty() {
+            anyhow::bail!("secret is empty");
+        }
+        Ok(value)
+    }
+
+    /// 从Vault KV v2读取密钥字段
+    async fn fetch_vault(&self, vault: &VaultSettings, path: &str, field: &str) -> Result<String> {
+        let token = std::env::var(&vault.token_env)
+            .with_context(|| format!("Vault token environment variable {} is not set", vault.token_env))?;
+        let url = format!(
+            "{}/v1/{}/data/{}",
+            vault.address.trim_end_matches('/'),
+            vault.mount.trim_matches('/'),
+            path
+        );
+
+        let mut request = self.client.get(&url).header("X-Vault-Token", token);
+        if let Some(namespace) = &vault.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("Vault returned {} for {}", status, path);
+        }
+        let body: Value = response.json().await?;
+        body["data"]["data"][field]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::anyhow!("field '{}' not found in Vault secret {}", field, path))
+    }
+
+    /// 调用AWS Secrets Manager的GetSecretValue
+    async fn fetch_aws(&self, aws: &AwsSecretsSettings, secret_id: &str, field: Option<&str>) -> Result<String> {
+        let credentials = AwsCredentials::from_env()?;
+        let endpoint = aws
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://secretsmanager.{}.amazonaws.com", aws.region));
+        let url = reqwest::Url::parse(&endpoint)?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => anyhow::bail!("Invalid secrets.aws.endpoint '{}'", endpoint),
+        };
+
+        let payload = json!({ "SecretId": secret_id }).to_string();
+        let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let mut headers = vec![
+            ("content-type".to_string(), "application/x-amz-json-1.1".to_string()),
+            ("host".to_string(), host),
+            ("x-amz-date".to_string(), amz_date.clone()),
+            ("x-amz-target".to_string(), "secretsmanager.GetSecretValue".to_string()),
+        ];
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token".to_string(), token.clone()));
+        }
+        let authorization = sign_v4(
+            &credentials,
+            &SigningScope {
+                amz_date: &amz_date,
+                region: &aws.region,
+                service: "secretsmanager",
+            },
+            "POST",
+            url.path(),
+            &headers,
+            payload.as_bytes(),
+        );
+
+        let mut request = self.client.post(url).header("authorization", authorization).body(payload);
+        for (name, value) in headers.iter().filter(|(name, _)| name != "host") {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("AWS Secrets Manager returned {} for {}", status, secret_id);
+        }
+        let body: Value = response.json().await?;
+        let secret = body["SecretString"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("secret {} has no SecretString", secret_id))?;
+
+        match field {
+            None => Ok(secret.to_string()),
+            Some(field) => serde_json::from_str::<Value>(secret)
+                .ok()
+                .and_then(|value| value[field].as_str().map(str::to_string))
+                .ok_or_else(|| anyhow::anyhow!("field '{}' not found in secret {}", field, secret_id)),
+        }
+    }
+}
+
+impl Default for SecretResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// AWS访问凭证
+struct AwsCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            access_key_id: std::env::var("AWS_ACCESS_KEY_ID").context("AWS_ACCESS_KEY_ID is not set")?,
+            secret_access_key: std::env::var("AWS_SECRET_ACCESS_KEY").context("AWS_SECRET_ACCESS_KEY is not set")?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+}
+
+/// SigV4签名范围
+struct SigningScope<'a> {
+    /// 格式为 YYYYMMDDTHHMMSSZ
+    amz_date: &'a str,
+    region: &'a str,
+    service: &'a str,
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// 按AWS Signature Version 4计算Authorization头，headers需包含所有参与签名的请求头（小写名称）
+fn sign_v4(
+    credentials: &AwsCredentials,
+    scope: &SigningScope,
+    method: &str,
+    path: &str,
+    headers: &[(String, String)],
+    payload: &[u8],
+) -> String {
+    let mut headers: Vec<&(String, String)> = headers.iter().collect();
+    headers.sort_by(|a, b| a.0.cmp(&b.0));
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        method,
+        if path.is_empty() { "/" } else { path },
+        canonical_headers,
+        signed_headers,
+        sha256_hex(payload)
+    );
+
+    let date = &scope.amz_date[..8];
+    let credential_scope = format!("{}/{}/{}/aws4_request", date, scope.region, scope.service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        scope.amz_date,
+        credential_scope,
+        sha256_hex(canonical_request.as_bytes())
+    );
+
+    let signing_key = [scope.region, scope.service, "aws4_request"].iter().fold(
+        hmac_sha256(format!("AWS4{}", credentials.secret_access_key).as_bytes(), date.as_bytes()),
+        |key, part| hmac_sha256(&key, part.as_bytes()),
+    );
+    let signature: String = hmac_sha256(&signing_key, string_to_sign.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, credential_scope, signed_headers, signature
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_references() {
+        assert_eq!(SecretReference::parse("sk-plain").unwrap(), None);
+        assert_eq!(
+            SecretReference::parse("vault://openai/prod").unwrap(),
+            Some(SecretReference::Vault {
+                path: "openai/prod".to_string(),
+                field: "api_key".to_string(),
+            })
+        );
+        assert_eq!(
+            SecretReference::parse("aws-sm://prod/openai#key").unwrap(),
+            Some(SecretReference::AwsSecretsManager {
+                secret_id: "prod/openai".to_string(),
+                field: Some("key".to_string()),
+            })
+        );
+        assert!(SecretReference::parse("vault://").is_err());
+        assert!(SecretReference::parse("aws-sm://id#").is_err());
+    }
+
+    #[test]
+    fn test_sign_v4_matches_aws_test_suite() {
+        // AWS SigV4测试套件中的get-vanilla用例
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+        let headers = vec![
+            ("host".to_string(), "example.amazonaws.com".to_string()),
+            ("x-amz-date".to_string(), "20150830T123600Z".to_string()),
+        ];
+        let authorization = sign_v4(
+            &credentials,
+            &SigningScope {
+                amz_date: "20150830T123600Z",
+                region: "us-east-1",
+                service: "service",
+            },
+            "GET",
+            "/",
+            &headers,
+            b"",
+        );
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+}
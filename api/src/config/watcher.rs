@@ -0,0 +1,263 @@
+use crate::config::loader::{include_paths, is_config_file, load_config_from};
+use crate::loadbalance::LoadBalanceService;
+use anyhow::Result;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde_json::json;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+/// 文件变化后等待的时间，合并编辑器保存时产生的多次事件
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// 配置文件监听器，被丢弃后停止监听
+pub struct ConfigWatcher {
+    _watcher: Arc<Mutex<RecommendedWatcher>>,
+}
+
+/// 变化后需要重新加载的路径：主配置文件、include中列出的文件和目录
+#[derive(Debug, Default, PartialEq)]
+struct WatchedPaths {
+    files: HashSet<PathBuf>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl WatchedPaths {
+    /// 主配置文件及其include中的路径，主配置文件无法解析时只包含主配置文件
+    fn for_config(path: &Path) -> Self {
+        let mut watched = Self::default();
+        watched.files.insert(path.to_path_buf());
+        match include_paths(&path.to_string_lossy()) {
+            Ok(includes) => {
+                for include in includes {
+                    let include = normalize(&include);
+                    if include.is_dir() {
+                        watched.dirs.insert(include);
+                    } else {
+                        watched.files.insert(include);
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to read include list from {}: {}", path.display(), e),
+        }
+        watched
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        self.files.contains(path)
+            || self.dirs.contains(path)
+            || path.parent().is_some_and(|dir| self.dirs.contains(dir)) && is_config_file(path)
+    }
+
+    /// 需要监听的目录：文件所在目录和include目录
+    /// 监听目录而非文件本身，编辑器通过重命名替换文件、被合并的文件新建时也能收到事件
+    fn watch_dirs(&self) -> HashSet<PathBuf> {
+        self.files
+            .iter()
+            .filter_map(|file| file.parent().map(Path::to_path_buf))
+            .chain(self.dirs.iter().cloned())
+            .collect()
+    }
+}
+
+/// 转为绝对路径以便和监听事件中的路径比较，文件不存在时只转换所在目录
+fn normalize(path: &Path) -> PathBuf {
+    if let Ok(path) = std::fs::canonicalize(path) {
+        return path;
+    }
+    match (path.parent().map(std::fs::canonicalize), path.file_name()) {
+        (Some(Ok(dir)), Some(name)) => dir.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// 按监听范围的变化增减监听的目录，目录不存在时跳过
+fn update_watches(watcher: &mut RecommendedWatcher, previous: &HashSet<PathBuf>, current: &HashSet<PathBuf>) {
+    for dir in previous.difference(current) {
+        let _ = watcher.unwatch(dir);
+    }
+    for dir in current.difference(previous) {
+        if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+            warn!("Failed to watch config directory {}: {}", dir.display(), e);
+        }
+    }
+}
+
+/// 监听配置文件及其include的文件和目录，变化后校验通过再热加载到负载均衡服务
+/// 每次重新加载后按新的include列表更新监听范围；返回的watcher需要保持存活，被丢弃后停止监听
+pub fn spawn_config_watcher(
+    config_path: &str,
+    load_balancer: Arc<LoadBalanceService>,
+) -> Result<ConfigWatcher> {
+    let path = std::fs::canonicalize(config_path)?;
+    if path.file_name().is_none() {
+        anyhow::bail!("Invalid config path: {}", config_path);
+    }
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<()>();
+    let watched = Arc::new(RwLock::new(WatchedPaths::for_config(&path)));
+
+    let event_paths = watched.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        match res {
+            Ok(event) => {
+                let watched = event_paths.read().unwrap_or_else(|e| e.into_inner());
+                let relevant = (event.kind.is_modify() || event.kind.is_create() || event.kind.is_remove())
+                    && event.paths.iter().any(|p| watched.matches(p));
+                if relevant {
+                    let _ = tx.send(());
+                }
+            }
+            Err(e) => warn!("Config watcher error: {}", e),
+        }
+    })?;
+    let mut watch_dirs = watched.read().unwrap_or_else(|e| e.into_inner()).watch_dirs();
+    update_watches(&mut watcher, &HashSet::new(), &watch_dirs);
+    let watcher = Arc::new(Mutex::new(watcher));
+
+    info!("Watching config file {} and its includes for changes", path.display());
+
+    // 任务只持有弱引用，watcher被丢弃后通道关闭，任务随之结束
+    let weak_watcher = Arc::downgrade(&watcher);
+    tokio::spawn(async move {
+        while rx.recv().await.is_some() {
+            // 等待写入完成并合并连续事件
+            tokio::time::sleep(DEBOUNCE).await;
+            while rx.try_recv().is_ok() {}
+
+            debug!("Config file {} changed, reloading", path.display());
+            reload_from_file(&path, &load_balancer).await;
+
+            // include列表可能已经改变，更新监听范围
+            let current = WatchedPaths::for_config(&path);
+            let current_dirs = current.watch_dirs();
+            if current_dirs != watch_dirs {
+                let Some(watcher) = weak_watcher.upgrade() else {
+                    break;
+                };
+                update_watches(&mut watcher.lock().unwrap_or_else(|e| e.into_inner()), &watch_dirs, &current_dirs);
+                watch_dirs = current_dirs;
+            }
+            *watched.write().unwrap_or_else(|e| e.into_inner()) = current;
+        }
+    });
+
+    Ok(ConfigWatcher { _watcher: watcher })
+}
+
+/// 读取并应用配置文件，失败时保留当前配置
+async fn reload_from_file(path: &Path, load_balancer: &LoadBalanceService) {
+    let events = load_balancer.get_events();
+    let path_str = path.to_string_lossy();
+
+    let new_config = match load_config_from(&path_str) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to parse config file {}, keeping previous configuration: {}", path_str, e);
+            events.publish("config_reload_failed", json!({ "path": path_str, "error": e.to_string() }));
+            return;
+        }
+    };
+
+    let models = new_config.models.len();
+    match load_balancer.reload_config(new_config).await {
+        Ok(()) => {
+            info!("Config file {} reloaded ({} models)", path_str, models);
+            events.publish("config_reloaded", json!({ "path": path_str, "models": models }));
+        }
+        Err(e) => {
+            error!("Config file {} rejected, keeping previous configuration: {}", path_str, e);
+            events.publish("config_reload_failed", json!({ "path": path_str, "error": e.to_string() }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_toml(model_name: &str) -> String {
+        format!(
+            r#"
+[providers.test]
+name = "Test"
+base_url = "https://api.test.com"
+api_key = "key"
+models = ["m"]
+
+[models.{model_name}]
+name = "{model_name}"
+[[models.{model_name}.backends]]
+provider = "test"
+model = "m"
+
+[users.admin]
+name = "Admin"
+token = "admin-token"
+"#
+        )
+    }
+
+    async fn wait_for(mut condition: impl FnMut() -> bool) -> bool {
+        for _ in 0..50 {
+            if condition() {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn test_watcher_reloads_valid_config_and_keeps_previous_on_error() {
+        let dir = std::env::temp_dir().join(format!("berry-watcher-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, config_toml("first")).unwrap();
+
+        let config = load_config_from(path.to_str().unwrap()).unwrap();
+        let service = Arc::new(LoadBalanceService::new(config).unwrap());
+        let _watcher = spawn_config_watcher(path.to_str().unwrap(), service.clone()).unwrap();
+
+        std::fs::write(&path, config_toml("second")).unwrap();
+        assert!(wait_for(|| service.get_config().models.contains_key("second")).await);
+
+        // 无效配置（引用不存在的provider）被拒绝，继续使用原配置
+        std::fs::write(&path, config_toml("third").replace("provider = \"test\"", "provider = \"missing\"")).unwrap();
+        tokio::time::sleep(DEBOUNCE * 3).await;
+        assert!(service.get_config().models.contains_key("second"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_reloads_included_files_and_follows_include_changes() {
+        let dir = std::env::temp_dir().join(format!("berry-watcher-include-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("extra")).unwrap();
+        let path = dir.join("config.toml");
+        let full = config_toml("first");
+        let (main, models) = full.split_at(full.find("[models.").unwrap());
+        let (models, users) = models.split_at(models.find("[users.").unwrap());
+        std::fs::write(&path, format!("include = [\"models.toml\"]\n{}{}", main, users)).unwrap();
+        std::fs::write(dir.join("models.toml"), models).unwrap();
+
+        let config = load_config_from(path.to_str().unwrap()).unwrap();
+        let service = Arc::new(LoadBalanceService::new(config).unwrap());
+        let _watcher = spawn_config_watcher(path.to_str().unwrap(), service.clone()).unwrap();
+
+        // 修改被合并的文件后重新加载
+        std::fs::write(dir.join("models.toml"), models.replace("first", "second")).unwrap();
+        assert!(wait_for(|| service.get_config().models.contains_key("second")).await);
+
+        // include列表新增目录后，目录中的文件变化也会触发重新加载
+        std::fs::write(&path, format!("include = [\"models.toml\", \"extra\"]\n{}{}", main, users)).unwrap();
+        tokio::time::sleep(DEBOUNCE * 3).await;
+        std::fs::write(dir.join("extra").join("more.toml"), models.replace("first", "third")).unwrap();
+        assert!(wait_for(|| service.get_config().models.contains_key("third")).await);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
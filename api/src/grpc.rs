@@ -0,0 +1,365 @@
+//! gRPC聊天完成服务（tonic）
+//! 请求转换为OpenAI聊天请求后经过与/v1/chat/completions相同的认证、路由、限流和计费流程
+
+use crate::app::AppState;
+use crate::relay::audit::AuditRecord;
+use crate::router::chat::{authorize_model_request, validate_chat_request};
+use axum::Json;
+use axum::http::{StatusCode, header};
+use axum_extra::TypedHeader;
+use eventsource_stream::Eventsource;
+use futures::{Stream, StreamExt};
+use headers::{Authorization, ContentType};
+use serde_json::{Value, json};
+use std::net::IpAddr;
+use std::pin::Pin;
+use tonic::{Code, Request, Response, Status};
+use tracing::{info, warn};
+
+pub mod proto {
+    tonic::include_proto!("berry.v1");
+}
+
+use proto::chat_completion_server::{ChatCompletion, ChatCompletionServer};
+use proto::{ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse, ToolCall, Usage};
+
+/// 启动gRPC服务，直到出错才返回
+pub async fn serve(state: AppState, listen: &str) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    info!("gRPC server listening on {}", listener.local_addr()?);
+    serve_with_listener(state, listener).await
+}
+
+/// 在已绑定的监听器上提供gRPC服务
+pub async fn serve_with_listener(state: AppState, listener: tokio::net::TcpListener) -> anyhow::Result<()> {
+    tonic::transport::Server::builder()
+        .add_service(ChatCompletionServer::new(ChatCompletionService { state }))
+        .serve_with_incoming(tonic::transport::server::TcpIncoming::from(listener))
+        .await?;
+    Ok(())
+}
+
+pub struct ChatCompletionService {
+    state: AppState,
+}
+
+#[tonic::async_trait]
+impl ChatCompletion for ChatCompletionService {
+    async fn create(&self, request: Request<ChatCompletionRequest>) -> Result<Response<ChatCompletionResponse>, Status> {
+        let response = self.relay(request, false).await?;
+        let value = read_json(response).await?;
+        Ok(Response::new(response_from_openai(&value)))
+    }
+
+    type CreateStreamStream = Pin<Box<dyn Stream<Item = Result<ChatCompletionChunk, Status>> + Send>>;
+
+    async fn create_stream(
+        &self,
+        request: Request<ChatCompletionRequest>,
+    ) -> Result<Response<Self::CreateStreamStream>, Status> {
+        let response = self.relay(request, true).await?;
+        let is_event_stream = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        if !is_event_stream {
+            // 流开始前的错误（认证、限流、没有可用后端等）以普通JSON返回
+            read_json(response).await?;
+            return Err(Status::internal("Upstream did not return an event stream"));
+        }
+
+        let chunks = response
+            .into_body()
+            .into_data_stream()
+            .eventsource()
+            .take_while(|event| futures::future::ready(!matches!(event, Ok(event) if event.data.trim() == "[DONE]")))
+            .filter_map(|event| async move {
+                match event {
+                    Ok(event) => {
+                        let chunk = serde_json::from_str::<Value>(&event.data).ok()?;
+                        Some(match chunk.get("error") {
+                            Some(error) => Err(status_from_error(error, StatusCode::BAD_GATEWAY)),
+                            None => Ok(chunk_from_openai(&chunk)),
+                        })
+                    }
+                    Err(e) => Some(Err(Status::unavailable(e.to_string()))),
+                }
+            });
+        Ok(Response::new(Box::pin(chunks)))
+    }
+}
+
+impl ChatCompletionService {
+    /// 认证并检查权限后交给负载均衡处理器，返回HTTP形式的响应
+    async fn relay(&self, request: Request<ChatCompletionRequest>, stream: bool) -> Result<axum::response::Response, Status> {
+        let client_ip = request.remote_addr().map(|addr| addr.ip());
+        let (metadata, _, request) = request.into_parts();
+        let headers = metadata.into_headers();
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string())
+            .ok_or_else(|| Status::unauthenticated("Missing bearer token in authorization metadata"))?;
+
+        let mut body = to_openai_request(&request, stream)?;
+        validate_chat_request(&body).map_err(Status::invalid_argument)?;
+        let user = match authorize_model_request(&self.state, &token, &mut body).await {
+            Ok(user) => user,
+            Err(response) => return Err(status_from_response(*response).await),
+        };
+        let key_permits = user.allowed_ips.is_empty() || client_ip.is_some_and(|ip| user.permits_ip(ip));
+        self.check_network_acl(client_ip, &user.name, &token, key_permits)?;
+        let authorization = Authorization::bearer(&token).map_err(|_| Status::unauthenticated("Invalid API key"))?;
+
+        Ok(self
+            .state
+            .handler
+            .clone()
+            .handle_completions(
+                user,
+                TypedHeader(authorization),
+                TypedHeader(ContentType::json()),
+                headers,
+                Json(body),
+            )
+            .await)
+    }
+
+    /// gRPC请求不经过HTTP中间件，在这里执行全局IP访问控制和密钥的IP绑定
+    fn check_network_acl(&self, client_ip: Option<IpAddr>, user: &str, token: &str, key_permits: bool) -> Result<(), Status> {
+        let config = self.state.config();
+        let reason = if let Some(acl) = &config.settings.network_acl
+            && !client_ip.is_some_and(|ip| acl.permits(ip))
+        {
+            format!("Requests from {} are not allowed", describe(client_ip))
+        } else if !key_permits {
+            format!("This API key cannot be used from {}", describe(client_ip))
+        } else {
+            return Ok(());
+        };
+        warn!("Rejected gRPC request from {} (user: {}): {}", describe(client_ip), user, reason);
+        self.state
+            .handler
+            .audit_rejection(AuditRecord::rejected(Some(user), Some(token), client_ip, 403, reason.clone()));
+        Err(Status::permission_denied(reason))
+    }
+}
+
+fn describe(client_ip: Option<IpAddr>) -> String {
+    client_ip.map_or_else(|| "an unknown address".to_string(), |ip| ip.to_string())
+}
+
+/// 把gRPC请求转换为OpenAI聊天请求，extra_json中的参数作为基础，显式字段覆盖同名参数
+pub fn to_openai_request(request: &ChatCompletionRequest, stream: bool) -> Result<Value, Status> {
+    let mut body = if request.extra_json.trim().is_empty() {
+        json!({})
+    } else {
+        match serde_json::from_str::<Value>(&request.extra_json) {
+            Ok(value @ Value::Object(_)) => value,
+            _ => return Err(Status::invalid_argument("extra_json must be a JSON object")),
+        }
+    };
+
+    let messages = request
+        .messages
+        .iter()
+        .map(|message| {
+            let mut converted = json!({ "role": message.role, "content": message.content });
+            if message.content.is_empty() && !message.tool_calls.is_empty() {
+                converted["content"] = Value::Null;
+            }
+            if let Some(name) = &message.name {
+                converted["name"] = json!(name);
+            }
+            if let Some(tool_call_id) = &message.tool_call_id {
+                converted["tool_call_id"] = json!(tool_call_id);
+            }
+            if !message.tool_calls.is_empty() {
+                converted["tool_calls"] = message
+                    .tool_calls
+                    .iter()
+                    .map(|call| {
+                        json!({
+                            "id": call.id,
+                            "type": "function",
+                            "function": { "name": call.name, "arguments": call.arguments },
+                        })
+                    })
+                    .collect();
+            }
+            converted
+        })
+        .collect::<Vec<_>>();
+
+    body["model"] = json!(request.model);
+    body["messages"] = Value::Array(messages);
+    body["stream"] = json!(stream);
+    if let Some(temperature) = request.temperature {
+        body["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = request.top_p {
+        body["top_p"] = json!(top_p);
+    }
+    if let Some(max_tokens) = request.max_tokens {
+        body["max_tokens"] = json!(max_tokens);
+    }
+    if !request.stop.is_empty() {
+        body["stop"] = json!(request.stop);
+    }
+    if let Some(user) = &request.user {
+        body["user"] = json!(user);
+    }
+    Ok(body)
+}
+
+fn usage(value: &Value) -> Option<Usage> {
+    let usage = value.get("usage").filter(|usage| usage.is_object())?;
+    Some(Usage {
+        prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or_default(),
+        completion_tokens: usage["completion_tokens"].as_u64().unwrap_or_default(),
+        total_tokens: usage["total_tokens"].as_u64().unwrap_or_default(),
+    })
+}
+
+fn tool_calls(value: &Value) -> Vec<ToolCall> {
+    value
+        .as_array()
+        .map(|calls| {
+            calls
+                .iter()
+                .enumerate()
+                .map(|(position, call)| ToolCall {
+                    index: call["index"].as_u64().unwrap_or(position as u64) as u32,
+                    id: call["id"].as_str().unwrap_or_default().to_string(),
+                    name: call["function"]["name"].as_str().unwrap_or_default().to_string(),
+                    arguments: call["function"]["arguments"].as_str().unwrap_or_default().to_string(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn string(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+pub fn response_from_openai(value: &Value) -> ChatCompletionResponse {
+    let choice = &value["choices"][0];
+    ChatCompletionResponse {
+        id: string(&value["id"]),
+        model: string(&value["model"]),
+        content: string(&choice["message"]["content"]),
+        finish_reason: string(&choice["finish_reason"]),
+        tool_calls: tool_calls(&choice["message"]["tool_calls"]),
+        usage: usage(value),
+    }
+}
+
+pub fn chunk_from_openai(value: &Value) -> ChatCompletionChunk {
+    let choice = &value["choices"][0];
+    ChatCompletionChunk {
+        id: string(&value["id"]),
+        model: string(&value["model"]),
+        content: string(&choice["delta"]["content"]),
+        finish_reason: string(&choice["finish_reason"]),
+        tool_calls: tool_calls(&choice["delta"]["tool_calls"]),
+        usage: usage(value),
+    }
+}
+
+/// 读取非流式响应：响应体可能带有保活空白，错误也可能在状态码200的响应体中返回
+async fn read_json(response: axum::response::Response) -> Result<Value, Status> {
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .map_err(|e| Status::unavailable(format!("Failed to read upstream response: {}", e)))?;
+    let value: Value = serde_json::from_slice(bytes.trim_ascii())
+        .map_err(|e| Status::internal(format!("Invalid upstream response: {}", e)))?;
+    match value.get("error") {
+        Some(error) => Err(status_from_error(error, status)),
+        None => Ok(value),
+    }
+}
+
+async fn status_from_response(response: axum::response::Response) -> Status {
+    match read_json(response).await {
+        Ok(_) => Status::internal("Request was rejected"),
+        Err(status) => status,
+    }
+}
+
+/// 按berry错误中的HTTP状态码映射gRPC状态码
+fn status_from_error(error: &Value, fallback: StatusCode) -> Status {
+    let status = error["status"]
+        .as_u64()
+        .and_then(|status| StatusCode::from_u16(status as u16).ok())
+        .filter(|status| !status.is_success())
+        .unwrap_or(if fallback.is_success() { StatusCode::BAD_GATEWAY } else { fallback });
+    let code = match status.as_u16() {
+        400 | 413 | 422 => Code::InvalidArgument,
+        401 => Code::Unauthenticated,
+        402 | 429 => Code::ResourceExhausted,
+        403 => Code::PermissionDenied,
+        404 => Code::NotFound,
+        408 | 504 => Code::DeadlineExceeded,
+        502 | 503 => Code::Unavailable,
+        _ => Code::Internal,
+    };
+    let message = match error {
+        Value::String(message) => message.clone(),
+        _ => {
+            let message = error["message"].as_str().unwrap_or("Unknown error");
+            match error["details"].as_str() {
+                Some(details) if !details.is_empty() => format!("{}: {}", message, details),
+                _ => message.to_string(),
+            }
+        }
+    };
+    Status::new(code, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::Message;
+
+    #[test]
+    fn test_to_openai_request() {
+        let request = ChatCompletionRequest {
+            model: "gpt-4".to_string(),
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: "hi".to_string(),
+                ..Default::default()
+            }],
+            max_tokens: Some(16),
+            extra_json: r#"{"response_format": {"type": "json_object"}, "model": "ignored"}"#.to_string(),
+            ..Default::default()
+        };
+        let body = to_openai_request(&request, true).unwrap();
+        assert_eq!(body["model"], "gpt-4");
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["max_tokens"], 16);
+        assert_eq!(body["response_format"]["type"], "json_object");
+        assert!(body.get("temperature").is_none());
+
+        let invalid = ChatCompletionRequest {
+            extra_json: "[1]".to_string(),
+            ..request
+        };
+        assert_eq!(to_openai_request(&invalid, false).unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_status_from_error() {
+        let status = status_from_error(
+            &json!({ "message": "Rate limit exceeded", "status": 429, "details": null }),
+            StatusCode::OK,
+        );
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert_eq!(status.message(), "Rate limit exceeded");
+        assert_eq!(status_from_error(&json!("stream broke"), StatusCode::OK).code(), Code::Unavailable);
+    }
+}
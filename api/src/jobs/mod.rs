@@ -0,0 +1,223 @@
+pub mod store;
+
+pub use store::{Job, JobStatus, JobStore};
+
+use crate::config::model::JobQueueSettings;
+use anyhow::Result;
+use futures::future::BoxFuture;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use store::now_ms;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+/// 清理已结束任务的间隔
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// 任务处理器，按任务类型注册
+/// 返回错误时按指数退避重试，直到达到最大执行次数；处理器可能因崩溃恢复而对同一任务执行多次，需要保证幂等
+pub trait JobHandler: Send + Sync + 'static {
+    fn run(&self, job: Job) -> BoxFuture<'static, Result<()>>;
+}
+
+impl<F, Fut> JobHandler for F
+where
+    F: Fn(Job) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    fn run(&self, job: Job) -> BoxFuture<'static, Result<()>> {
+        Box::pin(self(job))
+    }
+}
+
+/// 持久化任务队列
+/// 任务保存在SQLite中，由固定数量的worker领取执行；重启后未完成的任务重新排队
+pub struct JobQueue {
+    store: Arc<JobStore>,
+    settings: JobQueueSettings,
+    handlers: RwLock<HashMap<String, Arc<dyn JobHandler>>>,
+    notify: Notify,
+    shutdown: CancellationToken,
+    started: AtomicBool,
+}
+
+impl JobQueue {
+    /// 打开配置中的数据库文件
+    pub fn open(settings: &JobQueueSettings) -> Result<Self> {
+        Ok(Self::with_store(JobStore::open(&settings.path)?, settings.clone()))
+    }
+
+    pub fn with_store(store: JobStore, settings: JobQueueSettings) -> Self {
+        Self {
+            store: Arc::new(store),
+            settings,
+            handlers: RwLock::new(HashMap::new()),
+            notify: Notify::new(),
+            shutdown: CancellationToken::new(),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    /// 注册任务类型的处理器，已注册的类型会被替换；没有处理器的任务保持排队
+    pub fn register(&self, kind: &str, handler: impl JobHandler) {
+        if let Ok(mut handlers) = self.handlers.write() {
+            handlers.insert(kind.to_string(), Arc::new(handler));
+        }
+        self.notify.notify_waiters();
+    }
+
+    /// 添加任务，使用配置的最大执行次数，立即可执行
+    pub async fn enqueue(&self, kind: &str, payload: Value) -> Result<i64> {
+        self.enqueue_with(kind, payload, self.settings.max_attempts, Duration::ZERO).await
+    }
+
+    /// 添加任务并指定最大执行次数和延迟
+    pub async fn enqueue_with(&self, kind: &str, payload: Value, max_attempts: u32, delay: Duration) -> Result<i64> {
+        let kind = kind.to_string();
+        let run_at = now_ms() + delay.as_millis() as i64;
+        let id = self
+            .blocking(move |store| store.enqueue(&kind, &payload, max_attempts.max(1), run_at))
+            .await?;
+        if delay.is_zero() {
+            self.notify.notify_one();
+        }
+        Ok(id)
+    }
+
+    pub async fn job(&self, id: i64) -> Result<Option<Job>> {
+        self.blocking(move |store| store.get(id)).await
+    }
+
+    /// 各状态的任务数
+    pub async fn counts(&self) -> Result<HashMap<JobStatus, u64>> {
+        self.blocking(|store| store.counts()).await
+    }
+
+    /// 恢复上次未完成的任务并启动worker，重复调用时不做任何事
+    pub async fn start(self: &Arc<Self>) -> Result<()> {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let requeued = self.blocking(|store| store.recover()).await?;
+        if requeued > 0 {
+            info!("Requeued {} interrupted jobs", requeued);
+        }
+
+        for worker in 0..self.settings.workers {
+            tokio::spawn(self.clone().run_worker(worker));
+        }
+        tokio::spawn(self.clone().run_maintenance());
+        info!("Job queue started with {} workers ({})", self.settings.workers, self.settings.path);
+        Ok(())
+    }
+
+    /// 停止领取新任务，正在执行的任务在后台继续运行，进程退出时由下次启动的崩溃恢复处理
+    pub fn stop(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// 第n次执行失败后的重试等待时间：initial_backoff_ms * 2^(n-1)，不超过max_backoff_ms
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempts.saturating_sub(1));
+        Duration::from_millis(
+            self.settings
+                .initial_backoff_ms
+                .saturating_mul(factor)
+                .min(self.settings.max_backoff_ms),
+        )
+    }
+
+    async fn blocking<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(&JobStore) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || operation(&store)).await?
+    }
+
+    fn handler(&self, kind: &str) -> Option<Arc<dyn JobHandler>> {
+        self.handlers.read().ok()?.get(kind).cloned()
+    }
+
+    fn kinds(&self) -> Vec<String> {
+        self.handlers
+            .read()
+            .map(|handlers| handlers.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    async fn run_worker(self: Arc<Self>, worker: usize) {
+        let poll_interval = Duration::from_millis(self.settings.poll_interval_ms);
+        while !self.shutdown.is_cancelled() {
+            let kinds = self.kinds();
+            match self.blocking(move |store| store.claim(&kinds, now_ms())).await {
+                Ok(Some(job)) => {
+                    self.execute(worker, job).await;
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => error!("Job worker {} failed to claim a job: {:#}", worker, e),
+            }
+            tokio::select! {
+                _ = self.shutdown.cancelled() => break,
+                _ = self.notify.notified() => {}
+                _ = tokio::time::sleep(poll_interval) => {}
+            }
+        }
+        debug!("Job worker {} stopped", worker);
+    }
+
+    async fn execute(&self, worker: usize, job: Job) {
+        let (id, kind, attempts, max_attempts) = (job.id, job.kind.clone(), job.attempts, job.max_attempts);
+        debug!("Job worker {} running job {} ({}, attempt {}/{})", worker, id, kind, attempts, max_attempts);
+        let result = match self.handler(&kind) {
+            // 处理器在独立的任务中运行，panic不会终止worker
+            Some(handler) => match tokio::spawn(handler.run(job)).await {
+                Ok(result) => result,
+                Err(e) => Err(anyhow::anyhow!("Job handler panicked: {}", e)),
+            },
+            None => Err(anyhow::anyhow!("No handler registered for job kind '{}'", kind)),
+        };
+
+        let update = match result {
+            Ok(()) => {
+                debug!("Job {} ({}) succeeded", id, kind);
+                self.blocking(move |store| store.complete(id)).await
+            }
+            Err(e) => {
+                let error = format!("{:#}", e);
+                let retry_at = (attempts < max_attempts).then(|| now_ms() + self.backoff(attempts).as_millis() as i64);
+                match retry_at {
+                    Some(_) => warn!("Job {} ({}) failed on attempt {}/{}, will retry: {}", id, kind, attempts, max_attempts, error),
+                    None => error!("Job {} ({}) failed after {} attempts: {}", id, kind, attempts, error),
+                }
+                self.blocking(move |store| store.fail(id, &error, retry_at)).await
+            }
+        };
+        if let Err(e) = update {
+            error!("Failed to record result of job {}: {:#}", id, e);
+        }
+    }
+
+    /// 定期删除超过保留时间的已结束任务
+    async fn run_maintenance(self: Arc<Self>) {
+        let retention = Duration::from_secs(self.settings.retention_hours * 3600);
+        while !self.shutdown.is_cancelled() {
+            let before = now_ms() - retention.as_millis() as i64;
+            match self.blocking(move |store| store.purge_finished(before)).await {
+                Ok(0) => {}
+                Ok(purged) => debug!("Purged {} finished jobs", purged),
+                Err(e) => warn!("Failed to purge finished jobs: {:#}", e),
+            }
+            tokio::select! {
+                _ = self.shutdown.cancelled() => break,
+                _ = tokio::time::sleep(PURGE_INTERVAL) => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, Row, params, params_from_iter};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// 任务状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// 等待执行（包括等待重试）
+    Pending,
+    /// 已被worker领取
+    Running,
+    Succeeded,
+    /// 重试次数用尽
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "pending" => Self::Pending,
+            "running" => Self::Running,
+            "succeeded" => Self::Succeeded,
+            "failed" => Self::Failed,
+            _ => return None,
+        })
+    }
+}
+
+/// 持久化的任务，时间均为Unix毫秒
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: i64,
+    pub kind: String,
+    pub payload: Value,
+    pub status: JobStatus,
+    /// 已开始执行的次数，领取时加一
+    pub attempts: u32,
+    pub max_attempts: u32,
+    /// 最早可执行的时间
+    pub run_at: i64,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+const JOB_COLUMNS: &str = "id, kind, payload, status, attempts, max_attempts, run_at, last_error, created_at, updated_at";
+
+/// 基于SQLite的任务存储，所有状态变更都在单条语句或事务中完成
+pub struct JobStore {
+    conn: Mutex<Connection>,
+}
+
+impl JobStore {
+    /// 打开（或创建）数据库文件
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path).with_context(|| format!("Failed to open job database {}", path))?;
+        // WAL模式下进程崩溃不会丢失已提交的任务，读写互不阻塞
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::init(conn)
+    }
+
+    /// 内存数据库，用于测试
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                max_attempts INTEGER NOT NULL,
+                run_at INTEGER NOT NULL,
+                last_error TEXT,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS jobs_due ON jobs (status, run_at);",
+        )?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| anyhow::anyhow!("job store lock poisoned"))
+    }
+
+    /// 添加任务，返回任务ID
+    pub fn enqueue(&self, kind: &str, payload: &Value, max_attempts: u32, run_at: i64) -> Result<i64> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO jobs (kind, payload, status, max_attempts, run_at, created_at, updated_at)
+             VALUES (?1, ?2, 'pending', ?3, ?4, ?5, ?5)",
+            params![kind, payload.to_string(), max_attempts, run_at, now_ms()],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// 领取一个已到期的任务并标记为执行中，只领取kinds中的任务类型
+    pub fn claim(&self, kinds: &[String], now: i64) -> Result<Option<Job>> {
+        if kinds.is_empty() {
+            return Ok(None);
+        }
+        let placeholders = vec!["?"; kinds.len()].join(", ");
+        let sql = format!(
+            "UPDATE jobs SET status = 'running', attempts = attempts + 1, updated_at = ?
+             WHERE id = (
+                 SELECT id FROM jobs WHERE status = 'pending' AND run_at <= ? AND kind IN ({})
+                 ORDER BY run_at, id LIMIT 1
+             )
+             RETURNING {}",
+            placeholders, JOB_COLUMNS
+        );
+        let mut values: Vec<rusqlite::types::Value> = vec![now.into(), now.into()];
+        values.extend(kinds.iter().map(|kind| kind.clone().into()));
+        let conn = self.conn()?;
+        Ok(conn.query_row(&sql, params_from_iter(values), job_from_row).optional()?)
+    }
+
+    /// 标记任务成功
+    pub fn complete(&self, id: i64) -> Result<()> {
+        self.conn()?.execute(
+            "UPDATE jobs SET status = 'succeeded', last_error = NULL, updated_at = ?2 WHERE id = ?1",
+            params![id, now_ms()],
+        )?;
+        Ok(())
+    }
+
+    /// 记录失败：retry_at不为空时重新排队，否则标记为最终失败
+    pub fn fail(&self, id: i64, error: &str, retry_at: Option<i64>) -> Result<()> {
+        let conn = self.conn()?;
+        match retry_at {
+            Some(run_at) => conn.execute(
+                "UPDATE jobs SET status = 'pending', run_at = ?3, last_error = ?2, updated_at = ?4 WHERE id = ?1",
+                params![id, error, run_at, now_ms()],
+            )?,
+            None => conn.execute(
+                "UPDATE jobs SET status = 'failed', last_error = ?2, updated_at = ?3 WHERE id = ?1",
+                params![id, error, now_ms()],
+            )?,
+        };
+        Ok(())
+    }
+
+    /// 崩溃恢复：上次运行时未完成的任务重新排队，已用尽重试次数的标记为失败
+    /// 返回重新排队的任务数
+    pub fn recover(&self) -> Result<usize> {
+        let mut conn = self.conn()?;
+        let now = now_ms();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE jobs SET status = 'failed', last_error = 'Interrupted by shutdown or crash', updated_at = ?1
+             WHERE status = 'running' AND attempts >= max_attempts",
+            params![now],
+        )?;
+        let requeued = tx.execute(
+            "UPDATE jobs SET status = 'pending', run_at = ?1, updated_at = ?1 WHERE status = 'running'",
+            params![now],
+        )?;
+        tx.commit()?;
+        Ok(requeued)
+    }
+
+    pub fn get(&self, id: i64) -> Result<Option<Job>> {
+        let conn = self.conn()?;
+        Ok(conn
+            .query_row(&format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS), params![id], job_from_row)
+            .optional()?)
+    }
+
+    /// 各状态的任务数
+    pub fn counts(&self) -> Result<HashMap<JobStatus, u64>> {
+        let conn = self.conn()?;
+        let mut statement = conn.prepare("SELECT status, COUNT(*) FROM jobs GROUP BY status")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?)))?;
+        let mut counts = HashMap::new();
+        for row in rows {
+            let (status, count) = row?;
+            if let Some(status) = JobStatus::parse(&status) {
+                counts.insert(status, count);
+            }
+        }
+        Ok(counts)
+    }
+
+    /// 删除在before之前结束的成功和失败任务，返回删除数量
+    pub fn purge_finished(&self, before: i64) -> Result<usize> {
+        Ok(self.conn()?.execute(
+            "DELETE FROM jobs WHERE status IN ('succeeded', 'failed') AND updated_at < ?1",
+            params![before],
+        )?)
+    }
+}
+
+fn job_from_row(row: &Row<'_>) -> rusqlite::Result<Job> {
+    let payload: String = row.get(2)?;
+    let status: String = row.get(3)?;
+    Ok(Job {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        payload: serde_json::from_str(&payload).unwrap_or(Value::Null),
+        status: JobStatus::parse(&status).unwrap_or(JobStatus::Failed),
+        attempts: row.get(4)?,
+        max_attempts: row.get(5)?,
+        run_at: row.get(6)?,
+        last_error: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+    })
+}
+
+pub fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_claim_respects_kind_and_run_at() {
+        let store = JobStore::open_in_memory().unwrap();
+        let now = now_ms();
+        let later = store.enqueue("batch", &json!({"n": 1}), 3, now + 60_000).unwrap();
+        let due = store.enqueue("batch", &json!({"n": 2}), 3, now).unwrap();
+        store.enqueue("other", &json!({}), 3, now).unwrap();
+
+        let job = store.claim(&["batch".to_string()], now).unwrap().unwrap();
+        assert_eq!((job.id, job.status, job.attempts), (due, JobStatus::Running, 1));
+        assert_eq!(job.payload, json!({"n": 2}));
+        assert!(store.claim(&["batch".to_string()], now).unwrap().is_none());
+        assert_eq!(store.claim(&["batch".to_string()], now + 60_000).unwrap().unwrap().id, later);
+    }
+
+    #[test]
+    fn test_recover_requeues_running_jobs() {
+        let store = JobStore::open_in_memory().unwrap();
+        let now = now_ms();
+        let retryable = store.enqueue("batch", &json!({}), 3, now).unwrap();
+        let exhausted = store.enqueue("batch", &json!({}), 1, now).unwrap();
+        let kinds = ["batch".to_string()];
+        store.claim(&kinds, now).unwrap();
+        store.claim(&kinds, now).unwrap();
+
+        assert_eq!(store.recover().unwrap(), 1);
+        assert_eq!(store.get(retryable).unwrap().unwrap().status, JobStatus::Pending);
+        let failed = store.get(exhausted).unwrap().unwrap();
+        assert_eq!(failed.status, JobStatus::Failed);
+        assert!(failed.last_error.is_some());
+    }
+}
@@ -3,8 +3,13 @@ pub mod relay;
 pub mod loadbalance;
 pub mod auth;
 pub mod app;
+pub mod middleware;
 pub mod router;
+pub mod jobs;
 pub mod static_files;
+pub mod telemetry;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 
 // 重新导出主要的启动函数
-pub use app::start_server;
+pub use app::{check_server_config, start_server, start_server_with_middleware};
@@ -0,0 +1,338 @@
+use crate::config::model::{AlertSettings, AlertSeverity, PagerDutySettings, SmtpSettings, SmtpTls};
+use super::{EventBus, ServiceEvent};
+use anyhow::Result;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde_json::{Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+/// 告警来源标识
+const ALERT_SOURCE: &str = "berry-api";
+
+/// 一条告警，resolved为true时表示之前触发的同一告警已恢复
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub severity: AlertSeverity,
+    /// 去重键，同一问题的触发和恢复使用相同的键
+    pub dedup_key: String,
+    pub summary: String,
+    pub timestamp: String,
+    pub details: Value,
+    pub resolved: bool,
+}
+
+/// 从服务事件生成告警：后端抖动为warning，模型所有后端不可用为critical，SLO快速消耗按SLO配置的级别
+#[derive(Default)]
+pub struct AlertClassifier {
+    /// 各后端进入不健康列表的时间
+    unhealthy_times: HashMap<String, VecDeque<Instant>>,
+    /// 各后端上一次抖动告警的时间
+    last_flapping: HashMap<String, Instant>,
+}
+
+impl AlertClassifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 根据事件生成告警，不需要告警的事件返回None
+    pub fn classify(&mut self, settings: &AlertSettings, event: &ServiceEvent, now: Instant) -> Option<Alert> {
+        let alert = |severity, dedup_key: String, summary: String, resolved| Alert {
+            severity,
+            dedup_key,
+            summary,
+            timestamp: event.timestamp.clone(),
+            details: event.details.clone(),
+            resolved,
+        };
+
+        match event.event.as_str() {
+            "backend_unhealthy" => {
+                let backend = event.details["backend"].as_str()?.to_string();
+                let window = Duration::from_secs(settings.flapping_window_seconds);
+                let times = self.unhealthy_times.entry(backend.clone()).or_default();
+                times.push_back(now);
+                while times.front().is_some_and(|t| now.duration_since(*t) > window) {
+                    times.pop_front();
+                }
+                if times.len() < settings.flapping_threshold as usize {
+                    return None;
+                }
+                if self
+                    .last_flapping
+                    .get(&backend)
+                    .is_some_and(|last| now.duration_since(*last) <= window)
+                {
+                    return None;
+                }
+                let count = times.len();
+                self.last_flapping.insert(backend.clone(), now);
+                Some(alert(
+                    AlertSeverity::Warning,
+                    format!("{}/backend_flapping/{}", ALERT_SOURCE, backend),
+                    format!(
+                        "Backend {} became unhealthy {} times in {}s",
+                        backend, count, settings.flapping_window_seconds
+                    ),
+                    false,
+                ))
+            }
+            "model_down" => {
+                let model = event.details["model"].as_str()?;
+                Some(alert(
+                    AlertSeverity::Critical,
+                    format!("{}/model_down/{}", ALERT_SOURCE, model),
+                    format!("All backends of model {} are down", model),
+                    false,
+                ))
+            }
+            "model_restored" => {
+                let model = event.details["model"].as_str()?;
+                Some(alert(
+                    AlertSeverity::Critical,
+                    format!("{}/model_down/{}", ALERT_SOURCE, model),
+                    format!("Model {} has healthy backends again", model),
+                    true,
+                ))
+            }
+            "slo_burn_rate_high" | "slo_burn_rate_normal" => {
+                let objective = event.details["objective"].as_str()?;
+                let severity = serde_json::from_value(event.details["severity"].clone()).ok()?;
+                let resolved = event.event == "slo_burn_rate_normal";
+                let summary = if resolved {
+                    format!("SLO {} error budget burn rate is back to normal", objective)
+                } else {
+                    format!(
+                        "SLO {} is burning error budget fast (burn rate {:.1})",
+                        objective,
+                        event.details["short_burn_rate"].as_f64().unwrap_or_default()
+                    )
+                };
+                Some(alert(severity, format!("{}/slo_burn/{}", ALERT_SOURCE, objective), summary, resolved))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 生成PagerDuty Events API v2的请求体
+fn pagerduty_payload(settings: &PagerDutySettings, alert: &Alert) -> Value {
+    if alert.resolved {
+        return json!({
+            "routing_key": settings.routing_key,
+            "event_action": "resolve",
+            "dedup_key": alert.dedup_key,
+        });
+    }
+    json!({
+        "routing_key": settings.routing_key,
+        "event_action": "trigger",
+        "dedup_key": alert.dedup_key,
+        "payload": {
+            "summary": alert.summary,
+            "source": ALERT_SOURCE,
+            "severity": alert.severity.as_str(),
+            "timestamp": alert.timestamp,
+            "custom_details": alert.details,
+        },
+    })
+}
+
+/// 生成告警邮件
+fn alert_email(settings: &SmtpSettings, alert: &Alert) -> Result<Message> {
+    let status = if alert.resolved {
+        "RESOLVED".to_string()
+    } else {
+        alert.severity.as_str().to_uppercase()
+    };
+    let mut builder = Message::builder()
+        .from(settings.from.parse::<Mailbox>()?)
+        .subject(format!("[Berry API][{}] {}", status, alert.summary));
+    for to in &settings.to {
+        builder = builder.to(to.parse::<Mailbox>()?);
+    }
+    let body = format!(
+        "{}\n\nSeverity: {}\nTime: {}\nAlert: {}\n\nDetails:\n{}\n",
+        alert.summary,
+        alert.severity.as_str(),
+        alert.timestamp,
+        alert.dedup_key,
+        serde_json::to_string_pretty(&alert.details).unwrap_or_default()
+    );
+    Ok(builder.body(body)?)
+}
+
+/// 告警管理器：订阅事件总线，把告警按严重级别路由到PagerDuty和邮件
+pub struct AlertManager;
+
+impl AlertManager {
+    /// 启动后台任务，每个事件处理时读取最新配置，以便热加载生效
+    pub fn spawn<F>(events: &EventBus, settings: F)
+    where
+        F: Fn() -> Option<AlertSettings> + Send + Sync + 'static,
+    {
+        let mut receiver = events.subscribe();
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            let mut classifier = AlertClassifier::new();
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Alert manager lagged behind, {} events skipped", skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                let Some(current) = settings() else {
+                    continue;
+                };
+                let Some(alert) = classifier.classify(&current, &event, Instant::now()) else {
+                    continue;
+                };
+
+                debug!("Routing {} alert {}", alert.severity.as_str(), alert.dedup_key);
+                if let Some(pagerduty) = current.pagerduty.filter(|p| p.severities.contains(&alert.severity)) {
+                    Self::send_pagerduty(&client, pagerduty, alert.clone());
+                }
+                if let Some(smtp) = current.smtp.filter(|s| s.severities.contains(&alert.severity)) {
+                    Self::send_email(smtp, alert);
+                }
+            }
+        });
+    }
+
+    fn send_pagerduty(client: &reqwest::Client, settings: PagerDutySettings, alert: Alert) {
+        let request = client
+            .post(&settings.events_url)
+            .timeout(Duration::from_secs(settings.timeout_seconds))
+            .json(&pagerduty_payload(&settings, &alert));
+        tokio::spawn(async move {
+            match request.send().await {
+                Ok(resp) if resp.status().is_success() => debug!("PagerDuty alert {} sent", alert.dedup_key),
+                Ok(resp) => warn!("PagerDuty rejected alert {}: status {}", alert.dedup_key, resp.status()),
+                Err(e) => warn!("Failed to send PagerDuty alert {}: {}", alert.dedup_key, e),
+            }
+        });
+    }
+
+    fn send_email(settings: SmtpSettings, alert: Alert) {
+        tokio::spawn(async move {
+            let result = async {
+                let message = alert_email(&settings, &alert)?;
+                let mut builder = match settings.tls {
+                    SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)?,
+                    SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host)?,
+                    SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host),
+                }
+                .port(settings.port)
+                .timeout(Some(Duration::from_secs(settings.timeout_seconds)));
+                if let Some(username) = &settings.username {
+                    builder = builder.credentials(Credentials::new(
+                        username.clone(),
+                        settings.password.clone().unwrap_or_default(),
+                    ));
+                }
+                builder.build().send(message).await?;
+                anyhow::Ok(())
+            };
+            match result.await {
+                Ok(()) => debug!("Alert email {} sent to {} recipients", alert.dedup_key, settings.to.len()),
+                Err(e) => warn!("Failed to send alert email {}: {}", alert.dedup_key, e),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> AlertSettings {
+        AlertSettings {
+            flapping_threshold: 3,
+            flapping_window_seconds: 60,
+            pagerduty: None,
+            smtp: None,
+        }
+    }
+
+    fn event(name: &str, details: Value) -> ServiceEvent {
+        ServiceEvent {
+            event: name.to_string(),
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            details,
+        }
+    }
+
+    #[test]
+    fn test_flapping_backend_alerts_once_per_window() {
+        let settings = settings();
+        let mut classifier = AlertClassifier::new();
+        let unhealthy = event("backend_unhealthy", json!({ "backend": "openai:gpt-4" }));
+        let start = Instant::now();
+
+        let alerts: Vec<Option<Alert>> = (0..4)
+            .map(|i| classifier.classify(&settings, &unhealthy, start + Duration::from_secs(i * 10)))
+            .collect();
+        assert!(alerts[0].is_none() && alerts[1].is_none());
+        let alert = alerts[2].as_ref().unwrap();
+        assert_eq!(alert.severity, AlertSeverity::Warning);
+        assert_eq!(alert.dedup_key, "berry-api/backend_flapping/openai:gpt-4");
+        // 同一窗口内不重复告警
+        assert!(alerts[3].is_none());
+
+        // 窗口过后旧的记录失效，需要重新累计
+        let later = start + Duration::from_secs(200);
+        assert!(classifier.classify(&settings, &unhealthy, later).is_none());
+    }
+
+    #[test]
+    fn test_model_down_is_critical_and_resolves() {
+        let settings = settings();
+        let mut classifier = AlertClassifier::new();
+        let now = Instant::now();
+
+        let down = classifier
+            .classify(&settings, &event("model_down", json!({ "model": "gpt-4" })), now)
+            .unwrap();
+        let restored = classifier
+            .classify(&settings, &event("model_restored", json!({ "model": "gpt-4" })), now)
+            .unwrap();
+        assert_eq!(down.severity, AlertSeverity::Critical);
+        assert!(!down.resolved && restored.resolved);
+        assert_eq!(down.dedup_key, restored.dedup_key);
+
+        let pagerduty = PagerDutySettings {
+            routing_key: "key".to_string(),
+            events_url: "http://localhost".to_string(),
+            severities: vec![AlertSeverity::Critical],
+            timeout_seconds: 1,
+        };
+        let trigger = pagerduty_payload(&pagerduty, &down);
+        assert_eq!(trigger["event_action"], "trigger");
+        assert_eq!(trigger["payload"]["severity"], "critical");
+        let resolve = pagerduty_payload(&pagerduty, &restored);
+        assert_eq!(resolve["event_action"], "resolve");
+        assert!(resolve.get("payload").is_none());
+    }
+
+    #[test]
+    fn test_slo_burn_uses_configured_severity() {
+        let settings = settings();
+        let mut classifier = AlertClassifier::new();
+        let now = Instant::now();
+        let details = json!({ "objective": "latency", "short_burn_rate": 20.0, "severity": "warning" });
+
+        let high = classifier.classify(&settings, &event("slo_burn_rate_high", details.clone()), now).unwrap();
+        assert_eq!(high.severity, AlertSeverity::Warning);
+        assert_eq!(high.summary, "SLO latency is burning error budget fast (burn rate 20.0)");
+        let normal = classifier.classify(&settings, &event("slo_burn_rate_normal", details), now).unwrap();
+        assert!(normal.resolved);
+        assert_eq!(normal.dedup_key, high.dedup_key);
+    }
+}
@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+/// 时间来源
+/// MetricsCollector通过它判断冷却、剔除、慢启动和恢复检查等是否到期，测试中可替换为手动推进的时钟
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// 系统时钟
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// 手动推进的时钟，创建时与系统时钟一致，之后只在调用advance时前进
+#[derive(Debug)]
+pub struct ManualClock {
+    now: std::sync::Mutex<Instant>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            now: std::sync::Mutex::new(Instant::now()),
+        }
+    }
+
+    /// 把时钟向前推进
+    pub fn advance(&self, duration: Duration) {
+        if let Ok(mut now) = self.now.lock() {
+            *now += duration;
+        }
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.now.lock().map(|now| *now).unwrap_or_else(|e| *e.into_inner())
+    }
+}
@@ -0,0 +1,474 @@
+use crate::config::model::{Backend, ModelMapping, UserToken};
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// 按小时汇总的用量历史保留时长
+const USAGE_RETENTION_HOURS: i64 = 24 * 400;
+
+/// 一次请求的token用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    /// 从OpenAI格式的usage字段解析用量，也接受Responses接口的input_tokens/output_tokens
+    pub fn from_json(usage: &serde_json::Value) -> Option<Self> {
+        let prompt_tokens = usage
+            .get("prompt_tokens")
+            .or_else(|| usage.get("input_tokens"))?
+            .as_u64()?;
+        let completion_tokens = usage
+            .get("completion_tokens")
+            .or_else(|| usage.get("output_tokens"))
+            .and_then(|t| t.as_u64())
+            .unwrap_or(0);
+        Some(Self {
+            prompt_tokens,
+            completion_tokens,
+        })
+    }
+}
+
+/// 花费汇总
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SpendSummary {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// 累计花费（美元）
+    pub cost: f64,
+}
+
+impl SpendSummary {
+    fn add(&mut self, usage: TokenUsage, cost: f64) {
+        self.requests += 1;
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.cost += cost;
+    }
+
+    fn merge(&mut self, other: &SpendSummary) {
+        self.requests += other.requests;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.cost += other.cost;
+    }
+}
+
+/// 用量报告的时间分桶粒度
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageBucket {
+    Hour,
+    #[default]
+    Day,
+    Month,
+    /// 不按时间分桶，整个时间范围汇总为一行
+    Total,
+}
+
+impl UsageBucket {
+    /// 时间点所在分桶的起始时间（UTC）
+    fn start_of(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let date = time.date_naive();
+        match self {
+            Self::Hour => Utc.timestamp_opt(time.timestamp().div_euclid(3600) * 3600, 0).single(),
+            Self::Day => Some(date.and_hms_opt(0, 0, 0)?.and_utc()),
+            Self::Month => Some(date.with_day(1)?.and_hms_opt(0, 0, 0)?.and_utc()),
+            Self::Total => None,
+        }
+    }
+}
+
+/// 用量报告的分组维度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageDimension {
+    User,
+    Model,
+    Provider,
+}
+
+/// 用量查询条件，时间范围为左闭右开，按小时对齐
+#[derive(Debug, Clone, Default)]
+pub struct UsageQuery {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub bucket: UsageBucket,
+    pub group_by: Vec<UsageDimension>,
+    /// 只统计这些用户、模型和provider，为空时不过滤
+    pub users: Vec<String>,
+    pub models: Vec<String>,
+    pub providers: Vec<String>,
+}
+
+/// 用量报告中的一行，未参与分组的维度为空
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageRow {
+    pub bucket_start: Option<DateTime<Utc>>,
+    pub user: Option<String>,
+    pub model: Option<String>,
+    pub provider: Option<String>,
+    #[serde(flatten)]
+    pub usage: SpendSummary,
+}
+
+/// 按时间分桶和维度聚合的用量报告
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub bucket: UsageBucket,
+    pub group_by: Vec<UsageDimension>,
+    pub rows: Vec<UsageRow>,
+    pub total: SpendSummary,
+}
+
+/// 一小时内某用户通过某provider使用某模型的用量键（用户, 模型, provider）
+type UsageKey = (String, String, String);
+
+/// 报告中的分组键：分桶起始时间和参与分组的（用户, 模型, provider）
+type UsageGroup = (Option<DateTime<Utc>>, [Option<String>; 3]);
+
+/// 按用户、模型和provider汇总的花费报告
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CostReport {
+    pub users: HashMap<String, SpendSummary>,
+    pub models: HashMap<String, SpendSummary>,
+    pub providers: HashMap<String, SpendSummary>,
+}
+
+/// 超出预算的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetExceeded {
+    User { name: String, budget: f64, spent: f64 },
+    Model { name: String, budget: f64, spent: f64 },
+}
+
+impl std::fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BudgetExceeded::User { name, budget, spent } => write!(
+                f,
+                "User '{}' has exceeded its budget (${:.4} spent of ${:.4})",
+                name, spent, budget
+            ),
+            BudgetExceeded::Model { name, budget, spent } => write!(
+                f,
+                "Model '{}' has exceeded its budget (${:.4} spent of ${:.4})",
+                name, spent, budget
+            ),
+        }
+    }
+}
+
+/// 花费统计与预算控制
+/// 花费按后端配置的价格和上游返回的usage计算，仅保存在内存中，重启后清零
+/// 另按小时保留用户、模型、provider维度的用量历史，用于按时间范围生成用量报告
+pub struct CostTracker {
+    report: std::sync::RwLock<CostReport>,
+    /// 小时序号（Unix时间戳/3600） -> 用量
+    history: std::sync::RwLock<BTreeMap<i64, HashMap<UsageKey, SpendSummary>>>,
+}
+
+impl CostTracker {
+    pub fn new() -> Self {
+        Self {
+            report: std::sync::RwLock::new(CostReport::default()),
+            history: std::sync::RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// 记录一次请求的用量，返回本次请求的花费
+    pub fn record(&self, user: &str, model_id: &str, backend: &Backend, usage: TokenUsage) -> f64 {
+        self.record_at(user, model_id, backend, usage, Utc::now())
+    }
+
+    fn record_at(&self, user: &str, model_id: &str, backend: &Backend, usage: TokenUsage, now: DateTime<Utc>) -> f64 {
+        let cost = backend
+            .pricing
+            .as_ref()
+            .map(|pricing| pricing.cost(usage.prompt_tokens, usage.completion_tokens))
+            .unwrap_or(0.0);
+
+        if let Ok(mut report) = self.report.write() {
+            report.users.entry(user.to_string()).or_default().add(usage, cost);
+            report.models.entry(model_id.to_string()).or_default().add(usage, cost);
+            report
+                .providers
+                .entry(backend.provider.clone())
+                .or_default()
+                .add(usage, cost);
+        }
+
+        if let Ok(mut history) = self.history.write() {
+            let hour = now.timestamp().div_euclid(3600);
+            history
+                .entry(hour)
+                .or_default()
+                .entry((user.to_string(), model_id.to_string(), backend.provider.clone()))
+                .or_default()
+                .add(usage, cost);
+            // 清理超出保留时长的小时
+            let expired = hour - USAGE_RETENTION_HOURS;
+            while history.first_key_value().is_some_and(|(h, _)| *h < expired) {
+                history.pop_first();
+            }
+        }
+
+        tracing::debug!(
+            "Recorded usage for user '{}' model '{}' via {}:{}: {} prompt + {} completion tokens, ${:.6}",
+            user,
+            model_id,
+            backend.provider,
+            backend.model,
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            cost
+        );
+        cost
+    }
+
+    /// 检查用户和模型的预算，任一超出时返回原因
+    pub fn check_budget(
+        &self,
+        user: &UserToken,
+        model_id: &str,
+        mapping: Option<&ModelMapping>,
+    ) -> Result<(), BudgetExceeded> {
+        let Ok(report) = self.report.read() else {
+            return Ok(());
+        };
+
+        if let Some(budget) = user.budget {
+            let spent = report.users.get(&user.name).map(|s| s.cost).unwrap_or(0.0);
+            if spent >= budget {
+                return Err(BudgetExceeded::User {
+                    name: user.name.clone(),
+                    budget,
+                    spent,
+                });
+            }
+        }
+
+        if let Some(budget) = mapping.and_then(|m| m.budget) {
+            let spent = report.models.get(model_id).map(|s| s.cost).unwrap_or(0.0);
+            if spent >= budget {
+                return Err(BudgetExceeded::Model {
+                    name: model_id.to_string(),
+                    budget,
+                    spent,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 获取花费报告
+    pub fn report(&self) -> CostReport {
+        self.report.read().map(|r| r.clone()).unwrap_or_default()
+    }
+
+    /// 按时间范围、维度过滤和分组聚合用量历史，行按分桶时间和维度排序
+    pub fn usage(&self, query: &UsageQuery) -> UsageReport {
+        let mut groups: BTreeMap<UsageGroup, SpendSummary> = BTreeMap::new();
+        let mut total = SpendSummary::default();
+
+        let first = query.start.map_or(i64::MIN, |start| start.timestamp().div_euclid(3600));
+        let last = query.end.map_or(i64::MAX, |end| (end.timestamp() - 1).div_euclid(3600));
+        if let Ok(history) = self.history.read()
+            && first <= last
+        {
+            for (hour, entries) in history.range(first..=last) {
+                let Some(time) = Utc.timestamp_opt(hour * 3600, 0).single() else {
+                    continue;
+                };
+                let bucket = query.bucket.start_of(time);
+                for ((user, model, provider), summary) in entries {
+                    let matches = |filter: &Vec<String>, value: &String| filter.is_empty() || filter.contains(value);
+                    if !matches(&query.users, user) || !matches(&query.models, model) || !matches(&query.providers, provider) {
+                        continue;
+                    }
+                    let grouped = |dimension: UsageDimension, value: &String| {
+                        query.group_by.contains(&dimension).then(|| value.clone())
+                    };
+                    let key = [
+                        grouped(UsageDimension::User, user),
+                        grouped(UsageDimension::Model, model),
+                        grouped(UsageDimension::Provider, provider),
+                    ];
+                    groups.entry((bucket, key)).or_default().merge(summary);
+                    total.merge(summary);
+                }
+            }
+        }
+
+        let rows = groups
+            .into_iter()
+            .map(|((bucket_start, [user, model, provider]), usage)| UsageRow {
+                bucket_start,
+                user,
+                model,
+                provider,
+                usage,
+            })
+            .collect();
+        UsageReport {
+            start: query.start,
+            end: query.end,
+            bucket: query.bucket,
+            group_by: query.group_by.clone(),
+            rows,
+            total,
+        }
+    }
+}
+
+impl Default for CostTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::model::{BackendPricing, BillingMode};
+
+    fn backend() -> Backend {
+        Backend {
+            provider: "openai".to_string(),
+            model: "gpt-4".to_string(),
+            weight: 1.0,
+            priority: 1,
+            enabled: true,
+            tags: vec![],
+            billing_mode: BillingMode::PerToken,
+            pricing: Some(BackendPricing {
+                prompt_per_1k: 0.01,
+                completion_per_1k: 0.03,
+            }),
+            overrides: std::collections::HashMap::new(),
+            slow_start: None,
+            concurrency: None,
+            schedule: None,
+            region: None,
+            max_context: None,
+            supports_vision: true,
+            supports_tools: true,
+            supports_json_mode: true,
+            supports_streaming: true,
+            supports_responses: false,
+        }
+    }
+
+    fn user(budget: Option<f64>) -> UserToken {
+        UserToken {
+            name: "alice".to_string(),
+            token: "token".to_string(),
+            token_hash: None,
+            model_aliases: HashMap::new(),
+            allowed_models: vec![],
+            enabled: true,
+            rate_limit: None,
+            tags: vec![],
+            budget,
+            priority: Default::default(),
+            region: None,
+            content_filters: Vec::new(),
+            allowed_ips: Vec::new(),
+            tenant: None,
+            limits: None,
+        }
+    }
+
+    #[test]
+    fn test_record_aggregates_spend() {
+        let tracker = CostTracker::new();
+        let usage = TokenUsage {
+            prompt_tokens: 1000,
+            completion_tokens: 500,
+        };
+
+        let cost = tracker.record("alice", "gpt-4", &backend(), usage);
+        assert!((cost - 0.025).abs() < 1e-9);
+        tracker.record("bob", "gpt-4", &backend(), usage);
+
+        let report = tracker.report();
+        assert_eq!(report.users["alice"].requests, 1);
+        assert_eq!(report.models["gpt-4"].prompt_tokens, 2000);
+        assert!((report.providers["openai"].cost - 0.05).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_usage_groups_by_bucket_and_dimension() {
+        let tracker = CostTracker::new();
+        let usage = TokenUsage {
+            prompt_tokens: 1000,
+            completion_tokens: 500,
+        };
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        tracker.record_at("alice", "gpt-4", &backend(), usage, at("2024-01-01T10:15:00Z"));
+        tracker.record_at("alice", "gpt-4", &backend(), usage, at("2024-01-01T23:59:00Z"));
+        tracker.record_at("bob", "gpt-4", &backend(), usage, at("2024-01-02T01:00:00Z"));
+        tracker.record_at("alice", "gpt-4", &backend(), usage, at("2024-02-01T00:00:00Z"));
+
+        let report = tracker.usage(&UsageQuery {
+            group_by: vec![UsageDimension::User],
+            ..UsageQuery::default()
+        });
+        assert_eq!(report.rows.len(), 3);
+        assert_eq!(report.rows[0].bucket_start, Some(at("2024-01-01T00:00:00Z")));
+        assert_eq!(report.rows[0].user.as_deref(), Some("alice"));
+        assert_eq!(report.rows[0].usage.requests, 2);
+        assert!(report.rows[0].model.is_none());
+        assert_eq!(report.total.requests, 4);
+
+        // 时间范围左闭右开，按月汇总并只看alice
+        let report = tracker.usage(&UsageQuery {
+            start: Some(at("2024-01-01T11:00:00Z")),
+            end: Some(at("2024-02-01T00:00:00Z")),
+            bucket: UsageBucket::Month,
+            users: vec!["alice".to_string()],
+            ..UsageQuery::default()
+        });
+        assert_eq!(report.rows.len(), 1);
+        assert_eq!(report.rows[0].bucket_start, Some(at("2024-01-01T00:00:00Z")));
+        assert_eq!(report.rows[0].usage.requests, 1);
+        assert!((report.total.cost - 0.025).abs() < 1e-9);
+
+        let report = tracker.usage(&UsageQuery {
+            bucket: UsageBucket::Total,
+            group_by: vec![UsageDimension::Provider],
+            ..UsageQuery::default()
+        });
+        assert_eq!(report.rows.len(), 1);
+        assert!(report.rows[0].bucket_start.is_none());
+        assert_eq!(report.rows[0].provider.as_deref(), Some("openai"));
+    }
+
+    #[test]
+    fn test_budget_exceeded() {
+        let tracker = CostTracker::new();
+        assert!(tracker.check_budget(&user(Some(0.02)), "gpt-4", None).is_ok());
+
+        tracker.record(
+            "alice",
+            "gpt-4",
+            &backend(),
+            TokenUsage {
+                prompt_tokens: 1000,
+                completion_tokens: 500,
+            },
+        );
+        assert!(matches!(
+            tracker.check_budget(&user(Some(0.02)), "gpt-4", None),
+            Err(BudgetExceeded::User { .. })
+        ));
+        assert!(tracker.check_budget(&user(None), "gpt-4", None).is_ok());
+    }
+}
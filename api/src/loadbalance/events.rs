@@ -0,0 +1,47 @@
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+/// 事件总线缓冲的最大事件数，订阅者落后超过该数量时会丢失最早的事件
+const EVENT_BUFFER_SIZE: usize = 256;
+
+/// 服务运行事件（健康状态变化、管理操作等）
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceEvent {
+    pub event: String,
+    pub timestamp: String,
+    pub details: Value,
+}
+
+/// 服务事件总线，供管理接口实时订阅
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<ServiceEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        Self { sender }
+    }
+
+    /// 发布事件，没有订阅者时直接丢弃
+    pub fn publish(&self, event: &str, details: Value) {
+        let _ = self.sender.send(ServiceEvent {
+            event: event.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            details,
+        });
+    }
+
+    /// 订阅后续发布的事件
+    pub fn subscribe(&self) -> broadcast::Receiver<ServiceEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,199 @@
+use crate::config::model::ExperimentSettings;
+use super::TokenUsage;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+/// 实验分组
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExperimentArm {
+    Control,
+    Treatment,
+}
+
+impl ExperimentArm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExperimentArm::Control => "control",
+            ExperimentArm::Treatment => "treatment",
+        }
+    }
+}
+
+/// 一次请求的实验分组结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExperimentAssignment {
+    pub experiment: String,
+    pub arm: ExperimentArm,
+    /// 该分组要求的后端标签
+    pub tags: Vec<String>,
+}
+
+impl ExperimentAssignment {
+    /// 按用户密钥的哈希分组，同一用户在同一实验中始终落在同一组
+    pub fn assign(settings: &ExperimentSettings, client_key: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        settings.name.hash(&mut hasher);
+        client_key.hash(&mut hasher);
+        let bucket = (hasher.finish() % 10_000) as f64 / 100.0;
+
+        let (arm, tags) = if bucket < settings.treatment_percent {
+            (ExperimentArm::Treatment, &settings.treatment_tags)
+        } else {
+            (ExperimentArm::Control, &settings.control_tags)
+        };
+        Self {
+            experiment: settings.name.clone(),
+            arm,
+            tags: tags.clone(),
+        }
+    }
+
+    /// 响应头中标明的实验分组，格式为 实验名称:分组
+    pub fn header_value(&self) -> String {
+        format!("{}:{}", self.experiment, self.arm.as_str())
+    }
+}
+
+/// 单个实验分组的统计
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ArmStats {
+    pub requests: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub avg_latency_ms: f64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    #[serde(skip)]
+    total_latency_ms: u64,
+}
+
+/// 单个实验的统计报告
+#[derive(Debug, Clone, Serialize)]
+pub struct ExperimentReport {
+    pub model: String,
+    pub experiment: String,
+    pub arms: HashMap<ExperimentArm, ArmStats>,
+}
+
+/// A/B实验统计：按模型、实验和分组汇总延迟、错误率和用量，仅保存在内存中
+pub struct ExperimentTracker {
+    stats: std::sync::RwLock<HashMap<(String, String), HashMap<ExperimentArm, ArmStats>>>,
+}
+
+impl ExperimentTracker {
+    pub fn new() -> Self {
+        Self {
+            stats: std::sync::RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 记录一次实验请求的结果
+    pub fn record(
+        &self,
+        model_id: &str,
+        assignment: &ExperimentAssignment,
+        latency: Duration,
+        failed: bool,
+        usage: Option<TokenUsage>,
+    ) {
+        let Ok(mut stats) = self.stats.write() else {
+            return;
+        };
+        let arm = stats
+            .entry((model_id.to_string(), assignment.experiment.clone()))
+            .or_default()
+            .entry(assignment.arm)
+            .or_default();
+
+        arm.requests += 1;
+        if failed {
+            arm.errors += 1;
+        }
+        arm.total_latency_ms += latency.as_millis() as u64;
+        if let Some(usage) = usage {
+            arm.prompt_tokens += usage.prompt_tokens;
+            arm.completion_tokens += usage.completion_tokens;
+        }
+        arm.error_rate = arm.errors as f64 / arm.requests as f64;
+        arm.avg_latency_ms = arm.total_latency_ms as f64 / arm.requests as f64;
+    }
+
+    /// 获取所有实验的统计报告，按模型和实验名称排序
+    pub fn report(&self) -> Vec<ExperimentReport> {
+        let Ok(stats) = self.stats.read() else {
+            return Vec::new();
+        };
+        let mut reports: Vec<ExperimentReport> = stats
+            .iter()
+            .map(|((model, experiment), arms)| ExperimentReport {
+                model: model.clone(),
+                experiment: experiment.clone(),
+                arms: arms.clone(),
+            })
+            .collect();
+        reports.sort_by(|a, b| (&a.model, &a.experiment).cmp(&(&b.model, &b.experiment)));
+        reports
+    }
+}
+
+impl Default for ExperimentTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(treatment_percent: f64) -> ExperimentSettings {
+        ExperimentSettings {
+            name: "candidate".to_string(),
+            control_tags: vec!["stable".to_string()],
+            treatment_tags: vec!["candidate".to_string()],
+            treatment_percent,
+        }
+    }
+
+    #[test]
+    fn test_assignment_is_sticky_and_follows_percent() {
+        let settings = settings(30.0);
+        let first = ExperimentAssignment::assign(&settings, "key-1");
+        assert_eq!(first, ExperimentAssignment::assign(&settings, "key-1"));
+
+        let treatment = (0..1000)
+            .filter(|i| ExperimentAssignment::assign(&settings, &format!("key-{}", i)).arm == ExperimentArm::Treatment)
+            .count();
+        assert!((200..400).contains(&treatment), "unexpected treatment count {}", treatment);
+
+        let all_control = ExperimentAssignment::assign(&self::settings(0.0), "key-1");
+        assert_eq!(all_control.arm, ExperimentArm::Control);
+        assert_eq!(all_control.tags, vec!["stable".to_string()]);
+        assert_eq!(all_control.header_value(), "candidate:control");
+    }
+
+    #[test]
+    fn test_tracker_aggregates_per_arm() {
+        let tracker = ExperimentTracker::new();
+        let assignment = ExperimentAssignment::assign(&settings(100.0), "key-1");
+        let usage = TokenUsage {
+            prompt_tokens: 10,
+            completion_tokens: 5,
+        };
+        tracker.record("gpt-4", &assignment, Duration::from_millis(100), false, Some(usage));
+        tracker.record("gpt-4", &assignment, Duration::from_millis(300), true, None);
+
+        let report = tracker.report();
+        assert_eq!(report.len(), 1);
+        let arm = &report[0].arms[&ExperimentArm::Treatment];
+        assert_eq!(arm.requests, 2);
+        assert_eq!(arm.errors, 1);
+        assert_eq!(arm.error_rate, 0.5);
+        assert_eq!(arm.avg_latency_ms, 200.0);
+        assert_eq!(arm.prompt_tokens, 10);
+    }
+}
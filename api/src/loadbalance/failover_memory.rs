@@ -0,0 +1,169 @@
+use crate::config::model::Backend;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// 故障转移记忆条目
+#[derive(Debug, Clone)]
+pub struct FailoverEntry {
+    /// 首次尝试失败的后端
+    pub failed_backend: Backend,
+    /// 故障转移后请求成功的后端
+    pub fallback_backend: Backend,
+    pub expires_at: Instant,
+}
+
+/// 客户端故障转移记忆
+/// 按（客户端API密钥, 模型）记录最近一次故障转移，在原后端恢复前直接路由到备用后端，
+/// 避免同一客户端在部分故障期间反复承受首次尝试失败的延迟
+pub struct FailoverMemory {
+    ttl: std::sync::RwLock<Duration>,
+    entries: std::sync::RwLock<HashMap<(String, String), FailoverEntry>>,
+}
+
+impl FailoverMemory {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl: std::sync::RwLock::new(ttl),
+            entries: std::sync::RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn ttl(&self) -> Duration {
+        self.ttl.read().map(|ttl| *ttl).unwrap_or_default()
+    }
+
+    /// 是否启用（TTL为0时禁用）
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl().is_zero()
+    }
+
+    /// 清除所有记忆并使用新的TTL（配置变更后调用，记住的后端可能已被修改或删除）
+    pub fn reset(&self, ttl: Duration) {
+        if let Ok(mut current) = self.ttl.write() {
+            *current = ttl;
+        }
+        if let Ok(mut entries) = self.entries.write() {
+            entries.clear();
+        }
+    }
+
+    /// 记录一次故障转移
+    pub fn remember(
+        &self,
+        client_key: &str,
+        model_name: &str,
+        failed_backend: &Backend,
+        fallback_backend: &Backend,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        if let Ok(mut entries) = self.entries.write() {
+            // 顺带清理过期条目，避免长期运行后无限增长
+            let now = Instant::now();
+            entries.retain(|_, entry| entry.expires_at > now);
+            entries.insert(
+                (client_key.to_string(), model_name.to_string()),
+                FailoverEntry {
+                    failed_backend: failed_backend.clone(),
+                    fallback_backend: fallback_backend.clone(),
+                    expires_at: now + self.ttl(),
+                },
+            );
+        }
+    }
+
+    /// 查找未过期的故障转移记忆
+    pub fn lookup(&self, client_key: &str, model_name: &str) -> Option<FailoverEntry> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let key = (client_key.to_string(), model_name.to_string());
+        let entry = self.entries.read().ok()?.get(&key).cloned()?;
+        if entry.expires_at > Instant::now() {
+            Some(entry)
+        } else {
+            self.forget(client_key, model_name);
+            None
+        }
+    }
+
+    /// 清除故障转移记忆
+    pub fn forget(&self, client_key: &str, model_name: &str) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.remove(&(client_key.to_string(), model_name.to_string()));
+        }
+    }
+
+    /// 当前记忆条目数
+    pub fn len(&self) -> usize {
+        self.entries.read().map(|e| e.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::model::BillingMode;
+
+    fn backend(provider: &str) -> Backend {
+        Backend {
+            provider: provider.to_string(),
+            model: "model".to_string(),
+            weight: 1.0,
+            priority: 1,
+            enabled: true,
+            tags: vec![],
+            billing_mode: BillingMode::PerToken,
+            pricing: None,
+            overrides: HashMap::new(),
+            slow_start: None,
+            concurrency: None,
+            schedule: None,
+            region: None,
+            max_context: None,
+            supports_vision: true,
+            supports_tools: true,
+            supports_json_mode: true,
+            supports_streaming: true,
+            supports_responses: false,
+        }
+    }
+
+    #[test]
+    fn test_remember_and_expire() {
+        let memory = FailoverMemory::new(Duration::from_millis(50));
+        memory.remember("key", "gpt-4", &backend("a"), &backend("b"));
+
+        let entry = memory.lookup("key", "gpt-4").unwrap();
+        assert_eq!(entry.failed_backend.provider, "a");
+        assert_eq!(entry.fallback_backend.provider, "b");
+        assert!(memory.lookup("other-key", "gpt-4").is_none());
+
+        std::thread::sleep(Duration::from_millis(80));
+        assert!(memory.lookup("key", "gpt-4").is_none());
+        assert!(memory.is_empty());
+    }
+
+    #[test]
+    fn test_reset_clears_entries_and_updates_ttl() {
+        let memory = FailoverMemory::new(Duration::from_secs(60));
+        memory.remember("key", "gpt-4", &backend("a"), &backend("b"));
+        memory.reset(Duration::ZERO);
+        assert!(memory.is_empty());
+        assert!(!memory.is_enabled());
+    }
+
+    #[test]
+    fn test_disabled_with_zero_ttl() {
+        let memory = FailoverMemory::new(Duration::ZERO);
+        memory.remember("key", "gpt-4", &backend("a"), &backend("b"));
+        assert!(memory.lookup("key", "gpt-4").is_none());
+    }
+}
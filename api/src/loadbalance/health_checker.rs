@@ -1,28 +1,28 @@
-use crate::config::model::{Config, Provider, BillingMode};
+use crate::config::model::{Config, Provider, BillingMode, ProbeType};
 use crate::relay::client::openai::OpenAIClient;
 use super::MetricsCollector;
 use anyhow::Result;
 use reqwest::Client;
 use serde_json::json;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::time::interval;
 use tracing::{debug, error, info, warn};
 
 /// 健康检查器
-/// 定期检查所有provider的健康状态
+/// 按各provider配置的探测方式和间隔定期检查健康状态
 pub struct HealthChecker {
-    config: Arc<Config>,
+    config: std::sync::RwLock<Arc<Config>>,
     metrics: Arc<MetricsCollector>,
     client: Client,
-    check_interval: Duration,
     initial_check_done: Arc<std::sync::RwLock<bool>>,
+    // 各provider最近一次主动探测的时间
+    last_probe: std::sync::RwLock<HashMap<String, Instant>>,
 }
 
 impl HealthChecker {
     /// 创建新的健康检查器
     pub fn new(config: Arc<Config>, metrics: Arc<MetricsCollector>) -> Self {
-        let check_interval = Duration::from_secs(config.settings.health_check_interval_seconds);
         let timeout = Duration::from_secs(config.settings.request_timeout_seconds);
         
         let client = Client::builder()
@@ -31,36 +31,70 @@ impl HealthChecker {
             .expect("Failed to create HTTP client");
 
         Self {
-            config,
+            config: std::sync::RwLock::new(config),
             metrics,
             client,
-            check_interval,
             initial_check_done: Arc::new(std::sync::RwLock::new(false)),
+            last_probe: std::sync::RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 获取当前使用的配置
+    fn config(&self) -> Arc<Config> {
+        self.config
+            .read()
+            .map(|config| config.clone())
+            .unwrap_or_else(|e| e.into_inner().clone())
+    }
+
+    /// 更新配置（配置热加载后调用）
+    pub fn update_config(&self, config: Arc<Config>) {
+        if let Ok(mut current) = self.config.write() {
+            *current = config;
         }
     }
 
     /// 启动健康检查循环
     pub async fn start(&self) {
-        info!("Starting health checker with interval: {:?}", self.check_interval);
-        
-        let mut interval = interval(self.check_interval);
-        
+        info!("Starting health checker");
+
         loop {
-            interval.tick().await;
-            
-            if let Err(e) = self.check_all_providers().await {
+            if let Err(e) = self.check_due().await {
                 error!("Health check failed: {}", e);
             }
+            tokio::time::sleep(self.next_check_delay()).await;
         }
     }
 
-    /// 检查所有provider的健康状态
-    async fn check_all_providers(&self) -> Result<()> {
-        let enabled_providers: Vec<_> = self.config.providers.iter()
+    /// 距离下一个provider到期需要探测的等待时间
+    pub fn next_check_delay(&self) -> Duration {
+        let config = self.config();
+        let last_probe = self.last_probe.read().map(|l| l.clone()).unwrap_or_default();
+
+        config
+            .providers
+            .iter()
             .filter(|(_, provider)| provider.enabled)
-            .collect();
+            .map(|(provider_id, provider)| {
+                let interval = provider.probe_interval(&config.settings);
+                match last_probe.get(provider_id) {
+                    Some(last) => interval.saturating_sub(last.elapsed()),
+                    None => Duration::ZERO,
+                }
+            })
+            .min()
+            .unwrap_or(Duration::from_secs(config.settings.health_check_interval_seconds))
+            .max(Duration::from_secs(1))
+    }
 
-        debug!("Starting health check for {} enabled providers", enabled_providers.len());
+    /// 检查已到探测间隔的provider
+    pub async fn check_due(&self) -> Result<()> {
+        self.check_all_providers(true).await
+    }
+
+    /// 检查provider的健康状态，only_due为true时跳过未到探测间隔的provider
+    async fn check_all_providers(&self, only_due: bool) -> Result<()> {
+        let config = self.config();
 
         // 检查是否是初始检查
         let is_initial_check = {
@@ -68,6 +102,26 @@ impl HealthChecker {
             !*initial_done
         };
 
+        let enabled_providers: Vec<_> = {
+            let mut last_probe = self.last_probe.write().unwrap();
+            config.providers.iter()
+                .filter(|(_, provider)| provider.enabled)
+                .filter(|(provider_id, provider)| {
+                    let due = !only_due
+                        || is_initial_check
+                        || last_probe
+                            .get(*provider_id)
+                            .is_none_or(|last| last.elapsed() >= provider.probe_interval(&config.settings));
+                    if due {
+                        last_probe.insert((*provider_id).clone(), Instant::now());
+                    }
+                    due
+                })
+                .collect()
+        };
+
+        debug!("Starting health check for {} enabled providers", enabled_providers.len());
+
         if is_initial_check {
             info!("Performing initial health check - marking all enabled providers as healthy");
         } else {
@@ -84,7 +138,7 @@ impl HealthChecker {
             let provider_clone = provider.clone();
             let client = self.client.clone();
             let metrics = self.metrics.clone();
-            let config = self.config.clone();
+            let config = config.clone();
             let is_initial = is_initial_check;
 
             let task = tokio::spawn(async move {
@@ -122,7 +176,7 @@ impl HealthChecker {
         provider_id: &str,
         provider: &Provider,
         client: &Client,
-        metrics: &MetricsCollector,
+        metrics: &Arc<MetricsCollector>,
         config: &Config,
         is_initial_check: bool,
     ) {
@@ -146,12 +200,27 @@ impl HealthChecker {
 
         debug!("API key present for provider {}, proceeding with health check", provider_id);
 
+        // 被动模式：不发送探测请求，健康状态完全由实际请求结果决定
+        if provider.probe_type() == ProbeType::None {
+            debug!("Provider {} uses passive health checking, skipping active probe", provider_id);
+            for model in &provider.models {
+                let backend_key = format!("{}:{}", provider_id, model);
+                if is_initial_check {
+                    metrics.record_success(&backend_key);
+                }
+                metrics.update_health_check(&backend_key);
+            }
+            return;
+        }
+
+        let probe_timeout = provider.probe_timeout(&config.settings);
+
         // 检查该provider下每个模型的计费模式
         let mut has_per_token_models = false;
         let mut per_request_models = Vec::new();
 
         // 遍历所有模型映射，找到使用此provider的backends
-        for (_, model_mapping) in &config.models {
+        for model_mapping in config.models.values() {
             for backend in &model_mapping.backends {
                 if backend.provider == provider_id && provider.models.contains(&backend.model) {
                     match backend.billing_mode {
@@ -172,13 +241,7 @@ impl HealthChecker {
         // 如果有按token计费的模型，执行主动健康检查
         if has_per_token_models {
             debug!("Provider {} has per-token models, performing active health check", provider_id);
-            if provider.base_url.contains("httpbin.org") {
-                debug!("Detected test provider (httpbin), using HTTP status check for {}", provider_id);
-                Self::check_test_provider(provider_id, provider, client, metrics, start_time, is_initial_check).await;
-            } else {
-                debug!("Detected real AI provider, using models API check for {}", provider_id);
-                Self::check_real_provider(provider_id, provider, metrics, start_time, is_initial_check).await;
-            }
+            Self::probe_provider(provider_id, provider, client, metrics, probe_timeout, is_initial_check, &per_request_models).await;
         }
 
         // 处理按请求计费的模型
@@ -201,17 +264,128 @@ impl HealthChecker {
         // 如果既没有按token计费的模型，也没有按请求计费的模型，使用默认行为
         if !has_per_token_models && per_request_models.is_empty() {
             debug!("Provider {} has no configured backends, using default health check", provider_id);
-            if provider.base_url.contains("httpbin.org") {
-                Self::check_test_provider(provider_id, provider, client, metrics, start_time, is_initial_check).await;
-            } else {
-                Self::check_real_provider(provider_id, provider, metrics, start_time, is_initial_check).await;
-            }
+            Self::probe_provider(provider_id, provider, client, metrics, probe_timeout, is_initial_check, &per_request_models).await;
         }
 
         let total_time = start_time.elapsed();
         debug!("Completed health check for provider {} in {}ms", provider_id, total_time.as_millis());
     }
 
+    /// 按provider配置的探测方式执行主动探测，延迟从探测开始计算
+    async fn probe_provider(
+        provider_id: &str,
+        provider: &Provider,
+        client: &Client,
+        metrics: &Arc<MetricsCollector>,
+        probe_timeout: Duration,
+        is_initial_check: bool,
+        per_request_models: &[String],
+    ) {
+        let start_time = Instant::now();
+        match provider.probe_type() {
+            ProbeType::ChatProbe => {
+                debug!("Using chat probe for provider {}", provider_id);
+                // 按请求计费的模型每次探测都会产生费用，不做主动探测
+                let models: Vec<&String> = provider.models.iter()
+                    .filter(|model| !per_request_models.contains(model))
+                    .collect();
+                Self::check_chat_probe(provider_id, provider, &models, metrics, probe_timeout, is_initial_check).await;
+            }
+            _ if provider.base_url.contains("httpbin.org") => {
+                debug!("Detected test provider (httpbin), using HTTP status check for {}", provider_id);
+                Self::check_test_provider(provider_id, provider, client, metrics, start_time, probe_timeout, is_initial_check).await;
+            }
+            _ => {
+                debug!("Detected real AI provider, using models API check for {}", provider_id);
+                Self::check_real_provider(provider_id, provider, metrics, start_time, probe_timeout, is_initial_check).await;
+            }
+        }
+    }
+
+    /// 对每个模型发送max_tokens=1的最小聊天请求进行探测
+    async fn check_chat_probe(
+        provider_id: &str,
+        provider: &Provider,
+        models: &[&String],
+        metrics: &Arc<MetricsCollector>,
+        probe_timeout: Duration,
+        is_initial_check: bool,
+    ) {
+        let openai_client = match OpenAIClient::for_provider(provider_id, provider, probe_timeout, metrics.clone()) {
+            Ok(client) => client,
+            Err(e) => {
+                error!("Failed to create HTTP client for provider {}: {}", provider_id, e);
+                for model in models {
+                    metrics.record_failure(&format!("{}:{}", provider_id, model));
+                }
+                return;
+            }
+        };
+
+        for model in models {
+            let backend_key = format!("{}:{}", provider_id, model);
+            let start_time = Instant::now();
+            let result = openai_client
+                .chat_completions(Self::chat_headers(provider), &Self::chat_probe_body(model))
+                .await;
+            let latency = start_time.elapsed();
+
+            match result {
+                Ok(response) if response.status().is_success() => {
+                    debug!("Chat probe passed for backend {} ({}ms)", backend_key, latency.as_millis());
+                    metrics.record_latency(&backend_key, latency);
+                    // 后续检查不自动恢复不健康的后端，恢复仍由恢复检查负责
+                    if is_initial_check {
+                        metrics.record_success(&backend_key);
+                    }
+                    metrics.update_health_check(&backend_key);
+                }
+                Ok(response) => {
+                    warn!("Chat probe failed for backend {} with status: {}", backend_key, response.status());
+                    metrics.record_failure(&backend_key);
+                }
+                Err(e) => {
+                    error!("Chat probe error for backend {}: {}", backend_key, e);
+                    metrics.record_failure(&backend_key);
+                }
+            }
+        }
+    }
+
+    /// 构建探测用的最小聊天请求
+    fn chat_probe_body(model: &str) -> serde_json::Value {
+        json!({
+            "model": model,
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello"
+                }
+            ],
+            "max_tokens": 1,
+            "stream": false
+        })
+    }
+
+    /// 构建探测请求头（认证头及provider自定义头部）
+    fn chat_headers(provider: &Provider) -> reqwest::header::HeaderMap {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Authorization", format!("Bearer {}", provider.api_key).parse().unwrap());
+        headers.insert("Content-Type", "application/json".parse().unwrap());
+
+        for (key, value) in &provider.headers {
+            if let (Ok(header_name), Ok(header_value)) = (
+                key.parse::<reqwest::header::HeaderName>(),
+                value.parse::<reqwest::header::HeaderValue>()
+            ) {
+                headers.insert(header_name, header_value);
+            } else {
+                warn!("Failed to parse custom header for health check: {} = {}", key, value);
+            }
+        }
+        headers
+    }
+
     /// 检查测试provider（httpbin等）
     async fn check_test_provider(
         provider_id: &str,
@@ -219,12 +393,13 @@ impl HealthChecker {
         client: &Client,
         metrics: &MetricsCollector,
         start_time: Instant,
+        probe_timeout: Duration,
         is_initial_check: bool,
     ) {
         let health_check_url = format!("{}/status/200", provider.base_url);
         debug!("Testing provider {} with URL: {}", provider_id, health_check_url);
 
-        let mut request = client.get(&health_check_url);
+        let mut request = client.get(&health_check_url).timeout(probe_timeout);
 
         // 添加自定义头部
         if !provider.headers.is_empty() {
@@ -306,12 +481,28 @@ impl HealthChecker {
     async fn check_real_provider(
         provider_id: &str,
         provider: &Provider,
-        metrics: &MetricsCollector,
+        metrics: &Arc<MetricsCollector>,
         start_time: Instant,
+        probe_timeout: Duration,
         is_initial_check: bool,
     ) {
         debug!("Checking real AI provider {} using models API", provider_id);
-        let openai_client = OpenAIClient::with_base_url(provider.base_url.clone());
+        let openai_client = match OpenAIClient::for_provider(
+            provider_id,
+            provider,
+            probe_timeout,
+            metrics.clone(),
+        ) {
+            Ok(client) => client,
+            Err(e) => {
+                error!("Failed to create HTTP client for provider {}: {}", provider_id, e);
+                for model in &provider.models {
+                    let backend_key = format!("{}:{}", provider_id, model);
+                    metrics.record_failure(&backend_key);
+                }
+                return;
+            }
+        };
 
         debug!("Sending models API request to provider {} (base_url: {})", provider_id, provider.base_url);
         // 使用models API检查provider健康状态
@@ -382,19 +573,20 @@ impl HealthChecker {
     /// 手动触发健康检查
     pub async fn check_now(&self) -> Result<()> {
         info!("Manual health check triggered");
-        self.check_all_providers().await
+        self.check_all_providers(false).await
     }
 
     /// 检查特定provider的健康状态
     pub async fn check_provider(&self, provider_id: &str) -> Result<()> {
-        if let Some(provider) = self.config.providers.get(provider_id) {
+        let config = self.config();
+        if let Some(provider) = config.providers.get(provider_id) {
             if provider.enabled {
                 Self::check_provider_health(
                     provider_id,
                     provider,
                     &self.client,
                     &self.metrics,
-                    &self.config,
+                    &config,
                     false, // 手动触发的检查不是初始检查
                 ).await;
                 Ok(())
@@ -408,7 +600,8 @@ impl HealthChecker {
 
     /// 检查不健康的provider是否可以恢复
     pub async fn check_recovery(&self) -> Result<()> {
-        let recovery_interval = Duration::from_secs(self.config.settings.recovery_check_interval_seconds);
+        let config = self.config();
+        let recovery_interval = Duration::from_secs(config.settings.recovery_check_interval_seconds);
         let unhealthy_backends = self.metrics.get_unhealthy_backends();
 
         debug!("Starting recovery check process (interval: {}s)", recovery_interval.as_secs());
@@ -442,13 +635,13 @@ impl HealthChecker {
 
                 debug!("Parsed backend key: provider={}, model={}", provider_id, model_name);
 
-                if let Some(provider) = self.config.providers.get(provider_id) {
+                if let Some(provider) = config.providers.get(provider_id) {
                     if provider.enabled {
                         // 查找对应的backend配置来确定计费模式
                         let mut backend_billing_mode = BillingMode::PerToken; // 默认值
                         let mut found_backend = false;
 
-                        for (_, model_mapping) in &self.config.models {
+                        for model_mapping in config.models.values() {
                             for backend in &model_mapping.backends {
                                 if backend.provider == provider_id && backend.model == model_name {
                                     backend_billing_mode = backend.billing_mode.clone();
@@ -509,44 +702,27 @@ impl HealthChecker {
         let start_time = Instant::now();
         debug!("Starting chat-based recovery check for {}:{}", provider_id, model_name);
 
-        let openai_client = OpenAIClient::with_base_url(provider.base_url.clone());
+        let openai_client = match OpenAIClient::for_provider(
+            provider_id,
+            provider,
+            Duration::from_secs(provider.timeout_seconds),
+            self.metrics.clone(),
+        ) {
+            Ok(client) => client,
+            Err(e) => {
+                error!("Failed to create HTTP client for recovery check of {}:{}: {}", provider_id, model_name, e);
+                return;
+            }
+        };
         debug!("Created OpenAI client for recovery check (base_url: {})", provider.base_url);
 
         // 构建简单的chat请求
-        let test_body = json!({
-            "model": model_name,
-            "messages": [
-                {
-                    "role": "user",
-                    "content": "Hello"
-                }
-            ],
-            "max_tokens": 1,
-            "stream": false
-        });
+        let test_body = Self::chat_probe_body(model_name);
         debug!("Built test chat request for recovery check: {}", test_body);
 
         // 构建请求头
-        let mut headers = reqwest::header::HeaderMap::new();
-        headers.insert("Authorization", format!("Bearer {}", provider.api_key).parse().unwrap());
-        headers.insert("Content-Type", "application/json".parse().unwrap());
-        debug!("Added basic headers for recovery check (Authorization, Content-Type)");
-
-        // 添加自定义头部
-        if !provider.headers.is_empty() {
-            debug!("Adding {} custom headers for recovery check", provider.headers.len());
-            for (key, value) in &provider.headers {
-                if let (Ok(header_name), Ok(header_value)) = (
-                    key.parse::<reqwest::header::HeaderName>(),
-                    value.parse::<reqwest::header::HeaderValue>()
-                ) {
-                    debug!("Adding custom header for recovery: {} = {}", key, value);
-                    headers.insert(header_name, header_value);
-                } else {
-                    warn!("Failed to parse custom header for recovery check: {} = {}", key, value);
-                }
-            }
-        }
+        let headers = Self::chat_headers(provider);
+        debug!("Built {} headers for recovery check", headers.len());
 
         debug!("Sending chat request for recovery check to {}:{}", provider_id, model_name);
         match openai_client.chat_completions(headers, &test_body).await {
@@ -590,8 +766,9 @@ impl HealthChecker {
         let mut healthy_providers = 0;
         let mut total_models = 0;
         let mut healthy_models = 0;
+        let config = self.config();
 
-        for (provider_id, provider) in &self.config.providers {
+        for (provider_id, provider) in &config.providers {
             if provider.enabled {
                 total_providers += 1;
                 let mut provider_healthy = true;
@@ -671,6 +848,15 @@ mod tests {
             enabled: true,
             timeout_seconds: 5,
             max_retries: 1,
+            tls_pinning: None,
+            tls: None,
+            http_client: None,
+            proxy: None,
+            concurrency: None,
+            quota: None,
+            key_budget: None,
+            timeouts: None,
+            health_check: None,
         });
 
         let mut models = HashMap::new();
@@ -684,15 +870,44 @@ mod tests {
                 enabled: true,
                 tags: vec![],
                 billing_mode: BillingMode::PerToken,
+                pricing: None,
+                overrides: HashMap::new(),
+                slow_start: None,
+                concurrency: None,
+                schedule: None,
+                region: None,
+                max_context: None,
+                supports_vision: true,
+                supports_tools: true,
+                supports_json_mode: true,
+                supports_streaming: true,
+                supports_responses: false,
             }],
             strategy: LoadBalanceStrategy::WeightedRandom,
             enabled: true,
+            virtual_nodes: 100,
+            semantic_cache: true,
+            budget: None,
+            fallback_models: Vec::new(),
+            transforms: Vec::new(),
+            hedging: None,
+            max_in_flight_requests: None,
+            mirror: None,
+            experiment: None,
+            retry: None,
+            latency_slo_ms: None,
+            structured_output: None,
+            fair_queue: None,
+            timeouts: None,
+            content_filters: Vec::new(),
         });
 
         Config {
             providers,
             models,
             users: HashMap::new(),
+            peers: HashMap::new(),
+            tenants: HashMap::new(),
             settings: GlobalSettings {
                 health_check_interval_seconds: 10,
                 request_timeout_seconds: 5,
@@ -702,6 +917,49 @@ mod tests {
                 recovery_check_interval_seconds: 120,
                 max_internal_retries: 2,
                 health_check_timeout_seconds: 10,
+                failover_memory_ttl_seconds: 300,
+                model_degraded_ratio: 0.75,
+                model_down_ratio: 0.25,
+                model_health_hysteresis: 0.1,
+                health_webhook_urls: vec![],
+                readiness_required_models: Vec::new(),
+                config_hot_reload: true,
+                peer_max_hops: 1,
+                response_cache_enabled: false,
+                response_cache_ttl_seconds: 300,
+                response_cache_max_entries: 1000,
+                semantic_cache: None,
+                latency_ewma_alpha: 0.3,
+                audit_log: None,
+                keys_file: None,
+                max_embedding_batch_size: 2048,
+                max_request_body_mb: 32,
+                slow_start: Default::default(),
+                outlier_detection: None,
+                max_in_flight_requests: None,
+                priority_scheduling: None,
+                shared_state: None,
+                secrets: None,
+                config_history: Default::default(),
+                health_scoring: None,
+                region_routing: None,
+                content_filters: HashMap::new(),
+                moderation: None,
+                wasm_plugins: None,
+                notifications: None,
+                alerts: None,
+                slo: None,
+                request_log: None,
+                jwt: None,
+                network_acl: None,
+                cors: None,
+                grpc: None,
+                job_queue: None,
+                files: None,
+                stream_heartbeat: None,
+                stream_resumption: None,
+                request_capture: None,
+                fault_injection: false,
             },
         }
     }
@@ -717,4 +975,26 @@ mod tests {
         assert_eq!(summary.total_providers, 1);
         assert_eq!(summary.total_models, 1);
     }
+
+
+    #[tokio::test]
+    async fn test_passive_probe_respects_provider_interval() {
+        let mut config = create_test_config();
+        config.providers.get_mut("test-provider").unwrap().health_check =
+            Some(crate::config::model::ProviderHealthCheck {
+                probe: ProbeType::None,
+                interval_seconds: Some(60),
+                timeout_seconds: None,
+            });
+        let metrics = Arc::new(MetricsCollector::new());
+        let checker = HealthChecker::new(Arc::new(config), metrics.clone());
+
+        // 尚未探测过的provider立即到期
+        assert_eq!(checker.next_check_delay(), Duration::from_secs(1));
+
+        // 被动模式不发送请求，初始检查直接视为健康
+        checker.check_due().await.unwrap();
+        assert!(metrics.is_healthy("test-provider", "test-model"));
+        assert!(checker.next_check_delay() > Duration::from_secs(50));
+    }
 }
@@ -0,0 +1,258 @@
+use crate::config::model::HealthScoringSettings;
+use super::clock::{Clock, SystemClock};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 按分数调整权重时的最低比例，避免所有后端权重都为0时无法选择
+const MIN_WEIGHT_FACTOR: f64 = 0.05;
+
+/// 实际请求的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestOutcome {
+    Success,
+    Failure,
+    RateLimited,
+}
+
+/// 后端的综合健康评分及其组成
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthScore {
+    /// 综合分数，取值[0, 1]
+    pub score: f64,
+    pub healthy: bool,
+    /// 窗口内的错误率，请求数不足时为None
+    pub error_rate: Option<f64>,
+    /// 当前延迟与基线延迟之比，没有延迟数据时为None
+    pub latency_ratio: Option<f64>,
+    /// 窗口内的上游限流次数
+    pub rate_limited: u32,
+}
+
+#[derive(Debug, Default)]
+struct ScoreWindow {
+    outcomes: VecDeque<(Instant, RequestOutcome)>,
+    /// 延迟基线（秒），按较小的平滑系数跟踪长期延迟
+    baseline_latency: Option<f64>,
+}
+
+/// 后端综合健康评分：未配置时分数只由健康状态决定（健康为1，不健康为0）
+pub struct HealthScoreTracker {
+    settings: std::sync::RwLock<Option<HealthScoringSettings>>,
+    windows: std::sync::Mutex<HashMap<String, ScoreWindow>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl HealthScoreTracker {
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// 使用指定时钟创建，与MetricsCollector共用同一个时钟
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            settings: std::sync::RwLock::new(None),
+            windows: std::sync::Mutex::new(HashMap::new()),
+            clock,
+        }
+    }
+
+    /// 更新评分配置（随配置热加载更新），关闭时清除统计数据
+    pub fn set_settings(&self, settings: Option<HealthScoringSettings>) {
+        if settings.is_none()
+            && let Ok(mut windows) = self.windows.lock()
+        {
+            windows.clear();
+        }
+        if let Ok(mut current) = self.settings.write() {
+            *current = settings;
+        }
+    }
+
+    pub fn settings(&self) -> Option<HealthScoringSettings> {
+        self.settings.read().ok().and_then(|settings| settings.clone())
+    }
+
+    /// 记录一次实际请求的结果
+    pub fn record_outcome(&self, backend_key: &str, outcome: RequestOutcome) {
+        self.record_outcome_at(backend_key, outcome, self.clock.now());
+    }
+
+    pub(crate) fn record_outcome_at(&self, backend_key: &str, outcome: RequestOutcome, now: Instant) {
+        let Some(settings) = self.settings() else {
+            return;
+        };
+        let Ok(mut windows) = self.windows.lock() else {
+            return;
+        };
+        let window = windows.entry(backend_key.to_string()).or_default();
+        window.outcomes.push_back((now, outcome));
+        let length = Duration::from_secs(settings.window_seconds);
+        while window
+            .outcomes
+            .front()
+            .is_some_and(|(time, _)| now.duration_since(*time) > length)
+        {
+            window.outcomes.pop_front();
+        }
+    }
+
+    /// 用新的延迟样本更新延迟基线
+    pub fn record_latency(&self, backend_key: &str, latency: Duration) {
+        let Some(settings) = self.settings() else {
+            return;
+        };
+        let Ok(mut windows) = self.windows.lock() else {
+            return;
+        };
+        let window = windows.entry(backend_key.to_string()).or_default();
+        let sample = latency.as_secs_f64();
+        let alpha = settings.latency_baseline_alpha;
+        window.baseline_latency = Some(match window.baseline_latency {
+            Some(baseline) => alpha * sample + (1.0 - alpha) * baseline,
+            None => sample,
+        });
+    }
+
+    /// 计算后端的健康分数，latency为当前的平滑延迟
+    pub fn score(&self, backend_key: &str, healthy: bool, latency: Option<Duration>) -> HealthScore {
+        self.score_at(backend_key, healthy, latency, self.clock.now())
+    }
+
+    pub(crate) fn score_at(&self, backend_key: &str, healthy: bool, latency: Option<Duration>, now: Instant) -> HealthScore {
+        let mut result = HealthScore {
+            score: if healthy { 1.0 } else { 0.0 },
+            healthy,
+            error_rate: None,
+            latency_ratio: None,
+            rate_limited: 0,
+        };
+        let Some(settings) = self.settings() else {
+            return result;
+        };
+        let Ok(windows) = self.windows.lock() else {
+            return result;
+        };
+        let Some(window) = windows.get(backend_key) else {
+            return result;
+        };
+
+        let length = Duration::from_secs(settings.window_seconds);
+        let recent = window
+            .outcomes
+            .iter()
+            .filter(|(time, _)| now.saturating_duration_since(*time) <= length);
+        let (mut requests, mut failures) = (0u32, 0u32);
+        for (_, outcome) in recent {
+            match outcome {
+                RequestOutcome::Success => requests += 1,
+                RequestOutcome::Failure => {
+                    requests += 1;
+                    failures += 1;
+                }
+                RequestOutcome::RateLimited => result.rate_limited += 1,
+            }
+        }
+        if requests > 0 && requests >= settings.min_requests {
+            result.error_rate = Some(failures as f64 / requests as f64);
+        }
+        if let (Some(latency), Some(baseline)) = (latency, window.baseline_latency)
+            && baseline > 0.0
+        {
+            result.latency_ratio = Some(latency.as_secs_f64() / baseline);
+        }
+
+        let error_factor = 1.0 - result.error_rate.unwrap_or(0.0);
+        // 只有慢于基线时才扣分，例如延迟为基线的2倍时该项为0.5
+        let latency_factor = result.latency_ratio.map_or(1.0, |ratio| (1.0 / ratio).min(1.0));
+        let rate_limit_factor = 1.0 / (1.0 + result.rate_limited as f64);
+        result.score *= error_factor * latency_factor * rate_limit_factor;
+        result
+    }
+
+    /// 后端分数是否达到可用的最低分数，未配置时等同于健康状态
+    pub fn meets_threshold(&self, score: &HealthScore) -> bool {
+        match self.settings() {
+            Some(settings) => score.score >= settings.min_score,
+            None => score.healthy,
+        }
+    }
+
+    /// 按分数调整后的权重，未配置或关闭weight_by_score时返回原始权重
+    pub fn weight(&self, score: &HealthScore, weight: f64) -> f64 {
+        match self.settings() {
+            Some(settings) if settings.weight_by_score => weight * score.score.max(MIN_WEIGHT_FACTOR),
+            _ => weight,
+        }
+    }
+}
+
+impl Default for HealthScoreTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loadbalance::clock::ManualClock;
+
+    fn tracker(clock: Arc<ManualClock>) -> HealthScoreTracker {
+        let tracker = HealthScoreTracker::with_clock(clock);
+        tracker.set_settings(Some(HealthScoringSettings {
+            min_score: 0.5,
+            window_seconds: 60,
+            min_requests: 4,
+            latency_baseline_alpha: 0.5,
+            weight_by_score: true,
+        }));
+        tracker
+    }
+
+    #[test]
+    fn test_score_combines_errors_latency_and_rate_limits() {
+        let clock = Arc::new(ManualClock::new());
+        let tracker = tracker(clock.clone());
+        let now = clock.now();
+        for outcome in [RequestOutcome::Success, RequestOutcome::Success, RequestOutcome::Success] {
+            tracker.record_outcome_at("a:m", outcome, now);
+        }
+        // 请求数不足时不计错误率
+        tracker.record_outcome_at("a:m", RequestOutcome::RateLimited, now);
+        let score = tracker.score_at("a:m", true, None, now);
+        assert_eq!(score.error_rate, None);
+        assert_eq!(score.score, 0.5);
+        assert!(tracker.meets_threshold(&score));
+
+        tracker.record_outcome_at("a:m", RequestOutcome::Failure, now);
+        tracker.record_latency("a:m", Duration::from_millis(100));
+        let score = tracker.score_at("a:m", true, Some(Duration::from_millis(200)), now);
+        assert_eq!(score.error_rate, Some(0.25));
+        assert_eq!(score.latency_ratio, Some(2.0));
+        assert_eq!(score.score, 0.75 * 0.5 * 0.5);
+        assert!(!tracker.meets_threshold(&score));
+        assert_eq!(tracker.weight(&score, 2.0), 2.0 * score.score);
+
+        // 窗口外的结果不再计入
+        clock.advance(Duration::from_secs(120));
+        let score = tracker.score("a:m", true, Some(Duration::from_millis(50)));
+        assert_eq!(score.score, 1.0);
+    }
+
+    #[test]
+    fn test_unconfigured_score_follows_health() {
+        let tracker = HealthScoreTracker::new();
+        tracker.record_outcome("a:m", RequestOutcome::Failure);
+        let healthy = tracker.score("a:m", true, Some(Duration::from_secs(5)));
+        assert_eq!(healthy.score, 1.0);
+        assert!(tracker.meets_threshold(&healthy));
+        assert_eq!(tracker.weight(&healthy, 3.0), 3.0);
+
+        let unhealthy = tracker.score("a:m", false, None);
+        assert_eq!(unhealthy.score, 0.0);
+        assert!(!tracker.meets_threshold(&unhealthy));
+        assert_eq!(tracker.weight(&unhealthy, 3.0), 3.0);
+    }
+}
@@ -0,0 +1,268 @@
+use crate::config::model::{Config, KeyBudget};
+use crate::relay::cache::sha256_hex;
+use super::TokenUsage;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// provider当前使用的上游密钥及其月度上限
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderKey {
+    /// 密钥SHA-256摘要的前12位，用于区分轮换前后的密钥而不暴露密钥本身
+    pub fingerprint: String,
+    pub budget: Option<KeyBudget>,
+}
+
+impl ProviderKey {
+    pub fn new(api_key: &str, budget: Option<KeyBudget>) -> Self {
+        Self {
+            fingerprint: sha256_hex(api_key.as_bytes())[..12].to_string(),
+            budget,
+        }
+    }
+
+    /// 配置中所有provider当前使用的密钥
+    pub fn from_config(config: &Config) -> HashMap<String, ProviderKey> {
+        config
+            .providers
+            .iter()
+            .map(|(id, provider)| (id.clone(), ProviderKey::new(&provider.api_key, provider.key_budget.clone())))
+            .collect()
+    }
+}
+
+/// 密钥用量告警
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeyBudgetAlert {
+    pub provider: String,
+    pub key_fingerprint: String,
+    pub month: String,
+    /// 达到上限时为true，仅达到告警比例时为false
+    pub exhausted: bool,
+    /// 已使用上限的百分比（取花费和token中较高者）
+    pub used_percent: f64,
+}
+
+/// 单个密钥在当月的用量
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KeySpend {
+    pub provider: String,
+    pub key_fingerprint: String,
+    /// 统计月份（UTC），如 2025-01
+    pub month: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// 当月花费（美元）
+    pub cost: f64,
+    pub monthly_cost_limit: Option<f64>,
+    pub monthly_token_limit: Option<u64>,
+    pub exhausted: bool,
+}
+
+#[derive(Debug, Default)]
+struct KeySpendState {
+    month: String,
+    requests: u64,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    cost: f64,
+    alerted: bool,
+    exhausted_alerted: bool,
+}
+
+impl KeySpendState {
+    /// 已使用上限的百分比，没有配置上限时为0
+    fn used_percent(&self, budget: &KeyBudget) -> f64 {
+        let cost = budget.monthly_cost.map(|limit| self.cost / limit * 100.0);
+        let tokens = budget
+            .monthly_tokens
+            .map(|limit| (self.prompt_tokens + self.completion_tokens) as f64 / limit as f64 * 100.0);
+        cost.into_iter().chain(tokens).fold(0.0, f64::max)
+    }
+}
+
+/// 上游密钥月度用量统计：按provider和密钥指纹汇总，达到上限的密钥不再参与选择，仅保存在内存中
+pub struct KeySpendTracker {
+    keys: std::sync::RwLock<HashMap<String, ProviderKey>>,
+    spend: std::sync::Mutex<HashMap<(String, String), KeySpendState>>,
+}
+
+impl KeySpendTracker {
+    pub fn new() -> Self {
+        Self {
+            keys: std::sync::RwLock::new(HashMap::new()),
+            spend: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn current_month() -> String {
+        chrono::Utc::now().format("%Y-%m").to_string()
+    }
+
+    /// 设置各provider当前使用的密钥，密钥轮换后按新指纹重新统计
+    pub fn set_keys(&self, keys: HashMap<String, ProviderKey>) {
+        if let Ok(mut current) = self.keys.write() {
+            *current = keys;
+        }
+    }
+
+    /// 记录provider当前密钥的一次用量，首次达到告警比例或上限时返回告警
+    pub fn record(&self, provider: &str, usage: TokenUsage, cost: f64) -> Option<KeyBudgetAlert> {
+        self.record_in_month(provider, usage, cost, &Self::current_month())
+    }
+
+    fn record_in_month(&self, provider: &str, usage: TokenUsage, cost: f64, month: &str) -> Option<KeyBudgetAlert> {
+        let key = self.keys.read().ok()?.get(provider).cloned()?;
+        let mut spend = self.spend.lock().ok()?;
+        let state = spend
+            .entry((provider.to_string(), key.fingerprint.clone()))
+            .or_default();
+        if state.month != month {
+            *state = KeySpendState {
+                month: month.to_string(),
+                ..Default::default()
+            };
+        }
+        state.requests += 1;
+        state.prompt_tokens += usage.prompt_tokens;
+        state.completion_tokens += usage.completion_tokens;
+        state.cost += cost;
+
+        let budget = key.budget.as_ref()?;
+        let used_percent = state.used_percent(budget);
+        let exhausted = used_percent >= 100.0;
+        let alert = if exhausted && !state.exhausted_alerted {
+            state.exhausted_alerted = true;
+            state.alerted = true;
+            true
+        } else if used_percent >= budget.alert_percent && !state.alerted {
+            state.alerted = true;
+            true
+        } else {
+            false
+        };
+        alert.then(|| KeyBudgetAlert {
+            provider: provider.to_string(),
+            key_fingerprint: key.fingerprint,
+            month: month.to_string(),
+            exhausted,
+            used_percent,
+        })
+    }
+
+    /// provider当前密钥本月是否已达到上限
+    pub fn is_exhausted(&self, provider: &str) -> bool {
+        self.is_exhausted_in_month(provider, &Self::current_month())
+    }
+
+    fn is_exhausted_in_month(&self, provider: &str, month: &str) -> bool {
+        let Some(key) = self.keys.read().ok().and_then(|keys| keys.get(provider).cloned()) else {
+            return false;
+        };
+        let Some(budget) = &key.budget else {
+            return false;
+        };
+        self.spend.lock().is_ok_and(|spend| {
+            spend
+                .get(&(provider.to_string(), key.fingerprint))
+                .is_some_and(|state| state.month == month && state.used_percent(budget) >= 100.0)
+        })
+    }
+
+    /// 各provider当前密钥本月的用量，按provider排序
+    pub fn report(&self) -> Vec<KeySpend> {
+        let month = Self::current_month();
+        let Ok(keys) = self.keys.read() else {
+            return Vec::new();
+        };
+        let Ok(spend) = self.spend.lock() else {
+            return Vec::new();
+        };
+        let mut report: Vec<KeySpend> = keys
+            .iter()
+            .map(|(provider, key)| {
+                let state = spend
+                    .get(&(provider.clone(), key.fingerprint.clone()))
+                    .filter(|state| state.month == month);
+                let budget = key.budget.as_ref();
+                KeySpend {
+                    provider: provider.clone(),
+                    key_fingerprint: key.fingerprint.clone(),
+                    month: month.clone(),
+                    requests: state.map_or(0, |s| s.requests),
+                    prompt_tokens: state.map_or(0, |s| s.prompt_tokens),
+                    completion_tokens: state.map_or(0, |s| s.completion_tokens),
+                    cost: state.map_or(0.0, |s| s.cost),
+                    monthly_cost_limit: budget.and_then(|b| b.monthly_cost),
+                    monthly_token_limit: budget.and_then(|b| b.monthly_tokens),
+                    exhausted: budget
+                        .zip(state)
+                        .is_some_and(|(budget, state)| state.used_percent(budget) >= 100.0),
+                }
+            })
+            .collect();
+        report.sort_by(|a, b| a.provider.cmp(&b.provider));
+        report
+    }
+}
+
+impl Default for KeySpendTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(api_key: &str) -> KeySpendTracker {
+        let tracker = KeySpendTracker::new();
+        let budget = KeyBudget {
+            monthly_cost: Some(1.0),
+            monthly_tokens: Some(1000),
+            alert_percent: 50.0,
+        };
+        tracker.set_keys(HashMap::from([("openai".to_string(), ProviderKey::new(api_key, Some(budget)))]));
+        tracker
+    }
+
+    fn usage(tokens: u64) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: tokens,
+            completion_tokens: 0,
+        }
+    }
+
+    #[test]
+    fn test_alerts_once_and_exhausts_at_limit() {
+        let tracker = tracker("sk-1");
+        assert_eq!(tracker.record_in_month("openai", usage(100), 0.1, "2025-01"), None);
+
+        let alert = tracker.record_in_month("openai", usage(500), 0.1, "2025-01").unwrap();
+        assert!(!alert.exhausted);
+        assert_eq!(alert.used_percent, 60.0);
+        assert_eq!(tracker.record_in_month("openai", usage(100), 0.1, "2025-01"), None);
+        assert!(!tracker.is_exhausted_in_month("openai", "2025-01"));
+
+        // 花费未到上限，但token已到上限
+        let alert = tracker.record_in_month("openai", usage(300), 0.1, "2025-01").unwrap();
+        assert!(alert.exhausted);
+        assert!(tracker.is_exhausted_in_month("openai", "2025-01"));
+
+        // 新的月份重新计算
+        assert!(!tracker.is_exhausted_in_month("openai", "2025-02"));
+        assert_eq!(tracker.record_in_month("openai", usage(10), 0.0, "2025-02"), None);
+    }
+
+    #[test]
+    fn test_rotated_key_starts_fresh() {
+        let tracker = tracker("sk-1");
+        tracker.record_in_month("openai", usage(2000), 0.0, "2025-01");
+        assert!(tracker.is_exhausted_in_month("openai", "2025-01"));
+
+        let budget = tracker.keys.read().unwrap()["openai"].budget.clone();
+        tracker.set_keys(HashMap::from([("openai".to_string(), ProviderKey::new("sk-2", budget))]));
+        assert!(!tracker.is_exhausted_in_month("openai", "2025-01"));
+    }
+}
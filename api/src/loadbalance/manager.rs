@@ -1,14 +1,14 @@
 use crate::config::model::{Config, Backend, ModelMapping};
-use super::{BackendSelector, MetricsCollector};
+use super::{BackendSelector, MetricsCollector, ProviderKey, SelectionContext};
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 /// 负载均衡管理器
 /// 负责管理所有模型的负载均衡选择器和指标收集
 pub struct LoadBalanceManager {
-    config: Arc<Config>,
+    config: std::sync::RwLock<Arc<Config>>,
     selectors: Arc<RwLock<HashMap<String, BackendSelector>>>,
     metrics: Arc<MetricsCollector>,
 }
@@ -16,8 +16,17 @@ pub struct LoadBalanceManager {
 impl LoadBalanceManager {
     /// 创建新的负载均衡管理器
     pub fn new(config: Config) -> Self {
-        let config = Arc::new(config);
         let metrics = Arc::new(MetricsCollector::new());
+        metrics.set_latency_alpha(config.settings.latency_ewma_alpha);
+        metrics.set_slow_start_stages(config.settings.slow_start.stages.clone());
+        metrics.set_slow_start_overrides(config.slow_start_overrides());
+        metrics.set_outlier_detection(config.settings.outlier_detection.clone());
+        metrics.set_health_scoring(config.settings.health_scoring.clone());
+        metrics.set_slo(config.settings.slo.clone());
+        metrics.set_region_routing(config.settings.region_routing.clone());
+        metrics.set_provider_quotas(config.provider_quotas());
+        metrics.set_provider_keys(ProviderKey::from_config(&config));
+        let config = std::sync::RwLock::new(Arc::new(config));
         let selectors = Arc::new(RwLock::new(HashMap::new()));
 
         Self {
@@ -29,9 +38,11 @@ impl LoadBalanceManager {
 
     /// 初始化所有模型的选择器
     pub async fn initialize(&self) -> Result<()> {
+        let config = self.get_config();
         let mut selectors = self.selectors.write().await;
-        
-        for (model_id, model_mapping) in &self.config.models {
+        selectors.clear();
+
+        for (model_id, model_mapping) in &config.models {
             if model_mapping.enabled {
                 let selector = BackendSelector::new(
                     model_mapping.clone(),
@@ -47,15 +58,24 @@ impl LoadBalanceManager {
 
     /// 为指定模型选择后端
     pub async fn select_backend(&self, model_name: &str) -> Result<Backend> {
+        self.select_backend_with_context(model_name, &SelectionContext::default()).await
+    }
+
+    /// 根据请求上下文为指定模型选择后端
+    pub async fn select_backend_with_context(
+        &self,
+        model_name: &str,
+        context: &SelectionContext,
+    ) -> Result<Backend> {
         // 首先尝试通过模型ID查找
         if let Some(selector) = self.selectors.read().await.get(model_name) {
-            return selector.select();
+            return selector.select_with_context(context);
         }
 
         // 如果没找到，尝试通过模型的真实名称查找
         for (_, selector) in self.selectors.read().await.iter() {
             if selector.get_model_name() == model_name {
-                return selector.select();
+                return selector.select_with_context(context);
             }
         }
 
@@ -63,13 +83,13 @@ impl LoadBalanceManager {
     }
 
     /// 获取指定模型的配置
-    pub fn get_model_config(&self, model_name: &str) -> Option<&ModelMapping> {
-        self.config.get_model(model_name)
+    pub fn get_model_config(&self, model_name: &str) -> Option<ModelMapping> {
+        self.get_config().get_model(model_name).cloned()
     }
 
     /// 获取所有可用的模型列表
     pub fn get_available_models(&self) -> Vec<String> {
-        self.config.get_available_models()
+        self.get_config().get_available_models()
     }
 
     /// 记录请求成功
@@ -96,10 +116,26 @@ impl LoadBalanceManager {
         new_config.validate()?;
 
         // 更新配置
-        let _old_config = std::mem::replace(
-            &mut *Arc::get_mut(&mut self.config.clone()).unwrap(),
-            new_config
-        );
+        self.metrics.set_latency_alpha(new_config.settings.latency_ewma_alpha);
+        self.metrics.set_slow_start_stages(new_config.settings.slow_start.stages.clone());
+        self.metrics.set_slow_start_overrides(new_config.slow_start_overrides());
+        self.metrics.set_outlier_detection(new_config.settings.outlier_detection.clone());
+        self.metrics.set_health_scoring(new_config.settings.health_scoring.clone());
+        self.metrics.set_slo(new_config.settings.slo.clone());
+        self.metrics.set_region_routing(new_config.settings.region_routing.clone());
+        self.metrics.set_provider_quotas(new_config.provider_quotas());
+        self.metrics.set_provider_keys(ProviderKey::from_config(&new_config));
+
+        // 新加入的后端先经过慢启动，避免一上线就承担全部流量
+        let previous_backends = backend_keys(&self.get_config());
+        for backend_key in backend_keys(&new_config).difference(&previous_backends) {
+            tracing::info!("Backend {} added by config reload, starting slow start", backend_key);
+            self.metrics.start_slow_start(backend_key);
+        }
+
+        if let Ok(mut config) = self.config.write() {
+            *config = Arc::new(new_config);
+        }
 
         // 重新初始化选择器
         self.initialize().await?;
@@ -156,10 +192,25 @@ impl LoadBalanceManager {
 
     /// 获取配置的引用
     pub fn get_config(&self) -> Arc<Config> {
-        self.config.clone()
+        self.config
+            .read()
+            .map(|config| config.clone())
+            .unwrap_or_else(|e| e.into_inner().clone())
     }
 }
 
+/// 配置中所有启用的后端标识（provider:model）
+fn backend_keys(config: &Config) -> HashSet<String> {
+    config
+        .models
+        .values()
+        .filter(|mapping| mapping.enabled)
+        .flat_map(|mapping| mapping.backends.iter())
+        .filter(|backend| backend.enabled)
+        .map(|backend| format!("{}:{}", backend.provider, backend.model))
+        .collect()
+}
+
 /// 健康状态统计
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct HealthStats {
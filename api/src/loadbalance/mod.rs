@@ -2,8 +2,32 @@ pub mod selector;
 pub mod manager;
 pub mod health_checker;
 pub mod service;
+pub mod failover_memory;
+pub mod model_health;
+pub mod events;
+pub mod cost;
+pub mod experiment;
+pub mod key_budget;
+pub mod health_score;
+pub mod request_stats;
+pub mod notifier;
+pub mod alerts;
+pub mod slo;
+pub mod clock;
 
-pub use selector::{BackendSelector, MetricsCollector};
+pub use selector::{BackendSelector, CandidateExplanation, InFlightGuard, MetricsCollector, RequiredCapabilities, SelectionContext};
 pub use manager::{LoadBalanceManager, HealthStats};
 pub use health_checker::{HealthChecker, HealthSummary};
-pub use service::{LoadBalanceService, SelectedBackend, RequestResult, ServiceHealth};
+pub use failover_memory::{FailoverMemory, FailoverEntry};
+pub use events::{EventBus, ServiceEvent};
+pub use cost::{BudgetExceeded, CostReport, CostTracker, SpendSummary, TokenUsage, UsageBucket, UsageDimension, UsageQuery, UsageReport, UsageRow};
+pub use experiment::{ArmStats, ExperimentArm, ExperimentAssignment, ExperimentReport, ExperimentTracker};
+pub use health_score::{HealthScore, HealthScoreTracker, RequestOutcome};
+pub use key_budget::{KeyBudgetAlert, KeySpend, KeySpendTracker, ProviderKey};
+pub use request_stats::{RequestCounts, RequestRates, RequestStats, RequestStatsReport, WindowRate};
+pub use notifier::{HealthEventWatcher, HealthNotifier};
+pub use alerts::{Alert, AlertClassifier, AlertManager};
+pub use slo::{BurnRate, SloObjective, SloObjectiveReport, SloTracker, SloTransition};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use model_health::{ModelHealth, ModelHealthState, ModelHealthTracker, ModelHealthTransition};
+pub use service::{ConfigUpdateError, LoadBalanceService, SelectedBackend, SelectionExplanation, SelectionPreview, RequestResult, ServiceHealth};
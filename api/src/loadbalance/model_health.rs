@@ -0,0 +1,292 @@
+use crate::config::model::{Config, GlobalSettings};
+use super::MetricsCollector;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+/// 模型（映射）级别的聚合健康状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelHealthState {
+    /// 健康后端比例充足
+    Healthy,
+    /// 部分后端不健康，仍可服务但容量下降
+    Degraded,
+    /// 没有或几乎没有可用后端
+    Down,
+}
+
+impl ModelHealthState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModelHealthState::Healthy => "healthy",
+            ModelHealthState::Degraded => "degraded",
+            ModelHealthState::Down => "down",
+        }
+    }
+
+    /// 是否仍可处理请求（健康或降级）
+    pub fn is_available(&self) -> bool {
+        *self != ModelHealthState::Down
+    }
+}
+
+/// 单个模型的健康快照
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelHealth {
+    pub state: ModelHealthState,
+    pub healthy_backends: usize,
+    pub total_backends: usize,
+    pub healthy_ratio: f64,
+}
+
+/// 状态变更事件
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelHealthTransition {
+    pub model: String,
+    pub previous_state: ModelHealthState,
+    pub state: ModelHealthState,
+    pub healthy_backends: usize,
+    pub total_backends: usize,
+}
+
+/// 带滞回的模型健康状态跟踪器
+/// 状态变差时立即按阈值切换，恢复时健康比例需超过阈值加滞回量，避免在阈值附近反复抖动
+pub struct ModelHealthTracker {
+    degraded_ratio: f64,
+    down_ratio: f64,
+    hysteresis: f64,
+    webhook_urls: Vec<String>,
+    states: std::sync::RwLock<HashMap<String, ModelHealth>>,
+}
+
+impl ModelHealthTracker {
+    pub fn new(settings: &GlobalSettings) -> Self {
+        Self {
+            degraded_ratio: settings.model_degraded_ratio,
+            down_ratio: settings.model_down_ratio,
+            hysteresis: settings.model_health_hysteresis,
+            webhook_urls: settings.health_webhook_urls.clone(),
+            states: std::sync::RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 根据当前状态和健康比例计算下一个状态
+    fn next_state(&self, current: Option<ModelHealthState>, ratio: f64, healthy: usize) -> ModelHealthState {
+        if healthy == 0 || ratio < self.down_ratio {
+            return ModelHealthState::Down;
+        }
+
+        // 从更差的状态恢复时需要额外越过滞回量
+        let degraded_exit = match current {
+            Some(ModelHealthState::Degraded) | Some(ModelHealthState::Down) => {
+                self.degraded_ratio + self.hysteresis
+            }
+            _ => self.degraded_ratio,
+        };
+        let down_exit = match current {
+            Some(ModelHealthState::Down) => self.down_ratio + self.hysteresis,
+            _ => self.down_ratio,
+        };
+
+        if current == Some(ModelHealthState::Down) && ratio < down_exit {
+            ModelHealthState::Down
+        } else if ratio < degraded_exit {
+            ModelHealthState::Degraded
+        } else {
+            ModelHealthState::Healthy
+        }
+    }
+
+    /// 更新模型的健康状态，状态发生变化时返回变更事件
+    pub fn update(
+        &self,
+        model: &str,
+        healthy_backends: usize,
+        total_backends: usize,
+    ) -> Option<ModelHealthTransition> {
+        let ratio = if total_backends > 0 {
+            healthy_backends as f64 / total_backends as f64
+        } else {
+            0.0
+        };
+
+        let mut states = self.states.write().ok()?;
+        let previous = states.get(model).map(|h| h.state);
+        let state = self.next_state(previous, ratio, healthy_backends);
+
+        states.insert(
+            model.to_string(),
+            ModelHealth {
+                state,
+                healthy_backends,
+                total_backends,
+                healthy_ratio: ratio,
+            },
+        );
+
+        // 首次评估视为从健康状态开始
+        let previous_state = previous.unwrap_or(ModelHealthState::Healthy);
+        if previous_state != state {
+            Some(ModelHealthTransition {
+                model: model.to_string(),
+                previous_state,
+                state,
+                healthy_backends,
+                total_backends,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// 根据后端健康状况重新评估所有启用的模型，记录并通知状态变更
+    pub fn evaluate(&self, config: &Config, metrics: &MetricsCollector) -> Vec<ModelHealthTransition> {
+        let mut transitions = Vec::new();
+
+        // 配置热加载后移除已删除或禁用的模型
+        if let Ok(mut states) = self.states.write() {
+            states.retain(|model_id, _| config.models.get(model_id).is_some_and(|m| m.enabled));
+        }
+
+        for (model_id, mapping) in &config.models {
+            if !mapping.enabled {
+                continue;
+            }
+
+            let enabled: Vec<_> = mapping.backends.iter().filter(|b| b.enabled).collect();
+            let healthy = enabled
+                .iter()
+                .filter(|b| metrics.is_healthy(&b.provider, &b.model))
+                .count();
+
+            if let Some(transition) = self.update(model_id, healthy, enabled.len()) {
+                warn!(
+                    "Model '{}' health changed: {} -> {} ({}/{} backends healthy)",
+                    transition.model,
+                    transition.previous_state.as_str(),
+                    transition.state.as_str(),
+                    transition.healthy_backends,
+                    transition.total_backends
+                );
+                transitions.push(transition);
+            }
+        }
+
+        if !transitions.is_empty() {
+            self.notify_webhooks(&transitions);
+        }
+
+        transitions
+    }
+
+    /// 向配置的Webhook异步推送状态变更
+    fn notify_webhooks(&self, transitions: &[ModelHealthTransition]) {
+        if self.webhook_urls.is_empty() {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            debug!("No async runtime available, skipping health webhooks");
+            return;
+        };
+
+        let client = reqwest::Client::new();
+        let timestamp = chrono::Utc::now().to_rfc3339();
+        for transition in transitions {
+            let payload = json!({
+                "event": "model_health_changed",
+                "model": transition.model,
+                "previous_state": transition.previous_state,
+                "state": transition.state,
+                "healthy_backends": transition.healthy_backends,
+                "total_backends": transition.total_backends,
+                "timestamp": timestamp,
+            });
+
+            for url in &self.webhook_urls {
+                let client = client.clone();
+                let url = url.clone();
+                let payload = payload.clone();
+                handle.spawn(async move {
+                    match client
+                        .post(&url)
+                        .timeout(std::time::Duration::from_secs(10))
+                        .json(&payload)
+                        .send()
+                        .await
+                    {
+                        Ok(resp) if resp.status().is_success() => {
+                            debug!("Health webhook delivered to {}", url);
+                        }
+                        Ok(resp) => {
+                            warn!("Health webhook {} returned status {}", url, resp.status());
+                        }
+                        Err(e) => {
+                            warn!("Failed to deliver health webhook to {}: {}", url, e);
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    /// 获取模型当前的健康状态，未评估过的模型视为健康
+    pub fn get_state(&self, model: &str) -> ModelHealthState {
+        self.states
+            .read()
+            .ok()
+            .and_then(|states| states.get(model).map(|h| h.state))
+            .unwrap_or(ModelHealthState::Healthy)
+    }
+
+    /// 获取所有模型的健康快照
+    pub fn get_all(&self) -> HashMap<String, ModelHealth> {
+        self.states.read().map(|s| s.clone()).unwrap_or_default()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> ModelHealthTracker {
+        // 默认阈值：低于0.75降级，低于0.25宕机，滞回0.1
+        ModelHealthTracker::new(&GlobalSettings::default())
+    }
+
+    #[test]
+    fn test_state_thresholds() {
+        let tracker = tracker();
+        assert!(tracker.update("m", 4, 4).is_none());
+        assert_eq!(tracker.get_state("m"), ModelHealthState::Healthy);
+
+        let transition = tracker.update("m", 2, 4).unwrap();
+        assert_eq!(transition.previous_state, ModelHealthState::Healthy);
+        assert_eq!(transition.state, ModelHealthState::Degraded);
+
+        let transition = tracker.update("m", 0, 4).unwrap();
+        assert_eq!(transition.state, ModelHealthState::Down);
+    }
+
+    #[test]
+    fn test_hysteresis_on_recovery() {
+        let tracker = tracker();
+        tracker.update("m", 7, 10);
+        assert_eq!(tracker.get_state("m"), ModelHealthState::Degraded);
+
+        // 刚好达到降级阈值时仍保持降级，需超过阈值加滞回量才恢复
+        assert!(tracker.update("m", 8, 10).is_none());
+        assert_eq!(tracker.get_state("m"), ModelHealthState::Degraded);
+        tracker.update("m", 9, 10);
+        assert_eq!(tracker.get_state("m"), ModelHealthState::Healthy);
+
+        tracker.update("m", 2, 10);
+        assert_eq!(tracker.get_state("m"), ModelHealthState::Down);
+        tracker.update("m", 3, 10);
+        assert_eq!(tracker.get_state("m"), ModelHealthState::Down);
+        tracker.update("m", 4, 10);
+        assert_eq!(tracker.get_state("m"), ModelHealthState::Degraded);
+    }
+}
@@ -0,0 +1,387 @@
+use crate::config::model::{Config, NotificationEvent, NotificationSettings, NotificationWebhook, WebhookFormat};
+use super::{EventBus, MetricsCollector, ServiceEvent};
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+/// Discord消息内容的长度上限
+const DISCORD_MAX_CONTENT: usize = 2000;
+
+/// 后端上一次评估时的状态
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct BackendSnapshot {
+    unhealthy: bool,
+    recovery_stage: Option<usize>,
+}
+
+/// 后端和模型健康状态的变化检测
+/// 每次评估与上一次的快照比较，变化以事件的形式发布到事件总线
+#[derive(Default)]
+pub struct HealthEventWatcher {
+    backends: std::sync::Mutex<HashMap<String, BackendSnapshot>>,
+    down_models: std::sync::Mutex<HashSet<String>>,
+}
+
+impl HealthEventWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 比较当前状态和上一次的快照，返回发生的事件（事件名和详情）
+    pub fn evaluate(&self, config: &Config, metrics: &MetricsCollector) -> Vec<(NotificationEvent, Value)> {
+        let mut events = Vec::new();
+
+        // 同一个后端可能被多个模型引用，只评估一次
+        let mut current = HashMap::new();
+        for backend in config
+            .models
+            .values()
+            .filter(|mapping| mapping.enabled)
+            .flat_map(|mapping| mapping.backends.iter().filter(|b| b.enabled))
+        {
+            let key = format!("{}:{}", backend.provider, backend.model);
+            let snapshot = BackendSnapshot {
+                unhealthy: metrics.is_in_unhealthy_list(&key),
+                recovery_stage: metrics.get_recovery_stage(&backend.provider, &backend.model),
+            };
+            current.insert(key, snapshot);
+        }
+
+        if let Ok(mut previous) = self.backends.lock() {
+            let mut keys: Vec<_> = current.keys().collect();
+            keys.sort();
+            for key in keys {
+                let now = current[key];
+                let before = previous.get(key).copied().unwrap_or_default();
+                if now.unhealthy != before.unhealthy {
+                    let event = if now.unhealthy {
+                        NotificationEvent::BackendUnhealthy
+                    } else {
+                        NotificationEvent::BackendRecovered
+                    };
+                    events.push((event, json!({ "backend": key })));
+                }
+                if now.recovery_stage != before.recovery_stage {
+                    events.push((
+                        NotificationEvent::RecoveryStageChanged,
+                        json!({
+                            "backend": key,
+                            "previous_stage": before.recovery_stage,
+                            "stage": now.recovery_stage,
+                        }),
+                    ));
+                }
+            }
+            // 配置热加载后移除已删除的后端
+            *previous = current;
+        }
+
+        if let Ok(mut down_models) = self.down_models.lock() {
+            let mut models: Vec<_> = config.models.iter().filter(|(_, mapping)| mapping.enabled).collect();
+            models.sort_by_key(|(id, _)| id.as_str());
+            let mut still_configured = HashSet::new();
+            for (model_id, mapping) in models {
+                let enabled: Vec<_> = mapping.backends.iter().filter(|b| b.enabled).collect();
+                if enabled.is_empty() {
+                    continue;
+                }
+                still_configured.insert(model_id.clone());
+                let down = enabled.iter().all(|b| !metrics.is_healthy(&b.provider, &b.model));
+                let was_down = down_models.contains(model_id);
+                if down && !was_down {
+                    down_models.insert(model_id.clone());
+                    events.push((
+                        NotificationEvent::ModelDown,
+                        json!({ "model": model_id, "total_backends": enabled.len() }),
+                    ));
+                } else if !down && was_down {
+                    down_models.remove(model_id);
+                    let healthy = enabled.iter().filter(|b| metrics.is_healthy(&b.provider, &b.model)).count();
+                    events.push((
+                        NotificationEvent::ModelRestored,
+                        json!({ "model": model_id, "healthy_backends": healthy, "total_backends": enabled.len() }),
+                    ));
+                }
+            }
+            down_models.retain(|model_id| still_configured.contains(model_id));
+        }
+
+        events
+    }
+
+    /// 评估健康状态变化并发布到事件总线
+    pub fn publish(&self, config: &Config, metrics: &MetricsCollector, events: &EventBus) {
+        for (event, details) in self.evaluate(config, metrics) {
+            events.publish(event.as_str(), details);
+        }
+    }
+}
+
+/// 待发送的通知
+#[derive(Debug, Clone)]
+struct Notification {
+    event: NotificationEvent,
+    timestamp: String,
+    details: Value,
+}
+
+impl Notification {
+    fn from_service_event(event: &ServiceEvent) -> Option<Self> {
+        let kind = [
+            NotificationEvent::BackendUnhealthy,
+            NotificationEvent::BackendRecovered,
+            NotificationEvent::RecoveryStageChanged,
+            NotificationEvent::ModelDown,
+            NotificationEvent::ModelRestored,
+        ]
+        .into_iter()
+        .find(|kind| kind.as_str() == event.event)?;
+        Some(Self {
+            event: kind,
+            timestamp: event.timestamp.clone(),
+            details: event.details.clone(),
+        })
+    }
+
+    /// 事件涉及的对象（后端或模型），用于合并同一对象的事件
+    fn subject(&self) -> String {
+        let field = if self.details.get("backend").is_some() { "backend" } else { "model" };
+        self.details[field].as_str().unwrap_or_default().to_string()
+    }
+
+    /// 同类事件的分组：健康状态的进入和离开属于同一组
+    fn group(&self) -> &'static str {
+        match self.event {
+            NotificationEvent::BackendUnhealthy | NotificationEvent::BackendRecovered => "backend_health",
+            NotificationEvent::RecoveryStageChanged => "recovery_stage",
+            NotificationEvent::ModelDown | NotificationEvent::ModelRestored => "model_health",
+        }
+    }
+
+    fn describe(&self) -> String {
+        let subject = self.subject();
+        match self.event {
+            NotificationEvent::BackendUnhealthy => format!("🔴 Backend {} is unhealthy", subject),
+            NotificationEvent::BackendRecovered => format!("🟢 Backend {} recovered", subject),
+            NotificationEvent::RecoveryStageChanged => match self.details["stage"].as_u64() {
+                Some(stage) => format!("🟡 Backend {} entered recovery stage {}", subject, stage + 1),
+                None => format!("🟢 Backend {} finished recovery", subject),
+            },
+            NotificationEvent::ModelDown => format!("🚨 All backends of model {} are down", subject),
+            NotificationEvent::ModelRestored => format!("✅ Model {} has healthy backends again", subject),
+        }
+    }
+}
+
+/// 合并去抖窗口内的事件：同一对象同一组的事件只保留最后一条；
+/// 健康状态在窗口内来回切换（进入和离开次数相同）时视为抖动，整组丢弃
+fn coalesce(pending: Vec<Notification>) -> Vec<Notification> {
+    let mut groups: Vec<((&'static str, String), Vec<Notification>)> = Vec::new();
+    for notification in pending {
+        let key = (notification.group(), notification.subject());
+        match groups.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, items)) => items.push(notification),
+            None => groups.push((key, vec![notification])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter_map(|((group, _), mut items)| {
+            if group != "recovery_stage" && items.len() % 2 == 0 {
+                debug!("Dropping {} flapping {} notifications", items.len(), group);
+                return None;
+            }
+            let count = items.len();
+            let mut last = items.pop()?;
+            if count > 1
+                && let Some(details) = last.details.as_object_mut()
+            {
+                details.insert("occurrences".to_string(), json!(count));
+            }
+            Some(last)
+        })
+        .collect()
+}
+
+/// 按Webhook格式生成消息体
+fn render(format: WebhookFormat, notifications: &[&Notification]) -> Value {
+    match format {
+        WebhookFormat::Json => json!({
+            "source": "berry-api",
+            "events": notifications
+                .iter()
+                .map(|n| json!({
+                    "event": n.event,
+                    "timestamp": n.timestamp,
+                    "message": n.describe(),
+                    "details": n.details,
+                }))
+                .collect::<Vec<_>>(),
+        }),
+        WebhookFormat::Slack => {
+            let text = notifications.iter().map(|n| n.describe()).collect::<Vec<_>>().join("\n");
+            json!({ "text": format!("*Berry API health update*\n{}", text) })
+        }
+        WebhookFormat::Discord => {
+            let mut content = String::from("**Berry API health update**");
+            for notification in notifications {
+                let line = notification.describe();
+                if content.len() + line.len() + 1 > DISCORD_MAX_CONTENT {
+                    content.push_str("\n…");
+                    break;
+                }
+                content.push('\n');
+                content.push_str(&line);
+            }
+            json!({ "content": content })
+        }
+    }
+}
+
+/// 健康状态变化通知器：订阅事件总线，去抖合并后推送到配置的Webhook
+pub struct HealthNotifier;
+
+impl HealthNotifier {
+    /// 启动后台任务，每批事件发送时读取最新配置，以便热加载生效
+    pub fn spawn<F>(events: &EventBus, settings: F)
+    where
+        F: Fn() -> Option<NotificationSettings> + Send + Sync + 'static,
+    {
+        let mut receiver = events.subscribe();
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            loop {
+                let first = match Self::next_notification(&mut receiver).await {
+                    Some(notification) => notification,
+                    None => return,
+                };
+                let Some(current) = settings() else {
+                    continue;
+                };
+
+                let mut pending = vec![first];
+                if current.debounce_seconds > 0 {
+                    let deadline = tokio::time::Instant::now() + Duration::from_secs(current.debounce_seconds);
+                    while let Ok(Some(notification)) =
+                        tokio::time::timeout_at(deadline, Self::next_notification(&mut receiver)).await
+                    {
+                        pending.push(notification);
+                    }
+                } else {
+                    // 同一次评估产生的事件一起发送
+                    while let Ok(event) = receiver.try_recv() {
+                        pending.extend(Notification::from_service_event(&event));
+                    }
+                }
+
+                let notifications = coalesce(pending);
+                if notifications.is_empty() {
+                    continue;
+                }
+                let current = settings().unwrap_or(current);
+                for webhook in &current.webhooks {
+                    Self::deliver(&client, webhook, &notifications);
+                }
+            }
+        });
+    }
+
+    /// 等待下一个需要通知的事件，事件总线关闭时返回None
+    async fn next_notification(receiver: &mut broadcast::Receiver<ServiceEvent>) -> Option<Notification> {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    if let Some(notification) = Notification::from_service_event(&event) {
+                        return Some(notification);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Health notifier lagged behind, {} events skipped", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    fn deliver(client: &reqwest::Client, webhook: &NotificationWebhook, notifications: &[Notification]) {
+        let selected: Vec<&Notification> = notifications
+            .iter()
+            .filter(|n| webhook.events.is_empty() || webhook.events.contains(&n.event))
+            .collect();
+        if selected.is_empty() {
+            return;
+        }
+
+        let payload = render(webhook.format, &selected);
+        let request = client
+            .post(&webhook.url)
+            .timeout(Duration::from_secs(webhook.timeout_seconds))
+            .json(&payload);
+        let url = webhook.url.clone();
+        tokio::spawn(async move {
+            match request.send().await {
+                Ok(resp) if resp.status().is_success() => debug!("Health notification delivered to {}", url),
+                Ok(resp) => warn!("Health notification webhook {} returned status {}", url, resp.status()),
+                Err(e) => warn!("Failed to deliver health notification to {}: {}", url, e),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(event: NotificationEvent, details: Value) -> Notification {
+        Notification {
+            event,
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            details,
+        }
+    }
+
+    #[test]
+    fn test_coalesce_drops_flapping_and_keeps_latest() {
+        let a = json!({ "backend": "openai:gpt-4" });
+        let b = json!({ "backend": "azure:gpt-4" });
+        let pending = vec![
+            notification(NotificationEvent::BackendUnhealthy, a.clone()),
+            notification(NotificationEvent::BackendUnhealthy, b.clone()),
+            notification(NotificationEvent::BackendRecovered, a.clone()),
+            notification(NotificationEvent::RecoveryStageChanged, json!({ "backend": "azure:gpt-4", "stage": 0 })),
+            notification(NotificationEvent::RecoveryStageChanged, json!({ "backend": "azure:gpt-4", "stage": 1 })),
+        ];
+
+        let result = coalesce(pending);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].event, NotificationEvent::BackendUnhealthy);
+        assert_eq!(result[0].subject(), "azure:gpt-4");
+        assert_eq!(result[1].details["stage"], 1);
+        assert_eq!(result[1].details["occurrences"], 2);
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let down = notification(NotificationEvent::ModelDown, json!({ "model": "gpt-4", "total_backends": 2 }));
+        let stage = notification(NotificationEvent::RecoveryStageChanged, json!({ "backend": "openai:gpt-4", "stage": null }));
+        let selected = vec![&down, &stage];
+
+        let generic = render(WebhookFormat::Json, &selected);
+        assert_eq!(generic["events"][0]["event"], "model_down");
+        assert_eq!(generic["events"][0]["details"]["total_backends"], 2);
+
+        let slack = render(WebhookFormat::Slack, &selected);
+        let text = slack["text"].as_str().unwrap();
+        assert!(text.contains("All backends of model gpt-4 are down"));
+        assert!(text.contains("openai:gpt-4 finished recovery"));
+
+        let many: Vec<Notification> = (0..200)
+            .map(|i| notification(NotificationEvent::BackendUnhealthy, json!({ "backend": format!("provider-{}:model", i) })))
+            .collect();
+        let discord = render(WebhookFormat::Discord, &many.iter().collect::<Vec<_>>());
+        assert!(discord["content"].as_str().unwrap().chars().count() <= DISCORD_MAX_CONTENT);
+    }
+}
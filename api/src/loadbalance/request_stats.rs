@@ -0,0 +1,213 @@
+use super::clock::{Clock, SystemClock};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+/// 按秒分桶保留的最长统计窗口（15分钟）
+const RETENTION_SECS: u64 = 15 * 60;
+
+/// 单个统计窗口内的请求速率
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WindowRate {
+    pub requests: u64,
+    pub failed: u64,
+    /// 窗口内平均每秒请求数
+    pub requests_per_second: f64,
+    /// 窗口内成功率，无请求时为0
+    pub success_rate: f64,
+}
+
+/// 1分钟、5分钟和15分钟窗口的请求速率
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RequestRates {
+    #[serde(rename = "1m")]
+    pub one_minute: WindowRate,
+    #[serde(rename = "5m")]
+    pub five_minutes: WindowRate,
+    #[serde(rename = "15m")]
+    pub fifteen_minutes: WindowRate,
+}
+
+/// 请求计数快照
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RequestCounts {
+    pub total_requests: u64,
+    pub successful_requests: u64,
+    pub failed_requests: u64,
+    pub rates: RequestRates,
+}
+
+/// 全局和按模型的请求计数快照
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RequestStatsReport {
+    pub global: RequestCounts,
+    pub models: HashMap<String, RequestCounts>,
+}
+
+/// 累计请求计数和按秒分桶的近期请求（秒, 请求数, 失败数）
+#[derive(Debug, Default)]
+struct RequestCounter {
+    total: u64,
+    successful: u64,
+    failed: u64,
+    buckets: VecDeque<(u64, u64, u64)>,
+}
+
+impl RequestCounter {
+    fn record(&mut self, second: u64, success: bool) {
+        self.total += 1;
+        if success {
+            self.successful += 1;
+        } else {
+            self.failed += 1;
+        }
+
+        match self.buckets.back_mut() {
+            Some((last, requests, failed)) if *last == second => {
+                *requests += 1;
+                *failed += u64::from(!success);
+            }
+            _ => self.buckets.push_back((second, 1, u64::from(!success))),
+        }
+        while self
+            .buckets
+            .front()
+            .is_some_and(|(s, _, _)| second.saturating_sub(*s) >= RETENTION_SECS)
+        {
+            self.buckets.pop_front();
+        }
+    }
+
+    fn window(&self, now: u64, window_secs: u64) -> WindowRate {
+        let (requests, failed) = self
+            .buckets
+            .iter()
+            .filter(|(s, _, _)| now.saturating_sub(*s) < window_secs)
+            .fold((0, 0), |(r, f), (_, requests, failed)| (r + requests, f + failed));
+        WindowRate {
+            requests,
+            failed,
+            requests_per_second: requests as f64 / window_secs as f64,
+            success_rate: if requests > 0 {
+                (requests - failed) as f64 / requests as f64
+            } else {
+                0.0
+            },
+        }
+    }
+
+    fn snapshot(&self, now: u64) -> RequestCounts {
+        RequestCounts {
+            total_requests: self.total,
+            successful_requests: self.successful,
+            failed_requests: self.failed,
+            rates: RequestRates {
+                one_minute: self.window(now, 60),
+                five_minutes: self.window(now, 5 * 60),
+                fifteen_minutes: self.window(now, RETENTION_SECS),
+            },
+        }
+    }
+}
+
+/// 客户端请求计数：全局和按模型统计成功、失败次数及近期速率，仅保存在内存中
+#[derive(Debug)]
+pub struct RequestStats {
+    started: Instant,
+    global: RequestCounter,
+    models: HashMap<String, RequestCounter>,
+}
+
+impl RequestStats {
+    pub fn new() -> Self {
+        Self::with_clock(&SystemClock)
+    }
+
+    /// 以指定时钟的当前时间作为分桶起点，与MetricsCollector共用同一个时钟
+    pub fn with_clock(clock: &dyn Clock) -> Self {
+        Self {
+            started: clock.now(),
+            global: RequestCounter::default(),
+            models: HashMap::new(),
+        }
+    }
+
+    fn second(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.started).as_secs()
+    }
+
+    /// 记录一次请求的最终结果，未知模型只计入全局计数
+    pub fn record(&mut self, model_id: Option<&str>, success: bool, now: Instant) {
+        let second = self.second(now);
+        self.global.record(second, success);
+        if let Some(model_id) = model_id {
+            self.models
+                .entry(model_id.to_string())
+                .or_default()
+                .record(second, success);
+        }
+    }
+
+    /// 获取当前的计数快照
+    pub fn report(&self, now: Instant) -> RequestStatsReport {
+        let second = self.second(now);
+        RequestStatsReport {
+            global: self.global.snapshot(second),
+            models: self
+                .models
+                .iter()
+                .map(|(model, counter)| (model.clone(), counter.snapshot(second)))
+                .collect(),
+        }
+    }
+}
+
+impl Default for RequestStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_counts_per_model_and_globally() {
+        let mut stats = RequestStats::new();
+        let now = stats.started;
+        stats.record(Some("gpt-4"), true, now);
+        stats.record(Some("gpt-4"), false, now);
+        stats.record(None, true, now);
+
+        let report = stats.report(now);
+        assert_eq!(report.global.total_requests, 3);
+        assert_eq!(report.global.successful_requests, 2);
+        assert_eq!(report.global.failed_requests, 1);
+        assert_eq!(report.models["gpt-4"].failed_requests, 1);
+        assert_eq!(report.models.len(), 1);
+        assert_eq!(report.models["gpt-4"].rates.one_minute.success_rate, 0.5);
+        assert_eq!(report.global.rates.one_minute.requests_per_second, 3.0 / 60.0);
+    }
+
+    #[test]
+    fn test_windows_expire_old_requests() {
+        let mut stats = RequestStats::new();
+        let start = stats.started;
+        stats.record(Some("gpt-4"), false, start);
+        stats.record(Some("gpt-4"), true, start + Duration::from_secs(4 * 60));
+
+        let report = stats.report(start + Duration::from_secs(4 * 60 + 30));
+        let rates = &report.global.rates;
+        assert_eq!(rates.one_minute.requests, 1);
+        assert_eq!(rates.one_minute.failed, 0);
+        assert_eq!(rates.five_minutes.requests, 2);
+        assert_eq!(rates.fifteen_minutes.failed, 1);
+
+        // 超过15分钟后只保留累计计数
+        let later = stats.report(start + Duration::from_secs(30 * 60));
+        assert_eq!(later.global.total_requests, 2);
+        assert_eq!(later.global.rates.fifteen_minutes, WindowRate::default());
+    }
+}
@@ -1,12 +1,31 @@
-use crate::config::model::{Backend, LoadBalanceStrategy, ModelMapping};
+use crate::config::model::{Backend, HealthScoringSettings, LoadBalanceStrategy, ModelMapping, OutlierDetectionSettings, ProviderQuota, RegionRoutingSettings, SloSettings, SlowStartStage};
+use super::health_score::{HealthScore, HealthScoreTracker, RequestOutcome};
+use super::key_budget::{KeyBudgetAlert, KeySpend, KeySpendTracker, ProviderKey};
+use super::request_stats::{RequestStats, RequestStatsReport};
+use super::clock::{Clock, SystemClock};
+use super::slo::{SloObjectiveReport, SloTracker, SloTransition};
+use super::TokenUsage;
 use anyhow::Result;
-use rand::Rng;
+use rand::{Rng, RngCore, SeedableRng};
 use rand::distr::Distribution;
 use rand::distr::weighted::WeightedIndex;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
+use serde::Serialize;
+use serde_json::Value;
+
+/// 默认的延迟EWMA平滑系数
+const DEFAULT_LATENCY_EWMA_ALPHA: f64 = 0.3;
+
+/// 计算尾延迟时每个后端保留的最近延迟样本数
+const TAIL_LATENCY_SAMPLES: usize = 100;
+
+/// provider配额用量的统计窗口
+const QUOTA_WINDOW: Duration = Duration::from_secs(60);
 
 /// 后端选择错误类型
 #[derive(Debug, Clone)]
@@ -39,15 +58,157 @@ impl std::fmt::Display for BackendSelectionError {
 
 impl std::error::Error for BackendSelectionError {}
 
+/// 后端选择上下文，携带来自客户端请求的选择提示
+#[derive(Debug, Clone, Default)]
+pub struct SelectionContext {
+    /// 会话标识（来自请求头或请求体的`user`字段），用于会话粘性策略
+    pub session_key: Option<String>,
+    /// 对话指纹（系统提示词和首条用户消息），用于一致性哈希策略
+    pub conversation_fingerprint: Option<String>,
+    /// 客户端标识（客户端API密钥），用于故障转移记忆
+    pub client_key: Option<String>,
+    /// 请求要求的后端标签（来自x-berry-tags请求头），只有包含全部标签的后端参与选择
+    pub tags: Vec<String>,
+    /// 覆盖模型映射的负载均衡策略（来自x-berry-strategy请求头，仅管理员可用）
+    pub strategy_override: Option<LoadBalanceStrategy>,
+    /// 绕过后端选择直接使用的后端（provider:model，来自x-berry-backend请求头，仅管理员可用）
+    pub pinned_backend: Option<String>,
+    /// 客户端所在的区域（来自x-berry-region请求头或用户配置），优先选择同区域的后端
+    pub region: Option<String>,
+    /// 估算的请求上下文长度（token），只选择max_context足够的后端
+    pub context_tokens: Option<u64>,
+    /// 请求需要后端支持的能力，只选择具备全部能力的后端
+    pub capabilities: RequiredCapabilities,
+}
+
+/// 从请求内容推断出的后端能力要求
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequiredCapabilities {
+    /// 消息中包含图片
+    pub vision: bool,
+    /// 请求定义了工具
+    pub tools: bool,
+    /// 请求指定了JSON格式的response_format
+    pub json_mode: bool,
+    /// 流式请求
+    pub streaming: bool,
+}
+
+impl RequiredCapabilities {
+    /// 检查请求中的图片、tools（或旧版functions）、response_format和stream参数
+    pub fn from_request(body: &Value) -> Self {
+        let non_empty = |field: &str| {
+            body.get(field)
+                .and_then(Value::as_array)
+                .is_some_and(|items| !items.is_empty())
+        };
+        let vision = body
+            .get("messages")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|message| message.get("content").and_then(Value::as_array))
+            .flatten()
+            .any(|part| part.get("type").and_then(Value::as_str) == Some("image_url"));
+        let json_mode = matches!(
+            body.pointer("/response_format/type").and_then(Value::as_str),
+            Some("json_object" | "json_schema")
+        );
+        Self {
+            vision,
+            tools: non_empty("tools") || non_empty("functions"),
+            json_mode,
+            streaming: body.get("stream").and_then(Value::as_bool).unwrap_or(false),
+        }
+    }
+
+    /// 后端是否具备全部所需能力
+    pub fn supported_by(&self, backend: &Backend) -> bool {
+        (!self.vision || backend.supports_vision)
+            && (!self.tools || backend.supports_tools)
+            && (!self.json_mode || backend.supports_json_mode)
+            && (!self.streaming || backend.supports_streaming)
+    }
+
+    /// 只要求单项能力，name为names()返回的名称
+    fn only(name: &str) -> Self {
+        Self {
+            vision: name == "vision",
+            tools: name == "tools",
+            json_mode: name == "json_mode",
+            streaming: name == "streaming",
+        }
+    }
+
+    /// 所需能力的名称，用于错误信息
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.vision, "vision"),
+            (self.tools, "tools"),
+            (self.json_mode, "json_mode"),
+            (self.streaming, "streaming"),
+        ]
+        .into_iter()
+        .filter_map(|(required, name)| required.then_some(name))
+        .collect()
+    }
+}
+
+impl SelectionContext {
+    /// 后端是否包含请求要求的全部标签
+    pub fn matches_tags(&self, backend: &Backend) -> bool {
+        self.tags.iter().all(|tag| backend.tags.contains(tag))
+    }
+
+    /// 后端的上下文长度是否足够处理该请求
+    pub fn fits_context(&self, backend: &Backend) -> bool {
+        self.context_tokens.is_none_or(|tokens| backend.fits_context(tokens))
+    }
+
+    /// 后端是否具备请求所需的全部能力
+    pub fn supports_capabilities(&self, backend: &Backend) -> bool {
+        self.capabilities.supported_by(backend)
+    }
+}
+
+/// 单个后端在一次选择中的状态，用于调试时解释选择结果
+#[derive(Debug, Clone, Serialize)]
+pub struct CandidateExplanation {
+    /// provider:model
+    pub backend: String,
+    /// 计入慢启动和健康评分后的权重
+    pub effective_weight: f64,
+    pub healthy: bool,
+    /// 未参与选择的原因，参与选择时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub excluded: Option<String>,
+}
+
+/// 计算字符串的稳定哈希值
+fn hash_key(key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
 pub struct BackendSelector {
     mapping: ModelMapping,
     round_robin_counter: AtomicUsize,
     metrics: Arc<MetricsCollector>,
+    // 一致性哈希环：(虚拟节点哈希, 后端在mapping.backends中的索引)，按哈希升序排列
+    hash_ring: Vec<(u64, usize)>,
+    // 固定种子的随机数生成器，为空时使用线程本地的随机数生成器
+    rng: Option<std::sync::Mutex<rand::rngs::StdRng>>,
 }
 
 /// 指标收集器，用于收集后端性能数据
 pub struct MetricsCollector {
+    // 各后端延迟的指数加权移动平均值
     latencies: Arc<std::sync::RwLock<HashMap<String, Duration>>>,
+    // 延迟EWMA平滑系数（f64的位表示）
+    latency_alpha: AtomicU64,
+    // 各后端最近的延迟样本，用于计算尾延迟
+    latency_samples: std::sync::RwLock<HashMap<String, VecDeque<Duration>>>,
     health_status: Arc<std::sync::RwLock<HashMap<String, bool>>>,
     failure_counts: Arc<std::sync::RwLock<HashMap<String, u32>>>,
     last_health_check: Arc<std::sync::RwLock<HashMap<String, Instant>>>,
@@ -56,6 +217,80 @@ pub struct MetricsCollector {
     recovery_attempts: Arc<std::sync::RwLock<HashMap<String, u32>>>,
     // 新增：权重恢复状态管理
     weight_recovery_states: Arc<std::sync::RwLock<HashMap<String, WeightRecoveryState>>>,
+    // 慢启动阶段配置
+    slow_start: std::sync::RwLock<SlowStartConfig>,
+    // 异常后端剔除配置
+    outlier_detection: std::sync::RwLock<Option<OutlierDetectionSettings>>,
+    // 各后端统计窗口内的请求结果（时间, 是否成功）
+    request_outcomes: std::sync::RwLock<HashMap<String, VecDeque<(Instant, bool)>>>,
+    // 因错误率过高被剔除的后端及剔除截止时间
+    ejected_until: std::sync::RwLock<HashMap<String, Instant>>,
+    // TLS证书固定校验失败次数（按provider统计）
+    tls_pin_failures: Arc<std::sync::RwLock<HashMap<String, u64>>>,
+    // 各后端按阶段（connect、first_token、idle、total）统计的上游超时次数
+    upstream_timeouts: std::sync::RwLock<HashMap<String, HashMap<&'static str, u64>>>,
+    // 各后端当前在途请求数
+    in_flight: Arc<std::sync::RwLock<HashMap<String, usize>>>,
+    // 通过管理接口临时禁用的后端
+    admin_disabled: Arc<std::sync::RwLock<std::collections::HashSet<String>>>,
+    // 各后端累计处理的请求数
+    request_totals: Arc<std::sync::RwLock<HashMap<String, u64>>>,
+    // 上游返回429后按Retry-After冷却的后端及冷却截止时间
+    cooldown_until: std::sync::RwLock<HashMap<String, Instant>>,
+    // 各provider配置的上游配额
+    provider_quotas: std::sync::RwLock<HashMap<String, ProviderQuota>>,
+    // 配置了配额的provider在统计窗口内的用量
+    provider_usage: std::sync::Mutex<HashMap<String, ProviderUsage>>,
+    // 客户端请求的全局和按模型计数
+    request_stats: std::sync::Mutex<RequestStats>,
+    // 各provider上游密钥的月度用量
+    key_spend: KeySpendTracker,
+    // 后端综合健康评分
+    health_scores: HealthScoreTracker,
+    // 服务自身的SLO跟踪
+    slo: SloTracker,
+    // 跨区域故障转移的延迟惩罚
+    region_routing: std::sync::RwLock<RegionRoutingSettings>,
+    // 判断冷却、剔除、慢启动等是否到期使用的时钟
+    clock: Arc<dyn Clock>,
+}
+
+/// provider在统计窗口内的请求时间和token用量
+#[derive(Debug, Default)]
+struct ProviderUsage {
+    requests: VecDeque<Instant>,
+    tokens: VecDeque<(Instant, u64)>,
+}
+
+impl ProviderUsage {
+    fn prune(&mut self, now: Instant) {
+        while self.requests.front().is_some_and(|t| now.duration_since(*t) > QUOTA_WINDOW) {
+            self.requests.pop_front();
+        }
+        while self.tokens.front().is_some_and(|(t, _)| now.duration_since(*t) > QUOTA_WINDOW) {
+            self.tokens.pop_front();
+        }
+    }
+}
+
+/// 在途请求守卫，析构时自动减少对应后端的在途请求计数
+pub struct InFlightGuard {
+    metrics: Arc<MetricsCollector>,
+    backend_key: String,
+}
+
+impl std::fmt::Debug for InFlightGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InFlightGuard")
+            .field("backend_key", &self.backend_key)
+            .finish()
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.metrics.finish_request(&self.backend_key);
+    }
 }
 
 /// 不健康后端信息
@@ -69,53 +304,236 @@ pub struct UnhealthyBackend {
     pub recovery_attempts: u32,
 }
 
-/// 权重恢复状态
+/// 慢启动阶段配置：全局阶段和按后端覆盖的阶段
+#[derive(Debug)]
+struct SlowStartConfig {
+    stages: Vec<SlowStartStage>,
+    overrides: HashMap<String, Vec<SlowStartStage>>,
+}
+
+impl Default for SlowStartConfig {
+    fn default() -> Self {
+        Self {
+            stages: SlowStartStage::defaults(),
+            overrides: HashMap::new(),
+        }
+    }
+}
+
+impl SlowStartConfig {
+    fn stages_for(&self, backend_key: &str) -> &[SlowStartStage] {
+        self.overrides.get(backend_key).unwrap_or(&self.stages)
+    }
+}
+
+/// 权重恢复（慢启动）状态
 #[derive(Debug, Clone)]
 pub struct WeightRecoveryState {
     pub backend_key: String,
-    pub original_weight: f64,
-    pub current_weight: f64,
-    pub recovery_stage: RecoveryStage,
-    pub last_success_time: Instant,
-    pub success_count: u32,
-}
-
-/// 恢复阶段
-#[derive(Debug, Clone, PartialEq)]
-pub enum RecoveryStage {
-    /// 不健康状态，使用10%权重
-    Unhealthy,
-    /// 恢复中第一阶段，使用30%权重
-    RecoveryStage1,
-    /// 恢复中第二阶段，使用50%权重
-    RecoveryStage2,
-    /// 完全恢复，使用100%权重
-    FullyRecovered,
+    /// 当前所处的慢启动阶段（从0开始）
+    pub stage: usize,
+    /// 进入当前阶段的时间
+    pub stage_started: Instant,
+    /// 当前阶段内的成功次数
+    pub stage_successes: u32,
+    pub last_success_time: Option<Instant>,
 }
 
 impl MetricsCollector {
     pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// 使用指定时钟创建，测试中传入ManualClock即可推进冷却、剔除、慢启动等时间相关的状态而无需等待
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
         Self {
             latencies: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            latency_alpha: AtomicU64::new(DEFAULT_LATENCY_EWMA_ALPHA.to_bits()),
+            latency_samples: std::sync::RwLock::new(HashMap::new()),
             health_status: Arc::new(std::sync::RwLock::new(HashMap::new())),
             failure_counts: Arc::new(std::sync::RwLock::new(HashMap::new())),
             last_health_check: Arc::new(std::sync::RwLock::new(HashMap::new())),
             unhealthy_backends: Arc::new(std::sync::RwLock::new(HashMap::new())),
             recovery_attempts: Arc::new(std::sync::RwLock::new(HashMap::new())),
             weight_recovery_states: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            slow_start: std::sync::RwLock::new(SlowStartConfig::default()),
+            outlier_detection: std::sync::RwLock::new(None),
+            request_outcomes: std::sync::RwLock::new(HashMap::new()),
+            ejected_until: std::sync::RwLock::new(HashMap::new()),
+            tls_pin_failures: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            upstream_timeouts: std::sync::RwLock::new(HashMap::new()),
+            in_flight: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            admin_disabled: Arc::new(std::sync::RwLock::new(std::collections::HashSet::new())),
+            request_totals: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            cooldown_until: std::sync::RwLock::new(HashMap::new()),
+            provider_quotas: std::sync::RwLock::new(HashMap::new()),
+            provider_usage: std::sync::Mutex::new(HashMap::new()),
+            request_stats: std::sync::Mutex::new(RequestStats::with_clock(clock.as_ref())),
+            key_spend: KeySpendTracker::new(),
+            health_scores: HealthScoreTracker::with_clock(clock.clone()),
+            slo: SloTracker::with_clock(clock.clone()),
+            region_routing: std::sync::RwLock::new(RegionRoutingSettings::default()),
+            clock,
+        }
+    }
+
+    /// 当前时间，来自创建时指定的时钟
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// 设置延迟EWMA平滑系数，取值范围(0, 1]，由配置校验保证
+    pub fn set_latency_alpha(&self, alpha: f64) {
+        self.latency_alpha.store(alpha.to_bits(), Ordering::Relaxed);
+    }
+
+    /// 设置慢启动阶段，为空时后端恢复后直接使用完整权重
+    pub fn set_slow_start_stages(&self, stages: Vec<SlowStartStage>) {
+        if let Ok(mut current) = self.slow_start.write() {
+            current.stages = stages;
+        }
+    }
+
+    /// 设置按后端（provider:model）覆盖的慢启动阶段
+    pub fn set_slow_start_overrides(&self, overrides: HashMap<String, Vec<SlowStartStage>>) {
+        if let Ok(mut current) = self.slow_start.write() {
+            current.overrides = overrides;
         }
     }
 
-    /// 记录请求延迟
+    /// 设置异常后端剔除配置，为None时不启用
+    pub fn set_outlier_detection(&self, settings: Option<OutlierDetectionSettings>) {
+        if settings.is_none() {
+            if let Ok(mut outcomes) = self.request_outcomes.write() {
+                outcomes.clear();
+            }
+            if let Ok(mut ejected) = self.ejected_until.write() {
+                ejected.clear();
+            }
+        }
+        if let Ok(mut current) = self.outlier_detection.write() {
+            *current = settings;
+        }
+    }
+
+    /// 设置综合健康评分配置，为空时后端只按健康/不健康区分
+    pub fn set_health_scoring(&self, settings: Option<HealthScoringSettings>) {
+        self.health_scores.set_settings(settings);
+    }
+
+    /// 设置SLO目标，为空时不跟踪
+    pub fn set_slo(&self, settings: Option<SloSettings>) {
+        self.slo.set_settings(settings);
+    }
+
+    /// 设置区域路由配置，为空时区域之间没有延迟惩罚
+    pub fn set_region_routing(&self, settings: Option<RegionRoutingSettings>) {
+        if let Ok(mut current) = self.region_routing.write() {
+            *current = settings.unwrap_or_default();
+        }
+    }
+
+    /// 客户端区域到后端区域的延迟惩罚
+    pub fn region_penalty(&self, from: &str, to: Option<&str>) -> Duration {
+        self.region_routing
+            .read()
+            .map(|routing| routing.penalty(from, to))
+            .unwrap_or_default()
+    }
+
+    /// 记录一次实际请求的结果，失败时检查窗口内错误率，超过阈值则剔除后端
+    /// 失败本身（标记不健康）由调用方先通过record_failure记录；返回本次是否触发了剔除
+    pub fn record_request_outcome(&self, backend_key: &str, success: bool) -> bool {
+        self.health_scores.record_outcome_at(
+            backend_key,
+            if success { RequestOutcome::Success } else { RequestOutcome::Failure },
+            self.now(),
+        );
+        let Some(settings) = self.outlier_detection.read().ok().and_then(|s| s.clone()) else {
+            return false;
+        };
+
+        let now = self.now();
+        let window = Duration::from_secs(settings.window_seconds);
+        let (total, failures) = {
+            let Ok(mut outcomes) = self.request_outcomes.write() else {
+                return false;
+            };
+            let history = outcomes.entry(backend_key.to_string()).or_default();
+            history.push_back((now, success));
+            while let Some((time, _)) = history.front()
+                && now.duration_since(*time) > window
+            {
+                history.pop_front();
+            }
+            let failures = history.iter().filter(|(_, ok)| !ok).count();
+            (history.len(), failures)
+        };
+
+        if success || total < settings.min_requests as usize {
+            return false;
+        }
+        let error_rate = failures as f64 * 100.0 / total as f64;
+        if error_rate < settings.error_rate_percent {
+            return false;
+        }
+
+        tracing::warn!(
+            "Ejecting backend {} as outlier: error rate {:.1}% over last {} requests",
+            backend_key,
+            error_rate,
+            total
+        );
+        if let Ok(mut ejected) = self.ejected_until.write() {
+            ejected.insert(
+                backend_key.to_string(),
+                now + Duration::from_secs(settings.ejection_seconds),
+            );
+        }
+        // 剔除后重新统计，避免恢复后被旧数据再次剔除
+        if let Ok(mut outcomes) = self.request_outcomes.write() {
+            outcomes.remove(backend_key);
+        }
+        true
+    }
+
+    /// 后端是否处于错误率剔除期内
+    pub fn is_ejected(&self, backend_key: &str) -> bool {
+        let Ok(ejected) = self.ejected_until.read() else {
+            return false;
+        };
+        match ejected.get(backend_key) {
+            Some(until) => self.now() < *until,
+            None => false,
+        }
+    }
+
+    /// 记录请求延迟，按指数加权移动平均平滑，避免单次慢请求长期影响后端排序
     pub fn record_latency(&self, backend_key: &str, latency: Duration) {
+        self.health_scores.record_latency(backend_key, latency);
+        let alpha = f64::from_bits(self.latency_alpha.load(Ordering::Relaxed));
         if let Ok(mut latencies) = self.latencies.write() {
-            latencies.insert(backend_key.to_string(), latency);
+            let smoothed = match latencies.get(backend_key) {
+                Some(previous) => Duration::from_secs_f64(
+                    alpha * latency.as_secs_f64() + (1.0 - alpha) * previous.as_secs_f64(),
+                ),
+                // 首个样本直接作为初始值
+                None => latency,
+            };
+            latencies.insert(backend_key.to_string(), smoothed);
+        }
+        if let Ok(mut samples) = self.latency_samples.write() {
+            let samples = samples.entry(backend_key.to_string()).or_default();
+            if samples.len() == TAIL_LATENCY_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(latency);
         }
     }
 
     /// 记录请求失败
     pub fn record_failure(&self, backend_key: &str) {
-        let now = Instant::now();
+        let now = self.now();
         tracing::debug!("Recording failure for backend: {}", backend_key);
 
         if let Ok(mut failures) = self.failure_counts.write() {
@@ -160,13 +578,13 @@ impl MetricsCollector {
         }
 
         // 清理权重恢复状态（如果存在）
-        if let Ok(mut recovery_states) = self.weight_recovery_states.write() {
-            if recovery_states.remove(backend_key).is_some() {
-                tracing::debug!(
-                    "Cleared weight recovery state for failed backend {}",
-                    backend_key
-                );
-            }
+        if let Ok(mut recovery_states) = self.weight_recovery_states.write()
+            && recovery_states.remove(backend_key).is_some()
+        {
+            tracing::debug!(
+                "Cleared weight recovery state for failed backend {}",
+                backend_key
+            );
         }
     }
 
@@ -174,6 +592,12 @@ impl MetricsCollector {
     pub fn record_success(&self, backend_key: &str) {
         tracing::debug!("Recording success for backend: {}", backend_key);
 
+        // 剔除期内的后端不因成功而恢复
+        if self.is_ejected(backend_key) {
+            tracing::debug!("Backend {} is ejected as outlier, ignoring success", backend_key);
+            return;
+        }
+
         // 重置失败计数
         if let Ok(mut failures) = self.failure_counts.write() {
             failures.insert(backend_key.to_string(), 0);
@@ -187,28 +611,26 @@ impl MetricsCollector {
         }
 
         // 从不健康列表中移除
-        if let Ok(mut unhealthy) = self.unhealthy_backends.write() {
-            if unhealthy.remove(backend_key).is_some() {
-                tracing::debug!("Removed backend {} from unhealthy list", backend_key);
-            }
+        let mut was_unhealthy = false;
+        if let Ok(mut unhealthy) = self.unhealthy_backends.write()
+            && unhealthy.remove(backend_key).is_some()
+        {
+            was_unhealthy = true;
+            tracing::debug!("Removed backend {} from unhealthy list", backend_key);
         }
 
         // 重置恢复尝试计数
-        if let Ok(mut recovery) = self.recovery_attempts.write() {
-            if recovery.remove(backend_key).is_some() {
-                tracing::debug!("Reset recovery attempts for backend {}", backend_key);
-            }
+        if let Ok(mut recovery) = self.recovery_attempts.write()
+            && recovery.remove(backend_key).is_some()
+        {
+            tracing::debug!("Reset recovery attempts for backend {}", backend_key);
         }
 
-        // 清理权重恢复状态
-        if let Ok(mut recovery_states) = self.weight_recovery_states.write() {
-            if recovery_states.remove(backend_key).is_some() {
-                tracing::debug!(
-                    "Cleared weight recovery state for recovered backend {}",
-                    backend_key
-                );
-            }
+        // 从不健康恢复的后端进入慢启动，已在慢启动中的后端推进阶段
+        if was_unhealthy {
+            self.start_slow_start(backend_key);
         }
+        self.record_slow_start_success(backend_key);
     }
 
     /// 检查后端是否健康
@@ -222,6 +644,27 @@ impl MetricsCollector {
         }
     }
 
+    /// 获取后端的综合健康评分
+    pub fn get_health_score(&self, provider: &str, model: &str) -> HealthScore {
+        let backend_key = format!("{}:{}", provider, model);
+        self.health_scores.score_at(
+            &backend_key,
+            self.is_healthy(provider, model),
+            self.get_latency(provider, model),
+            self.now(),
+        )
+    }
+
+    /// 后端的健康分数是否达到可用的最低分数，未配置健康评分时等同于is_healthy
+    pub fn meets_health_threshold(&self, provider: &str, model: &str) -> bool {
+        self.health_scores.meets_threshold(&self.get_health_score(provider, model))
+    }
+
+    /// 按健康分数调整后的权重，未配置健康评分时返回原始权重
+    pub fn get_score_weight(&self, provider: &str, model: &str, weight: f64) -> f64 {
+        self.health_scores.weight(&self.get_health_score(provider, model), weight)
+    }
+
     /// 获取后端延迟
     pub fn get_latency(&self, provider: &str, model: &str) -> Option<Duration> {
         let backend_key = format!("{}:{}", provider, model);
@@ -233,6 +676,19 @@ impl MetricsCollector {
         }
     }
 
+    /// 获取后端最近请求的p95延迟
+    pub fn get_tail_latency(&self, provider: &str, model: &str) -> Option<Duration> {
+        let backend_key = format!("{}:{}", provider, model);
+        let samples = self.latency_samples.read().ok()?;
+        let mut sorted: Vec<Duration> = samples.get(&backend_key)?.iter().copied().collect();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort();
+        let index = ((sorted.len() as f64 * 0.95).ceil() as usize).clamp(1, sorted.len()) - 1;
+        Some(sorted[index])
+    }
+
     /// 获取失败计数
     pub fn get_failure_count(&self, provider: &str, model: &str) -> u32 {
         let backend_key = format!("{}:{}", provider, model);
@@ -247,7 +703,7 @@ impl MetricsCollector {
     /// 更新健康检查时间
     pub fn update_health_check(&self, backend_key: &str) {
         if let Ok(mut last_check) = self.last_health_check.write() {
-            last_check.insert(backend_key.to_string(), Instant::now());
+            last_check.insert(backend_key.to_string(), self.now());
         }
     }
 
@@ -278,7 +734,7 @@ impl MetricsCollector {
 
     /// 记录恢复尝试
     pub fn record_recovery_attempt(&self, backend_key: &str) {
-        let now = Instant::now();
+        let now = self.now();
         tracing::debug!("Recording recovery attempt for backend: {}", backend_key);
 
         if let Ok(mut unhealthy) = self.unhealthy_backends.write() {
@@ -319,118 +775,441 @@ impl MetricsCollector {
     }
 
     /// 记录按请求计费provider的被动验证成功
-    pub fn record_passive_success(&self, backend_key: &str, original_weight: f64) {
+    /// 后端在走完所有慢启动阶段前保留在不健康列表中
+    pub fn record_passive_success(&self, backend_key: &str) {
         tracing::debug!(
             "Recording passive success for per-request backend: {}",
             backend_key
         );
 
-        if let Ok(mut recovery_states) = self.weight_recovery_states.write() {
-            match recovery_states.get_mut(backend_key) {
-                Some(state) => {
-                    state.last_success_time = Instant::now();
-                    state.success_count += 1;
-
-                    // 根据成功次数逐步提高权重
-                    let new_stage = match state.success_count {
-                        1..=2 => RecoveryStage::RecoveryStage1, // 30%权重
-                        3..=4 => RecoveryStage::RecoveryStage2, // 50%权重
-                        _ => RecoveryStage::FullyRecovered,     // 100%权重
-                    };
-
-                    if new_stage != state.recovery_stage {
-                        state.recovery_stage = new_stage.clone();
-                        state.current_weight = match new_stage {
-                            RecoveryStage::RecoveryStage1 => original_weight * 0.3,
-                            RecoveryStage::RecoveryStage2 => original_weight * 0.5,
-                            RecoveryStage::FullyRecovered => original_weight,
-                            _ => state.current_weight,
-                        };
-
-                        tracing::debug!(
-                            "Backend {} advanced to stage {:?} with weight {:.2}",
-                            backend_key,
-                            new_stage,
-                            state.current_weight
-                        );
-
-                        // 如果完全恢复，从不健康列表中移除并标记为健康
-                        if new_stage == RecoveryStage::FullyRecovered {
-                            if let Ok(mut unhealthy) = self.unhealthy_backends.write() {
-                                unhealthy.remove(backend_key);
-                                tracing::debug!(
-                                    "Removed fully recovered backend {} from unhealthy list",
-                                    backend_key
-                                );
-                            }
-
-                            if let Ok(mut health) = self.health_status.write() {
-                                health.insert(backend_key.to_string(), true);
-                                tracing::debug!(
-                                    "Marked fully recovered backend {} as healthy",
-                                    backend_key
-                                );
-                            }
-                        }
-                    }
-                }
-                None => {
-                    // 首次被动成功，创建恢复状态
-                    let recovery_state = WeightRecoveryState {
-                        backend_key: backend_key.to_string(),
-                        original_weight,
-                        current_weight: original_weight * 0.3, // 从30%开始
-                        recovery_stage: RecoveryStage::RecoveryStage1,
-                        last_success_time: Instant::now(),
-                        success_count: 1,
-                    };
-
-                    recovery_states.insert(backend_key.to_string(), recovery_state);
-                    tracing::debug!(
-                        "Created recovery state for backend {} starting at 30% weight",
-                        backend_key
-                    );
-                }
-            }
+        if self.is_ejected(backend_key) {
+            tracing::debug!("Backend {} is ejected as outlier, ignoring passive success", backend_key);
+            return;
+        }
+
+        let has_state = self
+            .weight_recovery_states
+            .read()
+            .map(|states| states.contains_key(backend_key))
+            .unwrap_or(false);
+        if !has_state {
+            self.start_slow_start(backend_key);
+        }
+
+        // 未启用慢启动（无状态）或已走完所有阶段时视为完全恢复
+        if self.record_slow_start_success(backend_key) != Some(false) {
+            self.complete_recovery(backend_key);
         }
     }
 
-    /// 获取backend的当前权重（考虑恢复状态）
+    /// 获取backend的当前权重（考虑慢启动状态）
     pub fn get_effective_weight(&self, backend_key: &str, original_weight: f64) -> f64 {
-        if let Ok(recovery_states) = self.weight_recovery_states.read() {
-            if let Some(state) = recovery_states.get(backend_key) {
-                return state.current_weight;
-            }
+        // 剔除期内的后端不分配流量
+        if self.is_ejected(backend_key) {
+            return 0.0;
+        }
+
+        if let Some(fraction) = self.slow_start_fraction(backend_key) {
+            return original_weight * fraction;
         }
 
         // 检查是否在不健康列表中
         if self.is_in_unhealthy_list(backend_key) {
-            // 不健康的按请求计费provider使用10%权重
-            return original_weight * 0.1;
+            // 不健康的后端使用第一个慢启动阶段的权重
+            return original_weight * self.initial_slow_start_fraction(backend_key);
         }
 
         // 默认使用原始权重
         original_weight
     }
 
-    /// 初始化按请求计费provider的权重恢复状态
-    pub fn initialize_per_request_recovery(&self, backend_key: &str, original_weight: f64) {
+    /// 获取慢启动调整后的权重，不在慢启动中时返回原始权重
+    pub fn get_slow_start_weight(&self, backend_key: &str, original_weight: f64) -> f64 {
+        original_weight * self.slow_start_fraction(backend_key).unwrap_or(1.0)
+    }
+
+    /// 初始化按请求计费provider的权重恢复状态，从第一个慢启动阶段开始
+    pub fn initialize_per_request_recovery(&self, backend_key: &str) {
         tracing::debug!(
-            "Initializing per-request recovery for backend: {} with 10% weight",
+            "Initializing per-request recovery for backend: {}",
             backend_key
         );
+        self.start_slow_start(backend_key);
+    }
+
+    /// 让后端从第一个阶段开始慢启动，未配置慢启动阶段时不做处理
+    pub fn start_slow_start(&self, backend_key: &str) {
+        let enabled = self
+            .slow_start
+            .read()
+            .map(|config| !config.stages_for(backend_key).is_empty())
+            .unwrap_or(false);
+        if !enabled {
+            return;
+        }
 
         if let Ok(mut recovery_states) = self.weight_recovery_states.write() {
-            let recovery_state = WeightRecoveryState {
-                backend_key: backend_key.to_string(),
-                original_weight,
-                current_weight: original_weight * 0.1, // 从10%开始
-                recovery_stage: RecoveryStage::Unhealthy,
-                last_success_time: Instant::now(),
-                success_count: 0,
-            };
+            recovery_states.insert(
+                backend_key.to_string(),
+                WeightRecoveryState {
+                    backend_key: backend_key.to_string(),
+                    stage: 0,
+                    stage_started: self.now(),
+                    stage_successes: 0,
+                    last_success_time: None,
+                },
+            );
+            tracing::debug!("Started slow start for backend {}", backend_key);
+        }
+    }
+
+    /// 为慢启动中的后端记录一次成功并尝试推进阶段
+    /// 返回None表示后端不在慢启动中，Some(true)表示已走完所有阶段
+    fn record_slow_start_success(&self, backend_key: &str) -> Option<bool> {
+        let config = self.slow_start.read().ok()?;
+        let stages = config.stages_for(backend_key);
+        let mut recovery_states = self.weight_recovery_states.write().ok()?;
+        let state = recovery_states.get_mut(backend_key)?;
+
+        let now = self.now();
+        state.stage_successes += 1;
+        state.last_success_time = Some(now);
+
+        let completed = Self::advance_slow_start(stages, state, now);
+        if completed {
+            recovery_states.remove(backend_key);
+            tracing::debug!("Backend {} completed slow start", backend_key);
+        }
+        Some(completed)
+    }
+
+    /// 获取慢启动中后端的权重比例，同时按停留时间推进阶段
+    fn slow_start_fraction(&self, backend_key: &str) -> Option<f64> {
+        // 大多数后端不在慢启动中，先用读锁快速判断
+        if !self
+            .weight_recovery_states
+            .read()
+            .ok()?
+            .contains_key(backend_key)
+        {
+            return None;
+        }
+
+        let (fraction, completed) = {
+            let config = self.slow_start.read().ok()?;
+            let stages = config.stages_for(backend_key);
+            let mut recovery_states = self.weight_recovery_states.write().ok()?;
+            let state = recovery_states.get_mut(backend_key)?;
+            if Self::advance_slow_start(stages, state, self.now()) {
+                recovery_states.remove(backend_key);
+                (1.0, true)
+            } else {
+                (stages[state.stage].weight, false)
+            }
+        };
+
+        if completed {
+            tracing::debug!("Backend {} completed slow start", backend_key);
+            self.complete_recovery(backend_key);
+        }
+        Some(fraction)
+    }
+
+    /// 推进满足条件的阶段，返回是否已走完所有阶段
+    fn advance_slow_start(stages: &[SlowStartStage], state: &mut WeightRecoveryState, now: Instant) -> bool {
+        while let Some(stage) = stages.get(state.stage) {
+            let elapsed = now.duration_since(state.stage_started);
+            if state.stage_successes < stage.min_successes
+                || elapsed < Duration::from_secs(stage.min_duration_seconds)
+            {
+                return false;
+            }
+            state.stage += 1;
+            state.stage_started = now;
+            state.stage_successes = 0;
+            tracing::debug!(
+                "Backend {} advanced to slow start stage {}",
+                state.backend_key,
+                state.stage
+            );
+        }
+        true
+    }
+
+    /// 第一个慢启动阶段的权重比例，未配置时沿用10%
+    fn initial_slow_start_fraction(&self, backend_key: &str) -> f64 {
+        self.slow_start
+            .read()
+            .ok()
+            .and_then(|config| config.stages_for(backend_key).first().map(|stage| stage.weight))
+            .unwrap_or(0.1)
+    }
+
+    /// 完全恢复：从不健康列表中移除并标记为健康
+    fn complete_recovery(&self, backend_key: &str) {
+        if let Ok(mut unhealthy) = self.unhealthy_backends.write()
+            && unhealthy.remove(backend_key).is_some()
+        {
+            tracing::debug!(
+                "Removed fully recovered backend {} from unhealthy list",
+                backend_key
+            );
+        }
+
+        if let Ok(mut health) = self.health_status.write() {
+            health.insert(backend_key.to_string(), true);
+        }
+    }
+
+    /// 记录TLS证书固定校验失败
+    pub fn record_tls_pin_failure(&self, provider_id: &str) {
+        if let Ok(mut failures) = self.tls_pin_failures.write() {
+            *failures.entry(provider_id.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    /// 获取各provider的TLS证书固定校验失败次数
+    pub fn get_tls_pin_failures(&self) -> HashMap<String, u64> {
+        if let Ok(failures) = self.tls_pin_failures.read() {
+            failures.clone()
+        } else {
+            HashMap::new()
+        }
+    }
+
+    /// 记录一次上游超时，kind为超时发生的阶段
+    pub fn record_upstream_timeout(&self, backend_key: &str, kind: &'static str) {
+        if let Ok(mut timeouts) = self.upstream_timeouts.write() {
+            *timeouts
+                .entry(backend_key.to_string())
+                .or_default()
+                .entry(kind)
+                .or_insert(0) += 1;
+        }
+    }
+
+    /// 获取各后端按阶段统计的上游超时次数
+    pub fn get_upstream_timeouts(&self) -> HashMap<String, HashMap<&'static str, u64>> {
+        self.upstream_timeouts
+            .read()
+            .map(|timeouts| timeouts.clone())
+            .unwrap_or_default()
+    }
+
+    /// 标记后端开始处理一个请求，返回的守卫被丢弃时计数自动减少
+    pub fn begin_request(self: &Arc<Self>, backend_key: &str) -> InFlightGuard {
+        if let Ok(mut in_flight) = self.in_flight.write() {
+            *in_flight.entry(backend_key.to_string()).or_insert(0) += 1;
+        }
+        if let Ok(mut totals) = self.request_totals.write() {
+            *totals.entry(backend_key.to_string()).or_insert(0) += 1;
+        }
+        InFlightGuard {
+            metrics: self.clone(),
+            backend_key: backend_key.to_string(),
+        }
+    }
+
+    fn finish_request(&self, backend_key: &str) {
+        if let Ok(mut in_flight) = self.in_flight.write()
+            && let Some(count) = in_flight.get_mut(backend_key)
+        {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                in_flight.remove(backend_key);
+            }
+        }
+    }
+
+    /// 通过管理接口禁用或启用后端（运行时生效，不修改配置文件）
+    pub fn set_backend_admin_disabled(&self, backend_key: &str, disabled: bool) {
+        if let Ok(mut admin_disabled) = self.admin_disabled.write() {
+            if disabled {
+                admin_disabled.insert(backend_key.to_string());
+            } else {
+                admin_disabled.remove(backend_key);
+            }
+        }
+    }
+
+    /// 检查后端是否被管理接口禁用
+    pub fn is_backend_admin_disabled(&self, provider: &str, model: &str) -> bool {
+        let key = format!("{}:{}", provider, model);
+        self.admin_disabled
+            .read()
+            .map(|admin_disabled| admin_disabled.contains(&key))
+            .unwrap_or(false)
+    }
+
+    /// 记录一次上游限流，计入健康评分
+    pub fn record_rate_limited(&self, backend_key: &str) {
+        self.health_scores.record_outcome_at(backend_key, RequestOutcome::RateLimited, self.now());
+    }
+
+    /// 上游限流后让后端冷却一段时间，冷却期内所有选择策略都跳过该后端，但不标记为不健康
+    pub fn set_cooldown(&self, backend_key: &str, duration: Duration) {
+        let until = self.now() + duration;
+        if let Ok(mut cooldowns) = self.cooldown_until.write() {
+            let entry = cooldowns.entry(backend_key.to_string()).or_insert(until);
+            *entry = (*entry).max(until);
+        }
+    }
+
+    /// 后端剩余的冷却时间，不在冷却期内时返回None
+    pub fn cooldown_remaining(&self, provider: &str, model: &str) -> Option<Duration> {
+        let key = format!("{}:{}", provider, model);
+        let until = *self.cooldown_until.read().ok()?.get(&key)?;
+        until.checked_duration_since(self.now()).filter(|d| !d.is_zero())
+    }
+
+    /// 后端是否处于限流冷却期内
+    pub fn is_cooling_down(&self, provider: &str, model: &str) -> bool {
+        self.cooldown_remaining(provider, model).is_some()
+    }
+
+    /// 设置各provider的上游配额（随配置热加载更新），不再有配额的provider清除用量统计
+    pub fn set_provider_quotas(&self, quotas: HashMap<String, ProviderQuota>) {
+        if let Ok(mut usage) = self.provider_usage.lock() {
+            usage.retain(|provider, _| quotas.contains_key(provider));
+        }
+        if let Ok(mut current) = self.provider_quotas.write() {
+            *current = quotas;
+        }
+    }
+
+    /// 记录一次发往provider的请求（只统计配置了配额的provider）
+    pub fn record_provider_request(&self, provider: &str) {
+        self.with_provider_usage(provider, |usage, now| usage.requests.push_back(now));
+    }
+
+    /// 记录provider一次请求消耗的token数（只统计配置了配额的provider）
+    pub fn record_provider_tokens(&self, provider: &str, tokens: u64) {
+        self.with_provider_usage(provider, |usage, now| usage.tokens.push_back((now, tokens)));
+    }
+
+    fn with_provider_usage(&self, provider: &str, f: impl FnOnce(&mut ProviderUsage, Instant)) {
+        let has_quota = self
+            .provider_quotas
+            .read()
+            .is_ok_and(|quotas| quotas.contains_key(provider));
+        if !has_quota {
+            return;
+        }
+        if let Ok(mut usage) = self.provider_usage.lock() {
+            let now = self.now();
+            let usage = usage.entry(provider.to_string()).or_default();
+            usage.prune(now);
+            f(usage, now);
+        }
+    }
+
+    /// provider最近一分钟的配额用量比例（请求数和token数中较高的一项），未配置配额时返回None
+    pub fn provider_quota_usage(&self, provider: &str) -> Option<f64> {
+        let quota = self.provider_quotas.read().ok()?.get(provider)?.clone();
+        let mut usage = self.provider_usage.lock().ok()?;
+        let Some(usage) = usage.get_mut(provider) else {
+            return Some(0.0);
+        };
+        usage.prune(self.now());
+        let requests = quota
+            .requests_per_minute
+            .map(|rpm| usage.requests.len() as f64 / rpm as f64);
+        let tokens = quota
+            .tokens_per_minute
+            .map(|tpm| usage.tokens.iter().map(|(_, n)| *n).sum::<u64>() as f64 / tpm as f64);
+        Some(requests.into_iter().chain(tokens).fold(0.0, f64::max))
+    }
 
-            recovery_states.insert(backend_key.to_string(), recovery_state);
+    /// provider的用量是否已达到配额阈值
+    pub fn is_near_quota(&self, provider: &str) -> bool {
+        let Some(threshold) = self
+            .provider_quotas
+            .read()
+            .ok()
+            .and_then(|quotas| quotas.get(provider).map(|quota| quota.threshold_percent))
+        else {
+            return false;
+        };
+        self.provider_quota_usage(provider)
+            .is_some_and(|usage| usage * 100.0 >= threshold)
+    }
+
+    /// 设置各provider当前使用的上游密钥及月度上限
+    pub fn set_provider_keys(&self, keys: HashMap<String, ProviderKey>) {
+        self.key_spend.set_keys(keys);
+    }
+
+    /// 记录provider当前密钥的一次用量，首次达到告警比例或上限时返回告警
+    pub fn record_key_usage(&self, provider: &str, usage: TokenUsage, cost: f64) -> Option<KeyBudgetAlert> {
+        self.key_spend.record(provider, usage, cost)
+    }
+
+    /// provider当前密钥本月用量是否已达到上限
+    pub fn is_key_exhausted(&self, provider: &str) -> bool {
+        self.key_spend.is_exhausted(provider)
+    }
+
+    /// 获取各provider当前密钥本月的用量
+    pub fn get_key_spend(&self) -> Vec<KeySpend> {
+        self.key_spend.report()
+    }
+
+    /// 记录一次客户端请求的最终结果，用于全局和按模型的请求计数
+    pub fn record_client_request(&self, model_id: Option<&str>, success: bool) {
+        if let Ok(mut stats) = self.request_stats.lock() {
+            stats.record(model_id, success, self.now());
+        }
+    }
+
+    /// 记录一次客户端请求的SLO结果，available为false表示服务端错误（客户端错误不消耗错误预算）
+    pub fn record_slo(&self, available: bool, latency: Duration) {
+        self.slo.record_at(available, latency, self.now());
+    }
+
+    /// 获取各SLO目标的达成情况和错误预算消耗速率
+    pub fn get_slo_report(&self) -> Vec<SloObjectiveReport> {
+        self.slo.report_at(self.now())
+    }
+
+    /// 重新评估SLO快速消耗状态，返回状态发生变化的目标
+    pub fn evaluate_slo(&self) -> Vec<SloTransition> {
+        self.slo.evaluate_at(self.now())
+    }
+
+    /// 获取全局和按模型的请求计数及近期速率
+    pub fn get_request_stats(&self) -> RequestStatsReport {
+        self.request_stats
+            .lock()
+            .map(|stats| stats.report(self.now()))
+            .unwrap_or_default()
+    }
+
+    /// 获取后端累计处理的请求数
+    pub fn get_request_total(&self, provider: &str, model: &str) -> u64 {
+        let key = format!("{}:{}", provider, model);
+        self.request_totals
+            .read()
+            .map(|totals| totals.get(&key).copied().unwrap_or(0))
+            .unwrap_or(0)
+    }
+
+    /// 获取后端当前所处的慢启动阶段（从0开始），未处于慢启动时返回None
+    pub fn get_recovery_stage(&self, provider: &str, model: &str) -> Option<usize> {
+        let key = format!("{}:{}", provider, model);
+        self.weight_recovery_states
+            .read()
+            .ok()?
+            .get(&key)
+            .map(|state| state.stage)
+    }
+
+    /// 获取后端当前在途请求数
+    pub fn get_in_flight(&self, provider: &str, model: &str) -> usize {
+        let key = format!("{}:{}", provider, model);
+        if let Ok(in_flight) = self.in_flight.read() {
+            in_flight.get(&key).copied().unwrap_or(0)
+        } else {
+            0
         }
     }
 }
@@ -443,13 +1222,61 @@ impl Default for MetricsCollector {
 
 impl BackendSelector {
     pub fn new(mapping: ModelMapping, metrics: Arc<MetricsCollector>) -> Self {
+        let hash_ring = if mapping.strategy == LoadBalanceStrategy::ConsistentHash {
+            Self::build_hash_ring(&mapping)
+        } else {
+            Vec::new()
+        };
+
         Self {
             mapping,
             round_robin_counter: AtomicUsize::new(0),
             metrics,
+            hash_ring,
+            rng: None,
         }
     }
 
+    /// 使用固定种子的随机数生成器创建，同一种子和相同的选择顺序得到相同的结果，
+    /// 用于编写不依赖统计结果的确定性测试
+    pub fn with_seed(mapping: ModelMapping, metrics: Arc<MetricsCollector>, seed: u64) -> Self {
+        Self {
+            rng: Some(std::sync::Mutex::new(rand::rngs::StdRng::seed_from_u64(seed))),
+            ..Self::new(mapping, metrics)
+        }
+    }
+
+    /// 使用选择器的随机数生成器
+    fn with_rng<T>(&self, f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        match self.rng.as_ref().and_then(|rng| rng.lock().ok()) {
+            Some(mut rng) => f(&mut *rng),
+            None => f(&mut rand::rng()),
+        }
+    }
+
+    /// 为所有启用的后端构建一致性哈希环
+    fn build_hash_ring(mapping: &ModelMapping) -> Vec<(u64, usize)> {
+        let mut ring = Vec::new();
+
+        for (index, backend) in mapping.backends.iter().enumerate() {
+            if !backend.enabled {
+                continue;
+            }
+            for node in 0..mapping.virtual_nodes {
+                let node_key = format!("{}:{}#{}", backend.provider, backend.model, node);
+                ring.push((hash_key(&node_key), index));
+            }
+        }
+
+        ring.sort_by_key(|(hash, _)| *hash);
+        tracing::debug!(
+            "Built consistent hash ring for model '{}' with {} virtual nodes",
+            mapping.name,
+            ring.len()
+        );
+        ring
+    }
+
     /// 获取模型映射的引用
     pub fn get_mapping(&self) -> &ModelMapping {
         &self.mapping
@@ -461,23 +1288,92 @@ impl BackendSelector {
     }
 
     pub fn select(&self) -> Result<Backend> {
+        self.select_with_context(&SelectionContext::default())
+    }
+
+    /// 根据请求上下文选择后端
+    pub fn select_with_context(&self, context: &SelectionContext) -> Result<Backend> {
         let enabled_backends: Vec<Backend> = self
             .mapping
             .backends
             .iter()
-            .filter(|b| b.enabled)
+            .filter(|b| b.enabled && !self.metrics.is_backend_admin_disabled(&b.provider, &b.model))
+            .filter(|b| b.is_in_schedule())
+            .filter(|b| context.matches_tags(b) && context.fits_context(b) && context.supports_capabilities(b))
+            .filter(|b| !self.metrics.is_cooling_down(&b.provider, &b.model))
+            .filter(|b| !self.metrics.is_key_exhausted(&b.provider))
             .cloned()
             .collect();
 
         if enabled_backends.is_empty() {
+            let out_of_schedule = self
+                .mapping
+                .backends
+                .iter()
+                .any(|b| b.enabled && !b.is_in_schedule());
+            let cooling_down = self
+                .mapping
+                .backends
+                .iter()
+                .any(|b| b.enabled && self.metrics.is_cooling_down(&b.provider, &b.model));
+            let key_exhausted = self
+                .mapping
+                .backends
+                .iter()
+                .any(|b| b.enabled && self.metrics.is_key_exhausted(&b.provider));
+            let message = if cooling_down {
+                "All available backends are cooling down after upstream rate limits".to_string()
+            } else if key_exhausted {
+                "All available backends have reached their monthly key budget".to_string()
+            } else if out_of_schedule {
+                "All available backends are outside their scheduled hours".to_string()
+            } else if let Some(tokens) = context.context_tokens
+                && !self.mapping.backends.iter().any(|b| b.enabled && b.fits_context(tokens))
+            {
+                format!("No enabled backends have a context window of {} tokens", tokens)
+            } else if !self.mapping.backends.iter().any(|b| b.enabled && context.supports_capabilities(b)) {
+                format!("No enabled backends support {}", context.capabilities.names().join(", "))
+            } else if context.tags.is_empty() {
+                "No enabled backends available".to_string()
+            } else {
+                format!("No enabled backends match tags [{}]", context.tags.join(", "))
+            };
             return Err(self.create_detailed_error(
-                "No enabled backends available",
+                &message,
                 &self.mapping.backends,
                 &[],
             ).into());
         }
 
-        let result = match self.mapping.strategy {
+        // 接近上游配额的provider只在没有其他候选后端时使用
+        let (below_quota, near_quota): (Vec<Backend>, Vec<Backend>) = enabled_backends
+            .into_iter()
+            .partition(|b| !self.metrics.is_near_quota(&b.provider));
+        let enabled_backends = if below_quota.is_empty() { near_quota } else { below_quota };
+
+        let enabled_backends = match &context.region {
+            Some(region) => self.prefer_region(enabled_backends, region),
+            None => enabled_backends,
+        };
+
+        let strategy = context.strategy_override.as_ref().unwrap_or(&self.mapping.strategy);
+
+        // 慢启动中的后端按阶段比例降低权重，启用健康评分时再按分数调整；智能权重故障转移自行计算有效权重
+        let enabled_backends = if *strategy == LoadBalanceStrategy::SmartWeightedFailover {
+            enabled_backends
+        } else {
+            enabled_backends
+                .into_iter()
+                .map(|mut backend| {
+                    let backend_key = format!("{}:{}", backend.provider, backend.model);
+                    let weight = self.metrics.get_slow_start_weight(&backend_key, backend.weight);
+                    backend.weight = self.metrics.get_score_weight(&backend.provider, &backend.model, weight);
+                    backend
+                })
+                .collect()
+        };
+
+        let result = match strategy {
             LoadBalanceStrategy::WeightedRandom => self.select_weighted_random(&enabled_backends),
             LoadBalanceStrategy::RoundRobin => self.select_round_robin(&enabled_backends),
             LoadBalanceStrategy::LeastLatency => self.select_least_latency(&enabled_backends),
@@ -489,6 +1385,19 @@ impl BackendSelector {
             LoadBalanceStrategy::SmartWeightedFailover => {
                 self.select_smart_weighted_failover(&enabled_backends)
             }
+            LoadBalanceStrategy::StickySession => {
+                self.select_sticky_session(&enabled_backends, context)
+            }
+            LoadBalanceStrategy::ConsistentHash => {
+                self.select_consistent_hash(&enabled_backends, context)
+            }
+            LoadBalanceStrategy::LeastConnections => {
+                self.select_least_connections(&enabled_backends)
+            }
+            LoadBalanceStrategy::WeightedLeastLatency => {
+                self.select_weighted_least_latency(&enabled_backends)
+            }
+            LoadBalanceStrategy::CostOptimized => self.select_cost_optimized(&enabled_backends),
         };
 
         // 如果选择失败，创建详细的错误信息
@@ -496,7 +1405,7 @@ impl BackendSelector {
             tracing::error!(
                 "Backend selection failed for model '{}' using strategy '{:?}': {}",
                 self.mapping.name,
-                self.mapping.strategy,
+                strategy,
                 e
             );
         }
@@ -504,11 +1413,125 @@ impl BackendSelector {
         result
     }
 
+    /// 按select_with_context的过滤顺序说明每个后端是否参与选择以及被排除的原因
+    /// 不健康的后端会被服务层重新选择，有健康的候选后端时同样视为排除
+    pub fn explain(&self, context: &SelectionContext) -> Vec<CandidateExplanation> {
+        let backends = &self.mapping.backends;
+        let mut candidates: Vec<CandidateExplanation> = backends
+            .iter()
+            .map(|b| {
+                let backend_key = format!("{}:{}", b.provider, b.model);
+                let excluded = if !b.enabled {
+                    Some("disabled".to_string())
+                } else if self.metrics.is_backend_admin_disabled(&b.provider, &b.model) {
+                    Some("admin_disabled".to_string())
+                } else if !b.is_in_schedule() {
+                    Some("out_of_schedule".to_string())
+                } else if !context.matches_tags(b) {
+                    Some("tags".to_string())
+                } else if !context.fits_context(b) {
+                    Some("context_window".to_string())
+                } else if !context.supports_capabilities(b) {
+                    let missing: Vec<&str> = context
+                        .capabilities
+                        .names()
+                        .into_iter()
+                        .filter(|name| !RequiredCapabilities::only(name).supported_by(b))
+                        .collect();
+                    Some(format!("capability:{}", missing.join(",")))
+                } else if self.metrics.is_cooling_down(&b.provider, &b.model) {
+                    Some("cooldown".to_string())
+                } else if self.metrics.is_key_exhausted(&b.provider) {
+                    Some("key_budget".to_string())
+                } else {
+                    None
+                };
+                let weight = self.metrics.get_slow_start_weight(&backend_key, b.weight);
+                CandidateExplanation {
+                    backend: backend_key,
+                    effective_weight: self.metrics.get_score_weight(&b.provider, &b.model, weight),
+                    healthy: self.metrics.is_healthy(&b.provider, &b.model),
+                    excluded,
+                }
+            })
+            .collect();
+
+        let remaining = |candidates: &[CandidateExplanation]| -> Vec<usize> {
+            (0..candidates.len()).filter(|&i| candidates[i].excluded.is_none()).collect()
+        };
+
+        // 接近配额的provider只在没有其他候选后端时使用
+        let near_quota: Vec<usize> = remaining(&candidates)
+            .into_iter()
+            .filter(|&i| self.metrics.is_near_quota(&backends[i].provider))
+            .collect();
+        if near_quota.len() < remaining(&candidates).len() {
+            for i in near_quota {
+                candidates[i].excluded = Some("near_quota".to_string());
+            }
+        }
+
+        if let Some(region) = &context.region {
+            let indices = remaining(&candidates);
+            let preferred = self.prefer_region(indices.iter().map(|&i| backends[i].clone()).collect(), region);
+            for i in indices {
+                let kept = preferred
+                    .iter()
+                    .any(|b| b.provider == backends[i].provider && b.model == backends[i].model);
+                if !kept {
+                    candidates[i].excluded = Some("region".to_string());
+                }
+            }
+        }
+
+        let indices = remaining(&candidates);
+        if indices.iter().any(|&i| candidates[i].healthy) {
+            for i in indices {
+                if !candidates[i].healthy {
+                    candidates[i].excluded = Some("unhealthy".to_string());
+                }
+            }
+        }
+        candidates
+    }
+
+    /// 有健康的同区域后端时只在同区域中选择；否则跨区域到 平均延迟 + 区域惩罚 最小的后端所在区域
+    fn prefer_region(&self, backends: Vec<Backend>, region: &str) -> Vec<Backend> {
+        let healthy: Vec<&Backend> = backends
+            .iter()
+            .filter(|b| self.metrics.meets_health_threshold(&b.provider, &b.model))
+            .collect();
+        if healthy.iter().any(|b| b.region.as_deref() == Some(region)) {
+            return backends
+                .into_iter()
+                .filter(|b| b.region.as_deref() == Some(region))
+                .collect();
+        }
+
+        let nearest = healthy
+            .iter()
+            .min_by_key(|b| {
+                self.metrics.get_latency(&b.provider, &b.model).unwrap_or_default()
+                    + self.metrics.region_penalty(region, b.region.as_deref())
+            })
+            .map(|b| b.region.clone());
+        let Some(nearest) = nearest else {
+            // 没有健康后端时交给策略自行回退
+            return backends;
+        };
+        tracing::debug!(
+            "No healthy backend of model '{}' in region '{}', failing over to region {:?}",
+            self.mapping.name,
+            region,
+            nearest
+        );
+        backends.into_iter().filter(|b| b.region == nearest).collect()
+    }
+
     fn select_weighted_random(&self, backends: &[Backend]) -> Result<Backend> {
         let weights: Vec<f64> = backends.iter().map(|b| b.weight).collect();
         let dist = WeightedIndex::new(&weights)?;
-        let mut rng = rand::rng();
-        Ok(backends[dist.sample(&mut rng)].clone())
+        Ok(backends[self.with_rng(|rng| dist.sample(rng))].clone())
     }
 
     fn select_round_robin(&self, backends: &[Backend]) -> Result<Backend> {
@@ -530,13 +1553,174 @@ impl BackendSelector {
                 .get_latency(&backend.provider, &backend.model)
                 .unwrap_or(Duration::from_secs(999));
 
-            if latency < best_latency {
-                best_backend = backend;
-                best_latency = latency;
-            }
-        }
+            if latency < best_latency {
+                best_backend = backend;
+                best_latency = latency;
+            }
+        }
+
+        Ok(best_backend.clone())
+    }
+
+    fn select_least_connections(&self, backends: &[Backend]) -> Result<Backend> {
+        // 优先在健康后端中选择，全部不健康时退化为在所有后端中选择
+        let healthy_backends: Vec<&Backend> = backends
+            .iter()
+            .filter(|b| self.metrics.meets_health_threshold(&b.provider, &b.model))
+            .collect();
+        let candidates: Vec<&Backend> = if healthy_backends.is_empty() {
+            tracing::warn!(
+                "No healthy backends available for least connections on model '{}', selecting from all backends",
+                self.mapping.name
+            );
+            backends.iter().collect()
+        } else {
+            healthy_backends
+        };
+
+        // 在途请求最少者优先，相同时选择权重更高的
+        let best_backend = candidates
+            .into_iter()
+            .min_by(|a, b| {
+                let a_conns = self.metrics.get_in_flight(&a.provider, &a.model);
+                let b_conns = self.metrics.get_in_flight(&b.provider, &b.model);
+                a_conns
+                    .cmp(&b_conns)
+                    .then_with(|| b.weight.total_cmp(&a.weight))
+            })
+            .ok_or_else(|| anyhow::anyhow!("No backends available for least connections"))?;
+
+        Ok(best_backend.clone())
+    }
+
+    /// 成本优先：在未饱和且尾延迟满足SLO的健康后端中选择单价最低的，价格相同时按权重随机；
+    /// 都不满足SLO时选择尾延迟最低的未饱和后端，全部饱和时选择最便宜的
+    fn select_cost_optimized(&self, backends: &[Backend]) -> Result<Backend> {
+        let healthy_backends: Vec<&Backend> = backends
+            .iter()
+            .filter(|b| self.metrics.meets_health_threshold(&b.provider, &b.model))
+            .collect();
+        let candidates: Vec<&Backend> = if healthy_backends.is_empty() {
+            tracing::warn!(
+                "No healthy backends available for cost optimized on model '{}', selecting from all backends",
+                self.mapping.name
+            );
+            backends.iter().collect()
+        } else {
+            healthy_backends
+        };
+
+        let slo = self.mapping.latency_slo_ms.map(Duration::from_millis);
+        let unsaturated: Vec<&Backend> = candidates
+            .iter()
+            .copied()
+            .filter(|b| !self.is_saturated(b))
+            .collect();
+        let within_slo: Vec<&Backend> = unsaturated
+            .iter()
+            .copied()
+            .filter(|b| {
+                slo.is_none_or(|slo| {
+                    self.metrics
+                        .get_tail_latency(&b.provider, &b.model)
+                        .is_none_or(|latency| latency <= slo)
+                })
+            })
+            .collect();
+
+        let pool = if !within_slo.is_empty() {
+            within_slo
+        } else if let Some(fastest) = unsaturated.iter().copied().min_by_key(|b| {
+            self.metrics
+                .get_tail_latency(&b.provider, &b.model)
+                .unwrap_or(Duration::MAX)
+        }) {
+            tracing::debug!(
+                "No backend of model '{}' meets the latency SLO, using the fastest one",
+                self.mapping.name
+            );
+            vec![fastest]
+        } else {
+            candidates
+        };
+
+        let cheapest = pool
+            .iter()
+            .map(|b| Self::unit_cost(b))
+            .fold(f64::INFINITY, f64::min);
+        let cheapest: Vec<Backend> = pool
+            .into_iter()
+            .filter(|b| Self::unit_cost(b) <= cheapest)
+            .cloned()
+            .collect();
+        self.select_weighted_random(&cheapest)
+    }
+
+    /// 每1k输入和输出token的合计单价，未配置价格的后端视为最贵
+    fn unit_cost(backend: &Backend) -> f64 {
+        backend
+            .pricing
+            .as_ref()
+            .map_or(f64::INFINITY, |p| p.prompt_per_1k + p.completion_per_1k)
+    }
+
+    /// 后端的在途请求数是否已达到其并发上限
+    fn is_saturated(&self, backend: &Backend) -> bool {
+        backend.concurrency.as_ref().is_some_and(|limit| {
+            self.metrics.get_in_flight(&backend.provider, &backend.model) >= limit.max_concurrent_requests
+        })
+    }
+
+    fn select_weighted_least_latency(&self, backends: &[Backend]) -> Result<Backend> {
+        // 优先在健康后端中选择，全部不健康时退化为在所有后端中选择
+        let healthy_backends: Vec<&Backend> = backends
+            .iter()
+            .filter(|b| self.metrics.meets_health_threshold(&b.provider, &b.model))
+            .collect();
+        let candidates: Vec<&Backend> = if healthy_backends.is_empty() {
+            tracing::warn!(
+                "No healthy backends available for weighted least latency on model '{}', selecting from all backends",
+                self.mapping.name
+            );
+            backends.iter().collect()
+        } else {
+            healthy_backends
+        };
+
+        let latencies: Vec<Option<f64>> = candidates
+            .iter()
+            .map(|b| {
+                self.metrics
+                    .get_latency(&b.provider, &b.model)
+                    .map(|l| l.as_secs_f64().max(0.001))
+            })
+            .collect();
 
-        Ok(best_backend.clone())
+        // 尚无延迟数据的后端按已测后端的平均延迟估算，保证新后端也能分到流量
+        let measured: Vec<f64> = latencies.iter().flatten().copied().collect();
+        let default_latency = if measured.is_empty() {
+            1.0
+        } else {
+            measured.iter().sum::<f64>() / measured.len() as f64
+        };
+
+        let scores: Vec<f64> = candidates
+            .iter()
+            .zip(&latencies)
+            .map(|(b, latency)| b.weight / latency.unwrap_or(default_latency))
+            .collect();
+        tracing::debug!(
+            "Weighted least latency scores for model '{}': {:?}",
+            self.mapping.name,
+            candidates
+                .iter()
+                .zip(&scores)
+                .map(|(b, score)| (format!("{}:{}", b.provider, b.model), *score))
+                .collect::<Vec<_>>()
+        );
+
+        let dist = WeightedIndex::new(&scores)?;
+        Ok(candidates[self.with_rng(|rng| dist.sample(rng))].clone())
     }
 
     fn select_failover(&self, backends: &[Backend]) -> Result<Backend> {
@@ -546,7 +1730,7 @@ impl BackendSelector {
 
         // 尝试找到第一个健康的后端
         for backend in &sorted {
-            if self.metrics.is_healthy(&backend.provider, &backend.model) {
+            if self.metrics.meets_health_threshold(&backend.provider, &backend.model) {
                 tracing::debug!(
                     "Failover selected healthy backend {}:{} (priority: {}) for model '{}'",
                     backend.provider,
@@ -580,8 +1764,7 @@ impl BackendSelector {
     }
 
     fn select_random(&self, backends: &[Backend]) -> Result<Backend> {
-        let mut rng = rand::rng();
-        let index = rng.random_range(0..backends.len());
+        let index = self.with_rng(|rng| rng.random_range(0..backends.len()));
         Ok(backends[index].clone())
     }
 
@@ -589,7 +1772,7 @@ impl BackendSelector {
         // 首先过滤出健康的后端
         let healthy_backends: Vec<Backend> = backends
             .iter()
-            .filter(|b| self.metrics.is_healthy(&b.provider, &b.model))
+            .filter(|b| self.metrics.meets_health_threshold(&b.provider, &b.model))
             .cloned()
             .collect();
 
@@ -636,9 +1819,11 @@ impl BackendSelector {
 
         for backend in backends {
             let backend_key = format!("{}:{}", backend.provider, backend.model);
-            let effective_weight = self
-                .metrics
-                .get_effective_weight(&backend_key, backend.weight);
+            let effective_weight = self.metrics.get_score_weight(
+                &backend.provider,
+                &backend.model,
+                self.metrics.get_effective_weight(&backend_key, backend.weight),
+            );
 
             // 创建调整权重后的backend副本
             let mut adjusted_backend = backend.clone();
@@ -705,6 +1890,89 @@ impl BackendSelector {
         }
     }
 
+    fn select_sticky_session(&self, backends: &[Backend], context: &SelectionContext) -> Result<Backend> {
+        // 没有会话标识时无法保持粘性，直接使用权重选择
+        let Some(session_key) = context.session_key.as_deref() else {
+            tracing::debug!(
+                "No session key provided for sticky session on model '{}', using weighted selection",
+                self.mapping.name
+            );
+            return self.select_weighted_failover(backends);
+        };
+
+        // 对会话标识做哈希，同一会话总是映射到同一后端
+        let index = (hash_key(session_key) % backends.len() as u64) as usize;
+        let backend = &backends[index];
+
+        if self.metrics.meets_health_threshold(&backend.provider, &backend.model) {
+            tracing::debug!(
+                "Sticky session '{}' routed to backend {}:{} for model '{}'",
+                session_key,
+                backend.provider,
+                backend.model,
+                self.mapping.name
+            );
+            return Ok(backend.clone());
+        }
+
+        // 粘性后端不健康时回退到权重选择
+        tracing::warn!(
+            "Sticky backend {}:{} for session '{}' is unhealthy, falling back to weighted selection for model '{}'",
+            backend.provider,
+            backend.model,
+            session_key,
+            self.mapping.name
+        );
+        self.select_weighted_failover(backends)
+    }
+
+    fn select_consistent_hash(&self, backends: &[Backend], context: &SelectionContext) -> Result<Backend> {
+        let Some(fingerprint) = context.conversation_fingerprint.as_deref() else {
+            tracing::debug!(
+                "No conversation fingerprint for consistent hash on model '{}', using weighted selection",
+                self.mapping.name
+            );
+            return self.select_weighted_failover(backends);
+        };
+
+        if self.hash_ring.is_empty() {
+            return self.select_weighted_failover(backends);
+        }
+
+        // 在哈希环上顺时针查找第一个健康的候选后端，不健康的后端只会让其负责的请求迁移到下一个节点
+        let key_hash = hash_key(fingerprint);
+        let start = self.hash_ring.partition_point(|(hash, _)| *hash < key_hash);
+
+        for offset in 0..self.hash_ring.len() {
+            let (_, index) = self.hash_ring[(start + offset) % self.hash_ring.len()];
+            let backend = &self.mapping.backends[index];
+
+            if self.metrics.meets_health_threshold(&backend.provider, &backend.model)
+                && !self.metrics.is_backend_admin_disabled(&backend.provider, &backend.model)
+                && backend.is_in_schedule()
+                && context.matches_tags(backend)
+                && backends.iter().any(|b| b.provider == backend.provider && b.model == backend.model)
+            {
+                tracing::debug!(
+                    "Consistent hash routed fingerprint {:016x} to backend {}:{} for model '{}' (ring offset {})",
+                    key_hash,
+                    backend.provider,
+                    backend.model,
+                    self.mapping.name,
+                    offset
+                );
+                return Ok(backend.clone());
+            }
+        }
+
+        // 哈希环上没有健康的后端，回退到权重选择
+        tracing::warn!(
+            "No healthy backends on consistent hash ring for model '{}', falling back to weighted selection",
+            self.mapping.name
+        );
+        self.select_weighted_failover(backends)
+    }
+
     /// 创建详细的错误信息
     fn create_detailed_error(
         &self,
@@ -793,6 +2061,11 @@ impl BackendSelector {
                 "Backend disabled".to_string()
             } else if !is_healthy {
                 format!("Unhealthy (failures: {})", failure_count)
+            } else if !self.metrics.meets_health_threshold(&backend.provider, &backend.model) {
+                format!(
+                    "Low health score ({:.2})",
+                    self.metrics.get_health_score(&backend.provider, &backend.model).score
+                )
             } else {
                 "Available".to_string()
             };
@@ -816,6 +2089,7 @@ impl BackendSelector {
 mod tests {
     use super::*;
     use crate::config::model::{BillingMode, LoadBalanceStrategy, ModelMapping};
+    use crate::loadbalance::clock::ManualClock;
 
     fn create_test_backends() -> Vec<Backend> {
         vec![
@@ -827,6 +2101,18 @@ mod tests {
                 enabled: true,
                 tags: vec![],
                 billing_mode: BillingMode::PerToken,
+                pricing: None,
+                overrides: HashMap::new(),
+                slow_start: None,
+                concurrency: None,
+                schedule: None,
+                region: None,
+                max_context: None,
+                supports_vision: true,
+                supports_tools: true,
+                supports_json_mode: true,
+                supports_streaming: true,
+                supports_responses: false,
             },
             Backend {
                 provider: "provider2".to_string(),
@@ -836,6 +2122,18 @@ mod tests {
                 enabled: true,
                 tags: vec![],
                 billing_mode: BillingMode::PerRequest,
+                pricing: None,
+                overrides: HashMap::new(),
+                slow_start: None,
+                concurrency: None,
+                schedule: None,
+                region: None,
+                max_context: None,
+                supports_vision: true,
+                supports_tools: true,
+                supports_json_mode: true,
+                supports_streaming: true,
+                supports_responses: false,
             },
             Backend {
                 provider: "provider3".to_string(),
@@ -845,6 +2143,18 @@ mod tests {
                 enabled: true,
                 tags: vec![],
                 billing_mode: BillingMode::PerToken,
+                pricing: None,
+                overrides: HashMap::new(),
+                slow_start: None,
+                concurrency: None,
+                schedule: None,
+                region: None,
+                max_context: None,
+                supports_vision: true,
+                supports_tools: true,
+                supports_json_mode: true,
+                supports_streaming: true,
+                supports_responses: false,
             },
         ]
     }
@@ -855,6 +2165,21 @@ mod tests {
             backends: create_test_backends(),
             strategy: LoadBalanceStrategy::WeightedFailover,
             enabled: true,
+            virtual_nodes: 100,
+            semantic_cache: true,
+            budget: None,
+            fallback_models: Vec::new(),
+            transforms: Vec::new(),
+            hedging: None,
+            max_in_flight_requests: None,
+            mirror: None,
+            experiment: None,
+            retry: None,
+            latency_slo_ms: None,
+            structured_output: None,
+            fair_queue: None,
+            timeouts: None,
+            content_filters: Vec::new(),
         }
     }
 
@@ -862,33 +2187,23 @@ mod tests {
     fn test_weighted_failover_all_healthy() {
         let metrics = Arc::new(MetricsCollector::new());
         let mapping = create_test_mapping();
-        let selector = BackendSelector::new(mapping, metrics.clone());
+        let selector = BackendSelector::with_seed(mapping, metrics.clone(), 7);
 
         // 标记所有后端为健康
         metrics.record_success("provider1:model1");
         metrics.record_success("provider2:model2");
         metrics.record_success("provider3:model3");
 
-        // 多次选择，验证权重分布
-        let mut selections = std::collections::HashMap::new();
+        // 固定种子下的选择分布按权重0.6 : 0.3 : 0.1
+        let mut selections: HashMap<String, usize> = HashMap::new();
         for _ in 0..1000 {
-            let backend = selector.select().unwrap();
-            let key = format!("{}:{}", backend.provider, backend.model);
-            *selections.entry(key).or_insert(0) += 1;
+            *selections.entry(selector.select().unwrap().provider).or_default() += 1;
         }
-
-        // 验证选择分布大致符合权重比例
-        assert!(selections.contains_key("provider1:model1"));
-        assert!(selections.contains_key("provider2:model2"));
-        assert!(selections.contains_key("provider3:model3"));
-
-        // provider1应该被选择最多（权重0.6）
-        let provider1_count = selections.get("provider1:model1").unwrap_or(&0);
-        let provider2_count = selections.get("provider2:model2").unwrap_or(&0);
-        let provider3_count = selections.get("provider3:model3").unwrap_or(&0);
-
-        assert!(provider1_count > provider2_count);
-        assert!(provider2_count > provider3_count);
+        assert_eq!(selections, HashMap::from([
+            ("provider1".to_string(), 606),
+            ("provider2".to_string(), 293),
+            ("provider3".to_string(), 101),
+        ]));
     }
 
     #[test]
@@ -921,17 +2236,663 @@ mod tests {
     fn test_weighted_failover_all_failed() {
         let metrics = Arc::new(MetricsCollector::new());
         let mapping = create_test_mapping();
-        let selector = BackendSelector::new(mapping, metrics.clone());
+        let selector = BackendSelector::with_seed(mapping, metrics.clone(), 7);
 
         // 标记所有后端为不健康
         metrics.record_failure("provider1:model1");
         metrics.record_failure("provider2:model2");
         metrics.record_failure("provider3:model3");
 
-        // 应该选择优先级最高的后端（priority=1）
+        // 全部不健康时仍按权重从所有后端中选择（而非优先级），保证请求可以继续尝试
+        let mut selections: HashMap<String, usize> = HashMap::new();
+        for _ in 0..1000 {
+            *selections.entry(selector.select().unwrap().provider).or_default() += 1;
+        }
+        assert_eq!(selections, HashMap::from([
+            ("provider1".to_string(), 606),
+            ("provider2".to_string(), 293),
+            ("provider3".to_string(), 101),
+        ]));
+    }
+
+    #[test]
+    fn test_sticky_session_consistent_routing() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let mut mapping = create_test_mapping();
+        mapping.strategy = LoadBalanceStrategy::StickySession;
+        let selector = BackendSelector::new(mapping, metrics.clone());
+
+        let context = SelectionContext {
+            session_key: Some("session-abc".to_string()),
+            ..Default::default()
+        };
+
+        // 同一会话多次选择应始终命中同一后端
+        let first = selector.select_with_context(&context).unwrap();
+        for _ in 0..50 {
+            let backend = selector.select_with_context(&context).unwrap();
+            assert_eq!(backend.provider, first.provider);
+            assert_eq!(backend.model, first.model);
+        }
+    }
+
+    #[test]
+    fn test_sticky_session_fallback_when_unhealthy() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let mut mapping = create_test_mapping();
+        mapping.strategy = LoadBalanceStrategy::StickySession;
+        let selector = BackendSelector::new(mapping, metrics.clone());
+
+        let context = SelectionContext {
+            session_key: Some("session-abc".to_string()),
+            ..Default::default()
+        };
+        let sticky = selector.select_with_context(&context).unwrap();
+
+        // 粘性后端不健康后，应回退到其他健康后端
+        metrics.record_failure(&format!("{}:{}", sticky.provider, sticky.model));
+        for _ in 0..50 {
+            let backend = selector.select_with_context(&context).unwrap();
+            assert_ne!(backend.provider, sticky.provider);
+        }
+    }
+
+    #[test]
+    fn test_consistent_hash_rebalances_only_unhealthy_backend() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let mut mapping = create_test_mapping();
+        mapping.strategy = LoadBalanceStrategy::ConsistentHash;
+        let selector = BackendSelector::new(mapping, metrics.clone());
+
+        let route = |fingerprint: &str| {
+            let context = SelectionContext {
+                conversation_fingerprint: Some(fingerprint.to_string()),
+                ..Default::default()
+            };
+            let backend = selector.select_with_context(&context).unwrap();
+            format!("{}:{}", backend.provider, backend.model)
+        };
+
+        let fingerprints: Vec<String> = (0..200).map(|i| format!("conversation-{}", i)).collect();
+        let before: Vec<String> = fingerprints.iter().map(|f| route(f)).collect();
+
+        // 同一对话指纹应稳定命中同一后端
+        for (fingerprint, backend) in fingerprints.iter().zip(&before) {
+            assert_eq!(&route(fingerprint), backend);
+        }
+
+        // 标记一个后端不健康，只有原本落在它上面的对话需要迁移
+        metrics.record_failure("provider1:model1");
+        for (fingerprint, backend) in fingerprints.iter().zip(&before) {
+            let after = route(fingerprint);
+            if backend == "provider1:model1" {
+                assert_ne!(after, "provider1:model1");
+            } else {
+                assert_eq!(&after, backend);
+            }
+        }
+    }
+
+
+    #[test]
+    fn test_least_connections_prefers_fewest_in_flight() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let mut mapping = create_test_mapping();
+        mapping.strategy = LoadBalanceStrategy::LeastConnections;
+        let selector = BackendSelector::new(mapping, metrics.clone());
+
+        // 在途数相同时按权重选择（provider1权重最高）
         let backend = selector.select().unwrap();
         assert_eq!(backend.provider, "provider1");
-        assert_eq!(backend.model, "model1");
-        assert_eq!(backend.priority, 1);
+
+        let _p1 = metrics.begin_request("provider1:model1");
+        let _p2 = metrics.begin_request("provider2:model2");
+        let backend = selector.select().unwrap();
+        assert_eq!(backend.provider, "provider3");
+
+        // 守卫释放后计数恢复
+        {
+            let _p3a = metrics.begin_request("provider3:model3");
+            let _p3b = metrics.begin_request("provider3:model3");
+            assert_eq!(metrics.get_in_flight("provider3", "model3"), 2);
+            let backend = selector.select().unwrap();
+            assert_eq!(backend.provider, "provider1");
+        }
+        assert_eq!(metrics.get_in_flight("provider3", "model3"), 0);
+        let backend = selector.select().unwrap();
+        assert_eq!(backend.provider, "provider3");
+    }
+
+
+    #[test]
+    fn test_weighted_least_latency_balances_weight_and_latency() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let mut mapping = create_test_mapping();
+        mapping.strategy = LoadBalanceStrategy::WeightedLeastLatency;
+        mapping.backends.truncate(2);
+        mapping.backends[0].weight = 2.0;
+        mapping.backends[1].weight = 1.0;
+        let selector = BackendSelector::with_seed(mapping, metrics.clone(), 7);
+
+        // provider1权重是provider2的2倍，但延迟是其10倍，得分 2/1.0 : 1/0.1 = 1 : 5
+        metrics.record_latency("provider1:model1", Duration::from_millis(1000));
+        metrics.record_latency("provider2:model2", Duration::from_millis(100));
+
+        // 慢的后端仍然分到少量请求
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for _ in 0..3000 {
+            *counts.entry(selector.select().unwrap().provider).or_default() += 1;
+        }
+        assert_eq!(counts, HashMap::from([("provider1".to_string(), 532), ("provider2".to_string(), 2468)]));
+    }
+
+    #[test]
+    fn test_seeded_selector_is_reproducible() {
+        for strategy in [
+            LoadBalanceStrategy::WeightedRandom,
+            LoadBalanceStrategy::Random,
+            LoadBalanceStrategy::WeightedLeastLatency,
+        ] {
+            let mut mapping = create_test_mapping();
+            mapping.strategy = strategy;
+            let picks = |seed| {
+                let selector = BackendSelector::with_seed(mapping.clone(), Arc::new(MetricsCollector::new()), seed);
+                (0..50).map(|_| selector.select().unwrap().provider).collect::<Vec<_>>()
+            };
+            let first = picks(42);
+            assert_eq!(first, picks(42));
+            assert!(first.iter().any(|p| p != &first[0]), "seeded picks should still vary");
+        }
+    }
+
+
+    #[test]
+    fn test_cost_optimized_escalates_when_cheap_backend_is_slow_or_saturated() {
+        use crate::config::model::{BackendPricing, ConcurrencyLimit};
+
+        let metrics = Arc::new(MetricsCollector::new());
+        let mut mapping = create_test_mapping();
+        mapping.strategy = LoadBalanceStrategy::CostOptimized;
+        mapping.latency_slo_ms = Some(500);
+        for (backend, price) in mapping.backends.iter_mut().zip([3.0, 1.0, 2.0]) {
+            backend.pricing = Some(BackendPricing {
+                prompt_per_1k: price,
+                completion_per_1k: price,
+            });
+        }
+        mapping.backends[1].concurrency = Some(ConcurrencyLimit {
+            max_concurrent_requests: 1,
+            max_queue: 0,
+            queue_timeout_ms: 0,
+        });
+        let selector = BackendSelector::new(mapping, metrics.clone());
+
+        // 最便宜的provider2优先
+        assert_eq!(selector.select().unwrap().provider, "provider2");
+
+        // provider2达到并发上限时升级到次便宜的provider3
+        {
+            let _busy = metrics.begin_request("provider2:model2");
+            assert_eq!(selector.select().unwrap().provider, "provider3");
+        }
+
+        // provider2的尾延迟超出SLO时同样升级
+        for _ in 0..20 {
+            metrics.record_latency("provider2:model2", Duration::from_millis(900));
+        }
+        assert_eq!(metrics.get_tail_latency("provider2", "model2"), Some(Duration::from_millis(900)));
+        assert_eq!(selector.select().unwrap().provider, "provider3");
+
+        // 都不满足SLO时选择尾延迟最低的
+        metrics.record_latency("provider1:model1", Duration::from_millis(600));
+        metrics.record_latency("provider3:model3", Duration::from_millis(800));
+        assert_eq!(selector.select().unwrap().provider, "provider1");
+    }
+
+
+    #[test]
+    fn test_out_of_schedule_backends_are_skipped() {
+        use crate::config::model::BackendSchedule;
+        use chrono::Datelike;
+
+        let metrics = Arc::new(MetricsCollector::new());
+        let mut mapping = create_test_mapping();
+        mapping.backends.truncate(2);
+        // 只在后天生效的后端当前视为禁用
+        let later = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"]
+            [(chrono::Utc::now().weekday().num_days_from_monday() as usize + 2) % 7];
+        mapping.backends[0].schedule = Some(BackendSchedule {
+            timezone: "UTC".to_string(),
+            windows: vec![format!("{} 00:00-24:00", later)],
+        });
+        mapping.backends[1].schedule = Some(BackendSchedule {
+            timezone: "UTC".to_string(),
+            windows: vec!["* 00:00-24:00".to_string()],
+        });
+        let selector = BackendSelector::new(mapping.clone(), metrics.clone());
+        for _ in 0..20 {
+            assert_eq!(selector.select().unwrap().provider, "provider2");
+        }
+
+        mapping.backends.truncate(1);
+        let selector = BackendSelector::new(mapping, metrics);
+        let error = selector.select().unwrap_err();
+        assert!(error.to_string().contains("outside their scheduled hours"));
+    }
+
+
+    #[test]
+    fn test_region_preference_and_cross_region_failover() {
+        let metrics = Arc::new(MetricsCollector::new());
+        metrics.set_region_routing(Some(RegionRoutingSettings {
+            default_penalty_ms: 500,
+            penalties: HashMap::from([("us-east->us-west".to_string(), 50)]),
+        }));
+        let mut mapping = create_test_mapping();
+        mapping.strategy = LoadBalanceStrategy::WeightedRandom;
+        for (backend, region) in mapping.backends.iter_mut().zip(["us-east", "eu-west", "us-west"]) {
+            backend.region = Some(region.to_string());
+        }
+        let selector = BackendSelector::new(mapping, metrics.clone());
+        let context = SelectionContext {
+            region: Some("us-east".to_string()),
+            ..Default::default()
+        };
+
+        for _ in 0..20 {
+            assert_eq!(selector.select_with_context(&context).unwrap().provider, "provider1");
+        }
+
+        // 同区域后端不健康时跨区域到惩罚最小的us-west
+        metrics.record_failure("provider1:model1");
+        for _ in 0..20 {
+            assert_eq!(selector.select_with_context(&context).unwrap().provider, "provider3");
+        }
+
+        // us-west明显更慢时，延迟加惩罚更小的eu-west胜出
+        metrics.record_latency("provider3:model3", Duration::from_secs(2));
+        metrics.record_latency("provider2:model2", Duration::from_millis(100));
+        assert_eq!(selector.select_with_context(&context).unwrap().provider, "provider2");
+    }
+
+
+    #[test]
+    fn test_context_length_filters_small_backends() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let mut mapping = create_test_mapping();
+        mapping.backends[0].max_context = Some(8_000);
+        mapping.backends[1].max_context = Some(128_000);
+        mapping.backends[2].max_context = Some(32_000);
+        let selector = BackendSelector::new(mapping, metrics);
+
+        let context = SelectionContext {
+            context_tokens: Some(50_000),
+            ..Default::default()
+        };
+        for _ in 0..20 {
+            assert_eq!(selector.select_with_context(&context).unwrap().provider, "provider2");
+        }
+
+        let context = SelectionContext {
+            context_tokens: Some(200_000),
+            ..Default::default()
+        };
+        let error = selector.select_with_context(&context).unwrap_err();
+        assert!(error.to_string().contains("context window of 200000 tokens"));
+    }
+
+
+    #[test]
+    fn test_capabilities_filter_backends() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let mut mapping = create_test_mapping();
+        mapping.backends[0].supports_vision = false;
+        mapping.backends[1].supports_tools = false;
+        mapping.backends[2].supports_streaming = false;
+        let selector = BackendSelector::new(mapping, metrics);
+
+        let body = serde_json::json!({
+            "stream": true,
+            "tools": [{"type": "function", "function": {"name": "lookup"}}],
+            "messages": [{"role": "user", "content": [
+                {"type": "text", "text": "what is this?"},
+                {"type": "image_url", "image_url": {"url": "https://example.com/a.png"}}
+            ]}]
+        });
+        let capabilities = RequiredCapabilities::from_request(&body);
+        assert_eq!(capabilities.names(), vec!["vision", "tools", "streaming"]);
+
+        // 只要求图片和工具时只有provider3满足
+        let context = SelectionContext {
+            capabilities: RequiredCapabilities { streaming: false, ..capabilities },
+            ..Default::default()
+        };
+        for _ in 0..20 {
+            assert_eq!(selector.select_with_context(&context).unwrap().provider, "provider3");
+        }
+
+        let context = SelectionContext {
+            capabilities,
+            ..Default::default()
+        };
+        let error = selector.select_with_context(&context).unwrap_err();
+        assert!(error.to_string().contains("No enabled backends support vision, tools, streaming"));
+
+        let json_mode = RequiredCapabilities::from_request(&serde_json::json!({
+            "response_format": {"type": "json_object"},
+            "tools": []
+        }));
+        assert_eq!(json_mode.names(), vec!["json_mode"]);
+    }
+
+
+    #[test]
+    fn test_latency_ewma_smoothing() {
+        let metrics = MetricsCollector::new();
+        metrics.set_latency_alpha(0.5);
+
+        metrics.record_latency("provider1:model1", Duration::from_millis(100));
+        assert_eq!(metrics.get_latency("provider1", "model1"), Some(Duration::from_millis(100)));
+
+        // 单次慢请求只按alpha比例拉高平均延迟
+        metrics.record_latency("provider1:model1", Duration::from_millis(1100));
+        assert_eq!(metrics.get_latency("provider1", "model1"), Some(Duration::from_millis(600)));
+
+        // 后续正常请求使平均延迟逐步回落
+        metrics.record_latency("provider1:model1", Duration::from_millis(100));
+        assert_eq!(metrics.get_latency("provider1", "model1"), Some(Duration::from_millis(350)));
+    }
+
+
+    #[test]
+    fn test_tag_filter_restricts_candidates() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let mut mapping = create_test_mapping();
+        mapping.backends[1].tags = vec!["cheap".to_string(), "eu".to_string()];
+        mapping.backends[2].tags = vec!["cheap".to_string()];
+        let selector = BackendSelector::new(mapping, metrics);
+
+        let context = SelectionContext {
+            tags: vec!["cheap".to_string(), "eu".to_string()],
+            ..Default::default()
+        };
+        for _ in 0..50 {
+            let backend = selector.select_with_context(&context).unwrap();
+            assert_eq!(backend.provider, "provider2");
+        }
+
+        let context = SelectionContext {
+            tags: vec!["us".to_string()],
+            ..Default::default()
+        };
+        let error = selector.select_with_context(&context).unwrap_err();
+        assert!(error.to_string().contains("match tags [us]"));
+    }
+
+
+    #[test]
+    fn test_per_request_recovery_follows_default_slow_start() {
+        let metrics = MetricsCollector::new();
+        let key = "provider1:model1";
+
+        metrics.record_failure(key);
+        metrics.initialize_per_request_recovery(key);
+        assert!((metrics.get_effective_weight(key, 1.0) - 0.1).abs() < 1e-9);
+
+        // 默认阶段与原有行为一致：1次成功30%，3次50%，5次完全恢复
+        let expected = [0.3, 0.3, 0.5, 0.5, 1.0];
+        for weight in expected {
+            metrics.record_passive_success(key);
+            assert!((metrics.get_effective_weight(key, 1.0) - weight).abs() < 1e-9);
+        }
+        assert!(!metrics.is_in_unhealthy_list(key));
+        assert!(metrics.is_healthy("provider1", "model1"));
+        assert_eq!(metrics.get_recovery_stage("provider1", "model1"), None);
+    }
+
+    #[test]
+    fn test_per_token_recovery_uses_slow_start() {
+        let metrics = MetricsCollector::new();
+        metrics.set_slow_start_stages(vec![
+            SlowStartStage { weight: 0.2, min_successes: 1, min_duration_seconds: 0 },
+            SlowStartStage { weight: 0.6, min_successes: 2, min_duration_seconds: 0 },
+        ]);
+        let key = "provider1:model1";
+
+        // 健康后端的成功不触发慢启动
+        metrics.record_success(key);
+        assert_eq!(metrics.get_slow_start_weight(key, 2.0), 2.0);
+
+        metrics.record_failure(key);
+        metrics.record_success(key);
+        assert!(metrics.is_healthy("provider1", "model1"));
+        assert_eq!(metrics.get_recovery_stage("provider1", "model1"), Some(1));
+        assert!((metrics.get_slow_start_weight(key, 2.0) - 1.2).abs() < 1e-9);
+
+        metrics.record_success(key);
+        metrics.record_success(key);
+        assert_eq!(metrics.get_recovery_stage("provider1", "model1"), None);
+        assert_eq!(metrics.get_slow_start_weight(key, 2.0), 2.0);
+    }
+
+    #[test]
+    fn test_slow_start_stage_duration() {
+        let clock = Arc::new(ManualClock::new());
+        let metrics = MetricsCollector::with_clock(clock.clone());
+        metrics.set_slow_start_stages(vec![SlowStartStage {
+            weight: 0.5,
+            min_successes: 0,
+            min_duration_seconds: 30,
+        }]);
+        let key = "provider1:model1";
+
+        metrics.start_slow_start(key);
+        assert_eq!(metrics.get_slow_start_weight(key, 1.0), 0.5);
+
+        // 停留时间达到要求后无需成功请求即可完成慢启动
+        clock.advance(Duration::from_secs(29));
+        assert_eq!(metrics.get_slow_start_weight(key, 1.0), 0.5);
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(metrics.get_slow_start_weight(key, 1.0), 1.0);
+        assert_eq!(metrics.get_recovery_stage("provider1", "model1"), None);
+    }
+
+    #[test]
+    fn test_slow_start_reduces_share_in_weighted_random() {
+        let metrics = Arc::new(MetricsCollector::new());
+        metrics.set_slow_start_stages(vec![SlowStartStage {
+            weight: 0.1,
+            min_successes: 100,
+            min_duration_seconds: 0,
+        }]);
+        let mut mapping = create_test_mapping();
+        mapping.strategy = LoadBalanceStrategy::WeightedRandom;
+        for backend in &mut mapping.backends {
+            backend.weight = 1.0;
+        }
+        let selector = BackendSelector::with_seed(mapping, metrics.clone(), 7);
+        metrics.start_slow_start("provider1:model1");
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for _ in 0..3000 {
+            let backend = selector.select().unwrap();
+            *counts.entry(backend.provider).or_default() += 1;
+        }
+        let p1 = counts.get("provider1").copied().unwrap_or(0);
+        let p2 = counts.get("provider2").copied().unwrap_or(0);
+        assert!(p1 > 0);
+        assert!(p2 > p1 * 4, "slow-starting backend should get less traffic: {} vs {}", p1, p2);
+    }
+
+
+    #[test]
+    fn test_slow_start_backend_override() {
+        let metrics = MetricsCollector::new();
+        let mut overrides = HashMap::new();
+        overrides.insert(
+            "provider2:model2".to_string(),
+            vec![SlowStartStage { weight: 0.5, min_successes: 3, min_duration_seconds: 0 }],
+        );
+        metrics.set_slow_start_overrides(overrides);
+
+        // 未覆盖的后端使用全局默认阶段
+        metrics.start_slow_start("provider1:model1");
+        assert!((metrics.get_slow_start_weight("provider1:model1", 1.0) - 0.1).abs() < 1e-9);
+
+        metrics.start_slow_start("provider2:model2");
+        assert_eq!(metrics.get_slow_start_weight("provider2:model2", 1.0), 0.5);
+        metrics.record_success("provider2:model2");
+        metrics.record_success("provider2:model2");
+        assert_eq!(metrics.get_slow_start_weight("provider2:model2", 1.0), 0.5);
+        metrics.record_success("provider2:model2");
+        assert_eq!(metrics.get_recovery_stage("provider2", "model2"), None);
+    }
+
+
+    #[test]
+    fn test_outlier_ejection_by_error_rate() {
+        let clock = Arc::new(ManualClock::new());
+        let metrics = MetricsCollector::with_clock(clock.clone());
+        let key = "provider1:model1";
+
+        // 未启用时不剔除
+        metrics.record_failure(key);
+        assert!(!metrics.record_request_outcome(key, false));
+
+        metrics.set_outlier_detection(Some(OutlierDetectionSettings {
+            error_rate_percent: 50.0,
+            window_seconds: 60,
+            min_requests: 4,
+            ejection_seconds: 30,
+        }));
+        metrics.record_success(key);
+        for success in [true, true, true, false, false] {
+            assert!(!metrics.record_request_outcome(key, success));
+        }
+        // 第6个请求失败时错误率达到50%
+        metrics.record_failure(key);
+        assert!(metrics.record_request_outcome(key, false));
+        assert!(metrics.is_ejected(key));
+        assert_eq!(metrics.get_effective_weight(key, 1.0), 0.0);
+
+        // 剔除期内成功不会恢复健康
+        metrics.record_success(key);
+        assert!(!metrics.is_healthy("provider1", "model1"));
+        assert!(metrics.is_in_unhealthy_list(key));
+
+        // 剔除结束后按正常流程恢复
+        clock.advance(Duration::from_secs(31));
+        metrics.record_success(key);
+        assert!(metrics.is_healthy("provider1", "model1"));
+        assert!(!metrics.is_ejected(key));
+    }
+
+    #[test]
+    fn test_health_score_gates_and_weights_selection() {
+        let metrics = Arc::new(MetricsCollector::new());
+        metrics.set_health_scoring(Some(HealthScoringSettings {
+            min_score: 0.5,
+            window_seconds: 60,
+            min_requests: 4,
+            latency_baseline_alpha: 0.02,
+            weight_by_score: true,
+        }));
+        let selector = BackendSelector::new(create_test_mapping(), metrics.clone());
+
+        // provider1仍被标记为健康，但错误率为75%，低于最低分数
+        for success in [true, false, false, false] {
+            metrics.record_request_outcome("provider1:model1", success);
+        }
+        assert!(metrics.is_healthy("provider1", "model1"));
+        assert_eq!(metrics.get_health_score("provider1", "model1").score, 0.25);
+        assert!(!metrics.meets_health_threshold("provider1", "model1"));
+
+        // provider2两次限流后分数为1/3，同样被跳过
+        metrics.record_rate_limited("provider2:model2");
+        metrics.record_rate_limited("provider2:model2");
+        assert!(!metrics.meets_health_threshold("provider2", "model2"));
+
+        for _ in 0..100 {
+            let backend = selector.select().unwrap();
+            assert_eq!(backend.provider, "provider3");
+        }
+        assert_eq!(metrics.get_score_weight("provider1", "model1", 0.6), 0.6 * 0.25);
+
+        // 关闭健康评分后恢复按健康状态选择
+        metrics.set_health_scoring(None);
+        assert!(metrics.meets_health_threshold("provider1", "model1"));
+        assert_eq!(metrics.get_score_weight("provider1", "model1", 0.6), 0.6);
+    }
+
+    #[test]
+    fn test_cooling_down_backend_is_skipped_by_all_strategies() {
+        let clock = Arc::new(ManualClock::new());
+        let metrics = Arc::new(MetricsCollector::with_clock(clock.clone()));
+        metrics.set_cooldown("provider1:model1", Duration::from_secs(30));
+        assert!(metrics.is_healthy("provider1", "model1"));
+        assert_eq!(metrics.cooldown_remaining("provider1", "model1"), Some(Duration::from_secs(30)));
+
+        for strategy in [
+            LoadBalanceStrategy::WeightedFailover,
+            LoadBalanceStrategy::Failover,
+            LoadBalanceStrategy::StickySession,
+            LoadBalanceStrategy::ConsistentHash,
+        ] {
+            let mut mapping = create_test_mapping();
+            mapping.strategy = strategy;
+            let selector = BackendSelector::new(mapping, metrics.clone());
+            let context = SelectionContext {
+                session_key: Some("session-abc".to_string()),
+                ..Default::default()
+            };
+            for _ in 0..20 {
+                assert_ne!(selector.select_with_context(&context).unwrap().provider, "provider1");
+            }
+        }
+
+        // 冷却结束后重新参与选择
+        clock.advance(Duration::from_secs(31));
+        assert!(!metrics.is_cooling_down("provider1", "model1"));
+        let selector = BackendSelector::with_seed(create_test_mapping(), metrics.clone(), 7);
+        assert!((0..50).any(|_| selector.select().unwrap().provider == "provider1"));
+    }
+
+    #[test]
+    fn test_provider_near_quota_is_avoided_while_others_have_headroom() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let quota = |rpm, tpm| ProviderQuota {
+            requests_per_minute: rpm,
+            tokens_per_minute: tpm,
+            threshold_percent: 80.0,
+        };
+        metrics.set_provider_quotas(HashMap::from([
+            ("provider1".to_string(), quota(Some(10), None)),
+            ("provider2".to_string(), quota(None, Some(1000))),
+        ]));
+        let selector = BackendSelector::new(create_test_mapping(), metrics.clone());
+
+        // 未配置配额的provider不统计用量
+        metrics.record_provider_request("provider3");
+        assert_eq!(metrics.provider_quota_usage("provider3"), None);
+
+        for _ in 0..8 {
+            metrics.record_provider_request("provider1");
+        }
+        assert_eq!(metrics.provider_quota_usage("provider1"), Some(0.8));
+        assert!(metrics.is_near_quota("provider1"));
+        for _ in 0..20 {
+            assert_ne!(selector.select().unwrap().provider, "provider1");
+        }
+
+        // 所有provider都接近配额时仍按原策略选择
+        metrics.record_provider_tokens("provider2", 900);
+        metrics.set_provider_quotas(HashMap::from([
+            ("provider1".to_string(), quota(Some(10), None)),
+            ("provider2".to_string(), quota(None, Some(1000))),
+            ("provider3".to_string(), quota(Some(1), None)),
+        ]));
+        metrics.record_provider_request("provider3");
+        assert!(metrics.is_near_quota("provider2") && metrics.is_near_quota("provider3"));
+        assert!((0..50).any(|_| selector.select().unwrap().provider == "provider1"));
     }
 }
@@ -1,6 +1,13 @@
+use crate::auth::jwt::JwtAuthenticator;
+use crate::auth::keys::TokenVerifier;
+use crate::config::history::{ConfigHistory, ConfigVersion, ConfigVersionSummary};
 use crate::config::model::{Config, Backend};
-use super::{LoadBalanceManager, HealthChecker, MetricsCollector};
+use crate::config::secrets::{SecretResolver, has_secret_references};
+use crate::jobs::JobQueue;
+use super::{AlertManager, BackendSelector, CandidateExplanation, CostTracker, EventBus, ExperimentTracker, FailoverMemory, HealthEventWatcher, HealthNotifier, ModelHealth, SloObjectiveReport, ModelHealthState, ModelHealthTracker, LoadBalanceManager, HealthChecker, MetricsCollector, SelectionContext};
 use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
@@ -12,16 +19,62 @@ pub struct LoadBalanceService {
     manager: Arc<LoadBalanceManager>,
     health_checker: Arc<HealthChecker>,
     metrics: Arc<MetricsCollector>,
+    failover_memory: Arc<FailoverMemory>,
+    model_health: Arc<ModelHealthTracker>,
+    // 后端健康状态变化检测，变化事件用于Webhook通知
+    health_events: Arc<HealthEventWatcher>,
+    cost_tracker: Arc<CostTracker>,
+    experiments: Arc<ExperimentTracker>,
+    events: EventBus,
     is_running: Arc<RwLock<bool>>,
+    // 外部密钥解析器
+    secrets: Arc<SecretResolver>,
+    // 未解析密钥引用的原始配置，刷新密钥时以此为准
+    source_config: Arc<std::sync::RwLock<Arc<Config>>>,
+    // 已应用配置的历史版本
+    history: std::sync::Mutex<ConfigHistory>,
+    // 配置更新锁：读取当前配置、修改并应用的操作依次进行，避免基于过时的配置覆盖其他修改
+    config_updates: Arc<tokio::sync::Mutex<()>>,
+    // JWT认证使用的JWKS缓存
+    jwt: Arc<JwtAuthenticator>,
+    // argon2密钥校验结果缓存
+    token_verifier: Arc<TokenVerifier>,
+    // 持久化任务队列，未配置时为None
+    jobs: Option<Arc<JobQueue>>,
 }
 
+/// 修改配置失败的原因
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigUpdateError {
+    /// 无法按请求修改当前配置
+    #[error("{0:#}")]
+    Invalid(anyhow::Error),
+    /// 修改后的配置未通过校验或应用失败
+    #[error("{0}")]
+    Rejected(anyhow::Error),
+}
+
+/// 未配置settings.secrets时检查是否需要刷新密钥的间隔
+const DEFAULT_SECRETS_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
 impl LoadBalanceService {
     /// 创建新的负载均衡服务
     pub fn new(config: Config) -> Result<Self> {
         // 验证配置
         config.validate()?;
 
-        let manager = Arc::new(LoadBalanceManager::new(config.clone()));
+        let failover_memory = Arc::new(FailoverMemory::new(Duration::from_secs(
+            config.settings.failover_memory_ttl_seconds,
+        )));
+        let model_health = Arc::new(ModelHealthTracker::new(&config.settings));
+        let source_config = Arc::new(std::sync::RwLock::new(Arc::new(config.clone())));
+        let mut history = ConfigHistory::load(&config.settings.config_history);
+        history.record(&config, "startup");
+        let jobs = match &config.settings.job_queue {
+            Some(settings) => Some(Arc::new(JobQueue::open(settings)?)),
+            None => None,
+        };
+        let manager = Arc::new(LoadBalanceManager::new(config.flatten_tenants()));
         let metrics = manager.get_metrics();
         let health_checker = Arc::new(HealthChecker::new(
             manager.get_config(),
@@ -32,7 +85,20 @@ impl LoadBalanceService {
             manager,
             health_checker,
             metrics,
+            failover_memory,
+            model_health,
+            health_events: Arc::new(HealthEventWatcher::new()),
+            cost_tracker: Arc::new(CostTracker::new()),
+            experiments: Arc::new(ExperimentTracker::new()),
+            events: EventBus::new(),
             is_running: Arc::new(RwLock::new(false)),
+            secrets: Arc::new(SecretResolver::new()),
+            source_config,
+            history: std::sync::Mutex::new(history),
+            config_updates: Arc::new(tokio::sync::Mutex::new(())),
+            jwt: Arc::new(JwtAuthenticator::new()),
+            token_verifier: Arc::new(TokenVerifier::new()),
+            jobs,
         })
     }
 
@@ -48,21 +114,55 @@ impl LoadBalanceService {
 
         info!("Starting load balance service");
 
+        // 拉取配置中引用的外部密钥，失败时拒绝启动
+        let runtime = self.source_config().flatten_tenants();
+        if has_secret_references(&runtime) {
+            let applied = match self.secrets.resolve(&runtime).await {
+                Ok(resolved) => self.manager.reload_config(resolved).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = applied {
+                *self.is_running.write().await = false;
+                return Err(e);
+            }
+            self.health_checker.update_config(self.manager.get_config());
+            info!("Resolved secret references from external secret manager");
+        }
+
         // 初始化管理器
         self.manager.initialize().await?;
 
+        // 启动健康状态变化通知和告警，发送前读取最新配置
+        let notifier_manager = self.manager.clone();
+        HealthNotifier::spawn(&self.events, move || notifier_manager.get_config().settings.notifications.clone());
+        let alert_manager = self.manager.clone();
+        AlertManager::spawn(&self.events, move || alert_manager.get_config().settings.alerts.clone());
+
         // 启动健康检查器
         let health_checker = self.health_checker.clone();
         let is_running = self.is_running.clone();
+        let model_health = self.model_health.clone();
+        let health_events = self.health_events.clone();
+        let manager = self.manager.clone();
+        let metrics = self.metrics.clone();
+        let events = self.events.clone();
 
         tokio::spawn(async move {
             while *is_running.read().await {
-                if let Err(e) = health_checker.check_now().await {
+                if let Err(e) = health_checker.check_due().await {
                     error!("Health check failed: {}", e);
                 }
 
-                // 等待下一次检查
-                tokio::time::sleep(Duration::from_secs(30)).await;
+                // 根据最新的后端健康状况更新模型聚合状态
+                let config = manager.get_config();
+                for transition in model_health.evaluate(&config, &metrics) {
+                    events.publish("model_health_changed", serde_json::json!(transition));
+                }
+                health_events.publish(&config, &metrics, &events);
+                publish_slo_transitions(&config, &metrics, &events);
+
+                // 等待下一个provider到达探测间隔
+                tokio::time::sleep(health_checker.next_check_delay()).await;
             }
         });
 
@@ -81,28 +181,178 @@ impl LoadBalanceService {
             }
         });
 
+        // 定期重新拉取外部密钥，密钥轮换后无需重启
+        let manager = self.manager.clone();
+        let health_checker = self.health_checker.clone();
+        let secrets = self.secrets.clone();
+        let source_config = self.source_config.clone();
+        let config_updates = self.config_updates.clone();
+        let events = self.events.clone();
+        let is_running_secrets = self.is_running.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let interval = source_config
+                    .read()
+                    .ok()
+                    .and_then(|config| config.settings.secrets.as_ref().map(|s| s.refresh_interval_seconds))
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_SECRETS_REFRESH_INTERVAL);
+                tokio::time::sleep(interval).await;
+                if !*is_running_secrets.read().await {
+                    break;
+                }
+                let _guard = config_updates.lock().await;
+                let result = Self::rotate_secrets(&manager, &health_checker, &secrets, &source_config).await;
+                Self::publish_rotation(&events, &result);
+            }
+        });
+
+        // 拉取JWT签名公钥并定期刷新，身份提供方不可用时不影响API Key认证
+        self.refresh_jwks().await;
+        let jwt = self.jwt.clone();
+        let manager = self.manager.clone();
+        let is_running_jwt = self.is_running.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let interval = manager
+                    .get_config()
+                    .settings
+                    .jwt
+                    .as_ref()
+                    .map(|jwt| jwt.jwks_refresh_seconds)
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_SECRETS_REFRESH_INTERVAL);
+                tokio::time::sleep(interval).await;
+                if !*is_running_jwt.read().await {
+                    break;
+                }
+                if let Some(settings) = manager.get_config().settings.jwt.clone()
+                    && let Err(e) = jwt.refresh(&settings).await
+                {
+                    warn!("Failed to refresh JWKS, keeping previous keys: {:#}", e);
+                }
+            }
+        });
+
+        // 恢复上次未完成的任务并启动任务队列的worker
+        if let Some(jobs) = &self.jobs {
+            jobs.start().await?;
+        }
+
         info!("Load balance service started successfully");
         Ok(())
     }
 
+    /// 立即重新拉取外部密钥，返回密钥发生变化的provider
+    pub async fn refresh_secrets(&self) -> Result<Vec<String>> {
+        let _guard = self.config_updates.lock().await;
+        let result = Self::rotate_secrets(&self.manager, &self.health_checker, &self.secrets, &self.source_config).await;
+        Self::publish_rotation(&self.events, &result);
+        result
+    }
+
+    /// 拉取最新密钥，与当前生效的配置比较，有变化时应用新配置；调用方需持有配置更新锁
+    async fn rotate_secrets(
+        manager: &LoadBalanceManager,
+        health_checker: &HealthChecker,
+        secrets: &SecretResolver,
+        source_config: &std::sync::RwLock<Arc<Config>>,
+    ) -> Result<Vec<String>> {
+        let source = source_config
+            .read()
+            .map(|config| config.clone())
+            .map_err(|_| anyhow::anyhow!("source config lock poisoned"))?;
+        let runtime = source.flatten_tenants();
+        if !has_secret_references(&runtime) {
+            return Ok(Vec::new());
+        }
+
+        let resolved = secrets.resolve(&runtime).await?;
+        let current = manager.get_config();
+        let mut rotated: Vec<String> = resolved
+            .providers
+            .iter()
+            .filter(|(id, provider)| {
+                current
+                    .providers
+                    .get(*id)
+                    .is_none_or(|current| current.api_key != provider.api_key)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        if rotated.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        manager.reload_config(resolved).await?;
+        health_checker.update_config(manager.get_config());
+        rotated.sort();
+        Ok(rotated)
+    }
+
+    fn publish_rotation(events: &EventBus, result: &Result<Vec<String>>) {
+        match result {
+            Ok(rotated) if !rotated.is_empty() => {
+                info!("Rotated api keys for providers: {}", rotated.join(", "));
+                events.publish("secrets_rotated", serde_json::json!({ "providers": rotated }));
+            }
+            Ok(_) => debug!("Secret references unchanged"),
+            Err(e) => {
+                warn!("Failed to refresh secrets, keeping previous api keys: {:#}", e);
+                events.publish("secret_refresh_failed", serde_json::json!({ "error": format!("{:#}", e) }));
+            }
+        }
+    }
+
+    /// 未解析密钥引用的原始配置
+    pub fn source_config(&self) -> Arc<Config> {
+        self.source_config
+            .read()
+            .map(|config| config.clone())
+            .unwrap_or_else(|_| self.manager.get_config())
+    }
+
     /// 停止负载均衡服务
     pub async fn stop(&self) {
         let mut running = self.is_running.write().await;
         *running = false;
+        if let Some(jobs) = &self.jobs {
+            jobs.stop();
+        }
         info!("Load balance service stopped");
     }
 
     /// 为指定模型选择后端（带智能重试）
     pub async fn select_backend(&self, model_name: &str) -> Result<SelectedBackend> {
+        self.select_backend_with_context(model_name, &SelectionContext::default()).await
+    }
+
+    /// 根据请求上下文为指定模型选择后端（带智能重试）
+    pub async fn select_backend_with_context(
+        &self,
+        model_name: &str,
+        context: &SelectionContext,
+    ) -> Result<SelectedBackend> {
         let start_time = Instant::now();
         let max_retries = self.manager.get_config().settings.max_internal_retries;
 
+        if let Some(pinned) = &context.pinned_backend {
+            return self.select_pinned_backend(model_name, pinned, start_time);
+        }
+
+        // 该客户端近期发生过故障转移且原后端尚未恢复时，直接使用备用后端
+        if let Some(selected) = self.select_remembered_failover(model_name, context, start_time) {
+            return Ok(selected);
+        }
+
         debug!("Selecting backend for model: {} (max retries: {})", model_name, max_retries);
 
         for attempt in 0..=max_retries {
             debug!("Backend selection attempt {} for model '{}'", attempt + 1, model_name);
 
-            match self.manager.select_backend(model_name).await {
+            match self.manager.select_backend_with_context(model_name, context).await {
                 Ok(backend) => {
                     debug!("Load balancer selected backend: {}:{}", backend.provider, backend.model);
 
@@ -130,6 +380,7 @@ impl LoadBalanceService {
 
                         debug!("Successfully resolved provider config for: {}", backend.provider);
                         return Ok(SelectedBackend {
+                            timeouts: config.resolve_timeouts(model_name, provider),
                             backend,
                             provider: provider.clone(),
                             selection_time,
@@ -151,6 +402,7 @@ impl LoadBalanceService {
                             .ok_or_else(|| anyhow::anyhow!("Provider '{}' not found", backend.provider))?;
 
                         return Ok(SelectedBackend {
+                            timeouts: config.resolve_timeouts(model_name, provider),
                             backend,
                             provider: provider.clone(),
                             selection_time,
@@ -206,6 +458,182 @@ impl LoadBalanceService {
         )
     }
 
+    /// 为请求对冲选择与首个后端不同的健康后端，优先级最高、权重最大者优先
+    pub fn select_hedge_backend(
+        &self,
+        model_name: &str,
+        primary: &Backend,
+        context: &SelectionContext,
+    ) -> Option<SelectedBackend> {
+        // 指定了后端的请求不发送对冲请求
+        if context.pinned_backend.is_some() {
+            return None;
+        }
+
+        let start_time = Instant::now();
+        let config = self.manager.get_config();
+        let (_, mapping) = config.resolve_model(model_name)?;
+
+        let backend = mapping
+            .backends
+            .iter()
+            .filter(|b| b.enabled && b.is_in_schedule() && context.matches_tags(b) && context.fits_context(b) && context.supports_capabilities(b))
+            .filter(|b| b.provider != primary.provider || b.model != primary.model)
+            .filter(|b| {
+                self.metrics.meets_health_threshold(&b.provider, &b.model)
+                    && !self.metrics.is_backend_admin_disabled(&b.provider, &b.model)
+                    && !self.metrics.is_cooling_down(&b.provider, &b.model)
+                    && !self.metrics.is_near_quota(&b.provider)
+                    && !self.metrics.is_key_exhausted(&b.provider)
+            })
+            .min_by(|a, b| {
+                a.priority
+                    .cmp(&b.priority)
+                    .then_with(|| b.weight.total_cmp(&a.weight))
+            })?
+            .clone();
+        let provider = config.get_provider(&backend.provider)?.clone();
+
+        Some(SelectedBackend {
+            timeouts: config.resolve_timeouts(model_name, &provider),
+            backend,
+            provider,
+            selection_time: start_time.elapsed(),
+        })
+    }
+
+    /// 直接使用请求指定的后端，不检查健康状态、冷却和配额，便于复现特定后端的问题
+    fn select_pinned_backend(
+        &self,
+        model_name: &str,
+        pinned: &str,
+        start_time: Instant,
+    ) -> Result<SelectedBackend> {
+        let config = self.manager.get_config();
+        let (_, mapping) = config
+            .resolve_model(model_name)
+            .ok_or_else(|| anyhow::anyhow!("Model '{}' not found in configuration", model_name))?;
+        let backend = mapping
+            .backends
+            .iter()
+            .find(|b| format!("{}:{}", b.provider, b.model) == pinned)
+            .ok_or_else(|| {
+                anyhow::anyhow!("Pinned backend '{}' is not configured for model '{}'", pinned, model_name)
+            })?
+            .clone();
+        let provider = config
+            .get_provider(&backend.provider)
+            .ok_or_else(|| anyhow::anyhow!("Provider '{}' not found", backend.provider))?
+            .clone();
+
+        debug!("Using pinned backend {} for model '{}'", pinned, model_name);
+        Ok(SelectedBackend {
+            timeouts: config.resolve_timeouts(model_name, &provider),
+            backend,
+            provider,
+            selection_time: start_time.elapsed(),
+        })
+    }
+
+    /// 根据故障转移记忆选择后端
+    /// 管理员覆盖了负载均衡策略时不使用记忆；记住的后端按当前配置查找，已删除或停用时清除记忆
+    fn select_remembered_failover(
+        &self,
+        model_name: &str,
+        context: &SelectionContext,
+        start_time: Instant,
+    ) -> Option<SelectedBackend> {
+        if context.strategy_override.is_some() {
+            return None;
+        }
+        let client_key = context.client_key.as_deref()?;
+        let entry = self.failover_memory.lookup(client_key, model_name)?;
+        let failed = &entry.failed_backend;
+
+        let config = self.manager.get_config();
+        let live = config
+            .resolve_model(model_name)
+            .filter(|(_, mapping)| mapping.enabled)
+            .and_then(|(_, mapping)| {
+                mapping.backends.iter().find(|b| {
+                    b.enabled
+                        && b.provider == entry.fallback_backend.provider
+                        && b.model == entry.fallback_backend.model
+                })
+            })
+            .filter(|b| config.get_provider(&b.provider).is_some_and(|p| p.enabled));
+        let Some(fallback) = live.cloned() else {
+            debug!(
+                "Remembered fallback backend {}:{} is no longer configured, dropping failover memory for model '{}'",
+                entry.fallback_backend.provider, entry.fallback_backend.model, model_name
+            );
+            self.failover_memory.forget(client_key, model_name);
+            return None;
+        };
+
+        // 记住的后端不满足本次请求的标签、上下文长度或能力要求时，本次不使用（不清除记忆）
+        if !context.matches_tags(&fallback) || !context.fits_context(&fallback) || !context.supports_capabilities(&fallback) {
+            return None;
+        }
+
+        if self.metrics.meets_health_threshold(&failed.provider, &failed.model) {
+            debug!(
+                "Backend {}:{} recovered, dropping failover memory for model '{}'",
+                failed.provider, failed.model, model_name
+            );
+            self.failover_memory.forget(client_key, model_name);
+            return None;
+        }
+
+        if !self.metrics.meets_health_threshold(&fallback.provider, &fallback.model)
+            || self.metrics.is_backend_admin_disabled(&fallback.provider, &fallback.model)
+            || !fallback.is_in_schedule()
+            || self.metrics.is_cooling_down(&fallback.provider, &fallback.model)
+            || self.metrics.is_key_exhausted(&fallback.provider)
+        {
+            debug!(
+                "Remembered fallback backend {}:{} is unavailable, dropping failover memory for model '{}'",
+                fallback.provider, fallback.model, model_name
+            );
+            self.failover_memory.forget(client_key, model_name);
+            return None;
+        }
+
+        let provider = config.get_provider(&fallback.provider)?.clone();
+
+        debug!(
+            "Using remembered failover backend {}:{} for model '{}' (original {}:{} still unhealthy)",
+            fallback.provider, fallback.model, model_name, failed.provider, failed.model
+        );
+
+        Some(SelectedBackend {
+            timeouts: config.resolve_timeouts(model_name, &provider),
+            backend: fallback,
+            provider,
+            selection_time: start_time.elapsed(),
+        })
+    }
+
+    /// 记录客户端的故障转移，后续请求在原后端恢复前直接路由到备用后端
+    pub fn remember_failover(
+        &self,
+        client_key: &str,
+        model_name: &str,
+        failed_backend: &Backend,
+        fallback_backend: &Backend,
+    ) {
+        info!(
+            "Remembering failover for model '{}': {}:{} -> {}:{}",
+            model_name,
+            failed_backend.provider,
+            failed_backend.model,
+            fallback_backend.provider,
+            fallback_backend.model
+        );
+        self.failover_memory
+            .remember(client_key, model_name, failed_backend, fallback_backend);
+    }
+
     /// 记录请求结果
     pub async fn record_request_result(
         &self,
@@ -216,6 +644,7 @@ impl LoadBalanceService {
         match result {
             RequestResult::Success { latency } => {
                 let backend_key = format!("{}:{}", provider, model);
+                self.metrics.record_request_outcome(&backend_key, true);
 
                 // 检查backend的计费模式
                 let config = self.manager.get_config();
@@ -223,7 +652,7 @@ impl LoadBalanceService {
                 let mut found_backend = false;
 
                 // 查找对应的backend配置
-                for (_, model_mapping) in &config.models {
+                for model_mapping in config.models.values() {
                     for backend in &model_mapping.backends {
                         if backend.provider == provider && backend.model == model {
                             backend_billing_mode = backend.billing_mode.clone();
@@ -255,8 +684,7 @@ impl LoadBalanceService {
                         // 按请求计费：检查是否在不健康列表中
                         if self.metrics.is_in_unhealthy_list(&backend_key) {
                             // 不健康的按请求计费backend：使用被动验证
-                            self.metrics.record_passive_success(&backend_key,
-                                self.get_backend_original_weight(provider, model).unwrap_or(1.0));
+                            self.metrics.record_passive_success(&backend_key);
                             debug!(
                                 "Recorded passive success for per-request backend {}:{} (weight recovery)",
                                 provider, model
@@ -274,6 +702,17 @@ impl LoadBalanceService {
                     }
                 }
             }
+            RequestResult::RateLimited { retry_after } => {
+                info!(
+                    "Backend {}:{} is rate limited upstream, cooling down for {}ms",
+                    provider,
+                    model,
+                    retry_after.as_millis()
+                );
+                let backend_key = format!("{}:{}", provider, model);
+                self.metrics.set_cooldown(&backend_key, retry_after);
+                self.metrics.record_rate_limited(&backend_key);
+            }
             RequestResult::Failure { error } => {
                 self.manager.record_failure(provider, model);
                 debug!(
@@ -282,6 +721,8 @@ impl LoadBalanceService {
                     model,
                     error
                 );
+                self.metrics
+                    .record_request_outcome(&format!("{}:{}", provider, model), false);
 
                 // 对于按请求计费的backend，失败时需要初始化权重恢复状态
                 let config = self.manager.get_config();
@@ -289,7 +730,7 @@ impl LoadBalanceService {
                 let mut found_backend = false;
 
                 // 查找对应的backend配置
-                for (_, model_mapping) in &config.models {
+                for model_mapping in config.models.values() {
                     for backend in &model_mapping.backends {
                         if backend.provider == provider && backend.model == model {
                             backend_billing_mode = backend.billing_mode.clone();
@@ -304,9 +745,8 @@ impl LoadBalanceService {
 
                 if found_backend && backend_billing_mode == crate::config::model::BillingMode::PerRequest {
                     let backend_key = format!("{}:{}", provider, model);
-                    let original_weight = self.get_backend_original_weight(provider, model).unwrap_or(1.0);
-                    self.metrics.initialize_per_request_recovery(&backend_key, original_weight);
-                    debug!("Initialized per-request recovery for {}:{} with 10% weight", provider, model);
+                    self.metrics.initialize_per_request_recovery(&backend_key);
+                    debug!("Initialized per-request recovery for {}:{}", provider, model);
                 }
             }
         }
@@ -322,14 +762,45 @@ impl LoadBalanceService {
         let health_summary = self.health_checker.get_health_summary();
         let model_stats = self.manager.get_health_stats().await;
         let is_running = *self.is_running.read().await;
+        let requests = self.metrics.get_request_stats();
 
         ServiceHealth {
             is_running,
             health_summary,
             model_stats,
-            total_requests: 0, // TODO: 实现请求计数
-            successful_requests: 0, // TODO: 实现成功请求计数
+            total_requests: requests.global.total_requests,
+            successful_requests: requests.global.successful_requests,
+            failed_requests: requests.global.failed_requests,
+            request_rates: requests.global.rates,
+            model_requests: requests.models,
+        }
+    }
+
+    /// 重新评估并获取所有模型的聚合健康状态
+    pub fn get_model_health(&self) -> std::collections::HashMap<String, ModelHealth> {
+        let config = self.manager.get_config();
+        for transition in self.model_health.evaluate(&config, &self.metrics) {
+            self.events
+                .publish("model_health_changed", serde_json::json!(transition));
         }
+        self.health_events.publish(&config, &self.metrics, &self.events);
+        self.model_health.get_all()
+    }
+
+    /// 获取指定模型（ID或名称）的聚合健康状态
+    pub fn get_model_health_state(&self, model_name: &str) -> ModelHealthState {
+        let config = self.manager.get_config();
+        let model_id = if config.models.contains_key(model_name) {
+            model_name
+        } else {
+            config
+                .models
+                .iter()
+                .find(|(_, m)| m.name == model_name)
+                .map(|(id, _)| id.as_str())
+                .unwrap_or(model_name)
+        };
+        self.model_health.get_state(model_id)
     }
 
     /// 手动触发健康检查
@@ -338,17 +809,154 @@ impl LoadBalanceService {
     }
 
     /// 重新加载配置
+    /// 新配置校验失败时保持原配置不变；应用过程中出错时回滚到原配置
     pub async fn reload_config(&self, new_config: Config) -> Result<()> {
+        self.apply_config(new_config, "reload").await.map(|_| ())
+    }
+
+    /// 应用新配置并记入配置历史，返回生效的版本号；source说明配置来源（reload、patch等）
+    pub async fn apply_config(&self, new_config: Config, source: &str) -> Result<u64> {
+        let _guard = self.config_updates.lock().await;
+        self.apply_config_locked(new_config, source).await
+    }
+
+    /// 在配置更新锁内读取当前的原始配置，按update修改后应用，返回生效的版本号和修改后的配置
+    pub async fn update_config(
+        &self,
+        source: &str,
+        update: impl FnOnce(&Config) -> Result<Config>,
+    ) -> std::result::Result<(u64, Config), ConfigUpdateError> {
+        let _guard = self.config_updates.lock().await;
+        let new_config = update(&self.source_config()).map_err(ConfigUpdateError::Invalid)?;
+        let version = self
+            .apply_config_locked(new_config.clone(), source)
+            .await
+            .map_err(ConfigUpdateError::Rejected)?;
+        Ok((version, new_config))
+    }
+
+    /// 应用新配置，调用方需持有配置更新锁
+    async fn apply_config_locked(&self, new_config: Config, source: &str) -> Result<u64> {
         info!("Reloading load balance service configuration");
-        
+
         // 验证新配置
         new_config.validate()?;
-        
+
+        // 租户展开到全局配置后拉取引用的外部密钥，失败时保留当前配置
+        let runtime = new_config.flatten_tenants();
+        let resolved = if has_secret_references(&runtime) {
+            self.secrets.resolve(&runtime).await?
+        } else {
+            runtime
+        };
+
+        let previous_config = self.manager.get_config();
+
         // 重新加载管理器配置
-        self.manager.reload_config(new_config).await?;
-        
-        info!("Configuration reloaded successfully");
-        Ok(())
+        if let Err(e) = self.manager.reload_config(resolved).await {
+            error!("Failed to apply new configuration, rolling back: {}", e);
+            self.manager.reload_config((*previous_config).clone()).await?;
+            return Err(e);
+        }
+        let version = self
+            .history
+            .lock()
+            .map(|mut history| history.record(&new_config, source))
+            .unwrap_or_default();
+        if let Ok(mut current) = self.source_config.write() {
+            *current = Arc::new(new_config);
+        }
+
+        // 记住的后端可能已被修改或删除，TTL也可能改变
+        self.failover_memory
+            .reset(Duration::from_secs(self.manager.get_config().settings.failover_memory_ttl_seconds));
+
+        // 健康检查器使用新的provider列表
+        self.health_checker.update_config(self.manager.get_config());
+        self.refresh_jwks().await;
+
+        info!("Configuration reloaded successfully (version {})", version);
+        Ok(version)
+    }
+
+    /// 已应用配置的历史版本摘要，最新的在前
+    pub fn config_history(&self) -> Vec<ConfigVersionSummary> {
+        self.history.lock().map(|history| history.list()).unwrap_or_default()
+    }
+
+    /// 获取指定的历史版本
+    pub fn config_version(&self, version: u64) -> Option<Arc<ConfigVersion>> {
+        self.history.lock().ok()?.get(version)
+    }
+
+    /// 回滚到指定的历史版本，未指定时回滚到当前版本之前的一个版本
+    /// 回滚同样作为新版本记入历史，返回回滚的目标版本号和新的版本号
+    pub async fn rollback_config(&self, version: Option<u64>) -> Result<(u64, u64)> {
+        let _guard = self.config_updates.lock().await;
+        let target = {
+            let history = self
+                .history
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Config history unavailable"))?;
+            match version {
+                Some(version) => history
+                    .get(version)
+                    .ok_or_else(|| anyhow::anyhow!("Config version {} is not in the history", version))?,
+                None => history
+                    .previous()
+                    .ok_or_else(|| anyhow::anyhow!("No previous config version to roll back to"))?,
+            }
+        };
+        let version = self
+            .apply_config_locked(target.config.clone(), &format!("rollback:{}", target.version))
+            .await?;
+        Ok((target.version, version))
+    }
+
+    /// 按当前配置拉取JWT签名公钥，失败时只记录警告
+    async fn refresh_jwks(&self) {
+        let Some(settings) = self.manager.get_config().settings.jwt.clone() else {
+            return;
+        };
+        match self.jwt.refresh(&settings).await {
+            Ok(count) => info!("Loaded {} JWT signing keys for issuer {}", count, settings.issuer),
+            Err(e) => warn!("Failed to fetch JWKS: {:#}", e),
+        }
+    }
+
+    /// 获取JWT认证器
+    pub fn get_jwt_authenticator(&self) -> Arc<JwtAuthenticator> {
+        self.jwt.clone()
+    }
+
+    /// 获取argon2密钥校验器
+    pub fn get_token_verifier(&self) -> Arc<TokenVerifier> {
+        self.token_verifier.clone()
+    }
+
+    /// 持久化任务队列，未配置settings.job_queue时为None
+    pub fn get_job_queue(&self) -> Option<Arc<JobQueue>> {
+        self.jobs.clone()
+    }
+
+    /// 获取当前生效的配置
+    pub fn get_config(&self) -> Arc<Config> {
+        self.manager.get_config()
+    }
+
+    /// 获取花费统计
+    pub fn get_cost_tracker(&self) -> Arc<CostTracker> {
+        self.cost_tracker.clone()
+    }
+
+    /// 获取A/B实验统计
+    pub fn get_experiment_tracker(&self) -> Arc<ExperimentTracker> {
+        self.experiments.clone()
+    }
+
+    /// 获取服务事件总线
+    pub fn get_events(&self) -> EventBus {
+        self.events.clone()
     }
 
     /// 获取指标收集器
@@ -356,34 +964,119 @@ impl LoadBalanceService {
         self.metrics.clone()
     }
 
-    /// 检查服务是否正在运行
-    pub async fn is_running(&self) -> bool {
-        *self.is_running.read().await
-    }
-
-    /// 获取backend的原始权重
-    fn get_backend_original_weight(&self, provider: &str, model: &str) -> Option<f64> {
+    /// 在不发送请求的情况下重复运行选择器，统计各后端被选中的次数
+    /// 使用独立的选择器实例，不影响线上的轮询计数等选择状态
+    pub fn preview_selection(
+        &self,
+        model_name: &str,
+        samples: usize,
+        context: &SelectionContext,
+    ) -> Option<SelectionPreview> {
         let config = self.manager.get_config();
+        let (_, mapping) = config.resolve_model(model_name)?;
+        let selector = BackendSelector::new(mapping.clone(), self.metrics.clone());
 
-        // 遍历所有模型映射，找到匹配的backend
-        for (_, model_mapping) in &config.models {
-            for backend in &model_mapping.backends {
-                if backend.provider == provider && backend.model == model {
-                    return Some(backend.weight);
+        let mut preview = SelectionPreview::default();
+        for _ in 0..samples {
+            match selector.select_with_context(context) {
+                Ok(backend) => {
+                    *preview
+                        .selected
+                        .entry(format!("{}:{}", backend.provider, backend.model))
+                        .or_default() += 1;
+                }
+                Err(e) => {
+                    // 选择失败取决于当前后端状态，继续采样没有意义
+                    preview.error = Some(e.to_string());
+                    break;
                 }
             }
         }
+        Some(preview)
+    }
+
+    /// 解释当前状态下为请求选择后端的方式：生效的策略，以及各后端是否参与选择和被排除的原因
+    pub fn explain_selection(&self, model_name: &str, context: &SelectionContext) -> Option<SelectionExplanation> {
+        let config = self.manager.get_config();
+        let (model_id, mapping) = config.resolve_model(model_name)?;
+        let strategy = match &context.pinned_backend {
+            Some(_) => "pinned".to_string(),
+            None => {
+                let strategy = context.strategy_override.as_ref().unwrap_or(&mapping.strategy);
+                serde_json::to_value(strategy)
+                    .ok()
+                    .and_then(|value| value.as_str().map(str::to_string))
+                    .unwrap_or_default()
+            }
+        };
+        let selector = BackendSelector::new(mapping.clone(), self.metrics.clone());
+        Some(SelectionExplanation {
+            model: model_id.clone(),
+            strategy,
+            candidates: selector.explain(context),
+        })
+    }
+
+    /// 重新评估并获取服务自身的SLO达成情况
+    pub fn get_slo_report(&self) -> Vec<SloObjectiveReport> {
+        publish_slo_transitions(&self.manager.get_config(), &self.metrics, &self.events);
+        self.metrics.get_slo_report()
+    }
+
+    /// 检查服务是否正在运行
+    pub async fn is_running(&self) -> bool {
+        *self.is_running.read().await
+    }
+}
 
-        None
+/// 评估SLO快速消耗状态，变化时发布slo_burn_rate_high或slo_burn_rate_normal事件
+fn publish_slo_transitions(config: &Config, metrics: &MetricsCollector, events: &EventBus) {
+    let Some(slo) = &config.settings.slo else {
+        return;
+    };
+    for transition in metrics.evaluate_slo() {
+        let event = if transition.burning {
+            warn!(
+                "SLO {} error budget burning fast (short window: {:?}, long window: {:?})",
+                transition.objective.as_str(),
+                transition.short_burn_rate,
+                transition.long_burn_rate
+            );
+            "slo_burn_rate_high"
+        } else {
+            info!("SLO {} burn rate back to normal", transition.objective.as_str());
+            "slo_burn_rate_normal"
+        };
+        let mut details = serde_json::json!(transition);
+        details["severity"] = serde_json::json!(slo.alert_severity);
+        events.publish(event, details);
     }
 }
 
+/// 选择预演结果：各后端（provider:model）被选中的次数，以及导致采样中止的选择错误
+#[derive(Debug, Clone, Default)]
+pub struct SelectionPreview {
+    pub selected: HashMap<String, usize>,
+    pub error: Option<String>,
+}
+
+/// 后端选择的解释
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectionExplanation {
+    pub model: String,
+    /// 生效的负载均衡策略，管理员指定后端时为pinned
+    pub strategy: String,
+    pub candidates: Vec<CandidateExplanation>,
+}
+
 /// 选中的后端信息
 #[derive(Debug, Clone)]
 pub struct SelectedBackend {
     pub backend: Backend,
     pub provider: crate::config::model::Provider,
     pub selection_time: Duration,
+    /// 发往该后端的请求生效的分级超时
+    pub timeouts: crate::config::model::TimeoutSettings,
 }
 
 impl SelectedBackend {
@@ -409,6 +1102,10 @@ impl SelectedBackend {
     pub fn get_timeout(&self) -> Duration {
         Duration::from_secs(self.provider.timeout_seconds)
     }
+    /// 后端标识（provider:model）
+    pub fn label(&self) -> String {
+        format!("{}:{}", self.backend.provider, self.backend.model)
+    }
 }
 
 /// 请求结果
@@ -416,6 +1113,8 @@ impl SelectedBackend {
 pub enum RequestResult {
     Success { latency: Duration },
     Failure { error: String },
+    /// 上游限流（429）并给出了重试时间，后端进入冷却而不计为失败
+    RateLimited { retry_after: Duration },
 }
 
 /// 服务健康状态
@@ -426,6 +1125,11 @@ pub struct ServiceHealth {
     pub model_stats: std::collections::HashMap<String, super::manager::HealthStats>,
     pub total_requests: u64,
     pub successful_requests: u64,
+    pub failed_requests: u64,
+    /// 全局1分钟、5分钟和15分钟窗口的请求速率
+    pub request_rates: super::RequestRates,
+    /// 按模型的请求计数
+    pub model_requests: std::collections::HashMap<String, super::RequestCounts>,
 }
 
 impl ServiceHealth {
@@ -461,6 +1165,15 @@ mod tests {
             enabled: true,
             timeout_seconds: 30,
             max_retries: 3,
+            tls_pinning: None,
+            tls: None,
+            http_client: None,
+            proxy: None,
+            concurrency: None,
+            quota: None,
+            key_budget: None,
+            timeouts: None,
+            health_check: None,
         });
 
         let mut models = HashMap::new();
@@ -474,15 +1187,44 @@ mod tests {
                 enabled: true,
                 tags: vec![],
                 billing_mode: BillingMode::PerToken,
+                pricing: None,
+                overrides: HashMap::new(),
+                slow_start: None,
+                concurrency: None,
+                schedule: None,
+                region: None,
+                max_context: None,
+                supports_vision: true,
+                supports_tools: true,
+                supports_json_mode: true,
+                supports_streaming: true,
+                supports_responses: false,
             }],
             strategy: LoadBalanceStrategy::WeightedRandom,
             enabled: true,
+            virtual_nodes: 100,
+            semantic_cache: true,
+            budget: None,
+            fallback_models: Vec::new(),
+            transforms: Vec::new(),
+            hedging: None,
+            max_in_flight_requests: None,
+            mirror: None,
+            experiment: None,
+            retry: None,
+            latency_slo_ms: None,
+            structured_output: None,
+            fair_queue: None,
+            timeouts: None,
+            content_filters: Vec::new(),
         });
 
         Config {
             providers,
             models,
             users: HashMap::new(),
+            peers: HashMap::new(),
+            tenants: HashMap::new(),
             settings: GlobalSettings::default(),
         }
     }
@@ -517,4 +1259,158 @@ mod tests {
         
         service.stop().await;
     }
+
+
+    #[tokio::test]
+    async fn test_remembered_failover_until_recovery() {
+        let mut config = create_test_config();
+        config.providers.get_mut("test-provider").unwrap().models.push("backup-model".to_string());
+        let mapping = config.models.get_mut("test-model").unwrap();
+        let primary = mapping.backends[0].clone();
+        let fallback = Backend {
+            model: "backup-model".to_string(),
+            ..primary.clone()
+        };
+        mapping.backends.push(fallback.clone());
+        let service = LoadBalanceService::new(config).unwrap();
+        let context = SelectionContext {
+            client_key: Some("client-key".to_string()),
+            ..Default::default()
+        };
+
+        service.metrics.record_failure("test-provider:test-model");
+        service.remember_failover("client-key", "test-model", &primary, &fallback);
+
+        let selected = service
+            .select_backend_with_context("test-model", &context)
+            .await
+            .unwrap();
+        assert_eq!(selected.backend.model, "backup-model");
+
+        // 其他客户端不受影响
+        let other = SelectionContext {
+            client_key: Some("other-key".to_string()),
+            ..Default::default()
+        };
+        assert!(service.select_remembered_failover("test-model", &other, Instant::now()).is_none());
+
+        // 管理员覆盖策略时不使用记忆
+        let overridden = SelectionContext {
+            strategy_override: Some(LoadBalanceStrategy::Failover),
+            ..context.clone()
+        };
+        assert!(service.select_remembered_failover("test-model", &overridden, Instant::now()).is_none());
+
+        // 原后端恢复后清除记忆
+        service.metrics.record_success("test-provider:test-model");
+        assert!(service.select_remembered_failover("test-model", &context, Instant::now()).is_none());
+        assert!(service.failover_memory.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remembered_failover_follows_current_config() {
+        let mut config = create_test_config();
+        config.providers.get_mut("test-provider").unwrap().models.push("backup-model".to_string());
+        let mapping = config.models.get_mut("test-model").unwrap();
+        let primary = mapping.backends[0].clone();
+        let fallback = Backend {
+            model: "backup-model".to_string(),
+            ..primary.clone()
+        };
+        mapping.backends.push(fallback.clone());
+        let service = LoadBalanceService::new(config.clone()).unwrap();
+        let context = SelectionContext {
+            client_key: Some("client-key".to_string()),
+            ..Default::default()
+        };
+        service.metrics.record_failure("test-provider:test-model");
+
+        // 记住的后端被停用后不再使用，并清除记忆
+        service.remember_failover("client-key", "test-model", &primary, &fallback);
+        let mut disabled = config.clone();
+        disabled.models.get_mut("test-model").unwrap().backends[1].enabled = false;
+        service.manager.reload_config(disabled.flatten_tenants()).await.unwrap();
+        assert!(service.select_remembered_failover("test-model", &context, Instant::now()).is_none());
+        assert!(service.failover_memory.is_empty());
+
+        // 应用新配置时清除所有记忆
+        service.remember_failover("client-key", "test-model", &primary, &fallback);
+        service.apply_config(config, "reload").await.unwrap();
+        assert!(service.failover_memory.is_empty());
+    }
+
+
+    #[tokio::test]
+    async fn test_backend_added_by_reload_starts_slow() {
+        let config = create_test_config();
+        let service = LoadBalanceService::new(config.clone()).unwrap();
+        service.manager.initialize().await.unwrap();
+
+        let mut new_config = config;
+        new_config.providers.get_mut("test-provider").unwrap().models.push("new-model".to_string());
+        let mapping = new_config.models.get_mut("test-model").unwrap();
+        let mut added = mapping.backends[0].clone();
+        added.model = "new-model".to_string();
+        mapping.backends.push(added);
+        service.reload_config(new_config).await.unwrap();
+
+        // 新加入的后端从第一个慢启动阶段开始，已有后端不受影响
+        assert_eq!(service.metrics.get_recovery_stage("test-provider", "new-model"), Some(0));
+        assert_eq!(service.metrics.get_recovery_stage("test-provider", "test-model"), None);
+        assert!((service.metrics.get_slow_start_weight("test-provider:new-model", 1.0) - 0.1).abs() < 1e-9);
+    }
+
+
+    #[test]
+    fn test_backend_slow_start_validation() {
+        use crate::config::model::{SlowStartSettings, SlowStartStage};
+
+        let stages = |weight| SlowStartSettings {
+            stages: vec![SlowStartStage { weight, min_successes: 1, min_duration_seconds: 0 }],
+        };
+
+        let mut config = create_test_config();
+        config.models.get_mut("test-model").unwrap().backends[0].slow_start = Some(stages(0.5));
+        assert!(config.validate().is_ok());
+        assert_eq!(config.slow_start_overrides()["test-provider:test-model"][0].weight, 0.5);
+
+        config.models.get_mut("test-model").unwrap().backends[0].slow_start = Some(stages(1.5));
+        assert!(config.validate().is_err());
+
+        // 同一后端在不同模型中的慢启动配置冲突
+        let mut config = create_test_config();
+        let mut other = config.models["test-model"].clone();
+        other.backends[0].slow_start = Some(stages(0.5));
+        config.models.insert("other-model".to_string(), other);
+        config.models.get_mut("test-model").unwrap().backends[0].slow_start = Some(stages(0.2));
+        let error = config.validate().unwrap_err();
+        assert!(error.to_string().contains("conflicting slow start"));
+    }
+
+
+    #[tokio::test]
+    async fn test_request_failures_eject_outlier_backend() {
+        use crate::config::model::OutlierDetectionSettings;
+
+        let mut config = create_test_config();
+        config.settings.outlier_detection = Some(OutlierDetectionSettings {
+            error_rate_percent: 60.0,
+            window_seconds: 60,
+            min_requests: 3,
+            ejection_seconds: 30,
+        });
+        let service = LoadBalanceService::new(config).unwrap();
+        let success = || RequestResult::Success { latency: Duration::from_millis(10) };
+        let failure = || RequestResult::Failure { error: "boom".to_string() };
+
+        // 失败与成功交替时单次成功会恢复健康，但错误率超过阈值后被剔除
+        service.record_request_result("test-provider", "test-model", failure()).await;
+        service.record_request_result("test-provider", "test-model", success()).await;
+        assert!(service.metrics.is_healthy("test-provider", "test-model"));
+        service.record_request_result("test-provider", "test-model", failure()).await;
+        assert!(service.metrics.is_ejected("test-provider:test-model"));
+
+        service.record_request_result("test-provider", "test-model", success()).await;
+        assert!(!service.metrics.is_healthy("test-provider", "test-model"));
+    }
 }
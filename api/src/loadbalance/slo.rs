@@ -0,0 +1,369 @@
+use crate::config::model::SloSettings;
+use super::clock::{Clock, SystemClock};
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// SLO目标类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SloObjective {
+    /// 未返回服务端错误的请求比例
+    Availability,
+    /// 总耗时不超过阈值的请求比例
+    Latency,
+}
+
+impl SloObjective {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Availability => "availability",
+            Self::Latency => "latency",
+        }
+    }
+}
+
+/// 单个窗口内的错误预算消耗速率
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BurnRate {
+    pub window_seconds: u64,
+    pub requests: u64,
+    pub bad_requests: u64,
+    /// 请求数不足min_requests时为None
+    pub burn_rate: Option<f64>,
+}
+
+/// 单个SLO目标的达成情况
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SloObjectiveReport {
+    pub objective: SloObjective,
+    pub target: f64,
+    /// 延迟目标的耗时阈值
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold_ms: Option<u64>,
+    /// 自启动以来的请求总数和达标请求数
+    pub total_requests: u64,
+    pub good_requests: u64,
+    /// 自启动以来的达标比例，无请求时为None
+    pub compliance: Option<f64>,
+    /// 自启动以来剩余的错误预算比例，低于0表示预算已耗尽
+    pub error_budget_remaining: Option<f64>,
+    pub short_window: BurnRate,
+    pub long_window: BurnRate,
+    /// 短窗口和长窗口的消耗速率都超过告警阈值
+    pub burning: bool,
+}
+
+/// 快速消耗状态变化
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SloTransition {
+    pub objective: SloObjective,
+    pub burning: bool,
+    pub target: f64,
+    pub short_burn_rate: Option<f64>,
+    pub long_burn_rate: Option<f64>,
+}
+
+/// 累计计数和按秒分桶的近期请求（秒, 请求数, 失败数, 超时数）
+#[derive(Debug)]
+struct SloState {
+    started: Instant,
+    total: u64,
+    failed: u64,
+    slow: u64,
+    buckets: VecDeque<(u64, u64, u64, u64)>,
+    burning: HashSet<SloObjective>,
+}
+
+impl SloState {
+    fn new(now: Instant) -> Self {
+        Self {
+            started: now,
+            total: 0,
+            failed: 0,
+            slow: 0,
+            buckets: VecDeque::new(),
+            burning: HashSet::new(),
+        }
+    }
+
+    fn second(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.started).as_secs()
+    }
+
+    /// 窗口内的（请求数, 失败数, 超时数）
+    fn window(&self, now: u64, window_secs: u64) -> (u64, u64, u64) {
+        self.buckets
+            .iter()
+            .filter(|(s, ..)| now.saturating_sub(*s) < window_secs)
+            .fold((0, 0, 0), |(r, f, sl), (_, requests, failed, slow)| (r + requests, f + failed, sl + slow))
+    }
+}
+
+/// 服务自身的SLO跟踪：按客户端请求的最终结果统计可用性（服务端错误视为不可用）和延迟，计算错误预算消耗速率
+/// 未配置时不记录；统计仅保存在内存中，重启后重新开始
+pub struct SloTracker {
+    settings: std::sync::RwLock<Option<SloSettings>>,
+    state: std::sync::Mutex<SloState>,
+    clock: Arc<dyn Clock>,
+}
+
+impl SloTracker {
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// 使用指定时钟创建，与MetricsCollector共用同一个时钟
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            settings: std::sync::RwLock::new(None),
+            state: std::sync::Mutex::new(SloState::new(clock.now())),
+            clock,
+        }
+    }
+
+    /// 更新SLO配置（随配置热加载更新），目标变化时重新开始统计
+    pub fn set_settings(&self, settings: Option<SloSettings>) {
+        let Ok(mut current) = self.settings.write() else {
+            return;
+        };
+        if *current != settings
+            && let Ok(mut state) = self.state.lock()
+        {
+            *state = SloState::new(self.clock.now());
+        }
+        *current = settings;
+    }
+
+    pub fn settings(&self) -> Option<SloSettings> {
+        self.settings.read().ok().and_then(|settings| settings.clone())
+    }
+
+    /// 记录一次客户端请求是否可用及总耗时
+    pub fn record(&self, success: bool, latency: Duration) {
+        self.record_at(success, latency, self.clock.now());
+    }
+
+    pub(crate) fn record_at(&self, success: bool, latency: Duration, now: Instant) {
+        let Some(settings) = self.settings() else {
+            return;
+        };
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let second = state.second(now);
+        let failed = u64::from(!success);
+        let slow = u64::from(latency.as_millis() > u128::from(settings.latency_threshold_ms));
+        state.total += 1;
+        state.failed += failed;
+        state.slow += slow;
+
+        match state.buckets.back_mut() {
+            Some((last, requests, f, s)) if *last == second => {
+                *requests += 1;
+                *f += failed;
+                *s += slow;
+            }
+            _ => state.buckets.push_back((second, 1, failed, slow)),
+        }
+        while state
+            .buckets
+            .front()
+            .is_some_and(|(s, ..)| second.saturating_sub(*s) >= settings.long_window_seconds)
+        {
+            state.buckets.pop_front();
+        }
+    }
+
+    /// 获取各SLO目标的达成情况，未配置时返回空列表
+    pub fn report(&self) -> Vec<SloObjectiveReport> {
+        self.report_at(self.clock.now())
+    }
+
+    pub(crate) fn report_at(&self, now: Instant) -> Vec<SloObjectiveReport> {
+        let Some(settings) = self.settings() else {
+            return Vec::new();
+        };
+        let Ok(state) = self.state.lock() else {
+            return Vec::new();
+        };
+        let second = state.second(now);
+        let short = state.window(second, settings.short_window_seconds);
+        let long = state.window(second, settings.long_window_seconds);
+
+        let objectives = [
+            (SloObjective::Availability, settings.availability_target, None),
+            (SloObjective::Latency, settings.latency_target, Some(settings.latency_threshold_ms)),
+        ];
+        objectives
+            .into_iter()
+            .filter_map(|(objective, target, threshold_ms)| {
+                let target = target?;
+                let budget = 1.0 - target;
+                // 按目标类型选择不达标的计数
+                let bad = |(_, failed, slow): (u64, u64, u64)| match objective {
+                    SloObjective::Availability => failed,
+                    SloObjective::Latency => slow,
+                };
+                let burn_rate = |window_seconds: u64, counts: (u64, u64, u64)| {
+                    let bad_requests = bad(counts);
+                    BurnRate {
+                        window_seconds,
+                        requests: counts.0,
+                        bad_requests,
+                        burn_rate: (counts.0 >= settings.min_requests.max(1))
+                            .then(|| bad_requests as f64 / counts.0 as f64 / budget),
+                    }
+                };
+                let short_window = burn_rate(settings.short_window_seconds, short);
+                let long_window = burn_rate(settings.long_window_seconds, long);
+                let burning = [&short_window, &long_window]
+                    .iter()
+                    .all(|w| w.burn_rate.is_some_and(|rate| rate >= settings.burn_rate_alert_threshold));
+
+                let bad_total = bad((state.total, state.failed, state.slow));
+                let bad_ratio = (state.total > 0).then(|| bad_total as f64 / state.total as f64);
+                Some(SloObjectiveReport {
+                    objective,
+                    target,
+                    threshold_ms,
+                    total_requests: state.total,
+                    good_requests: state.total - bad_total,
+                    compliance: bad_ratio.map(|ratio| 1.0 - ratio),
+                    error_budget_remaining: bad_ratio.map(|ratio| 1.0 - ratio / budget),
+                    short_window,
+                    long_window,
+                    burning,
+                })
+            })
+            .collect()
+    }
+
+    /// 重新评估快速消耗状态，返回状态发生变化的目标
+    pub fn evaluate(&self) -> Vec<SloTransition> {
+        self.evaluate_at(self.clock.now())
+    }
+
+    pub(crate) fn evaluate_at(&self, now: Instant) -> Vec<SloTransition> {
+        let report = self.report_at(now);
+        let Ok(mut state) = self.state.lock() else {
+            return Vec::new();
+        };
+        let configured: HashSet<SloObjective> = report.iter().map(|r| r.objective).collect();
+        state.burning.retain(|objective| configured.contains(objective));
+
+        report
+            .into_iter()
+            .filter_map(|r| {
+                let changed = if r.burning {
+                    state.burning.insert(r.objective)
+                } else {
+                    state.burning.remove(&r.objective)
+                };
+                changed.then_some(SloTransition {
+                    objective: r.objective,
+                    burning: r.burning,
+                    target: r.target,
+                    short_burn_rate: r.short_window.burn_rate,
+                    long_burn_rate: r.long_window.burn_rate,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for SloTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::model::AlertSeverity;
+
+    fn settings() -> SloSettings {
+        SloSettings {
+            availability_target: Some(0.99),
+            latency_target: Some(0.9),
+            latency_threshold_ms: 1000,
+            short_window_seconds: 60,
+            long_window_seconds: 600,
+            burn_rate_alert_threshold: 10.0,
+            min_requests: 10,
+            alert_severity: AlertSeverity::Critical,
+        }
+    }
+
+    #[test]
+    fn test_report_computes_compliance_and_burn_rate() {
+        let tracker = SloTracker::new();
+        tracker.set_settings(Some(settings()));
+        let start = tracker.state.lock().unwrap().started;
+        for i in 0..100 {
+            let latency = Duration::from_millis(if i < 5 { 2000 } else { 100 });
+            tracker.record_at(i >= 2, latency, start);
+        }
+
+        let report = tracker.report_at(start);
+        let availability = &report[0];
+        assert_eq!(availability.objective, SloObjective::Availability);
+        assert_eq!((availability.total_requests, availability.good_requests), (100, 98));
+        assert!((availability.compliance.unwrap() - 0.98).abs() < 1e-9);
+        // 不达标比例2%是预算1%的两倍
+        assert!((availability.short_window.burn_rate.unwrap() - 2.0).abs() < 1e-9);
+        assert!((availability.error_budget_remaining.unwrap() + 1.0).abs() < 1e-9);
+
+        let latency = &report[1];
+        assert_eq!(latency.threshold_ms, Some(1000));
+        assert_eq!(latency.short_window.bad_requests, 5);
+        assert!((latency.long_window.burn_rate.unwrap() - 0.5).abs() < 1e-9);
+        assert!(!availability.burning && !latency.burning);
+    }
+
+    #[test]
+    fn test_burning_requires_both_windows_and_reports_transitions() {
+        let tracker = SloTracker::new();
+        tracker.set_settings(Some(SloSettings { latency_target: None, ..settings() }));
+        let start = tracker.state.lock().unwrap().started;
+
+        // 长窗口内有大量成功请求，短窗口的失败不足以让长窗口超过阈值
+        for _ in 0..1000 {
+            tracker.record_at(true, Duration::ZERO, start);
+        }
+        let later = start + Duration::from_secs(300);
+        for _ in 0..20 {
+            tracker.record_at(false, Duration::ZERO, later);
+        }
+        let report = tracker.report_at(later);
+        assert!(report[0].short_window.burn_rate.unwrap() >= 10.0);
+        assert!(!report[0].burning);
+        assert!(tracker.evaluate_at(later).is_empty());
+
+        // 早期的成功请求移出长窗口后开始告警，只报告一次
+        let much_later = start + Duration::from_secs(650);
+        for _ in 0..20 {
+            tracker.record_at(false, Duration::ZERO, much_later);
+        }
+        let transitions = tracker.evaluate_at(much_later);
+        assert_eq!(transitions.len(), 1);
+        assert!(transitions[0].burning);
+        assert!(tracker.evaluate_at(much_later).is_empty());
+
+        // 短窗口内没有足够请求时恢复
+        let recovered = tracker.evaluate_at(much_later + Duration::from_secs(120));
+        assert_eq!(recovered.len(), 1);
+        assert!(!recovered[0].burning);
+    }
+
+    #[test]
+    fn test_disabled_tracker_records_nothing() {
+        let tracker = SloTracker::new();
+        tracker.record(false, Duration::ZERO);
+        assert!(tracker.report().is_empty());
+        tracker.set_settings(Some(settings()));
+        assert_eq!(tracker.report()[0].total_requests, 0);
+    }
+}
@@ -0,0 +1,122 @@
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, Method, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use futures::StreamExt;
+use futures::future::BoxFuture;
+use std::sync::Arc;
+
+use crate::relay::error::RelayError;
+
+/// 中间件可以修改的请求
+pub struct MiddlewareRequest {
+    pub parts: axum::http::request::Parts,
+    /// 完整的请求体（已按max_request_body_mb限制大小）
+    pub body: Bytes,
+}
+
+/// 响应阶段可见的请求信息（所有on_request执行之后的状态）
+#[derive(Debug, Clone)]
+pub struct RequestInfo {
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+}
+
+/// 请求/响应中间件插件
+/// 下游项目实现该trait并通过MiddlewareStack注册，无需修改relay模块即可加入自定义逻辑（注入请求头、自定义认证、定制日志等）；
+/// 所有钩子都有默认实现，只需实现关心的部分
+pub trait BerryMiddleware: Send + Sync + 'static {
+    /// 请求交给路由处理前调用，可以修改请求头和请求体
+    /// 返回响应时不再继续处理请求（如自定义认证失败），该响应仍会经过所有中间件的响应钩子
+    fn on_request<'a>(&'a self, request: &'a mut MiddlewareRequest) -> BoxFuture<'a, Option<Response>> {
+        let _ = request;
+        Box::pin(async { None })
+    }
+
+    /// 响应头返回给客户端前调用，可以修改状态码和响应头
+    fn on_response(&self, request: &RequestInfo, response: &mut axum::http::response::Parts) {
+        let _ = (request, response);
+    }
+
+    /// 每个响应体分片返回给客户端前调用，返回值替换原分片
+    /// 流式响应按上游事件逐个调用，非流式响应可能拆分为多个分片（包括保活用的空白）
+    fn on_chunk(&self, request: &RequestInfo, chunk: Bytes) -> Bytes {
+        let _ = request;
+        chunk
+    }
+}
+
+/// 已注册的中间件
+/// on_request按注册顺序调用，on_response和on_chunk按注册的相反顺序调用
+#[derive(Clone, Default)]
+pub struct MiddlewareStack {
+    middlewares: Vec<Arc<dyn BerryMiddleware>>,
+}
+
+impl MiddlewareStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册一个中间件
+    pub fn with(mut self, middleware: impl BerryMiddleware) -> Self {
+        self.push(Arc::new(middleware));
+        self
+    }
+
+    pub fn push(&mut self, middleware: Arc<dyn BerryMiddleware>) {
+        self.middlewares.push(middleware);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.middlewares.is_empty()
+    }
+}
+
+/// 依次执行已注册中间件的钩子
+/// 位于请求体大小限制之内，请求体已读入内存；响应体可能被on_chunk改变长度，因此去掉Content-Length
+pub async fn run_middlewares(State(stack): State<MiddlewareStack>, request: Request, next: Next) -> Response {
+    let (parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => return RelayError::Internal(format!("Failed to read request body: {}", e)).into_response(),
+    };
+
+    let mut request = MiddlewareRequest { parts, body };
+    let mut rejected = None;
+    for middleware in &stack.middlewares {
+        if let Some(response) = middleware.on_request(&mut request).await {
+            rejected = Some(response);
+            break;
+        }
+    }
+
+    let MiddlewareRequest { parts, body } = request;
+    let info = Arc::new(RequestInfo {
+        method: parts.method.clone(),
+        uri: parts.uri.clone(),
+        headers: parts.headers.clone(),
+    });
+    let response = match rejected {
+        Some(response) => response,
+        None => next.run(Request::from_parts(parts, Body::from(body))).await,
+    };
+
+    let (mut parts, body) = response.into_parts();
+    for middleware in stack.middlewares.iter().rev() {
+        middleware.on_response(&info, &mut parts);
+    }
+    parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+    let stream = body.into_data_stream().map(move |chunk| {
+        chunk.map(|chunk| {
+            stack
+                .middlewares
+                .iter()
+                .rev()
+                .fold(chunk, |chunk, middleware| middleware.on_chunk(&info, chunk))
+        })
+    });
+    Response::from_parts(parts, Body::from_stream(stream))
+}
@@ -0,0 +1,198 @@
+use crate::config::model::{PrioritySchedulingSettings, RequestPriority};
+use crate::relay::concurrency::{ConcurrencyError, ConcurrencyPermit};
+use crate::relay::fair_queue::FairPermit;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// 在途请求数超出上限时的错误
+#[derive(Debug, thiserror::Error)]
+pub enum AdmissionError {
+    #[error("Server is at capacity: {0} requests in flight")]
+    GlobalLimit(usize),
+    #[error("Model '{0}' is at capacity: {1} requests in flight")]
+    ModelLimit(String, usize),
+    #[error("Model '{0}' is at capacity, timed out after waiting {1}ms in queue")]
+    QueueTimeout(String, u64),
+    /// 批量请求在压力下让出名额给更高优先级的请求
+    #[error("Batch request preempted: {0} is under load, capacity is reserved for higher-priority traffic")]
+    BatchPreempted(String),
+    /// 用户的在途请求数已达上限，且排队已满或等待超时
+    #[error("Too many concurrent requests for this API key: {0}")]
+    UserConcurrency(ConcurrencyError),
+}
+
+/// 已接纳请求占用的在途计数，析构时归还
+#[derive(Debug)]
+pub struct AdmissionGuard {
+    counters: Vec<Arc<AtomicUsize>>,
+    /// 通过公平排队获得的模型名额
+    fair_permit: Option<FairPermit>,
+    /// 用户的在途请求名额
+    user_permit: Option<ConcurrencyPermit>,
+}
+
+impl AdmissionGuard {
+    /// 请求结束时一并归还公平排队的名额
+    pub fn with_fair_permit(mut self, permit: Option<FairPermit>) -> Self {
+        self.fair_permit = permit;
+        self
+    }
+
+    /// 请求结束时一并归还用户的在途请求名额
+    pub fn with_user_permit(mut self, permit: Option<ConcurrencyPermit>) -> Self {
+        self.user_permit = permit;
+        self
+    }
+}
+
+impl Drop for AdmissionGuard {
+    fn drop(&mut self) {
+        for counter in &self.counters {
+            counter.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// 准入控制：限制全局和每个模型的在途请求数，超出上限时立即拒绝而不是排队
+pub struct AdmissionController {
+    global: Arc<AtomicUsize>,
+    models: std::sync::Mutex<HashMap<String, Arc<AtomicUsize>>>,
+}
+
+impl AdmissionController {
+    pub fn new() -> Self {
+        Self {
+            global: Arc::new(AtomicUsize::new(0)),
+            models: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 接纳一个请求，全局或模型的在途请求数已达上限时返回错误
+    /// 上限随配置热加载生效，未配置上限时只计数；启用优先级调度时按优先级只能使用上限的一部分
+    pub fn try_admit(
+        &self,
+        global_limit: Option<usize>,
+        model_id: &str,
+        model_limit: Option<usize>,
+        priority: RequestPriority,
+        scheduling: Option<&PrioritySchedulingSettings>,
+    ) -> Result<AdmissionGuard, AdmissionError> {
+        let share = |limit: Option<usize>| match scheduling {
+            Some(scheduling) => limit.map(|limit| scheduling.share_of(priority, limit)),
+            None => limit,
+        };
+        let mut guard = AdmissionGuard {
+            counters: Vec::with_capacity(2),
+            fair_permit: None,
+            user_permit: None,
+        };
+        if !Self::increment(&self.global, share(global_limit)) {
+            if priority == RequestPriority::Batch {
+                return Err(AdmissionError::BatchPreempted("server".to_string()));
+            }
+            return Err(AdmissionError::GlobalLimit(global_limit.unwrap_or_default()));
+        }
+        guard.counters.push(self.global.clone());
+
+        let model = self
+            .models
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(model_id.to_string())
+            .or_default()
+            .clone();
+        if !Self::increment(&model, share(model_limit)) {
+            // guard析构时归还已占用的全局计数
+            if priority == RequestPriority::Batch {
+                return Err(AdmissionError::BatchPreempted(format!("model '{}'", model_id)));
+            }
+            return Err(AdmissionError::ModelLimit(
+                model_id.to_string(),
+                model_limit.unwrap_or_default(),
+            ));
+        }
+        guard.counters.push(model);
+        Ok(guard)
+    }
+
+    /// 当前全局在途请求数
+    pub fn in_flight(&self) -> usize {
+        self.global.load(Ordering::SeqCst)
+    }
+
+    fn increment(counter: &AtomicUsize, limit: Option<usize>) -> bool {
+        counter
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| match limit {
+                Some(limit) if n >= limit => None,
+                _ => Some(n + 1),
+            })
+            .is_ok()
+    }
+}
+
+impl Default for AdmissionController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_global_limit_rejects_and_releases() {
+        let controller = AdmissionController::new();
+        let first = controller.try_admit(Some(2), "a", None, RequestPriority::Normal, None).unwrap();
+        let _second = controller.try_admit(Some(2), "b", None, RequestPriority::Normal, None).unwrap();
+        assert!(matches!(
+            controller.try_admit(Some(2), "c", None, RequestPriority::Normal, None),
+            Err(AdmissionError::GlobalLimit(2))
+        ));
+        assert_eq!(controller.in_flight(), 2);
+
+        drop(first);
+        assert!(controller.try_admit(Some(2), "c", None, RequestPriority::Normal, None).is_ok());
+    }
+
+    #[test]
+    fn test_model_limit_is_per_model() {
+        let controller = AdmissionController::new();
+        let _first = controller.try_admit(None, "gpt-4", Some(1), RequestPriority::Normal, None).unwrap();
+        assert!(matches!(
+            controller.try_admit(None, "gpt-4", Some(1), RequestPriority::Normal, None),
+            Err(AdmissionError::ModelLimit(_, 1))
+        ));
+        // 被模型上限拒绝的请求不占用全局计数
+        assert_eq!(controller.in_flight(), 1);
+        assert!(controller.try_admit(None, "gpt-3.5", Some(1), RequestPriority::Normal, None).is_ok());
+    }
+
+    #[test]
+    fn test_priority_shares_reserve_capacity_for_high_priority() {
+        let controller = AdmissionController::new();
+        let scheduling = PrioritySchedulingSettings {
+            normal_share_percent: 75.0,
+            batch_share_percent: 50.0,
+        };
+        let admit = |priority| controller.try_admit(Some(4), "gpt-4", None, priority, Some(&scheduling));
+
+        let _batch = admit(RequestPriority::Batch).unwrap();
+        let _batch2 = admit(RequestPriority::Batch).unwrap();
+        // 批量请求只能使用一半名额，超出时以抢占方式拒绝
+        assert!(matches!(
+            admit(RequestPriority::Batch),
+            Err(AdmissionError::BatchPreempted(_))
+        ));
+
+        let _normal = admit(RequestPriority::Normal).unwrap();
+        assert!(matches!(
+            admit(RequestPriority::Normal),
+            Err(AdmissionError::GlobalLimit(4))
+        ));
+        // 保留的名额只给高优先级请求
+        let _high = admit(RequestPriority::High).unwrap();
+        assert!(admit(RequestPriority::High).is_err());
+    }
+}
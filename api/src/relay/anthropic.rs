@@ -0,0 +1,535 @@
+//! Anthropic Messages API 与 OpenAI Chat Completions 之间的格式转换
+//! 入站的Anthropic请求先转换为OpenAI格式，再经过与/v1/chat/completions相同的负载均衡流程
+
+use serde_json::{Value, json};
+
+/// 把Anthropic Messages请求转换为OpenAI聊天请求
+pub fn to_openai_request(body: &Value) -> Result<Value, String> {
+    let model = body
+        .get("model")
+        .and_then(Value::as_str)
+        .ok_or("The 'model' field is required in the request body")?;
+    let max_tokens = body
+        .get("max_tokens")
+        .and_then(Value::as_u64)
+        .ok_or("The 'max_tokens' field is required in the request body")?;
+    let Some(Value::Array(input)) = body.get("messages") else {
+        return Err("'messages' must be an array".to_string());
+    };
+    if input.is_empty() {
+        return Err("'messages' must not be empty".to_string());
+    }
+
+    let mut messages = Vec::new();
+    match body.get("system") {
+        None | Some(Value::Null) => {}
+        Some(Value::String(system)) => messages.push(json!({ "role": "system", "content": system })),
+        Some(Value::Array(blocks)) => {
+            let text = blocks
+                .iter()
+                .filter_map(|block| block.get("text").and_then(Value::as_str))
+                .collect::<Vec<_>>()
+                .join("\n");
+            messages.push(json!({ "role": "system", "content": text }));
+        }
+        Some(_) => return Err("'system' must be a string or an array of text blocks".to_string()),
+    }
+    for (index, message) in input.iter().enumerate() {
+        match message.get("role").and_then(Value::as_str) {
+            Some("user") => convert_user_message(message, &mut messages)
+                .map_err(|e| format!("messages[{}]: {}", index, e))?,
+            Some("assistant") => messages.push(
+                convert_assistant_message(message).map_err(|e| format!("messages[{}]: {}", index, e))?,
+            ),
+            Some(role) => return Err(format!("Invalid role '{}' in messages[{}]", role, index)),
+            None => return Err(format!("Missing role in messages[{}]", index)),
+        }
+    }
+
+    let mut request = json!({
+        "model": model,
+        "messages": messages,
+        "max_tokens": max_tokens,
+    });
+    for field in ["temperature", "top_p", "stream"] {
+        if let Some(value) = body.get(field) {
+            request[field] = value.clone();
+        }
+    }
+    if let Some(stop) = body.get("stop_sequences") {
+        request["stop"] = stop.clone();
+    }
+    if let Some(user_id) = body.pointer("/metadata/user_id") {
+        request["user"] = user_id.clone();
+    }
+    if let Some(Value::Array(tools)) = body.get("tools") {
+        let tools = tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool["name"],
+                        "description": tool.get("description").cloned().unwrap_or(Value::Null),
+                        "parameters": tool.get("input_schema").cloned().unwrap_or_else(|| json!({ "type": "object" })),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        request["tools"] = Value::Array(tools);
+    }
+    if let Some(choice) = body.get("tool_choice") {
+        request["tool_choice"] = match choice["type"].as_str() {
+            Some("auto") => json!("auto"),
+            Some("any") => json!("required"),
+            Some("none") => json!("none"),
+            Some("tool") => json!({ "type": "function", "function": { "name": choice["name"] } }),
+            _ => return Err("Invalid 'tool_choice'".to_string()),
+        };
+        if choice.get("disable_parallel_tool_use").and_then(Value::as_bool) == Some(true) {
+            request["parallel_tool_calls"] = json!(false);
+        }
+    }
+    Ok(request)
+}
+
+/// 消息内容：字符串或内容块数组
+fn content_blocks(message: &Value) -> Result<Vec<Value>, String> {
+    match message.get("content") {
+        Some(Value::String(text)) => Ok(vec![json!({ "type": "text", "text": text })]),
+        Some(Value::Array(blocks)) => Ok(blocks.clone()),
+        _ => Err("'content' must be a string or an array of content blocks".to_string()),
+    }
+}
+
+/// 用户消息：tool_result块转换为单独的tool消息，放在其余内容之前
+fn convert_user_message(message: &Value, messages: &mut Vec<Value>) -> Result<(), String> {
+    let mut parts = Vec::new();
+    for block in content_blocks(message)? {
+        match block["type"].as_str() {
+            Some("text") => parts.push(json!({ "type": "text", "text": block["text"] })),
+            Some("image") => parts.push(image_part(&block)?),
+            Some("tool_result") => {
+                let content = match &block["content"] {
+                    Value::String(text) => text.clone(),
+                    Value::Array(blocks) => blocks
+                        .iter()
+                        .filter_map(|block| block.get("text").and_then(Value::as_str))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    _ => String::new(),
+                };
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": block["tool_use_id"],
+                    "content": content,
+                }));
+            }
+            Some(other) => return Err(format!("Unsupported content block type '{}'", other)),
+            None => return Err("Content block is missing 'type'".to_string()),
+        }
+    }
+    if parts.is_empty() {
+        return Ok(());
+    }
+    // 只有文本时合并为字符串，兼容不支持多模态内容的后端
+    let content = if parts.iter().all(|part| part["type"] == "text") {
+        Value::String(
+            parts
+                .iter()
+                .filter_map(|part| part["text"].as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    } else {
+        Value::Array(parts)
+    };
+    messages.push(json!({ "role": "user", "content": content }));
+    Ok(())
+}
+
+fn image_part(block: &Value) -> Result<Value, String> {
+    let source = &block["source"];
+    let url = match source["type"].as_str() {
+        Some("base64") => format!(
+            "data:{};base64,{}",
+            source["media_type"].as_str().unwrap_or("image/png"),
+            source["data"].as_str().unwrap_or_default()
+        ),
+        Some("url") => source["url"].as_str().unwrap_or_default().to_string(),
+        _ => return Err("Unsupported image source".to_string()),
+    };
+    Ok(json!({ "type": "image_url", "image_url": { "url": url } }))
+}
+
+/// 助手消息：tool_use块转换为tool_calls，thinking块不转发
+fn convert_assistant_message(message: &Value) -> Result<Value, String> {
+    let mut text = Vec::new();
+    let mut tool_calls = Vec::new();
+    for block in content_blocks(message)? {
+        match block["type"].as_str() {
+            Some("text") => text.push(block["text"].as_str().unwrap_or_default().to_string()),
+            Some("tool_use") => tool_calls.push(json!({
+                "id": block["id"],
+                "type": "function",
+                "function": {
+                    "name": block["name"],
+                    "arguments": block.get("input").unwrap_or(&json!({})).to_string(),
+                }
+            })),
+            Some("thinking") | Some("redacted_thinking") => {}
+            Some(other) => return Err(format!("Unsupported content block type '{}'", other)),
+            None => return Err("Content block is missing 'type'".to_string()),
+        }
+    }
+    let mut converted = json!({
+        "role": "assistant",
+        "content": if text.is_empty() { Value::Null } else { Value::String(text.join("\n")) },
+    });
+    if !tool_calls.is_empty() {
+        converted["tool_calls"] = Value::Array(tool_calls);
+    }
+    Ok(converted)
+}
+
+/// OpenAI的finish_reason对应的Anthropic stop_reason
+fn stop_reason(finish_reason: Option<&str>) -> Option<&'static str> {
+    Some(match finish_reason? {
+        "length" => "max_tokens",
+        "tool_calls" | "function_call" => "tool_use",
+        "content_filter" => "refusal",
+        _ => "end_turn",
+    })
+}
+
+fn usage(usage: &Value) -> Value {
+    json!({
+        "input_tokens": usage["prompt_tokens"].as_u64().unwrap_or_default(),
+        "output_tokens": usage["completion_tokens"].as_u64().unwrap_or_default(),
+    })
+}
+
+/// 工具参数解析失败时保留原始字符串，避免丢失后端返回的内容
+fn tool_input(arguments: &Value) -> Value {
+    match arguments {
+        Value::String(arguments) if arguments.trim().is_empty() => json!({}),
+        Value::String(arguments) => serde_json::from_str(arguments).unwrap_or_else(|_| json!({ "arguments": arguments })),
+        Value::Object(_) => arguments.clone(),
+        _ => json!({}),
+    }
+}
+
+/// 把OpenAI聊天响应转换为Anthropic消息，model使用客户端请求的名称
+pub fn from_openai_response(response: &Value, model: &str) -> Value {
+    let choice = &response["choices"][0];
+    let message = &choice["message"];
+    let mut content = Vec::new();
+    if let Some(text) = message["content"].as_str().filter(|text| !text.is_empty()) {
+        content.push(json!({ "type": "text", "text": text }));
+    }
+    if let Some(tool_calls) = message["tool_calls"].as_array() {
+        for call in tool_calls {
+            content.push(json!({
+                "type": "tool_use",
+                "id": call["id"],
+                "name": call["function"]["name"],
+                "input": tool_input(&call["function"]["arguments"]),
+            }));
+        }
+    }
+    json!({
+        "id": message_id(response),
+        "type": "message",
+        "role": "assistant",
+        "model": model,
+        "content": content,
+        "stop_reason": stop_reason(choice["finish_reason"].as_str()),
+        "stop_sequence": Value::Null,
+        "usage": usage(&response["usage"]),
+    })
+}
+
+fn message_id(response: &Value) -> String {
+    match response["id"].as_str() {
+        Some(id) => format!("msg_{}", id.trim_start_matches("chatcmpl-")),
+        None => format!("msg_{:032x}", rand::random::<u128>()),
+    }
+}
+
+/// HTTP状态码对应的Anthropic错误类型
+pub fn error_type(status: u16) -> &'static str {
+    match status {
+        400 | 422 => "invalid_request_error",
+        401 => "authentication_error",
+        402 => "billing_error",
+        403 => "permission_error",
+        404 => "not_found_error",
+        413 => "request_too_large",
+        429 => "rate_limit_error",
+        504 => "timeout_error",
+        503 | 529 => "overloaded_error",
+        _ => "api_error",
+    }
+}
+
+/// 把berry的错误响应（或流中的错误事件）转换为Anthropic错误格式
+pub fn error_body(error: &Value, status: u16) -> Value {
+    let message = match error {
+        Value::String(message) => message.clone(),
+        _ => {
+            let message = error["message"].as_str().unwrap_or("Unknown error");
+            match error["details"].as_str() {
+                Some(details) if !details.is_empty() => format!("{}: {}", message, details),
+                _ => message.to_string(),
+            }
+        }
+    };
+    json!({
+        "type": "error",
+        "error": { "type": error_type(status), "message": message },
+    })
+}
+
+/// 当前打开的内容块
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OpenBlock {
+    Text,
+    /// OpenAI流中工具调用的index
+    ToolUse(u64),
+}
+
+/// 把OpenAI流式响应块逐个转换为Anthropic流事件
+#[derive(Debug)]
+pub struct StreamTranslator {
+    model: String,
+    started: bool,
+    finished: bool,
+    /// 下一个内容块的index
+    next_index: u64,
+    open_block: Option<OpenBlock>,
+    stop_reason: Option<&'static str>,
+    usage: Value,
+}
+
+impl StreamTranslator {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            started: false,
+            finished: false,
+            next_index: 0,
+            open_block: None,
+            stop_reason: None,
+            usage: json!({ "prompt_tokens": 0, "completion_tokens": 0 }),
+        }
+    }
+
+    /// 处理一个OpenAI流式块，返回要发送的(事件名, 数据)
+    pub fn on_chunk(&mut self, chunk: &Value) -> Vec<(&'static str, Value)> {
+        let mut events = Vec::new();
+        if self.finished {
+            return events;
+        }
+        if let Some(error) = chunk.get("error") {
+            let status = error["status"].as_u64().unwrap_or(500) as u16;
+            self.finished = true;
+            events.push(("error", error_body(error, status)));
+            return events;
+        }
+        if !self.started {
+            self.started = true;
+            events.push((
+                "message_start",
+                json!({
+                    "type": "message_start",
+                    "message": {
+                        "id": message_id(chunk),
+                        "type": "message",
+                        "role": "assistant",
+                        "model": self.model,
+                        "content": [],
+                        "stop_reason": Value::Null,
+                        "stop_sequence": Value::Null,
+                        "usage": { "input_tokens": 0, "output_tokens": 0 },
+                    }
+                }),
+            ));
+        }
+        if chunk["usage"].is_object() {
+            self.usage = chunk["usage"].clone();
+        }
+
+        let choice = &chunk["choices"][0];
+        let delta = &choice["delta"];
+        if let Some(text) = delta["content"].as_str().filter(|text| !text.is_empty()) {
+            if self.open_block != Some(OpenBlock::Text) {
+                self.start_block(OpenBlock::Text, json!({ "type": "text", "text": "" }), &mut events);
+            }
+            events.push(self.block_delta(json!({ "type": "text_delta", "text": text })));
+        }
+        if let Some(tool_calls) = delta["tool_calls"].as_array() {
+            for call in tool_calls {
+                let index = call["index"].as_u64().unwrap_or_default();
+                if self.open_block != Some(OpenBlock::ToolUse(index)) {
+                    self.start_block(
+                        OpenBlock::ToolUse(index),
+                        json!({
+                            "type": "tool_use",
+                            "id": call["id"],
+                            "name": call["function"]["name"],
+                            "input": {},
+                        }),
+                        &mut events,
+                    );
+                }
+                if let Some(arguments) = call["function"]["arguments"].as_str().filter(|a| !a.is_empty()) {
+                    events.push(self.block_delta(json!({ "type": "input_json_delta", "partial_json": arguments })));
+                }
+            }
+        }
+        if let Some(reason) = stop_reason(choice["finish_reason"].as_str()) {
+            self.stop_reason = Some(reason);
+        }
+        events
+    }
+
+    /// 流结束时关闭打开的内容块并发送message_delta和message_stop
+    pub fn finish(&mut self) -> Vec<(&'static str, Value)> {
+        let mut events = Vec::new();
+        if self.finished {
+            return events;
+        }
+        if !self.started {
+            // 后端没有返回任何内容块
+            events.extend(self.on_chunk(&json!({})));
+        }
+        self.finished = true;
+        self.close_block(&mut events);
+        events.push((
+            "message_delta",
+            json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": self.stop_reason.unwrap_or("end_turn"), "stop_sequence": Value::Null },
+                "usage": usage(&self.usage),
+            }),
+        ));
+        events.push(("message_stop", json!({ "type": "message_stop" })));
+        events
+    }
+
+    fn start_block(&mut self, block: OpenBlock, content_block: Value, events: &mut Vec<(&'static str, Value)>) {
+        self.close_block(events);
+        events.push((
+            "content_block_start",
+            json!({ "type": "content_block_start", "index": self.next_index, "content_block": content_block }),
+        ));
+        self.open_block = Some(block);
+    }
+
+    fn block_delta(&self, delta: Value) -> (&'static str, Value) {
+        ("content_block_delta", json!({ "type": "content_block_delta", "index": self.next_index, "delta": delta }))
+    }
+
+    fn close_block(&mut self, events: &mut Vec<(&'static str, Value)>) {
+        if self.open_block.take().is_some() {
+            events.push(("content_block_stop", json!({ "type": "content_block_stop", "index": self.next_index })));
+            self.next_index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_openai_request() {
+        let request = to_openai_request(&json!({
+            "model": "claude-sonnet",
+            "max_tokens": 256,
+            "system": [{ "type": "text", "text": "Be brief." }],
+            "stop_sequences": ["END"],
+            "tools": [{ "name": "get_weather", "input_schema": { "type": "object" } }],
+            "tool_choice": { "type": "any" },
+            "messages": [
+                { "role": "user", "content": "Weather in Paris?" },
+                { "role": "assistant", "content": [
+                    { "type": "thinking", "thinking": "..." },
+                    { "type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": { "city": "Paris" } }
+                ]},
+                { "role": "user", "content": [
+                    { "type": "tool_result", "tool_use_id": "toolu_1", "content": "Sunny" },
+                    { "type": "text", "text": "Thanks" }
+                ]}
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(request["max_tokens"], 256);
+        assert_eq!(request["stop"], json!(["END"]));
+        assert_eq!(request["tool_choice"], "required");
+        assert_eq!(request["tools"][0]["function"]["name"], "get_weather");
+        let messages = request["messages"].as_array().unwrap();
+        assert_eq!(messages[0], json!({ "role": "system", "content": "Be brief." }));
+        assert_eq!(messages[2]["content"], Value::Null);
+        assert_eq!(messages[2]["tool_calls"][0]["function"]["arguments"], r#"{"city":"Paris"}"#);
+        assert_eq!(messages[3], json!({ "role": "tool", "tool_call_id": "toolu_1", "content": "Sunny" }));
+        assert_eq!(messages[4], json!({ "role": "user", "content": "Thanks" }));
+
+        assert!(to_openai_request(&json!({ "model": "m", "messages": [] })).is_err());
+    }
+
+    #[test]
+    fn test_from_openai_response() {
+        let response = from_openai_response(
+            &json!({
+                "id": "chatcmpl-abc",
+                "choices": [{
+                    "message": { "role": "assistant", "content": null, "tool_calls": [
+                        { "id": "call_1", "type": "function", "function": { "name": "f", "arguments": "{\"a\":1}" } }
+                    ]},
+                    "finish_reason": "tool_calls"
+                }],
+                "usage": { "prompt_tokens": 10, "completion_tokens": 5 }
+            }),
+            "claude-sonnet",
+        );
+        assert_eq!(response["id"], "msg_abc");
+        assert_eq!(response["model"], "claude-sonnet");
+        assert_eq!(response["stop_reason"], "tool_use");
+        assert_eq!(response["content"][0]["input"], json!({ "a": 1 }));
+        assert_eq!(response["usage"], json!({ "input_tokens": 10, "output_tokens": 5 }));
+    }
+
+    #[test]
+    fn test_stream_translator() {
+        let mut translator = StreamTranslator::new("claude-sonnet");
+        let mut events = Vec::new();
+        events.extend(translator.on_chunk(&json!({ "id": "x", "choices": [{ "delta": { "content": "Hi" } }] })));
+        events.extend(translator.on_chunk(&json!({ "choices": [{ "delta": { "tool_calls": [
+            { "index": 0, "id": "call_1", "function": { "name": "f", "arguments": "{}" } }
+        ]}, "finish_reason": "tool_calls" }] })));
+        events.extend(translator.on_chunk(&json!({ "choices": [], "usage": { "prompt_tokens": 3, "completion_tokens": 7 } })));
+        events.extend(translator.finish());
+
+        let names: Vec<_> = events.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            [
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop"
+            ]
+        );
+        assert_eq!(events[4].1["index"], 1);
+        assert_eq!(events[7].1["delta"]["stop_reason"], "tool_use");
+        assert_eq!(events[7].1["usage"]["output_tokens"], 7);
+        assert!(translator.finish().is_empty());
+    }
+}
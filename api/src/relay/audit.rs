@@ -0,0 +1,282 @@
+use crate::config::model::{AuditLogSettings, AuditSink};
+use crate::loadbalance::TokenUsage;
+use crate::relay::cache::sha256_hex;
+use crate::relay::moderation::ModerationVerdict;
+use serde::Serialize;
+use serde_json::Value;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// 消息内容脱敏后的占位符
+const REDACTED: &str = "[redacted]";
+
+/// 一条请求审计记录
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    pub user: String,
+    /// API密钥SHA-256摘要的前12位，可用于区分同一用户的多个密钥而不泄露密钥本身
+    pub key_id: String,
+    pub model: String,
+    /// 实际处理请求的后端（provider:model），缓存命中或未选中后端时为空
+    pub backend: Option<String>,
+    pub status: u16,
+    pub latency_ms: u64,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub error: Option<String>,
+    pub messages: Option<Value>,
+    /// 内容审核结果，未启用审核或请求中没有用户输入时为空
+    pub moderation: Option<ModerationVerdict>,
+    /// 客户端IP，只在路由层拒绝的请求中记录
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+}
+
+impl AuditRecord {
+    /// 根据请求创建审计记录，状态、后端、用量等在响应完成后填写
+    pub fn new(settings: &AuditLogSettings, user: &str, token: &str, body: &Value) -> Self {
+        let messages = body.get("messages").map(|messages| {
+            if settings.redact_content {
+                Self::redact(messages)
+            } else {
+                messages.clone()
+            }
+        });
+
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            user: user.to_string(),
+            key_id: sha256_hex(token.as_bytes())[..12].to_string(),
+            model: body
+                .get("model")
+                .and_then(|m| m.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            backend: None,
+            status: 0,
+            latency_ms: 0,
+            prompt_tokens: None,
+            completion_tokens: None,
+            error: None,
+            messages,
+            moderation: None,
+            client_ip: None,
+        }
+    }
+
+    /// 在路由层被拒绝的请求，未携带密钥或密钥无效时用户为anonymous
+    pub fn rejected(user: Option<&str>, token: Option<&str>, client_ip: Option<IpAddr>, status: u16, error: String) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            user: user.unwrap_or("anonymous").to_string(),
+            key_id: token
+                .map(|token| sha256_hex(token.as_bytes())[..12].to_string())
+                .unwrap_or_default(),
+            model: String::new(),
+            backend: None,
+            status,
+            latency_ms: 0,
+            prompt_tokens: None,
+            completion_tokens: None,
+            error: Some(error),
+            messages: None,
+            moderation: None,
+            client_ip: client_ip.map(|ip| ip.to_string()),
+        }
+    }
+
+    /// 填写token用量
+    pub fn set_usage(&mut self, usage: Option<TokenUsage>) {
+        self.prompt_tokens = usage.map(|u| u.prompt_tokens);
+        self.completion_tokens = usage.map(|u| u.completion_tokens);
+    }
+
+    /// 将消息内容替换为占位符，保留角色和消息数量
+    fn redact(messages: &Value) -> Value {
+        match messages {
+            Value::Array(items) => Value::Array(items.iter().map(Self::redact).collect()),
+            Value::Object(message) => Value::Object(
+                message
+                    .iter()
+                    .map(|(key, value)| match key.as_str() {
+                        "role" | "name" | "tool_call_id" => (key.clone(), value.clone()),
+                        _ => (key.clone(), Value::String(REDACTED.to_string())),
+                    })
+                    .collect(),
+            ),
+            _ => Value::String(REDACTED.to_string()),
+        }
+    }
+}
+
+/// 审计日志记录器
+/// 记录通过通道交给后台任务写出，不阻塞请求处理；输出目标变更（配置热加载）时重建后台任务
+pub struct AuditLogger {
+    writer: std::sync::Mutex<Option<(AuditSink, mpsc::UnboundedSender<AuditRecord>)>>,
+}
+
+impl AuditLogger {
+    pub fn new() -> Self {
+        Self {
+            writer: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// 写出一条审计记录
+    pub fn log(&self, settings: &AuditLogSettings, record: AuditRecord) {
+        let Ok(mut writer) = self.writer.lock() else {
+            return;
+        };
+
+        if writer.as_ref().is_none_or(|(sink, _)| sink != &settings.sink) {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            tokio::spawn(Self::run(settings.sink.clone(), receiver));
+            *writer = Some((settings.sink.clone(), sender));
+        }
+
+        if let Some((_, sender)) = writer.as_ref() {
+            let _ = sender.send(record);
+        }
+    }
+
+    async fn run(sink: AuditSink, mut receiver: mpsc::UnboundedReceiver<AuditRecord>) {
+        let client = reqwest::Client::new();
+        while let Some(record) = receiver.recv().await {
+            let result = match &sink {
+                AuditSink::Stdout => match serde_json::to_string(&record) {
+                    Ok(line) => {
+                        println!("{}", line);
+                        Ok(())
+                    }
+                    Err(e) => Err(e.into()),
+                },
+                AuditSink::File {
+                    path,
+                    max_size_mb,
+                    max_files,
+                } => Self::write_file(path, max_size_mb * 1024 * 1024, *max_files, &record).await,
+                AuditSink::Webhook {
+                    url,
+                    timeout_seconds,
+                } => client
+                    .post(url)
+                    .timeout(Duration::from_secs(*timeout_seconds))
+                    .json(&record)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map(|_| ())
+                    .map_err(Into::into),
+            };
+
+            if let Err(e) = result {
+                warn!("Failed to write audit record: {}", e);
+            }
+        }
+    }
+
+    /// 追加写入文件，写入前文件超过大小上限时先轮转
+    pub(crate) async fn write_file<T: Serialize>(
+        path: &str,
+        max_bytes: u64,
+        max_files: u32,
+        record: &T,
+    ) -> anyhow::Result<()> {
+        if let Ok(metadata) = tokio::fs::metadata(path).await
+            && metadata.len() >= max_bytes
+        {
+            Self::rotate(path, max_files).await;
+        }
+
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(&line).await?;
+        Ok(())
+    }
+
+    /// path.N-1 -> path.N ... path -> path.1，超出max_files的最旧文件被覆盖
+    async fn rotate(path: &str, max_files: u32) {
+        if max_files == 0 {
+            let _ = tokio::fs::remove_file(path).await;
+            return;
+        }
+        for index in (1..max_files).rev() {
+            let _ = tokio::fs::rename(format!("{}.{}", path, index), format!("{}.{}", path, index + 1)).await;
+        }
+        let _ = tokio::fs::rename(path, format!("{}.1", path)).await;
+    }
+}
+
+impl Default for AuditLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_redacts_content() {
+        let settings = AuditLogSettings {
+            sink: AuditSink::Stdout,
+            redact_content: true,
+        };
+        let body = json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "my secret"}]
+        });
+
+        let record = AuditRecord::new(&settings, "alice", "sk-token", &body);
+        assert_eq!(record.model, "gpt-4");
+        assert_eq!(record.key_id.len(), 12);
+        assert_eq!(
+            record.messages,
+            Some(json!([{"role": "user", "content": "[redacted]"}]))
+        );
+
+        let settings = AuditLogSettings {
+            redact_content: false,
+            ..settings
+        };
+        let record = AuditRecord::new(&settings, "alice", "sk-token", &body);
+        assert_eq!(record.messages, body.get("messages").cloned());
+    }
+
+    #[tokio::test]
+    async fn test_file_sink_rotates() {
+        let dir = std::env::temp_dir().join(format!("berry-audit-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("audit.log").to_string_lossy().to_string();
+        let settings = AuditLogSettings {
+            sink: AuditSink::Stdout,
+            redact_content: true,
+        };
+        let record = AuditRecord::new(&settings, "alice", "sk-token", &json!({"model": "gpt-4"}));
+
+        // 上限1字节：每次写入前都会轮转，最多保留2个历史文件
+        for _ in 0..4 {
+            AuditLogger::write_file(&path, 1, 2, &record).await.unwrap();
+        }
+        assert!(tokio::fs::metadata(&path).await.is_ok());
+        assert!(tokio::fs::metadata(format!("{}.2", path)).await.is_ok());
+        assert!(tokio::fs::metadata(format!("{}.3", path)).await.is_err());
+
+        let line = tokio::fs::read_to_string(&path).await.unwrap();
+        let value: Value = serde_json::from_str(line.trim()).unwrap();
+        assert_eq!(value["user"], "alice");
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+}
@@ -0,0 +1,202 @@
+use crate::config::model::GlobalSettings;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// 标记响应是否来自缓存的响应头
+pub const CACHE_STATUS_HEADER: &str = "x-berry-cache";
+
+/// 不影响生成结果、不参与缓存键计算的请求字段
+pub(crate) const IGNORED_FIELDS: &[&str] = &["stream", "stream_options", "user"];
+
+/// 计算SHA-256摘要的十六进制表示
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    let digest: [u8; 32] = Sha256::digest(data).into();
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+struct CacheEntry {
+    body: String,
+    inserted_at: Instant,
+}
+
+/// 缓存统计
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// 非流式聊天响应缓存
+/// 仅缓存temperature为0的确定性请求，重复请求直接返回缓存结果以节省上游费用
+pub struct ResponseCache {
+    entries: std::sync::RwLock<HashMap<String, CacheEntry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new() -> Self {
+        Self {
+            entries: std::sync::RwLock::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// 计算请求的缓存键，请求不可缓存时返回None
+    pub fn cache_key(settings: &GlobalSettings, body: &Value) -> Option<String> {
+        if !settings.response_cache_enabled {
+            return None;
+        }
+        if body.get("stream").and_then(|s| s.as_bool()).unwrap_or(false) {
+            return None;
+        }
+        // 未指定temperature时上游默认非0，结果不确定
+        if body.get("temperature").and_then(|t| t.as_f64()) != Some(0.0) {
+            return None;
+        }
+
+        let mut normalized = body.as_object()?.clone();
+        for field in IGNORED_FIELDS {
+            normalized.remove(*field);
+        }
+        // 0与0.0序列化结果不同，统一为同一个值
+        normalized.insert("temperature".to_string(), Value::from(0.0));
+        // serde_json的Map按键排序，序列化结果与字段顺序无关
+        let serialized = serde_json::to_string(&normalized).ok()?;
+        Some(sha256_hex(serialized.as_bytes()))
+    }
+
+    /// 查找未过期的缓存响应
+    pub fn get(&self, settings: &GlobalSettings, key: &str) -> Option<String> {
+        let ttl = Duration::from_secs(settings.response_cache_ttl_seconds);
+        let cached = self.entries.read().ok().and_then(|entries| {
+            entries
+                .get(key)
+                .filter(|entry| entry.inserted_at.elapsed() < ttl)
+                .map(|entry| entry.body.clone())
+        });
+
+        if cached.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        cached
+    }
+
+    /// 写入缓存，超出容量时先清理过期条目，再淘汰最早写入的条目
+    pub fn insert(&self, settings: &GlobalSettings, key: String, body: String) {
+        let ttl = Duration::from_secs(settings.response_cache_ttl_seconds);
+        let max_entries = settings.response_cache_max_entries;
+        if max_entries == 0 || ttl.is_zero() {
+            return;
+        }
+
+        if let Ok(mut entries) = self.entries.write() {
+            if entries.len() >= max_entries && !entries.contains_key(&key) {
+                entries.retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+                while entries.len() >= max_entries {
+                    let Some(oldest) = entries
+                        .iter()
+                        .min_by_key(|(_, entry)| entry.inserted_at)
+                        .map(|(k, _)| k.clone())
+                    else {
+                        break;
+                    };
+                    entries.remove(&oldest);
+                }
+            }
+
+            debug!("Caching response for key {}", key);
+            entries.insert(
+                key,
+                CacheEntry {
+                    body,
+                    inserted_at: Instant::now(),
+                },
+            );
+        }
+    }
+
+    /// 获取缓存统计
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.read().map(|e| e.len()).unwrap_or(0),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings() -> GlobalSettings {
+        GlobalSettings {
+            response_cache_enabled: true,
+            response_cache_max_entries: 2,
+            ..GlobalSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_cache_key_only_for_deterministic_requests() {
+        let settings = settings();
+        let messages = json!([{"role": "user", "content": "hi"}]);
+
+        let key = ResponseCache::cache_key(
+            &settings,
+            &json!({"model": "gpt-4", "messages": messages, "temperature": 0, "user": "a"}),
+        );
+        // 字段顺序和user字段不影响缓存键
+        let same = ResponseCache::cache_key(
+            &settings,
+            &json!({"temperature": 0.0, "messages": messages, "model": "gpt-4", "user": "b"}),
+        );
+        assert!(key.is_some());
+        assert_eq!(key, same);
+
+        assert!(ResponseCache::cache_key(&settings, &json!({"model": "gpt-4", "messages": messages})).is_none());
+        assert!(ResponseCache::cache_key(
+            &settings,
+            &json!({"model": "gpt-4", "messages": messages, "temperature": 0, "stream": true})
+        )
+        .is_none());
+        assert!(ResponseCache::cache_key(
+            &GlobalSettings::default(),
+            &json!({"model": "gpt-4", "messages": messages, "temperature": 0})
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_insert_evicts_oldest() {
+        let settings = settings();
+        let cache = ResponseCache::new();
+
+        cache.insert(&settings, "a".to_string(), "1".to_string());
+        cache.insert(&settings, "b".to_string(), "2".to_string());
+        cache.insert(&settings, "c".to_string(), "3".to_string());
+
+        assert!(cache.get(&settings, "a").is_none());
+        assert_eq!(cache.get(&settings, "c").as_deref(), Some("3"));
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 2);
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::config::model::{RequestCaptureSettings, UserToken};
+use crate::relay::audit::AuditLogger;
+use crate::relay::cache::sha256_hex;
+use axum::body::Body;
+use axum::http::HeaderMap;
+use axum::response::Response;
+use futures::StreamExt;
+use rand::Rng;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// 管理员要求抓取本次请求的请求头，值为1或true；其他用户携带时忽略
+pub const CAPTURE_HEADER: &str = "x-berry-capture";
+
+/// 不写入抓取记录的请求头，回放时使用回放工具自己的密钥
+const SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-api-key",
+    "api-key",
+    "x-goog-api-key",
+];
+
+/// 等待写入的最大记录数，写入过慢时超出部分被丢弃
+const QUEUE_CAPACITY: usize = 1000;
+
+/// 一条抓取记录：脱敏后的完整请求和客户端收到的响应
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureRecord {
+    pub timestamp: String,
+    pub request_id: Option<String>,
+    pub user: String,
+    /// API密钥SHA-256摘要的前12位
+    pub key_id: String,
+    /// 请求路径，如 /v1/chat/completions
+    pub path: String,
+    pub request_headers: BTreeMap<String, String>,
+    pub request_body: Value,
+    pub status: u16,
+    /// 实际处理请求的后端（provider:model），缓存命中或未选中后端时为空
+    pub backend: Option<String>,
+    pub latency_ms: u64,
+    pub response_body: String,
+    /// 响应体超过max_response_kb被截断
+    pub truncated: bool,
+}
+
+impl CaptureRecord {
+    /// 根据请求创建抓取记录，body应为已按日志规则脱敏的请求体，响应在完成后填写
+    pub fn new(user: &str, token: &str, path: &str, headers: &HeaderMap, body: &Value) -> Self {
+        let request_headers = headers
+            .iter()
+            .filter(|(name, _)| !SENSITIVE_HEADERS.contains(&name.as_str()))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            request_id: crate::telemetry::current_request_id(),
+            user: user.to_string(),
+            key_id: sha256_hex(token.as_bytes())[..12].to_string(),
+            path: path.to_string(),
+            request_headers,
+            request_body: body.clone(),
+            status: 0,
+            backend: None,
+            latency_ms: 0,
+            response_body: String::new(),
+            truncated: false,
+        }
+    }
+}
+
+/// 请求抓取记录器
+/// 记录通过通道交给后台任务写入文件，文件配置变更（配置热加载）时重建后台任务
+pub struct RequestCapture {
+    writer: std::sync::Mutex<Option<(RequestCaptureSettings, mpsc::Sender<CaptureRecord>)>>,
+}
+
+impl RequestCapture {
+    pub fn new() -> Self {
+        Self {
+            writer: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// 是否抓取该请求：管理员携带抓取请求头、属于指定用户或被抽样选中
+    pub fn should_capture(settings: &RequestCaptureSettings, user: &UserToken, headers: &HeaderMap) -> bool {
+        let requested = user.is_admin()
+            && headers
+                .get(CAPTURE_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true"));
+        requested
+            || settings.users.contains(&user.name)
+            || (settings.sample_percent > 0.0 && rand::rng().random::<f64>() * 100.0 < settings.sample_percent)
+    }
+
+    /// 在响应体传输过程中记录响应内容，传输完成后写出抓取记录
+    pub fn capture(
+        &self,
+        settings: &RequestCaptureSettings,
+        mut record: CaptureRecord,
+        response: Response,
+        start_time: Instant,
+    ) -> Response {
+        let Some(sender) = self.sender(settings) else {
+            return response;
+        };
+        let max_bytes = settings.max_response_kb * 1024;
+        record.status = response.status().as_u16();
+
+        let (parts, body) = response.into_parts();
+        let stream = futures::stream::unfold(
+            (body.into_data_stream(), Vec::new(), false, Some(record)),
+            move |(mut stream, mut buffer, mut truncated, mut record)| {
+                let sender = sender.clone();
+                async move {
+                    match stream.next().await {
+                        Some(chunk) => {
+                            if let Ok(bytes) = &chunk {
+                                let remaining = max_bytes.saturating_sub(buffer.len());
+                                truncated |= bytes.len() > remaining;
+                                buffer.extend_from_slice(&bytes[..bytes.len().min(remaining)]);
+                            }
+                            Some((chunk, (stream, buffer, truncated, record)))
+                        }
+                        None => {
+                            if let Some(mut record) = record.take() {
+                                record.latency_ms = start_time.elapsed().as_millis() as u64;
+                                record.response_body = String::from_utf8_lossy(&buffer).into_owned();
+                                record.truncated = truncated;
+                                if sender.try_send(record).is_err() {
+                                    warn!("Request capture queue is full, dropping capture");
+                                }
+                            }
+                            None
+                        }
+                    }
+                }
+            },
+        );
+        Response::from_parts(parts, Body::from_stream(stream))
+    }
+
+    /// 当前配置对应的写入通道
+    fn sender(&self, settings: &RequestCaptureSettings) -> Option<mpsc::Sender<CaptureRecord>> {
+        let mut writer = self.writer.lock().ok()?;
+        let changed = writer.as_ref().is_none_or(|(current, _)| {
+            (&current.path, current.max_size_mb, current.max_files)
+                != (&settings.path, settings.max_size_mb, settings.max_files)
+        });
+        if changed {
+            let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+            tokio::spawn(Self::run(settings.clone(), receiver));
+            *writer = Some((settings.clone(), sender));
+        }
+        writer.as_ref().map(|(_, sender)| sender.clone())
+    }
+
+    async fn run(settings: RequestCaptureSettings, mut receiver: mpsc::Receiver<CaptureRecord>) {
+        let max_bytes = settings.max_size_mb * 1024 * 1024;
+        while let Some(record) = receiver.recv().await {
+            if let Err(e) = AuditLogger::write_file(&settings.path, max_bytes, settings.max_files, &record).await {
+                warn!("Failed to write request capture: {}", e);
+            }
+        }
+    }
+}
+
+impl Default for RequestCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings(path: &str) -> RequestCaptureSettings {
+        RequestCaptureSettings {
+            path: path.to_string(),
+            max_size_mb: 100,
+            max_files: 1,
+            sample_percent: 0.0,
+            users: vec!["alice".to_string()],
+            max_response_kb: 1,
+        }
+    }
+
+    fn user(name: &str, tags: &[&str]) -> UserToken {
+        UserToken {
+            name: name.to_string(),
+            token: format!("{}-token", name),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_capture_selection_and_sanitized_headers() {
+        let settings = settings("unused");
+        let mut headers = HeaderMap::new();
+        assert!(RequestCapture::should_capture(&settings, &user("alice", &[]), &headers));
+        assert!(!RequestCapture::should_capture(&settings, &user("bob", &[]), &headers));
+        // 抓取请求头只对管理员有效
+        headers.insert(CAPTURE_HEADER, "true".parse().unwrap());
+        assert!(!RequestCapture::should_capture(&settings, &user("bob", &[]), &headers));
+        assert!(RequestCapture::should_capture(&settings, &user("bob", &["admin"]), &headers));
+
+        headers.insert("authorization", "Bearer sk-secret".parse().unwrap());
+        headers.insert("api-key", "sk-azure".parse().unwrap());
+        headers.insert("x-goog-api-key", "sk-gemini".parse().unwrap());
+        let record = CaptureRecord::new("bob", "sk-secret", "/v1/chat/completions", &headers, &json!({}));
+        assert!(!record.request_headers.contains_key("authorization"));
+        assert!(!record.request_headers.contains_key("api-key"));
+        assert!(!record.request_headers.contains_key("x-goog-api-key"));
+        assert_eq!(record.request_headers[CAPTURE_HEADER], "true");
+        assert!(!serde_json::to_string(&record).unwrap().contains("sk-secret"));
+    }
+
+    #[tokio::test]
+    async fn test_capture_truncates_response_body() {
+        let dir = std::env::temp_dir().join(format!("berry-capture-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("captures.jsonl").to_string_lossy().to_string();
+        let capture = RequestCapture::new();
+        let record = CaptureRecord::new("alice", "sk", "/v1/chat/completions", &HeaderMap::new(), &json!({"model": "gpt-4"}));
+
+        let response = capture.capture(&settings(&path), record, Response::new(Body::from("x".repeat(1500))), Instant::now());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.len(), 1500);
+
+        let mut line = String::new();
+        for _ in 0..50 {
+            line = tokio::fs::read_to_string(&path).await.unwrap_or_default();
+            if !line.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let value: Value = serde_json::from_str(line.trim()).unwrap();
+        assert_eq!(value["status"], 200);
+        assert_eq!(value["response_body"].as_str().unwrap().len(), 1024);
+        assert_eq!(value["truncated"], true);
+        assert_eq!(value["request_body"]["model"], "gpt-4");
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+}
@@ -1,4 +1,5 @@
 pub mod openai;
+pub mod tls;
 pub mod types;
 
 pub use types::*;
@@ -1,14 +1,30 @@
 use reqwest::Client;
 use serde_json::Value;
+use std::sync::Arc;
 use std::time::Duration;
-use super::types::{ClientError, ClientResponse};
+use super::types::{ClientError, ClientResponse, FormField};
+use crate::config::model::{HttpVersion, Provider};
+use crate::loadbalance::MetricsCollector;
+use crate::relay::fault::{Fault, FaultHook};
 
 const OPENAI_API_URL: &str = "https://aigc.x-see.cn/v1";
 
+/// 上游OpenAI兼容接口路径（相对于base_url）
+pub const CHAT_COMPLETIONS_PATH: &str = "/chat/completions";
+pub const COMPLETIONS_PATH: &str = "/completions";
+pub const EMBEDDINGS_PATH: &str = "/embeddings";
+pub const MODERATIONS_PATH: &str = "/moderations";
+pub const AUDIO_TRANSCRIPTIONS_PATH: &str = "/audio/transcriptions";
+pub const AUDIO_SPEECH_PATH: &str = "/audio/speech";
+pub const FILES_PATH: &str = "/files";
+pub const RESPONSES_PATH: &str = "/responses";
+
 #[derive(Clone)]
 pub struct OpenAIClient {
     client: Client,
     base_url: String,
+    /// 启用故障注入时发送请求前后注入故障
+    faults: Option<FaultHook>,
 }
 
 impl OpenAIClient {
@@ -29,6 +45,7 @@ impl OpenAIClient {
         Self {
             client,
             base_url: OPENAI_API_URL.to_string(),
+            faults: None,
         }
     }
 
@@ -41,9 +58,89 @@ impl OpenAIClient {
         Self {
             client,
             base_url,
+            faults: None,
         }
     }
 
+    /// 根据provider配置创建客户端，启用TLS证书固定时使用固定校验
+    /// 连接超时依次取timeouts.connect_seconds、http_client.connect_timeout_seconds，都未配置时使用connect_timeout
+    pub fn for_provider(
+        provider_id: &str,
+        provider: &Provider,
+        connect_timeout: Duration,
+        metrics: Arc<MetricsCollector>,
+    ) -> Result<Self, ClientError> {
+        let settings = provider.http_client.clone().unwrap_or_default();
+        let mut builder = Client::builder().connect_timeout(
+            provider
+                .timeouts
+                .as_ref()
+                .and_then(|timeouts| timeouts.connect_seconds)
+                .or(settings.connect_timeout_seconds)
+                .map(Duration::from_secs)
+                .unwrap_or(connect_timeout),
+        );
+        if let Some(max_idle) = settings.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(seconds) = settings.pool_idle_timeout_seconds {
+            builder = builder.pool_idle_timeout(Duration::from_secs(seconds));
+        }
+        if let Some(seconds) = settings.tcp_keepalive_seconds {
+            builder = builder.tcp_keepalive(Duration::from_secs(seconds));
+        }
+        if let Some(seconds) = settings.request_timeout_seconds {
+            builder = builder.timeout(Duration::from_secs(seconds));
+        }
+        if let Some(proxy) = &provider.proxy {
+            let mut upstream_proxy = reqwest::Proxy::all(&proxy.url)?;
+            if let Some(username) = &proxy.username {
+                upstream_proxy =
+                    upstream_proxy.basic_auth(username, proxy.password.as_deref().unwrap_or_default());
+            }
+            builder = builder.proxy(upstream_proxy);
+        }
+        builder = match settings.http_version {
+            HttpVersion::Auto => builder,
+            HttpVersion::Http1 => builder.http1_only(),
+            HttpVersion::Http2 => builder.http2_prior_knowledge(),
+        };
+
+        let pinning = provider.tls_pinning.as_ref().filter(|p| !p.is_empty());
+        if pinning.is_some() || provider.tls.is_some() {
+            let tls_config = super::tls::build_provider_tls_config(
+                provider_id,
+                pinning,
+                provider.tls.as_ref(),
+                metrics,
+            )?;
+            builder = builder.use_preconfigured_tls(tls_config);
+        }
+
+        let client = builder.build()?;
+
+        Ok(Self {
+            client,
+            base_url: provider.base_url.clone(),
+            faults: None,
+        })
+    }
+
+    /// 附加故障注入，发往该provider的JSON和multipart请求按请求中的模型注入故障
+    pub fn with_faults(mut self, hook: FaultHook) -> Self {
+        self.faults = Some(hook);
+        self
+    }
+
+    /// 注入请求前的故障，命中错误时返回代替上游响应的错误响应
+    async fn inject_before(&self, model: Option<&str>) -> (Option<Fault>, Option<reqwest::Response>) {
+        let Some(fault) = self.faults.as_ref().and_then(|hook| hook.fault_for(model)) else {
+            return (None, None);
+        };
+        let injected = fault.before_request().await;
+        (Some(fault), injected)
+    }
+
     // 构建请求头
     pub fn build_request_headers(
         &self,
@@ -73,16 +170,122 @@ impl OpenAIClient {
         headers: reqwest::header::HeaderMap,
         body: &Value,
     ) -> Result<reqwest::Response, ClientError> {
+        self.post_json(CHAT_COMPLETIONS_PATH, headers, body).await
+    }
+
+    // 向指定接口转发JSON请求
+    pub async fn post_json(
+        &self,
+        path: &str,
+        headers: reqwest::header::HeaderMap,
+        body: &Value,
+    ) -> Result<reqwest::Response, ClientError> {
+        let (fault, injected) = self.inject_before(body.get("model").and_then(Value::as_str)).await;
+        if let Some(response) = injected {
+            return Ok(response);
+        }
         let response = self.client
-            .post(format!("{}/chat/completions", self.base_url))
+            .post(format!("{}{}", self.base_url, path))
             .headers(headers)
             .json(body)
             .send()
             .await?;
 
+        Ok(match fault {
+            Some(fault) => fault.after_response(response),
+            None => response,
+        })
+    }
+
+    // 向指定接口转发multipart表单
+    pub async fn post_multipart(
+        &self,
+        path: &str,
+        headers: reqwest::header::HeaderMap,
+        fields: &[FormField],
+    ) -> Result<reqwest::Response, ClientError> {
+        let model = fields
+            .iter()
+            .find(|field| field.name == "model")
+            .and_then(|field| std::str::from_utf8(&field.data).ok());
+        let (fault, injected) = self.inject_before(model).await;
+        if let Some(response) = injected {
+            return Ok(response);
+        }
+        let mut form = reqwest::multipart::Form::new();
+        for field in fields {
+            let mut part = reqwest::multipart::Part::bytes(field.data.to_vec());
+            if let Some(file_name) = &field.file_name {
+                part = part.file_name(file_name.clone());
+            }
+            if let Some(content_type) = &field.content_type {
+                part = part.mime_str(content_type)?;
+            }
+            form = form.part(field.name.clone(), part);
+        }
+
+        let response = self.client
+            .post(format!("{}{}", self.base_url, path))
+            .headers(headers)
+            .multipart(form)
+            .send()
+            .await?;
+
+        Ok(match fault {
+            Some(fault) => fault.after_response(response),
+            None => response,
+        })
+    }
+
+    // 向指定接口发送GET请求
+    pub async fn get(
+        &self,
+        path: &str,
+        headers: reqwest::header::HeaderMap,
+    ) -> Result<reqwest::Response, ClientError> {
+        let response = self.client
+            .get(format!("{}{}", self.base_url, path))
+            .headers(headers)
+            .send()
+            .await?;
+
         Ok(response)
     }
 
+    // 向指定接口发送DELETE请求
+    pub async fn delete(
+        &self,
+        path: &str,
+        headers: reqwest::header::HeaderMap,
+    ) -> Result<reqwest::Response, ClientError> {
+        let response = self.client
+            .delete(format!("{}{}", self.base_url, path))
+            .headers(headers)
+            .send()
+            .await?;
+
+        Ok(response)
+    }
+
+    // 发送embedding请求
+    pub async fn embeddings(
+        &self,
+        token: &str,
+        body: &Value,
+    ) -> Result<ClientResponse, ClientError> {
+        let response = self.client
+            .post(format!("{}/embeddings", self.base_url))
+            .bearer_auth(token)
+            .json(body)
+            .send()
+            .await?;
+
+        let status = response.status().as_u16();
+        let body = response.text().await?;
+
+        Ok(ClientResponse::new(status, body))
+    }
+
     // 获取模型列表
     pub async fn models(
         &self,
@@ -107,3 +310,90 @@ impl Default for OpenAIClient {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_provider_with_http_client_settings() {
+        let provider: Provider = toml::from_str(
+            r#"
+            name = "OpenAI"
+            base_url = "https://api.openai.com/v1"
+            api_key = "sk-test"
+            models = ["gpt-4"]
+
+            [http_client]
+            pool_max_idle_per_host = 64
+            pool_idle_timeout_seconds = 90
+            http_version = "http2"
+            tcp_keepalive_seconds = 30
+            connect_timeout_seconds = 5
+            request_timeout_seconds = 600
+            "#,
+        )
+        .unwrap();
+        let settings = provider.http_client.as_ref().unwrap();
+        assert_eq!(settings.http_version, HttpVersion::Http2);
+        assert_eq!(settings.pool_max_idle_per_host, Some(64));
+
+        let metrics = Arc::new(MetricsCollector::new());
+        let client =
+            OpenAIClient::for_provider("openai", &provider, Duration::from_secs(30), metrics);
+        assert!(client.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_requests_sent_through_proxy() {
+        // 作为HTTP代理的本地服务：收到绝对形式的请求URI，返回代理认证头
+        let proxy = axum::Router::new().route(
+            "/v1/chat/completions",
+            axum::routing::post(|request: axum::extract::Request| async move {
+                axum::Json(serde_json::json!({
+                    "uri": request.uri().to_string(),
+                    "proxy_authorization": request
+                        .headers()
+                        .get("proxy-authorization")
+                        .and_then(|v| v.to_str().ok()),
+                }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, proxy).await.unwrap();
+        });
+
+        let provider: Provider = toml::from_str(&format!(
+            r#"
+            name = "Egress"
+            base_url = "http://upstream.invalid/v1"
+            api_key = "sk-test"
+            models = ["gpt-4"]
+
+            [proxy]
+            url = "http://{}"
+            username = "user"
+            password = "secret"
+            "#,
+            addr
+        ))
+        .unwrap();
+        let client = OpenAIClient::for_provider(
+            "egress",
+            &provider,
+            Duration::from_secs(5),
+            Arc::new(MetricsCollector::new()),
+        )
+        .unwrap();
+
+        let response = client
+            .chat_completions(reqwest::header::HeaderMap::new(), &serde_json::json!({}))
+            .await
+            .unwrap();
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["uri"], "http://upstream.invalid/v1/chat/completions");
+        assert_eq!(body["proxy_authorization"], "Basic dXNlcjpzZWNyZXQ=");
+    }
+}
@@ -0,0 +1,225 @@
+use std::sync::Arc;
+
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use sha2::{Digest, Sha256};
+
+use super::types::ClientError;
+use crate::config::model::{ProviderTls, TlsPinning};
+use crate::loadbalance::MetricsCollector;
+
+/// provider的服务端证书校验器
+/// 先执行常规的证书链校验（可配置跳过），配置了固定值时再要求证书链中至少一张证书匹配
+pub struct ProviderCertVerifier {
+    provider_id: String,
+    inner: Arc<WebPkiServerVerifier>,
+    skip_chain_verification: bool,
+    spki_pins: Vec<[u8; 32]>,
+    cert_pins: Vec<[u8; 32]>,
+    metrics: Arc<MetricsCollector>,
+}
+
+impl std::fmt::Debug for ProviderCertVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProviderCertVerifier")
+            .field("provider_id", &self.provider_id)
+            .field("skip_chain_verification", &self.skip_chain_verification)
+            .field("spki_pins", &self.spki_pins.len())
+            .field("cert_pins", &self.cert_pins.len())
+            .finish()
+    }
+}
+
+impl ProviderCertVerifier {
+    /// 检查单张证书是否匹配任一固定值
+    fn matches_pin(&self, cert: &CertificateDer<'_>) -> bool {
+        let cert_digest: [u8; 32] = Sha256::digest(cert.as_ref()).into();
+        if self.cert_pins.contains(&cert_digest) {
+            return true;
+        }
+
+        if self.spki_pins.is_empty() {
+            return false;
+        }
+
+        match x509_parser::parse_x509_certificate(cert.as_ref()) {
+            Ok((_, parsed)) => {
+                let spki_digest: [u8; 32] = Sha256::digest(parsed.public_key().raw).into();
+                self.spki_pins.contains(&spki_digest)
+            }
+            Err(e) => {
+                tracing::debug!("Failed to parse certificate for SPKI pinning: {}", e);
+                false
+            }
+        }
+    }
+}
+
+impl ServerCertVerifier for ProviderCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = if self.skip_chain_verification {
+            ServerCertVerified::assertion()
+        } else {
+            self.inner.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            )?
+        };
+
+        if self.spki_pins.is_empty() && self.cert_pins.is_empty() {
+            return Ok(verified);
+        }
+
+        if std::iter::once(end_entity)
+            .chain(intermediates)
+            .any(|cert| self.matches_pin(cert))
+        {
+            return Ok(verified);
+        }
+
+        self.metrics.record_tls_pin_failure(&self.provider_id);
+        tracing::error!(
+            "ALERT: TLS certificate pin mismatch for provider '{}' ({:?}), connection refused",
+            self.provider_id,
+            server_name
+        );
+        Err(rustls::Error::General(format!(
+            "certificate pin mismatch for provider '{}'",
+            self.provider_id
+        )))
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// 为配置了证书固定或TLS选项的provider构建rustls客户端配置
+pub fn build_provider_tls_config(
+    provider_id: &str,
+    pinning: Option<&TlsPinning>,
+    tls: Option<&ProviderTls>,
+    metrics: Arc<MetricsCollector>,
+) -> Result<rustls::ClientConfig, ClientError> {
+    let tls_error = |e: &dyn std::fmt::Display| ClientError::TlsConfigError(e.to_string());
+    let parse_pins = |pins: &[String]| {
+        pins.iter()
+            .map(|pin| TlsPinning::parse_pin(pin))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| tls_error(&e))
+    };
+    let spki_pins = parse_pins(pinning.map(|p| p.spki_sha256.as_slice()).unwrap_or_default())?;
+    let cert_pins = parse_pins(pinning.map(|p| p.cert_sha256.as_slice()).unwrap_or_default())?;
+    let tls = tls.cloned().unwrap_or_default();
+
+    let mut roots = rustls::RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    if let Some(ca_file) = &tls.ca_file {
+        for cert in CertificateDer::pem_file_iter(ca_file).map_err(|e| tls_error(&format!("{}: {}", ca_file, e)))? {
+            let cert = cert.map_err(|e| tls_error(&format!("{}: {}", ca_file, e)))?;
+            roots.add(cert).map_err(|e| tls_error(&format!("{}: {}", ca_file, e)))?;
+        }
+    }
+
+    let crypto_provider = Arc::new(rustls::crypto::ring::default_provider());
+    let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), crypto_provider.clone())
+        .build()
+        .map_err(|e| tls_error(&e))?;
+
+    if tls.insecure_skip_verify {
+        tracing::warn!(
+            "TLS certificate verification is disabled for provider '{}'",
+            provider_id
+        );
+    }
+    let verifier = ProviderCertVerifier {
+        provider_id: provider_id.to_string(),
+        inner,
+        skip_chain_verification: tls.insecure_skip_verify,
+        spki_pins,
+        cert_pins,
+        metrics,
+    };
+
+    let builder = rustls::ClientConfig::builder_with_provider(crypto_provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| tls_error(&e))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier));
+
+    let config = match (&tls.client_cert_file, &tls.client_key_file) {
+        (Some(cert_file), Some(key_file)) => {
+            let cert_chain = CertificateDer::pem_file_iter(cert_file)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .map_err(|e| tls_error(&format!("{}: {}", cert_file, e)))?;
+            let key = PrivateKeyDer::from_pem_file(key_file)
+                .map_err(|e| tls_error(&format!("{}: {}", key_file, e)))?;
+            builder
+                .with_client_auth_cert(cert_chain, key)
+                .map_err(|e| tls_error(&e))?
+        }
+        _ => builder.with_no_client_auth(),
+    };
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_pinned_tls_config() {
+        let pinning = TlsPinning {
+            spki_sha256: vec!["47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=".to_string()],
+            cert_sha256: vec![],
+        };
+        let metrics = Arc::new(MetricsCollector::new());
+
+        assert!(build_provider_tls_config("test-provider", Some(&pinning), None, metrics).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_pin_rejected() {
+        let pinning = TlsPinning {
+            spki_sha256: vec!["not-a-sha256-pin".to_string()],
+            cert_sha256: vec![],
+        };
+        let metrics = Arc::new(MetricsCollector::new());
+
+        assert!(matches!(
+            build_provider_tls_config("test-provider", Some(&pinning), None, metrics),
+            Err(ClientError::TlsConfigError(_))
+        ));
+    }
+}
@@ -9,6 +9,8 @@ pub enum ClientError {
     RequestError(#[from] reqwest::Error),
     #[error("JSON解析失败: {0}")]
     JsonParseError(#[from] serde_json::Error),
+    #[error("TLS配置错误: {0}")]
+    TlsConfigError(String),
     #[error("上游API返回错误: 状态码 {status}")]
     UpstreamError { status: u16, body: String },
 }
@@ -26,7 +28,16 @@ impl ClientResponse {
         Self {
             status,
             body,
-            is_success: status >= 200 && status < 300,
+            is_success: (200..300).contains(&status),
         }
     }
 }
+
+/// multipart表单中的一个字段，保存完整内容以便重试时重新构建表单
+#[derive(Debug, Clone)]
+pub struct FormField {
+    pub name: String,
+    pub file_name: Option<String>,
+    pub content_type: Option<String>,
+    pub data: bytes::Bytes,
+}
@@ -0,0 +1,301 @@
+use crate::config::model::ConcurrencyLimit;
+use crate::loadbalance::SelectedBackend;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 用户并发名额的限制范围前缀
+const USER_SCOPE: &str = "user:";
+
+/// 后端（或用户）并发名额不足时的错误
+#[derive(Debug, thiserror::Error)]
+pub enum ConcurrencyError {
+    #[error("{0} is at capacity and its wait queue is full")]
+    QueueFull(String),
+    #[error("{0} is at capacity, timed out waiting in queue after {1}ms")]
+    QueueTimeout(String, u64),
+}
+
+/// 单个限制范围（provider、后端或用户）的并发名额
+struct Slot {
+    limit: ConcurrencyLimit,
+    semaphore: Arc<Semaphore>,
+    queued: Arc<AtomicUsize>,
+}
+
+/// 请求占用的并发名额，析构时归还
+#[derive(Debug, Default)]
+pub struct ConcurrencyPermit {
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+/// 按provider和后端限制发往上游的并发请求数，也用于限制单个用户的在途请求数
+pub struct ConcurrencyLimiter {
+    slots: std::sync::Mutex<HashMap<String, Slot>>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new() -> Self {
+        Self {
+            slots: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 为选中的后端获取provider和后端两级并发名额，名额不足时在队列中等待
+    pub async fn acquire(
+        &self,
+        selected_backend: &SelectedBackend,
+    ) -> Result<ConcurrencyPermit, ConcurrencyError> {
+        let mut permits = Vec::new();
+        for (scope, limit) in Self::scopes(selected_backend) {
+            permits.push(self.acquire_scope(&scope, limit).await?);
+        }
+        Ok(ConcurrencyPermit { _permits: permits })
+    }
+
+    /// 立即获取并发名额，名额不足时不排队
+    pub fn try_acquire(&self, selected_backend: &SelectedBackend) -> Option<ConcurrencyPermit> {
+        let mut permits = Vec::new();
+        for (scope, limit) in Self::scopes(selected_backend) {
+            let (semaphore, _) = self.slot(&scope, limit);
+            permits.push(semaphore.try_acquire_owned().ok()?);
+        }
+        Some(ConcurrencyPermit { _permits: permits })
+    }
+
+    /// 获取用户的在途请求名额，名额不足时在队列中等待
+    pub async fn acquire_user(&self, user: &str, limit: &ConcurrencyLimit) -> Result<ConcurrencyPermit, ConcurrencyError> {
+        let permit = self.acquire_scope(&format!("{}{}", USER_SCOPE, user), limit).await?;
+        Ok(ConcurrencyPermit { _permits: vec![permit] })
+    }
+
+    /// 配置了并发上限的用户当前的在途请求数
+    pub fn user_in_flight(&self) -> HashMap<String, usize> {
+        let slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots
+            .iter()
+            .filter_map(|(scope, slot)| {
+                let user = scope.strip_prefix(USER_SCOPE)?;
+                let in_flight = slot.limit.max_concurrent_requests - slot.semaphore.available_permits();
+                Some((user.to_string(), in_flight))
+            })
+            .collect()
+    }
+
+    fn scopes(selected_backend: &SelectedBackend) -> Vec<(String, &ConcurrencyLimit)> {
+        let backend = &selected_backend.backend;
+        let mut scopes = Vec::new();
+        if let Some(limit) = &selected_backend.provider.concurrency {
+            scopes.push((format!("provider:{}", backend.provider), limit));
+        }
+        if let Some(limit) = &backend.concurrency {
+            scopes.push((format!("backend:{}:{}", backend.provider, backend.model), limit));
+        }
+        scopes
+    }
+
+    /// 获取限制范围对应的信号量，限制配置变化时重新创建（已占用的名额随旧信号量释放）
+    fn slot(&self, scope: &str, limit: &ConcurrencyLimit) -> (Arc<Semaphore>, Arc<AtomicUsize>) {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        let slot = slots
+            .entry(scope.to_string())
+            .and_modify(|slot| {
+                if slot.limit != *limit {
+                    *slot = Slot::new(limit);
+                }
+            })
+            .or_insert_with(|| Slot::new(limit));
+        (slot.semaphore.clone(), slot.queued.clone())
+    }
+
+    async fn acquire_scope(
+        &self,
+        scope: &str,
+        limit: &ConcurrencyLimit,
+    ) -> Result<OwnedSemaphorePermit, ConcurrencyError> {
+        let (semaphore, queued) = self.slot(scope, limit);
+        if let Ok(permit) = semaphore.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        // 名额已满，进入有界队列等待
+        if queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < limit.max_queue).then_some(n + 1)
+            })
+            .is_err()
+        {
+            return Err(ConcurrencyError::QueueFull(scope.to_string()));
+        }
+        let _queued = QueuedGuard(queued);
+        tracing::debug!("{} is at capacity, waiting in queue", scope);
+        let result = tokio::time::timeout(
+            Duration::from_millis(limit.queue_timeout_ms),
+            semaphore.acquire_owned(),
+        )
+        .await;
+
+        match result {
+            Ok(Ok(permit)) => Ok(permit),
+            _ => Err(ConcurrencyError::QueueTimeout(scope.to_string(), limit.queue_timeout_ms)),
+        }
+    }
+}
+
+/// 排队计数守卫，等待结束或请求被取消时减少排队数
+struct QueuedGuard(Arc<AtomicUsize>);
+
+impl Drop for QueuedGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Slot {
+    fn new(limit: &ConcurrencyLimit) -> Self {
+        Self {
+            limit: limit.clone(),
+            semaphore: Arc::new(Semaphore::new(limit.max_concurrent_requests)),
+            queued: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl Default for ConcurrencyLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::model::{Backend, BillingMode, Provider};
+
+    fn selected(provider_limit: Option<ConcurrencyLimit>, backend_limit: Option<ConcurrencyLimit>) -> SelectedBackend {
+        SelectedBackend {
+            backend: Backend {
+                provider: "local".to_string(),
+                model: "llama".to_string(),
+                weight: 1.0,
+                priority: 1,
+                enabled: true,
+                tags: vec![],
+                billing_mode: BillingMode::PerToken,
+                pricing: None,
+                overrides: HashMap::new(),
+                slow_start: None,
+                concurrency: backend_limit,
+                schedule: None,
+                region: None,
+                max_context: None,
+                supports_vision: true,
+                supports_tools: true,
+                supports_json_mode: true,
+                supports_streaming: true,
+                supports_responses: false,
+            },
+            provider: Provider {
+                name: "Local".to_string(),
+                base_url: "http://127.0.0.1:1/v1".to_string(),
+                api_key: "key".to_string(),
+                models: vec!["llama".to_string()],
+                headers: HashMap::new(),
+                enabled: true,
+                timeout_seconds: 5,
+                max_retries: 0,
+                tls_pinning: None,
+                tls: None,
+                health_check: None,
+                http_client: None,
+                proxy: None,
+                concurrency: provider_limit,
+                quota: None,
+                key_budget: None,
+                timeouts: None,
+            },
+            selection_time: Duration::ZERO,
+            timeouts: Default::default(),
+        }
+    }
+
+    fn limit(max_concurrent_requests: usize, max_queue: usize, queue_timeout_ms: u64) -> ConcurrencyLimit {
+        ConcurrencyLimit {
+            max_concurrent_requests,
+            max_queue,
+            queue_timeout_ms,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_when_queue_full_and_releases_on_drop() {
+        let limiter = ConcurrencyLimiter::new();
+        let backend = selected(None, Some(limit(1, 0, 1000)));
+
+        let permit = limiter.acquire(&backend).await.unwrap();
+        assert!(matches!(
+            limiter.acquire(&backend).await,
+            Err(ConcurrencyError::QueueFull(_))
+        ));
+        assert!(limiter.try_acquire(&backend).is_none());
+
+        drop(permit);
+        assert!(limiter.acquire(&backend).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_queued_request_waits_for_release_or_times_out() {
+        let limiter = Arc::new(ConcurrencyLimiter::new());
+        let backend = selected(Some(limit(1, 1, 100)), None);
+
+        let permit = limiter.acquire(&backend).await.unwrap();
+        assert!(matches!(
+            limiter.acquire(&backend).await,
+            Err(ConcurrencyError::QueueTimeout(_, 100))
+        ));
+
+        let waiter = {
+            let limiter = limiter.clone();
+            let backend = backend.clone();
+            tokio::spawn(async move { limiter.acquire(&backend).await.is_ok() })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(permit);
+        assert!(waiter.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_user_limit_tracks_in_flight() {
+        let limiter = ConcurrencyLimiter::new();
+        let user_limit = limit(2, 0, 100);
+
+        let first = limiter.acquire_user("alice", &user_limit).await.unwrap();
+        let _second = limiter.acquire_user("alice", &user_limit).await.unwrap();
+        assert!(matches!(
+            limiter.acquire_user("alice", &user_limit).await,
+            Err(ConcurrencyError::QueueFull(_))
+        ));
+        // 用户之间互不影响，后端的限制范围不计入用户在途数
+        let _other = limiter.acquire_user("bob", &user_limit).await.unwrap();
+        let _backend = limiter.acquire(&selected(Some(limit(1, 0, 100)), None)).await.unwrap();
+        assert_eq!(limiter.user_in_flight(), HashMap::from([("alice".to_string(), 2), ("bob".to_string(), 1)]));
+
+        drop(first);
+        assert_eq!(limiter.user_in_flight()["alice"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_provider_limit_shared_across_backends() {
+        let limiter = ConcurrencyLimiter::new();
+        let first = selected(Some(limit(1, 0, 100)), None);
+        let mut second = first.clone();
+        second.backend.model = "qwen".to_string();
+
+        let _permit = limiter.acquire(&first).await.unwrap();
+        assert!(limiter.acquire(&second).await.is_err());
+        // 没有配置限制的后端不受影响
+        assert!(limiter.try_acquire(&selected(None, None)).is_some());
+    }
+}
@@ -0,0 +1,206 @@
+use crate::loadbalance::SelectionExplanation;
+use crate::relay::error::RelayError;
+use axum::body::Body;
+use axum::http::{HeaderValue, header};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// 管理员请求选择过程说明的请求头，值为1或true
+pub const DEBUG_HEADER: &str = "x-berry-debug";
+
+/// 选择说明的响应头：各模型生效的负载均衡策略（逗号分隔）
+pub const DEBUG_STRATEGY_HEADER: &str = "x-berry-debug-strategy";
+/// 选择说明的响应头：各后端的有效权重、健康状态和被排除的原因（JSON）
+pub const DEBUG_CANDIDATES_HEADER: &str = "x-berry-debug-candidates";
+/// 选择说明的响应头：依次尝试的后端和结果（JSON）
+pub const DEBUG_ATTEMPTS_HEADER: &str = "x-berry-debug-attempts";
+
+tokio::task_local! {
+    static TRACE: DebugTrace;
+}
+
+/// 单次尝试的后端和结果
+#[derive(Debug, Clone, Serialize)]
+pub struct AttemptRecord {
+    pub attempt: usize,
+    pub model: String,
+    /// 选中的后端（provider:model），选择失败时为空
+    pub backend: Option<String>,
+    pub outcome: String,
+}
+
+#[derive(Debug, Default)]
+struct TraceState {
+    selections: Vec<SelectionExplanation>,
+    attempts: Vec<AttemptRecord>,
+    late_outcomes: Vec<(String, String)>,
+}
+
+/// 一个请求的选择过程记录，在作用域内由重试循环写入，请求结束时写入响应头
+#[derive(Debug, Clone, Default)]
+pub struct DebugTrace(Arc<Mutex<TraceState>>);
+
+/// 请求头的值是否开启选择说明
+pub fn is_enabled(value: &str) -> bool {
+    matches!(value.to_ascii_lowercase().as_str(), "1" | "true")
+}
+
+/// 记录一次后端选择的说明，只在开启选择说明的请求中计算
+pub fn record_selection(explain: impl FnOnce() -> Option<SelectionExplanation>) {
+    let _ = TRACE.try_with(|trace| {
+        if let Some(explanation) = explain() {
+            trace.state().selections.push(explanation);
+        }
+    });
+}
+
+/// 记录一次尝试的结果
+pub fn record_attempt(model: &str, backend: Option<&str>, outcome: impl Into<String>) {
+    let _ = TRACE.try_with(|trace| trace.record_attempt(model, backend, outcome));
+}
+
+/// 当前作用域的选择过程记录，用于在后台任务中补记尝试结果
+pub fn current() -> Option<DebugTrace> {
+    TRACE.try_with(DebugTrace::clone).ok()
+}
+
+impl DebugTrace {
+    fn record_attempt(&self, model: &str, backend: Option<&str>, outcome: impl Into<String>) {
+        let mut state = self.state();
+        let attempt = state.attempts.len() + 1;
+        state.attempts.push(AttemptRecord {
+            attempt,
+            model: model.to_string(),
+            backend: backend.map(str::to_string),
+            outcome: outcome.into(),
+        });
+    }
+
+    /// 补记后端最近一次尝试的最终结果，用于响应发出后才完成的上游请求（保活响应）
+    /// 写入响应头时覆盖该后端最近一次尝试的结果
+    pub fn record_late_outcome(&self, backend: &str, outcome: impl Into<String>) {
+        self.state().late_outcomes.push((backend.to_string(), outcome.into()));
+    }
+
+    /// 在选择说明的作用域内处理请求，trace为空时直接执行
+    pub async fn scope<F: Future>(trace: Option<Self>, future: F) -> F::Output {
+        match trace {
+            Some(trace) => TRACE.scope(trace, future).await,
+            None => future.await,
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, TraceState> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 把选择说明写入响应头
+    /// 非流式响应先读完响应体（保活响应的结果在响应头发出后才确定），流式响应在首个事件前已经完成选择
+    pub async fn attach(self, response: Response) -> Response {
+        let is_event_stream = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        let mut response = if is_event_stream {
+            response
+        } else {
+            let (mut parts, body) = response.into_parts();
+            let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+                Ok(bytes) => bytes,
+                Err(e) => return RelayError::Internal(format!("Failed to read response body: {}", e)).into_response(),
+            };
+            parts.headers.remove(header::TRANSFER_ENCODING);
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(bytes))
+        };
+
+        let mut state = self.state();
+        let late_outcomes = std::mem::take(&mut state.late_outcomes);
+        for (backend, outcome) in late_outcomes {
+            if let Some(attempt) = state
+                .attempts
+                .iter_mut()
+                .rev()
+                .find(|attempt| attempt.backend.as_deref() == Some(backend.as_str()))
+            {
+                attempt.outcome = outcome;
+            }
+        }
+        let strategies: Vec<&str> = state.selections.iter().map(|s| s.strategy.as_str()).collect();
+        let candidates: Vec<serde_json::Value> = state
+            .selections
+            .iter()
+            .flat_map(|selection| {
+                selection.candidates.iter().map(|candidate| {
+                    let mut value = serde_json::json!(candidate);
+                    value["model"] = serde_json::json!(selection.model);
+                    value
+                })
+            })
+            .collect();
+        let headers = response.headers_mut();
+        headers.insert(DEBUG_STRATEGY_HEADER, header_value(strategies.join(",")));
+        headers.insert(DEBUG_CANDIDATES_HEADER, header_value(serde_json::json!(candidates).to_string()));
+        headers.insert(DEBUG_ATTEMPTS_HEADER, header_value(serde_json::json!(state.attempts).to_string()));
+        response
+    }
+}
+
+/// 响应头只能包含可见ASCII字符，其他字符替换为?
+fn header_value(value: String) -> HeaderValue {
+    let value: String = value
+        .chars()
+        .map(|c| if c == ' ' || c.is_ascii_graphic() { c } else { '?' })
+        .collect();
+    HeaderValue::from_str(&value).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_records_only_inside_scope() {
+        record_attempt("gpt-4", Some("a:gpt-4"), "ignored");
+
+        let trace = DebugTrace::default();
+        DebugTrace::scope(Some(trace.clone()), async {
+            record_attempt("gpt-4", Some("a:gpt-4"), "Upstream returned HTTP 500");
+            record_attempt("gpt-4", None, "selection failed");
+        })
+        .await;
+
+        let response = trace.attach(Response::new(Body::from("{}"))).await;
+        let attempts: serde_json::Value =
+            serde_json::from_str(response.headers()[DEBUG_ATTEMPTS_HEADER].to_str().unwrap()).unwrap();
+        assert_eq!(attempts.as_array().unwrap().len(), 2);
+        assert_eq!(attempts[1]["attempt"], 2);
+        assert!(attempts[1]["backend"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_late_outcome_overrides_last_attempt() {
+        let trace = DebugTrace::default();
+        DebugTrace::scope(Some(trace.clone()), async {
+            record_attempt("gpt-4", Some("a:gpt-4"), "success");
+            let background = current().unwrap();
+            tokio::spawn(async move { background.record_late_outcome("a:gpt-4", "Upstream returned HTTP 500") })
+                .await
+                .unwrap();
+        })
+        .await;
+
+        let response = trace.attach(Response::new(Body::from("{}"))).await;
+        let attempts: serde_json::Value =
+            serde_json::from_str(response.headers()[DEBUG_ATTEMPTS_HEADER].to_str().unwrap()).unwrap();
+        assert_eq!(attempts[0]["outcome"], "Upstream returned HTTP 500");
+    }
+
+    #[test]
+    fn test_header_value_replaces_non_ascii() {
+        assert_eq!(header_value("上游 error".to_string()), "?? error");
+    }
+}
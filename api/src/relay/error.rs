@@ -0,0 +1,437 @@
+use crate::config::model::RetryPolicy;
+use crate::loadbalance::RequestResult;
+use crate::relay::admission::AdmissionError;
+use crate::relay::client::ClientError;
+use crate::relay::timeouts::TimeoutKind;
+use crate::relay::handler::types::{ErrorType, error_json};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use reqwest::StatusCode;
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use std::time::Duration;
+
+/// 上游给出的冷却时间上限，避免异常的响应头让后端长期不可用
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(600);
+
+/// 单次上游请求失败的原因，重试全部失败时按最后一次的原因向客户端返回错误
+#[derive(Debug, thiserror::Error)]
+pub enum UpstreamError {
+    #[error("Upstream request timed out ({0}): {1}")]
+    Timeout(TimeoutKind, String),
+    #[error("Upstream returned HTTP {0}")]
+    Status(StatusCode),
+    /// 上游限流，附带从响应头解析出的重试等待时间
+    #[error("Upstream returned HTTP 429 Too Many Requests")]
+    RateLimited(Option<Duration>),
+    #[error("Upstream request failed: {0}")]
+    Request(String),
+    /// JSON模式的请求返回了不合法的JSON内容
+    #[error("Upstream returned invalid JSON content: {0}")]
+    InvalidJson(String),
+}
+
+impl UpstreamError {
+    /// 由上游的错误状态码构造，429按响应头解析重试等待时间
+    pub fn from_response(status: StatusCode, headers: &HeaderMap) -> Self {
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            Self::RateLimited(retry_after(headers))
+        } else {
+            Self::Status(status)
+        }
+    }
+
+    /// 对应的后端请求结果：给出了重试时间的限流让后端冷却，其他错误计为后端失败
+    pub fn request_result(&self) -> RequestResult {
+        match self {
+            Self::RateLimited(Some(retry_after)) => RequestResult::RateLimited {
+                retry_after: *retry_after,
+            },
+            Self::RateLimited(None) => RequestResult::Failure {
+                error: "HTTP 429".to_string(),
+            },
+            Self::Status(status) => RequestResult::Failure {
+                error: format!("HTTP {}", status.as_u16()),
+            },
+            Self::Timeout(..) | Self::Request(_) | Self::InvalidJson(_) => RequestResult::Failure {
+                error: self.to_string(),
+            },
+        }
+    }
+
+    /// 按模型的重试策略判断是否换后端重试，连接失败和超时总是重试
+    pub fn is_retryable(&self, policy: &RetryPolicy) -> bool {
+        match self {
+            Self::Status(status) => policy.retries_status(status.as_u16()),
+            Self::RateLimited(_) => policy.retries_status(StatusCode::TOO_MANY_REQUESTS.as_u16()),
+            Self::Timeout(..) | Self::Request(_) | Self::InvalidJson(_) => true,
+        }
+    }
+}
+
+/// 从429响应头中解析重试等待时间：优先retry-after-ms和Retry-After（秒数或HTTP日期），
+/// 其次是OpenAI风格的x-ratelimit-reset-requests/x-ratelimit-reset-tokens（只取余量已耗尽的一项）
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+
+    let explicit = header("retry-after-ms")
+        .and_then(|ms| ms.parse::<f64>().ok())
+        .and_then(|ms| Duration::try_from_secs_f64(ms / 1000.0).ok())
+        .or_else(|| {
+            let value = header("retry-after")?;
+            match value.parse::<f64>() {
+                Ok(seconds) => Duration::try_from_secs_f64(seconds).ok(),
+                Err(_) => {
+                    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+                    (date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+                }
+            }
+        });
+    let retry_after = explicit.or_else(|| {
+        ["requests", "tokens"]
+            .into_iter()
+            .filter(|kind| header(&format!("x-ratelimit-remaining-{}", kind)).is_none_or(|r| r == "0"))
+            .filter_map(|kind| header(&format!("x-ratelimit-reset-{}", kind)).and_then(parse_reset_duration))
+            .max()
+    })?;
+    Some(retry_after.min(MAX_RETRY_AFTER))
+}
+
+/// 解析OpenAI风格的重置时间，如"20ms"、"1s"、"6m0s"、"1h30m"
+fn parse_reset_duration(value: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let number_len = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let number: f64 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+        let unit_len = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        total += number
+            * match &rest[..unit_len] {
+                "ms" => 0.001,
+                "s" => 1.0,
+                "m" => 60.0,
+                "h" => 3600.0,
+                _ => return None,
+            };
+        rest = &rest[unit_len..];
+    }
+    Duration::try_from_secs_f64(total).ok()
+}
+
+impl From<ClientError> for UpstreamError {
+    fn from(error: ClientError) -> Self {
+        match &error {
+            // 客户端只设置了连接超时和可选的总超时
+            ClientError::RequestError(e) if e.is_timeout() => {
+                let kind = if e.is_connect() { TimeoutKind::Connect } else { TimeoutKind::Total };
+                Self::Timeout(kind, error.to_string())
+            }
+            ClientError::UpstreamError { status, .. } => {
+                Self::Status(StatusCode::from_u16(*status).unwrap_or(StatusCode::BAD_GATEWAY))
+            }
+            _ => Self::Request(error.to_string()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for UpstreamError {
+    fn from(error: reqwest::Error) -> Self {
+        ClientError::from(error).into()
+    }
+}
+
+/// 返回给客户端的请求错误，每种错误对应固定的HTTP状态码和OpenAI兼容的错误类型、错误码
+#[derive(Debug, thiserror::Error)]
+pub enum RelayError {
+    #[error("{message}")]
+    InvalidRequest {
+        message: String,
+        details: Option<String>,
+    },
+    #[error("The provided API key is invalid")]
+    InvalidApiKey,
+    #[error("Access denied for model: {0}")]
+    ModelAccessDenied(String),
+    #[error("Model '{0}' not found")]
+    ModelNotFound(String),
+    #[error("No such file: {0}")]
+    FileNotFound(String),
+    #[error("No resumable stream for request: {0}")]
+    StreamNotFound(String),
+    #[error("Permission denied")]
+    PermissionDenied(String),
+    #[error("Request body too large")]
+    PayloadTooLarge(String),
+    #[error("Request exceeds the context length of model '{model}'")]
+    ContextLengthExceeded { model: String, details: String },
+    #[error("Request blocked by content policy")]
+    ContentPolicyViolation(String),
+    #[error("Content moderation unavailable")]
+    ModerationUnavailable(String),
+    #[error("Request exceeds the max_tokens limit")]
+    MaxTokensExceeded(String),
+    #[error("Request exceeded the maximum duration")]
+    DurationLimitExceeded(String),
+    #[error("Budget exceeded")]
+    BudgetExceeded(String),
+    #[error("File storage quota exceeded")]
+    StorageQuotaExceeded(String),
+    #[error("Rate limit exceeded")]
+    RateLimited(String),
+    #[error("Server overloaded")]
+    Overloaded(String),
+    #[error("Batch request preempted")]
+    BatchPreempted(String),
+    #[error("Service temporarily unavailable for model '{model}'")]
+    NoHealthyBackends { model: String, details: String },
+    #[error("Backends for model '{model}' are at capacity")]
+    BackendsAtCapacity { model: String, details: String },
+    #[error("Request timeout for model '{model}'")]
+    UpstreamTimeout { model: String, kind: TimeoutKind, details: String },
+    #[error("Upstream rate limit reached for model '{model}'")]
+    UpstreamRateLimited { model: String, details: String },
+    #[error("Upstream request failed for model '{model}'")]
+    UpstreamFailed { model: String, details: String },
+    #[error("Upstream file request failed")]
+    UpstreamFileFailed(String),
+    #[error("Configuration error for model '{model}'")]
+    Configuration { model: String, details: String },
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl From<AdmissionError> for RelayError {
+    fn from(error: AdmissionError) -> Self {
+        let details = format!("{}. Please retry later.", error);
+        match error {
+            AdmissionError::BatchPreempted(_) => Self::BatchPreempted(details),
+            AdmissionError::UserConcurrency(_) => Self::RateLimited(details),
+            _ => Self::Overloaded(details),
+        }
+    }
+}
+
+impl RelayError {
+    /// 按上游请求失败的原因区分超时、上游限流和其他上游错误
+    pub fn from_upstream(model: &str, error: &UpstreamError, details: String) -> Self {
+        let model = model.to_string();
+        match error {
+            UpstreamError::Timeout(kind, _) => Self::UpstreamTimeout { model, kind: *kind, details },
+            UpstreamError::RateLimited(_) | UpstreamError::Status(StatusCode::TOO_MANY_REQUESTS) => {
+                Self::UpstreamRateLimited { model, details }
+            }
+            UpstreamError::Status(StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT) => {
+                Self::UpstreamTimeout { model, kind: TimeoutKind::Total, details }
+            }
+            UpstreamError::Status(_) | UpstreamError::Request(_) | UpstreamError::InvalidJson(_) => {
+                Self::UpstreamFailed { model, details }
+            }
+        }
+    }
+
+    pub fn error_type(&self) -> ErrorType {
+        match self {
+            Self::InvalidRequest { .. }
+            | Self::ContextLengthExceeded { .. }
+            | Self::ContentPolicyViolation(_)
+            | Self::MaxTokensExceeded(_) => ErrorType::BadRequest,
+            Self::InvalidApiKey => ErrorType::Unauthorized,
+            Self::ModelAccessDenied(_) | Self::PermissionDenied(_) | Self::StorageQuotaExceeded(_) => {
+                ErrorType::Forbidden
+            }
+            Self::ModelNotFound(_) | Self::FileNotFound(_) | Self::StreamNotFound(_) => ErrorType::NotFound,
+            Self::PayloadTooLarge(_) => ErrorType::PayloadTooLarge,
+            Self::DurationLimitExceeded(_) => ErrorType::RequestTimeout,
+            Self::BudgetExceeded(_) => ErrorType::PaymentRequired,
+            Self::RateLimited(_) | Self::BatchPreempted(_) | Self::UpstreamRateLimited { .. } => {
+                ErrorType::TooManyRequests
+            }
+            Self::Overloaded(_)
+            | Self::ModerationUnavailable(_)
+            | Self::NoHealthyBackends { .. }
+            | Self::BackendsAtCapacity { .. } => {
+                ErrorType::ServiceUnavailable
+            }
+            Self::UpstreamTimeout { .. } => ErrorType::GatewayTimeout,
+            Self::UpstreamFailed { .. } | Self::UpstreamFileFailed(_) => ErrorType::BadGateway,
+            Self::Configuration { .. } | Self::Internal(_) => ErrorType::InternalServerError,
+        }
+    }
+
+    /// OpenAI兼容的错误码（error.code字段）
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest { .. } => "invalid_request",
+            Self::InvalidApiKey => "invalid_api_key",
+            Self::ModelAccessDenied(_) => "model_access_denied",
+            Self::ModelNotFound(_) => "model_not_found",
+            Self::FileNotFound(_) => "file_not_found",
+            Self::StreamNotFound(_) => "stream_not_found",
+            Self::PermissionDenied(_) => "permission_denied",
+            Self::PayloadTooLarge(_) => "request_too_large",
+            Self::ContextLengthExceeded { .. } => "context_length_exceeded",
+            Self::ContentPolicyViolation(_) => "content_policy_violation",
+            Self::ModerationUnavailable(_) => "moderation_unavailable",
+            Self::MaxTokensExceeded(_) => "max_tokens_exceeded",
+            Self::DurationLimitExceeded(_) => "max_duration_exceeded",
+            Self::BudgetExceeded(_) => "budget_exceeded",
+            Self::StorageQuotaExceeded(_) => "storage_quota_exceeded",
+            Self::RateLimited(_) => "rate_limit_exceeded",
+            Self::Overloaded(_) => "server_overloaded",
+            Self::BatchPreempted(_) => "batch_preempted",
+            Self::NoHealthyBackends { .. } => "no_healthy_backends",
+            Self::BackendsAtCapacity { .. } => "backends_at_capacity",
+            Self::UpstreamTimeout { kind, .. } => match kind {
+                TimeoutKind::Connect => "upstream_connect_timeout",
+                TimeoutKind::FirstToken => "upstream_first_token_timeout",
+                TimeoutKind::Idle => "upstream_idle_timeout",
+                TimeoutKind::Total => "upstream_timeout",
+            },
+            Self::UpstreamRateLimited { .. } => "upstream_rate_limited",
+            Self::UpstreamFailed { .. } | Self::UpstreamFileFailed(_) => "upstream_error",
+            Self::Configuration { .. } => "configuration_error",
+            Self::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn details(&self) -> Option<String> {
+        match self {
+            Self::InvalidRequest { details, .. } => details.clone(),
+            Self::InvalidApiKey
+            | Self::ModelAccessDenied(_)
+            | Self::ModelNotFound(_)
+            | Self::FileNotFound(_)
+            | Self::StreamNotFound(_)
+            | Self::Internal(_) => None,
+            Self::PermissionDenied(details)
+            | Self::PayloadTooLarge(details)
+            | Self::ContextLengthExceeded { details, .. }
+            | Self::ContentPolicyViolation(details)
+            | Self::ModerationUnavailable(details)
+            | Self::MaxTokensExceeded(details)
+            | Self::DurationLimitExceeded(details)
+            | Self::BudgetExceeded(details)
+            | Self::StorageQuotaExceeded(details)
+            | Self::RateLimited(details)
+            | Self::Overloaded(details)
+            | Self::BatchPreempted(details)
+            | Self::NoHealthyBackends { details, .. }
+            | Self::BackendsAtCapacity { details, .. }
+            | Self::UpstreamTimeout { details, .. }
+            | Self::UpstreamRateLimited { details, .. }
+            | Self::UpstreamFailed { details, .. }
+            | Self::UpstreamFileFailed(details)
+            | Self::Configuration { details, .. } => Some(details.clone()),
+        }
+    }
+
+    /// OpenAI兼容的错误响应体
+    pub fn to_json(&self) -> Value {
+        error_json(&self.error_type(), self.code(), &self.to_string(), self.details())
+    }
+}
+
+impl IntoResponse for RelayError {
+    fn into_response(self) -> Response {
+        (self.error_type().status_code(), Json(self.to_json())).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upstream_errors_map_to_distinct_statuses() {
+        let details = String::new();
+        let timeout = RelayError::from_upstream("gpt-4", &UpstreamError::Timeout(TimeoutKind::Total, "slow".into()), details.clone());
+        assert_eq!(timeout.error_type().status_code(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(timeout.code(), "upstream_timeout");
+        let first_token = UpstreamError::Timeout(TimeoutKind::FirstToken, "slow".into());
+        let first_token = RelayError::from_upstream("gpt-4", &first_token, details.clone());
+        assert_eq!(first_token.error_type().status_code(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(first_token.code(), "upstream_first_token_timeout");
+
+        let limited = RelayError::from_upstream(
+            "gpt-4",
+            &UpstreamError::Status(StatusCode::TOO_MANY_REQUESTS),
+            details.clone(),
+        );
+        assert_eq!(limited.error_type().status_code(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limited.code(), "upstream_rate_limited");
+
+        let failed = RelayError::from_upstream(
+            "gpt-4",
+            &UpstreamError::Status(StatusCode::INTERNAL_SERVER_ERROR),
+            details,
+        );
+        assert_eq!(failed.error_type().status_code(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[test]
+    fn test_retry_policy_filters_statuses_and_backs_off() {
+        let policy = RetryPolicy {
+            backoff_ms: 100,
+            max_backoff_ms: 300,
+            retry_on_status: vec![429, 503],
+            ..Default::default()
+        };
+        assert!(UpstreamError::Status(StatusCode::SERVICE_UNAVAILABLE).is_retryable(&policy));
+        assert!(UpstreamError::RateLimited(None).is_retryable(&policy));
+        assert!(!UpstreamError::Status(StatusCode::BAD_REQUEST).is_retryable(&policy));
+        assert!(UpstreamError::Timeout(TimeoutKind::Connect, "slow".into()).is_retryable(&policy));
+        assert!(UpstreamError::Status(StatusCode::BAD_REQUEST).is_retryable(&RetryPolicy::default()));
+
+        let backoffs: Vec<_> = (1..=4).map(|retry| policy.backoff(retry).as_millis()).collect();
+        assert_eq!(backoffs, vec![100, 200, 300, 300]);
+        assert_eq!(RetryPolicy::default().backoff(1), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_retry_after_headers() {
+        let headers = |pairs: &[(&'static str, &str)]| {
+            let mut map = HeaderMap::new();
+            for (name, value) in pairs {
+                map.insert(*name, value.parse().unwrap());
+            }
+            map
+        };
+
+        assert_eq!(retry_after(&headers(&[("retry-after", "5")])), Some(Duration::from_secs(5)));
+        assert_eq!(
+            retry_after(&headers(&[("retry-after-ms", "250"), ("retry-after", "5")])),
+            Some(Duration::from_millis(250))
+        );
+        let date = (chrono::Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+        let parsed = retry_after(&headers(&[("retry-after", &date)])).unwrap();
+        assert!(parsed > Duration::from_secs(25) && parsed <= Duration::from_secs(30));
+        // 只有余量耗尽的限额决定冷却时间
+        assert_eq!(
+            retry_after(&headers(&[
+                ("x-ratelimit-remaining-requests", "10"),
+                ("x-ratelimit-reset-requests", "1s"),
+                ("x-ratelimit-remaining-tokens", "0"),
+                ("x-ratelimit-reset-tokens", "1m30.5s"),
+            ])),
+            Some(Duration::from_millis(90_500))
+        );
+        assert_eq!(retry_after(&headers(&[("retry-after", "86400")])), Some(MAX_RETRY_AFTER));
+        assert_eq!(retry_after(&headers(&[])), None);
+        assert_eq!(retry_after(&headers(&[("retry-after", "soon")])), None);
+    }
+
+    #[test]
+    fn test_error_body_is_openai_compatible() {
+        let body = RelayError::ModelNotFound("gpt-5".to_string()).to_json();
+        assert_eq!(body["error"]["message"], "Model 'gpt-5' not found");
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert_eq!(body["error"]["code"], "model_not_found");
+        assert!(body["error"]["param"].is_null());
+        assert_eq!(body["error"]["status"], 404);
+
+        let body = RelayError::from(AdmissionError::GlobalLimit(8)).to_json();
+        assert_eq!(body["error"]["type"], "server_error");
+        assert!(body["error"]["details"].as_str().unwrap().contains("at capacity"));
+    }
+}
@@ -0,0 +1,351 @@
+use crate::config::model::FairQueueSettings;
+use crate::relay::admission::AdmissionError;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// 排队中的请求，名额空出时通过tx直接交给它
+struct Waiter {
+    id: u64,
+    cost: u64,
+    tx: oneshot::Sender<()>,
+}
+
+/// 单个用户的等待队列和差额计数
+#[derive(Default)]
+struct UserQueue {
+    waiters: VecDeque<Waiter>,
+    deficit: u64,
+    /// 本轮是否已经发放过额度
+    in_turn: bool,
+}
+
+/// 单个模型的名额和等待队列
+#[derive(Default)]
+struct ModelQueue {
+    in_flight: usize,
+    limit: usize,
+    quantum: u64,
+    /// 有请求等待的用户，按轮询顺序排列
+    active: VecDeque<String>,
+    users: HashMap<String, UserQueue>,
+    queued: usize,
+}
+
+impl ModelQueue {
+    fn push(&mut self, user: &str, waiter: Waiter) {
+        let queue = self.users.entry(user.to_string()).or_default();
+        if queue.waiters.is_empty() {
+            self.active.push_back(user.to_string());
+        }
+        queue.waiters.push_back(waiter);
+        self.queued += 1;
+    }
+
+    /// 按差额轮询取出下一个请求：轮到的用户获得一份额度，额度够支付队首请求时出队，
+    /// 不够时累计额度并让给下一个用户；用户的队列清空后额度清零
+    fn pop(&mut self) -> Option<Waiter> {
+        while let Some(user) = self.active.front().cloned() {
+            let quantum = self.quantum.max(1);
+            let queue = self.users.get_mut(&user)?;
+            let Some(cost) = queue.waiters.front().map(|waiter| waiter.cost) else {
+                self.active.pop_front();
+                self.users.remove(&user);
+                continue;
+            };
+            if !queue.in_turn {
+                queue.deficit += quantum;
+                queue.in_turn = true;
+            }
+            if queue.deficit < cost {
+                queue.in_turn = false;
+                self.active.rotate_left(1);
+                continue;
+            }
+            queue.deficit -= cost;
+            let waiter = queue.waiters.pop_front();
+            self.queued -= 1;
+            if queue.waiters.is_empty() {
+                self.active.pop_front();
+                self.users.remove(&user);
+            }
+            return waiter;
+        }
+        None
+    }
+
+    /// 移除仍在排队的请求，已经出队（拿到名额）时返回false
+    fn remove(&mut self, user: &str, id: u64) -> bool {
+        let Some(queue) = self.users.get_mut(user) else {
+            return false;
+        };
+        let Some(index) = queue.waiters.iter().position(|waiter| waiter.id == id) else {
+            return false;
+        };
+        queue.waiters.remove(index);
+        self.queued -= 1;
+        if queue.waiters.is_empty() {
+            self.users.remove(user);
+            self.active.retain(|active| active != user);
+        }
+        true
+    }
+
+    /// 归还一个名额：有请求等待时直接交给下一个请求，否则减少在途计数
+    fn release(&mut self) {
+        while self.in_flight <= self.limit
+            && let Some(waiter) = self.pop()
+        {
+            if waiter.tx.send(()).is_ok() {
+                return;
+            }
+        }
+        self.in_flight = self.in_flight.saturating_sub(1);
+    }
+
+    /// 上限调高后把空出的名额分给排队的请求
+    fn fill(&mut self) {
+        while self.in_flight < self.limit
+            && let Some(waiter) = self.pop()
+        {
+            if waiter.tx.send(()).is_ok() {
+                self.in_flight += 1;
+            }
+        }
+    }
+}
+
+/// 模型排队状态
+#[derive(Debug, Clone, Serialize)]
+pub struct FairQueueStats {
+    pub in_flight: usize,
+    pub queued: usize,
+    pub waiting_users: usize,
+}
+
+/// 模型在途请求达到上限时的公平排队，避免单个高并发用户占满名额让其他用户一直等待
+pub struct FairScheduler {
+    models: Arc<Mutex<HashMap<String, ModelQueue>>>,
+    next_id: AtomicU64,
+}
+
+/// 公平排队分配的模型名额，析构时交给下一个等待的请求
+pub struct FairPermit {
+    models: Arc<Mutex<HashMap<String, ModelQueue>>>,
+    model: String,
+}
+
+impl std::fmt::Debug for FairPermit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FairPermit").field("model", &self.model).finish()
+    }
+}
+
+impl Drop for FairPermit {
+    fn drop(&mut self) {
+        let mut models = self.models.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(queue) = models.get_mut(&self.model) {
+            queue.release();
+        }
+    }
+}
+
+/// 排队中的请求被取消（客户端断开）时移出队列，已经分到的名额继续交给下一个请求
+struct PendingGuard<'a> {
+    models: &'a Arc<Mutex<HashMap<String, ModelQueue>>>,
+    model: &'a str,
+    user: &'a str,
+    id: u64,
+    rx: oneshot::Receiver<()>,
+    done: bool,
+}
+
+impl PendingGuard<'_> {
+    /// 结束排队，返回是否拿到了名额
+    fn finish(&mut self) -> bool {
+        self.done = true;
+        let mut models = self.models.lock().unwrap_or_else(|e| e.into_inner());
+        let removed = models
+            .get_mut(self.model)
+            .is_some_and(|queue| queue.remove(self.user, self.id));
+        !removed && self.rx.try_recv().is_ok()
+    }
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        if !self.done && self.finish() {
+            let mut models = self.models.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(queue) = models.get_mut(self.model) {
+                queue.release();
+            }
+        }
+    }
+}
+
+impl FairScheduler {
+    pub fn new() -> Self {
+        Self {
+            models: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// 获取模型的一个名额，名额已满时按用户公平排队；cost为请求的估算token数
+    pub async fn acquire(
+        &self,
+        model: &str,
+        limit: usize,
+        user: &str,
+        cost: u64,
+        settings: &FairQueueSettings,
+    ) -> Result<FairPermit, AdmissionError> {
+        let permit = || FairPermit {
+            models: self.models.clone(),
+            model: model.to_string(),
+        };
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let rx = {
+            let mut models = self.models.lock().unwrap_or_else(|e| e.into_inner());
+            let queue = models.entry(model.to_string()).or_default();
+            queue.limit = limit;
+            queue.quantum = settings.quantum_tokens;
+            queue.fill();
+            // 有请求排队时新请求也要排队，不能越过队列直接占用名额
+            if queue.queued == 0 && queue.in_flight < limit {
+                queue.in_flight += 1;
+                return Ok(permit());
+            }
+            if queue.queued >= settings.max_queue {
+                return Err(AdmissionError::ModelLimit(model.to_string(), limit));
+            }
+            let (tx, rx) = oneshot::channel();
+            queue.push(user, Waiter { id, cost: cost.max(1), tx });
+            rx
+        };
+
+        let mut pending = PendingGuard {
+            models: &self.models,
+            model,
+            user,
+            id,
+            rx,
+            done: false,
+        };
+        let timeout = Duration::from_millis(settings.queue_timeout_ms);
+        let granted = tokio::time::timeout(timeout, &mut pending.rx).await.is_ok_and(|r| r.is_ok());
+        // 超时与分配名额同时发生时以分配为准
+        if granted || pending.finish() {
+            pending.done = true;
+            return Ok(permit());
+        }
+        tracing::debug!("Request from '{}' timed out in the fair queue of model '{}'", user, model);
+        Err(AdmissionError::QueueTimeout(model.to_string(), settings.queue_timeout_ms))
+    }
+
+    /// 各模型当前的在途和排队数
+    pub fn stats(&self) -> HashMap<String, FairQueueStats> {
+        let models = self.models.lock().unwrap_or_else(|e| e.into_inner());
+        models
+            .iter()
+            .map(|(model, queue)| {
+                (
+                    model.clone(),
+                    FairQueueStats {
+                        in_flight: queue.in_flight,
+                        queued: queue.queued,
+                        waiting_users: queue.active.len(),
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+impl Default for FairScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(quantum_tokens: u64) -> FairQueueSettings {
+        FairQueueSettings {
+            max_queue: 100,
+            queue_timeout_ms: 1000,
+            quantum_tokens,
+        }
+    }
+
+    /// 名额占满后依次排入请求，再逐个释放名额，返回拿到名额的用户顺序
+    async fn grant_order(quantum: u64, requests: &[(&'static str, u64)]) -> Vec<&'static str> {
+        let scheduler = Arc::new(FairScheduler::new());
+        let settings = settings(quantum);
+        let blocker = scheduler.acquire("gpt-4", 1, "blocker", 1, &settings).await.unwrap();
+
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        for (queued, (user, cost)) in requests.iter().copied().enumerate() {
+            let task_scheduler = scheduler.clone();
+            let settings = settings.clone();
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let _permit = task_scheduler.acquire("gpt-4", 1, user, cost, &settings).await.unwrap();
+                order_tx.send(user).unwrap();
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            });
+            // 等上一个请求入队后再发下一个，保证入队顺序
+            while scheduler.stats()["gpt-4"].queued <= queued {
+                tokio::task::yield_now().await;
+            }
+        }
+        drop(blocker);
+
+        let mut order = Vec::new();
+        for _ in requests {
+            order.push(order_rx.recv().await.unwrap());
+        }
+        order
+    }
+
+    #[tokio::test]
+    async fn test_users_take_turns_instead_of_fifo() {
+        let order = grant_order(10, &[("heavy", 10), ("heavy", 10), ("heavy", 10), ("light", 10), ("other", 10)]).await;
+        assert_eq!(order, vec!["heavy", "light", "other", "heavy", "heavy"]);
+    }
+
+    #[tokio::test]
+    async fn test_expensive_requests_wait_for_accumulated_deficit() {
+        // 大请求需要累计三轮额度，期间小请求的用户可以先执行
+        let order = grant_order(10, &[("big", 30), ("small", 10), ("small", 10), ("small", 10)]).await;
+        assert_eq!(order, vec!["small", "small", "big", "small"]);
+    }
+
+    #[tokio::test]
+    async fn test_queue_full_and_timeout() {
+        let scheduler = FairScheduler::new();
+        let settings = FairQueueSettings {
+            max_queue: 0,
+            queue_timeout_ms: 50,
+            quantum_tokens: 10,
+        };
+        let _permit = scheduler.acquire("gpt-4", 1, "a", 1, &settings).await.unwrap();
+        assert!(matches!(
+            scheduler.acquire("gpt-4", 1, "b", 1, &settings).await,
+            Err(AdmissionError::ModelLimit(_, 1))
+        ));
+
+        let settings = FairQueueSettings { max_queue: 1, ..settings };
+        assert!(matches!(
+            scheduler.acquire("gpt-4", 1, "b", 1, &settings).await,
+            Err(AdmissionError::QueueTimeout(_, 50))
+        ));
+        let stats = &scheduler.stats()["gpt-4"];
+        assert_eq!((stats.in_flight, stats.queued), (1, 0));
+    }
+}
@@ -0,0 +1,318 @@
+use futures::StreamExt;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// 对一个后端注入的故障，用于在预发环境验证故障转移、恢复和熔断，不影响真实的provider
+/// 只有配置了settings.fault_injection时才能设置并生效
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaultSpec {
+    /// 发送上游请求前额外等待的时间（毫秒），计入首个token超时
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// 不发送上游请求、直接返回错误状态码的请求比例（百分比，0-100）
+    #[serde(default)]
+    pub error_percent: f64,
+    /// 注入错误时返回的状态码，如503、429（触发上游限流冷却）
+    #[serde(default = "default_error_status")]
+    pub error_status: u16,
+    /// 流式响应在中途断开的比例（百分比，0-100）
+    #[serde(default)]
+    pub drop_stream_percent: f64,
+    /// 断开前转发的数据块数
+    #[serde(default = "default_drop_after_chunks")]
+    pub drop_after_chunks: usize,
+}
+
+fn default_error_status() -> u16 {
+    503
+}
+
+fn default_drop_after_chunks() -> usize {
+    1
+}
+
+impl FaultSpec {
+    /// 校验故障参数，至少要注入一种故障
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, percent) in [("error_percent", self.error_percent), ("drop_stream_percent", self.drop_stream_percent)] {
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(format!("{} must be within [0, 100]", name));
+            }
+        }
+        if !(400..=599).contains(&self.error_status) {
+            return Err("error_status must be an HTTP error status (400-599)".to_string());
+        }
+        if self.latency_ms == 0 && self.error_percent == 0.0 && self.drop_stream_percent == 0.0 {
+            return Err("at least one of latency_ms, error_percent and drop_stream_percent must be set".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 已注入各类故障的请求数
+#[derive(Debug, Default)]
+struct FaultCounters {
+    delayed: AtomicU64,
+    errors: AtomicU64,
+    dropped_streams: AtomicU64,
+}
+
+struct ActiveFault {
+    spec: FaultSpec,
+    expires_at: Option<(Instant, chrono::DateTime<chrono::Utc>)>,
+    counters: Arc<FaultCounters>,
+}
+
+/// 后端的故障注入状态
+#[derive(Debug, Clone, Serialize)]
+pub struct FaultStatus {
+    /// provider:model
+    pub backend: String,
+    #[serde(flatten)]
+    pub spec: FaultSpec,
+    /// 到期时间（RFC3339），为空时直到清除前一直生效
+    pub expires_at: Option<String>,
+    pub delayed: u64,
+    pub errors: u64,
+    pub dropped_streams: u64,
+}
+
+/// 按后端（provider:model）保存注入的故障，由管理接口修改，上游客户端发送请求时读取
+#[derive(Default)]
+pub struct FaultInjector {
+    faults: std::sync::RwLock<HashMap<String, ActiveFault>>,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置后端的故障，替换已有的故障；指定时长时到期后自动失效
+    pub fn set(&self, backend: &str, spec: FaultSpec, duration: Option<Duration>) -> FaultStatus {
+        let expires_at = duration.map(|d| {
+            (
+                Instant::now() + d,
+                chrono::Utc::now() + chrono::Duration::from_std(d).unwrap_or(chrono::Duration::MAX),
+            )
+        });
+        let fault = ActiveFault {
+            spec,
+            expires_at,
+            counters: Arc::default(),
+        };
+        let status = Self::status(backend, &fault);
+        if let Ok(mut faults) = self.faults.write() {
+            faults.insert(backend.to_string(), fault);
+        }
+        status
+    }
+
+    /// 清除后端的故障，backend为空时清除所有故障，返回清除的数量
+    pub fn clear(&self, backend: Option<&str>) -> usize {
+        let Ok(mut faults) = self.faults.write() else {
+            return 0;
+        };
+        match backend {
+            Some(backend) => faults.remove(backend).map_or(0, |_| 1),
+            None => {
+                let count = faults.len();
+                faults.clear();
+                count
+            }
+        }
+    }
+
+    /// 生效中的故障，按后端排序
+    pub fn list(&self) -> Vec<FaultStatus> {
+        let Ok(faults) = self.faults.read() else {
+            return Vec::new();
+        };
+        let now = Instant::now();
+        let mut list: Vec<FaultStatus> = faults
+            .iter()
+            .filter(|(_, fault)| fault.expires_at.is_none_or(|(at, _)| at > now))
+            .map(|(backend, fault)| Self::status(backend, fault))
+            .collect();
+        list.sort_by(|a, b| a.backend.cmp(&b.backend));
+        list
+    }
+
+    fn status(backend: &str, fault: &ActiveFault) -> FaultStatus {
+        FaultStatus {
+            backend: backend.to_string(),
+            spec: fault.spec.clone(),
+            expires_at: fault.expires_at.map(|(_, at)| at.to_rfc3339()),
+            delayed: fault.counters.delayed.load(Ordering::Relaxed),
+            errors: fault.counters.errors.load(Ordering::Relaxed),
+            dropped_streams: fault.counters.dropped_streams.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 发往该后端的请求要注入的故障，已到期的故障被移除
+    fn fault_for(&self, backend: &str) -> Option<Fault> {
+        let now = Instant::now();
+        {
+            let faults = self.faults.read().ok()?;
+            let fault = faults.get(backend)?;
+            if fault.expires_at.is_none_or(|(at, _)| at > now) {
+                return Some(Fault {
+                    spec: fault.spec.clone(),
+                    counters: fault.counters.clone(),
+                });
+            }
+        }
+        if let Ok(mut faults) = self.faults.write() {
+            faults.retain(|_, fault| fault.expires_at.is_none_or(|(at, _)| at > now));
+        }
+        None
+    }
+}
+
+/// 上游客户端持有的故障注入入口，只在启用故障注入时附加到客户端
+#[derive(Clone)]
+pub struct FaultHook {
+    provider: String,
+    injector: Arc<FaultInjector>,
+}
+
+impl FaultHook {
+    pub fn new(provider: &str, injector: Arc<FaultInjector>) -> Self {
+        Self {
+            provider: provider.to_string(),
+            injector,
+        }
+    }
+
+    /// 请求该provider下某个模型时要注入的故障
+    pub fn fault_for(&self, model: Option<&str>) -> Option<Fault> {
+        self.injector.fault_for(&format!("{}:{}", self.provider, model?))
+    }
+}
+
+/// 一次请求要注入的故障
+pub struct Fault {
+    spec: FaultSpec,
+    counters: Arc<FaultCounters>,
+}
+
+impl Fault {
+    /// 发送请求前注入延迟，命中错误比例时返回代替上游响应的错误响应
+    pub async fn before_request(&self) -> Option<reqwest::Response> {
+        if self.spec.latency_ms > 0 {
+            self.counters.delayed.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(Duration::from_millis(self.spec.latency_ms)).await;
+        }
+        if !roll(self.spec.error_percent) {
+            return None;
+        }
+        self.counters.errors.fetch_add(1, Ordering::Relaxed);
+        let body = serde_json::json!({
+            "error": {"message": "Injected fault", "type": "fault_injection", "code": self.spec.error_status}
+        });
+        let response = axum::http::Response::builder()
+            .status(self.spec.error_status)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .ok()?;
+        Some(reqwest::Response::from(response))
+    }
+
+    /// 命中断流比例时，成功的流式响应在转发drop_after_chunks个数据块后以错误结束
+    pub fn after_response(&self, response: reqwest::Response) -> reqwest::Response {
+        let is_stream = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        if !response.status().is_success() || !is_stream || !roll(self.spec.drop_stream_percent) {
+            return response;
+        }
+        self.counters.dropped_streams.fetch_add(1, Ordering::Relaxed);
+
+        let mut builder = axum::http::Response::builder()
+            .status(response.status())
+            .version(response.version());
+        if let Some(headers) = builder.headers_mut() {
+            *headers = response.headers().clone();
+        }
+        let dropped = futures::stream::once(async {
+            Err::<bytes::Bytes, Box<dyn std::error::Error + Send + Sync>>("stream dropped by fault injection".into())
+        });
+        let stream = response
+            .bytes_stream()
+            .map(|chunk| chunk.map_err(Into::into))
+            .take(self.spec.drop_after_chunks)
+            .chain(dropped);
+        match builder.body(reqwest::Body::wrap_stream(stream)) {
+            Ok(response) => reqwest::Response::from(response),
+            Err(e) => {
+                tracing::warn!("Failed to build dropped stream response: {}", e);
+                reqwest::Response::from(
+                    axum::http::Response::builder()
+                        .status(reqwest::StatusCode::BAD_GATEWAY)
+                        .body(String::new())
+                        .unwrap_or_default(),
+                )
+            }
+        }
+    }
+}
+
+/// 按百分比判定是否命中
+fn roll(percent: f64) -> bool {
+    percent > 0.0 && rand::rng().random::<f64>() * 100.0 < percent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> FaultSpec {
+        FaultSpec {
+            latency_ms: 0,
+            error_percent: 100.0,
+            error_status: 429,
+            drop_stream_percent: 0.0,
+            drop_after_chunks: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_injects_errors_until_cleared() {
+        let injector = Arc::new(FaultInjector::new());
+        let hook = FaultHook::new("openai", injector.clone());
+        assert!(hook.fault_for(Some("gpt-4")).is_none());
+
+        injector.set("openai:gpt-4", spec(), None);
+        assert!(hook.fault_for(Some("gpt-3.5")).is_none());
+        let response = hook.fault_for(Some("gpt-4")).unwrap().before_request().await.unwrap();
+        assert_eq!(response.status(), 429);
+        assert_eq!(injector.list()[0].errors, 1);
+
+        assert_eq!(injector.clear(None), 1);
+        assert!(hook.fault_for(Some("gpt-4")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fault_expires() {
+        let injector = Arc::new(FaultInjector::new());
+        injector.set("openai:gpt-4", spec(), Some(Duration::from_millis(20)));
+        assert!(injector.list()[0].expires_at.is_some());
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(injector.list().is_empty());
+        assert!(FaultHook::new("openai", injector).fault_for(Some("gpt-4")).is_none());
+    }
+
+    #[test]
+    fn test_spec_validation() {
+        assert!(spec().validate().is_ok());
+        assert!(FaultSpec { error_status: 200, ..spec() }.validate().is_err());
+        assert!(FaultSpec { error_percent: 150.0, ..spec() }.validate().is_err());
+        assert!(FaultSpec { error_percent: 0.0, ..spec() }.validate().is_err());
+    }
+}
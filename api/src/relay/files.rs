@@ -0,0 +1,286 @@
+use crate::config::model::Provider;
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+
+/// berry文件ID的前缀，客户端只能看到berry分配的ID，不会接触到provider的文件ID
+pub const FILE_ID_PREFIX: &str = "file-berry-";
+
+/// 上传到provider的文件及其所属用户，时间为Unix秒
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileRecord {
+    pub id: String,
+    pub owner: String,
+    pub provider: String,
+    pub provider_file_id: String,
+    pub filename: String,
+    pub purpose: String,
+    pub bytes: u64,
+    pub created_at: i64,
+    /// provider最近一次返回的文件对象，保留status等provider特有的字段
+    pub object: Value,
+}
+
+impl FileRecord {
+    /// 返回给客户端的OpenAI文件对象，ID替换为berry的文件ID
+    pub fn to_openai(&self) -> Value {
+        let mut object = match &self.object {
+            Value::Object(_) => self.object.clone(),
+            _ => json!({}),
+        };
+        object["id"] = json!(self.id);
+        object["object"] = json!("file");
+        object["bytes"] = json!(self.bytes);
+        object["created_at"] = json!(self.created_at);
+        object["filename"] = json!(self.filename);
+        object["purpose"] = json!(self.purpose);
+        object
+    }
+}
+
+const FILE_COLUMNS: &str = "id, owner, provider, provider_file_id, filename, purpose, bytes, created_at, object";
+
+/// 生成新的berry文件ID
+pub fn new_file_id() -> String {
+    format!("{}{:032x}", FILE_ID_PREFIX, rand::random::<u128>())
+}
+
+/// 基于SQLite的文件ID映射
+pub struct FileStore {
+    conn: Mutex<Connection>,
+}
+
+impl FileStore {
+    /// 打开（或创建）数据库文件
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path).with_context(|| format!("Failed to open file database {}", path))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::init(conn)
+    }
+
+    /// 内存数据库，用于测试
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS files (
+                id TEXT PRIMARY KEY,
+                owner TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                provider_file_id TEXT NOT NULL,
+                filename TEXT NOT NULL,
+                purpose TEXT NOT NULL,
+                bytes INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                object TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS files_owner ON files (owner, created_at);",
+        )?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| anyhow::anyhow!("file store lock poisoned"))
+    }
+
+    pub fn insert(&self, record: &FileRecord) -> Result<()> {
+        self.conn()?.execute(
+            &format!("INSERT INTO files ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)", FILE_COLUMNS),
+            params![
+                record.id,
+                record.owner,
+                record.provider,
+                record.provider_file_id,
+                record.filename,
+                record.purpose,
+                record.bytes,
+                record.created_at,
+                record.object.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 查询属于owner的文件，文件不存在或属于其他用户时返回None
+    pub fn get(&self, owner: &str, id: &str) -> Result<Option<FileRecord>> {
+        let conn = self.conn()?;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {} FROM files WHERE id = ?1 AND owner = ?2", FILE_COLUMNS),
+                params![id, owner],
+                file_from_row,
+            )
+            .optional()?)
+    }
+
+    /// 用户的文件，按创建时间从新到旧排列，可按用途过滤
+    pub fn list(&self, owner: &str, purpose: Option<&str>, limit: usize) -> Result<Vec<FileRecord>> {
+        let conn = self.conn()?;
+        let mut statement = conn.prepare(&format!(
+            "SELECT {} FROM files WHERE owner = ?1 AND (?2 IS NULL OR purpose = ?2)
+             ORDER BY created_at DESC, rowid DESC LIMIT ?3",
+            FILE_COLUMNS
+        ))?;
+        let rows = statement.query_map(params![owner, purpose, limit as i64], file_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// 保存provider返回的最新文件对象
+    pub fn update_object(&self, id: &str, object: &Value) -> Result<()> {
+        self.conn()?
+            .execute("UPDATE files SET object = ?2 WHERE id = ?1", params![id, object.to_string()])?;
+        Ok(())
+    }
+
+    pub fn delete(&self, id: &str) -> Result<bool> {
+        Ok(self.conn()?.execute("DELETE FROM files WHERE id = ?1", params![id])? > 0)
+    }
+
+    /// 用户已使用的存储空间（字节）
+    pub fn usage(&self, owner: &str) -> Result<u64> {
+        let conn = self.conn()?;
+        Ok(conn.query_row(
+            "SELECT COALESCE(SUM(bytes), 0) FROM files WHERE owner = ?1",
+            params![owner],
+            |row| row.get(0),
+        )?)
+    }
+}
+
+fn file_from_row(row: &Row<'_>) -> rusqlite::Result<FileRecord> {
+    let object: String = row.get(8)?;
+    Ok(FileRecord {
+        id: row.get(0)?,
+        owner: row.get(1)?,
+        provider: row.get(2)?,
+        provider_file_id: row.get(3)?,
+        filename: row.get(4)?,
+        purpose: row.get(5)?,
+        bytes: row.get(6)?,
+        created_at: row.get(7)?,
+        object: serde_json::from_str(&object).unwrap_or(Value::Null),
+    })
+}
+
+/// 按配置的数据库路径打开文件存储，路径变化（配置热加载）后重新打开
+pub struct FileRegistry {
+    opened: Mutex<Option<(String, Arc<FileStore>)>>,
+}
+
+impl FileRegistry {
+    pub fn new() -> Self {
+        Self { opened: Mutex::new(None) }
+    }
+
+    fn store(&self, path: &str) -> Result<Arc<FileStore>> {
+        let mut opened = self.opened.lock().map_err(|_| anyhow::anyhow!("file registry lock poisoned"))?;
+        match opened.as_ref() {
+            Some((current, store)) if current == path => Ok(store.clone()),
+            _ => {
+                let store = Arc::new(FileStore::open(path)?);
+                *opened = Some((path.to_string(), store.clone()));
+                Ok(store)
+            }
+        }
+    }
+
+    /// 在阻塞线程池中执行数据库操作
+    pub async fn run<T: Send + 'static>(
+        &self,
+        path: &str,
+        operation: impl FnOnce(&FileStore) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let store = self.store(path)?;
+        tokio::task::spawn_blocking(move || operation(&store)).await?
+    }
+
+    /// 把berry文件ID解析为所在provider和provider的文件ID，供需要引用已上传文件的接口使用
+    pub async fn resolve(&self, path: &str, owner: &str, id: &str) -> Result<Option<(String, String)>> {
+        let (owner, id) = (owner.to_string(), id.to_string());
+        let record = self.run(path, move |store| store.get(&owner, &id)).await?;
+        Ok(record.map(|record| (record.provider, record.provider_file_id)))
+    }
+}
+
+impl Default for FileRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 访问provider文件接口的请求头：provider密钥和自定义请求头
+pub fn upstream_headers(provider: &Provider) -> Result<reqwest::header::HeaderMap> {
+    if provider.api_key.is_empty() {
+        anyhow::bail!("API key is empty for provider: {}", provider.name);
+    }
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        reqwest::header::AUTHORIZATION,
+        format!("Bearer {}", provider.api_key).parse()?,
+    );
+    for (key, value) in &provider.headers {
+        if let (Ok(name), Ok(value)) = (
+            key.parse::<reqwest::header::HeaderName>(),
+            value.parse::<reqwest::header::HeaderValue>(),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+    crate::telemetry::inject_request_id(&mut headers);
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, owner: &str, purpose: &str, bytes: u64, created_at: i64) -> FileRecord {
+        FileRecord {
+            id: id.to_string(),
+            owner: owner.to_string(),
+            provider: "openai".to_string(),
+            provider_file_id: format!("file-up-{}", id),
+            filename: "data.jsonl".to_string(),
+            purpose: purpose.to_string(),
+            bytes,
+            created_at,
+            object: json!({ "id": format!("file-up-{}", id), "status": "processed" }),
+        }
+    }
+
+    #[test]
+    fn test_files_are_scoped_to_owner() {
+        let store = FileStore::open_in_memory().unwrap();
+        store.insert(&record("a", "alice", "batch", 100, 1)).unwrap();
+        store.insert(&record("b", "alice", "assistants", 50, 2)).unwrap();
+        store.insert(&record("c", "bob", "batch", 10, 3)).unwrap();
+
+        assert_eq!(store.usage("alice").unwrap(), 150);
+        assert_eq!(store.usage("carol").unwrap(), 0);
+        assert!(store.get("bob", "a").unwrap().is_none());
+        assert_eq!(store.get("alice", "a").unwrap().unwrap().provider_file_id, "file-up-a");
+
+        let ids = |records: Vec<FileRecord>| records.into_iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids(store.list("alice", None, 10).unwrap()), ["b", "a"]);
+        assert_eq!(ids(store.list("alice", Some("batch"), 10).unwrap()), ["a"]);
+        assert_eq!(ids(store.list("alice", None, 1).unwrap()), ["b"]);
+
+        assert!(store.delete("a").unwrap());
+        assert!(!store.delete("a").unwrap());
+        assert_eq!(store.usage("alice").unwrap(), 50);
+    }
+
+    #[test]
+    fn test_openai_object_uses_berry_id() {
+        let object = record("a", "alice", "batch", 100, 1).to_openai();
+        assert_eq!(object["id"], "a");
+        assert_eq!(object["object"], "file");
+        assert_eq!(object["bytes"], 100);
+        assert_eq!(object["status"], "processed");
+        assert!(new_file_id().starts_with(FILE_ID_PREFIX));
+    }
+}
@@ -0,0 +1,349 @@
+use crate::config::model::{BuiltinFilter, ContentFilterSet};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 内容过滤器，可以按需实现其他检测方式（如外部PII检测服务）
+pub trait ContentFilter: Send + Sync {
+    /// 返回过滤后的文本，没有命中时返回None
+    fn apply(&self, text: &str) -> Option<String>;
+}
+
+/// 按正则表达式替换命中的内容
+pub struct RegexFilter {
+    regex: Regex,
+    replacement: String,
+}
+
+impl RegexFilter {
+    pub fn new(pattern: &str, replacement: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            regex: Regex::new(pattern)?,
+            replacement: replacement.to_string(),
+        })
+    }
+
+    /// 不区分大小写地匹配任一关键词
+    pub fn keywords(keywords: &[String], replacement: &str) -> Result<Self, regex::Error> {
+        let alternatives: Vec<String> = keywords.iter().map(|k| regex::escape(k)).collect();
+        Self::new(&format!("(?i)(?:{})", alternatives.join("|")), replacement)
+    }
+}
+
+impl ContentFilter for RegexFilter {
+    fn apply(&self, text: &str) -> Option<String> {
+        if !self.regex.is_match(text) {
+            return None;
+        }
+        Some(self.regex.replace_all(text, regex::NoExpand(&self.replacement)).into_owned())
+    }
+}
+
+/// 内置规则的正则表达式，信用卡号放在电话号码之前，避免被部分匹配为电话号码
+fn builtin_pattern(filter: BuiltinFilter) -> &'static str {
+    match filter {
+        BuiltinFilter::Email => r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+        BuiltinFilter::CreditCard => r"\b\d{4}[ -]?\d{4}[ -]?\d{4}[ -]?\d{1,4}\b",
+        BuiltinFilter::Phone => r"(?:\+\d{1,3}[ -]?)?\b(?:\(\d{2,4}\)[ -]?)?\d{3,4}[ -]?\d{3,4}[ -]?\d{4}\b",
+        BuiltinFilter::Ipv4 => r"\b(?:\d{1,3}\.){3}\d{1,3}\b",
+        BuiltinFilter::ApiKey => r"\b(?:sk-[A-Za-z0-9_-]{16,}|AKIA[0-9A-Z]{16}|gh[pousr]_[A-Za-z0-9]{36})\b",
+    }
+}
+
+/// 按顺序应用的过滤器链
+#[derive(Clone, Default)]
+pub struct FilterChain {
+    filters: Vec<Arc<dyn ContentFilter>>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按规则集构建过滤器链：内置规则、自定义正则、关键词
+    pub fn from_set(set: &ContentFilterSet) -> Result<Self, regex::Error> {
+        let mut builtin = set.builtin.clone();
+        builtin.sort_by_key(|filter| *filter != BuiltinFilter::CreditCard);
+        let mut chain = Self::new();
+        for filter in builtin {
+            chain.push(Arc::new(RegexFilter::new(builtin_pattern(filter), &set.replacement)?));
+        }
+        for pattern in &set.patterns {
+            chain.push(Arc::new(RegexFilter::new(pattern, &set.replacement)?));
+        }
+        if !set.keywords.is_empty() {
+            chain.push(Arc::new(RegexFilter::keywords(&set.keywords, &set.replacement)?));
+        }
+        Ok(chain)
+    }
+
+    pub fn push(&mut self, filter: Arc<dyn ContentFilter>) {
+        self.filters.push(filter);
+    }
+
+    pub fn extend(&mut self, other: &FilterChain) {
+        self.filters.extend(other.filters.iter().cloned());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// 依次应用所有过滤器，没有命中时返回None
+    pub fn apply(&self, text: &str) -> Option<String> {
+        let mut filtered: Option<String> = None;
+        for filter in &self.filters {
+            if let Some(result) = filter.apply(filtered.as_deref().unwrap_or(text)) {
+                filtered = Some(result);
+            }
+        }
+        filtered
+    }
+
+    /// 过滤请求中的文本内容：聊天消息（字符串或文本片段）、补全的prompt和embeddings的input
+    pub fn redact_request(&self, body: &mut Value) -> bool {
+        let mut changed = false;
+        if let Some(messages) = body.get_mut("messages").and_then(Value::as_array_mut) {
+            for content in messages.iter_mut().filter_map(|m| m.get_mut("content")) {
+                changed |= self.redact_content(content);
+            }
+        }
+        for field in ["prompt", "input"] {
+            if let Some(value) = body.get_mut(field) {
+                changed |= self.redact_content(value);
+            }
+        }
+        changed
+    }
+
+    /// 过滤响应中的文本内容：非流式的message.content和text，流式分片的delta.content
+    pub fn redact_response(&self, body: &mut Value) -> bool {
+        let Some(choices) = body.get_mut("choices").and_then(Value::as_array_mut) else {
+            return false;
+        };
+        let mut changed = false;
+        for choice in choices {
+            for pointer in ["/message/content", "/delta/content", "/text"] {
+                if let Some(content) = choice.pointer_mut(pointer) {
+                    changed |= self.redact_content(content);
+                }
+            }
+        }
+        changed
+    }
+
+    /// 过滤返回给客户端的一个响应分片：SSE分片逐行过滤data事件，JSON响应过滤完整的响应体，
+    /// 保活用的空白分片和无法解析的内容原样返回，没有命中时返回None
+    pub fn redact_response_chunk(&self, chunk: &str, is_sse: bool) -> Option<String> {
+        if !is_sse {
+            let mut value = serde_json::from_str::<Value>(chunk.trim()).ok()?;
+            return self.redact_response(&mut value).then(|| value.to_string());
+        }
+        let mut changed = false;
+        let lines: Vec<String> = chunk
+            .split('\n')
+            .map(|line| {
+                if let Some(data) = line.strip_prefix("data:")
+                    && let Ok(mut value) = serde_json::from_str::<Value>(data.trim())
+                    && self.redact_response(&mut value)
+                {
+                    changed = true;
+                    return format!("data: {}", value);
+                }
+                line.to_string()
+            })
+            .collect();
+        changed.then(|| lines.join("\n"))
+    }
+
+    /// 内容可以是字符串、字符串数组或带text字段的内容片段数组
+    fn redact_content(&self, content: &mut Value) -> bool {
+        match content {
+            Value::String(text) => match self.apply(text) {
+                Some(filtered) => {
+                    *text = filtered;
+                    true
+                }
+                None => false,
+            },
+            Value::Array(parts) => parts.iter_mut().fold(false, |changed, part| {
+                let target = match part {
+                    Value::String(_) => Some(part),
+                    Value::Object(object) => object.get_mut("text"),
+                    _ => None,
+                };
+                target.is_some_and(|target| self.redact_content(target)) || changed
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// 一个请求适用的过滤器链（用户和模型引用的规则集合并后）
+#[derive(Clone, Default)]
+pub struct RequestFilters {
+    /// 写入日志前应用
+    pub log: FilterChain,
+    /// 转发给上游前应用
+    pub upstream: FilterChain,
+    /// 返回给客户端前应用
+    pub responses: FilterChain,
+}
+
+impl RequestFilters {
+    pub fn is_empty(&self) -> bool {
+        self.log.is_empty()
+    }
+
+    /// 写入日志用的请求副本，没有命中时返回None
+    pub fn redact_for_log(&self, body: &Value) -> Option<Value> {
+        let mut redacted = body.clone();
+        self.log.redact_request(&mut redacted).then_some(redacted)
+    }
+}
+
+/// 编译后的过滤规则集缓存，规则集内容变化（配置热加载）时重新编译
+pub struct ContentFilterRegistry {
+    compiled: std::sync::Mutex<HashMap<String, (ContentFilterSet, FilterChain)>>,
+}
+
+impl ContentFilterRegistry {
+    pub fn new() -> Self {
+        Self {
+            compiled: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 合并指定名称的规则集，不存在或无法编译的规则集被忽略（配置校验时已检查）
+    pub fn resolve<'a>(
+        &self,
+        sets: &HashMap<String, ContentFilterSet>,
+        names: impl IntoIterator<Item = &'a String>,
+    ) -> RequestFilters {
+        let mut filters = RequestFilters::default();
+        let Ok(mut compiled) = self.compiled.lock() else {
+            return filters;
+        };
+        let mut seen = Vec::new();
+        for name in names {
+            let Some(set) = sets.get(name) else {
+                continue;
+            };
+            if seen.contains(&name) {
+                continue;
+            }
+            seen.push(name);
+
+            let chain = match compiled.get(name) {
+                Some((cached, chain)) if cached == set => chain.clone(),
+                _ => match FilterChain::from_set(set) {
+                    Ok(chain) => {
+                        compiled.insert(name.clone(), (set.clone(), chain.clone()));
+                        chain
+                    }
+                    Err(e) => {
+                        tracing::warn!("Ignoring invalid content filter '{}': {}", name, e);
+                        continue;
+                    }
+                },
+            };
+            filters.log.extend(&chain);
+            if set.redact_upstream {
+                filters.upstream.extend(&chain);
+            }
+            if set.redact_responses {
+                filters.responses.extend(&chain);
+            }
+        }
+        filters
+    }
+}
+
+impl Default for ContentFilterRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn set(builtin: Vec<BuiltinFilter>, keywords: &[&str]) -> ContentFilterSet {
+        ContentFilterSet {
+            builtin,
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+            replacement: "[REDACTED]".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_builtin_and_keyword_redaction() {
+        let chain = FilterChain::from_set(&set(
+            vec![BuiltinFilter::Email, BuiltinFilter::Phone, BuiltinFilter::CreditCard, BuiltinFilter::ApiKey],
+            &["Project Falcon"],
+        ))
+        .unwrap();
+        let cases = [
+            ("mail alice@example.com now", "mail [REDACTED] now"),
+            ("call +1 415-555-0100 or 13812345678", "call [REDACTED] or [REDACTED]"),
+            ("card 4111 1111 1111 1111", "card [REDACTED]"),
+            ("key sk-abcdefghijklmnopqrstuv", "key [REDACTED]"),
+            ("about project falcon", "about [REDACTED]"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(chain.apply(input).as_deref(), Some(expected), "input: {}", input);
+        }
+        assert_eq!(chain.apply("nothing to hide"), None);
+    }
+
+    #[test]
+    fn test_redacts_request_and_response_bodies() {
+        let chain = FilterChain::from_set(&set(vec![BuiltinFilter::Email], &[])).unwrap();
+        let mut request = json!({
+            "messages": [
+                {"role": "system", "content": "be nice"},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "reply to bob@example.com"},
+                    {"type": "image_url", "image_url": {"url": "https://example.com/a.png"}}
+                ]}
+            ],
+            "input": ["x@example.com", "plain"]
+        });
+        assert!(chain.redact_request(&mut request));
+        assert_eq!(request["messages"][0]["content"], "be nice");
+        assert_eq!(request["messages"][1]["content"][0]["text"], "reply to [REDACTED]");
+        assert_eq!(request["input"], json!(["[REDACTED]", "plain"]));
+
+        let chunk = format!("data: {}\n\n", json!({"choices": [{"delta": {"content": "ping me@example.com"}}]}));
+        let redacted = chain.redact_response_chunk(&chunk, true).unwrap();
+        assert!(redacted.ends_with("\n\n"));
+        let event: Value = serde_json::from_str(redacted.trim().strip_prefix("data: ").unwrap()).unwrap();
+        assert_eq!(event["choices"][0]["delta"]["content"], "ping [REDACTED]");
+        assert_eq!(chain.redact_response_chunk("data: [DONE]\n\n", true), None);
+        assert_eq!(chain.redact_response_chunk(" ", false), None);
+    }
+
+    #[test]
+    fn test_registry_merges_sets_by_purpose() {
+        let mut upstream = set(vec![BuiltinFilter::Email], &[]);
+        upstream.redact_upstream = true;
+        let sets = HashMap::from([
+            ("pii".to_string(), upstream),
+            ("secrets".to_string(), set(vec![BuiltinFilter::ApiKey], &[])),
+        ]);
+        let registry = ContentFilterRegistry::new();
+        let names = ["pii".to_string(), "secrets".to_string(), "pii".to_string()];
+        let filters = registry.resolve(&sets, &names);
+
+        let text = "a@example.com sk-abcdefghijklmnopqrstuv";
+        assert_eq!(filters.log.apply(text).as_deref(), Some("[REDACTED] [REDACTED]"));
+        assert_eq!(filters.upstream.apply(text).as_deref(), Some("[REDACTED] sk-abcdefghijklmnopqrstuv"));
+        assert!(filters.responses.is_empty());
+        assert!(registry.resolve(&sets, &[]).is_empty());
+    }
+}
@@ -7,122 +7,2482 @@ use futures::StreamExt;
 use serde_json::{Value, json};
 use std::sync::Arc;
 use std::time::Instant;
+use tracing::Instrument;
+
+use crate::config::model::{AuditLogSettings, Backend, FilesSettings, LoadBalanceStrategy, MirrorSettings, ModelMapping, Provider, RequestCaptureSettings, RequestTransform, StructuredOutputSettings, UserToken};
+use crate::loadbalance::{ExperimentAssignment, LoadBalanceService, RequestResult, RequiredCapabilities, SelectedBackend, SelectionContext, TokenUsage};
+use crate::relay::admission::{AdmissionController, AdmissionError, AdmissionGuard};
+use crate::relay::audit::{AuditLogger, AuditRecord};
+use crate::relay::capture::{CaptureRecord, RequestCapture};
+use crate::relay::fault::{FaultHook, FaultInjector};
+use crate::relay::request_log::{RequestLogRecord, RequestLogger};
+use crate::relay::resume::{self, StreamResumeStore};
+use crate::relay::client::FormField;
+use crate::relay::client::openai::{
+    AUDIO_SPEECH_PATH, AUDIO_TRANSCRIPTIONS_PATH, CHAT_COMPLETIONS_PATH, COMPLETIONS_PATH,
+    EMBEDDINGS_PATH, FILES_PATH, MODERATIONS_PATH, OpenAIClient, RESPONSES_PATH,
+};
+use crate::relay::cache::{CACHE_STATUS_HEADER, ResponseCache};
+use crate::relay::concurrency::{ConcurrencyError, ConcurrencyLimiter, ConcurrencyPermit};
+use crate::relay::debug::{self, DebugTrace};
+use crate::relay::error::{RelayError, UpstreamError};
+use crate::relay::fair_queue::{FairQueueStats, FairScheduler};
+use crate::relay::files::{self, FileRecord, FileRegistry};
+use crate::relay::heartbeat;
+use crate::relay::filter::{ContentFilterRegistry, FilterChain, RequestFilters};
+use crate::relay::moderation::ModerationGuard;
+use crate::relay::peer::PeerForwarder;
+use crate::relay::rate_limit::RateLimiter;
+use crate::relay::semantic_cache::{SemanticCache, SemanticLookup};
+use crate::relay::structured_output::{StructuredOutputMetrics, repair_response, requests_json, validate_response};
+use crate::relay::timeouts::{TimeoutKind, UpstreamDeadlines};
+use crate::relay::tokens::estimate_request_tokens;
+use crate::relay::wasm::{WasmHook, WasmHookRunner, WasmPluginHost};
+use crate::relay::user_limits;
+use crate::relay::tool_calls::{ToolCallStreamNormalizer, normalize_response, normalize_response_text};
+
+use super::types::{ErrorType, create_error_response};
+
+/// 客户端指定会话标识的请求头（用于会话粘性策略）
+pub const SESSION_ID_HEADER: &str = "x-session-id";
+
+/// 客户端指定后端标签的请求头，逗号分隔，只路由到包含全部标签的后端
+pub const BACKEND_TAGS_HEADER: &str = "x-berry-tags";
+
+/// 管理员覆盖本次请求负载均衡策略的请求头，值为策略名称（如round_robin）
+pub const STRATEGY_OVERRIDE_HEADER: &str = "x-berry-strategy";
+
+/// 管理员绕过后端选择、直接指定后端的请求头，格式为provider:model
+pub const PINNED_BACKEND_HEADER: &str = "x-berry-backend";
+
+/// 客户端指定所在区域的请求头，优先路由到同区域的后端
+pub const REGION_HEADER: &str = "x-berry-region";
+
+/// 请求降级到其他模型处理时返回的响应头，值为实际使用的模型名称
+pub const FALLBACK_MODEL_HEADER: &str = "x-berry-fallback-model";
+
+/// 参与A/B实验的请求返回的响应头，值为 实验名称:分组
+pub const EXPERIMENT_HEADER: &str = "x-berry-experiment";
+
+/// 实际处理请求的后端，成功时附加在响应扩展中，用于花费统计
+#[derive(Clone)]
+struct ServedBackend(Backend);
+
+/// 生成类请求在重试、对冲和降级之间共用的信息
+struct RelayRequest<'a> {
+    endpoint: &'static str,
+    user: &'a UserToken,
+    authorization: &'a headers::Authorization<headers::authorization::Bearer>,
+    content_type: &'a headers::ContentType,
+    headers: &'a axum::http::HeaderMap,
+    start_time: Instant,
+}
+
+/// 发往选中后端的一次上游请求：后端、客户端、请求头、改写后的请求体及其并发名额
+struct UpstreamAttempt {
+    selected_backend: SelectedBackend,
+    client: OpenAIClient,
+    headers: reqwest::header::HeaderMap,
+    body: Value,
+    permit: ConcurrencyPermit,
+}
+
+/// 完成上游握手的Realtime会话
+struct RealtimeSession {
+    upstream: crate::relay::realtime::UpstreamSocket,
+    selected_backend: SelectedBackend,
+    user: UserToken,
+    model_id: String,
+    client_key: String,
+    connect_latency: std::time::Duration,
+}
+
+/// 上游流式响应解析出的SSE事件流
+type UpstreamEvents = futures::stream::BoxStream<
+    'static,
+    Result<eventsource_stream::Event, eventsource_stream::EventStreamError<reqwest::Error>>,
+>;
+
+/// 分级超时触发时的上游错误
+fn timeout_error(selected_backend: &SelectedBackend, kind: TimeoutKind) -> UpstreamError {
+    UpstreamError::Timeout(
+        kind,
+        format!(
+            "{}:{} did not respond within the configured {} timeout",
+            selected_backend.backend.provider, selected_backend.backend.model, kind
+        ),
+    )
+}
+
+/// 透传接口的请求体
+enum PassthroughBody {
+    Json(Value),
+    /// multipart表单（音频上传），模型名称在model字段中
+    Multipart(Vec<FormField>),
+}
+
+impl PassthroughBody {
+    fn model(&self) -> Option<&str> {
+        match self {
+            Self::Json(body) => body.get("model").and_then(|m| m.as_str()),
+            Self::Multipart(fields) => fields
+                .iter()
+                .find(|f| f.name == "model" && f.file_name.is_none())
+                .and_then(|f| std::str::from_utf8(&f.data).ok()),
+        }
+    }
 
-use crate::loadbalance::{LoadBalanceService, RequestResult};
-use crate::relay::client::openai::OpenAIClient;
+    fn has_field(&self, name: &str) -> bool {
+        match self {
+            Self::Json(body) => body.get(name).is_some(),
+            Self::Multipart(fields) => fields.iter().any(|f| f.name == name),
+        }
+    }
 
-use super::types::{create_service_unavailable_response, create_internal_error_response, create_gateway_timeout_response, ErrorType, create_error_response};
+    fn set_model(&mut self, model: &str) {
+        match self {
+            Self::Json(body) => body["model"] = Value::String(model.to_string()),
+            Self::Multipart(fields) => {
+                for field in fields.iter_mut().filter(|f| f.name == "model") {
+                    field.data = bytes::Bytes::from(model.to_string());
+                }
+            }
+        }
+    }
+}
 
 /// 负载均衡的OpenAI兼容处理器
 pub struct LoadBalancedHandler {
     load_balancer: std::sync::Arc<LoadBalanceService>,
+    peer_forwarder: PeerForwarder,
+    response_cache: Arc<ResponseCache>,
+    semantic_cache: Arc<SemanticCache>,
+    audit_logger: Arc<AuditLogger>,
+    /// 写入数据库的请求记录
+    request_logger: Arc<RequestLogger>,
+    /// 用于复现问题和回放的请求抓取
+    request_capture: RequestCapture,
+    /// 管理接口注入的后端故障
+    faults: Arc<FaultInjector>,
+    /// 按provider复用的上游客户端（保留连接池），记录创建时的provider配置以便热加载后重建
+    clients: std::sync::Mutex<std::collections::HashMap<String, (Provider, OpenAIClient)>>,
+    /// 按provider和后端限制发往上游的并发请求数
+    concurrency: ConcurrencyLimiter,
+    /// 全局和按模型的在途请求数准入控制
+    admission: AdmissionController,
+    /// 模型名额已满时按用户公平排队
+    fair_scheduler: FairScheduler,
+    /// 用户请求限流
+    rate_limiter: Arc<RateLimiter>,
+    /// JSON模式输出校验的重试和修复统计
+    structured_output: StructuredOutputMetrics,
+    /// 编译后的内容过滤规则集
+    content_filters: ContentFilterRegistry,
+    /// 请求前的内容审核
+    moderation: ModerationGuard,
+    /// WASM请求转换插件
+    wasm_plugins: WasmPluginHost,
+    /// 上传文件的ID映射
+    files: FileRegistry,
+    /// 流式响应的断点续传缓冲
+    stream_resume: StreamResumeStore,
 }
 
 impl LoadBalancedHandler {
     pub fn new(load_balancer: std::sync::Arc<LoadBalanceService>) -> Self {
-        Self { load_balancer }
+        Self {
+            load_balancer,
+            peer_forwarder: PeerForwarder::new(),
+            response_cache: Arc::new(ResponseCache::new()),
+            semantic_cache: Arc::new(SemanticCache::new()),
+            audit_logger: Arc::new(AuditLogger::new()),
+            request_logger: Arc::new(RequestLogger::new()),
+            request_capture: RequestCapture::new(),
+            faults: Arc::new(FaultInjector::new()),
+            clients: std::sync::Mutex::new(std::collections::HashMap::new()),
+            concurrency: ConcurrencyLimiter::new(),
+            admission: AdmissionController::new(),
+            fair_scheduler: FairScheduler::new(),
+            rate_limiter: Arc::new(RateLimiter::new()),
+            structured_output: StructuredOutputMetrics::new(),
+            content_filters: ContentFilterRegistry::new(),
+            moderation: ModerationGuard::new(),
+            wasm_plugins: WasmPluginHost::new(),
+            files: FileRegistry::new(),
+            stream_resume: StreamResumeStore::new(),
+        }
+    }
+
+    /// 记录在路由层被拒绝的请求，未配置审计日志时忽略
+    pub fn audit_rejection(&self, record: AuditRecord) {
+        if let Some(settings) = &self.load_balancer.get_config().settings.audit_log {
+            self.audit_logger.log(settings, record);
+        }
+    }
+
+    /// 获取选中后端所属provider的上游客户端，模型映射覆盖了连接超时时使用单独的客户端
+    fn client_for(
+        &self,
+        selected_backend: &crate::loadbalance::SelectedBackend,
+    ) -> Result<OpenAIClient, crate::relay::client::ClientError> {
+        let provider_id = &selected_backend.backend.provider;
+        let provider = &selected_backend.provider;
+        let connect_seconds = selected_backend.timeouts.connect_seconds;
+        if connect_seconds == provider.timeouts.as_ref().and_then(|t| t.connect_seconds) {
+            return self.client_for_provider(provider_id, provider);
+        }
+
+        let mut provider = provider.clone();
+        provider.timeouts.get_or_insert_with(Default::default).connect_seconds = connect_seconds;
+        let cache_key = format!("{}@connect={}s", provider_id, connect_seconds.unwrap_or_default());
+        self.cached_client(&cache_key, provider_id, &provider)
+    }
+
+    /// 获取provider的上游客户端，provider配置未变化时复用已创建的客户端
+    fn client_for_provider(
+        &self,
+        provider_id: &str,
+        provider: &Provider,
+    ) -> Result<OpenAIClient, crate::relay::client::ClientError> {
+        self.cached_client(provider_id, provider_id, provider)
+    }
+
+    fn cached_client(
+        &self,
+        cache_key: &str,
+        provider_id: &str,
+        provider: &Provider,
+    ) -> Result<OpenAIClient, crate::relay::client::ClientError> {
+        if let Ok(clients) = self.clients.lock()
+            && let Some((cached, client)) = clients.get(cache_key)
+            && cached == provider
+        {
+            return Ok(self.with_faults(provider_id, client.clone()));
+        }
+
+        // 只设置连接超时，不限制总请求时间（除非provider配置了request_timeout_seconds或分级超时）
+        // 连接成功后允许无限时间生成内容，直到客户端断开连接
+        let client = OpenAIClient::for_provider(
+            provider_id,
+            provider,
+            std::time::Duration::from_secs(provider.timeout_seconds),
+            self.load_balancer.get_metrics(),
+        )?;
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.insert(cache_key.to_string(), (provider.clone(), client.clone()));
+        }
+        Ok(self.with_faults(provider_id, client))
+    }
+
+    /// 启用故障注入时为客户端附加故障注入，每次取用客户端时按当前配置决定
+    fn with_faults(&self, provider_id: &str, client: OpenAIClient) -> OpenAIClient {
+        if self.load_balancer.get_config().settings.fault_injection {
+            client.with_faults(FaultHook::new(provider_id, self.faults.clone()))
+        } else {
+            client
+        }
+    }
+
+    /// 获取故障注入状态
+    pub fn faults(&self) -> &FaultInjector {
+        &self.faults
+    }
+
+    /// 获取响应缓存
+    pub fn response_cache(&self) -> &ResponseCache {
+        &self.response_cache
+    }
+
+    /// 获取语义缓存
+    pub fn semantic_cache(&self) -> &SemanticCache {
+        &self.semantic_cache
+    }
+
+    /// 获取各模型的公平排队状态
+    pub fn fair_queue_stats(&self) -> std::collections::HashMap<String, FairQueueStats> {
+        self.fair_scheduler.stats()
+    }
+
+    /// 配置了并发上限的用户当前的在途请求数
+    pub fn user_in_flight(&self) -> std::collections::HashMap<String, usize> {
+        self.concurrency.user_in_flight()
+    }
+
+    /// 获取结构化输出校验统计
+    pub fn structured_output(&self) -> &StructuredOutputMetrics {
+        &self.structured_output
+    }
+
+    /// 构造缓存命中的响应
+    fn cached_response(body: String, cache_status: &'static str) -> axum::response::Response {
+        axum::response::Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json")
+            .header(CACHE_STATUS_HEADER, cache_status)
+            .body(axum::body::Body::from(body))
+            .unwrap_or_else(|_| axum::response::Response::new(axum::body::Body::empty()))
+    }
+
+    /// 获取对等实例转发器
+    pub fn peer_forwarder(&self) -> &PeerForwarder {
+        &self.peer_forwarder
     }
 
     /// 处理聊天完成请求（支持负载均衡和智能重试）
+    #[tracing::instrument(
+        name = "chat_completion",
+        skip_all,
+        fields(user = %user.name, model = tracing::field::Empty, stream = tracing::field::Empty)
+    )]
     pub async fn handle_completions(
         self: Arc<Self>,
+        user: UserToken,
         TypedHeader(authorization): TypedHeader<
             headers::Authorization<headers::authorization::Bearer>,
         >,
         TypedHeader(content_type): TypedHeader<headers::ContentType>,
-        Json(mut body): Json<Value>,
+        request_headers: axum::http::HeaderMap,
+        Json(body): Json<Value>,
     ) -> axum::response::Response {
-        let start_time = Instant::now();
+        self.relay_streaming(
+            CHAT_COMPLETIONS_PATH,
+            user,
+            authorization,
+            content_type,
+            request_headers,
+            body,
+        )
+        .await
+    }
 
-        // 从请求体中提取模型名称
-        let model_name = match body.get("model").and_then(|m| m.as_str()) {
-            Some(name) => name.to_string(),
-            None => {
-                tracing::error!("Missing model field in request");
-                return create_error_response(
-                    ErrorType::BadRequest,
-                    "Missing model field in request",
-                    Some("The 'model' field is required in the request body".to_string()),
-                ).into_response();
+    /// 处理旧版文本补全请求（/v1/completions），后端选择、健康记录和重试与聊天完成相同
+    #[tracing::instrument(
+        name = "completion",
+        skip_all,
+        fields(user = %user.name, model = tracing::field::Empty, stream = tracing::field::Empty)
+    )]
+    pub async fn handle_text_completions(
+        self: Arc<Self>,
+        user: UserToken,
+        TypedHeader(authorization): TypedHeader<
+            headers::Authorization<headers::authorization::Bearer>,
+        >,
+        TypedHeader(content_type): TypedHeader<headers::ContentType>,
+        request_headers: axum::http::HeaderMap,
+        Json(body): Json<Value>,
+    ) -> axum::response::Response {
+        self.relay_streaming(
+            COMPLETIONS_PATH,
+            user,
+            authorization,
+            content_type,
+            request_headers,
+            body,
+        )
+        .await
+    }
+
+    /// 流式请求的心跳和断点续传：配置了心跳时在等待上游首个token期间发送SSE心跳，
+    /// 配置了断点续传时缓冲流式响应，带`Last-Event-ID`的重连请求直接从缓冲续传
+    async fn relay_streaming(
+        self: Arc<Self>,
+        endpoint: &'static str,
+        user: UserToken,
+        authorization: headers::Authorization<headers::authorization::Bearer>,
+        content_type: headers::ContentType,
+        request_headers: axum::http::HeaderMap,
+        body: Value,
+    ) -> axum::response::Response {
+        let stream = body.get("stream").and_then(Value::as_bool).unwrap_or(false);
+        let config = self.load_balancer.get_config();
+        let heartbeat = config.settings.stream_heartbeat.clone().filter(|_| stream);
+        let resumption = config.settings.stream_resumption.clone().filter(|_| stream);
+        if heartbeat.is_none() && resumption.is_none() {
+            return self
+                .relay_completions(endpoint, user, authorization, content_type, request_headers, body)
+                .await;
+        }
+
+        let request_id = request_headers
+            .get(crate::telemetry::REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let resumption = resumption.zip(request_id).map(|(settings, request_id)| {
+            let key = StreamResumeStore::key(&user.name, &request_id);
+            (settings, key, request_id)
+        });
+        if let Some((settings, key, request_id)) = &resumption
+            && let Some(last_event_id) = request_headers.get(resume::LAST_EVENT_ID_HEADER)
+        {
+            let Some(last_event_id) = last_event_id.to_str().ok().and_then(|id| id.trim().parse().ok()) else {
+                return RelayError::InvalidRequest {
+                    message: "Invalid Last-Event-ID header".to_string(),
+                    details: None,
+                }
+                .into_response();
+            };
+            return self
+                .stream_resume
+                .resume(settings, key, last_event_id)
+                .unwrap_or_else(|| RelayError::StreamNotFound(request_id.clone()).into_response());
+        }
+
+        // 上游超过请求超时时间没有新事件时停止缓冲
+        let idle_timeout = std::time::Duration::from_secs(config.settings.request_timeout_seconds.max(1));
+        let response = async move {
+            let response = self
+                .relay_completions(endpoint, user, authorization, content_type, request_headers, body)
+                .await;
+            match resumption {
+                Some((settings, key, _)) => self.stream_resume.record(&settings, key, idle_timeout, response),
+                None => response,
+            }
+        }
+        .in_current_span();
+        match heartbeat {
+            Some(heartbeat) => {
+                heartbeat::with_heartbeat(Box::pin(response), std::time::Duration::from_millis(heartbeat.interval_ms)).await
             }
+            None => response.await,
+        }
+    }
+
+    /// 获取内容审核统计
+    pub fn moderation(&self) -> &ModerationGuard {
+        &self.moderation
+    }
+
+    /// 请求前的内容审核，审核结果写入审计记录
+    /// 命中拒绝规则、或审核服务不可用且不放行时返回错误响应
+    async fn moderate(
+        &self,
+        config: &crate::config::model::Config,
+        user: &UserToken,
+        body: &Value,
+        audit: Option<&mut (AuditLogSettings, AuditRecord)>,
+    ) -> Option<axum::response::Response> {
+        let settings = config.settings.moderation.as_ref()?;
+        match self.moderation.check(config, settings, body).await {
+            Ok(None) => None,
+            Ok(Some(verdict)) => {
+                if verdict.flagged {
+                    tracing::warn!(
+                        "Request from user '{}' flagged by content moderation (categories: {:?}, blocked: {})",
+                        user.name,
+                        verdict.categories,
+                        verdict.blocked
+                    );
+                }
+                let rejected = verdict
+                    .blocked
+                    .then(|| RelayError::ContentPolicyViolation(verdict.reason()).into_response());
+                if let Some((_, record)) = audit {
+                    record.moderation = Some(verdict);
+                }
+                rejected
+            }
+            Err(e) if settings.fail_open => {
+                tracing::warn!("Content moderation failed, allowing request: {}", e);
+                None
+            }
+            Err(e) => {
+                tracing::error!("Content moderation failed, rejecting request: {}", e);
+                Some(RelayError::ModerationUnavailable(format!("{}. Please retry later.", e)).into_response())
+            }
+        }
+    }
+
+    /// 用户和模型引用的内容过滤规则集
+    fn request_filters(&self, config: &crate::config::model::Config, user: &UserToken, model_id: &str) -> RequestFilters {
+        let model_filters = config
+            .models
+            .get(model_id)
+            .map(|mapping| mapping.content_filters.as_slice())
+            .unwrap_or_default();
+        self.content_filters
+            .resolve(&config.settings.content_filters, user.content_filters.iter().chain(model_filters))
+    }
+
+    /// 在返回给客户端前过滤响应内容，响应体长度会变化，因此去掉Content-Length
+    fn redact_response_body(response: axum::response::Response, chain: FilterChain) -> axum::response::Response {
+        let (mut parts, body) = response.into_parts();
+        let is_sse = parts
+            .headers
+            .get(axum::http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+        let stream = body.into_data_stream().map(move |chunk| {
+            chunk.map(|bytes| {
+                match std::str::from_utf8(&bytes).ok().and_then(|text| chain.redact_response_chunk(text, is_sse)) {
+                    Some(redacted) => bytes::Bytes::from(redacted),
+                    None => bytes,
+                }
+            })
+        });
+        axum::response::Response::from_parts(parts, axum::body::Body::from_stream(stream))
+    }
+
+    /// 把非流式成功响应交给WASM插件转换，保活用的空白分片原样返回
+    fn transform_response_body(
+        response: axum::response::Response,
+        runner: WasmHookRunner,
+        model_id: String,
+    ) -> axum::response::Response {
+        let is_sse = response
+            .headers()
+            .get(axum::http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        if is_sse || !response.status().is_success() {
+            return response;
+        }
+        let (mut parts, body) = response.into_parts();
+        parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+        let stream = body.into_data_stream().then(move |chunk| {
+            let (runner, model_id) = (runner.clone(), model_id.clone());
+            async move {
+                let bytes: bytes::Bytes = chunk?;
+                let Some(value) = std::str::from_utf8(&bytes)
+                    .ok()
+                    .and_then(|text| serde_json::from_str::<Value>(text.trim()).ok())
+                else {
+                    return Ok::<_, axum::Error>(bytes);
+                };
+                Ok(match runner.run(&model_id, &value).await {
+                    Some(transformed) => bytes::Bytes::from(transformed.to_string()),
+                    None => bytes,
+                })
+            }
+        });
+        axum::response::Response::from_parts(parts, axum::body::Body::from_stream(stream))
+    }
+
+    /// 生成类请求的公共入口：处理请求后统计花费并写出审计日志
+    async fn relay_completions(
+        &self,
+        endpoint: &'static str,
+        user: UserToken,
+        authorization: headers::Authorization<headers::authorization::Bearer>,
+        content_type: headers::ContentType,
+        request_headers: axum::http::HeaderMap,
+        body: Value,
+    ) -> axum::response::Response {
+        let start_time = Instant::now();
+        crate::telemetry::continue_trace_from(&request_headers);
+        let debug_trace = match Self::debug_trace(&user, &request_headers) {
+            Ok(trace) => trace,
+            Err(e) => return e.into_response(),
         };
 
-        // 尝试处理请求，带内部重试机制
-        match self
-            .try_handle_with_retries(
-                &model_name,
-                &mut body,
-                &authorization,
-                &content_type,
-                start_time,
-            )
-            .await
+        let config = self.load_balancer.get_config();
+        let model_id = Self::resolve_model_id(&config, &body);
+        let filters = self.request_filters(&config, &user, &model_id);
+        let mut audit = config.settings.audit_log.clone().map(|settings| {
+            let logged = filters.redact_for_log(&body);
+            let record = AuditRecord::new(&settings, &user.name, authorization.token(), logged.as_ref().unwrap_or(&body));
+            (settings, record)
+        });
+        let capture = Self::start_capture(&config, &user, authorization.token(), endpoint, &request_headers, &filters, &body);
+        let mut body = body;
+        filters.upstream.redact_request(&mut body);
+        let wasm_runner = |hook| {
+            config
+                .settings
+                .wasm_plugins
+                .as_ref()
+                .and_then(|settings| self.wasm_plugins.runner(settings, hook))
+        };
+        if let Some(runner) = wasm_runner(WasmHook::Request)
+            && let Some(transformed) = runner.run(&model_id, &body).await
         {
-            Ok(response) => response,
-            Err(e) => {
-                tracing::error!(
-                    "All retry attempts failed for model '{}': {}",
-                    model_name,
-                    e
-                );
+            body = transformed;
+        }
+        // 模型配置了A/B实验时按用户密钥分组
+        let experiment = config
+            .models
+            .get(&model_id)
+            .and_then(|mapping| mapping.experiment.as_ref())
+            .map(|settings| ExperimentAssignment::assign(settings, authorization.token()));
+
+        let mut response = if let Some(limited) = self.check_rate_limit(&config, &user).await {
+            limited
+        } else if let Some(limits) = &user.limits
+            && let Err(e) = user_limits::apply_max_tokens(limits, user_limits::COMPLETION_TOKEN_PARAMS, &mut body)
+        {
+            e.into_response()
+        } else if let Some(rejected) = self.moderate(&config, &user, &body, audit.as_mut()).await {
+            rejected
+        } else {
+            match self.admit(&config, &user, &model_id, estimate_request_tokens(&body)).await {
+                Ok(admission) => {
+                    let request = RelayRequest {
+                        endpoint,
+                        user: &user,
+                        authorization: &authorization,
+                        content_type: &content_type,
+                        headers: &request_headers,
+                        start_time,
+                    };
+                    let response = self.process_completions(&request, body, experiment.as_ref());
+                    let response = DebugTrace::scope(debug_trace.clone(), Box::pin(response));
+                    let response = user_limits::with_max_duration(user.limits.as_ref(), start_time, response).await;
+                    Self::release_on_complete(response, admission)
+                }
+                Err(e) => RelayError::from(e).into_response(),
+            }
+        };
+        if let Some(trace) = debug_trace {
+            response = trace.attach(response).await;
+        }
+        if let Some(runner) = wasm_runner(WasmHook::Response) {
+            response = Self::transform_response_body(response, runner, model_id.clone());
+        }
+        if !filters.responses.is_empty() {
+            response = Self::redact_response_body(response, filters.responses);
+        }
+        if let Some(assignment) = experiment {
+            if let Ok(value) = axum::http::HeaderValue::from_str(&assignment.header_value()) {
+                response.headers_mut().insert(EXPERIMENT_HEADER, value);
+            }
+            response.extensions_mut().insert(assignment);
+        }
+        let response = self.capture_response(capture, response, start_time);
+        self.finish_response(response, user, model_id, audit, start_time)
+    }
 
-                // 创建更详细的错误响应，使用正确的HTTP状态码
-                let error_str = e.to_string();
-                if error_str.contains("Backend selection failed after") || error_str.contains("no available backends") {
-                    // 服务不可用 - 503
-                    create_service_unavailable_response(
-                        &format!("Service temporarily unavailable for model '{}'", model_name),
-                        Some(format!("All backends are currently unhealthy or unavailable. Details: {}", e)),
-                    ).into_response()
-                } else if error_str.contains("Failed to select backend") {
-                    // 服务不可用 - 503
-                    create_service_unavailable_response(
-                        &format!("No available backends for model '{}'", model_name),
-                        Some(format!("Backend selection failed. Please try again later. Details: {}", e)),
-                    ).into_response()
-                } else if error_str.contains("timeout") || error_str.contains("timed out") {
-                    // 网关超时 - 504
-                    create_gateway_timeout_response(
-                        &format!("Request timeout for model '{}'", model_name),
-                        Some(format!("Request processing timed out after multiple attempts. Details: {}", e)),
-                    ).into_response()
-                } else if error_str.contains("API key") || error_str.contains("configuration error") {
-                    // 内部服务器错误 - 500
-                    create_internal_error_response(
-                        &format!("Configuration error for model '{}'", model_name),
-                        Some("Please contact system administrator to check backend configuration".to_string()),
-                    ).into_response()
-                } else {
-                    // 通用内部服务器错误 - 500
-                    create_internal_error_response(
-                        &format!("Request processing failed for model '{}'", model_name),
-                        Some(format!("Request failed after multiple attempts. If the problem persists, contact support. Details: {}", e)),
-                    ).into_response()
+    /// 处理embeddings请求（支持负载均衡、重试和用量统计）
+    #[tracing::instrument(
+        name = "embeddings",
+        skip_all,
+        fields(user = %user.name, model = tracing::field::Empty)
+    )]
+    pub async fn handle_embeddings(
+        self: Arc<Self>,
+        user: UserToken,
+        TypedHeader(authorization): TypedHeader<
+            headers::Authorization<headers::authorization::Bearer>,
+        >,
+        request_headers: axum::http::HeaderMap,
+        Json(body): Json<Value>,
+    ) -> axum::response::Response {
+        self.relay_passthrough(
+            EMBEDDINGS_PATH,
+            user,
+            authorization,
+            request_headers,
+            PassthroughBody::Json(body),
+        )
+        .await
+    }
+
+    /// 处理内容审核请求（/v1/moderations）
+    #[tracing::instrument(
+        name = "moderations",
+        skip_all,
+        fields(user = %user.name, model = tracing::field::Empty)
+    )]
+    pub async fn handle_moderations(
+        self: Arc<Self>,
+        user: UserToken,
+        TypedHeader(authorization): TypedHeader<
+            headers::Authorization<headers::authorization::Bearer>,
+        >,
+        request_headers: axum::http::HeaderMap,
+        Json(body): Json<Value>,
+    ) -> axum::response::Response {
+        self.relay_passthrough(
+            MODERATIONS_PATH,
+            user,
+            authorization,
+            request_headers,
+            PassthroughBody::Json(body),
+        )
+        .await
+    }
+
+    /// 处理语音转文字请求（/v1/audio/transcriptions），转发multipart表单
+    #[tracing::instrument(
+        name = "audio_transcriptions",
+        skip_all,
+        fields(user = %user.name, model = tracing::field::Empty)
+    )]
+    pub async fn handle_audio_transcriptions(
+        self: Arc<Self>,
+        user: UserToken,
+        TypedHeader(authorization): TypedHeader<
+            headers::Authorization<headers::authorization::Bearer>,
+        >,
+        request_headers: axum::http::HeaderMap,
+        fields: Vec<FormField>,
+    ) -> axum::response::Response {
+        self.relay_passthrough(
+            AUDIO_TRANSCRIPTIONS_PATH,
+            user,
+            authorization,
+            request_headers,
+            PassthroughBody::Multipart(fields),
+        )
+        .await
+    }
+
+    /// 处理语音合成请求（/v1/audio/speech），音频以流的形式返回
+    #[tracing::instrument(
+        name = "audio_speech",
+        skip_all,
+        fields(user = %user.name, model = tracing::field::Empty)
+    )]
+    pub async fn handle_audio_speech(
+        self: Arc<Self>,
+        user: UserToken,
+        TypedHeader(authorization): TypedHeader<
+            headers::Authorization<headers::authorization::Bearer>,
+        >,
+        request_headers: axum::http::HeaderMap,
+        Json(body): Json<Value>,
+    ) -> axum::response::Response {
+        self.relay_passthrough(
+            AUDIO_SPEECH_PATH,
+            user,
+            authorization,
+            request_headers,
+            PassthroughBody::Json(body),
+        )
+        .await
+    }
+
+    /// 透传Responses请求（/v1/responses），只用于后端都原生支持Responses接口的模型
+    #[tracing::instrument(
+        name = "responses",
+        skip_all,
+        fields(user = %user.name, model = tracing::field::Empty)
+    )]
+    pub async fn handle_responses(
+        self: Arc<Self>,
+        user: UserToken,
+        TypedHeader(authorization): TypedHeader<
+            headers::Authorization<headers::authorization::Bearer>,
+        >,
+        request_headers: axum::http::HeaderMap,
+        Json(body): Json<Value>,
+    ) -> axum::response::Response {
+        self.relay_passthrough(
+            RESPONSES_PATH,
+            user,
+            authorization,
+            request_headers,
+            PassthroughBody::Json(body),
+        )
+        .await
+    }
+
+    /// 处理Realtime WebSocket会话（/v1/realtime）
+    /// 建立连接时选择后端并完成上游握手，握手失败时换后端重试；之后会话固定在该后端上，
+    /// 会话结束时按结束方式记录后端健康状态，并按response.done中的用量统计花费
+    #[tracing::instrument(
+        name = "realtime",
+        skip_all,
+        fields(user = %user.name, model = %model_name)
+    )]
+    pub async fn handle_realtime(
+        self: Arc<Self>,
+        user: UserToken,
+        client_key: String,
+        request_headers: axum::http::HeaderMap,
+        model_name: String,
+        upgrade: axum::extract::ws::WebSocketUpgrade,
+    ) -> axum::response::Response {
+        let config = self.load_balancer.get_config();
+        let model_id = Self::resolve_model_id(&config, &json!({ "model": model_name }));
+        if let Some(limited) = self.check_rate_limit(&config, &user).await {
+            return limited;
+        }
+        if let Some(response) = self.check_budget(&config, &user, &model_name) {
+            return response;
+        }
+        let admission = match self.admit(&config, &user, &model_id, 1).await {
+            Ok(admission) => admission,
+            Err(e) => return RelayError::from(e).into_response(),
+        };
+        let selection_context =
+            match Self::build_selection_context(&user, &client_key, &request_headers, &Value::Null) {
+                Ok(context) => context,
+                Err(e) => return e.into_response(),
+            };
+        let retry_policy = config
+            .resolve_model(&model_name)
+            .map(|(_, mapping)| mapping.retry_policy())
+            .unwrap_or_default();
+        let max_retries = retry_policy.max_attempts as usize;
+        let mut last_error: Option<RelayError> = None;
+
+        for attempt in 0..max_retries {
+            let selected_backend = match self
+                .load_balancer
+                .select_backend_with_context(&model_name, &selection_context)
+                .await
+            {
+                Ok(backend) => backend,
+                Err(e) => {
+                    last_error = Some(RelayError::NoHealthyBackends {
+                        model: model_name.clone(),
+                        details: format!("Backend selection failed. Details: {}", e),
+                    });
+                    continue;
+                }
+            };
+            let permit = match self.concurrency.acquire(&selected_backend).await {
+                Ok(permit) => permit,
+                Err(e) => {
+                    last_error = Some(RelayError::BackendsAtCapacity {
+                        model: model_name.clone(),
+                        details: format!("Too many concurrent requests, please try again later. Details: {}", e),
+                    });
+                    continue;
+                }
+            };
+
+            let connect_start = Instant::now();
+            match crate::relay::realtime::connect(&selected_backend, &request_headers).await {
+                Ok(upstream) => {
+                    let connect_latency = connect_start.elapsed();
+                    let handler = self.clone();
+                    return upgrade
+                        .protocols(["realtime"])
+                        .on_upgrade(move |socket| async move {
+                            let _permit = permit;
+                            let _admission = admission;
+                            let session = RealtimeSession {
+                                upstream,
+                                selected_backend,
+                                user,
+                                model_id,
+                                client_key,
+                                connect_latency,
+                            };
+                            handler.run_realtime_session(socket, session).await;
+                        })
+                        .into_response();
+                }
+                Err(e) => {
+                    let backend = &selected_backend.backend;
+                    self.load_balancer
+                        .record_request_result(
+                            &backend.provider,
+                            &backend.model,
+                            RequestResult::Failure { error: format!("{:#}", e) },
+                        )
+                        .await;
+                    tracing::warn!("Realtime connection failed on attempt {}: {:#}", attempt + 1, e);
+                    last_error = Some(RelayError::from_upstream(
+                        &model_name,
+                        &UpstreamError::Request(format!("{:#}", e)),
+                        format!("Realtime connection failed after {} attempts. Details: {:#}", attempt + 1, e),
+                    ));
+                    if attempt + 1 < max_retries {
+                        tokio::time::sleep(retry_policy.backoff(attempt as u32 + 1)).await;
+                    }
                 }
             }
         }
+
+        last_error
+            .unwrap_or_else(|| RelayError::Internal("Unexpected end of retry loop".to_string()))
+            .into_response()
     }
 
-    /// 尝试处理请求，带重试机制
-    async fn try_handle_with_retries(
+    /// 转发Realtime会话直到结束，然后记录后端健康状态、花费和审计日志
+    async fn run_realtime_session(&self, socket: axum::extract::ws::WebSocket, session: RealtimeSession) {
+        let RealtimeSession {
+            upstream,
+            selected_backend,
+            user,
+            model_id,
+            client_key,
+            connect_latency,
+        } = session;
+        let start_time = Instant::now();
+        let backend = &selected_backend.backend;
+        let _in_flight = self.begin_upstream_request(backend);
+        tracing::info!("Realtime session for '{}' opened on {}:{}", user.name, backend.provider, backend.model);
+
+        let mut total: Option<TokenUsage> = None;
+        let end = crate::relay::realtime::relay(socket, upstream, |usage| {
+            let total = total.get_or_insert_default();
+            total.prompt_tokens += usage.prompt_tokens;
+            total.completion_tokens += usage.completion_tokens;
+        })
+        .await;
+
+        let error = match &end {
+            crate::relay::realtime::SessionEnd::UpstreamFailed(error) => Some(error.clone()),
+            _ => None,
+        };
+        let result = match &error {
+            Some(error) => RequestResult::Failure { error: error.clone() },
+            None => RequestResult::Success { latency: connect_latency },
+        };
+        self.load_balancer
+            .record_request_result(&backend.provider, &backend.model, result)
+            .await;
+
+        let config = self.load_balancer.get_config();
+        let metrics = self.load_balancer.get_metrics();
+        let counted_model = config.models.contains_key(&model_id).then_some(model_id.as_str());
+        metrics.record_client_request(counted_model, error.is_none());
+        metrics.record_slo(error.is_none(), connect_latency);
+        if let Some(usage) = total {
+            self.load_balancer
+                .get_cost_tracker()
+                .record(&user.name, &model_id, backend, usage);
+            metrics.record_provider_tokens(&backend.provider, usage.prompt_tokens + usage.completion_tokens);
+        }
+        if let Some(settings) = &config.settings.audit_log {
+            let mut record = AuditRecord::new(settings, &user.name, &client_key, &json!({ "model": model_id }));
+            record.backend = Some(format!("{}:{}", backend.provider, backend.model));
+            record.status = axum::http::StatusCode::SWITCHING_PROTOCOLS.as_u16();
+            record.latency_ms = start_time.elapsed().as_millis() as u64;
+            record.set_usage(total);
+            record.error = error;
+            self.audit_logger.log(settings, record);
+        }
+        tracing::info!(
+            "Realtime session for '{}' on {}:{} ended after {:?}: {:?}",
+            user.name,
+            backend.provider,
+            backend.model,
+            start_time.elapsed(),
+            end
+        );
+    }
+
+    /// 文件接口的公共检查：未配置文件接口时返回错误，超出用户限流时返回429
+    async fn files_settings(
         &self,
-        model_name: &str,
-        body: &mut Value,
-        authorization: &headers::Authorization<headers::authorization::Bearer>,
-        content_type: &headers::ContentType,
-        start_time: Instant,
-    ) -> Result<axum::response::Response, anyhow::Error> {
-        let max_retries = 3; // 可以从配置中读取
-        let original_model = model_name.to_string();
+        user: &UserToken,
+    ) -> Result<(Arc<crate::config::model::Config>, FilesSettings), axum::response::Response> {
+        let config = self.load_balancer.get_config();
+        let Some(settings) = config.settings.files.clone() else {
+            return Err(RelayError::InvalidRequest {
+                message: "File endpoints are not enabled".to_string(),
+                details: Some("Configure settings.files to enable /v1/files".to_string()),
+            }
+            .into_response());
+        };
+        if let Some(limited) = self.check_rate_limit(&config, user).await {
+            return Err(limited);
+        }
+        Ok((config, settings))
+    }
 
-        for attempt in 0..max_retries {
-            // 重置模型名称为原始请求的模型名称
-            body["model"] = Value::String(original_model.clone());
+    /// 查询属于用户的文件记录，文件不存在或属于其他用户时返回404
+    async fn owned_file(
+        &self,
+        settings: &FilesSettings,
+        user: &UserToken,
+        file_id: &str,
+    ) -> Result<FileRecord, axum::response::Response> {
+        let (owner, id) = (user.name.clone(), file_id.to_string());
+        match self.files.run(&settings.database, move |store| store.get(&owner, &id)).await {
+            Ok(Some(record)) => Ok(record),
+            Ok(None) => Err(RelayError::FileNotFound(file_id.to_string()).into_response()),
+            Err(e) => Err(RelayError::Internal(format!("File store error: {:#}", e)).into_response()),
+        }
+    }
 
-            // 使用负载均衡器选择后端
-            let selected_backend = match self.load_balancer.select_backend(model_name).await {
-                Ok(backend) => backend,
+    /// 文件所在provider的客户端和请求头
+    fn file_client(
+        &self,
+        config: &crate::config::model::Config,
+        record: &FileRecord,
+    ) -> Result<(OpenAIClient, reqwest::header::HeaderMap), RelayError> {
+        let provider = config.providers.get(&record.provider).ok_or_else(|| {
+            RelayError::UpstreamFileFailed(format!("Provider '{}' is no longer configured", record.provider))
+        })?;
+        let client = self
+            .client_for_provider(&record.provider, provider)
+            .map_err(|e| RelayError::UpstreamFileFailed(e.to_string()))?;
+        let headers =
+            files::upstream_headers(provider).map_err(|e| RelayError::UpstreamFileFailed(e.to_string()))?;
+        Ok((client, headers))
+    }
+
+    /// 上传文件（/v1/files）：按配置顺序转发给支持文件接口的provider，返回berry分配的文件ID
+    #[tracing::instrument(name = "file_upload", skip_all, fields(user = %user.name))]
+    pub async fn handle_file_upload(self: Arc<Self>, user: UserToken, fields: Vec<FormField>) -> axum::response::Response {
+        let (config, settings) = match self.files_settings(&user).await {
+            Ok(result) => result,
+            Err(response) => return response,
+        };
+        let Some(file) = fields.iter().find(|f| f.name == "file") else {
+            return RelayError::InvalidRequest {
+                message: "Missing file".to_string(),
+                details: Some("The form must contain a 'file' field".to_string()),
+            }
+            .into_response();
+        };
+        let Some(purpose) = fields
+            .iter()
+            .find(|f| f.name == "purpose")
+            .and_then(|f| std::str::from_utf8(&f.data).ok())
+            .map(str::to_string)
+        else {
+            return RelayError::InvalidRequest {
+                message: "Missing purpose".to_string(),
+                details: Some("The form must contain a 'purpose' field".to_string()),
+            }
+            .into_response();
+        };
+        let filename = file.file_name.clone().unwrap_or_else(|| "file".to_string());
+        let bytes = file.data.len() as u64;
+
+        if let Some(quota) = settings.quota_bytes(&user.name) {
+            let owner = user.name.clone();
+            let used = match self.files.run(&settings.database, move |store| store.usage(&owner)).await {
+                Ok(used) => used,
+                Err(e) => return RelayError::Internal(format!("File store error: {:#}", e)).into_response(),
+            };
+            if used.saturating_add(bytes) > quota {
+                return RelayError::StorageQuotaExceeded(format!(
+                    "Uploading {} bytes would exceed the storage quota of {} bytes ({} bytes in use)",
+                    bytes, quota, used
+                ))
+                .into_response();
+            }
+        }
+
+        let mut failures = Vec::new();
+        for provider_id in &settings.providers {
+            let Some(provider) = config.providers.get(provider_id).filter(|p| p.enabled) else {
+                continue;
+            };
+            let sent = async {
+                let client = self.client_for_provider(provider_id, provider)?;
+                let headers = files::upstream_headers(provider)?;
+                Ok::<_, anyhow::Error>(client.post_multipart(FILES_PATH, headers, &fields).await?)
+            }
+            .await;
+            let response = match sent {
+                Ok(response) => response,
+                Err(e) => {
+                    tracing::warn!("File upload to provider '{}' failed: {:#}", provider_id, e);
+                    failures.push(format!("{}: {}", provider_id, e));
+                    continue;
+                }
+            };
+            let status = response.status();
+            if !status.is_success() {
+                // 请求本身的错误（如不支持的用途）换provider也不会成功，直接返回上游的错误
+                if status.is_client_error()
+                    && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+                    && status != reqwest::StatusCode::REQUEST_TIMEOUT
+                {
+                    return Self::upstream_file_response(response).await;
+                }
+                tracing::warn!("File upload to provider '{}' returned HTTP {}", provider_id, status);
+                failures.push(format!("{}: HTTP {}", provider_id, status));
+                continue;
+            }
+
+            let object = response.json::<Value>().await.unwrap_or(Value::Null);
+            let Some(provider_file_id) = object.get("id").and_then(|id| id.as_str()) else {
+                failures.push(format!("{}: response did not contain a file id", provider_id));
+                continue;
+            };
+            let record = FileRecord {
+                id: files::new_file_id(),
+                owner: user.name.clone(),
+                provider: provider_id.clone(),
+                provider_file_id: provider_file_id.to_string(),
+                filename: filename.clone(),
+                purpose: purpose.clone(),
+                bytes,
+                created_at: object
+                    .get("created_at")
+                    .and_then(|t| t.as_i64())
+                    .unwrap_or_else(|| chrono::Utc::now().timestamp()),
+                object: object.clone(),
+            };
+            let stored = record.clone();
+            if let Err(e) = self.files.run(&settings.database, move |store| store.insert(&stored)).await {
+                return RelayError::Internal(format!("File store error: {:#}", e)).into_response();
+            }
+            tracing::info!(
+                "User '{}' uploaded file {} ({} bytes) to provider '{}' as {}",
+                user.name, record.id, bytes, provider_id, record.provider_file_id
+            );
+            return Json(record.to_openai()).into_response();
+        }
+
+        if failures.is_empty() {
+            failures.push("No enabled provider accepts file uploads".to_string());
+        }
+        RelayError::UpstreamFileFailed(failures.join("; ")).into_response()
+    }
+
+    /// 列出用户上传的文件（/v1/files），只从本地记录读取
+    pub async fn handle_file_list(
+        self: Arc<Self>,
+        user: UserToken,
+        purpose: Option<String>,
+        limit: Option<usize>,
+    ) -> axum::response::Response {
+        let (_, settings) = match self.files_settings(&user).await {
+            Ok(result) => result,
+            Err(response) => return response,
+        };
+        let owner = user.name.clone();
+        let limit = limit.unwrap_or(10_000).clamp(1, 10_000);
+        let listed = self
+            .files
+            .run(&settings.database, move |store| store.list(&owner, purpose.as_deref(), limit))
+            .await;
+        match listed {
+            Ok(records) => Json(json!({
+                "object": "list",
+                "data": records.iter().map(FileRecord::to_openai).collect::<Vec<_>>(),
+                "has_more": false,
+            }))
+            .into_response(),
+            Err(e) => RelayError::Internal(format!("File store error: {:#}", e)).into_response(),
+        }
+    }
+
+    /// 查询文件（/v1/files/{id}），从provider获取最新状态，provider不可用时返回本地记录
+    pub async fn handle_file_retrieve(self: Arc<Self>, user: UserToken, file_id: String) -> axum::response::Response {
+        let (config, settings) = match self.files_settings(&user).await {
+            Ok(result) => result,
+            Err(response) => return response,
+        };
+        let mut record = match self.owned_file(&settings, &user, &file_id).await {
+            Ok(record) => record,
+            Err(response) => return response,
+        };
+
+        let path = format!("{}/{}", FILES_PATH, record.provider_file_id);
+        let refreshed = match self.file_client(&config, &record) {
+            Ok((client, headers)) => match client.get(&path, headers).await {
+                Ok(response) if response.status().is_success() => response.json::<Value>().await.ok(),
+                Ok(response) => {
+                    tracing::warn!("Provider '{}' returned HTTP {} for file {}", record.provider, response.status(), file_id);
+                    None
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to refresh file {} from provider '{}': {}", file_id, record.provider, e);
+                    None
+                }
+            },
+            Err(e) => {
+                tracing::warn!("Failed to refresh file {}: {}", file_id, e.details().unwrap_or_default());
+                None
+            }
+        };
+        if let Some(object) = refreshed.filter(|object| object.is_object()) {
+            let (id, stored) = (record.id.clone(), object.clone());
+            if let Err(e) = self.files.run(&settings.database, move |store| store.update_object(&id, &stored)).await {
+                tracing::warn!("Failed to update file {}: {:#}", file_id, e);
+            }
+            record.object = object;
+        }
+        Json(record.to_openai()).into_response()
+    }
+
+    /// 下载文件内容（/v1/files/{id}/content），边接收边转发
+    pub async fn handle_file_content(self: Arc<Self>, user: UserToken, file_id: String) -> axum::response::Response {
+        let (config, settings) = match self.files_settings(&user).await {
+            Ok(result) => result,
+            Err(response) => return response,
+        };
+        let record = match self.owned_file(&settings, &user, &file_id).await {
+            Ok(record) => record,
+            Err(response) => return response,
+        };
+        let (client, headers) = match self.file_client(&config, &record) {
+            Ok(result) => result,
+            Err(e) => return e.into_response(),
+        };
+        let path = format!("{}/{}/content", FILES_PATH, record.provider_file_id);
+        match client.get(&path, headers).await {
+            Ok(response) => Self::upstream_file_response(response).await,
+            Err(e) => RelayError::UpstreamFileFailed(e.to_string()).into_response(),
+        }
+    }
+
+    /// 删除文件（/v1/files/{id}），provider上已不存在的文件也删除本地记录
+    pub async fn handle_file_delete(self: Arc<Self>, user: UserToken, file_id: String) -> axum::response::Response {
+        let (config, settings) = match self.files_settings(&user).await {
+            Ok(result) => result,
+            Err(response) => return response,
+        };
+        let record = match self.owned_file(&settings, &user, &file_id).await {
+            Ok(record) => record,
+            Err(response) => return response,
+        };
+        let (client, headers) = match self.file_client(&config, &record) {
+            Ok(result) => result,
+            Err(e) => return e.into_response(),
+        };
+        let path = format!("{}/{}", FILES_PATH, record.provider_file_id);
+        match client.delete(&path, headers).await {
+            Ok(response) if response.status().is_success() || response.status() == reqwest::StatusCode::NOT_FOUND => {}
+            Ok(response) => {
+                return RelayError::UpstreamFileFailed(format!(
+                    "Provider '{}' returned HTTP {}",
+                    record.provider,
+                    response.status()
+                ))
+                .into_response();
+            }
+            Err(e) => return RelayError::UpstreamFileFailed(e.to_string()).into_response(),
+        }
+
+        let id = record.id.clone();
+        if let Err(e) = self.files.run(&settings.database, move |store| store.delete(&id)).await {
+            return RelayError::Internal(format!("File store error: {:#}", e)).into_response();
+        }
+        tracing::info!("User '{}' deleted file {}", user.name, record.id);
+        Json(json!({ "id": record.id, "object": "file", "deleted": true })).into_response()
+    }
+
+    /// 原样返回provider文件接口的响应（状态码、内容类型和响应体）
+    async fn upstream_file_response(response: reqwest::Response) -> axum::response::Response {
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .cloned()
+            .unwrap_or_else(|| reqwest::header::HeaderValue::from_static("application/octet-stream"));
+        let mut builder = axum::response::Response::builder()
+            .status(status.as_u16())
+            .header("Content-Type", content_type);
+        if let Some(disposition) = response.headers().get(reqwest::header::CONTENT_DISPOSITION) {
+            builder = builder.header("Content-Disposition", disposition.clone());
+        }
+        builder
+            .body(axum::body::Body::from_stream(response.bytes_stream()))
+            .unwrap_or_else(|e| RelayError::Internal(e.to_string()).into_response())
+    }
+
+    /// 透传接口的公共入口：处理请求后统计花费并写出审计日志
+    async fn relay_passthrough(
+        &self,
+        endpoint: &'static str,
+        user: UserToken,
+        authorization: headers::Authorization<headers::authorization::Bearer>,
+        request_headers: axum::http::HeaderMap,
+        body: PassthroughBody,
+    ) -> axum::response::Response {
+        let start_time = Instant::now();
+        crate::telemetry::continue_trace_from(&request_headers);
+        let debug_trace = match Self::debug_trace(&user, &request_headers) {
+            Ok(trace) => trace,
+            Err(e) => return e.into_response(),
+        };
+
+        // multipart表单只记录模型名称
+        let model_only;
+        let summary = match &body {
+            PassthroughBody::Json(value) => value,
+            PassthroughBody::Multipart(_) => {
+                model_only = json!({ "model": body.model() });
+                &model_only
+            }
+        };
+        let config = self.load_balancer.get_config();
+        let model_id = Self::resolve_model_id(&config, summary);
+        let cost = estimate_request_tokens(summary);
+        let filters = self.request_filters(&config, &user, &model_id);
+        let audit = config.settings.audit_log.clone().map(|settings| {
+            let logged = filters.redact_for_log(summary);
+            let record = AuditRecord::new(&settings, &user.name, authorization.token(), logged.as_ref().unwrap_or(summary));
+            (settings, record)
+        });
+        // multipart表单无法回放，不抓取
+        let capture = match &body {
+            PassthroughBody::Json(value) => {
+                Self::start_capture(&config, &user, authorization.token(), endpoint, &request_headers, &filters, value)
+            }
+            PassthroughBody::Multipart(_) => None,
+        };
+        let mut body = body;
+        if let PassthroughBody::Json(value) = &mut body {
+            filters.upstream.redact_request(value);
+        }
+
+        let mut response = if let Some(limited) = self.check_rate_limit(&config, &user).await {
+            limited
+        } else if let (RESPONSES_PATH, Some(limits), PassthroughBody::Json(value)) = (endpoint, &user.limits, &mut body)
+            && let Err(e) = user_limits::apply_max_tokens(limits, user_limits::RESPONSE_TOKEN_PARAMS, value)
+        {
+            e.into_response()
+        } else {
+            match self.admit(&config, &user, &model_id, cost).await {
+                Ok(admission) => {
+                    let response = self.process_passthrough(
+                        endpoint,
+                        &user,
+                        authorization.token(),
+                        &request_headers,
+                        body,
+                        start_time,
+                    );
+                    let response = DebugTrace::scope(debug_trace.clone(), Box::pin(response));
+                    let response = user_limits::with_max_duration(user.limits.as_ref(), start_time, response).await;
+                    Self::release_on_complete(response, admission)
+                }
+                Err(e) => RelayError::from(e).into_response(),
+            }
+        };
+        if let Some(trace) = debug_trace {
+            response = trace.attach(response).await;
+        }
+        let response = self.capture_response(capture, response, start_time);
+        self.finish_response(response, user, model_id, audit, start_time)
+    }
+
+    /// 请求体中模型对应的模型ID，未配置时原样返回模型名称
+    fn resolve_model_id(config: &crate::config::model::Config, body: &Value) -> String {
+        body.get("model")
+            .and_then(|m| m.as_str())
+            .map(|name| {
+                config
+                    .resolve_model(name)
+                    .map(|(id, _)| id.clone())
+                    .unwrap_or_else(|| name.to_string())
+            })
+            .unwrap_or_default()
+    }
+
+    /// 超出用户限流时返回429响应
+    async fn check_rate_limit(
+        &self,
+        config: &crate::config::model::Config,
+        user: &UserToken,
+    ) -> Option<axum::response::Response> {
+        let limit = user.rate_limit.as_ref()?;
+        let exceeded = self
+            .rate_limiter
+            .check_request(config.settings.shared_state.as_ref(), &user.name, limit)
+            .await
+            .err()?;
+
+        tracing::warn!("Rejecting request from user '{}': {}", user.name, exceeded);
+        Some(RelayError::RateLimited(exceeded.to_string()).into_response())
+    }
+
+    /// 准入控制：全局或模型的在途请求数已达上限（或超出用户优先级可用的份额）时拒绝请求
+    /// 模型配置了公平排队时，模型名额已满的请求按用户轮流排队等待，cost为请求的估算token数；
+    /// 用户配置了并发上限时先占用用户的在途请求名额
+    async fn admit(
+        &self,
+        config: &crate::config::model::Config,
+        user: &UserToken,
+        model_id: &str,
+        cost: u64,
+    ) -> Result<AdmissionGuard, AdmissionError> {
+        let user_limit = user.limits.as_ref().and_then(|limits| limits.concurrency.as_ref());
+        let user_permit = match user_limit {
+            Some(limit) => Some(
+                self.concurrency
+                    .acquire_user(&user.name, limit)
+                    .await
+                    .map_err(AdmissionError::UserConcurrency)
+                    .inspect_err(|e| tracing::warn!("Rejecting request from user '{}': {}", user.name, e))?,
+            ),
+            None => None,
+        };
+        let mapping = config.models.get(model_id);
+        let model_limit = mapping.and_then(|mapping| mapping.max_in_flight_requests);
+        let fair_queue = mapping.and_then(|mapping| mapping.fair_queue.as_ref()).zip(model_limit);
+        let fair_permit = match fair_queue {
+            Some((settings, limit)) => Some(
+                self.fair_scheduler
+                    .acquire(model_id, limit, &user.name, cost, settings)
+                    .await
+                    .inspect_err(|e| tracing::warn!("Rejecting request from user '{}': {}", user.name, e))?,
+            ),
+            None => None,
+        };
+        // 公平排队已经限制了模型的在途请求数
+        let model_limit = model_limit.filter(|_| fair_permit.is_none());
+        self.admission
+            .try_admit(
+                config.settings.max_in_flight_requests,
+                model_id,
+                model_limit,
+                user.priority,
+                config.settings.priority_scheduling.as_ref(),
+            )
+            .map(|guard| guard.with_fair_permit(fair_permit).with_user_permit(user_permit))
+            .inspect_err(|e| {
+                tracing::warn!(
+                    "Shedding {} priority request for model '{}': {}",
+                    user.priority,
+                    model_id,
+                    e
+                )
+            })
+    }
+
+    /// 响应体传输结束或连接断开时释放准入计数
+    fn release_on_complete(
+        response: axum::response::Response,
+        admission: AdmissionGuard,
+    ) -> axum::response::Response {
+        let (parts, body) = response.into_parts();
+        let stream = body.into_data_stream().map(move |chunk| {
+            let _ = &admission;
+            chunk
+        });
+        axum::response::Response::from_parts(parts, axum::body::Body::from_stream(stream))
+    }
+
+    /// 超出用户或模型预算时返回402响应
+    fn check_budget(
+        &self,
+        config: &crate::config::model::Config,
+        user: &UserToken,
+        model_name: &str,
+    ) -> Option<axum::response::Response> {
+        let resolved_model = config.resolve_model(model_name);
+        let model_id = resolved_model
+            .map(|(id, _)| id.as_str())
+            .unwrap_or(model_name);
+        let exceeded = self
+            .load_balancer
+            .get_cost_tracker()
+            .check_budget(user, model_id, resolved_model.map(|(_, mapping)| mapping))
+            .err()?;
+
+        tracing::warn!("Rejecting request for model '{}': {}", model_name, exceeded);
+        Some(RelayError::BudgetExceeded(exceeded.to_string()).into_response())
+    }
+
+    /// 模型有后端配置了max_context时估算请求的上下文长度，没有任何启用的后端放得下时拒绝请求
+    fn estimate_context_tokens(
+        resolved_model: Option<(&String, &ModelMapping)>,
+        body: &Value,
+    ) -> Result<Option<u64>, RelayError> {
+        let Some((model_id, mapping)) = resolved_model else {
+            return Ok(None);
+        };
+        if mapping.backends.iter().all(|b| b.max_context.is_none()) {
+            return Ok(None);
+        }
+        let tokens = estimate_request_tokens(body);
+        let largest = mapping
+            .backends
+            .iter()
+            .filter(|b| b.enabled)
+            .map(|b| b.max_context.unwrap_or(u64::MAX))
+            .max();
+        if let Some(largest) = largest
+            && tokens > largest
+        {
+            return Err(RelayError::ContextLengthExceeded {
+                model: model_id.clone(),
+                details: format!(
+                    "Request needs about {} tokens, the largest context window is {} tokens",
+                    tokens, largest
+                ),
+            });
+        }
+        Ok(Some(tokens))
+    }
+
+    /// 处理聊天完成和文本补全请求的主体逻辑：预算、缓存、对等转发和带重试的后端请求
+    async fn process_completions(
+        &self,
+        request: &RelayRequest<'_>,
+        mut body: Value,
+        experiment: Option<&ExperimentAssignment>,
+    ) -> axum::response::Response {
+        let RelayRequest {
+            endpoint,
+            user,
+            authorization,
+            content_type,
+            headers: request_headers,
+            ..
+        } = *request;
+        let mut selection_context =
+            match Self::build_selection_context(user, authorization.token(), request_headers, &body) {
+                Ok(context) => context,
+                Err(e) => return e.into_response(),
+            };
+        // 实验请求只路由到所在分组的后端
+        if let Some(assignment) = experiment {
+            selection_context.tags.extend(assignment.tags.iter().cloned());
+        }
+
+        // 从请求体中提取模型名称
+        let model_name = match body.get("model").and_then(|m| m.as_str()) {
+            Some(name) => name.to_string(),
+            None => {
+                tracing::error!("Missing model field in request");
+                return create_error_response(
+                    ErrorType::BadRequest,
+                    "Missing model field in request",
+                    Some("The 'model' field is required in the request body".to_string()),
+                ).into_response();
+            }
+        };
+
+        let span = tracing::Span::current();
+        span.record("model", model_name.as_str());
+        span.record(
+            "stream",
+            body.get("stream").and_then(|s| s.as_bool()).unwrap_or(false),
+        );
+
+        let config = self.load_balancer.get_config();
+        let resolved_model = config.resolve_model(&model_name);
+
+        // 超出用户或模型预算时拒绝请求
+        if let Some(response) = self.check_budget(&config, user, &model_name) {
+            return response;
+        }
+
+        // 只把请求发往上下文长度足够的后端
+        match Self::estimate_context_tokens(resolved_model, &body) {
+            Ok(tokens) => selection_context.context_tokens = tokens,
+            Err(e) => return e.into_response(),
+        }
+
+        // 响应缓存、语义缓存和对等转发只用于聊天完成接口
+        let is_chat = endpoint == CHAT_COMPLETIONS_PATH;
+        // 指定了后端的调试请求和实验请求必须真正发往后端，不使用缓存
+        let use_cache = is_chat && selection_context.pinned_backend.is_none() && experiment.is_none();
+
+        // 确定性请求优先查询响应缓存
+        let cache_key = ResponseCache::cache_key(&config.settings, &body).filter(|_| use_cache);
+        if let Some(key) = &cache_key
+            && let Some(cached) = self.response_cache.get(&config.settings, key)
+        {
+            tracing::debug!("Serving cached response for model '{}'", model_name);
+            return Self::cached_response(cached, "hit");
+        }
+
+        // 精确缓存未命中时查询语义缓存，embedding后端出错不影响正常请求
+        let semantic_lookup = if use_cache {
+            self.semantic_cache
+                .lookup(&config, self.load_balancer.get_metrics(), &model_name, &body)
+                .await
+        } else {
+            Ok(SemanticLookup::Skip)
+        };
+        let semantic_miss = match semantic_lookup {
+            Ok(SemanticLookup::Hit(cached)) => return Self::cached_response(cached, "semantic-hit"),
+            Ok(SemanticLookup::Miss { partition, embedding }) => Some((partition, embedding)),
+            Ok(SemanticLookup::Skip) => None,
+            Err(e) => {
+                tracing::warn!("Semantic cache lookup failed for model '{}': {}", model_name, e);
+                None
+            }
+        };
+
+        // 本地没有该模型时转发给对等实例，跳数请求头防止实例间循环转发
+        let hops = PeerForwarder::incoming_hops(request_headers);
+        if is_chat
+            && !Self::serves_model_locally(&config, &model_name)
+            && self.peer_forwarder.can_forward(&config, &model_name, hops)
+        {
+            if let Some(response) = self
+                .peer_forwarder
+                .forward_chat_completions(&config, &model_name, request_headers, &body, hops)
+                .await
+            {
+                return response;
+            }
+            return RelayError::NoHealthyBackends {
+                model: model_name,
+                details: "All peers serving this model are currently unavailable".to_string(),
+            }
+            .into_response();
+        }
+        if resolved_model.is_none() {
+            return RelayError::ModelNotFound(model_name).into_response();
+        }
+
+        // 按配置比例把请求异步复制到镜像后端
+        if let Some((_, mapping)) = resolved_model
+            && let Some(mirror) = &mapping.mirror
+        {
+            self.mirror_request(endpoint, mapping, mirror, &body, authorization, content_type);
+        }
+
+        // 配置了降级模型且所有后端都不健康时直接降级，否则尝试处理请求，带内部重试机制
+        let metrics = self.load_balancer.get_metrics();
+        let skip_to_fallback = resolved_model.is_some_and(|(_, mapping)| {
+            !mapping.fallback_models.is_empty()
+                && !mapping
+                    .backends
+                    .iter()
+                    .any(|b| b.enabled && metrics.is_healthy(&b.provider, &b.model))
+        });
+        let result = if skip_to_fallback {
+            Err(RelayError::NoHealthyBackends {
+                model: model_name.clone(),
+                details: "All backends are currently unhealthy, trying fallback models".to_string(),
+            })
+        } else {
+            self.try_handle_with_retries(request, &selection_context, &model_name, &mut body)
+                .await
+        };
+
+        match result {
+            Ok(response) => {
+                if cache_key.is_none() && semantic_miss.is_none() {
+                    return response;
+                }
+
+                let response_cache = self.response_cache.clone();
+                let semantic_cache = self.semantic_cache.clone();
+                let settings = config.settings.clone();
+                Self::store_on_complete(response, move |text| {
+                    if let Some(key) = cache_key {
+                        response_cache.insert(&settings, key, text.to_string());
+                    }
+                    if let (Some((partition, embedding)), Some(semantic)) =
+                        (semantic_miss, &settings.semantic_cache)
+                    {
+                        semantic_cache.insert(semantic, partition, embedding, text.to_string());
+                    }
+                })
+            }
+            Err(e) => {
+                tracing::error!(
+                    "All retry attempts failed for model '{}': {}",
+                    model_name,
+                    e
+                );
+
+                // 本地后端全部失败时，尝试由对等实例兜底
+                body["model"] = Value::String(model_name.clone());
+                if is_chat
+                    && let Some(response) = self
+                        .peer_forwarder
+                        .forward_chat_completions(&config, &model_name, request_headers, &body, hops)
+                        .await
+                {
+                    return response;
+                }
+
+                // 按配置依次降级到其他模型，响应头标明实际使用的模型
+                if let Some((_, mapping)) = resolved_model {
+                    for fallback in &mapping.fallback_models {
+                        let Some((_, fallback_mapping)) = config.resolve_model(fallback) else {
+                            continue;
+                        };
+                        let fallback_name = fallback_mapping.name.clone();
+                        if !config.user_can_access_model(user, &fallback_name) {
+                            tracing::debug!(
+                                "Skipping fallback model '{}' for user '{}': not enabled or not allowed",
+                                fallback_name,
+                                user.name
+                            );
+                            continue;
+                        }
+
+                        tracing::warn!("Falling back from model '{}' to '{}'", model_name, fallback_name);
+                        match self
+                            .try_handle_with_retries(request, &selection_context, &fallback_name, &mut body)
+                            .await
+                        {
+                            Ok(mut response) => {
+                                if let Ok(value) = axum::http::HeaderValue::from_str(&fallback_name) {
+                                    response.headers_mut().insert(FALLBACK_MODEL_HEADER, value);
+                                }
+                                return response;
+                            }
+                            Err(fallback_error) => {
+                                tracing::warn!(
+                                    "Fallback model '{}' also failed: {}",
+                                    fallback_name,
+                                    fallback_error
+                                );
+                            }
+                        }
+                    }
+                }
+
+                e.into_response()
+            }
+        }
+    }
+
+    /// 处理透传请求的主体逻辑：必填字段、embeddings批量上限、预算和带重试的后端请求
+    async fn process_passthrough(
+        &self,
+        endpoint: &'static str,
+        user: &UserToken,
+        client_key: &str,
+        request_headers: &axum::http::HeaderMap,
+        mut body: PassthroughBody,
+        start_time: Instant,
+    ) -> axum::response::Response {
+        let Some(model_name) = body.model().map(str::to_string) else {
+            return create_error_response(
+                ErrorType::BadRequest,
+                "Missing model field in request",
+                Some("The 'model' field is required in the request body".to_string()),
+            )
+            .into_response();
+        };
+        tracing::Span::current().record("model", model_name.as_str());
+
+        let required_field = if endpoint == AUDIO_TRANSCRIPTIONS_PATH { "file" } else { "input" };
+        if !body.has_field(required_field) {
+            return create_error_response(
+                ErrorType::BadRequest,
+                &format!("Missing {} field in request", required_field),
+                Some(format!("The '{}' field is required in the request body", required_field)),
+            )
+            .into_response();
+        }
+
+        let config = self.load_balancer.get_config();
+        if endpoint == EMBEDDINGS_PATH
+            && let PassthroughBody::Json(value) = &body
+        {
+            let batch_size = Self::embedding_batch_size(&value["input"]);
+            if batch_size > config.settings.max_embedding_batch_size {
+                return create_error_response(
+                    ErrorType::BadRequest,
+                    "Too many embedding inputs",
+                    Some(format!(
+                        "Request contains {} inputs, the maximum is {}",
+                        batch_size, config.settings.max_embedding_batch_size
+                    )),
+                )
+                .into_response();
+            }
+        }
+
+        if let Some(response) = self.check_budget(&config, user, &model_name) {
+            return response;
+        }
+
+        let json_body = match &body {
+            PassthroughBody::Json(value) => value,
+            PassthroughBody::Multipart(_) => &Value::Null,
+        };
+        let selection_context =
+            match Self::build_selection_context(user, client_key, request_headers, json_body) {
+                Ok(context) => context,
+                Err(e) => return e.into_response(),
+            };
+        let retry_policy = config
+            .resolve_model(&model_name)
+            .map(|(_, mapping)| mapping.retry_policy())
+            .unwrap_or_default();
+        let max_retries = retry_policy.max_attempts as usize;
+        let mut last_error: Option<RelayError> = None;
+
+        for attempt in 0..max_retries {
+            if attempt == 0 {
+                debug::record_selection(|| self.load_balancer.explain_selection(&model_name, &selection_context));
+            }
+            let selected_backend = match self
+                .load_balancer
+                .select_backend_with_context(&model_name, &selection_context)
+                .await
+            {
+                Ok(backend) => backend,
+                Err(e) => {
+                    debug::record_attempt(&model_name, None, format!("selection failed: {}", e));
+                    tracing::warn!(
+                        "Backend selection for {} failed on attempt {}: {}",
+                        endpoint,
+                        attempt + 1,
+                        e
+                    );
+                    last_error = Some(RelayError::NoHealthyBackends {
+                        model: model_name.clone(),
+                        details: format!("Backend selection failed. Details: {}", e),
+                    });
+                    continue;
+                }
+            };
+
+            body.set_model(&selected_backend.backend.model);
+            // 后端并发名额不足时换一个后端重试，不计为后端失败
+            let _permit = match self.concurrency.acquire(&selected_backend).await {
+                Ok(permit) => permit,
+                Err(e) => {
+                    debug::record_attempt(&model_name, Some(&selected_backend.label()), e.to_string());
+                    tracing::warn!(
+                        "Request to {} skipped backend on attempt {}: {}",
+                        endpoint,
+                        attempt + 1,
+                        e
+                    );
+                    last_error = Some(RelayError::BackendsAtCapacity {
+                        model: model_name.clone(),
+                        details: format!("Too many concurrent requests, please try again later. Details: {}", e),
+                    });
+                    continue;
+                }
+            };
+            match self
+                .send_passthrough(endpoint, &selected_backend, &body, attempt, start_time)
+                .await
+            {
+                Ok(response) => {
+                    debug::record_attempt(&model_name, Some(&selected_backend.label()), "success");
+                    return response;
+                }
+                Err(e) => {
+                    debug::record_attempt(&model_name, Some(&selected_backend.label()), e.to_string());
+                    let result = match e.downcast_ref::<UpstreamError>() {
+                        Some(upstream) => upstream.request_result(),
+                        None => RequestResult::Failure {
+                            error: e.to_string(),
+                        },
+                    };
+                    self.load_balancer
+                        .record_request_result(
+                            &selected_backend.backend.provider,
+                            &selected_backend.backend.model,
+                            result,
+                        )
+                        .await;
+                    tracing::warn!(
+                        "Request to {} failed on attempt {}: {}",
+                        endpoint,
+                        attempt + 1,
+                        e
+                    );
+                    let details = format!("Request failed after {} attempts. Details: {}", attempt + 1, e);
+                    // 上游请求本身的失败按原因分类，其他错误来自后端配置
+                    last_error = Some(match e.downcast_ref::<UpstreamError>() {
+                        Some(upstream) => RelayError::from_upstream(&model_name, upstream, details),
+                        None => RelayError::Configuration {
+                            model: model_name.clone(),
+                            details,
+                        },
+                    });
+                    if e
+                        .downcast_ref::<UpstreamError>()
+                        .is_some_and(|upstream| !upstream.is_retryable(&retry_policy))
+                    {
+                        break;
+                    }
+                    if attempt + 1 < max_retries {
+                        tokio::time::sleep(retry_policy.backoff(attempt as u32 + 1)).await;
+                    }
+                }
+            }
+        }
+
+        last_error
+            .unwrap_or_else(|| RelayError::Internal("Unexpected end of retry loop".to_string()))
+            .into_response()
+    }
+
+    /// embeddings请求中的输入条数：字符串数组或token数组的数组按元素计数，单个字符串或token数组计为1
+    fn embedding_batch_size(input: &Value) -> usize {
+        match input.as_array() {
+            Some(items) if items.iter().all(|item| item.is_number()) => 1,
+            Some(items) => items.len(),
+            None => 1,
+        }
+    }
+
+    /// 向选中的后端发送一次透传请求
+    /// 上游5xx和429视为后端失败以触发重试，其他客户端错误原样返回
+    async fn send_passthrough(
+        &self,
+        endpoint: &'static str,
+        selected_backend: &crate::loadbalance::SelectedBackend,
+        body: &PassthroughBody,
+        attempt: usize,
+        start_time: Instant,
+    ) -> Result<axum::response::Response, anyhow::Error> {
+        let backend = &selected_backend.backend;
+        let api_key = selected_backend.get_api_key()?;
+        let client = self.client_for(selected_backend)?;
+
+        let upstream_span = tracing::info_span!(
+            "upstream_request",
+            attempt = attempt + 1,
+            provider = %backend.provider,
+            model = %backend.model,
+            error = tracing::field::Empty,
+        );
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            format!("Bearer {}", api_key).parse()?,
+        );
+        for (key, value) in selected_backend.get_headers() {
+            if let (Ok(header_name), Ok(header_value)) = (
+                key.parse::<reqwest::header::HeaderName>(),
+                value.parse::<reqwest::header::HeaderValue>(),
+            ) {
+                headers.insert(header_name, header_value);
+            }
+        }
+        upstream_span.in_scope(|| crate::telemetry::inject_trace_context(&mut headers));
+        crate::telemetry::inject_request_id(&mut headers);
+
+        let _in_flight = self.begin_upstream_request(backend);
+        let result = async {
+            let response = match body {
+                PassthroughBody::Json(value) => client
+                    .post_json(endpoint, headers, value)
+                    .await
+                    .map_err(UpstreamError::from)?,
+                PassthroughBody::Multipart(fields) => client
+                    .post_multipart(endpoint, headers, fields)
+                    .await
+                    .map_err(UpstreamError::from)?,
+            };
+            let status = response.status();
+            if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Err(anyhow::Error::from(UpstreamError::from_response(status, response.headers())));
+            }
+            let content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .cloned()
+                .unwrap_or_else(|| reqwest::header::HeaderValue::from_static("application/json"));
+            // 合成的音频和流式响应边接收边转发，其他接口读取完整响应体
+            let is_stream = content_type.to_str().is_ok_and(|v| v.starts_with("text/event-stream"));
+            let body = if (endpoint == AUDIO_SPEECH_PATH || is_stream) && status.is_success() {
+                axum::body::Body::from_stream(response.bytes_stream())
+            } else {
+                axum::body::Body::from(response.bytes().await.map_err(UpstreamError::from)?)
+            };
+            Ok((status, content_type, body))
+        }
+        .instrument(upstream_span.clone())
+        .await;
+
+        let (status, content_type, body) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                upstream_span.record("error", e.to_string().as_str());
+                return Err(e);
+            }
+        };
+
+        if status.is_success() {
+            self.load_balancer
+                .record_request_result(
+                    &backend.provider,
+                    &backend.model,
+                    RequestResult::Success {
+                        latency: start_time.elapsed(),
+                    },
+                )
+                .await;
+        }
+
+        let mut response = axum::response::Response::builder()
+            .status(status.as_u16())
+            .header("Content-Type", content_type)
+            .body(body)?;
+        if status.is_success() {
+            response.extensions_mut().insert(ServedBackend(backend.clone()));
+        }
+        Ok(response)
+    }
+
+    /// 在响应体传输过程中解析上游返回的usage，传输完成后记录花费并写出审计日志和请求记录
+    /// 流式响应从SSE数据行中读取（需要上游在最后一个分片中返回usage），非流式响应读取完整JSON
+    fn finish_response(
+        &self,
+        response: axum::response::Response,
+        user: UserToken,
+        model_id: String,
+        audit: Option<(AuditLogSettings, AuditRecord)>,
+        start_time: Instant,
+    ) -> axum::response::Response {
+        let served = response.extensions().get::<ServedBackend>().cloned();
+        let experiment = response.extensions().get::<ExperimentAssignment>().cloned();
+        let status = response.status().as_u16();
+        let metrics = self.load_balancer.get_metrics();
+        // 只按已配置的模型分别计数，避免客户端传入任意模型名称
+        let counted_model = self
+            .load_balancer
+            .get_config()
+            .models
+            .contains_key(&model_id)
+            .then(|| model_id.clone());
+        let request_log = self
+            .load_balancer
+            .get_config()
+            .settings
+            .request_log
+            .clone()
+            .map(|settings| (settings, RequestLogRecord::new(&user.name, &model_id)));
+        if served.is_none() && audit.is_none() && experiment.is_none() && request_log.is_none() {
+            metrics.record_client_request(counted_model.as_deref(), status < 400);
+            metrics.record_slo(status < 500, start_time.elapsed());
+            return response;
+        }
+        let is_sse = response
+            .headers()
+            .get(axum::http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        // 音频等二进制响应中没有usage，不缓存响应体
+        let is_binary = response
+            .headers()
+            .get(axum::http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("audio/") || v.starts_with("application/octet-stream"));
+        let cost_tracker = self.load_balancer.get_cost_tracker();
+        let audit_logger = self.audit_logger.clone();
+        let request_logger = self.request_logger.clone();
+        let rate_limiter = self.rate_limiter.clone();
+        let shared_state = self.load_balancer.get_config().settings.shared_state.clone();
+        let user_name = user.name;
+        let rate_limit = user.rate_limit;
+        let experiments = self.load_balancer.get_experiment_tracker();
+        let events = self.load_balancer.get_events();
+        let finish = move |usage: Option<TokenUsage>, error: Option<String>| {
+            metrics.record_client_request(counted_model.as_deref(), status < 400 && error.is_none());
+            metrics.record_slo(status < 500 && error.is_none(), start_time.elapsed());
+            if let Some(assignment) = &experiment {
+                let failed = status >= 400 || error.is_some();
+                experiments.record(&model_id, assignment, start_time.elapsed(), failed, usage);
+            }
+            if let (Some(ServedBackend(backend)), Some(usage)) = (&served, usage) {
+                let cost = cost_tracker.record(&user_name, &model_id, backend, usage);
+                metrics.record_provider_tokens(&backend.provider, usage.prompt_tokens + usage.completion_tokens);
+                if let Some(alert) = metrics.record_key_usage(&backend.provider, usage, cost) {
+                    if alert.exhausted {
+                        tracing::error!(
+                            "ALERT: api key {} of provider '{}' reached its monthly budget, backends of this provider are excluded until next month",
+                            alert.key_fingerprint,
+                            alert.provider
+                        );
+                    } else {
+                        tracing::warn!(
+                            "Api key {} of provider '{}' has used {:.1}% of its monthly budget",
+                            alert.key_fingerprint,
+                            alert.provider,
+                            alert.used_percent
+                        );
+                    }
+                    events.publish("key_budget_alert", json!(alert));
+                }
+            }
+            // 按实际用量扣除用户的token限流额度
+            if let (Some(limit), Some(usage)) = (rate_limit, usage)
+                && limit.tokens_per_minute.is_some()
+            {
+                let user_name = user_name.clone();
+                tokio::spawn(async move {
+                    let tokens = usage.prompt_tokens + usage.completion_tokens;
+                    rate_limiter
+                        .record_tokens(shared_state.as_ref(), &user_name, &limit, tokens)
+                        .await;
+                });
+            }
+            if let Some((settings, mut record)) = audit {
+                record.backend = served
+                    .as_ref()
+                    .map(|ServedBackend(b)| format!("{}:{}", b.provider, b.model));
+                record.status = status;
+                record.latency_ms = start_time.elapsed().as_millis() as u64;
+                record.set_usage(usage);
+                record.error = error.clone();
+                audit_logger.log(&settings, record);
+            }
+            if let Some((settings, mut record)) = request_log {
+                record.backend = served
+                    .as_ref()
+                    .map(|ServedBackend(b)| format!("{}:{}", b.provider, b.model));
+                record.status = status;
+                record.latency_ms = start_time.elapsed().as_millis() as u64;
+                record.set_usage(usage);
+                record.error = error;
+                request_logger.log(&settings, record);
+            }
+        };
+
+        let (parts, body) = response.into_parts();
+        let stream = futures::stream::unfold(
+            (body.into_data_stream(), Vec::new(), None, None, Some(finish)),
+            move |(mut stream, mut buffer, mut usage, mut error, mut finish)| async move {
+                match stream.next().await {
+                    Some(Ok(chunk)) => {
+                        if !is_binary {
+                            buffer.extend_from_slice(&chunk);
+                        }
+                        if is_sse {
+                            // 逐行解析，只保留未完整的最后一行
+                            while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                                let line: Vec<u8> = buffer.drain(..=pos).collect();
+                                if let Some(data) = std::str::from_utf8(&line)
+                                    .ok()
+                                    .and_then(|l| l.trim().strip_prefix("data:"))
+                                    && let Ok(value) = serde_json::from_str::<Value>(data.trim())
+                                {
+                                    // Responses接口的流式用量在response.completed事件的response.usage中
+                                    if let Some(parsed) = value
+                                        .get("usage")
+                                        .or_else(|| value.pointer("/response/usage"))
+                                        .and_then(TokenUsage::from_json)
+                                    {
+                                        usage = Some(parsed);
+                                    }
+                                    if let Some(message) = Self::error_message(&value) {
+                                        error = Some(message);
+                                    }
+                                }
+                            }
+                        }
+                        Some((Ok(chunk), (stream, buffer, usage, error, finish)))
+                    }
+                    Some(Err(e)) => {
+                        error = Some(e.to_string());
+                        Some((Err(e), (stream, buffer, usage, error, finish)))
+                    }
+                    None => {
+                        if !is_sse
+                            && let Some(value) = std::str::from_utf8(&buffer)
+                                .ok()
+                                .and_then(|text| serde_json::from_str::<Value>(text.trim()).ok())
+                        {
+                            usage = value.get("usage").and_then(TokenUsage::from_json);
+                            error = error.or_else(|| Self::error_message(&value));
+                        }
+                        if let Some(finish) = finish.take() {
+                            finish(usage, error);
+                        }
+                        None
+                    }
+                }
+            },
+        );
+
+        axum::response::Response::from_parts(parts, axum::body::Body::from_stream(stream))
+    }
+
+    /// 提取错误响应中的错误信息
+    fn error_message(value: &Value) -> Option<String> {
+        let error = value.get("error")?;
+        let message = error
+            .get("message")
+            .and_then(|m| m.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| error.to_string());
+        match error.get("details").and_then(|d| d.as_str()) {
+            Some(details) => Some(format!("{}: {}", message, details)),
+            None => Some(message),
+        }
+    }
+
+    /// 在响应体传输完成后写入缓存（非流式响应可能以保活空格开头，错误信息在响应体内返回）
+    fn store_on_complete<F>(response: axum::response::Response, store: F) -> axum::response::Response
+    where
+        F: FnOnce(&str) + Send + 'static,
+    {
+        let (mut parts, body) = response.into_parts();
+        parts.headers.insert(
+            CACHE_STATUS_HEADER,
+            axum::http::HeaderValue::from_static("miss"),
+        );
+
+        let stream = futures::stream::unfold(
+            (body.into_data_stream(), Vec::new(), Some(store)),
+            |(mut stream, mut buffer, mut store)| async move {
+                match stream.next().await {
+                    Some(Ok(chunk)) => {
+                        buffer.extend_from_slice(&chunk);
+                        Some((Ok(chunk), (stream, buffer, store)))
+                    }
+                    // 传输出错时放弃缓存
+                    Some(Err(e)) => Some((Err(e), (stream, buffer, None))),
+                    None => {
+                        if let Some(store) = store.take()
+                            && let Ok(text) = std::str::from_utf8(&buffer)
+                            && serde_json::from_str::<Value>(text.trim())
+                                .is_ok_and(|v| v.get("error").is_none())
+                        {
+                            store(text.trim());
+                        }
+                        None
+                    }
+                }
+            },
+        );
+
+        axum::response::Response::from_parts(parts, axum::body::Body::from_stream(stream))
+    }
+
+    /// 本实例是否配置了可用的该模型（按模型ID或模型名称匹配）
+    fn serves_model_locally(config: &crate::config::model::Config, model_name: &str) -> bool {
+        config
+            .models
+            .iter()
+            .any(|(model_id, mapping)| mapping.enabled && (model_id == model_name || mapping.name == model_name))
+    }
+
+    /// 开始一次上游请求：计入后端在途请求数和provider的配额用量
+    fn begin_upstream_request(&self, backend: &Backend) -> crate::loadbalance::InFlightGuard {
+        let metrics = self.load_balancer.get_metrics();
+        metrics.record_provider_request(&backend.provider);
+        metrics.begin_request(&format!("{}:{}", backend.provider, backend.model))
+    }
+
+    /// 从请求头和请求体中提取后端选择上下文
+    fn build_selection_context(
+        user: &UserToken,
+        client_key: &str,
+        request_headers: &axum::http::HeaderMap,
+        body: &Value,
+    ) -> Result<SelectionContext, RelayError> {
+        // 优先使用请求头中的会话标识，其次使用请求体中的user字段
+        let session_key = request_headers
+            .get(SESSION_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string())
+            .or_else(|| {
+                body.get("user")
+                    .and_then(|u| u.as_str())
+                    .filter(|u| !u.is_empty())
+                    .map(|u| u.to_string())
+            });
+
+        // 对话指纹：系统提示词加首条用户消息，同一对话的后续轮次保持不变
+        let conversation_fingerprint = body
+            .get("messages")
+            .and_then(|m| m.as_array())
+            .and_then(|messages| {
+                let role_is = |message: &Value, role: &str| {
+                    message.get("role").and_then(|r| r.as_str()) == Some(role)
+                };
+
+                let mut parts: Vec<String> = messages
+                    .iter()
+                    .filter(|m| role_is(m, "system"))
+                    .filter_map(|m| m.get("content").map(|c| c.to_string()))
+                    .collect();
+                if let Some(content) = messages
+                    .iter()
+                    .find(|m| role_is(m, "user"))
+                    .and_then(|m| m.get("content"))
+                {
+                    parts.push(content.to_string());
+                }
+
+                if parts.is_empty() {
+                    None
+                } else {
+                    Some(parts.join("\n"))
+                }
+            });
+
+        let tags = request_headers
+            .get(BACKEND_TAGS_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // 请求头中的区域优先于用户配置的区域
+        let region = request_headers
+            .get(REGION_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+            .or_else(|| user.region.clone());
+
+        Ok(SelectionContext {
+            session_key,
+            conversation_fingerprint,
+            client_key: Some(client_key.to_string()),
+            tags,
+            strategy_override: Self::strategy_override(user, request_headers)?,
+            pinned_backend: Self::pinned_backend(user, request_headers)?,
+            region,
+            context_tokens: None,
+            capabilities: RequiredCapabilities::from_request(body),
+        })
+    }
+
+    /// 读取只允许管理员使用的调试请求头，非管理员携带时拒绝请求
+    fn admin_header<'a>(
+        user: &UserToken,
+        request_headers: &'a axum::http::HeaderMap,
+        name: &str,
+    ) -> Result<Option<&'a str>, RelayError> {
+        let Some(value) = request_headers.get(name) else {
+            return Ok(None);
+        };
+
+        if !user.is_admin() {
+            tracing::warn!("User '{}' sent {} header without admin tag", user.name, name);
+            return Err(RelayError::PermissionDenied(format!(
+                "The {} header is only available to admin users",
+                name
+            )));
+        }
+
+        Ok(Some(value.to_str().unwrap_or_default().trim()))
+    }
+
+    /// 按抓取配置决定是否抓取该请求，请求体按日志用的内容过滤规则脱敏
+    fn start_capture(
+        config: &crate::config::model::Config,
+        user: &UserToken,
+        token: &str,
+        endpoint: &str,
+        request_headers: &axum::http::HeaderMap,
+        filters: &RequestFilters,
+        body: &Value,
+    ) -> Option<(RequestCaptureSettings, CaptureRecord)> {
+        let settings = config.settings.request_capture.as_ref()?;
+        if !RequestCapture::should_capture(settings, user, request_headers) {
+            return None;
+        }
+        let logged = filters.redact_for_log(body);
+        let path = format!("/v1{}", endpoint);
+        let record = CaptureRecord::new(&user.name, token, &path, request_headers, logged.as_ref().unwrap_or(body));
+        Some((settings.clone(), record))
+    }
+
+    /// 记录客户端收到的响应，完成后写出抓取记录
+    fn capture_response(
+        &self,
+        capture: Option<(RequestCaptureSettings, CaptureRecord)>,
+        response: axum::response::Response,
+        start_time: Instant,
+    ) -> axum::response::Response {
+        let Some((settings, mut record)) = capture else {
+            return response;
+        };
+        record.backend = response
+            .extensions()
+            .get::<ServedBackend>()
+            .map(|ServedBackend(b)| format!("{}:{}", b.provider, b.model));
+        self.request_capture.capture(&settings, record, response, start_time)
+    }
+
+    /// 解析x-berry-debug请求头，只有管理员可以请求选择过程说明
+    fn debug_trace(user: &UserToken, request_headers: &axum::http::HeaderMap) -> Result<Option<DebugTrace>, RelayError> {
+        let enabled = Self::admin_header(user, request_headers, debug::DEBUG_HEADER)?.is_some_and(debug::is_enabled);
+        Ok(enabled.then(DebugTrace::default))
+    }
+
+    /// 解析x-berry-strategy请求头，只有管理员可以覆盖模型映射的负载均衡策略
+    fn strategy_override(
+        user: &UserToken,
+        request_headers: &axum::http::HeaderMap,
+    ) -> Result<Option<LoadBalanceStrategy>, RelayError> {
+        let Some(name) = Self::admin_header(user, request_headers, STRATEGY_OVERRIDE_HEADER)? else {
+            return Ok(None);
+        };
+
+        let strategy = serde_json::from_value::<LoadBalanceStrategy>(Value::String(name.to_string()))
+            .map_err(|_| RelayError::InvalidRequest {
+                message: format!("Invalid {} header", STRATEGY_OVERRIDE_HEADER),
+                details: Some(format!("Unknown load balance strategy '{}'", name)),
+            })?;
+
+        tracing::debug!("User '{}' overrides load balance strategy with {:?}", user.name, strategy);
+        Ok(Some(strategy))
+    }
+
+    /// 解析x-berry-backend请求头，只有管理员可以指定处理请求的后端
+    fn pinned_backend(
+        user: &UserToken,
+        request_headers: &axum::http::HeaderMap,
+    ) -> Result<Option<String>, RelayError> {
+        let Some(value) = Self::admin_header(user, request_headers, PINNED_BACKEND_HEADER)? else {
+            return Ok(None);
+        };
+
+        match value.split_once(':') {
+            Some((provider, model)) if !provider.is_empty() && !model.is_empty() => {
+                tracing::debug!("User '{}' pins request to backend {}", user.name, value);
+                Ok(Some(value.to_string()))
+            }
+            _ => Err(RelayError::InvalidRequest {
+                message: format!("Invalid {} header", PINNED_BACKEND_HEADER),
+                details: Some(format!("Expected 'provider:model', got '{}'", value)),
+            }),
+        }
+    }
+
+    /// 尝试处理请求，带重试机制
+    async fn try_handle_with_retries(
+        &self,
+        request: &RelayRequest<'_>,
+        selection_context: &SelectionContext,
+        model_name: &str,
+        body: &mut Value,
+    ) -> Result<axum::response::Response, RelayError> {
+        let original_model = model_name.to_string();
+        let config = self.load_balancer.get_config();
+        let mapping = config.resolve_model(model_name).map(|(_, mapping)| mapping);
+        let retry_policy = mapping.map(ModelMapping::retry_policy).unwrap_or_default();
+        let max_retries = retry_policy.max_attempts;
+        let strategy = mapping
+            .map(|mapping| format!("{:?}", mapping.strategy))
+            .unwrap_or_default();
+        let transforms = mapping.map(|mapping| mapping.transforms.as_slice()).unwrap_or_default();
+        // 改写规则和参数覆盖按后端应用，每次重试从原始请求体开始
+        let rewrites_body = !transforms.is_empty()
+            || mapping.is_some_and(|mapping| mapping.backends.iter().any(|b| !b.overrides.is_empty()));
+        let original_body = rewrites_body.then(|| body.clone());
+        // 流式请求按模型配置启用请求对冲
+        let hedge_delay = mapping
+            .and_then(|mapping| mapping.hedging.as_ref())
+            .filter(|_| body.get("stream").and_then(|s| s.as_bool()) == Some(true))
+            .map(|hedging| std::time::Duration::from_millis(hedging.delay_ms));
+        // 要求JSON输出的非流式请求按模型配置校验响应内容
+        let structured_output = mapping
+            .and_then(|mapping| mapping.structured_output.as_ref())
+            .filter(|_| body.get("stream").and_then(|s| s.as_bool()) != Some(true) && requests_json(body));
+        // 本次请求中首个请求失败的后端，用于故障转移记忆
+        let mut failed_backend: Option<crate::config::model::Backend> = None;
+
+        for attempt in 0..max_retries {
+            // 重置为原始请求体和模型名称
+            if let Some(original_body) = &original_body {
+                *body = original_body.clone();
+            }
+            body["model"] = Value::String(original_model.clone());
+
+            if attempt == 0 {
+                debug::record_selection(|| self.load_balancer.explain_selection(model_name, selection_context));
+            }
+
+            // 使用负载均衡器选择后端
+            let select_span = tracing::info_span!(
+                "select_backend",
+                attempt = attempt + 1,
+                strategy = %strategy,
+                provider = tracing::field::Empty,
+                backend_model = tracing::field::Empty,
+            );
+            let selected_backend = match self
+                .load_balancer
+                .select_backend_with_context(model_name, selection_context)
+                .instrument(select_span.clone())
+                .await
+            {
+                Ok(backend) => {
+                    select_span.record("provider", backend.backend.provider.as_str());
+                    select_span.record("backend_model", backend.backend.model.as_str());
+                    backend
+                }
                 Err(e) => {
+                    debug::record_attempt(model_name, None, format!("selection failed: {}", e));
                     if attempt == max_retries - 1 {
                         // 最后一次尝试失败，提供详细错误信息
                         tracing::error!(
@@ -131,12 +2491,13 @@ impl LoadBalancedHandler {
                             max_retries,
                             e
                         );
-                        return Err(anyhow::anyhow!(
-                            "Backend selection failed for model '{}' after {} attempts. {}",
-                            model_name,
-                            max_retries,
-                            e
-                        ));
+                        return Err(RelayError::NoHealthyBackends {
+                            model: model_name.to_string(),
+                            details: format!(
+                                "Backend selection failed after {} attempts. All backends are currently unhealthy or unavailable. Details: {}",
+                                max_retries, e
+                            ),
+                        });
                     }
                     tracing::warn!(
                         "Backend selection failed on attempt {}, retrying: {}",
@@ -156,13 +2517,38 @@ impl LoadBalancedHandler {
                 selected_backend.selection_time.as_millis()
             );
 
-            // 更新请求体中的模型名称为后端的真实模型名称
-            body["model"] = Value::String(selected_backend.backend.model.clone());
+            // 获取后端并发名额，名额不足时转给其他有空闲名额的后端，都没有时重试，不计为后端失败
+            let selected_label = selected_backend.label();
+            let (selected_backend, permit) = match self
+                .acquire_backend(model_name, selected_backend, selection_context)
+                .await
+            {
+                Ok(acquired) => acquired,
+                Err(e) => {
+                    debug::record_attempt(model_name, Some(&selected_label), e.to_string());
+                    if attempt == max_retries - 1 {
+                        return Err(RelayError::BackendsAtCapacity {
+                            model: model_name.to_string(),
+                            details: format!(
+                                "Too many concurrent requests, please try again later. Details: {}",
+                                e
+                            ),
+                        });
+                    }
+                    tracing::warn!("Backend at capacity on attempt {}, retrying: {}", attempt + 1, e);
+                    continue;
+                }
+            };
+
+            // 对冲请求从改写前的请求体开始构建
+            let hedge_body = hedge_delay.map(|_| body.clone());
+            Self::prepare_backend_body(body, &selected_backend.backend, transforms);
 
             // 获取API密钥
             let api_key = match selected_backend.get_api_key() {
                 Ok(key) => key,
                 Err(e) => {
+                    debug::record_attempt(model_name, Some(&selected_backend.label()), e.to_string());
                     self.load_balancer
                         .record_request_result(
                             &selected_backend.backend.provider,
@@ -174,46 +2560,65 @@ impl LoadBalancedHandler {
                         .await;
 
                     if attempt == max_retries - 1 {
-                        return Err(anyhow::anyhow!(
-                            "API key configuration error for model '{}': {}. Please check provider configuration.",
-                            model_name,
-                            e
-                        ));
+                        return Err(RelayError::Configuration {
+                            model: model_name.to_string(),
+                            details: format!("API key configuration error: {}. Please check provider configuration.", e),
+                        });
                     }
                     tracing::warn!("API key error on attempt {}, retrying: {}", attempt + 1, e);
                     continue;
                 }
             };
 
-            // 创建客户端，只设置连接超时，不限制总请求时间
-            // 连接成功后允许无限时间生成内容，直到客户端断开连接
-            let connect_timeout = std::time::Duration::from_secs(selected_backend.provider.timeout_seconds);
-            let client = OpenAIClient::with_base_url_and_timeout(
-                selected_backend.provider.base_url.clone(),
-                connect_timeout,
+            let client = match self.client_for(&selected_backend) {
+                Ok(client) => client,
+                Err(e) => {
+                    debug::record_attempt(model_name, Some(&selected_backend.label()), e.to_string());
+                    self.load_balancer
+                        .record_request_result(
+                            &selected_backend.backend.provider,
+                            &selected_backend.backend.model,
+                            RequestResult::Failure {
+                                error: e.to_string(),
+                            },
+                        )
+                        .await;
+
+                    if attempt == max_retries - 1 {
+                        return Err(RelayError::Configuration {
+                            model: model_name.to_string(),
+                            details: format!("HTTP client configuration error: {}. Please check provider configuration.", e),
+                        });
+                    }
+                    tracing::warn!("Client build error on attempt {}, retrying: {}", attempt + 1, e);
+                    continue;
+                }
+            };
+
+            let upstream_span = tracing::info_span!(
+                "upstream_request",
+                attempt = attempt + 1,
+                provider = %selected_backend.backend.provider,
+                model = %selected_backend.backend.model,
+                error = tracing::field::Empty,
             );
 
             // 构建请求头
-            let headers = match client.build_request_headers(&authorization, &content_type) {
+            let headers = match Self::upstream_headers(
+                &client,
+                &api_key,
+                &selected_backend,
+                request.authorization,
+                request.content_type,
+            ) {
                 Ok(mut h) => {
-                    // 使用选中后端的API密钥
-                    h.insert(
-                        "Authorization",
-                        format!("Bearer {}", api_key).parse().unwrap(),
-                    );
-
-                    // 添加自定义头部
-                    for (key, value) in selected_backend.get_headers() {
-                        if let (Ok(header_name), Ok(header_value)) = (
-                            key.parse::<reqwest::header::HeaderName>(),
-                            value.parse::<reqwest::header::HeaderValue>(),
-                        ) {
-                            h.insert(header_name, header_value);
-                        }
-                    }
+                    // 向上游传递追踪上下文
+                    upstream_span.in_scope(|| crate::telemetry::inject_trace_context(&mut h));
+                    crate::telemetry::inject_request_id(&mut h);
                     h
                 }
                 Err(e) => {
+                    debug::record_attempt(model_name, Some(&selected_backend.label()), e.to_string());
                     self.load_balancer
                         .record_request_result(
                             &selected_backend.backend.provider,
@@ -225,11 +2630,10 @@ impl LoadBalancedHandler {
                         .await;
 
                     if attempt == max_retries - 1 {
-                        return Err(anyhow::anyhow!(
-                            "Request header configuration error for model '{}': {}. Please check provider configuration.",
-                            model_name,
-                            e
-                        ));
+                        return Err(RelayError::Configuration {
+                            model: model_name.to_string(),
+                            details: format!("Request header configuration error: {}. Please check provider configuration.", e),
+                        });
                     }
                     tracing::warn!(
                         "Header build error on attempt {}, retrying: {}",
@@ -241,51 +2645,447 @@ impl LoadBalancedHandler {
             };
 
             // 尝试发送请求
-            match self
-                .try_single_request(&client, headers, body, &selected_backend, start_time)
-                .await
-            {
-                Ok(response) => return Ok(response),
+            let upstream = UpstreamAttempt {
+                selected_backend: selected_backend.clone(),
+                client,
+                headers,
+                body: body.clone(),
+                permit,
+            };
+            let result = match (hedge_delay, &hedge_body) {
+                (Some(delay), Some(hedge_body)) => {
+                    self.try_hedged_request(request, upstream, delay, retry_policy.retry_partial_stream, || {
+                        self.prepare_hedge(
+                            request,
+                            selection_context,
+                            model_name,
+                            &selected_backend,
+                            hedge_body,
+                            transforms,
+                        )
+                    })
+                    .instrument(upstream_span.clone())
+                    .await
+                }
+                _ => match structured_output {
+                    Some(settings) => self
+                        .try_structured_output_request(
+                            request.endpoint,
+                            upstream,
+                            request.start_time,
+                            settings,
+                            attempt + 1 < max_retries,
+                        )
+                        .instrument(upstream_span.clone())
+                        .await,
+                    None => self
+                        .try_single_request(
+                            request.endpoint,
+                            upstream,
+                            request.start_time,
+                            retry_policy.retry_partial_stream,
+                        )
+                        .instrument(upstream_span.clone())
+                        .await,
+                }
+                .map(|response| (response, selected_backend.clone())),
+            };
+            match result {
+                Ok((mut response, served)) => {
+                    debug::record_attempt(model_name, Some(&served.label()), "success");
+                    response
+                        .extensions_mut()
+                        .insert(ServedBackend(served.backend.clone()));
+                    if let (Some(failed), Some(client_key)) =
+                        (&failed_backend, &selection_context.client_key)
+                        && (failed.provider != served.backend.provider
+                            || failed.model != served.backend.model)
+                    {
+                        self.load_balancer.remember_failover(
+                            client_key,
+                            model_name,
+                            failed,
+                            &served.backend,
+                        );
+                    }
+                    return Ok(response);
+                }
                 Err(e) => {
-                    // 记录失败
+                    debug::record_attempt(model_name, Some(&selected_backend.label()), e.to_string());
+                    upstream_span.record("error", e.to_string().as_str());
+                    failed_backend.get_or_insert_with(|| selected_backend.backend.clone());
+                    if let UpstreamError::Timeout(kind, _) = &e {
+                        self.load_balancer.get_metrics().record_upstream_timeout(
+                            &format!("{}:{}", selected_backend.backend.provider, selected_backend.backend.model),
+                            kind.as_str(),
+                        );
+                    }
+
+                    // 记录失败，上游限流时让后端冷却
                     self.load_balancer
                         .record_request_result(
                             &selected_backend.backend.provider,
                             &selected_backend.backend.model,
-                            RequestResult::Failure {
-                                error: e.to_string(),
-                            },
+                            e.request_result(),
                         )
                         .await;
 
-                    if attempt == max_retries - 1 {
-                        return Err(anyhow::anyhow!(
-                            "Request to backend failed for model '{}' after {} attempts: {}. All available backends may be experiencing issues.",
+                    // 按模型的重试策略决定是否重试，不可重试的错误直接返回
+                    if attempt == max_retries - 1 || !e.is_retryable(&retry_policy) {
+                        return Err(RelayError::from_upstream(
                             model_name,
-                            max_retries,
-                            e
+                            &e,
+                            format!(
+                                "Request failed after {} attempts: {}. All available backends may be experiencing issues.",
+                                attempt + 1, e
+                            ),
                         ));
                     }
-                    tracing::warn!("Request failed on attempt {}, retrying: {}", attempt + 1, e);
+                    let backoff = retry_policy.backoff(attempt + 1);
+                    tracing::warn!(
+                        "Request failed on attempt {}, retrying in {}ms: {}",
+                        attempt + 1,
+                        backoff.as_millis(),
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
                     continue;
                 }
             }
         }
 
-        Err(anyhow::anyhow!("Unexpected end of retry loop"))
+        Err(RelayError::Internal("Unexpected end of retry loop".to_string()))
+    }
+
+    /// 将请求体改写为发往指定后端的形式：替换模型名称并应用改写规则和参数覆盖
+    fn prepare_backend_body(body: &mut Value, backend: &Backend, transforms: &[RequestTransform]) {
+        // 更新请求体中的模型名称为后端的真实模型名称
+        body["model"] = Value::String(backend.model.clone());
+        crate::relay::transform::apply_transforms(transforms, &backend.provider, body);
+        crate::relay::transform::apply_overrides(&backend.overrides, body);
+
+        // 配置了价格的后端要求流式响应携带usage，用于花费统计
+        if backend.pricing.is_some() && body.get("stream").and_then(|s| s.as_bool()) == Some(true) {
+            body["stream_options"]["include_usage"] = Value::Bool(true);
+        }
+    }
+
+    /// 构建发往选中后端的请求头：使用后端的API密钥并添加provider自定义头部
+    fn upstream_headers(
+        client: &OpenAIClient,
+        api_key: &str,
+        selected_backend: &SelectedBackend,
+        authorization: &headers::Authorization<headers::authorization::Bearer>,
+        content_type: &headers::ContentType,
+    ) -> Result<reqwest::header::HeaderMap, crate::relay::client::ClientError> {
+        let mut h = client.build_request_headers(authorization, content_type)?;
+        // 使用选中后端的API密钥
+        h.insert(
+            "Authorization",
+            format!("Bearer {}", api_key).parse().unwrap(),
+        );
+
+        // 添加自定义头部
+        for (key, value) in selected_backend.get_headers() {
+            if let (Ok(header_name), Ok(header_value)) = (
+                key.parse::<reqwest::header::HeaderName>(),
+                value.parse::<reqwest::header::HeaderValue>(),
+            ) {
+                h.insert(header_name, header_value);
+            }
+        }
+        Ok(h)
+    }
+
+    /// 获取选中后端的并发名额
+    /// 名额和排队都不可用时，转给另一个有空闲名额的健康后端
+    async fn acquire_backend(
+        &self,
+        model_name: &str,
+        selected_backend: SelectedBackend,
+        selection_context: &SelectionContext,
+    ) -> Result<(SelectedBackend, ConcurrencyPermit), ConcurrencyError> {
+        let error = match self.concurrency.acquire(&selected_backend).await {
+            Ok(permit) => return Ok((selected_backend, permit)),
+            Err(e) => e,
+        };
+        if let Some(overflow) = self.load_balancer.select_hedge_backend(
+            model_name,
+            &selected_backend.backend,
+            selection_context,
+        ) && let Some(permit) = self.concurrency.try_acquire(&overflow)
+        {
+            tracing::info!(
+                "{}, overflowing request to {}:{}",
+                error,
+                overflow.backend.provider,
+                overflow.backend.model
+            );
+            return Ok((overflow, permit));
+        }
+        Err(error)
+    }
+
+    /// 准备对冲请求：选择另一个健康后端并构建其请求体、客户端和请求头
+    /// 对冲后端没有空闲并发名额时不进行对冲
+    fn prepare_hedge(
+        &self,
+        request: &RelayRequest<'_>,
+        selection_context: &SelectionContext,
+        model_name: &str,
+        primary: &SelectedBackend,
+        base_body: &Value,
+        transforms: &[RequestTransform],
+    ) -> Option<UpstreamAttempt> {
+        let hedge = self
+            .load_balancer
+            .select_hedge_backend(model_name, &primary.backend, selection_context)?;
+        let Some(permit) = self.concurrency.try_acquire(&hedge) else {
+            tracing::debug!(
+                "Hedge backend {}:{} is at capacity, skipping hedging",
+                hedge.backend.provider,
+                hedge.backend.model
+            );
+            return None;
+        };
+
+        let mut body = base_body.clone();
+        Self::prepare_backend_body(&mut body, &hedge.backend, transforms);
+
+        let prepared = hedge.get_api_key().and_then(|api_key| {
+            let client = self.client_for(&hedge)?;
+            let headers =
+                Self::upstream_headers(&client, &api_key, &hedge, request.authorization, request.content_type)?;
+            Ok((client, headers))
+        });
+        match prepared {
+            Ok((client, mut headers)) => {
+                crate::telemetry::inject_trace_context(&mut headers);
+                crate::telemetry::inject_request_id(&mut headers);
+                Some(UpstreamAttempt {
+                    selected_backend: hedge,
+                    client,
+                    headers,
+                    body,
+                    permit,
+                })
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to prepare hedged request to {}:{}: {}",
+                    hedge.backend.provider,
+                    hedge.backend.model,
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// 按比例把请求复制到镜像后端：在后台发送并丢弃响应，失败只记录日志
+    /// 镜像后端没有空闲并发名额时跳过本次复制
+    fn mirror_request(
+        &self,
+        endpoint: &'static str,
+        mapping: &ModelMapping,
+        mirror: &MirrorSettings,
+        base_body: &Value,
+        authorization: &headers::Authorization<headers::authorization::Bearer>,
+        content_type: &headers::ContentType,
+    ) {
+        use rand::Rng;
+        if rand::rng().random::<f64>() * 100.0 >= mirror.percent {
+            return;
+        }
+
+        let config = self.load_balancer.get_config();
+        let Some(provider) = config.get_provider(&mirror.provider) else {
+            return;
+        };
+        let target = SelectedBackend {
+            backend: Backend {
+                provider: mirror.provider.clone(),
+                model: mirror.model.clone(),
+                weight: 1.0,
+                priority: 0,
+                enabled: true,
+                tags: vec![],
+                billing_mode: Default::default(),
+                pricing: None,
+                overrides: Default::default(),
+                slow_start: None,
+                concurrency: None,
+                schedule: None,
+                region: None,
+                max_context: None,
+                supports_vision: true,
+                supports_tools: true,
+                supports_json_mode: true,
+                supports_streaming: true,
+                supports_responses: false,
+            },
+            provider: provider.clone(),
+            selection_time: std::time::Duration::ZERO,
+            timeouts: match &mapping.timeouts {
+                Some(overrides) => provider.timeouts.clone().unwrap_or_default().overridden_by(overrides),
+                None => provider.timeouts.clone().unwrap_or_default(),
+            },
+        };
+        let Some(permit) = self.concurrency.try_acquire(&target) else {
+            tracing::debug!("Mirror backend {}:{} is at capacity, skipping", mirror.provider, mirror.model);
+            return;
+        };
+
+        let mut body = base_body.clone();
+        Self::prepare_backend_body(&mut body, &target.backend, &mapping.transforms);
+        let prepared = target.get_api_key().and_then(|api_key| {
+            let client = self.client_for(&target)?;
+            let headers =
+                Self::upstream_headers(&client, &api_key, &target, authorization, content_type)?;
+            Ok((client, headers))
+        });
+        let (client, mut headers) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to prepare mirrored request to {}:{}: {}",
+                    mirror.provider,
+                    mirror.model,
+                    e
+                );
+                return;
+            }
+        };
+        crate::telemetry::inject_request_id(&mut headers);
+        self.load_balancer.get_metrics().record_provider_request(&mirror.provider);
+
+        let model_name = mapping.name.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let start = Instant::now();
+            let result = match client.post_json(endpoint, headers, &body).await {
+                // 读完响应体（包括流式响应）后丢弃
+                Ok(response) => {
+                    let status = response.status();
+                    response.bytes().await.map(|_| status).map_err(|e| e.to_string())
+                }
+                Err(e) => Err(e.to_string()),
+            };
+            match result {
+                Ok(status) if status.is_success() => tracing::debug!(
+                    "Mirrored request for model '{}' to {}:{} finished with status {} in {}ms",
+                    model_name,
+                    target.backend.provider,
+                    target.backend.model,
+                    status,
+                    start.elapsed().as_millis()
+                ),
+                Ok(status) => tracing::warn!(
+                    "Mirrored request for model '{}' to {}:{} returned status {}",
+                    model_name,
+                    target.backend.provider,
+                    target.backend.model,
+                    status
+                ),
+                Err(e) => tracing::warn!(
+                    "Mirrored request for model '{}' to {}:{} failed: {}",
+                    model_name,
+                    target.backend.provider,
+                    target.backend.model,
+                    e
+                ),
+            }
+        }.in_current_span());
+    }
+
+    /// 带请求对冲的单次请求
+    /// 首个后端在对冲延迟内没有响应时向另一个后端发送同样的请求，返回先成功的响应及其后端；
+    /// 被取消的一方尚未得到结果，不记录成功或失败
+    async fn try_hedged_request<F>(
+        &self,
+        request: &RelayRequest<'_>,
+        upstream: UpstreamAttempt,
+        delay: std::time::Duration,
+        peek_stream: bool,
+        prepare_hedge: F,
+    ) -> Result<(axum::response::Response, SelectedBackend), UpstreamError>
+    where
+        F: FnOnce() -> Option<UpstreamAttempt>,
+    {
+        let selected_backend = upstream.selected_backend.clone();
+        let primary = self.try_single_request(request.endpoint, upstream, request.start_time, peek_stream);
+        tokio::pin!(primary);
+
+        tokio::select! {
+            result = &mut primary => {
+                return result.map(|response| (response, selected_backend.clone()));
+            }
+            _ = tokio::time::sleep(delay) => {}
+        }
+
+        let Some(hedge_upstream) = prepare_hedge() else {
+            return primary.await.map(|response| (response, selected_backend.clone()));
+        };
+        let hedge = hedge_upstream.selected_backend.clone();
+        tracing::info!(
+            "Backend {}:{} did not respond within {}ms, hedging request to {}:{}",
+            selected_backend.backend.provider,
+            selected_backend.backend.model,
+            delay.as_millis(),
+            hedge.backend.provider,
+            hedge.backend.model
+        );
+
+        let hedge_span = tracing::info_span!(
+            "hedge_request",
+            provider = %hedge.backend.provider,
+            model = %hedge.backend.model,
+        );
+        let secondary = self
+            .try_single_request(request.endpoint, hedge_upstream, request.start_time, peek_stream)
+            .instrument(hedge_span);
+        tokio::pin!(secondary);
+
+        // 先成功的一方胜出，返回时丢弃另一方的future即取消其上游请求；一方失败时等待另一方
+        tokio::select! {
+            result = &mut primary => match result {
+                Ok(response) => Ok((response, selected_backend.clone())),
+                Err(e) => {
+                    tracing::warn!("Primary request failed while hedging: {}", e);
+                    secondary.await.map(|response| (response, hedge.clone()))
+                }
+            },
+            result = &mut secondary => match result {
+                Ok(response) => {
+                    tracing::info!(
+                        "Hedged request to {}:{} responded first, cancelling {}:{}",
+                        hedge.backend.provider,
+                        hedge.backend.model,
+                        selected_backend.backend.provider,
+                        selected_backend.backend.model
+                    );
+                    Ok((response, hedge.clone()))
+                }
+                Err(e) => {
+                    tracing::warn!("Hedged request failed: {}", e);
+                    primary.await.map(|response| (response, selected_backend.clone()))
+                }
+            },
+        }
     }
 
-    /// 尝试单次请求
+    /// 尝试单次请求，持有的并发名额在上游请求结束时释放
+    /// peek_stream为true时，流式响应在首个事件到达后才返回，之前中断的流作为失败处理以便重试
     async fn try_single_request(
         &self,
-        client: &OpenAIClient,
-        headers: reqwest::header::HeaderMap,
-        body: &Value,
-        selected_backend: &crate::loadbalance::SelectedBackend,
+        endpoint: &'static str,
+        upstream: UpstreamAttempt,
         start_time: Instant,
-    ) -> Result<axum::response::Response, anyhow::Error> {
+        peek_stream: bool,
+    ) -> Result<axum::response::Response, UpstreamError> {
         // 检查是否为流式请求
-        let is_stream = body
+        let is_stream = upstream
+            .body
             .get("stream")
             .unwrap_or(&Value::Bool(false))
             .as_bool()
@@ -293,100 +3093,171 @@ impl LoadBalancedHandler {
 
         if is_stream {
             // 流式请求：尝试发送请求，失败时返回错误以触发重试
-            match self
-                .try_streaming_request(
-                    client.clone(),
-                    headers,
-                    body.clone(),
-                    selected_backend.clone(),
-                    start_time,
-                )
+            self
+                .try_streaming_request(endpoint, upstream, start_time, peek_stream)
                 .await
-            {
-                Ok(response) => Ok(response.into_response()),
-                Err(e) => Err(anyhow::anyhow!("Streaming request failed: {}", e)),
-            }
+                .map(IntoResponse::into_response)
         } else {
             // 非流式请求：使用保活机制，立即开始响应
-            match self
-                .try_non_streaming_request_with_keepalive(
-                    client.clone(),
-                    headers,
-                    body.clone(),
-                    selected_backend.clone(),
-                    start_time,
-                )
+            self
+                .try_non_streaming_request_with_keepalive(endpoint, upstream, start_time)
                 .await
-            {
-                Ok(response) => Ok(response),
-                Err(e) => Err(anyhow::anyhow!("Non-streaming request failed: {}", e)),
+        }
+    }
+
+    /// 要求JSON输出的非流式请求：等待完整响应并校验内容，不合法时换后端重试，不能重试时按配置修复
+    /// 不使用保活响应，以便在返回给客户端之前决定是否重试
+    async fn try_structured_output_request(
+        &self,
+        endpoint: &'static str,
+        upstream: UpstreamAttempt,
+        start_time: Instant,
+        settings: &StructuredOutputSettings,
+        can_retry: bool,
+    ) -> Result<axum::response::Response, UpstreamError> {
+        let UpstreamAttempt {
+            selected_backend,
+            client,
+            headers,
+            body,
+            permit,
+        } = upstream;
+        let provider = &selected_backend.backend.provider;
+        let model = &selected_backend.backend.model;
+        let _in_flight = (self.begin_upstream_request(&selected_backend.backend), permit);
+        let deadlines = UpstreamDeadlines::start(&selected_backend.timeouts);
+        let timed_out = |kind| timeout_error(&selected_backend, kind);
+
+        let response = deadlines
+            .within_total(client.post_json(endpoint, headers, &body))
+            .await
+            .map_err(timed_out)??;
+        if !response.status().is_success() {
+            return Err(UpstreamError::from_response(response.status(), response.headers()));
+        }
+        let latency = start_time.elapsed();
+        let text = deadlines
+            .within_total(response.text())
+            .await
+            .map_err(timed_out)?
+            .map_err(|e| UpstreamError::Request(format!("Failed to read response body: {}", e)))?;
+        let mut value: Value = serde_json::from_str(&text)
+            .map_err(|e| UpstreamError::Request(format!("JSON parsing failed: {}", e)))?;
+        normalize_response(&mut value);
+
+        if let Err(e) = validate_response(&value) {
+            self.structured_output.record_invalid();
+            if settings.retry && can_retry {
+                tracing::warn!("Backend {}:{} returned invalid JSON content, retrying: {}", provider, model, e);
+                self.structured_output.record_retry();
+                return Err(UpstreamError::InvalidJson(e));
+            }
+            if settings.repair {
+                let repaired = repair_response(&mut value);
+                self.structured_output.record_repair(repaired);
+                if !repaired {
+                    tracing::warn!("Failed to repair invalid JSON content from {}:{}: {}", provider, model, e);
+                }
             }
         }
+
+        self.load_balancer
+            .record_request_result(provider, model, RequestResult::Success { latency })
+            .await;
+        Ok(Json(value).into_response())
     }
 
     /// 尝试流式请求（可能失败以触发重试）
     async fn try_streaming_request(
         &self,
-        client: OpenAIClient,
-        headers: reqwest::header::HeaderMap,
-        body: Value,
-        selected_backend: crate::loadbalance::SelectedBackend,
+        endpoint: &'static str,
+        upstream: UpstreamAttempt,
         start_time: Instant,
+        peek_stream: bool,
     ) -> Result<
         Sse<futures::stream::BoxStream<'static, Result<Event, std::convert::Infallible>>>,
-        anyhow::Error,
+        UpstreamError,
     > {
+        let UpstreamAttempt {
+            selected_backend,
+            client,
+            headers,
+            body,
+            permit,
+        } = upstream;
         let provider = &selected_backend.backend.provider;
         let model = &selected_backend.backend.model;
 
-        // 发送API请求
-        let response = match client.chat_completions(headers, &body).await {
-            Ok(resp) => resp,
-            Err(e) => {
-                tracing::debug!("Streaming request failed: {:?}", e);
+        // 在途请求计数，流结束时随守卫一起释放
+        let in_flight = self.begin_upstream_request(&selected_backend.backend);
+        let deadlines = UpstreamDeadlines::start(&selected_backend.timeouts);
+
+        // 发送API请求，响应头需要在首个token的截止时间内返回
+        let response = match deadlines.before_first_token(client.post_json(endpoint, headers, &body)).await {
+            Ok(Ok(resp)) => resp,
+            result => {
+                let error = match result {
+                    Err(kind) => timeout_error(&selected_backend, kind),
+                    Ok(Err(e)) => e.into(),
+                    Ok(Ok(_)) => unreachable!(),
+                };
+                tracing::debug!("Streaming request failed: {}", error);
                 // 记录失败但不在这里处理，让重试机制处理
                 self.load_balancer
-                    .record_request_result(
-                        provider,
-                        model,
-                        RequestResult::Failure {
-                            error: e.to_string(),
-                        },
-                    )
+                    .record_request_result(provider, model, error.request_result())
                     .await;
-                return Err(anyhow::anyhow!("API request failed: {}", e));
+                return Err(error);
             }
         };
 
         // 检查HTTP状态
         if !response.status().is_success() {
-            let status = response.status();
-            tracing::debug!("Streaming request failed with status: {}", status);
-            // 记录失败但不在这里处理，让重试机制处理
+            let error = UpstreamError::from_response(response.status(), response.headers());
+            tracing::debug!("Streaming request failed: {}", error);
+            // 记录失败（或限流冷却）但不在这里处理，让重试机制处理
             self.load_balancer
-                .record_request_result(
-                    provider,
-                    model,
-                    RequestResult::Failure {
-                        error: format!("HTTP {}", status),
-                    },
-                )
+                .record_request_result(provider, model, error.request_result())
                 .await;
-            return Err(anyhow::anyhow!("HTTP error: {}", status));
+            return Err(error);
+        }
+
+        let mut events = response.bytes_stream().eventsource().boxed();
+        if peek_stream {
+            // 等到首个事件后再向客户端返回，之前中断的流仍可换后端重试
+            match deadlines.before_first_token(events.next()).await {
+                Ok(Some(Ok(first))) => {
+                    events = futures::stream::once(async move { Ok(first) }).chain(events).boxed();
+                }
+                interrupted => {
+                    let error = match interrupted {
+                        Err(kind) => timeout_error(&selected_backend, kind),
+                        Ok(Some(Err(e))) => UpstreamError::Request(format!("Stream interrupted before first event: {}", e)),
+                        _ => UpstreamError::Request("Stream ended before first event".to_string()),
+                    };
+                    tracing::debug!("Streaming request failed: {}", error);
+                    self.load_balancer
+                        .record_request_result(provider, model, error.request_result())
+                        .await;
+                    return Err(error);
+                }
+            }
         }
 
         // 成功情况 - 创建流式响应
         Ok(self
-            .create_successful_stream(response, selected_backend, start_time)
+            .create_successful_stream(events, selected_backend, start_time, (in_flight, permit), deadlines, peek_stream)
             .await)
     }
 
-    /// 创建成功的流式响应
+    /// 创建成功的流式响应，流结束时释放在途请求守卫和并发名额；first_received表示首个事件已经读取
     async fn create_successful_stream(
         &self,
-        response: reqwest::Response,
+        events: UpstreamEvents,
         selected_backend: crate::loadbalance::SelectedBackend,
         start_time: Instant,
+        in_flight: (crate::loadbalance::InFlightGuard, ConcurrencyPermit),
+        deadlines: UpstreamDeadlines,
+        first_received: bool,
     ) -> Sse<futures::stream::BoxStream<'static, Result<Event, std::convert::Infallible>>> {
         let load_balancer = self.load_balancer.clone();
         let provider = selected_backend.backend.provider.clone();
@@ -419,21 +3290,46 @@ impl LoadBalancedHandler {
                 .await;
         });
 
-        // 创建带保活机制的流式响应
-        let data_stream = response
-            .bytes_stream()
-            .eventsource()
-            .map(|result| match result {
-                Ok(event) => {
+        // 创建带保活机制的流式响应，工具调用分片统一转换为OpenAI格式
+        // 首个token、数据块间隔或总时长超时后发送错误事件并结束上游流
+        let mut tool_calls = ToolCallStreamNormalizer::new();
+        let timeout_backend = selected_backend.clone();
+        let data_stream = deadlines
+            .guard_stream(events, first_received)
+            .map(move |result| match result {
+                Err(kind) => {
+                    let error = timeout_error(&timeout_backend, kind);
+                    tracing::warn!("{}", error);
+                    metrics.record_upstream_timeout(&backend_key, kind.as_str());
+                    let load_balancer = load_balancer.clone();
+                    let backend = timeout_backend.backend.clone();
+                    let result = error.request_result();
+                    tokio::spawn(async move {
+                        load_balancer.record_request_result(&backend.provider, &backend.model, result).await;
+                    });
+                    let error = RelayError::from_upstream(&timeout_backend.backend.model, &error, error.to_string());
+                    Ok(Event::default().data(error.to_json().to_string()))
+                }
+                Ok(Ok(event)) => {
                     tracing::debug!("SSE event: {:?}", event.data);
-                    Ok(Event::default().data(event.data))
+                    Ok(Event::default().data(tool_calls.normalize_event(event.data)))
                 }
-                Err(err) => {
+                Ok(Err(err)) => {
                     tracing::error!("SSE error: {:?}", err);
                     Ok(Event::default().data(json!({"error": err.to_string()}).to_string()))
                 }
             });
 
+        // 流式传输阶段的span，随在途请求守卫一起在上游流结束时关闭
+        let stream_span = tracing::info_span!("stream", provider = %provider, model = %model);
+
+        // 上游流结束时释放在途请求守卫和并发名额（保活流不会结束，不能依赖整个流被丢弃）
+        let mut in_flight = Some((in_flight, stream_span));
+        let data_stream = data_stream.chain(futures::stream::poll_fn(move |_| {
+            in_flight.take();
+            std::task::Poll::Ready(None)
+        }));
+
         // 创建保活定时器流，每30秒发送一次SSE keep-alive注释
         // 这可以防止代理服务器或负载均衡器因超时而断开连接
         let keepalive_interval = tokio_stream::wrappers::IntervalStream::new(
@@ -450,6 +3346,7 @@ impl LoadBalancedHandler {
     /// 尝试非流式请求（可能失败以触发重试）
     async fn try_non_streaming_request(
         &self,
+        endpoint: &'static str,
         client: OpenAIClient,
         headers: reqwest::header::HeaderMap,
         body: Value,
@@ -458,9 +3355,10 @@ impl LoadBalancedHandler {
     ) -> Result<Json<Value>, anyhow::Error> {
         let provider = &selected_backend.backend.provider;
         let model = &selected_backend.backend.model;
+        let _in_flight = self.begin_upstream_request(&selected_backend.backend);
 
         // 发送API请求
-        let response = match client.chat_completions(headers, &body).await {
+        let response = match client.post_json(endpoint, headers, &body).await {
             Ok(resp) => resp,
             Err(e) => {
                 tracing::debug!("Non-streaming request failed: {:?}", e);
@@ -501,7 +3399,10 @@ impl LoadBalancedHandler {
 
             match response.text().await {
                 Ok(text) => match serde_json::from_str::<Value>(&text) {
-                    Ok(value) => Ok(Json(value)),
+                    Ok(mut value) => {
+                        normalize_response(&mut value);
+                        Ok(Json(value))
+                    }
                     Err(e) => {
                         tracing::error!("JSON parsing failed: {:?}", e);
                         Err(anyhow::anyhow!("JSON parsing failed: {}", e))
@@ -533,17 +3434,22 @@ impl LoadBalancedHandler {
     /// 尝试非流式请求（带保活机制）
     async fn try_non_streaming_request_with_keepalive(
         &self,
-        client: OpenAIClient,
-        headers: reqwest::header::HeaderMap,
-        body: Value,
-        selected_backend: crate::loadbalance::SelectedBackend,
+        endpoint: &'static str,
+        upstream: UpstreamAttempt,
         start_time: Instant,
-    ) -> Result<axum::response::Response, anyhow::Error> {
+    ) -> Result<axum::response::Response, UpstreamError> {
+        let UpstreamAttempt {
+            selected_backend,
+            client,
+            headers,
+            body,
+            permit,
+        } = upstream;
         let provider = &selected_backend.backend.provider;
         let model = &selected_backend.backend.model;
 
         // 创建一个通道来传递最终结果
-        let (result_tx, result_rx) = tokio::sync::mpsc::channel::<Result<String, anyhow::Error>>(1);
+        let (result_tx, result_rx) = tokio::sync::mpsc::channel::<Result<String, RelayError>>(1);
 
         // 在后台发送API请求
         let client_clone = client.clone();
@@ -552,24 +3458,42 @@ impl LoadBalancedHandler {
         let provider_clone = provider.clone();
         let model_clone = model.clone();
         let load_balancer_clone = self.load_balancer.clone();
-        let start_time_clone = start_time.clone();
+        let start_time_clone = start_time;
+        let in_flight = self.begin_upstream_request(&selected_backend.backend);
+        let deadlines = UpstreamDeadlines::start(&selected_backend.timeouts);
+        let debug_trace = debug::current();
 
         tokio::spawn(async move {
-            let response = match client_clone.chat_completions(headers_clone, &body_clone).await {
-                Ok(resp) => resp,
-                Err(e) => {
-                    tracing::debug!("Non-streaming request failed: {:?}", e);
+            // 后台请求完成前持有在途请求守卫和并发名额
+            let _in_flight = (in_flight, permit);
+            let failure = |e: UpstreamError| {
+                // 重试循环已按成功记录本次尝试，这里补记真实结果
+                if let Some(trace) = &debug_trace {
+                    trace.record_late_outcome(&selected_backend.label(), e.to_string());
+                }
+                RelayError::from_upstream(&model_clone, &e, e.to_string())
+            };
+            let backend_key = format!("{}:{}", provider_clone, model_clone);
+            // 响应已经以保活形式返回，超时不会再经过重试循环，在这里计数
+            let timed_out = |kind: TimeoutKind| {
+                load_balancer_clone.get_metrics().record_upstream_timeout(&backend_key, kind.as_str());
+                timeout_error(&selected_backend, kind)
+            };
+            let request = client_clone.post_json(endpoint, headers_clone, &body_clone);
+            let response = match deadlines.within_total(async { request.await.map_err(UpstreamError::from) }).await {
+                Ok(Ok(resp)) => resp,
+                result => {
+                    let error = match result {
+                        Err(kind) | Ok(Err(UpstreamError::Timeout(kind, _))) => timed_out(kind),
+                        Ok(Err(e)) => e,
+                        Ok(Ok(_)) => unreachable!(),
+                    };
+                    tracing::debug!("Non-streaming request failed: {}", error);
                     // 记录失败
                     load_balancer_clone
-                        .record_request_result(
-                            &provider_clone,
-                            &model_clone,
-                            RequestResult::Failure {
-                                error: e.to_string(),
-                            },
-                        )
+                        .record_request_result(&provider_clone, &model_clone, error.request_result())
                         .await;
-                    let _ = result_tx.send(Err(anyhow::anyhow!("API request failed: {}", e))).await;
+                    let _ = result_tx.send(Err(failure(error))).await;
                     return;
                 }
             };
@@ -595,32 +3519,34 @@ impl LoadBalancedHandler {
                     .record_request_result(&provider_clone, &model_clone, RequestResult::Success { latency })
                     .await;
 
-                match response.text().await {
-                    Ok(text) => {
-                        let _ = result_tx.send(Ok(text)).await;
+                match deadlines.within_total(response.text()).await {
+                    Ok(Ok(text)) => {
+                        let _ = result_tx.send(Ok(normalize_response_text(text))).await;
                     },
-                    Err(e) => {
+                    Err(kind) => {
+                        let error = timed_out(kind);
+                        tracing::warn!("{}", error);
+                        load_balancer_clone
+                            .record_request_result(&provider_clone, &model_clone, error.request_result())
+                            .await;
+                        let _ = result_tx.send(Err(failure(error))).await;
+                    }
+                    Ok(Err(e)) => {
                         tracing::error!("Failed to read response body: {:?}", e);
-                        let _ = result_tx.send(Err(anyhow::anyhow!("Failed to read response body: {}", e))).await;
+                        let _ = result_tx.send(Err(failure(UpstreamError::Request(format!("Failed to read response body: {}", e))))).await;
                     }
                 }
             } else {
-                // 记录失败
-                let status = response.status().as_u16();
+                // 记录失败（或限流冷却）
+                let error = UpstreamError::from_response(response.status(), response.headers());
                 load_balancer_clone
-                    .record_request_result(
-                        &provider_clone,
-                        &model_clone,
-                        RequestResult::Failure {
-                            error: format!("HTTP {}", status),
-                        },
-                    )
+                    .record_request_result(&provider_clone, &model_clone, error.request_result())
                     .await;
 
-                tracing::debug!("Non-streaming request failed with status: {}", status);
-                let _ = result_tx.send(Err(anyhow::anyhow!("HTTP error: {}", status))).await;
+                tracing::debug!("Non-streaming request failed: {}", error);
+                let _ = result_tx.send(Err(failure(error))).await;
             }
-        });
+        }.in_current_span());
 
         // 创建真正的流式保活响应
         let response_stream = futures::stream::unfold(
@@ -643,22 +3569,13 @@ impl LoadBalancedHandler {
                                 Some((Ok::<bytes::Bytes, std::convert::Infallible>(bytes::Bytes::from(text)), (result_rx, true)))
                             }
                             Some(Err(e)) => {
-                                // 处理错误，然后结束流
-                                let error_json = serde_json::json!({
-                                    "error": {
-                                        "message": "Request failed",
-                                        "details": e.to_string()
-                                    }
-                                });
+                                // 响应头已发出，错误类型只能在响应体中体现，然后结束流
+                                let error_json = e.to_json();
                                 Some((Ok(bytes::Bytes::from(error_json.to_string())), (result_rx, true)))
                             }
                             None => {
                                 // 通道关闭，发送错误然后结束流
-                                let error_json = serde_json::json!({
-                                    "error": {
-                                        "message": "Request was cancelled"
-                                    }
-                                });
+                                let error_json = RelayError::Internal("Request was cancelled".to_string()).to_json();
                                 Some((Ok(bytes::Bytes::from(error_json.to_string())), (result_rx, true)))
                             }
                         }
@@ -679,7 +3596,7 @@ impl LoadBalancedHandler {
             .header("Cache-Control", "no-cache")
             .header("Transfer-Encoding", "chunked")
             .body(body)
-            .map_err(|e| anyhow::anyhow!("Failed to build response: {}", e))?;
+            .map_err(|e| UpstreamError::Request(format!("Failed to build response: {}", e)))?;
 
         Ok(response)
     }
@@ -688,6 +3605,7 @@ impl LoadBalancedHandler {
     /// 处理流式请求（兜底方法，当重试失败时使用）
     async fn handle_streaming_request(
         &self,
+        endpoint: &'static str,
         client: OpenAIClient,
         headers: reqwest::header::HeaderMap,
         body: Value,
@@ -696,7 +3614,18 @@ impl LoadBalancedHandler {
     ) -> Sse<futures::stream::BoxStream<'static, Result<Event, std::convert::Infallible>>> {
         // 尝试请求，如果失败则返回错误流
         match self
-            .try_streaming_request(client, headers, body, selected_backend, start_time)
+            .try_streaming_request(
+                endpoint,
+                UpstreamAttempt {
+                    selected_backend,
+                    client,
+                    headers,
+                    body,
+                    permit: ConcurrencyPermit::default(),
+                },
+                start_time,
+                false,
+            )
             .await
         {
             Ok(sse) => sse,
@@ -724,6 +3653,7 @@ impl LoadBalancedHandler {
     /// 处理非流式请求（兜底方法，当重试失败时使用）
     async fn handle_non_streaming_request(
         &self,
+        endpoint: &'static str,
         client: OpenAIClient,
         headers: reqwest::header::HeaderMap,
         body: Value,
@@ -732,7 +3662,7 @@ impl LoadBalancedHandler {
     ) -> Json<Value> {
         // 尝试请求，如果失败则返回错误响应
         match self
-            .try_non_streaming_request(client, headers, body, selected_backend, start_time)
+            .try_non_streaming_request(endpoint, client, headers, body, selected_backend, start_time)
             .await
         {
             Ok(response) => response,
@@ -746,17 +3676,12 @@ impl LoadBalancedHandler {
     }
 
     /// 获取可用模型列表（根据用户权限过滤）
-    pub async fn handle_models_for_user(&self, user_models: Vec<String>) -> Json<Value> {
+    pub async fn handle_models_for_user(&self, user: Option<&UserToken>, user_models: Vec<String>) -> Json<Value> {
+        let config = self.load_balancer.get_config();
+        let model_health = self.load_balancer.get_model_health();
         let model_list: Vec<Value> = user_models
-            .into_iter()
-            .map(|model_name| {
-                json!({
-                    "id": model_name,
-                    "object": "model",
-                    "created": chrono::Utc::now().timestamp(),
-                    "owned_by": "berry-api"
-                })
-            })
+            .iter()
+            .filter_map(|model_name| Self::model_object(&config, &model_health, user, model_name))
             .collect();
 
         Json(json!({
@@ -764,4 +3689,48 @@ impl LoadBalancedHandler {
             "data": model_list
         }))
     }
+
+    /// 获取单个模型的信息，用户无权访问或模型不可用时返回404
+    pub async fn handle_model_for_user(&self, user: &UserToken, model_name: &str) -> axum::response::Response {
+        let config = self.load_balancer.get_config();
+        if !config.get_user_available_models(user).iter().any(|m| m == model_name) {
+            return RelayError::ModelNotFound(model_name.to_string()).into_response();
+        }
+
+        let model_health = self.load_balancer.get_model_health();
+        match Self::model_object(&config, &model_health, Some(user), model_name) {
+            Some(model) => Json(model).into_response(),
+            None => RelayError::ModelNotFound(model_name.to_string()).into_response(),
+        }
+    }
+
+    /// 构建OpenAI格式的模型对象：owned_by为模型映射ID，berry扩展字段包含负载均衡策略和后端健康情况
+    fn model_object(
+        config: &crate::config::model::Config,
+        model_health: &std::collections::HashMap<String, crate::loadbalance::ModelHealth>,
+        user: Option<&UserToken>,
+        model_name: &str,
+    ) -> Option<Value> {
+        // 用户别名优先，其次按模型ID或对外名称查找，租户用户查找本租户的模型
+        let internal_name = user.and_then(|user| user.tenant_model_name(model_name));
+        let (model_id, mapping) = user
+            .and_then(|user| user.model_aliases.get(model_name))
+            .and_then(|model_id| config.models.get_key_value(model_id))
+            .or_else(|| config.resolve_model(internal_name.as_deref().unwrap_or(model_name)))?;
+        let health = model_health.get(model_id);
+
+        Some(json!({
+            "id": model_name,
+            "object": "model",
+            "created": chrono::Utc::now().timestamp(),
+            "owned_by": user.map_or(model_id.as_str(), |user| user.visible_model_name(model_id)),
+            "berry": {
+                "model": user.map_or(mapping.name.as_str(), |user| user.visible_model_name(&mapping.name)),
+                "strategy": mapping.strategy,
+                "state": health.map(|h| h.state),
+                "healthy_backends": health.map(|h| h.healthy_backends).unwrap_or(0),
+                "total_backends": mapping.backends.iter().filter(|b| b.enabled).count(),
+            }
+        }))
+    }
 }
@@ -5,23 +5,13 @@ use crate::relay::client::ClientError;
 
 // 创建错误事件
 pub fn create_error_event(error: &ClientError) -> Event {
-    Event::default().data(
-        json!({
-            "error": {
-                "message": error.to_string()
-            }
-        })
-        .to_string(),
-    )
+    Event::default().data(create_error_json(error).to_string())
 }
 
 // 创建错误 JSON 响应
 pub fn create_error_json(error: &ClientError) -> Value {
-    json!({
-        "error": {
-            "message": error.to_string()
-        }
-    })
+    let error_type = ErrorType::from_client_error(error);
+    error_json(&error_type, error_type.default_code(), &error.to_string(), None)
 }
 
 // 创建网络错误 JSON 响应
@@ -53,16 +43,22 @@ pub enum ErrorType {
     BadRequest,
     /// 认证错误 - 401 Unauthorized
     Unauthorized,
+    /// 超出预算 - 402 Payment Required
+    PaymentRequired,
     /// 权限错误 - 403 Forbidden
     Forbidden,
     /// 资源未找到 - 404 Not Found
     NotFound,
     /// 请求超时 - 408 Request Timeout
     RequestTimeout,
+    /// 请求体过大 - 413 Payload Too Large
+    PayloadTooLarge,
     /// 请求过多 - 429 Too Many Requests
     TooManyRequests,
     /// 服务器内部错误 - 500 Internal Server Error
     InternalServerError,
+    /// 上游错误 - 502 Bad Gateway
+    BadGateway,
     /// 服务不可用 - 503 Service Unavailable
     ServiceUnavailable,
     /// 网关超时 - 504 Gateway Timeout
@@ -75,16 +71,71 @@ impl ErrorType {
         match self {
             ErrorType::BadRequest => StatusCode::BAD_REQUEST,
             ErrorType::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorType::PaymentRequired => StatusCode::PAYMENT_REQUIRED,
             ErrorType::Forbidden => StatusCode::FORBIDDEN,
             ErrorType::NotFound => StatusCode::NOT_FOUND,
             ErrorType::RequestTimeout => StatusCode::REQUEST_TIMEOUT,
+            ErrorType::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
             ErrorType::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
             ErrorType::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorType::BadGateway => StatusCode::BAD_GATEWAY,
             ErrorType::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
             ErrorType::GatewayTimeout => StatusCode::GATEWAY_TIMEOUT,
         }
     }
 
+    /// OpenAI兼容的错误类型（error.type字段）
+    pub fn openai_type(&self) -> &'static str {
+        match self {
+            ErrorType::BadRequest | ErrorType::NotFound | ErrorType::PayloadTooLarge => {
+                "invalid_request_error"
+            }
+            ErrorType::Unauthorized => "authentication_error",
+            ErrorType::PaymentRequired => "insufficient_quota",
+            ErrorType::Forbidden => "permission_error",
+            ErrorType::RequestTimeout | ErrorType::GatewayTimeout => "timeout_error",
+            ErrorType::TooManyRequests => "rate_limit_error",
+            ErrorType::InternalServerError | ErrorType::BadGateway | ErrorType::ServiceUnavailable => {
+                "server_error"
+            }
+        }
+    }
+
+    /// 未指定更具体的错误码时使用的默认错误码（error.code字段）
+    pub fn default_code(&self) -> &'static str {
+        match self {
+            ErrorType::BadRequest => "invalid_request",
+            ErrorType::Unauthorized => "invalid_api_key",
+            ErrorType::PaymentRequired => "budget_exceeded",
+            ErrorType::Forbidden => "permission_denied",
+            ErrorType::NotFound => "not_found",
+            ErrorType::RequestTimeout => "request_timeout",
+            ErrorType::PayloadTooLarge => "request_too_large",
+            ErrorType::TooManyRequests => "rate_limit_exceeded",
+            ErrorType::InternalServerError => "internal_error",
+            ErrorType::BadGateway => "upstream_error",
+            ErrorType::ServiceUnavailable => "service_unavailable",
+            ErrorType::GatewayTimeout => "gateway_timeout",
+        }
+    }
+
+    /// 根据客户端错误的类型确定错误类型
+    pub fn from_client_error(error: &ClientError) -> Self {
+        match error {
+            ClientError::HeaderParseError(_) => ErrorType::BadRequest,
+            ClientError::RequestError(e) if e.is_timeout() => ErrorType::GatewayTimeout,
+            ClientError::RequestError(_) | ClientError::JsonParseError(_) => ErrorType::BadGateway,
+            ClientError::TlsConfigError(_) => ErrorType::InternalServerError,
+            ClientError::UpstreamError { status, .. } => match *status {
+                401 | 403 => ErrorType::BadGateway,
+                408 | 504 => ErrorType::GatewayTimeout,
+                429 => ErrorType::TooManyRequests,
+                400..=499 => ErrorType::BadRequest,
+                _ => ErrorType::BadGateway,
+            },
+        }
+    }
+
     /// 根据错误消息内容推断错误类型
     pub fn from_error_message(message: &str) -> Self {
         let message_lower = message.to_lowercase();
@@ -109,37 +160,29 @@ impl ErrorType {
     }
 }
 
-/// 创建带有正确HTTP状态码的错误响应
-pub fn create_error_response(error_type: ErrorType, message: &str, details: Option<String>) -> impl IntoResponse {
-    let status_code = error_type.status_code();
-    let error_json = json!({
+/// OpenAI兼容的错误响应体，额外附带HTTP状态码和错误详情
+pub fn error_json(error_type: &ErrorType, code: &str, message: &str, details: Option<String>) -> Value {
+    json!({
         "error": {
             "message": message,
-            "type": format!("{:?}", error_type),
-            "status": status_code.as_u16(),
+            "type": error_type.openai_type(),
+            "param": Value::Null,
+            "code": code,
+            "status": error_type.status_code().as_u16(),
             "details": details,
         }
-    });
+    })
+}
 
-    (status_code, Json(error_json))
+/// 创建带有正确HTTP状态码的错误响应
+pub fn create_error_response(error_type: ErrorType, message: &str, details: Option<String>) -> impl IntoResponse {
+    let error_json = error_json(&error_type, error_type.default_code(), message, details);
+    (error_type.status_code(), Json(error_json))
 }
 
 /// 创建带有正确HTTP状态码的错误响应（从ClientError）
 pub fn create_client_error_response(error: &ClientError) -> impl IntoResponse {
-    let message = error.to_string();
-    let error_type = ErrorType::from_error_message(&message);
-
-    let status_code = error_type.status_code();
-    let error_json = json!({
-        "error": {
-            "message": message,
-            "type": format!("{:?}", error_type),
-            "status": status_code.as_u16(),
-            "details": Option::<String>::None,
-        }
-    });
-
-    (status_code, Json(error_json))
+    create_error_response(ErrorType::from_client_error(error), &error.to_string(), None).into_response()
 }
 
 /// 创建服务不可用错误响应
@@ -0,0 +1,147 @@
+use axum::body::{Body, BodyDataStream, Bytes};
+use axum::http::{StatusCode, header};
+use axum::response::Response;
+use futures::StreamExt;
+use futures::future::BoxFuture;
+use std::time::Duration;
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+/// 心跳使用的SSE注释，标准SSE客户端会忽略注释行
+pub const HEARTBEAT_COMMENT: &[u8] = b": ping\n\n";
+
+enum HeartbeatState {
+    /// 等待上游响应
+    Waiting(BoxFuture<'static, Response>, Interval),
+    /// 转发上游响应体，收到首个数据事件前继续发送心跳
+    Forwarding(BodyDataStream, Option<Interval>),
+    Done,
+}
+
+/// 为流式请求加上心跳：请求在一个心跳间隔内完成且不是SSE响应时原样返回（包括错误状态码），
+/// 否则返回SSE响应，在上游返回首个数据事件前每隔一个间隔发送`: ping`注释。
+/// 超过一个间隔才出现的错误响应以`data: {"error":...}`事件的形式写入流中
+pub async fn with_heartbeat(mut response: BoxFuture<'static, Response>, interval: Duration) -> Response {
+    let ready = tokio::select! {
+        response = &mut response => Some(response),
+        _ = tokio::time::sleep(interval) => None,
+    };
+
+    let (parts, state) = match ready {
+        Some(response) if !is_event_stream(&response) => return response,
+        // 已经返回响应头，但首个token可能还要等待一段时间
+        Some(response) => {
+            let (parts, body) = response.into_parts();
+            let ticker = heartbeat_ticker(Instant::now() + interval, interval);
+            (Some(parts), HeartbeatState::Forwarding(body.into_data_stream(), Some(ticker)))
+        }
+        None => (None, HeartbeatState::Waiting(response, heartbeat_ticker(Instant::now(), interval))),
+    };
+
+    let stream = futures::stream::unfold(state, |state| async move {
+        match state {
+            HeartbeatState::Waiting(mut response, mut ticker) => tokio::select! {
+                response = &mut response => Some(into_stream_state(response, ticker).await),
+                _ = ticker.tick() => Some((Ok(Bytes::from_static(HEARTBEAT_COMMENT)), HeartbeatState::Waiting(response, ticker))),
+            },
+            HeartbeatState::Forwarding(mut body, Some(mut ticker)) => tokio::select! {
+                chunk = body.next() => match chunk {
+                    Some(Ok(bytes)) => {
+                        // 收到数据事件后上游已经开始输出，不再需要心跳
+                        let ticker = (!contains_data(&bytes)).then_some(ticker);
+                        Some((Ok(bytes), HeartbeatState::Forwarding(body, ticker)))
+                    }
+                    Some(Err(e)) => Some((Err(e), HeartbeatState::Done)),
+                    None => None,
+                },
+                _ = ticker.tick() => Some((Ok(Bytes::from_static(HEARTBEAT_COMMENT)), HeartbeatState::Forwarding(body, Some(ticker)))),
+            },
+            HeartbeatState::Forwarding(mut body, None) => {
+                let chunk = body.next().await?;
+                Some((chunk, HeartbeatState::Forwarding(body, None)))
+            }
+            HeartbeatState::Done => None,
+        }
+    });
+
+    match parts {
+        Some(parts) => Response::from_parts(parts, Body::from_stream(stream)),
+        None => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(Body::from_stream(stream))
+            .unwrap(),
+    }
+}
+
+fn heartbeat_ticker(start: Instant, interval: Duration) -> Interval {
+    let mut ticker = tokio::time::interval_at(start, interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker
+}
+
+fn is_event_stream(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"))
+}
+
+/// 上游响应到达：SSE响应继续转发，其他响应（通常是错误）读出后作为一个数据事件发送
+async fn into_stream_state(response: Response, ticker: Interval) -> (Result<Bytes, axum::Error>, HeartbeatState) {
+    if is_event_stream(&response) {
+        return (Ok(Bytes::new()), HeartbeatState::Forwarding(response.into_body().into_data_stream(), Some(ticker)));
+    }
+
+    match axum::body::to_bytes(response.into_body(), usize::MAX).await {
+        Ok(body) => {
+            let body = String::from_utf8_lossy(body.trim_ascii()).replace('\n', "");
+            (Ok(Bytes::from(format!("data: {}\n\n", body))), HeartbeatState::Done)
+        }
+        Err(e) => (Err(e), HeartbeatState::Done),
+    }
+}
+
+fn contains_data(bytes: &[u8]) -> bool {
+    bytes.split(|b| *b == b'\n').any(|line| line.starts_with(b"data:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+    use serde_json::json;
+
+    async fn body_text(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fast_response_is_returned_unchanged() {
+        let response = with_heartbeat(
+            Box::pin(async { (StatusCode::TOO_MANY_REQUESTS, "slow down").into_response() }),
+            Duration::from_millis(200),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body_text(response).await, "slow down");
+    }
+
+    #[tokio::test]
+    async fn test_slow_error_is_sent_as_stream_event() {
+        let response = with_heartbeat(
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_millis(130)).await;
+                (StatusCode::BAD_GATEWAY, axum::Json(json!({"error": {"message": "boom"}}))).into_response()
+            }),
+            Duration::from_millis(50),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = body_text(response).await;
+        assert!(text.starts_with(": ping\n\n"));
+        assert!(text.ends_with("data: {\"error\":{\"message\":\"boom\"}}\n\n"));
+    }
+}
@@ -1,2 +1,30 @@
+pub mod admission;
+pub mod anthropic;
+pub mod audit;
+pub mod cache;
+pub mod capture;
 pub mod client;
+pub mod concurrency;
+pub mod debug;
+pub mod error;
+pub mod fair_queue;
+pub mod fault;
+pub mod files;
+pub mod filter;
 pub mod handler;
+pub mod heartbeat;
+pub mod moderation;
+pub mod peer;
+pub mod rate_limit;
+pub mod realtime;
+pub mod request_log;
+pub mod resume;
+pub mod responses;
+pub mod semantic_cache;
+pub mod structured_output;
+pub mod timeouts;
+pub mod tokens;
+pub mod tool_calls;
+pub mod transform;
+pub mod user_limits;
+pub mod wasm;
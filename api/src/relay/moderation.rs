@@ -0,0 +1,263 @@
+use crate::config::model::{Config, ModerationAction, ModerationService, ModerationSettings};
+use crate::relay::client::openai::MODERATIONS_PATH;
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 一次审核的结果，写入审计日志
+#[derive(Debug, Clone, Serialize)]
+pub struct ModerationVerdict {
+    /// 是否命中（配置了categories时只看这些类别）
+    pub flagged: bool,
+    /// 命中的类别
+    pub categories: Vec<String>,
+    /// 是否因此拒绝了请求
+    pub blocked: bool,
+}
+
+impl ModerationVerdict {
+    /// 根据审核服务返回的results计算结果，任一条输入命中即视为命中
+    pub fn from_response(settings: &ModerationSettings, response: &Value) -> Result<Self> {
+        let results = response
+            .get("results")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow::anyhow!("Moderation response missing results"))?;
+
+        let mut flagged = false;
+        let mut categories = Vec::new();
+        for result in results {
+            let hits = result
+                .get("categories")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+                .filter(|(_, hit)| hit.as_bool() == Some(true))
+                .map(|(category, _)| category.clone());
+            if settings.categories.is_empty() {
+                flagged |= result.get("flagged").and_then(Value::as_bool).unwrap_or(false);
+                categories.extend(hits);
+            } else {
+                categories.extend(hits.filter(|category| settings.categories.contains(category)));
+                flagged |= !categories.is_empty();
+            }
+        }
+        categories.sort();
+        categories.dedup();
+
+        Ok(Self {
+            flagged,
+            categories,
+            blocked: flagged && settings.action == ModerationAction::Block,
+        })
+    }
+
+    /// 返回给客户端的拒绝原因
+    pub fn reason(&self) -> String {
+        if self.categories.is_empty() {
+            "The request was flagged by content moderation".to_string()
+        } else {
+            format!("The request was flagged by content moderation: {}", self.categories.join(", "))
+        }
+    }
+}
+
+/// 提取需要审核的用户输入：user角色消息的文本内容和补全接口的prompt
+pub fn moderation_input(body: &Value) -> Vec<String> {
+    fn texts(content: &Value, out: &mut Vec<String>) {
+        match content {
+            Value::String(text) if !text.trim().is_empty() => out.push(text.clone()),
+            // 多模态内容只取文本部分
+            Value::Array(parts) => {
+                for part in parts {
+                    match part {
+                        Value::Object(object) => {
+                            if let Some(text) = object.get("text") {
+                                texts(text, out);
+                            }
+                        }
+                        other => texts(other, out),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut input = Vec::new();
+    if let Some(messages) = body.get("messages").and_then(Value::as_array) {
+        for message in messages {
+            if message.get("role").and_then(Value::as_str) == Some("user")
+                && let Some(content) = message.get("content")
+            {
+                texts(content, &mut input);
+            }
+        }
+    }
+    if let Some(prompt) = body.get("prompt") {
+        texts(prompt, &mut input);
+    }
+    input
+}
+
+/// 审核统计
+#[derive(Debug, Clone, Serialize)]
+pub struct ModerationStats {
+    /// 完成审核的请求数
+    pub checked: u64,
+    /// 命中的请求数
+    pub flagged: u64,
+    /// 被拒绝的请求数
+    pub blocked: u64,
+    /// 审核服务出错的次数
+    pub errors: u64,
+}
+
+/// 请求前的内容审核
+pub struct ModerationGuard {
+    client: reqwest::Client,
+    checked: AtomicU64,
+    flagged: AtomicU64,
+    blocked: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl ModerationGuard {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            checked: AtomicU64::new(0),
+            flagged: AtomicU64::new(0),
+            blocked: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    /// 审核请求，请求中没有用户输入时返回None
+    pub async fn check(&self, config: &Config, settings: &ModerationSettings, body: &Value) -> Result<Option<ModerationVerdict>> {
+        let input = moderation_input(body);
+        if input.is_empty() {
+            return Ok(None);
+        }
+        let result = self.request(config, settings, input).await;
+        match &result {
+            Ok(verdict) => {
+                self.checked.fetch_add(1, Ordering::Relaxed);
+                if verdict.flagged {
+                    self.flagged.fetch_add(1, Ordering::Relaxed);
+                }
+                if verdict.blocked {
+                    self.blocked.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            Err(_) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        result.map(Some)
+    }
+
+    async fn request(&self, config: &Config, settings: &ModerationSettings, input: Vec<String>) -> Result<ModerationVerdict> {
+        let (url, api_key) = match &settings.service {
+            ModerationService::Endpoint { url, api_key } => (url.clone(), api_key.clone()),
+            ModerationService::Provider { provider } => {
+                let provider = config
+                    .providers
+                    .get(provider)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown moderation provider '{}'", provider))?;
+                (
+                    format!("{}{}", provider.base_url.trim_end_matches('/'), MODERATIONS_PATH),
+                    Some(provider.api_key.clone()),
+                )
+            }
+        };
+
+        let mut body = json!({ "input": input });
+        if let Some(model) = &settings.model {
+            body["model"] = json!(model);
+        }
+        let mut request = self
+            .client
+            .post(url)
+            .timeout(Duration::from_secs(settings.timeout_seconds))
+            .json(&body);
+        if let Some(api_key) = api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            anyhow::bail!("Moderation request failed with status {}", response.status());
+        }
+        let value: Value = response.json().await?;
+        ModerationVerdict::from_response(settings, &value)
+    }
+
+    pub fn stats(&self) -> ModerationStats {
+        ModerationStats {
+            checked: self.checked.load(Ordering::Relaxed),
+            flagged: self.flagged.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for ModerationGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(action: ModerationAction, categories: &[&str]) -> ModerationSettings {
+        ModerationSettings {
+            service: ModerationService::Endpoint {
+                url: "http://localhost/moderations".to_string(),
+                api_key: None,
+            },
+            model: None,
+            action,
+            categories: categories.iter().map(|c| c.to_string()).collect(),
+            timeout_seconds: 5,
+            fail_open: true,
+        }
+    }
+
+    #[test]
+    fn test_verdict_respects_categories_and_action() {
+        let response = json!({
+            "results": [{"flagged": true, "categories": {"violence": true, "harassment": false, "hate": true}}]
+        });
+
+        let verdict = ModerationVerdict::from_response(&settings(ModerationAction::Block, &[]), &response).unwrap();
+        assert!(verdict.flagged && verdict.blocked);
+        assert_eq!(verdict.categories, vec!["hate", "violence"]);
+
+        let verdict = ModerationVerdict::from_response(&settings(ModerationAction::Flag, &["violence"]), &response).unwrap();
+        assert!(verdict.flagged && !verdict.blocked);
+        assert_eq!(verdict.categories, vec!["violence"]);
+
+        let verdict = ModerationVerdict::from_response(&settings(ModerationAction::Block, &["harassment"]), &response).unwrap();
+        assert!(!verdict.flagged && !verdict.blocked);
+
+        assert!(ModerationVerdict::from_response(&settings(ModerationAction::Block, &[]), &json!({})).is_err());
+    }
+
+    #[test]
+    fn test_input_contains_only_user_text() {
+        let body = json!({
+            "messages": [
+                {"role": "system", "content": "be nice"},
+                {"role": "user", "content": "hello"},
+                {"role": "assistant", "content": "hi"},
+                {"role": "user", "content": [{"type": "text", "text": "look"}, {"type": "image_url", "image_url": {"url": "x"}}]}
+            ]
+        });
+        assert_eq!(moderation_input(&body), vec!["hello", "look"]);
+        assert_eq!(moderation_input(&json!({"prompt": ["a", ""]})), vec!["a"]);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::config::model::{Config, Peer};
+use axum::body::Body;
+use axum::response::Response;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// 记录请求已在berry实例间转发次数的请求头
+pub const FORWARD_HOPS_HEADER: &str = "x-berry-forward-hops";
+
+/// 对等实例健康状态
+#[derive(Debug, Clone, Default)]
+struct PeerState {
+    consecutive_failures: u32,
+    unhealthy_since: Option<Instant>,
+    last_error: Option<String>,
+    forwarded_requests: u64,
+}
+
+/// 对等实例状态快照
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerStatus {
+    pub name: String,
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub forwarded_requests: u64,
+}
+
+/// 对等实例转发器
+/// 本地无法服务某个模型时，将请求转发给声明支持该模型的对等berry实例
+pub struct PeerForwarder {
+    client: reqwest::Client,
+    states: std::sync::RwLock<HashMap<String, PeerState>>,
+}
+
+impl PeerForwarder {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            states: std::sync::RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 从请求头中读取已转发的跳数
+    pub fn incoming_hops(headers: &axum::http::HeaderMap) -> u32 {
+        headers
+            .get(FORWARD_HOPS_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// 对等实例是否可用（熔断超时后允许重新尝试）
+    fn is_peer_available(&self, peer_id: &str, config: &Config) -> bool {
+        let threshold = config.settings.circuit_breaker_failure_threshold.max(1);
+        let retry_after = Duration::from_secs(config.settings.circuit_breaker_timeout_seconds);
+
+        let Ok(states) = self.states.read() else {
+            return true;
+        };
+        match states.get(peer_id) {
+            Some(state) if state.consecutive_failures >= threshold => state
+                .unhealthy_since
+                .is_some_and(|since| since.elapsed() >= retry_after),
+            _ => true,
+        }
+    }
+
+    /// 获取可以服务指定模型的对等实例，健康的实例优先
+    fn candidate_peers<'a>(&self, config: &'a Config, model_name: &str) -> Vec<(&'a String, &'a Peer)> {
+        let mut peers: Vec<_> = config
+            .peers
+            .iter()
+            .filter(|(_, peer)| peer.enabled && peer.models.iter().any(|m| m == model_name))
+            .filter(|(peer_id, _)| self.is_peer_available(peer_id, config))
+            .collect();
+        peers.sort_by_key(|(peer_id, _)| {
+            let failures = self
+                .states
+                .read()
+                .ok()
+                .and_then(|s| s.get(*peer_id).map(|state| state.consecutive_failures))
+                .unwrap_or(0);
+            (failures, (*peer_id).clone())
+        });
+        peers
+    }
+
+    /// 是否存在可以转发该模型的对等实例
+    pub fn can_forward(&self, config: &Config, model_name: &str, hops: u32) -> bool {
+        hops < config.settings.peer_max_hops && !self.candidate_peers(config, model_name).is_empty()
+    }
+
+    fn record_success(&self, peer_id: &str) {
+        if let Ok(mut states) = self.states.write() {
+            let state = states.entry(peer_id.to_string()).or_default();
+            if state.consecutive_failures > 0 {
+                info!("Peer '{}' recovered", peer_id);
+            }
+            state.consecutive_failures = 0;
+            state.unhealthy_since = None;
+            state.forwarded_requests += 1;
+        }
+    }
+
+    fn record_failure(&self, peer_id: &str, error: String, threshold: u32) {
+        if let Ok(mut states) = self.states.write() {
+            let state = states.entry(peer_id.to_string()).or_default();
+            state.consecutive_failures += 1;
+            state.last_error = Some(error);
+            if state.consecutive_failures >= threshold {
+                if state.unhealthy_since.is_none() {
+                    warn!("Peer '{}' marked unhealthy after {} failures", peer_id, state.consecutive_failures);
+                }
+                // 每次失败都重新计时，熔断超时后才会再次尝试
+                state.unhealthy_since = Some(Instant::now());
+            }
+        }
+    }
+
+    /// 将聊天请求转发给对等实例，所有候选实例都失败时返回None
+    pub async fn forward_chat_completions(
+        &self,
+        config: &Config,
+        model_name: &str,
+        request_headers: &axum::http::HeaderMap,
+        body: &Value,
+        hops: u32,
+    ) -> Option<Response> {
+        if hops >= config.settings.peer_max_hops {
+            debug!("Request already forwarded {} times, not forwarding model '{}' again", hops, model_name);
+            return None;
+        }
+
+        let threshold = config.settings.circuit_breaker_failure_threshold.max(1);
+        for (peer_id, peer) in self.candidate_peers(config, model_name) {
+            let url = format!("{}/v1/chat/completions", peer.base_url.trim_end_matches('/'));
+            info!("Forwarding model '{}' to peer '{}' ({})", model_name, peer_id, url);
+
+            let mut request = self
+                .client
+                .post(&url)
+                .bearer_auth(&peer.api_key)
+                .header(FORWARD_HOPS_HEADER, (hops + 1).to_string())
+                .timeout(Duration::from_secs(peer.timeout_seconds))
+                .json(body);
+            if let Some(session) = request_headers.get(crate::relay::handler::loadbalanced::SESSION_ID_HEADER) {
+                request = request.header(crate::relay::handler::loadbalanced::SESSION_ID_HEADER, session.clone());
+            }
+            if let Some(tags) = request_headers.get(crate::relay::handler::loadbalanced::BACKEND_TAGS_HEADER) {
+                request = request.header(crate::relay::handler::loadbalanced::BACKEND_TAGS_HEADER, tags.clone());
+            }
+            let mut trace_headers = reqwest::header::HeaderMap::new();
+            crate::telemetry::inject_trace_context(&mut trace_headers);
+            crate::telemetry::inject_request_id(&mut trace_headers);
+            request = request.headers(trace_headers);
+
+            match request.send().await {
+                // 对等实例自身的5xx视为不可用，继续尝试下一个
+                Ok(response) if response.status().is_server_error() => {
+                    let error = format!("HTTP {}", response.status());
+                    warn!("Peer '{}' failed for model '{}': {}", peer_id, model_name, error);
+                    self.record_failure(peer_id, error, threshold);
+                }
+                Ok(response) => {
+                    self.record_success(peer_id);
+                    return Some(Self::into_response(response));
+                }
+                Err(e) => {
+                    warn!("Peer '{}' unreachable for model '{}': {}", peer_id, model_name, e);
+                    self.record_failure(peer_id, e.to_string(), threshold);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// 将对等实例的响应原样（含流式响应）转换为本地响应
+    fn into_response(response: reqwest::Response) -> Response {
+        let status = axum::http::StatusCode::from_u16(response.status().as_u16())
+            .unwrap_or(axum::http::StatusCode::BAD_GATEWAY);
+        let mut builder = Response::builder().status(status);
+        for name in [reqwest::header::CONTENT_TYPE, reqwest::header::CACHE_CONTROL] {
+            if let Some(value) = response.headers().get(&name) {
+                builder = builder.header(name.as_str(), value.as_bytes());
+            }
+        }
+        builder
+            .body(Body::from_stream(response.bytes_stream()))
+            .unwrap_or_else(|_| Response::new(Body::empty()))
+    }
+
+    /// 获取所有已配置对等实例的状态
+    pub fn get_status(&self, config: &Config) -> Vec<PeerStatus> {
+        let states = self.states.read().map(|s| s.clone()).unwrap_or_default();
+        let mut statuses: Vec<PeerStatus> = config
+            .peers
+            .keys()
+            .map(|peer_id| {
+                let state = states.get(peer_id).cloned().unwrap_or_default();
+                PeerStatus {
+                    name: peer_id.clone(),
+                    healthy: state.unhealthy_since.is_none(),
+                    consecutive_failures: state.consecutive_failures,
+                    last_error: state.last_error,
+                    forwarded_requests: state.forwarded_requests,
+                }
+            })
+            .collect();
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+}
+
+impl Default for PeerForwarder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::model::GlobalSettings;
+
+    fn config_with_peer() -> Config {
+        let mut peers = HashMap::new();
+        peers.insert("eu".to_string(), Peer {
+            name: "EU".to_string(),
+            base_url: "http://127.0.0.1:1".to_string(),
+            api_key: "peer-token".to_string(),
+            models: vec!["claude-eu".to_string()],
+            enabled: true,
+            timeout_seconds: 1,
+        });
+
+        Config {
+            providers: HashMap::new(),
+            models: HashMap::new(),
+            users: HashMap::new(),
+            peers,
+            tenants: HashMap::new(),
+            settings: GlobalSettings {
+                circuit_breaker_failure_threshold: 2,
+                ..GlobalSettings::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_hops_prevent_loops() {
+        let forwarder = PeerForwarder::new();
+        let config = config_with_peer();
+
+        assert!(forwarder.can_forward(&config, "claude-eu", 0));
+        assert!(!forwarder.can_forward(&config, "claude-eu", 1));
+        assert!(!forwarder.can_forward(&config, "other-model", 0));
+
+        let mut headers = axum::http::HeaderMap::new();
+        assert_eq!(PeerForwarder::incoming_hops(&headers), 0);
+        headers.insert(FORWARD_HOPS_HEADER, "1".parse().unwrap());
+        assert_eq!(PeerForwarder::incoming_hops(&headers), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_peer_marked_unhealthy() {
+        let forwarder = PeerForwarder::new();
+        let config = config_with_peer();
+        let headers = axum::http::HeaderMap::new();
+        let body = serde_json::json!({"model": "claude-eu", "messages": []});
+
+        for _ in 0..2 {
+            let response = forwarder
+                .forward_chat_completions(&config, "claude-eu", &headers, &body, 0)
+                .await;
+            assert!(response.is_none());
+        }
+
+        let status = forwarder.get_status(&config);
+        assert!(!status[0].healthy);
+        assert_eq!(status[0].consecutive_failures, 2);
+        // 熔断期间不再作为候选
+        assert!(!forwarder.can_forward(&config, "claude-eu", 0));
+    }
+}
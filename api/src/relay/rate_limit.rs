@@ -0,0 +1,346 @@
+use crate::config::model::{RateLimit, SharedStateSettings};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// 记录token用量时不检查余量，允许桶内余额为负（后续请求需等待补充）
+const UNCONDITIONAL: f64 = -1e300;
+
+/// 连接Redis未成功时，在该间隔内不再尝试连接，请求直接使用本实例内的令牌桶
+const REDIS_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Redis令牌桶脚本：先检查所有桶，全部满足后再一起扣除，返回0表示放行，否则返回首个不满足的桶序号（从1开始）
+/// 每个桶对应4个参数：容量、窗口（毫秒）、扣除量、所需最小余量
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+redis.replicate_commands()
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local balances = {}
+for i, key in ipairs(KEYS) do
+  local base = (i - 1) * 4
+  local capacity = tonumber(ARGV[base + 1])
+  local window = tonumber(ARGV[base + 2])
+  local bucket = redis.call('HMGET', key, 'tokens', 'ts')
+  local tokens = tonumber(bucket[1]) or capacity
+  local ts = tonumber(bucket[2]) or now
+  tokens = math.min(capacity, tokens + math.max(0, now - ts) * capacity / window)
+  if tokens < tonumber(ARGV[base + 4]) then
+    return i
+  end
+  balances[i] = tokens
+end
+for i, key in ipairs(KEYS) do
+  local base = (i - 1) * 4
+  local capacity = tonumber(ARGV[base + 1])
+  local window = tonumber(ARGV[base + 2])
+  local tokens = balances[i] - tonumber(ARGV[base + 3])
+  redis.call('HSET', key, 'tokens', tostring(tokens), 'ts', now)
+  redis.call('PEXPIRE', key, math.ceil((capacity - tokens) * window / capacity) + 1000)
+end
+return 0
+"#;
+
+/// 超出限流时的错误
+#[derive(Debug, thiserror::Error)]
+#[error("Rate limit exceeded: {0}")]
+pub struct RateLimitExceeded(pub String);
+
+/// 单个令牌桶：容量为窗口内的上限，按 容量/窗口 的速率匀速补充
+#[derive(Debug, Clone, Copy)]
+struct BucketSpec {
+    name: &'static str,
+    capacity: f64,
+    window: Duration,
+    cost: f64,
+    min_required: f64,
+}
+
+impl BucketSpec {
+    fn describe(&self) -> String {
+        let unit = if self.name == "tpm" { "tokens" } else { "requests" };
+        let period = match self.window.as_secs() {
+            60 => "minute",
+            3600 => "hour",
+            _ => "day",
+        };
+        format!("{} {} per {}", self.capacity, unit, period)
+    }
+}
+
+struct LocalBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// 共享令牌桶使用的Redis连接，以及最近一次尚未成功的连接尝试
+#[derive(Default)]
+struct RedisState {
+    connection: Option<(String, redis::aio::ConnectionManager)>,
+    last_attempt: Option<(String, Instant)>,
+}
+
+/// 用户请求限流（令牌桶）
+/// 配置了shared_state时在Redis中用Lua脚本原子更新，所有实例共享同一组令牌桶；
+/// 未配置或Redis不可用时退回本实例内的令牌桶
+pub struct RateLimiter {
+    local: std::sync::Mutex<HashMap<String, LocalBucket>>,
+    redis: std::sync::Mutex<RedisState>,
+    script: redis::Script,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self {
+            local: std::sync::Mutex::new(HashMap::new()),
+            redis: std::sync::Mutex::new(RedisState::default()),
+            script: redis::Script::new(TOKEN_BUCKET_SCRIPT),
+        }
+    }
+
+    /// 检查并占用一次请求额度；配置了TPM时还要求token余量为正
+    pub async fn check_request(
+        &self,
+        shared: Option<&SharedStateSettings>,
+        user: &str,
+        limit: &RateLimit,
+    ) -> Result<(), RateLimitExceeded> {
+        let mut buckets: Vec<BucketSpec> = [
+            ("rpm", limit.requests_per_minute, 60),
+            ("rph", limit.requests_per_hour, 3600),
+            ("rpd", limit.requests_per_day, 86400),
+        ]
+        .into_iter()
+        .filter(|(_, capacity, _)| *capacity > 0)
+        .map(|(name, capacity, seconds)| BucketSpec {
+            name,
+            capacity: capacity as f64,
+            window: Duration::from_secs(seconds),
+            cost: 1.0,
+            min_required: 1.0,
+        })
+        .collect();
+        if let Some(bucket) = Self::token_bucket(limit, 0.0, f64::MIN_POSITIVE) {
+            buckets.push(bucket);
+        }
+
+        match self.take(shared, user, &buckets).await {
+            None => Ok(()),
+            Some(index) => Err(RateLimitExceeded(buckets[index].describe())),
+        }
+    }
+
+    /// 请求完成后按上游返回的usage扣除token额度
+    pub async fn record_tokens(
+        &self,
+        shared: Option<&SharedStateSettings>,
+        user: &str,
+        limit: &RateLimit,
+        tokens: u64,
+    ) {
+        if let Some(bucket) = Self::token_bucket(limit, tokens as f64, UNCONDITIONAL) {
+            self.take(shared, user, &[bucket]).await;
+        }
+    }
+
+    fn token_bucket(limit: &RateLimit, cost: f64, min_required: f64) -> Option<BucketSpec> {
+        limit
+            .tokens_per_minute
+            .filter(|tpm| *tpm > 0)
+            .map(|tpm| BucketSpec {
+                name: "tpm",
+                capacity: tpm as f64,
+                window: Duration::from_secs(60),
+                cost,
+                min_required,
+            })
+    }
+
+    /// 从各令牌桶扣除额度，返回首个余量不足的桶序号
+    async fn take(
+        &self,
+        shared: Option<&SharedStateSettings>,
+        user: &str,
+        buckets: &[BucketSpec],
+    ) -> Option<usize> {
+        if buckets.is_empty() {
+            return None;
+        }
+        if let Some(shared) = shared {
+            match self.take_shared(shared, user, buckets).await {
+                Ok(result) => return result,
+                Err(e) => tracing::warn!(
+                    "Shared rate limit unavailable, falling back to local limits: {}",
+                    e
+                ),
+            }
+        }
+        self.take_local(user, buckets)
+    }
+
+    async fn take_shared(
+        &self,
+        shared: &SharedStateSettings,
+        user: &str,
+        buckets: &[BucketSpec],
+    ) -> redis::RedisResult<Option<usize>> {
+        let mut connection = self.connection(&shared.redis_url).await?;
+        let mut invocation = self.script.prepare_invoke();
+        for bucket in buckets {
+            invocation
+                .key(format!("{}:ratelimit:{}:{}", shared.key_prefix, user, bucket.name))
+                .arg(bucket.capacity)
+                .arg(bucket.window.as_millis() as u64)
+                .arg(bucket.cost)
+                .arg(bucket.min_required);
+        }
+        let rejected: usize = invocation.invoke_async(&mut connection).await?;
+        Ok(rejected.checked_sub(1))
+    }
+
+    /// 获取Redis连接，地址变化（热加载）时重新连接
+    /// 连接在锁外建立；正在连接或连接失败后的重试间隔内直接返回错误，请求不等待连接
+    async fn connection(&self, url: &str) -> redis::RedisResult<redis::aio::ConnectionManager> {
+        {
+            let mut redis = self.redis.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((current, connection)) = &redis.connection
+                && current == url
+            {
+                return Ok(connection.clone());
+            }
+            if let Some((attempted, at)) = &redis.last_attempt
+                && attempted == url
+                && at.elapsed() < REDIS_RETRY_INTERVAL
+            {
+                return Err(redis::RedisError::from((
+                    redis::ErrorKind::IoError,
+                    "Redis is unavailable, waiting before reconnecting",
+                )));
+            }
+            redis.last_attempt = Some((url.to_string(), Instant::now()));
+        }
+
+        let config = redis::aio::ConnectionManagerConfig::new()
+            .set_connection_timeout(Duration::from_secs(1))
+            .set_response_timeout(Duration::from_secs(1))
+            .set_number_of_retries(1);
+        let connection = redis::Client::open(url)?
+            .get_connection_manager_with_config(config)
+            .await?;
+        let mut redis = self.redis.lock().unwrap_or_else(|e| e.into_inner());
+        redis.connection = Some((url.to_string(), connection.clone()));
+        redis.last_attempt = None;
+        Ok(connection)
+    }
+
+    fn take_local(&self, user: &str, buckets: &[BucketSpec]) -> Option<usize> {
+        let mut local = self.local.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let mut balances = Vec::with_capacity(buckets.len());
+        for (index, bucket) in buckets.iter().enumerate() {
+            let tokens = match local.get(&format!("{}:{}", user, bucket.name)) {
+                Some(state) => (state.tokens
+                    + now.duration_since(state.updated).as_secs_f64() * bucket.capacity
+                        / bucket.window.as_secs_f64())
+                .min(bucket.capacity),
+                None => bucket.capacity,
+            };
+            if tokens < bucket.min_required {
+                return Some(index);
+            }
+            balances.push(tokens);
+        }
+        for (bucket, tokens) in buckets.iter().zip(balances) {
+            local.insert(
+                format!("{}:{}", user, bucket.name),
+                LocalBucket {
+                    tokens: tokens - bucket.cost,
+                    updated: now,
+                },
+            );
+        }
+        None
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(rpm: u32, tpm: Option<u64>) -> RateLimit {
+        RateLimit {
+            requests_per_minute: rpm,
+            requests_per_hour: 0,
+            requests_per_day: 0,
+            tokens_per_minute: tpm,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_per_minute_is_enforced_per_user() {
+        let limiter = RateLimiter::new();
+        let limit = limit(2, None);
+
+        assert!(limiter.check_request(None, "alice", &limit).await.is_ok());
+        assert!(limiter.check_request(None, "alice", &limit).await.is_ok());
+        let error = limiter.check_request(None, "alice", &limit).await.unwrap_err();
+        assert!(error.to_string().contains("2 requests per minute"));
+        // 不同用户的额度相互独立
+        assert!(limiter.check_request(None, "bob", &limit).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_token_usage_blocks_until_refilled() {
+        let limiter = RateLimiter::new();
+        let limit = limit(0, Some(1000));
+
+        assert!(limiter.check_request(None, "alice", &limit).await.is_ok());
+        // 单个请求的用量可以超出剩余额度，之后的请求被拒绝
+        limiter.record_tokens(None, "alice", &limit, 1500).await;
+        let error = limiter.check_request(None, "alice", &limit).await.unwrap_err();
+        assert!(error.to_string().contains("1000 tokens per minute"));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_request_does_not_consume_other_buckets() {
+        let limiter = RateLimiter::new();
+        let limit = RateLimit {
+            requests_per_minute: 10,
+            requests_per_hour: 1,
+            requests_per_day: 0,
+            tokens_per_minute: None,
+        };
+
+        assert!(limiter.check_request(None, "alice", &limit).await.is_ok());
+        assert!(limiter.check_request(None, "alice", &limit).await.is_err());
+        let local = limiter.local.lock().unwrap();
+        assert_eq!(local["alice:rpm"].tokens, 9.0);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_redis_falls_back_to_local_limits() {
+        let limiter = RateLimiter::new();
+        let shared = SharedStateSettings {
+            redis_url: "redis://127.0.0.1:1/".to_string(),
+            key_prefix: "berry".to_string(),
+        };
+        let limit = limit(1, None);
+
+        assert!(limiter.check_request(Some(&shared), "alice", &limit).await.is_ok());
+        assert!(limiter.check_request(Some(&shared), "alice", &limit).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failed_redis_connection_is_not_retried_immediately() {
+        let limiter = RateLimiter::new();
+        let url = "redis://127.0.0.1:1/";
+
+        assert!(limiter.connection(url).await.is_err());
+        let error = limiter.connection(url).await.err().unwrap();
+        assert!(error.to_string().contains("waiting before reconnecting"));
+    }
+}
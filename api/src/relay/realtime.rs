@@ -0,0 +1,229 @@
+use crate::loadbalance::{SelectedBackend, TokenUsage};
+use anyhow::{Context, Result};
+use axum::extract::ws::{self, WebSocket};
+use futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::{self, http::HeaderValue};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// Realtime接口相对于provider base_url的路径
+pub const REALTIME_PATH: &str = "/realtime";
+
+/// 后端连接异常断开时发给客户端的关闭码（1011 内部错误）
+const UPSTREAM_FAILURE_CLOSE_CODE: u16 = 1011;
+
+/// 转发给上游的客户端请求头
+const FORWARDED_HEADERS: &[&str] = &["openai-beta"];
+
+pub type UpstreamSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Realtime会话的结束方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEnd {
+    /// 客户端关闭或断开连接
+    ClientClosed,
+    /// 后端正常关闭连接
+    UpstreamClosed,
+    /// 后端连接出错或异常关闭，计为后端失败
+    UpstreamFailed(String),
+}
+
+/// 把provider的HTTP地址转换为Realtime WebSocket地址
+pub fn upstream_url(base_url: &str, model: &str) -> Result<String> {
+    let base_url = base_url.trim_end_matches('/');
+    let ws_url = if let Some(rest) = base_url.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = base_url.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        anyhow::bail!("Unsupported provider base_url for realtime: {}", base_url);
+    };
+    let mut url = reqwest::Url::parse(&format!("{}{}", ws_url, REALTIME_PATH))?;
+    url.query_pairs_mut().append_pair("model", model);
+    Ok(url.to_string())
+}
+
+/// 连接选中后端的Realtime接口，握手超时使用provider的timeout_seconds
+pub async fn connect(selected_backend: &SelectedBackend, request_headers: &axum::http::HeaderMap) -> Result<UpstreamSocket> {
+    let url = upstream_url(&selected_backend.provider.base_url, &selected_backend.backend.model)?;
+    let mut request = url.as_str().into_client_request()?;
+    let headers = request.headers_mut();
+    headers.insert(
+        tungstenite::http::header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", selected_backend.get_api_key()?))?,
+    );
+    for name in FORWARDED_HEADERS {
+        if let Some(value) = request_headers.get(*name).and_then(|v| HeaderValue::from_bytes(v.as_bytes()).ok()) {
+            headers.insert(*name, value);
+        }
+    }
+    for (key, value) in selected_backend.get_headers() {
+        if let (Ok(name), Ok(value)) = (
+            key.parse::<tungstenite::http::HeaderName>(),
+            HeaderValue::from_str(&value),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+
+    let timeout = Duration::from_secs(selected_backend.provider.timeout_seconds);
+    let (socket, _) = tokio::time::timeout(timeout, tokio_tungstenite::connect_async(request))
+        .await
+        .with_context(|| format!("Realtime handshake with {} timed out", selected_backend.provider.name))?
+        .with_context(|| format!("Realtime handshake with {} failed", selected_backend.provider.name))?;
+    Ok(socket)
+}
+
+/// 在客户端和后端之间双向转发消息，直到任一方关闭
+/// 后端出错时以1011关闭客户端连接；on_usage在每个response.done事件中收到本轮的token用量
+pub async fn relay(client: WebSocket, upstream: UpstreamSocket, mut on_usage: impl FnMut(TokenUsage)) -> SessionEnd {
+    let (mut client_tx, mut client_rx) = client.split();
+    let (mut upstream_tx, mut upstream_rx) = upstream.split();
+
+    let end = loop {
+        tokio::select! {
+            message = client_rx.next() => match message {
+                Some(Ok(message)) => {
+                    let closing = matches!(message, ws::Message::Close(_));
+                    if let Err(e) = upstream_tx.send(to_upstream(message)).await {
+                        break SessionEnd::UpstreamFailed(format!("Failed to send to upstream: {}", e));
+                    }
+                    if closing {
+                        break SessionEnd::ClientClosed;
+                    }
+                }
+                Some(Err(_)) | None => {
+                    let _ = upstream_tx.close().await;
+                    break SessionEnd::ClientClosed;
+                }
+            },
+            message = upstream_rx.next() => match message {
+                Some(Ok(tungstenite::Message::Frame(_))) => {}
+                Some(Ok(message)) => {
+                    if let tungstenite::Message::Text(text) = &message
+                        && let Some(usage) = response_usage(text.as_str())
+                    {
+                        on_usage(usage);
+                    }
+                    let close_code = match &message {
+                        tungstenite::Message::Close(frame) => Some(frame.as_ref().map(|frame| frame.code)),
+                        _ => None,
+                    };
+                    let Some(forwarded) = to_client(message) else {
+                        continue;
+                    };
+                    if client_tx.send(forwarded).await.is_err() {
+                        let _ = upstream_tx.close().await;
+                        break SessionEnd::ClientClosed;
+                    }
+                    match close_code {
+                        Some(Some(code)) if is_failure_close(code) => {
+                            break SessionEnd::UpstreamFailed(format!("Upstream closed the session with code {}", code));
+                        }
+                        Some(_) => break SessionEnd::UpstreamClosed,
+                        None => {}
+                    }
+                }
+                Some(Err(e)) => break SessionEnd::UpstreamFailed(e.to_string()),
+                None => break SessionEnd::UpstreamFailed("Upstream connection closed without a close frame".to_string()),
+            },
+        }
+    };
+
+    if let SessionEnd::UpstreamFailed(reason) = &end {
+        let _ = client_tx
+            .send(ws::Message::Close(Some(ws::CloseFrame {
+                code: UPSTREAM_FAILURE_CLOSE_CODE,
+                reason: "Upstream realtime connection failed".into(),
+            })))
+            .await;
+        tracing::warn!("Realtime upstream failed: {}", reason);
+    }
+    let _ = client_tx.close().await;
+    end
+}
+
+/// 后端异常关闭（内部错误、稍后重试、异常断开）计为失败，其他关闭码视为正常结束
+fn is_failure_close(code: CloseCode) -> bool {
+    matches!(code, CloseCode::Error | CloseCode::Again | CloseCode::Abnormal)
+}
+
+/// response.done事件中的token用量
+fn response_usage(text: &str) -> Option<TokenUsage> {
+    // 只解析包含用量的事件，音频增量等大多数事件直接跳过
+    if !text.contains("\"response.done\"") {
+        return None;
+    }
+    let event: Value = serde_json::from_str(text).ok()?;
+    if event["type"] != "response.done" {
+        return None;
+    }
+    let usage = &event["response"]["usage"];
+    Some(TokenUsage {
+        prompt_tokens: usage["input_tokens"].as_u64()?,
+        completion_tokens: usage["output_tokens"].as_u64().unwrap_or_default(),
+    })
+}
+
+fn to_upstream(message: ws::Message) -> tungstenite::Message {
+    match message {
+        ws::Message::Text(text) => tungstenite::Message::Text(text.as_str().into()),
+        ws::Message::Binary(data) => tungstenite::Message::Binary(data),
+        ws::Message::Ping(data) => tungstenite::Message::Ping(data),
+        ws::Message::Pong(data) => tungstenite::Message::Pong(data),
+        ws::Message::Close(frame) => tungstenite::Message::Close(frame.map(|frame| CloseFrame {
+            code: CloseCode::from(frame.code),
+            reason: frame.reason.as_str().into(),
+        })),
+    }
+}
+
+fn to_client(message: tungstenite::Message) -> Option<ws::Message> {
+    Some(match message {
+        tungstenite::Message::Text(text) => ws::Message::Text(text.as_str().into()),
+        tungstenite::Message::Binary(data) => ws::Message::Binary(data),
+        tungstenite::Message::Ping(data) => ws::Message::Ping(data),
+        tungstenite::Message::Pong(data) => ws::Message::Pong(data),
+        tungstenite::Message::Close(frame) => ws::Message::Close(frame.map(|frame| ws::CloseFrame {
+            code: frame.code.into(),
+            reason: frame.reason.as_str().into(),
+        })),
+        tungstenite::Message::Frame(_) => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upstream_url() {
+        assert_eq!(
+            upstream_url("https://api.openai.com/v1/", "gpt-4o-realtime").unwrap(),
+            "wss://api.openai.com/v1/realtime?model=gpt-4o-realtime"
+        );
+        assert_eq!(
+            upstream_url("http://127.0.0.1:8080/v1", "a b").unwrap(),
+            "ws://127.0.0.1:8080/v1/realtime?model=a+b"
+        );
+        assert!(upstream_url("ftp://example.com", "m").is_err());
+    }
+
+    #[test]
+    fn test_response_usage() {
+        let done = r#"{"type":"response.done","response":{"usage":{"input_tokens":12,"output_tokens":30}}}"#;
+        assert_eq!(
+            response_usage(done),
+            Some(TokenUsage {
+                prompt_tokens: 12,
+                completion_tokens: 30
+            })
+        );
+        assert_eq!(response_usage(r#"{"type":"response.audio.delta","delta":"AAAA"}"#), None);
+    }
+}
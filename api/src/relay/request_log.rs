@@ -0,0 +1,401 @@
+use crate::config::model::{RequestLogSettings, RequestLogSink};
+use crate::loadbalance::TokenUsage;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// 写入数据库的列，顺序与建表和插入语句一致
+const COLUMNS: &str = r#"timestamp, "user", model, backend, status, latency_ms, prompt_tokens, completion_tokens, error"#;
+
+/// 一条请求记录
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestLogRecord {
+    pub timestamp: DateTime<Utc>,
+    pub user: String,
+    pub model: String,
+    /// 实际处理请求的后端（provider:model），缓存命中或未选中后端时为空
+    pub backend: Option<String>,
+    pub status: u16,
+    pub latency_ms: u64,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl RequestLogRecord {
+    pub fn new(user: &str, model: &str) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            user: user.to_string(),
+            model: model.to_string(),
+            backend: None,
+            status: 0,
+            latency_ms: 0,
+            prompt_tokens: None,
+            completion_tokens: None,
+            error: None,
+        }
+    }
+
+    /// 填写token用量
+    pub fn set_usage(&mut self, usage: Option<TokenUsage>) {
+        self.prompt_tokens = usage.map(|u| u.prompt_tokens);
+        self.completion_tokens = usage.map(|u| u.completion_tokens);
+    }
+}
+
+/// 请求记录写入器
+/// 记录进入有界队列后由后台任务按批写入数据库；队列满时直接丢弃，丢弃数量在下次写入时汇总告警
+/// 写入配置变更（配置热加载）时关闭旧队列，旧任务写完剩余记录后退出
+pub struct RequestLogger {
+    writer: std::sync::Mutex<Option<(RequestLogSettings, mpsc::Sender<RequestLogRecord>)>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl RequestLogger {
+    pub fn new() -> Self {
+        Self {
+            writer: std::sync::Mutex::new(None),
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// 提交一条请求记录，不等待写入
+    pub fn log(&self, settings: &RequestLogSettings, record: RequestLogRecord) {
+        let Ok(mut writer) = self.writer.lock() else {
+            return;
+        };
+
+        if writer.as_ref().is_none_or(|(current, _)| current != settings) {
+            let (sender, receiver) = mpsc::channel(settings.queue_capacity);
+            tokio::spawn(Self::run(settings.clone(), receiver, self.dropped.clone()));
+            *writer = Some((settings.clone(), sender));
+        }
+
+        if let Some((_, sender)) = writer.as_ref()
+            && sender.try_send(record).is_err()
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    async fn run(
+        settings: RequestLogSettings,
+        mut receiver: mpsc::Receiver<RequestLogRecord>,
+        dropped: Arc<AtomicU64>,
+    ) {
+        let mut sink = SinkWriter::new(&settings);
+        let flush_interval = Duration::from_millis(settings.flush_interval_ms);
+
+        while let Some(first) = receiver.recv().await {
+            let mut batch = vec![first];
+            let deadline = tokio::time::Instant::now() + flush_interval;
+            while batch.len() < settings.batch_size {
+                match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                    Ok(Some(record)) => batch.push(record),
+                    Ok(None) | Err(_) => break,
+                }
+            }
+
+            let mut attempt = 0;
+            loop {
+                match sink.write(&batch).await {
+                    Ok(()) => {
+                        debug!("Wrote {} request records to {}", batch.len(), sink.name());
+                        break;
+                    }
+                    Err(e) if attempt < settings.max_retries => {
+                        attempt += 1;
+                        debug!("Failed to write request records (attempt {}): {:#}", attempt, e);
+                        tokio::time::sleep(Duration::from_millis(200 << attempt.min(6))).await;
+                    }
+                    Err(e) => {
+                        warn!("Failed to write {} request records to {}: {:#}", batch.len(), sink.name(), e);
+                        break;
+                    }
+                }
+            }
+
+            let dropped = dropped.swap(0, Ordering::Relaxed);
+            if dropped > 0 {
+                warn!("Request log queue is full, dropped {} records", dropped);
+            }
+        }
+    }
+}
+
+impl Default for RequestLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 数据库写入端，首次写入时建立连接并按需建表
+enum SinkWriter {
+    Clickhouse {
+        client: reqwest::Client,
+        sink: RequestLogSink,
+        create_table: bool,
+    },
+    Postgres {
+        client: Option<tokio_postgres::Client>,
+        sink: RequestLogSink,
+        create_table: bool,
+    },
+}
+
+impl SinkWriter {
+    fn new(settings: &RequestLogSettings) -> Self {
+        match &settings.sink {
+            RequestLogSink::Clickhouse { .. } => Self::Clickhouse {
+                client: reqwest::Client::new(),
+                sink: settings.sink.clone(),
+                create_table: settings.create_table,
+            },
+            RequestLogSink::Postgres { .. } => Self::Postgres {
+                client: None,
+                sink: settings.sink.clone(),
+                create_table: settings.create_table,
+            },
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Clickhouse { .. } => "clickhouse",
+            Self::Postgres { .. } => "postgres",
+        }
+    }
+
+    async fn write(&mut self, records: &[RequestLogRecord]) -> anyhow::Result<()> {
+        match self {
+            Self::Clickhouse {
+                client,
+                sink,
+                create_table,
+            } => {
+                if *create_table {
+                    clickhouse_query(client, sink, clickhouse_create_table_sql(sink), String::new()).await?;
+                    *create_table = false;
+                }
+                let mut body = String::new();
+                for record in records {
+                    body.push_str(&serde_json::to_string(record)?);
+                    body.push('\n');
+                }
+                let query = format!("INSERT INTO {} FORMAT JSONEachRow", clickhouse_table(sink));
+                clickhouse_query(client, sink, query, body).await
+            }
+            Self::Postgres {
+                client,
+                sink,
+                create_table,
+            } => {
+                if client.as_ref().is_none_or(|c| c.is_closed()) {
+                    *client = Some(postgres_connect(sink).await?);
+                }
+                let Some(pg) = client.as_ref() else {
+                    return Ok(());
+                };
+                if *create_table {
+                    pg.batch_execute(&postgres_create_table_sql(sink.table())).await?;
+                    *create_table = false;
+                }
+                postgres_insert(pg, sink.table(), records).await
+            }
+        }
+    }
+}
+
+fn clickhouse_table(sink: &RequestLogSink) -> String {
+    match sink {
+        RequestLogSink::Clickhouse { database, table, .. } => format!("{}.{}", database, table),
+        RequestLogSink::Postgres { table, .. } => table.clone(),
+    }
+}
+
+fn clickhouse_create_table_sql(sink: &RequestLogSink) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (\
+         timestamp DateTime64(3, 'UTC'), user String, model String, backend Nullable(String), \
+         status UInt16, latency_ms UInt64, prompt_tokens Nullable(UInt64), completion_tokens Nullable(UInt64), \
+         error Nullable(String)) ENGINE = MergeTree ORDER BY (timestamp, model)",
+        clickhouse_table(sink)
+    )
+}
+
+/// 通过HTTP接口执行语句，INSERT的数据放在请求体中
+async fn clickhouse_query(
+    client: &reqwest::Client,
+    sink: &RequestLogSink,
+    query: String,
+    body: String,
+) -> anyhow::Result<()> {
+    let RequestLogSink::Clickhouse {
+        url,
+        username,
+        password,
+        timeout_seconds,
+        ..
+    } = sink
+    else {
+        anyhow::bail!("not a clickhouse sink");
+    };
+    let mut request = client
+        .post(url)
+        .query(&[("query", query.as_str()), ("date_time_input_format", "best_effort")])
+        .timeout(Duration::from_secs(*timeout_seconds))
+        .body(body);
+    if let Some(username) = username {
+        request = request.header("X-ClickHouse-User", username);
+    }
+    if let Some(password) = password {
+        request = request.header("X-ClickHouse-Key", password);
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        anyhow::bail!("clickhouse returned {}: {}", status, text.trim());
+    }
+    Ok(())
+}
+
+fn postgres_create_table_sql(table: &str) -> String {
+    format!(
+        r#"CREATE TABLE IF NOT EXISTS {table} (
+            id BIGSERIAL PRIMARY KEY,
+            timestamp TIMESTAMPTZ NOT NULL,
+            "user" TEXT NOT NULL,
+            model TEXT NOT NULL,
+            backend TEXT,
+            status INTEGER NOT NULL,
+            latency_ms BIGINT NOT NULL,
+            prompt_tokens BIGINT,
+            completion_tokens BIGINT,
+            error TEXT
+        );
+        CREATE INDEX IF NOT EXISTS {table}_timestamp_idx ON {table} (timestamp)"#
+    )
+}
+
+/// 以数组参数展开批量插入，语句不随批大小变化
+fn postgres_insert_sql(table: &str) -> String {
+    format!(
+        "INSERT INTO {} ({}) SELECT * FROM UNNEST(\
+         $1::timestamptz[], $2::text[], $3::text[], $4::text[], $5::integer[], \
+         $6::bigint[], $7::bigint[], $8::bigint[], $9::text[])",
+        table, COLUMNS
+    )
+}
+
+async fn postgres_insert(
+    client: &tokio_postgres::Client,
+    table: &str,
+    records: &[RequestLogRecord],
+) -> anyhow::Result<()> {
+    let timestamps: Vec<DateTime<Utc>> = records.iter().map(|r| r.timestamp).collect();
+    let users: Vec<&str> = records.iter().map(|r| r.user.as_str()).collect();
+    let models: Vec<&str> = records.iter().map(|r| r.model.as_str()).collect();
+    let backends: Vec<Option<&str>> = records.iter().map(|r| r.backend.as_deref()).collect();
+    let statuses: Vec<i32> = records.iter().map(|r| i32::from(r.status)).collect();
+    let latencies: Vec<i64> = records.iter().map(|r| r.latency_ms as i64).collect();
+    let prompt_tokens: Vec<Option<i64>> = records.iter().map(|r| r.prompt_tokens.map(|t| t as i64)).collect();
+    let completion_tokens: Vec<Option<i64>> =
+        records.iter().map(|r| r.completion_tokens.map(|t| t as i64)).collect();
+    let errors: Vec<Option<&str>> = records.iter().map(|r| r.error.as_deref()).collect();
+
+    client
+        .execute(
+            &postgres_insert_sql(table),
+            &[
+                &timestamps,
+                &users,
+                &models,
+                &backends,
+                &statuses,
+                &latencies,
+                &prompt_tokens,
+                &completion_tokens,
+                &errors,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// 建立Postgres连接，sslmode=require时使用TLS（系统内置根证书），否则使用明文连接
+async fn postgres_connect(sink: &RequestLogSink) -> anyhow::Result<tokio_postgres::Client> {
+    let RequestLogSink::Postgres { url, .. } = sink else {
+        anyhow::bail!("not a postgres sink");
+    };
+    let config: tokio_postgres::Config = url.parse().context("invalid postgres url")?;
+
+    let client = if config.get_ssl_mode() == tokio_postgres::config::SslMode::Require {
+        let roots = rustls::RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let tls_config = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let (client, connection) = config
+            .connect(tokio_postgres_rustls::MakeRustlsConnect::new(tls_config))
+            .await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                warn!("Request log postgres connection closed: {}", e);
+            }
+        });
+        client
+    } else {
+        let (client, connection) = config.connect(tokio_postgres::NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                warn!("Request log postgres connection closed: {}", e);
+            }
+        });
+        client
+    };
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sql_uses_configured_table() {
+        let sink = RequestLogSink::Clickhouse {
+            url: "http://localhost:8123".to_string(),
+            database: "analytics".to_string(),
+            table: "requests".to_string(),
+            username: None,
+            password: None,
+            timeout_seconds: 5,
+        };
+        assert!(clickhouse_create_table_sql(&sink).starts_with("CREATE TABLE IF NOT EXISTS analytics.requests ("));
+
+        let insert = postgres_insert_sql("requests");
+        assert!(insert.starts_with(r#"INSERT INTO requests (timestamp, "user", model"#));
+        assert!(insert.contains("$9::text[]"));
+        assert!(postgres_create_table_sql("requests").contains("requests_timestamp_idx"));
+    }
+
+    #[test]
+    fn test_record_serializes_for_json_each_row() {
+        let mut record = RequestLogRecord::new("alice", "gpt-4");
+        record.set_usage(Some(TokenUsage {
+            prompt_tokens: 10,
+            completion_tokens: 5,
+        }));
+        let value = serde_json::to_value(&record).unwrap();
+        assert_eq!(value["user"], "alice");
+        assert_eq!(value["prompt_tokens"], 10);
+        assert!(value["backend"].is_null());
+        assert!(value["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+}
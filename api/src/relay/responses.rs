@@ -0,0 +1,605 @@
+//! OpenAI Responses API 与 Chat Completions 之间的格式转换
+//! 模型的后端都支持Responses接口时直接透传，否则把请求转换为聊天请求，再把聊天响应转换回Responses格式
+
+use serde_json::{Value, json};
+
+/// 流式响应的结束事件，透传时用于判断上游是否完整结束
+pub const TERMINAL_EVENTS: &[&str] = &["response.completed", "response.incomplete", "response.failed", "error"];
+
+/// 把Responses请求转换为OpenAI聊天请求
+pub fn to_chat_request(body: &Value) -> Result<Value, String> {
+    let model = body
+        .get("model")
+        .and_then(Value::as_str)
+        .ok_or("The 'model' field is required in the request body")?;
+    if body.get("previous_response_id").is_some_and(|id| !id.is_null()) {
+        return Err("'previous_response_id' requires backends with native Responses API support".to_string());
+    }
+
+    let mut messages = Vec::new();
+    match body.get("instructions") {
+        None | Some(Value::Null) => {}
+        Some(Value::String(instructions)) => messages.push(json!({ "role": "system", "content": instructions })),
+        Some(_) => return Err("'instructions' must be a string".to_string()),
+    }
+    match body.get("input") {
+        Some(Value::String(text)) => messages.push(json!({ "role": "user", "content": text })),
+        Some(Value::Array(items)) => {
+            for (index, item) in items.iter().enumerate() {
+                convert_item(item, &mut messages).map_err(|e| format!("input[{}]: {}", index, e))?;
+            }
+        }
+        _ => return Err("'input' must be a string or an array of input items".to_string()),
+    }
+    if messages.iter().all(|message| message["role"] == "system") {
+        return Err("'input' must contain at least one message".to_string());
+    }
+
+    let mut request = json!({ "model": model, "messages": messages });
+    for field in ["temperature", "top_p", "user", "parallel_tool_calls"] {
+        if let Some(value) = body.get(field).filter(|value| !value.is_null()) {
+            request[field] = value.clone();
+        }
+    }
+    if let Some(max_tokens) = body.get("max_output_tokens").filter(|value| !value.is_null()) {
+        request["max_tokens"] = max_tokens.clone();
+    }
+    if body.get("stream").and_then(Value::as_bool) == Some(true) {
+        request["stream"] = json!(true);
+        request["stream_options"] = json!({ "include_usage": true });
+    }
+    if let Some(Value::Array(tools)) = body.get("tools") {
+        let tools = tools
+            .iter()
+            .map(|tool| match tool["type"].as_str() {
+                Some("function") => {
+                    let mut function = json!({
+                        "name": tool["name"],
+                        "parameters": tool.get("parameters").cloned().unwrap_or_else(|| json!({ "type": "object" })),
+                    });
+                    for field in ["description", "strict"] {
+                        if let Some(value) = tool.get(field) {
+                            function[field] = value.clone();
+                        }
+                    }
+                    Ok(json!({ "type": "function", "function": function }))
+                }
+                Some(other) => Err(format!("Tool type '{}' requires native Responses API support", other)),
+                None => Err("Tool is missing 'type'".to_string()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        request["tools"] = Value::Array(tools);
+    }
+    if let Some(choice) = body.get("tool_choice") {
+        request["tool_choice"] = match choice {
+            Value::String(_) => choice.clone(),
+            _ if choice["type"] == "function" => json!({ "type": "function", "function": { "name": choice["name"] } }),
+            _ => return Err("Invalid 'tool_choice'".to_string()),
+        };
+    }
+    if let Some(format) = body.pointer("/text/format") {
+        match format["type"].as_str() {
+            Some("text") | None => {}
+            Some("json_object") => request["response_format"] = json!({ "type": "json_object" }),
+            Some("json_schema") => {
+                let mut schema = json!({ "name": format["name"], "schema": format["schema"] });
+                if let Some(strict) = format.get("strict") {
+                    schema["strict"] = strict.clone();
+                }
+                request["response_format"] = json!({ "type": "json_schema", "json_schema": schema });
+            }
+            Some(other) => return Err(format!("Unsupported text format '{}'", other)),
+        }
+    }
+    Ok(request)
+}
+
+/// 转换一个输入项：消息、函数调用或函数调用结果
+fn convert_item(item: &Value, messages: &mut Vec<Value>) -> Result<(), String> {
+    match item.get("type").and_then(Value::as_str).unwrap_or("message") {
+        "message" => {
+            let role = match item["role"].as_str() {
+                Some("developer") | Some("system") => "system",
+                Some(role @ ("user" | "assistant")) => role,
+                Some(role) => return Err(format!("Invalid role '{}'", role)),
+                None => return Err("Message is missing 'role'".to_string()),
+            };
+            messages.push(json!({ "role": role, "content": message_content(&item["content"])? }));
+        }
+        "function_call" => {
+            let call = json!({
+                "id": item["call_id"],
+                "type": "function",
+                "function": { "name": item["name"], "arguments": item["arguments"].as_str().unwrap_or("{}") },
+            });
+            // 连续的函数调用合并到同一条助手消息中
+            match messages.last_mut() {
+                Some(last) if last["role"] == "assistant" && last["tool_calls"].is_array() => {
+                    if let Some(calls) = last["tool_calls"].as_array_mut() {
+                        calls.push(call);
+                    }
+                }
+                _ => messages.push(json!({ "role": "assistant", "content": Value::Null, "tool_calls": [call] })),
+            }
+        }
+        "function_call_output" => {
+            let output = match &item["output"] {
+                Value::String(output) => output.clone(),
+                other => other.to_string(),
+            };
+            messages.push(json!({ "role": "tool", "tool_call_id": item["call_id"], "content": output }));
+        }
+        // 推理内容不转发给聊天接口
+        "reasoning" => {}
+        other => return Err(format!("Input item type '{}' requires native Responses API support", other)),
+    }
+    Ok(())
+}
+
+/// 消息内容：字符串或内容部分数组，只有文本时合并为字符串
+fn message_content(content: &Value) -> Result<Value, String> {
+    let parts = match content {
+        Value::String(_) => return Ok(content.clone()),
+        Value::Array(parts) => parts,
+        _ => return Err("'content' must be a string or an array of content parts".to_string()),
+    };
+    let mut converted = Vec::new();
+    for part in parts {
+        match part["type"].as_str() {
+            Some("input_text") | Some("output_text") | Some("text") => {
+                converted.push(json!({ "type": "text", "text": part["text"] }))
+            }
+            Some("input_image") => {
+                let Some(url) = part["image_url"].as_str() else {
+                    return Err("input_image requires 'image_url'".to_string());
+                };
+                let mut image_url = json!({ "url": url });
+                if let Some(detail) = part.get("detail") {
+                    image_url["detail"] = detail.clone();
+                }
+                converted.push(json!({ "type": "image_url", "image_url": image_url }));
+            }
+            Some(other) => return Err(format!("Content type '{}' requires native Responses API support", other)),
+            None => return Err("Content part is missing 'type'".to_string()),
+        }
+    }
+    if converted.iter().all(|part| part["type"] == "text") {
+        return Ok(Value::String(
+            converted
+                .iter()
+                .filter_map(|part| part["text"].as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ));
+    }
+    Ok(Value::Array(converted))
+}
+
+fn usage(usage: &Value) -> Value {
+    let input_tokens = usage["prompt_tokens"].as_u64().unwrap_or_default();
+    let output_tokens = usage["completion_tokens"].as_u64().unwrap_or_default();
+    json!({
+        "input_tokens": input_tokens,
+        "output_tokens": output_tokens,
+        "total_tokens": input_tokens + output_tokens,
+    })
+}
+
+fn random_id(prefix: &str) -> String {
+    format!("{}_{:032x}", prefix, rand::random::<u128>())
+}
+
+fn response_id(chunk: &Value) -> String {
+    match chunk["id"].as_str() {
+        Some(id) => format!("resp_{}", id.trim_start_matches("chatcmpl-")),
+        None => random_id("resp"),
+    }
+}
+
+fn message_item(id: &str, text: &str, status: &str) -> Value {
+    json!({
+        "type": "message",
+        "id": id,
+        "status": status,
+        "role": "assistant",
+        "content": [{ "type": "output_text", "text": text, "annotations": [] }],
+    })
+}
+
+fn function_call_item(id: &str, call_id: &Value, name: &Value, arguments: &str, status: &str) -> Value {
+    json!({
+        "type": "function_call",
+        "id": id,
+        "call_id": call_id,
+        "name": name,
+        "arguments": arguments,
+        "status": status,
+    })
+}
+
+/// 聊天响应的finish_reason为length时响应未完成
+fn response_object(id: &str, model: &str, created_at: i64, output: Vec<Value>, finish_reason: Option<&str>, usage: Value) -> Value {
+    let (status, incomplete_details) = match finish_reason {
+        Some("length") => ("incomplete", json!({ "reason": "max_output_tokens" })),
+        Some("content_filter") => ("incomplete", json!({ "reason": "content_filter" })),
+        _ => ("completed", Value::Null),
+    };
+    json!({
+        "id": id,
+        "object": "response",
+        "created_at": created_at,
+        "status": status,
+        "incomplete_details": incomplete_details,
+        "error": Value::Null,
+        "model": model,
+        "output": output,
+        "usage": usage,
+    })
+}
+
+/// 把OpenAI聊天响应转换为Responses响应，model使用客户端请求的名称
+pub fn from_chat_response(response: &Value, model: &str) -> Value {
+    let choice = &response["choices"][0];
+    let message = &choice["message"];
+    let mut output = Vec::new();
+    if let Some(text) = message["content"].as_str().filter(|text| !text.is_empty()) {
+        output.push(message_item(&random_id("msg"), text, "completed"));
+    }
+    if let Some(tool_calls) = message["tool_calls"].as_array() {
+        for call in tool_calls {
+            output.push(function_call_item(
+                &random_id("fc"),
+                &call["id"],
+                &call["function"]["name"],
+                call["function"]["arguments"].as_str().unwrap_or("{}"),
+                "completed",
+            ));
+        }
+    }
+    response_object(
+        &response_id(response),
+        model,
+        response["created"].as_i64().unwrap_or_else(|| chrono::Utc::now().timestamp()),
+        output,
+        choice["finish_reason"].as_str(),
+        usage(&response["usage"]),
+    )
+}
+
+/// 当前打开的输出项
+#[derive(Debug)]
+enum OpenItem {
+    Message { id: String, text: String },
+    /// OpenAI流中工具调用的index
+    FunctionCall { index: u64, id: String, call_id: Value, name: Value, arguments: String },
+}
+
+/// 把OpenAI流式响应块逐个转换为Responses流事件
+#[derive(Debug)]
+pub struct StreamTranslator {
+    model: String,
+    id: String,
+    created_at: i64,
+    started: bool,
+    finished: bool,
+    sequence_number: u64,
+    output: Vec<Value>,
+    open_item: Option<OpenItem>,
+    finish_reason: Option<String>,
+    usage: Value,
+}
+
+impl StreamTranslator {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            id: String::new(),
+            created_at: chrono::Utc::now().timestamp(),
+            started: false,
+            finished: false,
+            sequence_number: 0,
+            output: Vec::new(),
+            open_item: None,
+            finish_reason: None,
+            usage: json!({ "prompt_tokens": 0, "completion_tokens": 0 }),
+        }
+    }
+
+    /// 处理一个OpenAI流式块，返回要发送的(事件名, 数据)
+    pub fn on_chunk(&mut self, chunk: &Value) -> Vec<(&'static str, Value)> {
+        let mut events = Vec::new();
+        if self.finished {
+            return events;
+        }
+        if let Some(error) = chunk.get("error") {
+            self.finished = true;
+            let event = json!({
+                "type": "error",
+                "code": error["code"].as_str().or(error["type"].as_str()),
+                "message": error["message"].as_str().unwrap_or("Unknown error"),
+                "param": Value::Null,
+            });
+            events.push(self.event("error", event));
+            return events;
+        }
+        if !self.started {
+            self.started = true;
+            self.id = response_id(chunk);
+            let response = self.snapshot("in_progress");
+            events.push(self.event("response.created", json!({ "type": "response.created", "response": response })));
+            events.push(self.event("response.in_progress", json!({ "type": "response.in_progress", "response": response })));
+        }
+        if chunk["usage"].is_object() {
+            self.usage = chunk["usage"].clone();
+        }
+
+        let choice = &chunk["choices"][0];
+        let delta = &choice["delta"];
+        if let Some(text) = delta["content"].as_str().filter(|text| !text.is_empty()) {
+            if !matches!(self.open_item, Some(OpenItem::Message { .. })) {
+                self.close_item(&mut events);
+                let id = random_id("msg");
+                let output_index = self.output.len();
+                let mut item = message_item(&id, "", "in_progress");
+                item["content"] = json!([]);
+                events.push(self.event(
+                    "response.output_item.added",
+                    json!({ "type": "response.output_item.added", "output_index": output_index, "item": item }),
+                ));
+                events.push(self.event(
+                    "response.content_part.added",
+                    json!({
+                        "type": "response.content_part.added",
+                        "item_id": id,
+                        "output_index": output_index,
+                        "content_index": 0,
+                        "part": { "type": "output_text", "text": "", "annotations": [] },
+                    }),
+                ));
+                self.open_item = Some(OpenItem::Message { id, text: String::new() });
+            }
+            let output_index = self.output.len();
+            if let Some(OpenItem::Message { id, text: buffer }) = &mut self.open_item {
+                buffer.push_str(text);
+                let event = json!({
+                    "type": "response.output_text.delta",
+                    "item_id": id,
+                    "output_index": output_index,
+                    "content_index": 0,
+                    "delta": text,
+                });
+                events.push(self.event("response.output_text.delta", event));
+            }
+        }
+        if let Some(tool_calls) = delta["tool_calls"].as_array() {
+            for call in tool_calls {
+                let index = call["index"].as_u64().unwrap_or_default();
+                if !matches!(self.open_item, Some(OpenItem::FunctionCall { index: open, .. }) if open == index) {
+                    self.close_item(&mut events);
+                    let id = random_id("fc");
+                    let (call_id, name) = (call["id"].clone(), call["function"]["name"].clone());
+                    let item = function_call_item(&id, &call_id, &name, "", "in_progress");
+                    events.push(self.event(
+                        "response.output_item.added",
+                        json!({ "type": "response.output_item.added", "output_index": self.output.len(), "item": item }),
+                    ));
+                    self.open_item = Some(OpenItem::FunctionCall { index, id, call_id, name, arguments: String::new() });
+                }
+                let output_index = self.output.len();
+                if let Some(arguments) = call["function"]["arguments"].as_str().filter(|a| !a.is_empty())
+                    && let Some(OpenItem::FunctionCall { id, arguments: buffer, .. }) = &mut self.open_item
+                {
+                    buffer.push_str(arguments);
+                    let event = json!({
+                        "type": "response.function_call_arguments.delta",
+                        "item_id": id,
+                        "output_index": output_index,
+                        "delta": arguments,
+                    });
+                    events.push(self.event("response.function_call_arguments.delta", event));
+                }
+            }
+        }
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            self.finish_reason = Some(reason.to_string());
+        }
+        events
+    }
+
+    /// 流结束时关闭打开的输出项并发送response.completed（或response.incomplete）
+    pub fn finish(&mut self) -> Vec<(&'static str, Value)> {
+        let mut events = Vec::new();
+        if self.finished {
+            return events;
+        }
+        if !self.started {
+            // 后端没有返回任何内容
+            events.extend(self.on_chunk(&json!({})));
+        }
+        self.finished = true;
+        self.close_item(&mut events);
+        let response = response_object(
+            &self.id,
+            &self.model,
+            self.created_at,
+            self.output.clone(),
+            self.finish_reason.as_deref(),
+            usage(&self.usage),
+        );
+        let name = if response["status"] == "incomplete" { "response.incomplete" } else { "response.completed" };
+        events.push(self.event(name, json!({ "type": name, "response": response })));
+        events
+    }
+
+    fn snapshot(&self, status: &str) -> Value {
+        let mut response = response_object(&self.id, &self.model, self.created_at, Vec::new(), None, Value::Null);
+        response["status"] = json!(status);
+        response
+    }
+
+    fn event(&mut self, name: &'static str, mut data: Value) -> (&'static str, Value) {
+        data["sequence_number"] = json!(self.sequence_number);
+        self.sequence_number += 1;
+        (name, data)
+    }
+
+    fn close_item(&mut self, events: &mut Vec<(&'static str, Value)>) {
+        let output_index = self.output.len();
+        let item = match self.open_item.take() {
+            None => return,
+            Some(OpenItem::Message { id, text }) => {
+                events.push(self.event(
+                    "response.output_text.done",
+                    json!({
+                        "type": "response.output_text.done",
+                        "item_id": id,
+                        "output_index": output_index,
+                        "content_index": 0,
+                        "text": text,
+                    }),
+                ));
+                events.push(self.event(
+                    "response.content_part.done",
+                    json!({
+                        "type": "response.content_part.done",
+                        "item_id": id,
+                        "output_index": output_index,
+                        "content_index": 0,
+                        "part": { "type": "output_text", "text": text, "annotations": [] },
+                    }),
+                ));
+                message_item(&id, &text, "completed")
+            }
+            Some(OpenItem::FunctionCall { id, call_id, name, arguments, .. }) => {
+                events.push(self.event(
+                    "response.function_call_arguments.done",
+                    json!({
+                        "type": "response.function_call_arguments.done",
+                        "item_id": id,
+                        "output_index": output_index,
+                        "arguments": arguments,
+                    }),
+                ));
+                function_call_item(&id, &call_id, &name, &arguments, "completed")
+            }
+        };
+        events.push(self.event(
+            "response.output_item.done",
+            json!({ "type": "response.output_item.done", "output_index": output_index, "item": item }),
+        ));
+        self.output.push(item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_chat_request() {
+        let request = to_chat_request(&json!({
+            "model": "gpt-4o",
+            "instructions": "Be brief.",
+            "max_output_tokens": 128,
+            "stream": true,
+            "tools": [{ "type": "function", "name": "get_weather", "parameters": { "type": "object" } }],
+            "tool_choice": { "type": "function", "name": "get_weather" },
+            "text": { "format": { "type": "json_schema", "name": "w", "schema": { "type": "object" }, "strict": true } },
+            "input": [
+                { "role": "user", "content": [{ "type": "input_text", "text": "Weather in Paris?" }] },
+                { "type": "reasoning", "summary": [] },
+                { "type": "function_call", "call_id": "call_1", "name": "get_weather", "arguments": "{\"city\":\"Paris\"}" },
+                { "type": "function_call", "call_id": "call_2", "name": "get_weather", "arguments": "{\"city\":\"Lyon\"}" },
+                { "type": "function_call_output", "call_id": "call_1", "output": "Sunny" }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(request["max_tokens"], 128);
+        assert_eq!(request["stream_options"], json!({ "include_usage": true }));
+        assert_eq!(request["tools"][0]["function"]["name"], "get_weather");
+        assert_eq!(request["tool_choice"]["function"]["name"], "get_weather");
+        assert_eq!(request["response_format"]["json_schema"]["strict"], true);
+        let messages = request["messages"].as_array().unwrap();
+        assert_eq!(messages[0], json!({ "role": "system", "content": "Be brief." }));
+        assert_eq!(messages[1], json!({ "role": "user", "content": "Weather in Paris?" }));
+        assert_eq!(messages[2]["tool_calls"].as_array().unwrap().len(), 2);
+        assert_eq!(messages[3], json!({ "role": "tool", "tool_call_id": "call_1", "content": "Sunny" }));
+
+        assert!(to_chat_request(&json!({ "model": "m", "input": "hi", "previous_response_id": "resp_1" })).is_err());
+        assert!(to_chat_request(&json!({ "model": "m", "input": "hi", "tools": [{ "type": "web_search" }] })).is_err());
+        assert!(to_chat_request(&json!({ "model": "m", "instructions": "x", "input": [] })).is_err());
+    }
+
+    #[test]
+    fn test_from_chat_response() {
+        let response = from_chat_response(
+            &json!({
+                "id": "chatcmpl-abc",
+                "created": 1700000000,
+                "choices": [{
+                    "message": { "role": "assistant", "content": "Checking.", "tool_calls": [
+                        { "id": "call_1", "type": "function", "function": { "name": "f", "arguments": "{\"a\":1}" } }
+                    ]},
+                    "finish_reason": "tool_calls"
+                }],
+                "usage": { "prompt_tokens": 10, "completion_tokens": 5 }
+            }),
+            "gpt-4o",
+        );
+        assert_eq!(response["id"], "resp_abc");
+        assert_eq!(response["object"], "response");
+        assert_eq!(response["status"], "completed");
+        assert_eq!(response["model"], "gpt-4o");
+        assert_eq!(response["output"][0]["content"][0]["text"], "Checking.");
+        assert_eq!(response["output"][1]["call_id"], "call_1");
+        assert_eq!(response["output"][1]["arguments"], "{\"a\":1}");
+        assert_eq!(response["usage"], json!({ "input_tokens": 10, "output_tokens": 5, "total_tokens": 15 }));
+
+        let truncated = from_chat_response(&json!({ "choices": [{ "message": { "content": "a" }, "finish_reason": "length" }] }), "m");
+        assert_eq!(truncated["status"], "incomplete");
+        assert_eq!(truncated["incomplete_details"]["reason"], "max_output_tokens");
+    }
+
+    #[test]
+    fn test_stream_translator() {
+        let mut translator = StreamTranslator::new("gpt-4o");
+        let mut events = Vec::new();
+        events.extend(translator.on_chunk(&json!({ "id": "x", "choices": [{ "delta": { "content": "Hi" } }] })));
+        events.extend(translator.on_chunk(&json!({ "choices": [{ "delta": { "content": " there" } }] })));
+        events.extend(translator.on_chunk(&json!({ "choices": [{ "delta": { "tool_calls": [
+            { "index": 0, "id": "call_1", "function": { "name": "f", "arguments": "{}" } }
+        ]}, "finish_reason": "tool_calls" }] })));
+        events.extend(translator.on_chunk(&json!({ "choices": [], "usage": { "prompt_tokens": 3, "completion_tokens": 7 } })));
+        events.extend(translator.finish());
+
+        let names: Vec<_> = events.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            [
+                "response.created",
+                "response.in_progress",
+                "response.output_item.added",
+                "response.content_part.added",
+                "response.output_text.delta",
+                "response.output_text.delta",
+                "response.output_text.done",
+                "response.content_part.done",
+                "response.output_item.done",
+                "response.output_item.added",
+                "response.function_call_arguments.delta",
+                "response.function_call_arguments.done",
+                "response.output_item.done",
+                "response.completed"
+            ]
+        );
+        assert!(events.iter().enumerate().all(|(i, (_, data))| data["sequence_number"] == i as u64));
+        assert_eq!(events[6].1["text"], "Hi there");
+        assert_eq!(events[9].1["output_index"], 1);
+        let completed = &events[13].1["response"];
+        assert_eq!(completed["id"], "resp_x");
+        assert_eq!(completed["output"].as_array().unwrap().len(), 2);
+        assert_eq!(completed["usage"]["output_tokens"], 7);
+        assert!(translator.finish().is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+use crate::config::model::StreamResumptionSettings;
+use axum::body::{Body, Bytes};
+use axum::http::{StatusCode, header};
+use axum::response::Response;
+use eventsource_stream::{Event, Eventsource};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::Instrument;
+
+/// 客户端断线重连时携带的最后收到的事件ID
+pub const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Progress {
+    /// 已缓冲的事件数，事件ID从1开始，等于事件序号
+    len: usize,
+    /// 上游流已经结束
+    done: bool,
+}
+
+/// 单个流式请求的事件缓冲
+struct StreamBuffer {
+    events: Mutex<Vec<Bytes>>,
+    progress: watch::Sender<Progress>,
+    finished_at: Mutex<Option<Instant>>,
+}
+
+impl StreamBuffer {
+    fn new() -> Self {
+        Self {
+            events: Mutex::new(Vec::new()),
+            progress: watch::channel(Progress::default()).0,
+            finished_at: Mutex::new(None),
+        }
+    }
+
+    fn push(&self, event: Bytes) {
+        let len = {
+            let mut events = self.events.lock().unwrap();
+            events.push(event);
+            events.len()
+        };
+        self.progress.send_modify(|progress| progress.len = len);
+    }
+
+    fn finish(&self) {
+        *self.finished_at.lock().unwrap() = Some(Instant::now());
+        self.progress.send_modify(|progress| progress.done = true);
+    }
+
+    fn expired(&self, ttl: Duration) -> bool {
+        self.finished_at
+            .lock()
+            .unwrap()
+            .is_some_and(|finished_at| finished_at.elapsed() >= ttl)
+    }
+}
+
+/// 流式响应的断点续传缓冲：上游流由后台任务读取并按请求ID缓冲，客户端断开后上游生成继续进行，
+/// 客户端带着同一请求ID和`Last-Event-ID`重连时从缓冲中补发之后的事件，不会重新请求上游
+pub struct StreamResumeStore {
+    streams: Mutex<HashMap<String, Arc<StreamBuffer>>>,
+}
+
+impl Default for StreamResumeStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamResumeStore {
+    pub fn new() -> Self {
+        Self {
+            streams: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 缓冲按用户隔离，其他用户即使知道请求ID也无法续传
+    pub fn key(user: &str, request_id: &str) -> String {
+        format!("{}:{}", user, request_id)
+    }
+
+    /// 缓冲SSE响应并返回带事件ID的响应，其他响应（如错误）原样返回
+    pub fn record(&self, settings: &StreamResumptionSettings, key: String, idle_timeout: Duration, response: Response) -> Response {
+        let is_event_stream = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        if !is_event_stream {
+            return response;
+        }
+
+        let ttl = Duration::from_secs(settings.ttl_seconds);
+        let buffer = Arc::new(StreamBuffer::new());
+        {
+            let mut streams = self.streams.lock().unwrap();
+            streams.retain(|_, buffer| !buffer.expired(ttl));
+            evict_oldest(&mut streams, settings.max_streams);
+            streams.insert(key, buffer.clone());
+        }
+
+        let (mut parts, body) = response.into_parts();
+        parts.headers.remove(header::CONTENT_LENGTH);
+        let writer = buffer.clone();
+        tokio::spawn(
+            async move {
+                let mut events = body.into_data_stream().eventsource();
+                // 聊天流带有不会结束的保活定时器，收到[DONE]或长时间没有新事件时视为结束
+                while let Ok(Some(event)) = tokio::time::timeout(idle_timeout, events.next()).await {
+                    let Ok(event) = event else { break };
+                    let done = event.data.trim() == "[DONE]";
+                    let id = writer.progress.borrow().len + 1;
+                    writer.push(format_event(id, &event));
+                    if done {
+                        break;
+                    }
+                }
+                writer.finish();
+            }
+            .in_current_span(),
+        );
+
+        Response::from_parts(parts, Body::from_stream(subscribe(buffer, 0)))
+    }
+
+    /// 从`last_event_id`之后继续发送缓冲的事件，流仍在进行时继续跟随；缓冲不存在或已过期时返回None
+    pub fn resume(&self, settings: &StreamResumptionSettings, key: &str, last_event_id: usize) -> Option<Response> {
+        let ttl = Duration::from_secs(settings.ttl_seconds);
+        let buffer = {
+            let mut streams = self.streams.lock().unwrap();
+            streams.retain(|_, buffer| !buffer.expired(ttl));
+            streams.get(key).cloned()?
+        };
+        Some(
+            Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, "text/event-stream")
+                .header(header::CACHE_CONTROL, "no-cache")
+                .body(Body::from_stream(subscribe(buffer, last_event_id)))
+                .unwrap(),
+        )
+    }
+
+    /// 当前保留的流缓冲数
+    pub fn len(&self) -> usize {
+        self.streams.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// 超出数量上限时淘汰最早结束的缓冲，仍在进行的流不淘汰
+fn evict_oldest(streams: &mut HashMap<String, Arc<StreamBuffer>>, max_streams: usize) {
+    while streams.len() >= max_streams {
+        let oldest = streams
+            .iter()
+            .filter_map(|(key, buffer)| buffer.finished_at.lock().unwrap().map(|at| (at, key.clone())))
+            .min();
+        match oldest {
+            Some((_, key)) => streams.remove(&key),
+            None => break,
+        };
+    }
+}
+
+/// 从第`from`个事件之后开始读取缓冲，直到上游流结束
+fn subscribe(buffer: Arc<StreamBuffer>, from: usize) -> impl futures::Stream<Item = Result<Bytes, Infallible>> + Send {
+    let progress = buffer.progress.subscribe();
+    futures::stream::unfold((buffer, progress, from), |(buffer, mut progress, next)| async move {
+        loop {
+            let Progress { len, done } = *progress.borrow_and_update();
+            if next < len {
+                let chunk: Vec<u8> = buffer.events.lock().unwrap()[next..len].concat();
+                return Some((Ok(Bytes::from(chunk)), (buffer, progress, len)));
+            }
+            if done || progress.changed().await.is_err() {
+                return None;
+            }
+        }
+    })
+}
+
+fn format_event(id: usize, event: &Event) -> Bytes {
+    let mut out = format!("id: {}\n", id);
+    if !event.event.is_empty() && event.event != "message" {
+        out.push_str(&format!("event: {}\n", event.event));
+    }
+    for line in event.data.split('\n') {
+        out.push_str(&format!("data: {}\n", line));
+    }
+    out.push('\n');
+    Bytes::from(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+
+    fn settings() -> StreamResumptionSettings {
+        StreamResumptionSettings {
+            ttl_seconds: 60,
+            max_streams: 2,
+        }
+    }
+
+    fn sse(body: &'static str) -> Response {
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn text(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_events_are_numbered_and_resumable() {
+        let store = StreamResumeStore::new();
+        let response = store.record(
+            &settings(),
+            StreamResumeStore::key("alice", "req-1"),
+            Duration::from_secs(1),
+            sse("data: one\n\n: keep-alive\n\ndata: two\n\ndata: [DONE]\n\n"),
+        );
+        assert_eq!(
+            text(response).await,
+            "id: 1\ndata: one\n\nid: 2\ndata: two\n\nid: 3\ndata: [DONE]\n\n"
+        );
+
+        let resumed = store.resume(&settings(), &StreamResumeStore::key("alice", "req-1"), 1).unwrap();
+        assert_eq!(text(resumed).await, "id: 2\ndata: two\n\nid: 3\ndata: [DONE]\n\n");
+        assert!(store.resume(&settings(), &StreamResumeStore::key("bob", "req-1"), 1).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_non_stream_responses_are_not_buffered() {
+        let store = StreamResumeStore::new();
+        let response = store.record(
+            &settings(),
+            StreamResumeStore::key("alice", "req-1"),
+            Duration::from_secs(1),
+            (StatusCode::TOO_MANY_REQUESTS, "slow down").into_response(),
+        );
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(store.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_finished_streams_are_evicted_over_capacity() {
+        let store = StreamResumeStore::new();
+        for id in ["a", "b", "c"] {
+            let response = store.record(&settings(), id.to_string(), Duration::from_secs(1), sse("data: x\n\ndata: [DONE]\n\n"));
+            text(response).await;
+        }
+        assert_eq!(store.len(), 2);
+        assert!(store.resume(&settings(), "a", 0).is_none());
+        assert!(store.resume(&settings(), "c", 0).is_some());
+    }
+}
@@ -0,0 +1,277 @@
+use crate::config::model::{Config, SemanticCacheSettings};
+use crate::loadbalance::MetricsCollector;
+use crate::relay::cache::{CacheStats, IGNORED_FIELDS, sha256_hex};
+use crate::relay::client::openai::OpenAIClient;
+use anyhow::Result;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+struct SemanticEntry {
+    embedding: Vec<f32>,
+    body: String,
+    inserted_at: Instant,
+}
+
+/// 语义缓存查询结果
+pub enum SemanticLookup {
+    /// 命中缓存的响应体
+    Hit(String),
+    /// 未命中，响应完成后可按分区和向量写入缓存
+    Miss { partition: String, embedding: Vec<f32> },
+    /// 请求不适用语义缓存
+    Skip,
+}
+
+/// 语义缓存
+/// 对提示词计算embedding，与同一分区（模型及其他请求参数相同）内已缓存请求的余弦相似度
+/// 超过阈值时直接返回缓存响应
+pub struct SemanticCache {
+    entries: std::sync::RwLock<HashMap<String, Vec<SemanticEntry>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl SemanticCache {
+    pub fn new() -> Self {
+        Self {
+            entries: std::sync::RwLock::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// 提取用于计算embedding的提示词文本
+    fn prompt_text(body: &Value) -> Option<String> {
+        let messages = body.get("messages")?.as_array()?;
+        let mut parts = Vec::new();
+        for message in messages {
+            let role = message.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+            let content = match message.get("content") {
+                Some(Value::String(text)) => text.clone(),
+                // 多模态内容只取文本部分
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .filter_map(|item| item.get("text").and_then(|t| t.as_str()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                _ => continue,
+            };
+            parts.push(format!("{}: {}", role, content));
+        }
+
+        if parts.is_empty() { None } else { Some(parts.join("\n")) }
+    }
+
+    /// 计算缓存分区：除消息外的请求参数都相同的请求才可以共享缓存
+    fn partition_key(body: &Value) -> Option<String> {
+        let mut normalized = body.as_object()?.clone();
+        normalized.remove("messages");
+        for field in IGNORED_FIELDS {
+            normalized.remove(*field);
+        }
+        let serialized = serde_json::to_string(&normalized).ok()?;
+        Some(sha256_hex(serialized.as_bytes()))
+    }
+
+    /// 计算两个向量的余弦相似度
+    fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+        if a.len() != b.len() || a.is_empty() {
+            return 0.0;
+        }
+        let (mut dot, mut norm_a, mut norm_b) = (0.0f64, 0.0f64, 0.0f64);
+        for (x, y) in a.iter().zip(b) {
+            let (x, y) = (*x as f64, *y as f64);
+            dot += x * y;
+            norm_a += x * x;
+            norm_b += y * y;
+        }
+        if norm_a == 0.0 || norm_b == 0.0 {
+            0.0
+        } else {
+            dot / (norm_a.sqrt() * norm_b.sqrt())
+        }
+    }
+
+    /// 调用配置的embedding后端计算文本向量
+    async fn embed(
+        config: &Config,
+        settings: &SemanticCacheSettings,
+        metrics: Arc<MetricsCollector>,
+        text: &str,
+    ) -> Result<Vec<f32>> {
+        let provider = config
+            .providers
+            .get(&settings.provider)
+            .ok_or_else(|| anyhow::anyhow!("Unknown embedding provider '{}'", settings.provider))?;
+        let client = OpenAIClient::for_provider(
+            &settings.provider,
+            provider,
+            Duration::from_secs(provider.timeout_seconds),
+            metrics,
+        )?;
+
+        let response = client
+            .embeddings(
+                &provider.api_key,
+                &json!({ "model": settings.model, "input": text }),
+            )
+            .await?;
+        if !response.is_success {
+            anyhow::bail!("Embedding request failed with status {}", response.status);
+        }
+
+        let value: Value = serde_json::from_str(&response.body)?;
+        let embedding = value["data"][0]["embedding"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("Embedding response missing data[0].embedding"))?
+            .iter()
+            .filter_map(|v| v.as_f64().map(|f| f as f32))
+            .collect::<Vec<_>>();
+        if embedding.is_empty() {
+            anyhow::bail!("Embedding response returned an empty vector");
+        }
+        Ok(embedding)
+    }
+
+    /// 查询语义缓存，仅对启用了语义缓存的模型和非流式请求生效
+    pub async fn lookup(
+        &self,
+        config: &Config,
+        metrics: Arc<MetricsCollector>,
+        model_name: &str,
+        body: &Value,
+    ) -> Result<SemanticLookup> {
+        let Some(settings) = &config.settings.semantic_cache else {
+            return Ok(SemanticLookup::Skip);
+        };
+        let model_enabled = config
+            .resolve_model(model_name)
+            .is_some_and(|(_, m)| m.semantic_cache);
+        let is_stream = body.get("stream").and_then(|s| s.as_bool()).unwrap_or(false);
+        if !model_enabled || is_stream {
+            return Ok(SemanticLookup::Skip);
+        }
+        let (Some(text), Some(partition)) = (Self::prompt_text(body), Self::partition_key(body)) else {
+            return Ok(SemanticLookup::Skip);
+        };
+
+        let embedding = Self::embed(config, settings, metrics, &text).await?;
+        let ttl = Duration::from_secs(settings.ttl_seconds);
+
+        let best = self.entries.read().ok().and_then(|entries| {
+            entries
+                .get(&partition)?
+                .iter()
+                .filter(|entry| entry.inserted_at.elapsed() < ttl)
+                .map(|entry| (Self::cosine_similarity(&embedding, &entry.embedding), entry))
+                .filter(|(similarity, _)| *similarity >= settings.similarity_threshold)
+                .max_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(similarity, entry)| (similarity, entry.body.clone()))
+        });
+
+        match best {
+            Some((similarity, cached)) => {
+                debug!(
+                    "Semantic cache hit for model '{}' (similarity {:.4})",
+                    model_name, similarity
+                );
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Ok(SemanticLookup::Hit(cached))
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Ok(SemanticLookup::Miss { partition, embedding })
+            }
+        }
+    }
+
+    /// 写入缓存，分区超出容量时淘汰过期和最早写入的条目
+    pub fn insert(
+        &self,
+        settings: &SemanticCacheSettings,
+        partition: String,
+        embedding: Vec<f32>,
+        body: String,
+    ) {
+        if settings.max_entries == 0 {
+            return;
+        }
+        let ttl = Duration::from_secs(settings.ttl_seconds);
+
+        if let Ok(mut entries) = self.entries.write() {
+            let bucket = entries.entry(partition).or_default();
+            bucket.retain(|entry| entry.inserted_at.elapsed() < ttl);
+            if bucket.len() >= settings.max_entries {
+                // 条目按写入顺序追加，最前面的最早写入
+                let excess = bucket.len() + 1 - settings.max_entries;
+                bucket.drain(..excess);
+            }
+            bucket.push(SemanticEntry {
+                embedding,
+                body,
+                inserted_at: Instant::now(),
+            });
+        }
+    }
+
+    /// 获取缓存统计
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self
+                .entries
+                .read()
+                .map(|e| e.values().map(|bucket| bucket.len()).sum())
+                .unwrap_or(0),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for SemanticCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((SemanticCache::cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-9);
+        assert!(SemanticCache::cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-9);
+        assert_eq!(SemanticCache::cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_partition_ignores_messages() {
+        let a = json!({"model": "gpt-4", "temperature": 0.2, "messages": [{"role": "user", "content": "hi"}]});
+        let b = json!({"model": "gpt-4", "temperature": 0.2, "messages": [{"role": "user", "content": "hello"}]});
+        let c = json!({"model": "gpt-4", "temperature": 0.7, "messages": [{"role": "user", "content": "hi"}]});
+        assert_eq!(SemanticCache::partition_key(&a), SemanticCache::partition_key(&b));
+        assert_ne!(SemanticCache::partition_key(&a), SemanticCache::partition_key(&c));
+        assert_eq!(SemanticCache::prompt_text(&a).as_deref(), Some("user: hi"));
+    }
+
+    #[test]
+    fn test_insert_keeps_max_entries() {
+        let settings = SemanticCacheSettings {
+            provider: "embed".to_string(),
+            model: "text-embedding-3-small".to_string(),
+            similarity_threshold: 0.9,
+            ttl_seconds: 60,
+            max_entries: 2,
+        };
+        let cache = SemanticCache::new();
+        for i in 0..3 {
+            cache.insert(&settings, "p".to_string(), vec![i as f32, 1.0], i.to_string());
+        }
+        assert_eq!(cache.stats().entries, 2);
+    }
+}
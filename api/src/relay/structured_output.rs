@@ -0,0 +1,229 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 请求是否要求JSON格式的输出（response_format为json_object或json_schema）
+pub fn requests_json(body: &Value) -> bool {
+    matches!(
+        body.pointer("/response_format/type").and_then(Value::as_str),
+        Some("json_object" | "json_schema")
+    )
+}
+
+/// 检查响应中每个choice的message.content是否为合法JSON，只有工具调用没有content的choice不检查
+pub fn validate_response(response: &Value) -> Result<(), String> {
+    let Some(choices) = response.get("choices").and_then(Value::as_array) else {
+        return Err("response has no choices".to_string());
+    };
+    for (index, choice) in choices.iter().enumerate() {
+        let Some(content) = choice.pointer("/message/content").and_then(Value::as_str) else {
+            continue;
+        };
+        if let Err(e) = serde_json::from_str::<Value>(content) {
+            return Err(format!("choice {}: {}", index, e));
+        }
+    }
+    Ok(())
+}
+
+/// 修复响应中不合法的JSON内容，返回是否全部修复成功
+pub fn repair_response(response: &mut Value) -> bool {
+    let Some(choices) = response.get_mut("choices").and_then(Value::as_array_mut) else {
+        return false;
+    };
+    let mut repaired = true;
+    for content in choices
+        .iter_mut()
+        .filter_map(|choice| choice.pointer_mut("/message/content"))
+    {
+        let Some(text) = content.as_str() else {
+            continue;
+        };
+        if serde_json::from_str::<Value>(text).is_ok() {
+            continue;
+        }
+        match repair_json(text) {
+            Some(fixed) => *content = Value::String(fixed),
+            None => repaired = false,
+        }
+    }
+    repaired
+}
+
+/// 尝试修复模型输出的JSON文本：去掉Markdown代码块标记和JSON前后的多余文本，删除尾随逗号，
+/// 补全被截断的字符串和括号，修复后仍不合法时返回None
+pub fn repair_json(text: &str) -> Option<String> {
+    let text = strip_code_fence(text.trim());
+    let start = text.find(['{', '['])?;
+
+    let mut repaired = String::with_capacity(text.len());
+    let mut closers = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text[start..].chars() {
+        if in_string {
+            repaired.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                repaired.push(c);
+            }
+            '{' => {
+                closers.push('}');
+                repaired.push(c);
+            }
+            '[' => {
+                closers.push(']');
+                repaired.push(c);
+            }
+            '}' | ']' => {
+                if closers.pop() != Some(c) {
+                    return None;
+                }
+                trim_trailing_comma(&mut repaired);
+                repaired.push(c);
+                // 最外层结束后的文本（如模型的解释说明）丢弃
+                if closers.is_empty() {
+                    break;
+                }
+            }
+            _ => repaired.push(c),
+        }
+    }
+
+    // 输出被截断时补全未闭合的字符串和括号
+    if in_string {
+        if escaped {
+            repaired.pop();
+        }
+        repaired.push('"');
+    }
+    while let Some(closer) = closers.pop() {
+        trim_trailing_comma(&mut repaired);
+        if repaired.ends_with(':') {
+            repaired.push_str("null");
+        }
+        repaired.push(closer);
+    }
+
+    serde_json::from_str::<Value>(&repaired).ok().map(|_| repaired)
+}
+
+/// 去掉"```json ... ```"形式的代码块标记
+fn strip_code_fence(text: &str) -> &str {
+    let Some(rest) = text.strip_prefix("```") else {
+        return text;
+    };
+    // 跳过语言标记所在的行
+    let body = rest.split_once('\n').map_or("", |(_, body)| body);
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+fn trim_trailing_comma(text: &mut String) {
+    text.truncate(text.trim_end().len());
+    if text.ends_with(',') {
+        text.pop();
+    }
+}
+
+/// 结构化输出校验统计
+#[derive(Debug, Clone, Serialize)]
+pub struct StructuredOutputStats {
+    /// 内容不是合法JSON的响应数
+    pub invalid: u64,
+    /// 因此换后端重试的次数
+    pub retried: u64,
+    /// 修复成功的响应数
+    pub repaired: u64,
+    /// 修复失败、原样返回的响应数
+    pub repair_failed: u64,
+}
+
+/// 结构化输出校验的计数器
+#[derive(Debug, Default)]
+pub struct StructuredOutputMetrics {
+    invalid: AtomicU64,
+    retried: AtomicU64,
+    repaired: AtomicU64,
+    repair_failed: AtomicU64,
+}
+
+impl StructuredOutputMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_invalid(&self) {
+        self.invalid.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_retry(&self) {
+        self.retried.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一次修复的结果
+    pub fn record_repair(&self, success: bool) {
+        let counter = if success { &self.repaired } else { &self.repair_failed };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> StructuredOutputStats {
+        StructuredOutputStats {
+            invalid: self.invalid.load(Ordering::Relaxed),
+            retried: self.retried.load(Ordering::Relaxed),
+            repaired: self.repaired.load(Ordering::Relaxed),
+            repair_failed: self.repair_failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_repairs_common_model_mistakes() {
+        let cases = [
+            ("```json\n{\"a\": 1}\n```", r#"{"a": 1}"#),
+            ("Here is the result: {\"a\": [1, 2,],} Hope this helps!", r#"{"a": [1, 2]}"#),
+            (r#"{"a": {"b": "trunc"#, r#"{"a": {"b": "trunc"}}"#),
+            (r#"[{"a": 1}, {"b":"#, r#"[{"a": 1}, {"b":null}]"#),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(repair_json(input).as_deref(), Some(expected), "input: {}", input);
+        }
+        assert_eq!(repair_json("no json here"), None);
+        assert_eq!(repair_json("{\"a\": 1]"), None);
+    }
+
+    #[test]
+    fn test_validates_and_repairs_responses() {
+        let mut response = json!({
+            "choices": [
+                {"message": {"role": "assistant", "content": "{\"ok\": true}"}},
+                {"message": {"role": "assistant", "content": "```json\n{\"ok\": true,}\n```"}},
+                {"message": {"role": "assistant", "content": null, "tool_calls": []}}
+            ]
+        });
+        assert!(requests_json(&json!({"response_format": {"type": "json_schema"}})));
+        assert!(!requests_json(&json!({"response_format": {"type": "text"}})));
+
+        assert!(validate_response(&response).unwrap_err().starts_with("choice 1"));
+        assert!(repair_response(&mut response));
+        assert!(validate_response(&response).is_ok());
+        assert_eq!(response["choices"][1]["message"]["content"], "{\"ok\": true}");
+
+        let mut broken = json!({"choices": [{"message": {"content": "sorry, I can't"}}]});
+        assert!(!repair_response(&mut broken));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::config::model::TimeoutSettings;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// 上游请求超时发生的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeoutKind {
+    /// 建立连接
+    Connect,
+    /// 等待流式响应的首个数据块
+    FirstToken,
+    /// 流式响应两个数据块之间
+    Idle,
+    /// 整个请求的总时长
+    Total,
+}
+
+impl TimeoutKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Connect => "connect",
+            Self::FirstToken => "first_token",
+            Self::Idle => "idle",
+            Self::Total => "total",
+        }
+    }
+}
+
+impl std::fmt::Display for TimeoutKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 单次上游请求的截止时间，从发出请求时开始计算（连接超时由HTTP客户端负责）
+#[derive(Debug, Clone, Copy)]
+pub struct UpstreamDeadlines {
+    started: Instant,
+    first_token: Option<Duration>,
+    idle: Option<Duration>,
+    total: Option<Duration>,
+}
+
+impl UpstreamDeadlines {
+    pub fn start(settings: &TimeoutSettings) -> Self {
+        Self {
+            started: Instant::now(),
+            first_token: settings.first_token_seconds.map(Duration::from_secs),
+            idle: settings.idle_seconds.map(Duration::from_secs),
+            total: settings.total_seconds.map(Duration::from_secs),
+        }
+    }
+
+    /// 取较早到期的截止时间，同时到期时按总时长计
+    fn earliest(&self, limit: Option<(Instant, TimeoutKind)>) -> Option<(Instant, TimeoutKind)> {
+        let total = self.total.map(|total| (self.started + total, TimeoutKind::Total));
+        match (limit, total) {
+            (Some(limit), Some(total)) if limit.0 < total.0 => Some(limit),
+            (limit, None) => limit,
+            (_, total) => total,
+        }
+    }
+
+    fn first_token_deadline(&self) -> Option<(Instant, TimeoutKind)> {
+        self.earliest(self.first_token.map(|limit| (self.started + limit, TimeoutKind::FirstToken)))
+    }
+
+    fn next_chunk_deadline(&self, last_chunk: Instant) -> Option<(Instant, TimeoutKind)> {
+        self.earliest(self.idle.map(|limit| (last_chunk + limit, TimeoutKind::Idle)))
+    }
+
+    /// 在首个数据块的截止时间内完成（用于流式请求的响应头和首个事件）
+    pub async fn before_first_token<F: Future>(&self, future: F) -> Result<F::Output, TimeoutKind> {
+        run_until(self.first_token_deadline(), future).await
+    }
+
+    /// 在总时长内完成（用于非流式请求）
+    pub async fn within_total<F: Future>(&self, future: F) -> Result<F::Output, TimeoutKind> {
+        run_until(self.earliest(None), future).await
+    }
+
+    /// 对流式响应的每个数据块应用首个token、间隔和总时长的超时，超时后产生一个错误并结束。
+    /// first_received表示首个数据块已经在返回响应前读取
+    pub fn guard_stream<S>(self, stream: S, first_received: bool) -> BoxStream<'static, Result<S::Item, TimeoutKind>>
+    where
+        S: Stream + Send + Unpin + 'static,
+        S::Item: Send,
+    {
+        let last_chunk = first_received.then(Instant::now);
+        futures::stream::unfold(Some((stream, last_chunk)), move |state| async move {
+            let (mut stream, last_chunk) = state?;
+            let deadline = match last_chunk {
+                Some(last_chunk) => self.next_chunk_deadline(last_chunk),
+                None => self.first_token_deadline(),
+            };
+            match run_until(deadline, stream.next()).await {
+                Ok(Some(item)) => Some((Ok(item), Some((stream, Some(Instant::now()))))),
+                Ok(None) => None,
+                Err(kind) => Some((Err(kind), None)),
+            }
+        })
+        .boxed()
+    }
+}
+
+async fn run_until<F: Future>(deadline: Option<(Instant, TimeoutKind)>, future: F) -> Result<F::Output, TimeoutKind> {
+    match deadline {
+        Some((deadline, kind)) => tokio::time::timeout_at(deadline, future).await.map_err(|_| kind),
+        None => Ok(future.await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deadlines(first_token: Option<u64>, idle: Option<u64>, total: Option<u64>) -> UpstreamDeadlines {
+        UpstreamDeadlines {
+            started: Instant::now(),
+            first_token: first_token.map(Duration::from_millis),
+            idle: idle.map(Duration::from_millis),
+            total: total.map(Duration::from_millis),
+        }
+    }
+
+    fn delayed(delays: &[u64]) -> BoxStream<'static, u64> {
+        futures::stream::iter(delays.to_vec())
+            .then(|delay| async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                delay
+            })
+            .boxed()
+    }
+
+    async fn collect(deadlines: UpstreamDeadlines, delays: &[u64]) -> Vec<Result<u64, TimeoutKind>> {
+        deadlines.guard_stream(delayed(delays), false).collect().await
+    }
+
+    #[tokio::test]
+    async fn test_first_token_timeout() {
+        let deadlines = deadlines(Some(50), Some(10), None);
+        assert_eq!(collect(deadlines, &[150]).await, vec![Err(TimeoutKind::FirstToken)]);
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_between_chunks() {
+        let deadlines = deadlines(Some(200), Some(100), None);
+        assert_eq!(collect(deadlines, &[150, 10, 250]).await, vec![Ok(150), Ok(10), Err(TimeoutKind::Idle)]);
+    }
+
+    #[tokio::test]
+    async fn test_total_timeout_wins_when_earlier() {
+        let deadlines = deadlines(None, Some(300), Some(200));
+        assert_eq!(collect(deadlines, &[100, 250]).await, vec![Ok(100), Err(TimeoutKind::Total)]);
+
+        let deadlines = UpstreamDeadlines::start(&TimeoutSettings {
+            total_seconds: Some(1),
+            ..Default::default()
+        });
+        let result = deadlines.within_total(tokio::time::sleep(Duration::from_millis(1500))).await;
+        assert_eq!(result, Err(TimeoutKind::Total));
+    }
+}
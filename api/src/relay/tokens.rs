@@ -0,0 +1,101 @@
+use serde_json::Value;
+use tiktoken_rs::CoreBPE;
+
+/// 每条消息的格式开销（角色和分隔符）
+const TOKENS_PER_MESSAGE: u64 = 4;
+/// 模型回复的起始开销
+const TOKENS_PER_REPLY: u64 = 3;
+/// 每张图片按低分辨率模式计算的token数
+const TOKENS_PER_IMAGE: u64 = 85;
+
+fn tokenizer() -> &'static CoreBPE {
+    tiktoken_rs::cl100k_base_singleton()
+}
+
+fn count(text: &str) -> u64 {
+    tokenizer().encode_with_special_tokens(text).len() as u64
+}
+
+/// 估算请求占用的上下文长度：提示词的token数加上请求的最大输出token数
+/// 按cl100k编码计数，其他模型的分词结果会略有差异，只用于跳过明显放不下的后端
+pub fn estimate_request_tokens(body: &Value) -> u64 {
+    prompt_tokens(body) + max_output_tokens(body)
+}
+
+/// 估算聊天消息（或文本补全的prompt）和工具定义的token数
+pub fn prompt_tokens(body: &Value) -> u64 {
+    let mut tokens = 0;
+    if let Some(messages) = body.get("messages").and_then(Value::as_array) {
+        for message in messages {
+            tokens += TOKENS_PER_MESSAGE + content_tokens(message.get("content"));
+            if let Some(name) = message.get("name").and_then(Value::as_str) {
+                tokens += count(name);
+            }
+            if let Some(tool_calls) = message.get("tool_calls") {
+                tokens += count(&tool_calls.to_string());
+            }
+        }
+        tokens += TOKENS_PER_REPLY;
+    }
+    tokens += content_tokens(body.get("prompt"));
+    if let Some(tools) = body.get("tools") {
+        tokens += count(&tools.to_string());
+    }
+    tokens
+}
+
+/// 消息内容可以是字符串或内容片段数组（文本、图片），补全的prompt还可以是token数组
+fn content_tokens(content: Option<&Value>) -> u64 {
+    match content {
+        Some(Value::String(text)) => count(text),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .map(|part| match part {
+                Value::String(text) => count(text),
+                Value::Number(_) => 1,
+                _ if part.get("type").and_then(Value::as_str) == Some("image_url") => TOKENS_PER_IMAGE,
+                _ => part.get("text").and_then(Value::as_str).map_or(0, count),
+            })
+            .sum(),
+        _ => 0,
+    }
+}
+
+/// 请求的最大输出token数，未指定时为0
+fn max_output_tokens(body: &Value) -> u64 {
+    ["max_completion_tokens", "max_tokens"]
+        .iter()
+        .find_map(|param| body.get(*param).and_then(Value::as_u64))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_estimates_chat_messages_and_output() {
+        let body = json!({
+            "model": "gpt-4",
+            "max_tokens": 100,
+            "messages": [
+                {"role": "system", "content": "hello world"},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "hello world"},
+                    {"type": "image_url", "image_url": {"url": "https://example.com/a.png"}}
+                ]}
+            ]
+        });
+        // 两条消息各4 + 文本各2 + 图片85 + 回复3
+        assert_eq!(prompt_tokens(&body), 4 + 2 + 4 + 2 + 85 + 3);
+        assert_eq!(estimate_request_tokens(&body), prompt_tokens(&body) + 100);
+    }
+
+    #[test]
+    fn test_estimates_completion_prompts() {
+        assert_eq!(estimate_request_tokens(&json!({"prompt": "hello world"})), 2);
+        assert_eq!(estimate_request_tokens(&json!({"prompt": [1, 2, 3], "max_completion_tokens": 5})), 8);
+        assert_eq!(estimate_request_tokens(&json!({})), 0);
+    }
+}
@@ -0,0 +1,308 @@
+use rand::Rng;
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+
+/// 不同provider表示工具调用结束的finish_reason，统一为OpenAI的"tool_calls"
+const TOOL_FINISH_REASONS: &[&str] = &["tool_use", "tool_call", "function_call"];
+
+/// 为provider没有返回id的工具调用生成"call_"加24位十六进制的id
+fn generate_call_id() -> String {
+    let mut rng = rand::rng();
+    let suffix: String = (0..24)
+        .map(|_| format!("{:x}", rng.random_range(0..16u8)))
+        .collect();
+    format!("call_{}", suffix)
+}
+
+/// 工具参数统一为JSON字符串，部分provider直接返回JSON对象
+fn normalize_arguments(function: &mut Map<String, Value>) -> bool {
+    match function.get("arguments") {
+        Some(Value::String(_)) | None => false,
+        Some(Value::Null) => {
+            function.insert("arguments".to_string(), Value::String(String::new()));
+            true
+        }
+        Some(arguments) => {
+            let arguments = arguments.to_string();
+            function.insert("arguments".to_string(), Value::String(arguments));
+            true
+        }
+    }
+}
+
+/// 统一finish_reason，返回是否有修改
+fn normalize_finish_reason(choice: &mut Map<String, Value>, has_tool_calls: bool) -> bool {
+    let Some(reason) = choice.get("finish_reason").and_then(Value::as_str) else {
+        return false;
+    };
+    if has_tool_calls && TOOL_FINISH_REASONS.contains(&reason) {
+        choice.insert("finish_reason".to_string(), json!("tool_calls"));
+        return true;
+    }
+    false
+}
+
+/// 把非流式响应中的工具调用转换为OpenAI格式：补全id和type，参数转为字符串，统一finish_reason
+/// 返回是否有修改
+pub fn normalize_response(response: &mut Value) -> bool {
+    let Some(choices) = response.get_mut("choices").and_then(Value::as_array_mut) else {
+        return false;
+    };
+    let mut changed = false;
+    for choice in choices.iter_mut().filter_map(Value::as_object_mut) {
+        let Some(tool_calls) = choice
+            .get_mut("message")
+            .and_then(|message| message.get_mut("tool_calls"))
+            .and_then(Value::as_array_mut)
+        else {
+            continue;
+        };
+        for call in tool_calls.iter_mut().filter_map(Value::as_object_mut) {
+            if !call.get("id").is_some_and(Value::is_string) {
+                call.insert("id".to_string(), json!(generate_call_id()));
+                changed = true;
+            }
+            if call.get("type").and_then(Value::as_str) != Some("function") {
+                call.insert("type".to_string(), json!("function"));
+                changed = true;
+            }
+            // 非流式响应中的index没有意义，OpenAI不返回该字段
+            if call.remove("index").is_some() {
+                changed = true;
+            }
+            if let Some(function) = call.get_mut("function").and_then(Value::as_object_mut) {
+                changed |= normalize_arguments(function);
+            }
+        }
+        let has_tool_calls = !tool_calls.is_empty();
+        changed |= normalize_finish_reason(choice, has_tool_calls);
+    }
+    changed
+}
+
+/// 规范化非流式响应体，没有工具调用或无法解析时原样返回
+pub fn normalize_response_text(text: String) -> String {
+    if !text.contains("tool_calls") {
+        return text;
+    }
+    let Ok(mut value) = serde_json::from_str::<Value>(&text) else {
+        return text;
+    };
+    if normalize_response(&mut value) {
+        value.to_string()
+    } else {
+        text
+    }
+}
+
+/// 流式响应中某个choice已出现的工具调用
+#[derive(Debug, Default)]
+struct ChoiceState {
+    /// 已出现的工具调用id到index的映射
+    ids: HashMap<String, u64>,
+    /// 已发送过首个分片（带id、type和name）的index
+    started: Vec<u64>,
+    /// 没有id也没有index的分片追加到最后一个工具调用
+    last_index: Option<u64>,
+}
+
+/// 流式工具调用的规范化状态，每个流一个实例
+/// OpenAI的格式是：每个工具调用用index区分，首个分片带id、type和function.name，后续分片只带arguments的增量
+#[derive(Debug, Default)]
+pub struct ToolCallStreamNormalizer {
+    choices: HashMap<u64, ChoiceState>,
+}
+
+impl ToolCallStreamNormalizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 规范化一个SSE事件的数据，没有工具调用时原样返回
+    pub fn normalize_event(&mut self, data: String) -> String {
+        if self.choices.is_empty() && !data.contains("tool_calls") {
+            return data;
+        }
+        let Ok(mut value) = serde_json::from_str::<Value>(&data) else {
+            return data;
+        };
+        if self.normalize_chunk(&mut value) {
+            value.to_string()
+        } else {
+            data
+        }
+    }
+
+    /// 规范化一个流式分片，返回是否有修改
+    pub fn normalize_chunk(&mut self, chunk: &mut Value) -> bool {
+        let Some(choices) = chunk.get_mut("choices").and_then(Value::as_array_mut) else {
+            return false;
+        };
+        let mut changed = false;
+        for (position, choice) in choices.iter_mut().enumerate() {
+            let Some(choice) = choice.as_object_mut() else {
+                continue;
+            };
+            let choice_index = choice
+                .get("index")
+                .and_then(Value::as_u64)
+                .unwrap_or(position as u64);
+            if let Some(tool_calls) = choice
+                .get_mut("delta")
+                .and_then(|delta| delta.get_mut("tool_calls"))
+                .and_then(Value::as_array_mut)
+            {
+                let state = self.choices.entry(choice_index).or_default();
+                for call in tool_calls.iter_mut().filter_map(Value::as_object_mut) {
+                    changed |= state.normalize_delta(call);
+                }
+            }
+            let has_tool_calls = self.choices.contains_key(&choice_index);
+            changed |= normalize_finish_reason(choice, has_tool_calls);
+        }
+        changed
+    }
+}
+
+impl ChoiceState {
+    fn normalize_delta(&mut self, call: &mut Map<String, Value>) -> bool {
+        let mut changed = false;
+        let id = call.get("id").and_then(Value::as_str).map(str::to_string);
+
+        // 确定index：优先使用provider给出的index，其次按id对应，都没有时为新的或最后一个工具调用
+        let index = match (call.get("index").and_then(Value::as_u64), &id) {
+            (Some(index), _) => index,
+            (None, Some(id)) => match self.ids.get(id) {
+                Some(index) => *index,
+                None => self.started.iter().max().map_or(0, |max| max + 1),
+            },
+            (None, None) if call.get("function").and_then(|f| f.get("name")).is_some() => {
+                self.started.iter().max().map_or(0, |max| max + 1)
+            }
+            (None, None) => self.last_index.unwrap_or(0),
+        };
+        if call.get("index").and_then(Value::as_u64) != Some(index) {
+            call.insert("index".to_string(), json!(index));
+            changed = true;
+        }
+        self.last_index = Some(index);
+
+        if let Some(function) = call.get_mut("function").and_then(Value::as_object_mut) {
+            changed |= normalize_arguments(function);
+        }
+
+        if self.started.contains(&index) {
+            // 后续分片只保留arguments增量，部分provider在每个分片中重复id、type和name
+            changed |= call.remove("id").is_some();
+            changed |= call.remove("type").is_some();
+            if let Some(function) = call.get_mut("function").and_then(Value::as_object_mut) {
+                changed |= function.remove("name").is_some();
+            }
+            return changed;
+        }
+
+        self.started.push(index);
+        let id = match id {
+            Some(id) => id,
+            None => {
+                changed = true;
+                generate_call_id()
+            }
+        };
+        self.ids.insert(id.clone(), index);
+        call.insert("id".to_string(), json!(id));
+        if call.get("type").and_then(Value::as_str) != Some("function") {
+            call.insert("type".to_string(), json!("function"));
+            changed = true;
+        }
+        if let Some(function) = call.entry("function").or_insert_with(|| json!({})).as_object_mut() {
+            if !function.contains_key("name") {
+                function.insert("name".to_string(), json!(""));
+                changed = true;
+            }
+            if !function.contains_key("arguments") {
+                function.insert("arguments".to_string(), json!(""));
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalizes_non_streaming_tool_calls() {
+        let mut response = json!({
+            "choices": [{
+                "index": 0,
+                "finish_reason": "tool_use",
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{"index": 0, "function": {"name": "lookup", "arguments": {"city": "Paris"}}}]
+                }
+            }]
+        });
+        assert!(normalize_response(&mut response));
+        let choice = &response["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        let call = &choice["message"]["tool_calls"][0];
+        assert!(call["id"].as_str().unwrap().starts_with("call_"));
+        assert_eq!(call["type"], "function");
+        assert!(call.get("index").is_none());
+        assert_eq!(call["function"]["arguments"], r#"{"city":"Paris"}"#);
+
+        // 已经是标准格式时不修改
+        let canonical = response.to_string();
+        assert_eq!(normalize_response_text(canonical.clone()), canonical);
+    }
+
+    #[test]
+    fn test_normalizes_streaming_tool_call_deltas() {
+        let mut normalizer = ToolCallStreamNormalizer::new();
+        let mut chunk = |delta: Value, finish_reason: Value| {
+            let mut chunk = json!({"choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]});
+            normalizer.normalize_chunk(&mut chunk);
+            chunk["choices"][0].clone()
+        };
+
+        // 没有index和type、每个分片重复id和name的provider
+        let first = chunk(
+            json!({"tool_calls": [{"id": "a", "function": {"name": "lookup", "arguments": "{\"ci"}}]}),
+            Value::Null,
+        );
+        assert_eq!(
+            first["delta"]["tool_calls"][0],
+            json!({"index": 0, "id": "a", "type": "function", "function": {"name": "lookup", "arguments": "{\"ci"}})
+        );
+        let second = chunk(
+            json!({"tool_calls": [{"id": "a", "function": {"name": "lookup", "arguments": "ty\":1}"}}]}),
+            Value::Null,
+        );
+        assert_eq!(second["delta"]["tool_calls"][0], json!({"index": 0, "function": {"arguments": "ty\":1}"}}));
+
+        // 第二个工具调用没有id时生成id并分配新的index，参数对象转为字符串
+        let third = chunk(
+            json!({"tool_calls": [{"function": {"name": "search", "arguments": {"q": "x"}}}]}),
+            Value::Null,
+        );
+        let call = &third["delta"]["tool_calls"][0];
+        assert_eq!(call["index"], 1);
+        assert!(call["id"].as_str().unwrap().starts_with("call_"));
+        assert_eq!(call["function"]["arguments"], r#"{"q":"x"}"#);
+
+        let last = chunk(json!({}), json!("tool_use"));
+        assert_eq!(last["finish_reason"], "tool_calls");
+    }
+
+    #[test]
+    fn test_plain_text_stream_is_untouched() {
+        let mut normalizer = ToolCallStreamNormalizer::new();
+        let data = json!({"choices": [{"index": 0, "delta": {"content": "hi"}, "finish_reason": "function_call"}]}).to_string();
+        assert_eq!(normalizer.normalize_event(data.clone()), data);
+        assert_eq!(normalizer.normalize_event("[DONE]".to_string()), "[DONE]");
+    }
+}
@@ -0,0 +1,127 @@
+use crate::config::model::{ParamOverride, RequestTransform};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// 限制上限的token参数名称
+const MAX_TOKENS_PARAMS: &[&str] = &["max_tokens", "max_completion_tokens"];
+
+/// 按顺序应用适用于该provider的改写规则
+pub fn apply_transforms(transforms: &[RequestTransform], provider: &str, body: &mut Value) {
+    let Some(object) = body.as_object_mut() else {
+        return;
+    };
+
+    for transform in transforms.iter().filter(|t| t.applies_to(provider)) {
+        for param in &transform.strip_params {
+            object.remove(param);
+        }
+        for (param, value) in &transform.defaults {
+            object.entry(param.clone()).or_insert_with(|| value.clone());
+        }
+        if let Some(cap) = transform.max_tokens {
+            for param in MAX_TOKENS_PARAMS {
+                if let Some(value) = object.get_mut(*param)
+                    && value.as_u64().is_some_and(|tokens| tokens > cap)
+                {
+                    *value = Value::from(cap);
+                }
+            }
+        }
+    }
+}
+
+/// 应用后端的参数覆盖
+pub fn apply_overrides(overrides: &HashMap<String, ParamOverride>, body: &mut Value) {
+    let Some(object) = body.as_object_mut() else {
+        return;
+    };
+
+    for (param, param_override) in overrides {
+        match param_override {
+            ParamOverride::Set(value) => {
+                object.insert(param.clone(), value.clone());
+            }
+            ParamOverride::Max(max) => {
+                if let Some(value) = object.get_mut(param)
+                    && value.as_f64().is_some_and(|current| current > *max)
+                {
+                    // 整数上限保持整数，避免max_tokens变成4096.0
+                    *value = if max.fract() == 0.0 && (value.is_i64() || value.is_u64()) {
+                        Value::from(*max as i64)
+                    } else {
+                        Value::from(*max)
+                    };
+                }
+            }
+            ParamOverride::Remove => {
+                object.remove(param);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_apply_overrides() {
+        let overrides: HashMap<String, ParamOverride> = toml::from_str(
+            r#"
+            max_tokens = { max = 4096 }
+            temperature = { max = 1.0 }
+            top_p = "remove"
+            seed = { set = 42 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(overrides["top_p"], ParamOverride::Remove);
+        let mut body = json!({
+            "model": "gpt-4",
+            "max_tokens": 8192,
+            "temperature": 1.5,
+            "top_p": 0.9
+        });
+
+        apply_overrides(&overrides, &mut body);
+        assert_eq!(body["max_tokens"], json!(4096));
+        assert_eq!(body["temperature"], json!(1.0));
+        assert!(body.get("top_p").is_none());
+        assert_eq!(body["seed"], 42);
+    }
+
+    #[test]
+    fn test_apply_transforms() {
+        let transforms = vec![
+            RequestTransform {
+                max_tokens: Some(4096),
+                defaults: HashMap::from([("temperature".to_string(), json!(0.7))]),
+                ..Default::default()
+            },
+            RequestTransform {
+                providers: vec!["azure".to_string()],
+                strip_params: vec!["logit_bias".to_string()],
+                ..Default::default()
+            },
+        ];
+        let request = json!({
+            "model": "gpt-4",
+            "max_tokens": 8192,
+            "logit_bias": {"50256": -100}
+        });
+
+        let mut body = request.clone();
+        apply_transforms(&transforms, "openai", &mut body);
+        assert_eq!(body["max_tokens"], 4096);
+        assert_eq!(body["temperature"], 0.7);
+        assert!(body.get("logit_bias").is_some());
+
+        let mut body = request.clone();
+        body["temperature"] = json!(0.0);
+        apply_transforms(&transforms, "azure", &mut body);
+        assert_eq!(body["temperature"], 0.0);
+        assert!(body.get("logit_bias").is_none());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::config::model::{MaxTokensAction, UserRequestLimits};
+use crate::relay::error::RelayError;
+use axum::body::{Body, Bytes};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use futures::StreamExt;
+use serde_json::Value;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// 聊天和文本补全请求中限制生成长度的参数，请求都未指定时写入第一个
+pub const COMPLETION_TOKEN_PARAMS: &[&str] = &["max_tokens", "max_completion_tokens"];
+/// Responses请求中限制生成长度的参数
+pub const RESPONSE_TOKEN_PARAMS: &[&str] = &["max_output_tokens"];
+
+/// 按用户的上限限制请求的生成长度：超出时降低到上限或拒绝请求，未指定时使用上限
+pub fn apply_max_tokens(limits: &UserRequestLimits, params: &[&str], body: &mut Value) -> Result<(), RelayError> {
+    let Some(cap) = limits.max_tokens else {
+        return Ok(());
+    };
+    let Some(object) = body.as_object_mut() else {
+        return Ok(());
+    };
+
+    let mut specified = false;
+    for param in params {
+        let Some(value) = object.get_mut(*param).filter(|value| !value.is_null()) else {
+            continue;
+        };
+        specified = true;
+        let Some(tokens) = value.as_u64().filter(|tokens| *tokens > cap) else {
+            continue;
+        };
+        match limits.on_max_tokens {
+            MaxTokensAction::Clamp => *value = Value::from(cap),
+            MaxTokensAction::Reject => {
+                return Err(RelayError::MaxTokensExceeded(format!(
+                    "{} = {} exceeds the limit of {} for this API key",
+                    param, tokens, cap
+                )));
+            }
+        }
+    }
+    if !specified && let Some(param) = params.first() {
+        object.insert(param.to_string(), Value::from(cap));
+    }
+    Ok(())
+}
+
+/// 限制请求从`started`开始的总耗时：超时前没有返回响应时返回408，
+/// 响应体在到期时截断并追加错误（流式响应为`data:`事件，保活的非流式响应为错误JSON）
+pub async fn with_max_duration<F>(limits: Option<&UserRequestLimits>, started: Instant, response: F) -> Response
+where
+    F: Future<Output = Response>,
+{
+    let Some(max_duration) = limits.and_then(|limits| limits.max_duration_seconds).map(Duration::from_secs) else {
+        return response.await;
+    };
+    let deadline = tokio::time::Instant::now() + max_duration.saturating_sub(started.elapsed());
+    let error = RelayError::DurationLimitExceeded(format!(
+        "Request exceeded the maximum duration of {}s for this API key",
+        max_duration.as_secs()
+    ));
+
+    let response = match tokio::time::timeout_at(deadline, response).await {
+        Ok(response) => response,
+        Err(_) => return error.into_response(),
+    };
+
+    let (mut parts, body) = response.into_parts();
+    let is_event_stream = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"));
+    let error = if is_event_stream {
+        Bytes::from(format!("data: {}\n\n", error.to_json()))
+    } else {
+        Bytes::from(error.to_json().to_string())
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    let expired = Box::pin(tokio::time::sleep_until(deadline));
+    let stream = futures::stream::unfold(Some((body.into_data_stream(), expired, error)), |state| async move {
+        let (mut body, mut expired, error) = state?;
+        tokio::select! {
+            chunk = body.next() => chunk.map(|chunk| (chunk, Some((body, expired, error)))),
+            _ = &mut expired => Some((Ok(error), None)),
+        }
+    });
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn limits(max_tokens: u64, on_max_tokens: MaxTokensAction) -> UserRequestLimits {
+        UserRequestLimits {
+            max_tokens: Some(max_tokens),
+            on_max_tokens,
+            max_duration_seconds: None,
+            concurrency: None,
+        }
+    }
+
+    #[test]
+    fn test_max_tokens_clamped_or_filled_in() {
+        let clamp = limits(1000, MaxTokensAction::Clamp);
+
+        let mut body = json!({"max_tokens": 100000, "max_completion_tokens": 500});
+        apply_max_tokens(&clamp, COMPLETION_TOKEN_PARAMS, &mut body).unwrap();
+        assert_eq!(body, json!({"max_tokens": 1000, "max_completion_tokens": 500}));
+
+        let mut body = json!({"max_completion_tokens": null});
+        apply_max_tokens(&clamp, COMPLETION_TOKEN_PARAMS, &mut body).unwrap();
+        assert_eq!(body["max_tokens"], 1000);
+
+        let mut body = json!({"input": "hi"});
+        apply_max_tokens(&clamp, RESPONSE_TOKEN_PARAMS, &mut body).unwrap();
+        assert_eq!(body["max_output_tokens"], 1000);
+    }
+
+    #[test]
+    fn test_max_tokens_rejected() {
+        let reject = limits(1000, MaxTokensAction::Reject);
+
+        let mut body = json!({"max_completion_tokens": 1001});
+        let error = apply_max_tokens(&reject, COMPLETION_TOKEN_PARAMS, &mut body).unwrap_err();
+        assert_eq!(error.code(), "max_tokens_exceeded");
+
+        let mut body = json!({"max_tokens": 1000});
+        assert!(apply_max_tokens(&reject, COMPLETION_TOKEN_PARAMS, &mut body).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stream_truncated_at_deadline() {
+        let limits = UserRequestLimits {
+            max_duration_seconds: Some(1),
+            ..Default::default()
+        };
+        let chunks = futures::stream::iter(["data: one\n\n", "data: two\n\n"]).then(|chunk| async move {
+            tokio::time::sleep(Duration::from_millis(700)).await;
+            Ok::<_, std::convert::Infallible>(chunk)
+        });
+        let response = Response::builder()
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .body(Body::from_stream(chunks))
+            .unwrap();
+
+        let response = with_max_duration(Some(&limits), Instant::now(), async { response }).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.starts_with("data: one\n\ndata: {\"error\""), "unexpected body: {}", text);
+        assert!(text.contains("max_duration_exceeded"));
+        assert!(!text.contains("two"));
+    }
+}
@@ -0,0 +1,392 @@
+use crate::config::model::WasmPluginSettings;
+use serde_json::Value;
+#[cfg(feature = "wasm-plugins")]
+use std::sync::Arc;
+
+/// 插件挂载点
+/// 插件ABI（版本1）：
+/// - 导出线性内存 `memory` 和 `berry_alloc(len: i32) -> i32`，宿主调用后者分配输入缓冲区
+/// - 按需导出挂载点函数 `berry_on_request` / `berry_on_response`，签名为 `(ptr: i32, len: i32) -> i64`，
+///   输入为JSON `{"hook": "request", "model": "...", "body": {...}}`，返回值高32位为输出地址、低32位为输出长度，
+///   输出为替换后的body（JSON），长度为0表示不修改
+/// - 可选导出 `berry_abi_version() -> i32`，存在时必须返回1
+/// - 不能导入任何宿主函数，每次调用使用新的实例，调用之间不共享状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasmHook {
+    /// 聊天和补全请求转发前的请求体
+    Request,
+    /// 非流式成功响应返回给客户端前的响应体
+    Response,
+}
+
+impl WasmHook {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Request => "request",
+            Self::Response => "response",
+        }
+    }
+
+    #[cfg_attr(not(feature = "wasm-plugins"), allow(dead_code))]
+    fn export_name(&self) -> &'static str {
+        match self {
+            Self::Request => "berry_on_request",
+            Self::Response => "berry_on_response",
+        }
+    }
+}
+
+/// 已加载的插件在某个挂载点的执行器
+#[derive(Clone)]
+pub struct WasmHookRunner {
+    #[cfg(feature = "wasm-plugins")]
+    plugins: Arc<runtime::PluginSet>,
+    hook: WasmHook,
+}
+
+impl WasmHookRunner {
+    /// 依次执行插件，返回修改后的body，没有插件修改时返回None
+    /// 插件执行是同步的CPU计算，放到阻塞线程池中运行
+    pub async fn run(&self, model: &str, body: &Value) -> Option<Value> {
+        #[cfg(feature = "wasm-plugins")]
+        {
+            let (plugins, hook) = (self.plugins.clone(), self.hook);
+            let (model, body) = (model.to_string(), body.clone());
+            tokio::task::spawn_blocking(move || plugins.run(hook, &model, body))
+                .await
+                .ok()
+                .flatten()
+        }
+        #[cfg(not(feature = "wasm-plugins"))]
+        {
+            let _ = (model, body, self.hook);
+            None
+        }
+    }
+}
+
+/// WASM插件宿主
+/// 按配置懒加载插件目录，配置变更（热加载）时重新加载
+pub struct WasmPluginHost {
+    #[cfg(feature = "wasm-plugins")]
+    loaded: std::sync::Mutex<Option<(WasmPluginSettings, Arc<runtime::PluginSet>)>>,
+    #[cfg(not(feature = "wasm-plugins"))]
+    warned: std::sync::atomic::AtomicBool,
+}
+
+impl WasmPluginHost {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "wasm-plugins")]
+            loaded: std::sync::Mutex::new(None),
+            #[cfg(not(feature = "wasm-plugins"))]
+            warned: std::sync::atomic::AtomicBool::new(false),
+        }
+    }
+
+    /// 获取挂载点的执行器，没有插件导出该挂载点时返回None
+    #[cfg(feature = "wasm-plugins")]
+    pub fn runner(&self, settings: &WasmPluginSettings, hook: WasmHook) -> Option<WasmHookRunner> {
+        let plugins = {
+            let mut loaded = self.loaded.lock().ok()?;
+            match loaded.as_ref() {
+                Some((current, plugins)) if current == settings => plugins.clone(),
+                _ => {
+                    let plugins = Arc::new(runtime::PluginSet::load(settings));
+                    *loaded = Some((settings.clone(), plugins.clone()));
+                    plugins
+                }
+            }
+        };
+        plugins.has_hook(hook).then_some(WasmHookRunner { plugins, hook })
+    }
+
+    /// 未启用wasm-plugins编译特性时插件配置不生效
+    #[cfg(not(feature = "wasm-plugins"))]
+    pub fn runner(&self, settings: &WasmPluginSettings, hook: WasmHook) -> Option<WasmHookRunner> {
+        if !self.warned.swap(true, std::sync::atomic::Ordering::Relaxed) {
+            tracing::warn!(
+                "settings.wasm_plugins is configured ({}) but this build does not include the wasm-plugins feature, plugins are ignored",
+                settings.directory
+            );
+        }
+        let _ = hook;
+        None
+    }
+}
+
+impl Default for WasmPluginHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "wasm-plugins")]
+mod runtime {
+    use super::WasmHook;
+    use crate::config::model::WasmPluginSettings;
+    use anyhow::{Context, Result};
+    use serde::Serialize;
+    use serde_json::Value;
+    use std::path::Path;
+    use std::time::Duration;
+    use wasmtime::{Engine, InstancePre, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+    /// 插件支持的ABI版本
+    const ABI_VERSION: i32 = 1;
+    /// epoch计时的间隔，执行时间上限按此粒度计算
+    const EPOCH_TICK: Duration = Duration::from_millis(1);
+
+    #[derive(Serialize)]
+    struct PluginInput<'a> {
+        hook: &'static str,
+        model: &'a str,
+        body: &'a Value,
+    }
+
+    struct WasmPlugin {
+        name: String,
+        pre: InstancePre<StoreLimits>,
+        hooks: Vec<WasmHook>,
+        timeout_ms: u64,
+    }
+
+    /// 一个插件目录中加载的全部插件
+    pub struct PluginSet {
+        engine: Engine,
+        plugins: Vec<WasmPlugin>,
+        max_memory_bytes: usize,
+    }
+
+    impl PluginSet {
+        /// 加载插件目录，无法加载的插件记录错误后跳过
+        pub fn load(settings: &WasmPluginSettings) -> Self {
+            let mut config = wasmtime::Config::new();
+            config.epoch_interruption(true);
+            let engine = Engine::new(&config).expect("default wasmtime config is valid");
+            spawn_epoch_ticker(&engine);
+
+            let mut set = Self {
+                engine,
+                plugins: Vec::new(),
+                max_memory_bytes: (settings.max_memory_mb * 1024 * 1024) as usize,
+            };
+            let mut paths = match std::fs::read_dir(&settings.directory) {
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "wasm" || ext == "wat"))
+                    .collect::<Vec<_>>(),
+                Err(e) => {
+                    tracing::error!("Failed to read wasm plugin directory '{}': {}", settings.directory, e);
+                    return set;
+                }
+            };
+            paths.sort();
+
+            for path in paths {
+                match set.load_plugin(settings, &path) {
+                    Ok(plugin) => {
+                        tracing::info!(
+                            "Loaded wasm plugin '{}' (hooks: {:?}, timeout: {}ms)",
+                            plugin.name,
+                            plugin.hooks.iter().map(WasmHook::name).collect::<Vec<_>>(),
+                            plugin.timeout_ms
+                        );
+                        set.plugins.push(plugin);
+                    }
+                    Err(e) => tracing::error!("Failed to load wasm plugin '{}': {:#}", path.display(), e),
+                }
+            }
+            set
+        }
+
+        fn load_plugin(&self, settings: &WasmPluginSettings, path: &Path) -> Result<WasmPlugin> {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let module = Module::from_file(&self.engine, path)?;
+            // 沙箱：不提供任何宿主函数，插件无法访问文件、网络和时钟
+            if let Some(import) = module.imports().next() {
+                anyhow::bail!(
+                    "plugins must not import host functions, found import '{}::{}'",
+                    import.module(),
+                    import.name()
+                );
+            }
+            let hooks: Vec<WasmHook> = [WasmHook::Request, WasmHook::Response]
+                .into_iter()
+                .filter(|hook| module.get_export(hook.export_name()).is_some())
+                .collect();
+            if hooks.is_empty() {
+                anyhow::bail!("plugin exports neither berry_on_request nor berry_on_response");
+            }
+
+            let plugin = WasmPlugin {
+                timeout_ms: settings
+                    .plugin_timeouts_ms
+                    .get(&name)
+                    .copied()
+                    .unwrap_or(settings.timeout_ms),
+                name,
+                pre: Linker::new(&self.engine).instantiate_pre(&module)?,
+                hooks,
+            };
+
+            let mut store = self.store(plugin.timeout_ms);
+            let instance = plugin.pre.instantiate(&mut store)?;
+            if let Ok(version) = instance.get_typed_func::<(), i32>(&mut store, "berry_abi_version") {
+                let version = version.call(&mut store, ())?;
+                if version != ABI_VERSION {
+                    anyhow::bail!("unsupported plugin ABI version {} (expected {})", version, ABI_VERSION);
+                }
+            }
+            Ok(plugin)
+        }
+
+        fn store(&self, timeout_ms: u64) -> Store<StoreLimits> {
+            let limits = StoreLimitsBuilder::new()
+                .memory_size(self.max_memory_bytes)
+                .instances(1)
+                .build();
+            let mut store = Store::new(&self.engine, limits);
+            store.limiter(|limits| limits);
+            store.set_epoch_deadline((timeout_ms / EPOCH_TICK.as_millis() as u64).max(1));
+            store
+        }
+
+        pub fn has_hook(&self, hook: WasmHook) -> bool {
+            self.plugins.iter().any(|plugin| plugin.hooks.contains(&hook))
+        }
+
+        /// 依次执行导出了该挂载点的插件，出错或超时的插件跳过
+        pub fn run(&self, hook: WasmHook, model: &str, body: Value) -> Option<Value> {
+            let mut current = body;
+            let mut changed = false;
+            for plugin in self.plugins.iter().filter(|plugin| plugin.hooks.contains(&hook)) {
+                let result = serde_json::to_vec(&PluginInput {
+                    hook: hook.name(),
+                    model,
+                    body: &current,
+                })
+                .map_err(anyhow::Error::from)
+                .and_then(|input| self.call(plugin, hook, &input));
+                match result {
+                    Ok(None) => {}
+                    Ok(Some(output)) => match serde_json::from_slice::<Value>(&output) {
+                        Ok(value) => {
+                            current = value;
+                            changed = true;
+                        }
+                        Err(e) => tracing::warn!("Wasm plugin '{}' returned invalid JSON: {}", plugin.name, e),
+                    },
+                    Err(e) if e.downcast_ref::<Trap>() == Some(&Trap::Interrupt) => tracing::warn!(
+                        "Wasm plugin '{}' exceeded its {}ms time limit on {} hook",
+                        plugin.name,
+                        plugin.timeout_ms,
+                        hook.name()
+                    ),
+                    Err(e) => tracing::warn!("Wasm plugin '{}' failed on {} hook: {:#}", plugin.name, hook.name(), e),
+                }
+            }
+            changed.then_some(current)
+        }
+
+        fn call(&self, plugin: &WasmPlugin, hook: WasmHook, input: &[u8]) -> Result<Option<Vec<u8>>> {
+            let mut store = self.store(plugin.timeout_ms);
+            let instance = plugin.pre.instantiate(&mut store)?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .context("plugin does not export memory")?;
+            let alloc = instance.get_typed_func::<i32, i32>(&mut store, "berry_alloc")?;
+            let func = instance.get_typed_func::<(i32, i32), i64>(&mut store, hook.export_name())?;
+
+            let len = i32::try_from(input.len()).context("plugin input too large")?;
+            let ptr = alloc.call(&mut store, len)?;
+            memory.write(&mut store, ptr as u32 as usize, input)?;
+            let packed = func.call(&mut store, (ptr, len))? as u64;
+
+            let (output_ptr, output_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+            if output_len == 0 {
+                return Ok(None);
+            }
+            let mut output = vec![0; output_len];
+            memory.read(&store, output_ptr, &mut output)?;
+            Ok(Some(output))
+        }
+    }
+
+    /// 定期推进engine的epoch，执行超过deadline的插件被中断；engine释放后线程退出
+    fn spawn_epoch_ticker(engine: &Engine) {
+        let engine = engine.weak();
+        std::thread::spawn(move || {
+            while let Some(engine) = engine.upgrade() {
+                engine.increment_epoch();
+                drop(engine);
+                std::thread::sleep(EPOCH_TICK);
+            }
+        });
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use serde_json::json;
+        use std::collections::HashMap;
+
+        /// 输出固定的JSON
+        const CONSTANT_PLUGIN: &str = r#"(module
+            (memory (export "memory") 1)
+            (data (i32.const 0) "{\"replaced\":true}")
+            (func (export "berry_abi_version") (result i32) (i32.const 1))
+            (func (export "berry_alloc") (param i32) (result i32) (i32.const 1024))
+            (func (export "berry_on_request") (param i32 i32) (result i64) (i64.const 17)))"#;
+        /// 死循环，只能被超时中断
+        const LOOP_PLUGIN: &str = r#"(module
+            (memory (export "memory") 1)
+            (func (export "berry_alloc") (param i32) (result i32) (i32.const 1024))
+            (func (export "berry_on_request") (param i32 i32) (result i64) (loop $spin (br $spin)) (i64.const 0)))"#;
+        /// 导入宿主函数，应被拒绝
+        const IMPORT_PLUGIN: &str = r#"(module
+            (import "env" "log" (func))
+            (memory (export "memory") 1)
+            (func (export "berry_alloc") (param i32) (result i32) (i32.const 0))
+            (func (export "berry_on_request") (param i32 i32) (result i64) (i64.const 0)))"#;
+
+        fn load(name: &str, plugins: &[(&str, &str)]) -> PluginSet {
+            let directory = std::env::temp_dir().join(format!("berry-wasm-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&directory);
+            std::fs::create_dir_all(&directory).unwrap();
+            for (file, source) in plugins {
+                std::fs::write(directory.join(file), source).unwrap();
+            }
+            let set = PluginSet::load(&WasmPluginSettings {
+                directory: directory.to_string_lossy().to_string(),
+                timeout_ms: 1000,
+                plugin_timeouts_ms: HashMap::from([("a_loop".to_string(), 20)]),
+                max_memory_mb: 16,
+            });
+            let _ = std::fs::remove_dir_all(&directory);
+            set
+        }
+
+        #[test]
+        fn test_plugins_transform_and_time_out() {
+            let set = load(
+                "run",
+                &[("a_loop.wat", LOOP_PLUGIN), ("b_constant.wat", CONSTANT_PLUGIN), ("c_import.wat", IMPORT_PLUGIN)],
+            );
+            let names: Vec<_> = set.plugins.iter().map(|plugin| plugin.name.as_str()).collect();
+            assert_eq!(names, ["a_loop", "b_constant"]);
+            assert!(set.has_hook(WasmHook::Request));
+            assert!(!set.has_hook(WasmHook::Response));
+
+            // 超时的插件被跳过，后面的插件照常执行
+            let started = std::time::Instant::now();
+            let output = set.run(WasmHook::Request, "gpt-4", json!({"model": "gpt-4"}));
+            assert_eq!(output, Some(json!({"replaced": true})));
+            assert!(started.elapsed() < Duration::from_millis(900));
+            assert_eq!(set.run(WasmHook::Response, "gpt-4", json!({})), None);
+        }
+    }
+}
@@ -0,0 +1,1297 @@
+use crate::app::AppState;
+use crate::config::model::{AdminRole, LoadBalanceStrategy, RequestPriority, UserToken};
+use crate::loadbalance::{ConfigUpdateError, SelectionContext, UsageBucket, UsageDimension, UsageQuery, UsageReport};
+use crate::relay::fault::FaultSpec;
+use crate::relay::handler::{ErrorType, create_error_response};
+use crate::telemetry::LogFilterControl;
+use axum::{
+    extract::{Path, Query, State},
+    response::{
+        IntoResponse, Response, Sse,
+        sse::{Event, KeepAlive},
+    },
+    Json,
+};
+use axum_extra::TypedHeader;
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::json;
+use ipnet::IpNet;
+use std::collections::HashMap;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, info, warn};
+
+pub use crate::config::model::ADMIN_TAG;
+
+type Bearer = headers::Authorization<headers::authorization::Bearer>;
+type AdminAuth = TypedHeader<Bearer>;
+
+/// 校验管理员令牌
+async fn authorize_admin(state: &AppState, authorization: &Bearer) -> Result<UserToken, Box<Response>> {
+    match state.authenticate(authorization.token()).await {
+        Some(user) if user.is_admin() => Ok(user),
+        Some(user) => {
+            warn!("User '{}' attempted to access admin API without platform admin role", user.name);
+            let details = match user.admin_role() {
+                Some(AdminRole::Tenant(tenant)) => {
+                    format!("Tenant admins can only use the /admin/tenants/{}/ endpoints", tenant)
+                }
+                _ => format!("The token must belong to a user tagged '{}'", ADMIN_TAG),
+            };
+            Err(Box::new(
+                create_error_response(ErrorType::Forbidden, "Admin privileges required", Some(details))
+                    .into_response(),
+            ))
+        }
+        None => Err(Box::new(
+            create_error_response(ErrorType::Unauthorized, "The provided API key is invalid", None)
+                .into_response(),
+        )),
+    }
+}
+
+/// 租户管理员可以修改的租户配置段，名称、启用状态和共享provider只能由平台管理员修改
+const TENANT_ADMIN_SECTIONS: &[&str] = &["providers", "models", "users"];
+
+/// 校验租户管理接口的访问权限：平台管理员可以管理所有租户，租户管理员只能管理自己的租户
+async fn authorize_tenant_admin(
+    state: &AppState,
+    authorization: &Bearer,
+    tenant: &str,
+) -> Result<(UserToken, AdminRole), Box<Response>> {
+    let forbidden = |message: &str, user: &UserToken| {
+        warn!("User '{}' denied access to admin API of tenant '{}'", user.name, tenant);
+        Box::new(create_error_response(ErrorType::Forbidden, message, None).into_response())
+    };
+    let Some(user) = state.authenticate(authorization.token()).await else {
+        return Err(Box::new(
+            create_error_response(ErrorType::Unauthorized, "The provided API key is invalid", None)
+                .into_response(),
+        ));
+    };
+    let role = match user.admin_role() {
+        Some(AdminRole::Tenant(own)) if own != tenant => {
+            return Err(forbidden("Tenant admins can only manage their own tenant", &user));
+        }
+        Some(role) => role,
+        None => return Err(forbidden("Admin privileges required", &user)),
+    };
+    if !state.load_balancer.source_config().tenants.contains_key(tenant) {
+        return Err(Box::new(
+            create_error_response(ErrorType::NotFound, &format!("Tenant '{}' not found", tenant), None)
+                .into_response(),
+        ));
+    }
+    Ok((user, role))
+}
+
+/// 管理接口：服务状态概览
+pub async fn admin_status(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+) -> Response {
+    if let Err(resp) = authorize_admin(&state, &authorization).await {
+        return *resp;
+    }
+
+    let health = state.load_balancer.get_service_health().await;
+    let model_health = state.load_balancer.get_model_health();
+    let runtime_keys = state.runtime_users.read().map(|u| u.len()).unwrap_or(0);
+
+    Json(json!({
+        "service_running": health.is_running,
+        "healthy": health.is_healthy(),
+        "version": {
+            "git_sha": env!("VERGEN_GIT_SHA"),
+            "build_time": env!("VERGEN_BUILD_TIMESTAMP"),
+        },
+        "providers": {
+            "total": health.health_summary.total_providers,
+            "healthy": health.health_summary.healthy_providers,
+        },
+        "models": model_health,
+        "runtime_keys": runtime_keys,
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    }))
+    .into_response()
+}
+
+/// 管理接口：列出所有后端及其运行状态
+pub async fn admin_list_backends(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+) -> Response {
+    if let Err(resp) = authorize_admin(&state, &authorization).await {
+        return *resp;
+    }
+
+    let config = state.load_balancer.get_config();
+    let metrics = state.load_balancer.get_metrics();
+    let mut backends = Vec::new();
+
+    let mut model_ids: Vec<&String> = config.models.keys().collect();
+    model_ids.sort();
+    for model_id in model_ids {
+        let mapping = &config.models[model_id];
+        for backend in &mapping.backends {
+            backends.push(json!({
+                "model_id": model_id,
+                "model_name": mapping.name,
+                "provider": backend.provider,
+                "model": backend.model,
+                "weight": backend.weight,
+                "priority": backend.priority,
+                "enabled": backend.enabled,
+                "admin_disabled": metrics.is_backend_admin_disabled(&backend.provider, &backend.model),
+                "in_schedule": backend.is_in_schedule(),
+                "region": backend.region,
+                "healthy": metrics.is_healthy(&backend.provider, &backend.model),
+                "health_score": metrics.get_health_score(&backend.provider, &backend.model),
+                "cooldown_remaining_ms": metrics.cooldown_remaining(&backend.provider, &backend.model).map(|d| d.as_millis()),
+                "latency_ms": metrics.get_latency(&backend.provider, &backend.model).map(|l| l.as_millis()),
+                "p95_latency_ms": metrics.get_tail_latency(&backend.provider, &backend.model).map(|l| l.as_millis()),
+                "failure_count": metrics.get_failure_count(&backend.provider, &backend.model),
+                "in_flight": metrics.get_in_flight(&backend.provider, &backend.model),
+            }));
+        }
+    }
+
+    Json(json!({ "backends": backends })).into_response()
+}
+
+/// 默认的选择预演次数
+const DEFAULT_PREVIEW_SAMPLES: usize = 1000;
+/// 单次选择预演的最大次数
+const MAX_PREVIEW_SAMPLES: usize = 100_000;
+
+/// 选择预演的查询参数
+#[derive(Debug, Deserialize)]
+pub struct RoutePreviewQuery {
+    pub model: String,
+    /// 选择次数
+    pub n: Option<usize>,
+    /// 按该策略预演，默认使用模型映射配置的策略
+    pub strategy: Option<LoadBalanceStrategy>,
+    /// 按来自该区域的请求预演
+    pub region: Option<String>,
+}
+
+/// 管理接口：预演后端选择，不发送请求，返回各后端的选中分布、有效权重和健康状态
+pub async fn admin_route_preview(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Query(query): Query<RoutePreviewQuery>,
+) -> Response {
+    if let Err(resp) = authorize_admin(&state, &authorization).await {
+        return *resp;
+    }
+
+    let samples = query.n.unwrap_or(DEFAULT_PREVIEW_SAMPLES);
+    if samples == 0 || samples > MAX_PREVIEW_SAMPLES {
+        return create_error_response(
+            ErrorType::BadRequest,
+            "Invalid sample count",
+            Some(format!("n must be between 1 and {}", MAX_PREVIEW_SAMPLES)),
+        )
+        .into_response();
+    }
+
+    let config = state.load_balancer.get_config();
+    let Some((model_id, mapping)) = config.resolve_model(&query.model) else {
+        return create_error_response(
+            ErrorType::NotFound,
+            &format!("Model '{}' not found", query.model),
+            None,
+        )
+        .into_response();
+    };
+
+    let context = SelectionContext {
+        strategy_override: query.strategy.clone(),
+        region: query.region.clone(),
+        ..Default::default()
+    };
+    let Some(preview) = state.load_balancer.preview_selection(model_id, samples, &context) else {
+        return create_error_response(
+            ErrorType::NotFound,
+            &format!("Model '{}' not found", query.model),
+            None,
+        )
+        .into_response();
+    };
+
+    let metrics = state.load_balancer.get_metrics();
+    let completed: usize = preview.selected.values().sum();
+    let backends: Vec<_> = mapping
+        .backends
+        .iter()
+        .map(|backend| {
+            let backend_key = format!("{}:{}", backend.provider, backend.model);
+            let selected = preview.selected.get(&backend_key).copied().unwrap_or(0);
+            json!({
+                "provider": backend.provider,
+                "model": backend.model,
+                "weight": backend.weight,
+                "effective_weight": metrics.get_score_weight(
+                    &backend.provider,
+                    &backend.model,
+                    metrics.get_effective_weight(&backend_key, backend.weight),
+                ),
+                "priority": backend.priority,
+                "enabled": backend.enabled,
+                "admin_disabled": metrics.is_backend_admin_disabled(&backend.provider, &backend.model),
+                "in_schedule": backend.is_in_schedule(),
+                "region": backend.region,
+                "healthy": metrics.is_healthy(&backend.provider, &backend.model),
+                "health_score": metrics.get_health_score(&backend.provider, &backend.model).score,
+                "cooling_down": metrics.is_cooling_down(&backend.provider, &backend.model),
+                "near_quota": metrics.is_near_quota(&backend.provider),
+                "key_exhausted": metrics.is_key_exhausted(&backend.provider),
+                "selected": selected,
+                "share": if completed > 0 { selected as f64 / completed as f64 } else { 0.0 },
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "model_id": model_id,
+        "model_name": mapping.name,
+        "strategy": query.strategy.as_ref().unwrap_or(&mapping.strategy),
+        "samples": samples,
+        "completed": completed,
+        "error": preview.error,
+        "backends": backends,
+    }))
+    .into_response()
+}
+
+/// 启用/禁用后端的请求体
+#[derive(Debug, Deserialize)]
+pub struct BackendToggleRequest {
+    pub provider: String,
+    pub model: String,
+}
+
+/// 管理接口：运行时禁用后端
+pub async fn admin_disable_backend(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Json(request): Json<BackendToggleRequest>,
+) -> Response {
+    set_backend_disabled(state, authorization, request, true).await
+}
+
+/// 管理接口：重新启用被禁用的后端
+pub async fn admin_enable_backend(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Json(request): Json<BackendToggleRequest>,
+) -> Response {
+    set_backend_disabled(state, authorization, request, false).await
+}
+
+async fn set_backend_disabled(
+    state: AppState,
+    authorization: Bearer,
+    request: BackendToggleRequest,
+    disabled: bool,
+) -> Response {
+    let admin = match authorize_admin(&state, &authorization).await {
+        Ok(admin) => admin,
+        Err(resp) => return *resp,
+    };
+
+    let config = state.load_balancer.get_config();
+    let exists = config.models.values().any(|m| {
+        m.backends
+            .iter()
+            .any(|b| b.provider == request.provider && b.model == request.model)
+    });
+    if !exists {
+        return create_error_response(
+            ErrorType::NotFound,
+            &format!("Backend {}:{} not found", request.provider, request.model),
+            None,
+        )
+        .into_response();
+    }
+
+    let backend_key = format!("{}:{}", request.provider, request.model);
+    state
+        .load_balancer
+        .get_metrics()
+        .set_backend_admin_disabled(&backend_key, disabled);
+
+    let event = if disabled { "backend_disabled" } else { "backend_enabled" };
+    info!("Admin '{}' {} backend {}", admin.name, event, backend_key);
+    state.load_balancer.get_events().publish(
+        event,
+        json!({ "backend_key": backend_key, "by": admin.name }),
+    );
+
+    Json(json!({
+        "backend_key": backend_key,
+        "admin_disabled": disabled
+    }))
+    .into_response()
+}
+
+/// 管理接口：从配置文件重新加载配置
+pub async fn admin_reload(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+) -> Response {
+    let admin = match authorize_admin(&state, &authorization).await {
+        Ok(admin) => admin,
+        Err(resp) => return *resp,
+    };
+
+    let new_config = match crate::config::loader::load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            return create_error_response(
+                ErrorType::BadRequest,
+                "Failed to load configuration file",
+                Some(e.to_string()),
+            )
+            .into_response();
+        }
+    };
+
+    let models = new_config.models.len();
+    let version = match state.load_balancer.apply_config(new_config, "reload").await {
+        Ok(version) => version,
+        Err(e) => {
+            return create_error_response(
+                ErrorType::BadRequest,
+                "Configuration rejected",
+                Some(e.to_string()),
+            )
+            .into_response();
+        }
+    };
+
+    info!("Admin '{}' reloaded configuration (version {})", admin.name, version);
+    state.load_balancer.get_events().publish(
+        "config_reloaded",
+        json!({ "models": models, "version": version, "by": admin.name }),
+    );
+
+    Json(json!({ "reloaded": true, "models": models, "version": version })).into_response()
+}
+
+/// 替换配置中的明文密钥，外部密钥引用保持原样
+fn redact_config(config: &crate::config::model::Config) -> crate::config::model::Config {
+    const REDACTED: &str = "********";
+    let mut config = config.clone();
+    let tenant_providers = config.tenants.values_mut().flat_map(|tenant| tenant.providers.values_mut());
+    for provider in config.providers.values_mut().chain(tenant_providers) {
+        if !matches!(crate::config::secrets::SecretReference::parse(&provider.api_key), Ok(Some(_))) {
+            provider.api_key = REDACTED.to_string();
+        }
+    }
+    for peer in config.peers.values_mut() {
+        peer.api_key = REDACTED.to_string();
+    }
+    let tenant_users = config.tenants.values_mut().flat_map(|tenant| tenant.users.values_mut());
+    for user in config.users.values_mut().chain(tenant_users) {
+        if !user.token.is_empty() {
+            user.token = REDACTED.to_string();
+        }
+    }
+    if let Some(alerts) = config.settings.alerts.as_mut() {
+        if let Some(pagerduty) = alerts.pagerduty.as_mut() {
+            pagerduty.routing_key = REDACTED.to_string();
+        }
+        if let Some(password) = alerts.smtp.as_mut().and_then(|smtp| smtp.password.as_mut()) {
+            *password = REDACTED.to_string();
+        }
+    }
+    // Postgres连接串中可能带有密码
+    match config.settings.request_log.as_mut().map(|request_log| &mut request_log.sink) {
+        Some(crate::config::model::RequestLogSink::Clickhouse {
+            password: Some(password),
+            ..
+        }) => *password = REDACTED.to_string(),
+        Some(crate::config::model::RequestLogSink::Postgres { url, .. }) => *url = REDACTED.to_string(),
+        _ => {}
+    }
+    config
+}
+
+/// 管理接口：查看当前配置（密钥已隐藏）
+pub async fn admin_get_config(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+) -> Response {
+    if let Err(resp) = authorize_admin(&state, &authorization).await {
+        return *resp;
+    }
+    Json(redact_config(&state.load_balancer.source_config())).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PersistQuery {
+    /// 应用后是否写回配置文件
+    #[serde(default)]
+    pub persist: bool,
+}
+
+/// 把已生效的配置写回配置文件，失败时返回错误信息（配置仍然生效）
+fn persist_config(config: &crate::config::model::Config) -> Option<String> {
+    let path = crate::config::loader::config_path();
+    let error = crate::config::loader::save_config_to(&path, config).err()?;
+    error!("Failed to persist configuration to {}: {:#}", path, error);
+    Some(format!("{:#}", error))
+}
+
+/// 管理接口：按JSON Merge Patch修改配置，校验通过后立即生效，可选写回配置文件
+pub async fn admin_patch_config(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Query(query): Query<PersistQuery>,
+    Json(patch): Json<serde_json::Value>,
+) -> Response {
+    let admin = match authorize_admin(&state, &authorization).await {
+        Ok(admin) => admin,
+        Err(resp) => return *resp,
+    };
+
+    // 读取、修改和应用在配置更新锁内完成，并发的修改不会互相覆盖
+    let updated = state
+        .load_balancer
+        .update_config("patch", |source| crate::config::patch::apply_patch(source, &patch))
+        .await;
+    let (version, new_config) = match updated {
+        Ok(updated) => updated,
+        Err(e) => {
+            let message = match e {
+                ConfigUpdateError::Invalid(_) => "Invalid config patch",
+                ConfigUpdateError::Rejected(_) => "Configuration rejected",
+            };
+            return create_error_response(ErrorType::BadRequest, message, Some(e.to_string())).into_response();
+        }
+    };
+
+    let sections: Vec<&String> = patch.as_object().map(|p| p.keys().collect()).unwrap_or_default();
+    let persist_error = query.persist.then(|| persist_config(&new_config)).flatten();
+    let persisted = query.persist && persist_error.is_none();
+
+    info!("Admin '{}' patched configuration sections {:?} (version {})", admin.name, sections, version);
+    state.load_balancer.get_events().publish(
+        "config_patched",
+        json!({ "sections": sections, "version": version, "persisted": persisted, "by": admin.name }),
+    );
+
+    Json(json!({
+        "applied": true,
+        "version": version,
+        "providers": new_config.providers.len(),
+        "models": new_config.models.len(),
+        "persisted": persisted,
+        "persist_error": persist_error
+    }))
+    .into_response()
+}
+
+/// 管理接口：查看租户的配置（密钥已隐藏），不包含全局和其他租户的配置
+pub async fn admin_get_tenant_config(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Path(tenant): Path<String>,
+) -> Response {
+    if let Err(resp) = authorize_tenant_admin(&state, &authorization, &tenant).await {
+        return *resp;
+    }
+    let mut config = redact_config(&state.load_balancer.source_config());
+    Json(config.tenants.remove(&tenant)).into_response()
+}
+
+/// 管理接口：按JSON Merge Patch修改租户的配置，租户管理员只能修改providers、models和users
+pub async fn admin_patch_tenant_config(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Path(tenant): Path<String>,
+    Query(query): Query<PersistQuery>,
+    Json(patch): Json<serde_json::Value>,
+) -> Response {
+    let (admin, role) = match authorize_tenant_admin(&state, &authorization, &tenant).await {
+        Ok(authorized) => authorized,
+        Err(resp) => return *resp,
+    };
+
+    let sections: Vec<String> = patch.as_object().map(|p| p.keys().cloned().collect()).unwrap_or_default();
+    if let AdminRole::Tenant(_) = role
+        && let Some(section) = sections.iter().find(|s| !TENANT_ADMIN_SECTIONS.contains(&s.as_str()))
+    {
+        return create_error_response(
+            ErrorType::Forbidden,
+            &format!("Tenant admins cannot modify '{}'", section),
+            Some(format!("Allowed sections: {}", TENANT_ADMIN_SECTIONS.join(", "))),
+        )
+        .into_response();
+    }
+
+    let patch = json!({ "tenants": { &tenant: patch } });
+    let updated = state
+        .load_balancer
+        .update_config("tenant_patch", |source| crate::config::patch::apply_patch(source, &patch))
+        .await;
+    let (version, new_config) = match updated {
+        Ok(updated) => updated,
+        Err(e) => {
+            let message = match e {
+                ConfigUpdateError::Invalid(_) => "Invalid config patch",
+                ConfigUpdateError::Rejected(_) => "Configuration rejected",
+            };
+            return create_error_response(ErrorType::BadRequest, message, Some(e.to_string())).into_response();
+        }
+    };
+
+    let persist_error = query.persist.then(|| persist_config(&new_config)).flatten();
+    let persisted = query.persist && persist_error.is_none();
+
+    info!(
+        "Admin '{}' patched tenant '{}' sections {:?} (version {})",
+        admin.name, tenant, sections, version
+    );
+    state.load_balancer.get_events().publish(
+        "tenant_config_patched",
+        json!({ "tenant": tenant, "sections": sections, "version": version, "persisted": persisted, "by": admin.name }),
+    );
+
+    let patched = &new_config.tenants[&tenant];
+    Json(json!({
+        "applied": true,
+        "version": version,
+        "providers": patched.providers.len(),
+        "models": patched.models.len(),
+        "users": patched.users.len(),
+        "persisted": persisted,
+        "persist_error": persist_error
+    }))
+    .into_response()
+}
+
+/// 管理接口：已应用配置的历史版本
+pub async fn admin_config_history(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+) -> Response {
+    if let Err(resp) = authorize_admin(&state, &authorization).await {
+        return *resp;
+    }
+    Json(json!({ "versions": state.load_balancer.config_history() })).into_response()
+}
+
+/// 管理接口：查看某个历史版本的配置（密钥已隐藏）
+pub async fn admin_config_version(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Path(version): Path<u64>,
+) -> Response {
+    if let Err(resp) = authorize_admin(&state, &authorization).await {
+        return *resp;
+    }
+    let Some(entry) = state.load_balancer.config_version(version) else {
+        return create_error_response(
+            ErrorType::NotFound,
+            &format!("Config version {} not found", version),
+            None,
+        )
+        .into_response();
+    };
+    Json(json!({
+        "version": entry.version,
+        "applied_at": entry.applied_at,
+        "source": entry.source,
+        "config": redact_config(&entry.config)
+    }))
+    .into_response()
+}
+
+/// 回滚配置的请求体
+#[derive(Debug, Default, Deserialize)]
+pub struct RollbackRequest {
+    /// 目标版本，为空时回滚到上一个版本
+    #[serde(default)]
+    pub version: Option<u64>,
+}
+
+/// 管理接口：回滚到历史版本的配置
+pub async fn admin_config_rollback(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Query(query): Query<PersistQuery>,
+    request: Option<Json<RollbackRequest>>,
+) -> Response {
+    let admin = match authorize_admin(&state, &authorization).await {
+        Ok(admin) => admin,
+        Err(resp) => return *resp,
+    };
+
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let (target, version) = match state.load_balancer.rollback_config(request.version).await {
+        Ok(versions) => versions,
+        Err(e) => {
+            return create_error_response(ErrorType::BadRequest, "Rollback failed", Some(e.to_string()))
+                .into_response();
+        }
+    };
+
+    let persist_error = query
+        .persist
+        .then(|| persist_config(&state.load_balancer.source_config()))
+        .flatten();
+    let persisted = query.persist && persist_error.is_none();
+
+    warn!("Admin '{}' rolled configuration back to version {} (now version {})", admin.name, target, version);
+    state.load_balancer.get_events().publish(
+        "config_rolled_back",
+        json!({ "target": target, "version": version, "persisted": persisted, "by": admin.name }),
+    );
+
+    Json(json!({
+        "rolled_back_to": target,
+        "version": version,
+        "persisted": persisted,
+        "persist_error": persist_error
+    }))
+    .into_response()
+}
+
+/// 创建API密钥的请求体
+#[derive(Debug, Deserialize)]
+pub struct CreateKeyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 累计花费上限（美元）
+    #[serde(default)]
+    pub budget: Option<f64>,
+    /// 模型别名：请求中的模型名称 -> 模型ID
+    #[serde(default)]
+    pub model_aliases: HashMap<String, String>,
+    /// 请求优先级（high、normal、batch）
+    #[serde(default)]
+    pub priority: RequestPriority,
+    /// 绑定的来源IP或CIDR，为空时不限制
+    #[serde(default, deserialize_with = "crate::config::model::deserialize_ip_nets")]
+    pub allowed_ips: Vec<IpNet>,
+}
+
+/// 生成随机API密钥
+fn generate_token() -> String {
+    let mut rng = rand::rng();
+    let suffix: String = (0..32)
+        .map(|_| format!("{:x}", rng.random_range(0..16u8)))
+        .collect();
+    format!("berry-{}", suffix)
+}
+
+/// 管理接口：创建运行时API密钥
+pub async fn admin_create_key(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Json(request): Json<CreateKeyRequest>,
+) -> Response {
+    match authorize_admin(&state, &authorization).await {
+        Ok(admin) => create_key(&state, &admin, None, request),
+        Err(resp) => *resp,
+    }
+}
+
+/// 管理接口：为租户创建运行时API密钥，名称和模型ID都在租户内解析
+pub async fn admin_create_tenant_key(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Path(tenant): Path<String>,
+    Json(request): Json<CreateKeyRequest>,
+) -> Response {
+    match authorize_tenant_admin(&state, &authorization, &tenant).await {
+        Ok((admin, _)) => create_key(&state, &admin, Some(&tenant), request),
+        Err(resp) => *resp,
+    }
+}
+
+/// 创建运行时API密钥；指定租户时密钥属于该租户，名称和模型ID加上租户前缀
+fn create_key(state: &AppState, admin: &UserToken, tenant: Option<&str>, mut request: CreateKeyRequest) -> Response {
+    if request.name.trim().is_empty() {
+        return create_error_response(ErrorType::BadRequest, "Key name must not be empty", None)
+            .into_response();
+    }
+    if let Some(tenant) = tenant {
+        let scoped = |id: &mut String| *id = format!("{}/{}", tenant, id);
+        scoped(&mut request.name);
+        request.allowed_models.iter_mut().for_each(scoped);
+        request.model_aliases.values_mut().for_each(scoped);
+    }
+
+    let config = state.load_balancer.get_config();
+    if let Some(unknown) = request
+        .allowed_models
+        .iter()
+        .chain(request.model_aliases.values())
+        .find(|m| !config.models.contains_key(*m) || config.model_tenant(m) != tenant)
+    {
+        let unknown = tenant
+            .and_then(|tenant| unknown.strip_prefix(tenant)?.strip_prefix('/'))
+            .unwrap_or(unknown);
+        return create_error_response(
+            ErrorType::BadRequest,
+            &format!("Unknown model '{}'", unknown),
+            None,
+        )
+        .into_response();
+    }
+
+    if !request.allowed_models.is_empty()
+        && let Some((alias, _)) = request
+            .model_aliases
+            .iter()
+            .find(|(_, model_id)| !request.allowed_models.contains(*model_id))
+    {
+        return create_error_response(
+            ErrorType::BadRequest,
+            &format!("Alias '{}' targets a model outside allowed_models", alias),
+            None,
+        )
+        .into_response();
+    }
+
+    // 内存和密钥文件中只保存哈希，明文密钥仅在本次响应中返回
+    let token = generate_token();
+    let user = UserToken {
+        name: request.name.clone(),
+        token: String::new(),
+        token_hash: Some(crate::auth::keys::hash_token(&token)),
+        model_aliases: request.model_aliases,
+        allowed_models: request.allowed_models,
+        enabled: true,
+        rate_limit: None,
+        tags: request.tags,
+        budget: request.budget,
+        priority: request.priority,
+        region: None,
+        content_filters: Vec::new(),
+        allowed_ips: request.allowed_ips,
+        tenant: tenant.map(str::to_string),
+        limits: None,
+    };
+
+    {
+        let Ok(mut runtime_users) = state.runtime_users.write() else {
+            return create_error_response(
+                ErrorType::InternalServerError,
+                "Key store unavailable",
+                None,
+            )
+            .into_response();
+        };
+        if runtime_users.contains_key(&user.name) || state.config().users.contains_key(&user.name) {
+            return create_error_response(
+                ErrorType::BadRequest,
+                &format!("Key '{}' already exists", user.name),
+                None,
+            )
+            .into_response();
+        }
+        runtime_users.insert(user.name.clone(), user.clone());
+    }
+
+    let persisted = match state.persist_runtime_users() {
+        Ok(persisted) => persisted,
+        Err(e) => {
+            error!("Failed to persist runtime keys: {}", e);
+            false
+        }
+    };
+
+    info!("Admin '{}' created runtime key '{}'", admin.name, user.name);
+    state
+        .load_balancer
+        .get_events()
+        .publish("key_created", json!({ "name": user.name, "tenant": tenant, "by": admin.name }));
+
+    Json(json!({
+        "name": user.name,
+        "token": token,
+        "allowed_models": user.allowed_models,
+        "tags": user.tags,
+        "budget": user.budget,
+        "priority": user.priority,
+        "model_aliases": user.model_aliases,
+        "allowed_ips": user.allowed_ips,
+        "persisted": persisted
+    }))
+    .into_response()
+}
+
+/// 管理接口：吊销运行时API密钥（配置文件中的用户需修改配置文件）
+pub async fn admin_revoke_key(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Path(name): Path<String>,
+) -> Response {
+    match authorize_admin(&state, &authorization).await {
+        Ok(admin) => revoke_key(&state, &admin, None, &name),
+        Err(resp) => *resp,
+    }
+}
+
+/// 管理接口：吊销租户的运行时API密钥，名称不带租户前缀
+pub async fn admin_revoke_tenant_key(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Path((tenant, name)): Path<(String, String)>,
+) -> Response {
+    match authorize_tenant_admin(&state, &authorization, &tenant).await {
+        Ok((admin, _)) => revoke_key(&state, &admin, Some(&tenant), &format!("{}/{}", tenant, name)),
+        Err(resp) => *resp,
+    }
+}
+
+/// 吊销运行时API密钥；指定租户时只能吊销属于该租户的密钥
+fn revoke_key(state: &AppState, admin: &UserToken, tenant: Option<&str>, name: &str) -> Response {
+    let removed = state.runtime_users.write().ok().and_then(|mut users| {
+        if users.get(name)?.tenant.as_deref() != tenant {
+            return None;
+        }
+        users.remove(name)
+    });
+    if removed.is_none() {
+        let details = state
+            .config()
+            .users
+            .contains_key(name)
+            .then(|| "Keys defined in the config file must be removed from the config file".to_string());
+        return create_error_response(
+            ErrorType::NotFound,
+            &format!("Runtime key '{}' not found", name),
+            details,
+        )
+        .into_response();
+    }
+
+    let persisted = match state.persist_runtime_users() {
+        Ok(persisted) => persisted,
+        Err(e) => {
+            error!("Failed to persist runtime keys: {}", e);
+            false
+        }
+    };
+
+    info!("Admin '{}' revoked runtime key '{}'", admin.name, name);
+    state
+        .load_balancer
+        .get_events()
+        .publish("key_revoked", json!({ "name": name, "tenant": tenant, "by": admin.name }));
+
+    Json(json!({ "name": name, "revoked": true, "persisted": persisted })).into_response()
+}
+
+/// 管理接口：以SSE实时推送服务事件
+pub async fn admin_events(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+) -> Response {
+    if let Err(resp) = authorize_admin(&state, &authorization).await {
+        return *resp;
+    }
+
+    let receiver = state.load_balancer.get_events().subscribe();
+    let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let sse = Event::default()
+                        .event(event.event.clone())
+                        .json_data(&event)
+                        .unwrap_or_else(|_| Event::default().comment("serialization error"));
+                    return Some((Ok::<_, std::convert::Infallible>(sse), receiver));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Admin event subscriber lagged, skipped {} events", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+}
+
+/// 修改日志过滤规则的请求体
+#[derive(Debug, Deserialize)]
+pub struct LogFilterRequest {
+    /// EnvFilter语法的过滤规则，如 `info,berry_api_api::relay=trace`
+    pub filter: String,
+    /// 规则生效的时长（秒），到期后恢复启动时的规则；省略时一直生效
+    pub duration_seconds: Option<u64>,
+}
+
+/// 运行时日志过滤器，服务没有通过init_tracing初始化日志时返回503
+fn log_filter_control() -> Result<&'static LogFilterControl, Box<Response>> {
+    crate::telemetry::log_filter().ok_or_else(|| {
+        Box::new(
+            create_error_response(
+                ErrorType::ServiceUnavailable,
+                "Log filter control is not available",
+                Some("Logging was not initialized by this process".to_string()),
+            )
+            .into_response(),
+        )
+    })
+}
+
+/// 管理接口：查看当前的日志过滤规则
+pub async fn admin_get_log_filter(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+) -> Response {
+    if let Err(resp) = authorize_admin(&state, &authorization).await {
+        return *resp;
+    }
+    match log_filter_control() {
+        Ok(control) => Json(control.status()).into_response(),
+        Err(resp) => *resp,
+    }
+}
+
+/// 管理接口：临时修改日志过滤规则，无需修改RUST_LOG重启服务
+pub async fn admin_set_log_filter(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Json(request): Json<LogFilterRequest>,
+) -> Response {
+    let admin = match authorize_admin(&state, &authorization).await {
+        Ok(admin) => admin,
+        Err(resp) => return *resp,
+    };
+    let control = match log_filter_control() {
+        Ok(control) => control,
+        Err(resp) => return *resp,
+    };
+    if request.duration_seconds == Some(0) {
+        return create_error_response(
+            ErrorType::BadRequest,
+            "Invalid log filter duration",
+            Some("duration_seconds must be greater than 0".to_string()),
+        )
+        .into_response();
+    }
+
+    let duration = request.duration_seconds.map(std::time::Duration::from_secs);
+    let status = match control.set(&request.filter, duration) {
+        Ok(status) => status,
+        Err(e) => {
+            return create_error_response(ErrorType::BadRequest, "Invalid log filter", Some(e.to_string())).into_response();
+        }
+    };
+
+    info!(
+        "Admin '{}' changed log filter to '{}' (duration: {:?}s)",
+        admin.name, status.filter, request.duration_seconds
+    );
+    state.load_balancer.get_events().publish(
+        "log_filter_changed",
+        json!({ "filter": status.filter, "expires_at": status.expires_at, "by": admin.name }),
+    );
+    Json(status).into_response()
+}
+
+/// 管理接口：立即恢复启动时的日志过滤规则
+pub async fn admin_reset_log_filter(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+) -> Response {
+    let admin = match authorize_admin(&state, &authorization).await {
+        Ok(admin) => admin,
+        Err(resp) => return *resp,
+    };
+    let control = match log_filter_control() {
+        Ok(control) => control,
+        Err(resp) => return *resp,
+    };
+
+    let status = control.reset();
+    info!("Admin '{}' reset log filter to '{}'", admin.name, status.filter);
+    state.load_balancer.get_events().publish(
+        "log_filter_changed",
+        json!({ "filter": status.filter, "expires_at": null, "by": admin.name }),
+    );
+    Json(status).into_response()
+}
+
+/// 注入故障的请求体
+#[derive(Debug, Deserialize)]
+pub struct FaultRequest {
+    pub provider: String,
+    pub model: String,
+    #[serde(flatten)]
+    pub fault: FaultSpec,
+    /// 故障生效的时长（秒），到期后自动清除；省略时直到清除前一直生效
+    pub duration_seconds: Option<u64>,
+}
+
+/// 清除故障的查询参数，都省略时清除所有故障
+#[derive(Debug, Deserialize)]
+pub struct FaultClearParams {
+    pub provider: Option<String>,
+    pub model: Option<String>,
+}
+
+/// 管理接口：查看注入的故障和各类故障的注入次数
+pub async fn admin_list_faults(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+) -> Response {
+    if let Err(resp) = authorize_admin(&state, &authorization).await {
+        return *resp;
+    }
+    Json(json!({
+        "enabled": state.load_balancer.get_config().settings.fault_injection,
+        "faults": state.handler.faults().list(),
+    }))
+    .into_response()
+}
+
+/// 管理接口：对后端注入延迟、错误或断流，需要配置settings.fault_injection
+pub async fn admin_set_fault(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Json(request): Json<FaultRequest>,
+) -> Response {
+    let admin = match authorize_admin(&state, &authorization).await {
+        Ok(admin) => admin,
+        Err(resp) => return *resp,
+    };
+    let config = state.load_balancer.get_config();
+    if !config.settings.fault_injection {
+        return create_error_response(
+            ErrorType::Forbidden,
+            "Fault injection is disabled",
+            Some("Set settings.fault_injection = true to enable it".to_string()),
+        )
+        .into_response();
+    }
+    let exists = config.models.values().any(|m| {
+        m.backends
+            .iter()
+            .any(|b| b.provider == request.provider && b.model == request.model)
+    });
+    if !exists {
+        return create_error_response(
+            ErrorType::NotFound,
+            &format!("Backend {}:{} not found", request.provider, request.model),
+            None,
+        )
+        .into_response();
+    }
+    if let Err(details) = request.fault.validate() {
+        return create_error_response(ErrorType::BadRequest, "Invalid fault", Some(details)).into_response();
+    }
+    if request.duration_seconds == Some(0) {
+        return create_error_response(
+            ErrorType::BadRequest,
+            "Invalid fault duration",
+            Some("duration_seconds must be greater than 0".to_string()),
+        )
+        .into_response();
+    }
+
+    let backend_key = format!("{}:{}", request.provider, request.model);
+    let duration = request.duration_seconds.map(std::time::Duration::from_secs);
+    let status = state.handler.faults().set(&backend_key, request.fault, duration);
+    warn!(
+        "Admin '{}' injected fault into backend {} (duration: {:?}s)",
+        admin.name, backend_key, request.duration_seconds
+    );
+    state.load_balancer.get_events().publish(
+        "fault_injected",
+        json!({ "fault": status, "by": admin.name }),
+    );
+    Json(status).into_response()
+}
+
+/// 管理接口：清除指定后端或所有后端的故障
+pub async fn admin_clear_faults(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Query(params): Query<FaultClearParams>,
+) -> Response {
+    let admin = match authorize_admin(&state, &authorization).await {
+        Ok(admin) => admin,
+        Err(resp) => return *resp,
+    };
+    let backend_key = match (&params.provider, &params.model) {
+        (Some(provider), Some(model)) => Some(format!("{}:{}", provider, model)),
+        (None, None) => None,
+        _ => {
+            return create_error_response(
+                ErrorType::BadRequest,
+                "Invalid fault selector",
+                Some("provider and model must be given together".to_string()),
+            )
+            .into_response();
+        }
+    };
+
+    let cleared = state.handler.faults().clear(backend_key.as_deref());
+    info!(
+        "Admin '{}' cleared {} fault(s) ({})",
+        admin.name,
+        cleared,
+        backend_key.as_deref().unwrap_or("all backends")
+    );
+    state.load_balancer.get_events().publish(
+        "fault_cleared",
+        json!({ "backend_key": backend_key, "cleared": cleared, "by": admin.name }),
+    );
+    Json(json!({ "cleared": cleared })).into_response()
+}
+
+/// 用量报告的查询参数
+#[derive(Debug, Deserialize)]
+pub struct UsageParams {
+    /// 起始时间（含），RFC3339或YYYY-MM-DD（UTC）
+    pub start: Option<String>,
+    /// 结束时间（不含），格式同start
+    pub end: Option<String>,
+    /// 时间分桶：hour、day（默认）、month、total
+    #[serde(default)]
+    pub bucket: UsageBucket,
+    /// 逗号分隔的分组维度：user、model、provider
+    pub group_by: Option<String>,
+    /// 逗号分隔的用户、模型、provider过滤条件
+    pub user: Option<String>,
+    pub model: Option<String>,
+    pub provider: Option<String>,
+    /// 输出格式：json（默认）、csv
+    pub format: Option<String>,
+}
+
+/// 解析RFC3339时间或YYYY-MM-DD日期（当天0点，UTC）
+fn parse_usage_time(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&chrono::Utc))
+        .ok()
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|time| time.and_utc())
+        })
+}
+
+impl UsageParams {
+    fn to_query(&self) -> Result<UsageQuery, String> {
+        let parse_time = |value: &Option<String>, name: &str| {
+            value
+                .as_deref()
+                .map(|v| parse_usage_time(v).ok_or_else(|| format!("Invalid {} '{}': expected RFC3339 or YYYY-MM-DD", name, v)))
+                .transpose()
+        };
+        let list = |value: &Option<String>| -> Vec<String> {
+            value
+                .as_deref()
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let group_by = list(&self.group_by)
+            .into_iter()
+            .map(|dimension| {
+                serde_json::from_value(json!(dimension))
+                    .map_err(|_| format!("Invalid group_by '{}': expected user, model or provider", dimension))
+            })
+            .collect::<Result<Vec<UsageDimension>, _>>()?;
+
+        Ok(UsageQuery {
+            start: parse_time(&self.start, "start")?,
+            end: parse_time(&self.end, "end")?,
+            bucket: self.bucket,
+            group_by,
+            users: list(&self.user),
+            models: list(&self.model),
+            providers: list(&self.provider),
+        })
+    }
+}
+
+/// 用量报告转换为CSV，首行为表头
+fn usage_csv(report: &UsageReport) -> String {
+    let escape = |value: &str| {
+        if value.contains([',', '"', '\n']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    };
+    let mut csv = String::from("bucket_start,user,model,provider,requests,prompt_tokens,completion_tokens,cost\n");
+    for row in &report.rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{:.6}\n",
+            row.bucket_start.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            escape(row.user.as_deref().unwrap_or_default()),
+            escape(row.model.as_deref().unwrap_or_default()),
+            escape(row.provider.as_deref().unwrap_or_default()),
+            row.usage.requests,
+            row.usage.prompt_tokens,
+            row.usage.completion_tokens,
+            row.usage.cost
+        ));
+    }
+    csv
+}
+
+/// 管理接口：按用户、模型、provider和时间分桶聚合的用量报告（请求数、token数、估算花费），用于生成费用分摊报表
+pub async fn admin_usage(
+    State(state): State<AppState>,
+    TypedHeader(authorization): AdminAuth,
+    Query(params): Query<UsageParams>,
+) -> Response {
+    if let Err(resp) = authorize_admin(&state, &authorization).await {
+        return *resp;
+    }
+
+    let query = match params.to_query() {
+        Ok(query) => query,
+        Err(e) => {
+            return create_error_response(ErrorType::BadRequest, "Invalid usage query", Some(e)).into_response();
+        }
+    };
+    let report = state.load_balancer.get_cost_tracker().usage(&query);
+
+    match params.format.as_deref() {
+        None | Some("json") => {
+            // 附带配置了并发上限的用户当前的在途请求数
+            let in_flight: HashMap<String, usize> = state
+                .handler
+                .user_in_flight()
+                .into_iter()
+                .filter(|(user, _)| query.users.is_empty() || query.users.contains(user))
+                .collect();
+            let mut body = json!(report);
+            body["in_flight"] = json!(in_flight);
+            Json(body).into_response()
+        }
+        Some("csv") => (
+            [(axum::http::header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+            usage_csv(&report),
+        )
+            .into_response(),
+        Some(other) => create_error_response(
+            ErrorType::BadRequest,
+            "Invalid usage query",
+            Some(format!("Invalid format '{}': expected json or csv", other)),
+        )
+        .into_response(),
+    }
+}
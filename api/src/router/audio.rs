@@ -0,0 +1,93 @@
+use crate::app::AppState;
+use crate::relay::client::FormField;
+use crate::relay::handler::{ErrorType, create_error_response};
+use axum::{
+    extract::{Multipart, State},
+    http::HeaderMap,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::TypedHeader;
+use serde_json::{Value, json};
+
+use super::chat::authorize_model_request;
+
+/// V1 API: 语音转文字
+pub async fn audio_transcriptions(
+    State(state): State<AppState>,
+    TypedHeader(authorization): TypedHeader<headers::Authorization<headers::authorization::Bearer>>,
+    request_headers: HeaderMap,
+    mut multipart: Multipart,
+) -> axum::response::Response {
+    let mut fields = Vec::new();
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return invalid_form(e),
+        };
+        let name = field.name().unwrap_or_default().to_string();
+        let file_name = field.file_name().map(str::to_string);
+        let content_type = field.content_type().map(str::to_string);
+        match field.bytes().await {
+            Ok(data) => fields.push(FormField {
+                name,
+                file_name,
+                content_type,
+                data,
+            }),
+            Err(e) => return invalid_form(e),
+        }
+    }
+
+    // 按表单中的model字段做认证、别名替换和权限检查
+    let mut body = fields
+        .iter()
+        .find(|f| f.name == "model")
+        .and_then(|f| std::str::from_utf8(&f.data).ok())
+        .map(|model| json!({ "model": model }))
+        .unwrap_or_else(|| json!({}));
+    let user = match authorize_model_request(&state, authorization.token(), &mut body).await {
+        Ok(user) => user,
+        Err(response) => return *response,
+    };
+    if let Some(model) = body.get("model").and_then(|m| m.as_str()) {
+        for field in fields.iter_mut().filter(|f| f.name == "model") {
+            field.data = bytes::Bytes::from(model.to_string());
+        }
+    }
+
+    state
+        .handler
+        .clone()
+        .handle_audio_transcriptions(user, TypedHeader(authorization), request_headers, fields)
+        .await
+}
+
+/// V1 API: 语音合成
+pub async fn audio_speech(
+    State(state): State<AppState>,
+    TypedHeader(authorization): TypedHeader<headers::Authorization<headers::authorization::Bearer>>,
+    request_headers: HeaderMap,
+    Json(mut body): Json<Value>,
+) -> axum::response::Response {
+    let user = match authorize_model_request(&state, authorization.token(), &mut body).await {
+        Ok(user) => user,
+        Err(response) => return *response,
+    };
+
+    state
+        .handler
+        .clone()
+        .handle_audio_speech(user, TypedHeader(authorization), request_headers, Json(body))
+        .await
+}
+
+fn invalid_form(error: axum::extract::multipart::MultipartError) -> axum::response::Response {
+    create_error_response(
+        ErrorType::BadRequest,
+        "Invalid multipart form",
+        Some(error.body_text()),
+    )
+    .into_response()
+}
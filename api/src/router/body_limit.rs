@@ -0,0 +1,33 @@
+use crate::app::AppState;
+use crate::relay::error::RelayError;
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    middleware::Next,
+    response::IntoResponse,
+};
+
+/// 按配置限制请求体大小（随热加载生效），超出时返回413
+/// 所有接口都需要完整的请求体，这里先读取再交给后续处理器
+pub async fn limit_request_body(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> axum::response::Response {
+    let max_mb = state.config().settings.max_request_body_mb;
+    let limit = (max_mb * 1024 * 1024) as usize;
+
+    let (parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, limit).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return RelayError::PayloadTooLarge(format!(
+                "The maximum request body size is {} MB: {}",
+                max_mb, e
+            ))
+            .into_response();
+        }
+    };
+
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
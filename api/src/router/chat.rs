@@ -1,53 +1,39 @@
 use crate::app::AppState;
+use crate::config::model::UserToken;
 use axum::{
     extract::State,
+    http::HeaderMap,
     response::IntoResponse,
     Json,
 };
 use axum_extra::TypedHeader;
-use serde_json::{Value, json};
+use serde_json::Value;
+
+use crate::relay::error::RelayError;
+
+/// 聊天消息允许的角色
+const VALID_ROLES: &[&str] = &["system", "developer", "user", "assistant", "tool", "function"];
 
 /// V1 API: 聊天完成
 pub async fn chat_completions(
     State(state): State<AppState>,
     TypedHeader(authorization): TypedHeader<headers::Authorization<headers::authorization::Bearer>>,
     TypedHeader(content_type): TypedHeader<headers::ContentType>,
-    Json(body): Json<Value>,
+    request_headers: HeaderMap,
+    Json(mut body): Json<Value>,
 ) -> axum::response::Response {
-    // 认证检查
-    let token = authorization.token();
-    let user = match state.config.validate_user_token(token) {
-        Some(user) if user.enabled => user,
-        _ => {
-            return (
-                axum::http::StatusCode::UNAUTHORIZED,
-                Json(json!({
-                    "error": {
-                        "type": "invalid_token",
-                        "message": "The provided API key is invalid",
-                        "code": 401
-                    }
-                })),
-            )
-                .into_response();
-        }
+    let user = match authorize_model_request(&state, authorization.token(), &mut body).await {
+        Ok(user) => user,
+        Err(response) => return *response,
     };
 
-    // 检查模型访问权限
-    if let Some(model_name) = body.get("model").and_then(|m| m.as_str()) {
-        if !state.config.user_can_access_model(user, model_name) {
-            return (
-                axum::http::StatusCode::FORBIDDEN,
-                Json(json!({
-                    "error": {
-                        "type": "model_access_denied",
-                        "message": format!("Access denied for model: {}", model_name),
-                        "code": 403
-                    }
-                })),
-            )
-                .into_response();
+    // 格式错误的请求直接拒绝，不转发给上游，也不计入后端失败
+    if let Err(details) = validate_chat_request(&body) {
+        return RelayError::InvalidRequest {
+            message: "Invalid chat completion request".to_string(),
+            details: Some(details),
         }
+        .into_response();
     }
 
     // 继续处理请求
@@ -55,9 +41,120 @@ pub async fn chat_completions(
         .handler
         .clone()
         .handle_completions(
+            user,
+            TypedHeader(authorization),
+            TypedHeader(content_type),
+            request_headers,
+            Json(body),
+        )
+        .await
+}
+
+/// V1 API: 文本补全（旧版接口）
+pub async fn completions(
+    State(state): State<AppState>,
+    TypedHeader(authorization): TypedHeader<headers::Authorization<headers::authorization::Bearer>>,
+    TypedHeader(content_type): TypedHeader<headers::ContentType>,
+    request_headers: HeaderMap,
+    Json(mut body): Json<Value>,
+) -> axum::response::Response {
+    let user = match authorize_model_request(&state, authorization.token(), &mut body).await {
+        Ok(user) => user,
+        Err(response) => return *response,
+    };
+
+    state
+        .handler
+        .clone()
+        .handle_text_completions(
+            user,
             TypedHeader(authorization),
             TypedHeader(content_type),
+            request_headers,
             Json(body),
         )
         .await
 }
+
+/// 检查聊天请求的基本结构：messages为非空数组，每条消息都有合法的role
+pub(crate) fn validate_chat_request(body: &Value) -> Result<(), String> {
+    let messages = match body.get("messages") {
+        Some(Value::Array(messages)) if !messages.is_empty() => messages,
+        Some(Value::Array(_)) => return Err("'messages' must not be empty".to_string()),
+        Some(_) => return Err("'messages' must be an array".to_string()),
+        None => return Err("The 'messages' field is required in the request body".to_string()),
+    };
+
+    for (index, message) in messages.iter().enumerate() {
+        match message.get("role").and_then(|r| r.as_str()) {
+            Some(role) if VALID_ROLES.contains(&role) => {}
+            Some(role) => {
+                return Err(format!("Invalid role '{}' in messages[{}]", role, index));
+            }
+            None => return Err(format!("Missing role in messages[{}]", index)),
+        }
+    }
+
+    Ok(())
+}
+
+/// 认证请求并检查模型访问权限，用户别名会被替换为目标模型
+pub(crate) async fn authorize_model_request(
+    state: &AppState,
+    token: &str,
+    body: &mut Value,
+) -> Result<UserToken, Box<axum::response::Response>> {
+    // 认证检查
+    let user = match state.authenticate(token).await {
+        Some(user) if user.enabled => user,
+        _ => {
+            return Err(Box::new(RelayError::InvalidApiKey.into_response()));
+        }
+    };
+
+    // 用户别名替换为目标模型，租户用户的模型名称加上租户前缀，之后按目标模型检查权限和选择后端
+    let config = state.config();
+    if let Some(model_name) = body.get("model").and_then(|m| m.as_str()) {
+        if let Some(target) = config.resolve_user_alias(&user, model_name) {
+            tracing::debug!("Resolved alias for user '{}' to model '{}'", user.name, target);
+            body["model"] = Value::String(target);
+        } else if let Some(target) = user.tenant_model_name(model_name) {
+            body["model"] = Value::String(target);
+        }
+    }
+
+    // 检查模型访问权限
+    if let Some(model_name) = body.get("model").and_then(|m| m.as_str())
+        && !config.user_can_access_model(&user, model_name)
+    {
+        return Err(Box::new(
+            RelayError::ModelAccessDenied(model_name.to_string()).into_response(),
+        ));
+    }
+
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_chat_request() {
+        assert!(validate_chat_request(&json!({
+            "model": "gpt-4",
+            "messages": [{"role": "system", "content": "hi"}, {"role": "user", "content": "hello"}]
+        }))
+        .is_ok());
+
+        assert!(validate_chat_request(&json!({"model": "gpt-4"})).is_err());
+        assert!(validate_chat_request(&json!({"messages": "hello"})).is_err());
+        assert!(validate_chat_request(&json!({"messages": []})).is_err());
+        assert!(validate_chat_request(&json!({"messages": [{"content": "hello"}]})).is_err());
+        assert_eq!(
+            validate_chat_request(&json!({"messages": [{"role": "user"}, {"role": "robot"}]})),
+            Err("Invalid role 'robot' in messages[1]".to_string())
+        );
+    }
+}
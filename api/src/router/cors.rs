@@ -0,0 +1,82 @@
+use crate::app::AppState;
+use crate::config::model::CorsSettings;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::IntoResponse,
+};
+
+/// 跨域请求允许的方法
+const ALLOWED_METHODS: &str = "GET, POST, OPTIONS";
+
+/// 按配置处理公开接口的CORS（随热加载生效）：直接响应预检请求，并为跨域请求的响应添加CORS响应头
+pub async fn handle_cors(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> axum::response::Response {
+    let config = state.config();
+    let origin = request.headers().get(header::ORIGIN).cloned();
+    let (Some(cors), Some(origin)) = (config.settings.cors.as_ref(), origin) else {
+        return next.run(request).await;
+    };
+    if !is_public_path(request.uri().path()) {
+        return next.run(request).await;
+    }
+    let allowed = origin.to_str().is_ok_and(|origin| cors.allows_origin(origin));
+
+    let is_preflight = request.method() == Method::OPTIONS
+        && request.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    if is_preflight {
+        // 不允许的来源不返回CORS响应头，由浏览器拦截
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        if allowed {
+            let headers = response.headers_mut();
+            set_origin_headers(headers, cors, &origin);
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static(ALLOWED_METHODS));
+            let allowed_headers = if cors.allowed_headers.iter().any(|h| h == "*") {
+                request.headers().get(header::ACCESS_CONTROL_REQUEST_HEADERS).cloned()
+            } else {
+                HeaderValue::from_str(&cors.allowed_headers.join(", ")).ok()
+            };
+            if let Some(value) = allowed_headers {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, value);
+            }
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(cors.max_age_seconds));
+        }
+        response.headers_mut().append(header::VARY, HeaderValue::from_static("origin"));
+        return response;
+    }
+
+    let mut response = next.run(request).await;
+    if allowed {
+        let headers = response.headers_mut();
+        set_origin_headers(headers, cors, &origin);
+        if !cors.exposed_headers.is_empty()
+            && let Ok(value) = HeaderValue::from_str(&cors.exposed_headers.join(", "))
+        {
+            headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, value);
+        }
+    }
+    response.headers_mut().append(header::VARY, HeaderValue::from_static("origin"));
+    response
+}
+
+/// 只有面向客户端的接口允许跨域访问，管理接口和仪表盘不受影响
+fn is_public_path(path: &str) -> bool {
+    path.starts_with("/v1/") || path == "/models" || path == "/health"
+}
+
+fn set_origin_headers(headers: &mut HeaderMap, cors: &CorsSettings, origin: &HeaderValue) {
+    // 未开启凭据且允许任意来源时返回 *，否则回显请求的来源
+    let allow_origin = if !cors.allow_credentials && cors.allowed_origins.iter().any(|o| o == "*") {
+        HeaderValue::from_static("*")
+    } else {
+        origin.clone()
+    };
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+    if cors.allow_credentials {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::app::AppState;
+use axum::{Json, extract::State, response::IntoResponse};
+use serde_json::json;
+
+/// 仪表盘统计接口，返回各模型及其后端的实时状态
+/// 请求速率和延迟曲线由前端根据相邻两次采样的累计值计算
+pub async fn dashboard_stats(State(state): State<AppState>) -> impl IntoResponse {
+    let config = state.config();
+    let metrics = state.load_balancer.get_metrics();
+    let model_health = state.load_balancer.get_model_health();
+
+    let mut model_ids: Vec<&String> = config.models.keys().collect();
+    model_ids.sort();
+
+    let models: Vec<_> = model_ids
+        .into_iter()
+        .map(|model_id| {
+            let mapping = &config.models[model_id];
+            let backends: Vec<_> = mapping
+                .backends
+                .iter()
+                .map(|backend| {
+                    let backend_key = format!("{}:{}", backend.provider, backend.model);
+                    json!({
+                        "provider": backend.provider,
+                        "model": backend.model,
+                        "enabled": backend.enabled,
+                        "admin_disabled": metrics.is_backend_admin_disabled(&backend.provider, &backend.model),
+                        "healthy": metrics.is_healthy(&backend.provider, &backend.model),
+                        "ejected": metrics.is_ejected(&backend_key),
+                        "cooldown_remaining_ms": metrics.cooldown_remaining(&backend.provider, &backend.model).map(|d| d.as_millis()),
+                        "provider_quota_usage_percent": metrics.provider_quota_usage(&backend.provider).map(|u| u * 100.0),
+                        "weight": backend.weight,
+                        "effective_weight": metrics.get_effective_weight(&backend_key, backend.weight),
+                        "recovery_stage": metrics
+                            .get_recovery_stage(&backend.provider, &backend.model)
+                            .map(|stage| format!("slow_start_stage{}", stage + 1)),
+                        "latency_ms": metrics.get_latency(&backend.provider, &backend.model).map(|l| l.as_millis()),
+                        "failure_count": metrics.get_failure_count(&backend.provider, &backend.model),
+                        "in_flight": metrics.get_in_flight(&backend.provider, &backend.model),
+                        "total_requests": metrics.get_request_total(&backend.provider, &backend.model),
+                    })
+                })
+                .collect();
+
+            json!({
+                "id": model_id,
+                "name": mapping.name,
+                "enabled": mapping.enabled,
+                "strategy": mapping.strategy,
+                "health": model_health.get(model_id),
+                "backends": backends,
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "service": {
+            "running": state.load_balancer.is_running().await,
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "models": models,
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    }))
+}
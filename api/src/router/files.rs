@@ -0,0 +1,114 @@
+use crate::app::AppState;
+use crate::config::model::UserToken;
+use crate::relay::client::FormField;
+use crate::relay::error::RelayError;
+use crate::relay::handler::{ErrorType, create_error_response};
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    response::{IntoResponse, Response},
+};
+use axum_extra::TypedHeader;
+use serde::Deserialize;
+
+type BearerAuth = TypedHeader<headers::Authorization<headers::authorization::Bearer>>;
+
+/// 列出文件的查询参数
+#[derive(Debug, Deserialize)]
+pub struct ListFilesQuery {
+    pub purpose: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// V1 API: 上传文件
+pub async fn upload_file(
+    State(state): State<AppState>,
+    TypedHeader(authorization): BearerAuth,
+    mut multipart: Multipart,
+) -> Response {
+    let user = match authenticate(&state, authorization.token()).await {
+        Ok(user) => user,
+        Err(e) => return e.into_response(),
+    };
+    let mut fields = Vec::new();
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return invalid_form(e),
+        };
+        let name = field.name().unwrap_or_default().to_string();
+        let file_name = field.file_name().map(str::to_string);
+        let content_type = field.content_type().map(str::to_string);
+        match field.bytes().await {
+            Ok(data) => fields.push(FormField {
+                name,
+                file_name,
+                content_type,
+                data,
+            }),
+            Err(e) => return invalid_form(e),
+        }
+    }
+    state.handler.clone().handle_file_upload(user, fields).await
+}
+
+/// V1 API: 列出当前用户的文件
+pub async fn list_files(
+    State(state): State<AppState>,
+    TypedHeader(authorization): BearerAuth,
+    Query(query): Query<ListFilesQuery>,
+) -> Response {
+    match authenticate(&state, authorization.token()).await {
+        Ok(user) => state.handler.clone().handle_file_list(user, query.purpose, query.limit).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// V1 API: 查询文件
+pub async fn retrieve_file(
+    State(state): State<AppState>,
+    TypedHeader(authorization): BearerAuth,
+    Path(file_id): Path<String>,
+) -> Response {
+    match authenticate(&state, authorization.token()).await {
+        Ok(user) => state.handler.clone().handle_file_retrieve(user, file_id).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// V1 API: 下载文件内容
+pub async fn file_content(
+    State(state): State<AppState>,
+    TypedHeader(authorization): BearerAuth,
+    Path(file_id): Path<String>,
+) -> Response {
+    match authenticate(&state, authorization.token()).await {
+        Ok(user) => state.handler.clone().handle_file_content(user, file_id).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// V1 API: 删除文件
+pub async fn delete_file(
+    State(state): State<AppState>,
+    TypedHeader(authorization): BearerAuth,
+    Path(file_id): Path<String>,
+) -> Response {
+    match authenticate(&state, authorization.token()).await {
+        Ok(user) => state.handler.clone().handle_file_delete(user, file_id).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn authenticate(state: &AppState, token: &str) -> Result<UserToken, RelayError> {
+    state.authenticate(token).await.filter(|user| user.enabled).ok_or(RelayError::InvalidApiKey)
+}
+
+fn invalid_form(error: axum::extract::multipart::MultipartError) -> Response {
+    create_error_response(
+        ErrorType::BadRequest,
+        "Invalid multipart form",
+        Some(error.body_text()),
+    )
+    .into_response()
+}
@@ -1,16 +1,18 @@
 use crate::app::AppState;
+use crate::loadbalance::ModelHealthState;
 use axum::{
-    extract::State,
+    extract::{Query, State},
     response::IntoResponse,
     Json,
 };
+use serde::Deserialize;
 use serde_json::json;
 use std::collections::HashMap;
 
 /// 详细健康检查处理器 - 返回具体模型和渠道的健康状态
 pub async fn detailed_health_check(State(state): State<AppState>) -> impl IntoResponse {
     let health = state.load_balancer.get_service_health().await;
-    let config = &state.config;
+    let config = state.config();
     let metrics = state.load_balancer.get_metrics();
 
     // 获取详细的提供商健康状态
@@ -89,7 +91,7 @@ pub async fn detailed_health_check(State(state): State<AppState>) -> impl IntoRe
                 "backends": model_backends,
                 "total_backends": model_mapping.backends.iter().filter(|b| b.enabled).count(),
                 "healthy_backends": healthy_backends,
-                "health_ratio": if model_mapping.backends.len() > 0 {
+                "health_ratio": if !model_mapping.backends.is_empty() {
                     healthy_backends as f64 / model_mapping.backends.iter().filter(|b| b.enabled).count() as f64
                 } else {
                     0.0
@@ -157,3 +159,92 @@ pub async fn simple_health_check(State(state): State<AppState>) -> impl IntoResp
         "timestamp": chrono::Utc::now().timestamp()
     }))
 }
+
+/// 存活检查处理器 - 进程能够处理请求即返回200，不检查后端状态
+pub async fn healthz() -> impl IntoResponse {
+    Json(json!({
+        "status": "alive",
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    }))
+}
+
+/// 就绪检查查询参数
+#[derive(Debug, Deserialize)]
+pub struct ReadyzQuery {
+    /// 仅检查指定模型（逗号分隔），默认检查配置的readiness_required_models，未配置时检查所有启用的模型
+    pub models: Option<String>,
+    /// 是否将降级状态视为就绪，默认是
+    pub allow_degraded: Option<bool>,
+}
+
+/// 就绪检查处理器 - 服务运行中、配置已加载，且要求的模型都至少有一个健康后端时才可以接收流量
+pub async fn readyz(
+    State(state): State<AppState>,
+    Query(query): Query<ReadyzQuery>,
+) -> impl IntoResponse {
+    let is_running = state.load_balancer.is_running().await;
+    let allow_degraded = query.allow_degraded.unwrap_or(true);
+    let mut model_health = state.load_balancer.get_model_health();
+    let config = state.config();
+
+    let required: Vec<String> = match &query.models {
+        Some(models) => models
+            .split(',')
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .collect(),
+        None => config.settings.readiness_required_models.clone(),
+    };
+
+    // 要求的模型不存在或未启用时同样视为未就绪
+    let mut not_ready: Vec<String> = Vec::new();
+    if !required.is_empty() {
+        let required_ids: Vec<&String> = required
+            .iter()
+            .filter_map(|model| match config.resolve_model(model) {
+                Some((model_id, _)) if model_health.contains_key(model_id) => Some(model_id),
+                _ => {
+                    not_ready.push(model.clone());
+                    None
+                }
+            })
+            .collect();
+        model_health.retain(|model_id, _| required_ids.contains(&model_id));
+    }
+
+    not_ready.extend(
+        model_health
+            .iter()
+            .filter(|(_, health)| {
+                health.healthy_backends == 0
+                    || match health.state {
+                        ModelHealthState::Healthy => false,
+                        ModelHealthState::Degraded => !allow_degraded,
+                        ModelHealthState::Down => true,
+                    }
+            })
+            .map(|(model_id, _)| model_id.clone()),
+    );
+    not_ready.sort();
+
+    let config_loaded = !config.models.is_empty();
+    let ready = is_running && config_loaded && !model_health.is_empty() && not_ready.is_empty();
+    let status_code = if ready {
+        axum::http::StatusCode::OK
+    } else {
+        axum::http::StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status_code,
+        Json(json!({
+            "ready": ready,
+            "service_running": is_running,
+            "config_loaded": config_loaded,
+            "allow_degraded": allow_degraded,
+            "not_ready_models": not_ready,
+            "models": model_health,
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })),
+    )
+}
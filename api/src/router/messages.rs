@@ -0,0 +1,203 @@
+use crate::app::AppState;
+use crate::relay::anthropic::{self, StreamTranslator};
+use crate::relay::tokens::estimate_request_tokens;
+use axum::{
+    Json,
+    body::Body,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response, Sse, sse::Event},
+};
+use axum_extra::TypedHeader;
+use eventsource_stream::Eventsource;
+use futures::StreamExt;
+use headers::{Authorization, ContentType};
+use serde_json::{Value, json};
+use std::convert::Infallible;
+
+use super::chat::authorize_model_request;
+
+/// Anthropic客户端通过x-api-key传递密钥
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// 转换响应时不保留的上游响应头，由新的响应体重新生成
+const REPLACED_HEADERS: &[header::HeaderName] = &[header::CONTENT_TYPE, header::CONTENT_LENGTH, header::TRANSFER_ENCODING];
+
+/// V1 API: Anthropic Messages，转换为聊天请求后经过相同的负载均衡流程
+pub async fn messages(
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let Some(token) = api_key(&request_headers) else {
+        return error_response(StatusCode::UNAUTHORIZED, "Missing API key");
+    };
+    let requested_model = body.get("model").and_then(Value::as_str).unwrap_or_default().to_string();
+    let stream = body.get("stream").and_then(Value::as_bool).unwrap_or(false);
+
+    let mut request = match anthropic::to_openai_request(&body) {
+        Ok(request) => request,
+        Err(details) => return error_response(StatusCode::BAD_REQUEST, &details),
+    };
+    let user = match authorize_model_request(&state, &token, &mut request).await {
+        Ok(user) => user,
+        Err(response) => return translate_error(*response).await,
+    };
+    let Ok(authorization) = Authorization::bearer(&token) else {
+        return error_response(StatusCode::UNAUTHORIZED, "Invalid API key");
+    };
+
+    let response = state
+        .handler
+        .clone()
+        .handle_completions(
+            user,
+            TypedHeader(authorization),
+            TypedHeader(ContentType::json()),
+            request_headers,
+            Json(request),
+        )
+        .await;
+
+    let is_event_stream = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"));
+    if stream && is_event_stream {
+        translate_stream(response, requested_model)
+    } else {
+        translate_message(response, &requested_model).await
+    }
+}
+
+/// V1 API: 估算Anthropic请求的输入token数
+pub async fn count_tokens(
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+    Json(mut body): Json<Value>,
+) -> Response {
+    let Some(token) = api_key(&request_headers) else {
+        return error_response(StatusCode::UNAUTHORIZED, "Missing API key");
+    };
+    // count_tokens请求不需要max_tokens
+    if body.get("max_tokens").is_none() {
+        body["max_tokens"] = json!(1);
+    }
+    let mut request = match anthropic::to_openai_request(&body) {
+        Ok(request) => request,
+        Err(details) => return error_response(StatusCode::BAD_REQUEST, &details),
+    };
+    if let Err(response) = authorize_model_request(&state, &token, &mut request).await {
+        return translate_error(*response).await;
+    }
+    Json(json!({ "input_tokens": estimate_request_tokens(&request) })).into_response()
+}
+
+/// 优先使用x-api-key，其次使用Authorization中的Bearer令牌
+pub fn api_key(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+        })
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(anthropic::error_body(&json!({ "message": message }), status.as_u16()))).into_response()
+}
+
+/// 把berry的错误响应转换为Anthropic格式，保留状态码
+async fn translate_error(response: Response) -> Response {
+    let status = response.status();
+    let (parts, body) = response.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX).await.unwrap_or_default();
+    let error = serde_json::from_slice::<Value>(&body)
+        .ok()
+        .and_then(|value| value.get("error").cloned())
+        .unwrap_or_else(|| json!({ "message": String::from_utf8_lossy(&body) }));
+    with_headers(
+        (status, Json(anthropic::error_body(&error, status.as_u16()))).into_response(),
+        &parts.headers,
+    )
+}
+
+/// 非流式响应：响应体可能带有保活空白，错误也可能在状态码200的响应体中返回
+async fn translate_message(response: Response, model: &str) -> Response {
+    let (parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return error_response(StatusCode::BAD_GATEWAY, &format!("Failed to read upstream response: {}", e)),
+    };
+    let value: Value = match serde_json::from_slice(bytes.trim_ascii()) {
+        Ok(value) => value,
+        Err(e) => return error_response(StatusCode::BAD_GATEWAY, &format!("Invalid upstream response: {}", e)),
+    };
+
+    let response = match value.get("error") {
+        Some(error) => {
+            let status = error["status"]
+                .as_u64()
+                .and_then(|status| StatusCode::from_u16(status as u16).ok())
+                .filter(|status| !status.is_success())
+                .unwrap_or(if parts.status.is_success() { StatusCode::BAD_GATEWAY } else { parts.status });
+            (status, Json(anthropic::error_body(error, status.as_u16()))).into_response()
+        }
+        None => (parts.status, Json(anthropic::from_openai_response(&value, model))).into_response(),
+    };
+    with_headers(response, &parts.headers)
+}
+
+/// 流式响应：逐个转换OpenAI流式块，保活注释转换为ping事件
+fn translate_stream(response: Response, model: String) -> Response {
+    let (parts, body) = response.into_parts();
+    let translator = StreamTranslator::new(model);
+    let events = body.into_data_stream().eventsource();
+
+    let stream = futures::stream::unfold(Some((events, translator)), |state| async move {
+        let (mut events, mut translator) = state?;
+        let translated = match events.next().await {
+            Some(Ok(event)) if event.data.trim() == "[DONE]" => {
+                return Some((translator.finish(), None));
+            }
+            Some(Ok(event)) => match serde_json::from_str::<Value>(&event.data) {
+                Ok(chunk) => translator.on_chunk(&chunk),
+                Err(_) => Vec::new(),
+            },
+            Some(Err(e)) => translator.on_chunk(&json!({ "error": { "message": e.to_string(), "status": 502 } })),
+            // 上游没有发送[DONE]就结束时也补全结束事件
+            None => return Some((translator.finish(), None)),
+        };
+        Some((translated, Some((events, translator))))
+    })
+    .flat_map(|events| {
+        futures::stream::iter(events.into_iter().map(|(name, data)| {
+            Ok::<_, Infallible>(Event::default().event(name).data(data.to_string()))
+        }))
+    });
+
+    let response = Sse::new(stream)
+        .keep_alive(
+            axum::response::sse::KeepAlive::new()
+                .interval(std::time::Duration::from_secs(30))
+                .event(Event::default().event("ping").data(r#"{"type":"ping"}"#)),
+        )
+        .into_response();
+    with_headers(response, &parts.headers)
+}
+
+/// 保留上游响应的其他响应头（如x-request-id）
+fn with_headers(mut response: Response<Body>, headers: &HeaderMap) -> Response {
+    for (name, value) in headers {
+        if !REPLACED_HEADERS.contains(name) {
+            response.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    response
+}
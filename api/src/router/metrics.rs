@@ -11,13 +11,29 @@ use serde_json::json;
 pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
     let health = state.load_balancer.get_service_health().await;
     let static_files_info = get_static_files_info();
+    let tls_pin_failures = state.load_balancer.get_metrics().get_tls_pin_failures();
+    let upstream_timeouts = state.load_balancer.get_metrics().get_upstream_timeouts();
+    let key_spend = state.load_balancer.get_metrics().get_key_spend();
+    let model_health = state.load_balancer.get_model_health();
+    let peers = state.handler.peer_forwarder().get_status(&state.config());
+    let response_cache = state.handler.response_cache().stats();
+    let semantic_cache = state.handler.semantic_cache().stats();
+    let structured_output = state.handler.structured_output().stats();
+    let fair_queue = state.handler.fair_queue_stats();
+    let moderation = state.handler.moderation().stats();
+    let costs = state.load_balancer.get_cost_tracker().report();
+    let experiments = state.load_balancer.get_experiment_tracker().report();
+    let slo = state.load_balancer.get_slo_report();
 
     Json(json!({
         "service": {
             "running": health.is_running,
             "total_requests": health.total_requests,
             "successful_requests": health.successful_requests,
-            "success_rate": health.success_rate()
+            "success_rate": health.success_rate(),
+            "failed_requests": health.failed_requests,
+            "rates": health.request_rates,
+            "models": health.model_requests
         },
         "providers": {
             "total": health.health_summary.total_providers,
@@ -28,8 +44,21 @@ pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
             "total": health.health_summary.total_models,
             "healthy": health.health_summary.healthy_models,
             "health_ratio": health.health_summary.model_health_ratio,
-            "details": health.model_stats
+            "details": health.model_stats,
+            "health_states": model_health
         },
+        "slo": slo,
+        "peers": peers,
+        "response_cache": response_cache,
+        "semantic_cache": semantic_cache,
+        "structured_output": structured_output,
+        "fair_queue": fair_queue,
+        "moderation": moderation,
+        "costs": costs,
+        "key_spend": key_spend,
+        "experiments": experiments,
+        "tls_pin_failures": tls_pin_failures,
+        "upstream_timeouts": upstream_timeouts,
         "static_files": static_files_info,
         "timestamp": chrono::Utc::now().to_rfc3339()
     }))
@@ -1,7 +1,22 @@
 pub mod api;
 pub mod relay;
+#[allow(clippy::module_inception)]
 pub mod router;
 pub mod health;
 pub mod models;
 pub mod metrics;
-pub mod chat;
\ No newline at end of file
+pub mod chat;
+pub mod embeddings;
+pub mod moderations;
+pub mod audio;
+pub mod files;
+pub mod realtime;
+pub mod messages;
+pub mod responses;
+pub mod body_limit;
+pub mod cors;
+pub mod network_acl;
+pub mod request_id;
+pub mod admin;
+pub mod dashboard;
+pub mod usage;
@@ -1,15 +1,15 @@
 use crate::app::AppState;
 use axum::{
-    extract::State,
+    extract::{Path, State},
     response::IntoResponse,
 };
 use axum_extra::TypedHeader;
-use serde_json::json;
+use crate::relay::error::RelayError;
 
 /// 列出可用模型（无认证，返回所有可用模型）
 pub async fn list_models(State(state): State<AppState>) -> impl IntoResponse {
     let all_models = state.load_balancer.get_available_models();
-    state.handler.handle_models_for_user(all_models).await
+    state.handler.handle_models_for_user(None, all_models).await
 }
 
 /// V1 API: 列出可用模型（需要认证）
@@ -19,30 +19,36 @@ pub async fn list_models_v1(
 ) -> impl IntoResponse {
     // 认证检查
     let token = authorization.token();
-    let user = match state.config.validate_user_token(token) {
+    let user = match state.authenticate(token).await {
         Some(user) if user.enabled => user,
         _ => {
-            return (
-                axum::http::StatusCode::UNAUTHORIZED,
-                axum::Json(json!({
-                    "error": {
-                        "type": "invalid_token",
-                        "message": "The provided API key is invalid",
-                        "code": 401
-                    }
-                })),
-            )
-                .into_response();
+            return RelayError::InvalidApiKey.into_response();
         }
     };
 
     // 获取用户可访问的模型列表
-    let user_models = state.config.get_user_available_models(user);
+    let user_models = state.config().get_user_available_models(&user);
 
     // 使用handler的方法来格式化响应
     state
         .handler
-        .handle_models_for_user(user_models)
+        .handle_models_for_user(Some(&user), user_models)
         .await
         .into_response()
 }
+
+/// V1 API: 获取单个模型的信息（需要认证）
+pub async fn retrieve_model_v1(
+    State(state): State<AppState>,
+    TypedHeader(authorization): TypedHeader<headers::Authorization<headers::authorization::Bearer>>,
+    Path(model): Path<String>,
+) -> impl IntoResponse {
+    let user = match state.authenticate(authorization.token()).await {
+        Some(user) if user.enabled => user,
+        _ => {
+            return RelayError::InvalidApiKey.into_response();
+        }
+    };
+
+    state.handler.handle_model_for_user(&user, &model).await
+}
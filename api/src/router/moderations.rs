@@ -0,0 +1,25 @@
+use crate::app::AppState;
+use axum::{extract::State, http::HeaderMap, Json};
+use axum_extra::TypedHeader;
+use serde_json::Value;
+
+use super::chat::authorize_model_request;
+
+/// V1 API: 内容审核
+pub async fn moderations(
+    State(state): State<AppState>,
+    TypedHeader(authorization): TypedHeader<headers::Authorization<headers::authorization::Bearer>>,
+    request_headers: HeaderMap,
+    Json(mut body): Json<Value>,
+) -> axum::response::Response {
+    let user = match authorize_model_request(&state, authorization.token(), &mut body).await {
+        Ok(user) => user,
+        Err(response) => return *response,
+    };
+
+    state
+        .handler
+        .clone()
+        .handle_moderations(user, TypedHeader(authorization), request_headers, Json(body))
+        .await
+}
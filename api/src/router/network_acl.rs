@@ -0,0 +1,129 @@
+use crate::app::AppState;
+use crate::config::model::NetworkAclSettings;
+use crate::relay::audit::AuditRecord;
+use crate::relay::error::RelayError;
+use crate::router::messages::API_KEY_HEADER;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::IntoResponse,
+};
+use std::net::{IpAddr, SocketAddr};
+use tracing::warn;
+
+/// 健康探针不受IP访问控制，负载均衡器和编排系统的探测地址通常不在允许列表中
+const EXEMPT_PATHS: &[&str] = &["/healthz", "/readyz"];
+
+/// 按来源IP执行全局允许/拒绝列表和密钥的IP绑定，拒绝时返回403并写入审计日志
+pub async fn enforce_network_acl(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> axum::response::Response {
+    if EXEMPT_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let config = state.config();
+    let acl = config.settings.network_acl.as_ref();
+    let client_ip = client_ip(&request, acl);
+    let token = client_token(request.headers());
+
+    if let Some(acl) = acl
+        && !client_ip.is_some_and(|ip| acl.permits(ip))
+    {
+        let user = match token {
+            Some(token) => state.authenticate(token).await,
+            None => None,
+        };
+        return reject(
+            &state,
+            user.as_ref().map(|user| user.name.as_str()),
+            token,
+            client_ip,
+            format!("Requests from {} are not allowed", describe(client_ip)),
+        );
+    }
+
+    // 只有存在绑定IP的密钥时才需要提前识别用户
+    if let Some(token) = token
+        && has_ip_bindings(&state)
+        && let Some(user) = state.authenticate(token).await
+        && !client_ip.is_some_and(|ip| user.permits_ip(ip))
+    {
+        return reject(
+            &state,
+            Some(&user.name),
+            Some(token),
+            client_ip,
+            format!("This API key cannot be used from {}", describe(client_ip)),
+        );
+    }
+
+    next.run(request).await
+}
+
+/// 客户端IP：直连地址是可信代理时，取X-Forwarded-For中从右往左第一个不是可信代理的地址
+/// 没有连接信息时（未通过into_make_service_with_connect_info启动）返回None
+pub fn client_ip(request: &Request, acl: Option<&NetworkAclSettings>) -> Option<IpAddr> {
+    let peer = request.extensions().get::<ConnectInfo<SocketAddr>>()?.0.ip();
+    let Some(acl) = acl.filter(|acl| acl.is_trusted_proxy(peer)) else {
+        return Some(peer);
+    };
+
+    let mut client = peer;
+    let hops = request
+        .headers()
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+    for hop in hops.into_iter().rev() {
+        let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+            break;
+        };
+        client = ip;
+        if !acl.is_trusted_proxy(ip) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+/// Authorization中的Bearer令牌，Anthropic格式的请求使用x-api-key
+fn client_token(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let api_key = || headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok());
+    bearer.or_else(api_key).map(str::trim)
+}
+
+fn has_ip_bindings(state: &AppState) -> bool {
+    state.config().users.values().any(|user| !user.allowed_ips.is_empty())
+        || state
+            .runtime_users
+            .read()
+            .is_ok_and(|users| users.values().any(|user| !user.allowed_ips.is_empty()))
+}
+
+fn describe(client_ip: Option<IpAddr>) -> String {
+    client_ip.map_or_else(|| "an unknown address".to_string(), |ip| ip.to_string())
+}
+
+fn reject(
+    state: &AppState,
+    user: Option<&str>,
+    token: Option<&str>,
+    client_ip: Option<IpAddr>,
+    reason: String,
+) -> axum::response::Response {
+    warn!("Rejected request from {} (user: {}): {}", describe(client_ip), user.unwrap_or("anonymous"), reason);
+    state
+        .handler
+        .audit_rejection(AuditRecord::rejected(user, token, client_ip, 403, reason.clone()));
+    RelayError::PermissionDenied(reason).into_response()
+}
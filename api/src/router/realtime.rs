@@ -0,0 +1,60 @@
+use crate::app::AppState;
+use crate::relay::error::RelayError;
+use axum::{
+    extract::{Query, State, ws::WebSocketUpgrade},
+    http::{HeaderMap, header},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::chat::authorize_model_request;
+
+/// 浏览器无法设置Authorization请求头，按OpenAI的约定通过子协议传递密钥
+const API_KEY_PROTOCOL_PREFIX: &str = "openai-insecure-api-key.";
+
+#[derive(Debug, Deserialize)]
+pub struct RealtimeQuery {
+    pub model: String,
+}
+
+/// V1 API: Realtime WebSocket会话，模型通过查询参数model指定
+pub async fn realtime(
+    State(state): State<AppState>,
+    Query(query): Query<RealtimeQuery>,
+    request_headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> axum::response::Response {
+    let Some(token) = client_token(&request_headers) else {
+        return RelayError::InvalidApiKey.into_response();
+    };
+    let mut body = json!({ "model": query.model });
+    let user = match authorize_model_request(&state, &token, &mut body).await {
+        Ok(user) => user,
+        Err(response) => return *response,
+    };
+    let model = body["model"].as_str().unwrap_or(&query.model).to_string();
+
+    state
+        .handler
+        .clone()
+        .handle_realtime(user, token, request_headers, model, upgrade)
+        .await
+}
+
+/// 优先使用Authorization请求头，其次使用Sec-WebSocket-Protocol中的密钥
+fn client_token(headers: &HeaderMap) -> Option<String> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let protocol = || {
+        headers
+            .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .find_map(|protocol| protocol.trim().strip_prefix(API_KEY_PROTOCOL_PREFIX))
+    };
+    bearer.or_else(protocol).map(|token| token.trim().to_string())
+}
@@ -0,0 +1,61 @@
+use crate::telemetry::{REQUEST_ID_HEADER, with_request_id};
+use axum::{extract::Request, http::HeaderValue, middleware::Next};
+use rand::Rng;
+use tracing::Instrument;
+
+/// 客户端提供的请求ID最大长度，超出或包含不可见字符时重新生成
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// 为每个请求分配请求ID（优先使用客户端的x-request-id），
+/// 请求处理过程中的span和日志都带有该ID，响应头中返回同一ID
+pub async fn assign_request_id(mut request: Request, next: Next) -> axum::response::Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(generate_request_id);
+    let header_value = HeaderValue::from_str(&request_id).ok();
+    if let Some(value) = &header_value {
+        request.headers_mut().insert(REQUEST_ID_HEADER, value.clone());
+    }
+
+    let span = tracing::info_span!("request", request_id = %request_id);
+    let mut response = with_request_id(request_id, next.run(request))
+        .instrument(span)
+        .await;
+    if let Some(value) = header_value {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// 生成32位十六进制的随机请求ID
+fn generate_request_id() -> String {
+    let mut rng = rand::rng();
+    (0..32)
+        .map(|_| format!("{:x}", rng.random_range(0..16u8)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_id_validation() {
+        assert!(is_valid_request_id("req-123_abc"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("has space"));
+        assert!(!is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+
+        let generated = generate_request_id();
+        assert_eq!(generated.len(), 32);
+        assert!(is_valid_request_id(&generated));
+    }
+}
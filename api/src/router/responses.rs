@@ -0,0 +1,194 @@
+use crate::app::AppState;
+use crate::config::model::Config;
+use crate::relay::error::RelayError;
+use crate::relay::responses::{self, StreamTranslator, TERMINAL_EVENTS};
+use axum::{
+    Json,
+    body::Body,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response, Sse, sse::Event},
+};
+use axum_extra::TypedHeader;
+use eventsource_stream::Eventsource;
+use futures::StreamExt;
+use headers::ContentType;
+use serde_json::{Value, json};
+use std::convert::Infallible;
+
+use super::chat::authorize_model_request;
+
+/// 转换响应时不保留的上游响应头，由新的响应体重新生成
+const REPLACED_HEADERS: &[header::HeaderName] = &[header::CONTENT_TYPE, header::CONTENT_LENGTH, header::TRANSFER_ENCODING];
+
+/// V1 API: Responses，后端都原生支持时直接透传，否则转换为聊天请求
+pub async fn responses(
+    State(state): State<AppState>,
+    TypedHeader(authorization): TypedHeader<headers::Authorization<headers::authorization::Bearer>>,
+    request_headers: HeaderMap,
+    Json(mut body): Json<Value>,
+) -> Response {
+    let requested_model = body.get("model").and_then(Value::as_str).unwrap_or_default().to_string();
+    let stream = body.get("stream").and_then(Value::as_bool).unwrap_or(false);
+    let user = match authorize_model_request(&state, authorization.token(), &mut body).await {
+        Ok(user) => user,
+        Err(response) => return *response,
+    };
+
+    let model = body.get("model").and_then(Value::as_str).unwrap_or_default();
+    if supports_native_responses(&state.config(), model) {
+        let response = state
+            .handler
+            .clone()
+            .handle_responses(user, TypedHeader(authorization), request_headers, Json(body))
+            .await;
+        return if stream && is_event_stream(&response) { normalize_stream(response) } else { response };
+    }
+
+    let request = match responses::to_chat_request(&body) {
+        Ok(request) => request,
+        Err(details) => {
+            return RelayError::InvalidRequest {
+                message: "Invalid responses request".to_string(),
+                details: Some(details),
+            }
+            .into_response();
+        }
+    };
+    let response = state
+        .handler
+        .clone()
+        .handle_completions(
+            user,
+            TypedHeader(authorization),
+            TypedHeader(ContentType::json()),
+            request_headers,
+            Json(request),
+        )
+        .await;
+    if stream && is_event_stream(&response) {
+        translate_stream(response, requested_model)
+    } else {
+        translate_response(response, &requested_model).await
+    }
+}
+
+/// 模型的所有启用后端都原生支持Responses接口
+fn supports_native_responses(config: &Config, model: &str) -> bool {
+    config.resolve_model(model).is_some_and(|(_, mapping)| {
+        let mut backends = mapping.backends.iter().filter(|backend| backend.enabled).peekable();
+        backends.peek().is_some() && backends.all(|backend| backend.supports_responses)
+    })
+}
+
+fn is_event_stream(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"))
+}
+
+fn stream_error(message: &str) -> Event {
+    Event::default()
+        .event("error")
+        .data(json!({ "type": "error", "code": "upstream_error", "message": message, "param": Value::Null }).to_string())
+}
+
+/// 透传的流式响应：补全事件名，丢弃[DONE]，上游没有发送结束事件就断开时补发error事件
+fn normalize_stream(response: Response) -> Response {
+    let (parts, body) = response.into_parts();
+    let events = body.into_data_stream().eventsource();
+
+    let stream = futures::stream::unfold(Some((events, false)), |state| async move {
+        let (mut events, terminated) = state?;
+        match events.next().await {
+            Some(Ok(event)) if event.data.trim() == "[DONE]" => Some((Vec::new(), Some((events, terminated)))),
+            Some(Ok(event)) => {
+                let name = match event.event.as_str() {
+                    "" | "message" => serde_json::from_str::<Value>(&event.data)
+                        .ok()
+                        .and_then(|data| data["type"].as_str().map(str::to_string))
+                        .unwrap_or_else(|| "message".to_string()),
+                    name => name.to_string(),
+                };
+                let terminated = terminated || TERMINAL_EVENTS.contains(&name.as_str());
+                Some((vec![Event::default().event(name).data(event.data)], Some((events, terminated))))
+            }
+            Some(Err(e)) => Some((vec![stream_error(&e.to_string())], None)),
+            None if terminated => None,
+            None => Some((vec![stream_error("Upstream stream ended before the response completed")], None)),
+        }
+    })
+    .flat_map(|events| futures::stream::iter(events.into_iter().map(Ok::<_, Infallible>)));
+
+    with_headers(Sse::new(stream).keep_alive(Default::default()).into_response(), &parts.headers)
+}
+
+/// 非流式响应：响应体可能带有保活空白，错误也可能在状态码200的响应体中返回
+async fn translate_response(response: Response, model: &str) -> Response {
+    let (parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return RelayError::Internal(format!("Failed to read upstream response: {}", e)).into_response(),
+    };
+    let value: Value = match serde_json::from_slice(bytes.trim_ascii()) {
+        Ok(value) => value,
+        Err(_) => return Response::from_parts(parts, Body::from(bytes)),
+    };
+
+    let response = match value.get("error") {
+        // 错误响应已经是OpenAI格式，只修正状态码
+        Some(error) => {
+            let status = error["status"]
+                .as_u64()
+                .and_then(|status| StatusCode::from_u16(status as u16).ok())
+                .filter(|status| !status.is_success())
+                .unwrap_or(if parts.status.is_success() { StatusCode::BAD_GATEWAY } else { parts.status });
+            (status, Json(json!({ "error": error }))).into_response()
+        }
+        None => (parts.status, Json(responses::from_chat_response(&value, model))).into_response(),
+    };
+    with_headers(response, &parts.headers)
+}
+
+/// 流式响应：逐个转换OpenAI流式块为Responses事件
+fn translate_stream(response: Response, model: String) -> Response {
+    let (parts, body) = response.into_parts();
+    let translator = StreamTranslator::new(model);
+    let events = body.into_data_stream().eventsource();
+
+    let stream = futures::stream::unfold(Some((events, translator)), |state| async move {
+        let (mut events, mut translator) = state?;
+        let translated = match events.next().await {
+            Some(Ok(event)) if event.data.trim() == "[DONE]" => {
+                return Some((translator.finish(), None));
+            }
+            Some(Ok(event)) => match serde_json::from_str::<Value>(&event.data) {
+                Ok(chunk) => translator.on_chunk(&chunk),
+                Err(_) => Vec::new(),
+            },
+            Some(Err(e)) => translator.on_chunk(&json!({ "error": { "message": e.to_string(), "code": "upstream_error" } })),
+            // 上游没有发送[DONE]就结束时也补全结束事件
+            None => return Some((translator.finish(), None)),
+        };
+        Some((translated, Some((events, translator))))
+    })
+    .flat_map(|events| {
+        futures::stream::iter(events.into_iter().map(|(name, data)| {
+            Ok::<_, Infallible>(Event::default().event(name).data(data.to_string()))
+        }))
+    });
+
+    with_headers(Sse::new(stream).keep_alive(Default::default()).into_response(), &parts.headers)
+}
+
+/// 保留上游响应的其他响应头（如x-request-id）
+fn with_headers(mut response: Response<Body>, headers: &HeaderMap) -> Response {
+    for (name, value) in headers {
+        if !REPLACED_HEADERS.contains(name) {
+            response.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    response
+}
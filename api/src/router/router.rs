@@ -1,16 +1,33 @@
 use crate::app::AppState;
-use crate::static_files::{serve_index, serve_static_file};
+use crate::static_files::{serve_dashboard, serve_index, serve_static_file};
 use axum::{
     Router,
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    routing::{delete, get, post},
 };
 use tower_http::trace::TraceLayer;
 
 use super::{
-    chat::chat_completions,
-    health::{detailed_health_check, simple_health_check},
+    admin::{
+        admin_clear_faults, admin_config_history, admin_config_rollback, admin_config_version, admin_create_key,
+        admin_create_tenant_key, admin_disable_backend, admin_enable_backend, admin_events, admin_get_config,
+        admin_get_log_filter, admin_get_tenant_config, admin_list_backends, admin_list_faults, admin_patch_config,
+        admin_patch_tenant_config, admin_reload, admin_reset_log_filter, admin_revoke_key, admin_revoke_tenant_key,
+        admin_route_preview, admin_set_fault, admin_set_log_filter, admin_status, admin_usage,
+    },
+    audio::{audio_speech, audio_transcriptions},
+    chat::{chat_completions, completions},
+    embeddings::embeddings,
+    files::{delete_file, file_content, list_files, retrieve_file, upload_file},
+    dashboard::dashboard_stats,
+    health::{detailed_health_check, healthz, readyz, simple_health_check},
+    messages::{count_tokens, messages},
     metrics::metrics,
-    models::{list_models, list_models_v1},
+    models::{list_models, list_models_v1, retrieve_model_v1},
+    moderations::moderations,
+    realtime::realtime,
+    responses::responses,
+    usage::{billing_subscription, billing_usage, legacy_usage, organization_costs, organization_usage_completions},
 };
 
 /// 创建应用路由
@@ -18,12 +35,20 @@ pub fn create_app_router() -> Router<AppState> {
     Router::new()
         .route("/", get(index))
         .route("/health", get(detailed_health_check))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
         .route("/metrics", get(metrics))
         .route("/models", get(list_models))
         .nest("/v1", create_v1_routes())
+        .nest("/admin", create_admin_routes())
         // 静态文件路由 - 使用嵌入的文件
         .route("/status", get(serve_index))
         .route("/status/{*path}", get(serve_static_file))
+        // 内置仪表盘
+        .route("/dashboard", get(serve_dashboard))
+        .route("/dashboard/api/stats", get(dashboard_stats))
+        // 请求体大小由 body_limit 中间件按配置限制
+        .layer(DefaultBodyLimit::disable())
         .layer(TraceLayer::new_for_http())
 }
 
@@ -31,8 +56,54 @@ pub fn create_app_router() -> Router<AppState> {
 fn create_v1_routes() -> Router<AppState> {
     Router::new()
         .route("/chat/completions", post(chat_completions))
+        .route("/completions", post(completions))
+        .route("/messages", post(messages))
+        .route("/messages/count_tokens", post(count_tokens))
+        .route("/responses", post(responses))
+        .route("/embeddings", post(embeddings))
+        .route("/moderations", post(moderations))
+        .route("/audio/transcriptions", post(audio_transcriptions))
+        .route("/audio/speech", post(audio_speech))
+        .route("/realtime", get(realtime))
+        .route("/files", post(upload_file).get(list_files))
+        .route("/files/{file_id}", get(retrieve_file).delete(delete_file))
+        .route("/files/{file_id}/content", get(file_content))
         .route("/models", get(list_models_v1))
+        .route("/models/{*model}", get(retrieve_model_v1))
         .route("/health", get(simple_health_check))
+        .route("/usage", get(legacy_usage))
+        .route("/dashboard/billing/subscription", get(billing_subscription))
+        .route("/dashboard/billing/usage", get(billing_usage))
+        .route("/organization/usage/completions", get(organization_usage_completions))
+        .route("/organization/costs", get(organization_costs))
+}
+
+/// 创建管理 API 路由
+fn create_admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/status", get(admin_status))
+        .route("/backends", get(admin_list_backends))
+        .route("/backends/disable", post(admin_disable_backend))
+        .route("/backends/enable", post(admin_enable_backend))
+        .route("/route-preview", get(admin_route_preview))
+        .route("/reload", post(admin_reload))
+        .route("/config", get(admin_get_config).patch(admin_patch_config))
+        .route("/config/history", get(admin_config_history))
+        .route("/config/history/{version}", get(admin_config_version))
+        .route("/config/rollback", post(admin_config_rollback))
+        .route("/keys", post(admin_create_key))
+        .route("/keys/{name}", delete(admin_revoke_key))
+        .route("/events", get(admin_events))
+        .route("/usage", get(admin_usage))
+        .route(
+            "/log-filter",
+            get(admin_get_log_filter).put(admin_set_log_filter).delete(admin_reset_log_filter),
+        )
+        .route("/faults", get(admin_list_faults).post(admin_set_fault).delete(admin_clear_faults))
+        // 租户管理接口，租户管理员只能访问自己租户的路径
+        .route("/tenants/{tenant}/config", get(admin_get_tenant_config).patch(admin_patch_tenant_config))
+        .route("/tenants/{tenant}/keys", post(admin_create_tenant_key))
+        .route("/tenants/{tenant}/keys/{name}", delete(admin_revoke_tenant_key))
 }
 
 /// 首页处理器
@@ -0,0 +1,358 @@
+use crate::app::AppState;
+use crate::config::model::UserToken;
+use crate::loadbalance::{UsageBucket, UsageDimension, UsageQuery, UsageRow};
+use crate::relay::error::RelayError;
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::TypedHeader;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+type Bearer = TypedHeader<headers::Authorization<headers::authorization::Bearer>>;
+
+/// 未设置预算的用户在订阅信息中返回的额度（美元）
+const UNLIMITED_BUDGET_USD: f64 = 100_000_000.0;
+
+async fn authenticate(state: &AppState, authorization: &Bearer) -> Result<UserToken, Box<Response>> {
+    match state.authenticate(authorization.token()).await {
+        Some(user) if user.enabled => Ok(user),
+        _ => Err(Box::new(RelayError::InvalidApiKey.into_response())),
+    }
+}
+
+fn invalid_request(details: String) -> Box<Response> {
+    Box::new(
+        RelayError::InvalidRequest {
+            message: "Invalid usage query".to_string(),
+            details: Some(details),
+        }
+        .into_response(),
+    )
+}
+
+fn parse_date(value: &str, name: &str) -> Result<DateTime<Utc>, Box<Response>> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc())
+        .ok_or_else(|| invalid_request(format!("Invalid {} '{}': expected YYYY-MM-DD", name, value)))
+}
+
+/// 兼容OpenAI: 账户订阅信息，额度为用户的花费上限
+pub async fn billing_subscription(State(state): State<AppState>, authorization: Bearer) -> Response {
+    let user = match authenticate(&state, &authorization).await {
+        Ok(user) => user,
+        Err(response) => return *response,
+    };
+    let hard_limit = user.budget.unwrap_or(UNLIMITED_BUDGET_USD);
+
+    Json(json!({
+        "object": "billing_subscription",
+        "has_payment_method": true,
+        "soft_limit_usd": hard_limit,
+        "hard_limit_usd": hard_limit,
+        "system_hard_limit_usd": hard_limit,
+        "access_until": 0,
+        "plan": { "id": "berry", "title": "Berry API" },
+    }))
+    .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BillingUsageParams {
+    pub start_date: String,
+    pub end_date: String,
+}
+
+/// 兼容OpenAI: 调用者在日期范围内（结束日期不含）按天、按模型的花费，单位为美分
+pub async fn billing_usage(
+    State(state): State<AppState>,
+    authorization: Bearer,
+    Query(params): Query<BillingUsageParams>,
+) -> Response {
+    let user = match authenticate(&state, &authorization).await {
+        Ok(user) => user,
+        Err(response) => return *response,
+    };
+    let (start, end) = match (parse_date(&params.start_date, "start_date"), parse_date(&params.end_date, "end_date")) {
+        (Ok(start), Ok(end)) => (start, end),
+        (Err(response), _) | (_, Err(response)) => return *response,
+    };
+
+    let report = state.load_balancer.get_cost_tracker().usage(&UsageQuery {
+        start: Some(start),
+        end: Some(end),
+        bucket: UsageBucket::Day,
+        group_by: vec![UsageDimension::Model],
+        users: vec![user.name.clone()],
+        ..UsageQuery::default()
+    });
+
+    let mut daily_costs: Vec<Value> = Vec::new();
+    for row in &report.rows {
+        let timestamp = row.bucket_start.map(|t| t.timestamp() as f64);
+        let name = row.model.as_deref().map(|model| user.visible_model_name(model));
+        let line_item = json!({ "name": name, "cost": row.usage.cost * 100.0 });
+        match daily_costs.last_mut() {
+            Some(day) if day["timestamp"].as_f64() == timestamp => {
+                if let Some(items) = day["line_items"].as_array_mut() {
+                    items.push(line_item);
+                }
+            }
+            _ => daily_costs.push(json!({ "timestamp": timestamp, "line_items": [line_item] })),
+        }
+    }
+
+    Json(json!({
+        "object": "list",
+        "daily_costs": daily_costs,
+        "total_usage": report.total.cost * 100.0,
+    }))
+    .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LegacyUsageParams {
+    pub date: String,
+}
+
+/// 兼容OpenAI旧版 /v1/usage：调用者某一天按小时、按模型的请求数和token数
+pub async fn legacy_usage(
+    State(state): State<AppState>,
+    authorization: Bearer,
+    Query(params): Query<LegacyUsageParams>,
+) -> Response {
+    let user = match authenticate(&state, &authorization).await {
+        Ok(user) => user,
+        Err(response) => return *response,
+    };
+    let start = match parse_date(&params.date, "date") {
+        Ok(start) => start,
+        Err(response) => return *response,
+    };
+
+    let report = state.load_balancer.get_cost_tracker().usage(&UsageQuery {
+        start: Some(start),
+        end: Some(start + chrono::Duration::days(1)),
+        bucket: UsageBucket::Hour,
+        group_by: vec![UsageDimension::Model],
+        users: vec![user.name.clone()],
+        ..UsageQuery::default()
+    });
+    let data: Vec<Value> = report
+        .rows
+        .iter()
+        .map(|row| {
+            json!({
+                "aggregation_timestamp": row.bucket_start.map(|t| t.timestamp()),
+                "n_requests": row.usage.requests,
+                "operation": "completion",
+                "snapshot_id": row.model.as_deref().map(|model| user.visible_model_name(model)),
+                "n_context_tokens_total": row.usage.prompt_tokens,
+                "n_generated_tokens_total": row.usage.completion_tokens,
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "object": "list",
+        "data": data,
+        "ft_data": [],
+        "dalle_api_data": [],
+        "whisper_api_data": [],
+        "tts_api_data": [],
+        "current_usage_usd": report.total.cost,
+        "in_flight_requests": state.handler.user_in_flight().get(&user.name).copied().unwrap_or_default(),
+    }))
+    .into_response()
+}
+
+/// 组织用量接口的查询条件
+struct OrganizationQuery {
+    start: i64,
+    end: i64,
+    width: i64,
+    limit: i64,
+    group_by: Vec<UsageDimension>,
+    users: Vec<String>,
+    models: Vec<String>,
+}
+
+impl OrganizationQuery {
+    /// 解析查询参数，group_by等数组参数既可重复（group_by[]=model）也可逗号分隔
+    /// line_item按模型分组；project_id、api_key_id、batch没有对应数据，接受但不分组
+    fn parse(params: &[(String, String)], user: &UserToken) -> Result<Self, Box<Response>> {
+        let values = |name: &str| -> Vec<String> {
+            params
+                .iter()
+                .filter(|(key, _)| key == name || key.strip_suffix("[]") == Some(name))
+                .flat_map(|(_, value)| value.split(','))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let integer = |name: &str| -> Result<Option<i64>, Box<Response>> {
+            values(name)
+                .first()
+                .map(|value| value.parse().map_err(|_| invalid_request(format!("Invalid {} '{}'", name, value))))
+                .transpose()
+        };
+
+        let width = match values("bucket_width").first().map(String::as_str) {
+            None | Some("1d") => 86400,
+            Some("1h") => 3600,
+            Some(other) => return Err(invalid_request(format!("Unsupported bucket_width '{}': expected 1h or 1d", other))),
+        };
+        let (default_limit, max_limit) = if width == 3600 { (24, 168) } else { (7, 31) };
+        let limit = integer("limit")?.unwrap_or(default_limit);
+        if !(1..=max_limit).contains(&limit) {
+            return Err(invalid_request(format!("limit must be between 1 and {}", max_limit)));
+        }
+
+        // 分页时page为下一页的起始时间
+        let start = match integer("page")? {
+            Some(page) => page,
+            None => integer("start_time")?.ok_or_else(|| invalid_request("start_time is required".to_string()))?,
+        };
+        let end = integer("end_time")?.unwrap_or_else(|| Utc::now().timestamp());
+
+        let mut group_by = Vec::new();
+        for field in values("group_by") {
+            let dimension = match field.as_str() {
+                "model" | "line_item" => UsageDimension::Model,
+                "user_id" => UsageDimension::User,
+                "project_id" | "api_key_id" | "batch" => continue,
+                other => return Err(invalid_request(format!("Unsupported group_by '{}'", other))),
+            };
+            if !group_by.contains(&dimension) {
+                group_by.push(dimension);
+            }
+        }
+
+        // 非管理员只能查看自己的用量
+        let users = if user.is_admin() { values("user_ids") } else { vec![user.name.clone()] };
+
+        Ok(Self {
+            start: start.div_euclid(width) * width,
+            end,
+            width,
+            limit,
+            group_by,
+            users,
+            // 租户用户使用不带租户前缀的模型名称
+            models: values("models")
+                .into_iter()
+                .map(|model| user.tenant_model_name(&model).unwrap_or(model))
+                .collect(),
+        })
+    }
+
+    /// 按时间分桶生成分页结果，空的时间桶也会返回
+    fn page(&self, state: &AppState, result: impl Fn(&UsageRow) -> Value) -> Value {
+        let buckets = ((self.end - self.start + self.width - 1) / self.width).max(0);
+        let returned = buckets.min(self.limit);
+        let page_end = self.start + returned * self.width;
+        let time = |seconds: i64| Utc.timestamp_opt(seconds, 0).single();
+
+        let report = state.load_balancer.get_cost_tracker().usage(&UsageQuery {
+            start: time(self.start),
+            end: time(page_end),
+            bucket: if self.width == 3600 { UsageBucket::Hour } else { UsageBucket::Day },
+            group_by: self.group_by.clone(),
+            users: self.users.clone(),
+            models: self.models.clone(),
+            ..UsageQuery::default()
+        });
+
+        let data: Vec<Value> = (0..returned)
+            .map(|index| {
+                let start_time = self.start + index * self.width;
+                let results: Vec<Value> = report
+                    .rows
+                    .iter()
+                    .filter(|row| row.bucket_start.map(|t| t.timestamp()) == Some(start_time))
+                    .map(&result)
+                    .collect();
+                json!({
+                    "object": "bucket",
+                    "start_time": start_time,
+                    "end_time": start_time + self.width,
+                    "results": results,
+                })
+            })
+            .collect();
+
+        let has_more = buckets > returned;
+        json!({
+            "object": "page",
+            "data": data,
+            "has_more": has_more,
+            "next_page": has_more.then(|| page_end.to_string()),
+        })
+    }
+}
+
+/// 兼容OpenAI组织用量接口：按时间分桶的请求数和token数
+pub async fn organization_usage_completions(
+    State(state): State<AppState>,
+    authorization: Bearer,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Response {
+    let user = match authenticate(&state, &authorization).await {
+        Ok(user) => user,
+        Err(response) => return *response,
+    };
+    let query = match OrganizationQuery::parse(&params, &user) {
+        Ok(query) => query,
+        Err(response) => return *response,
+    };
+
+    Json(query.page(&state, |row| {
+        json!({
+            "object": "organization.usage.completions.result",
+            "input_tokens": row.usage.prompt_tokens,
+            "output_tokens": row.usage.completion_tokens,
+            "input_cached_tokens": 0,
+            "input_audio_tokens": 0,
+            "output_audio_tokens": 0,
+            "num_model_requests": row.usage.requests,
+            "project_id": null,
+            "user_id": row.user,
+            "api_key_id": null,
+            "model": row.model.as_deref().map(|model| user.visible_model_name(model)),
+            "batch": null,
+        })
+    }))
+    .into_response()
+}
+
+/// 兼容OpenAI组织花费接口：按时间分桶的估算花费（美元），line_item为模型
+pub async fn organization_costs(
+    State(state): State<AppState>,
+    authorization: Bearer,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Response {
+    let user = match authenticate(&state, &authorization).await {
+        Ok(user) => user,
+        Err(response) => return *response,
+    };
+    let query = match OrganizationQuery::parse(&params, &user) {
+        Ok(query) => query,
+        Err(response) => return *response,
+    };
+
+    Json(query.page(&state, |row| {
+        json!({
+            "object": "organization.costs.result",
+            "amount": { "value": row.usage.cost, "currency": "usd" },
+            "line_item": row.model.as_deref().map(|model| user.visible_model_name(model)),
+            "project_id": null,
+        })
+    }))
+    .into_response()
+}
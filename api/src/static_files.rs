@@ -19,6 +19,11 @@ pub async fn serve_index() -> impl IntoResponse {
     serve_file("index.html").await
 }
 
+/// 处理仪表盘页面请求
+pub async fn serve_dashboard() -> impl IntoResponse {
+    serve_file("dashboard.html").await
+}
+
 /// 内部函数：根据路径提供文件
 async fn serve_file(path: &str) -> Response {
     // 清理路径，移除开头的斜杠
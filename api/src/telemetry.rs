@@ -0,0 +1,319 @@
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Registry, reload};
+
+/// 启用OTLP导出的环境变量，值为collector地址（如 http://localhost:4318）
+pub const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// 运行时日志过滤器的控制句柄，init_tracing之后可用
+static LOG_FILTER: OnceLock<LogFilterControl> = OnceLock::new();
+
+/// 初始化日志与链路追踪
+/// 日志级别由RUST_LOG环境变量决定，运行中可通过管理接口临时修改；设置了OTEL_EXPORTER_OTLP_ENDPOINT时
+/// 额外通过OTLP/HTTP导出span，返回的TracerProvider需要在退出前关闭以发送剩余的span
+pub fn init_tracing() -> Option<SdkTracerProvider> {
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .with_file(true)
+        .with_line_number(true);
+
+    let provider = match std::env::var(OTLP_ENDPOINT_ENV) {
+        Ok(endpoint) if !endpoint.is_empty() => match build_tracer_provider() {
+            Ok(provider) => Some(provider),
+            Err(e) => {
+                eprintln!("Failed to initialize OTLP exporter for {}: {}", endpoint, e);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    let otel_layer = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer("berry-api"))
+    });
+
+    let filter = EnvFilter::from_default_env();
+    let default = filter.to_string();
+    let (filter, handle) = reload::Layer::new(filter);
+    let _ = LOG_FILTER.set(LogFilterControl::new(handle, default));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .with(otel_layer)
+        .init();
+
+    provider
+}
+
+/// 运行时日志过滤器，未通过init_tracing初始化日志时为空
+pub fn log_filter() -> Option<&'static LogFilterControl> {
+    LOG_FILTER.get()
+}
+
+/// 修改日志过滤器失败时的错误
+#[derive(Debug, thiserror::Error)]
+pub enum LogFilterError {
+    #[error("Invalid log filter '{0}': {1}")]
+    Invalid(String, String),
+    #[error("Failed to reload log filter: {0}")]
+    Reload(String),
+}
+
+/// 日志过滤器的当前状态
+#[derive(Debug, Clone, Serialize)]
+pub struct LogFilterStatus {
+    /// 当前生效的过滤规则（EnvFilter语法）
+    pub filter: String,
+    /// 启动时由RUST_LOG决定的过滤规则
+    pub default: String,
+    /// 临时规则到期恢复默认规则的时间，未设置时长时为空
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default)]
+struct OverrideState {
+    expires_at: Option<DateTime<Utc>>,
+    /// 每次修改递增，到期任务只恢复自己设置的规则
+    generation: u64,
+}
+
+/// 运行时修改日志过滤规则（如临时打开 `berry_api_api::relay=trace`），无需修改RUST_LOG重启服务
+#[derive(Clone)]
+pub struct LogFilterControl {
+    handle: reload::Handle<EnvFilter, Registry>,
+    default: String,
+    state: Arc<Mutex<OverrideState>>,
+}
+
+impl LogFilterControl {
+    pub fn new(handle: reload::Handle<EnvFilter, Registry>, default: String) -> Self {
+        Self {
+            handle,
+            default,
+            state: Arc::new(Mutex::new(OverrideState::default())),
+        }
+    }
+
+    /// 当前生效的过滤规则
+    pub fn status(&self) -> LogFilterStatus {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        LogFilterStatus {
+            filter: self.handle.with_current(|filter| filter.to_string()).unwrap_or_default(),
+            default: self.default.clone(),
+            expires_at: state.expires_at,
+        }
+    }
+
+    /// 替换过滤规则，指定duration时到期后恢复默认规则
+    pub fn set(&self, directives: &str, duration: Option<Duration>) -> Result<LogFilterStatus, LogFilterError> {
+        let filter = EnvFilter::try_new(directives)
+            .map_err(|e| LogFilterError::Invalid(directives.to_string(), e.to_string()))?;
+        let generation = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            self.handle.reload(filter).map_err(|e| LogFilterError::Reload(e.to_string()))?;
+            state.generation += 1;
+            state.expires_at = duration
+                .and_then(|duration| chrono::Duration::from_std(duration).ok())
+                .map(|duration| Utc::now() + duration);
+            state.generation
+        };
+
+        if let Some(duration) = duration {
+            let control = self.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(duration).await;
+                control.restore(generation);
+            });
+        }
+        Ok(self.status())
+    }
+
+    /// 立即恢复默认规则
+    pub fn reset(&self) -> LogFilterStatus {
+        let generation = self.state.lock().unwrap_or_else(|e| e.into_inner()).generation;
+        self.restore(generation);
+        self.status()
+    }
+
+    /// 规则在此期间没有再次修改时恢复默认规则
+    fn restore(&self, generation: u64) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.generation != generation {
+            return;
+        }
+        let Ok(filter) = EnvFilter::try_new(&self.default) else {
+            return;
+        };
+        match self.handle.reload(filter) {
+            Ok(()) => {
+                state.generation += 1;
+                state.expires_at = None;
+                tracing::info!("Log filter restored to '{}'", self.default);
+            }
+            Err(e) => tracing::warn!("Failed to restore log filter: {}", e),
+        }
+    }
+}
+
+fn build_tracer_provider() -> anyhow::Result<SdkTracerProvider> {
+    // 导出地址、请求头、超时等均读取标准的OTEL_EXPORTER_OTLP_*环境变量
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()?;
+    let service_name =
+        std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "berry-api".to_string());
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build())
+}
+
+/// 关闭TracerProvider，发送缓冲中剩余的span
+pub async fn shutdown_tracing(provider: SdkTracerProvider) {
+    // shutdown会阻塞等待导出线程结束
+    let result = tokio::task::spawn_blocking(move || provider.shutdown()).await;
+    if let Ok(Err(e)) = result {
+        tracing::warn!("Failed to flush OTLP spans: {}", e);
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut reqwest::header::HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            reqwest::header::HeaderName::from_bytes(key.as_bytes()),
+            reqwest::header::HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+struct HeaderExtractor<'a>(&'a axum::http::HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// 将当前span的追踪上下文写入上游请求头（traceparent/tracestate）
+pub fn inject_trace_context(headers: &mut reqwest::header::HeaderMap) {
+    let context = tracing::Span::current().context();
+    TraceContextPropagator::new().inject_context(&context, &mut HeaderInjector(headers));
+}
+
+/// 请求ID请求头：客户端未提供时由网关生成，随响应返回并转发给上游和对等实例
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// 在请求ID的作用域内执行请求处理
+pub async fn with_request_id<F: std::future::Future>(request_id: String, future: F) -> F::Output {
+    REQUEST_ID.scope(request_id, future).await
+}
+
+/// 当前请求的ID，不在请求处理中时为空
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// 将当前请求ID写入上游请求头
+pub fn inject_request_id(headers: &mut reqwest::header::HeaderMap) {
+    if let Some(id) = current_request_id()
+        && let Ok(value) = reqwest::header::HeaderValue::from_str(&id)
+    {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+}
+
+/// 客户端请求携带traceparent时，将当前span挂到调用方的链路下
+pub fn continue_trace_from(headers: &axum::http::HeaderMap) {
+    if !headers.contains_key("traceparent") {
+        return;
+    }
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(headers));
+    let _ = tracing::Span::current().set_parent(parent);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+
+    fn control(default: &str) -> (LogFilterControl, impl tracing::Subscriber) {
+        let (filter, handle) = reload::Layer::new(EnvFilter::new(default));
+        let subscriber = tracing_subscriber::registry().with(filter);
+        (LogFilterControl::new(handle, EnvFilter::new(default).to_string()), subscriber)
+    }
+
+    #[tokio::test]
+    async fn test_log_filter_override_expires() {
+        // reload句柄只持有过滤器的弱引用，测试期间需要保留订阅者
+        let (control, _subscriber) = control("warn");
+        let status = control.set("berry_api_api::relay=trace,debug", Some(Duration::from_millis(100))).unwrap();
+        assert_eq!(status.filter, "berry_api_api::relay=trace,debug");
+        assert!(status.expires_at.is_some());
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let status = control.status();
+        assert_eq!((status.filter.as_str(), status.expires_at), ("warn", None));
+    }
+
+    #[tokio::test]
+    async fn test_log_filter_rejects_invalid_and_resets() {
+        let (control, _subscriber) = control("info");
+        assert!(matches!(control.set("relay=loud", None), Err(LogFilterError::Invalid(..))));
+
+        // 新规则覆盖尚未到期的临时规则，旧规则的到期任务不再生效
+        control.set("debug", Some(Duration::from_millis(50))).unwrap();
+        control.set("trace", None).unwrap();
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(control.status().filter, "trace");
+
+        assert_eq!(control.reset().filter, "info");
+    }
+
+    #[test]
+    fn test_trace_context_roundtrip() {
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let context = opentelemetry::Context::new().with_remote_span_context(span_context);
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        TraceContextPropagator::new().inject_context(&context, &mut HeaderInjector(&mut headers));
+        assert_eq!(
+            headers["traceparent"],
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+
+        let extracted = TraceContextPropagator::new().extract(&HeaderExtractor(&headers));
+        assert_eq!(
+            extracted.span().span_context().trace_id(),
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap()
+        );
+    }
+}
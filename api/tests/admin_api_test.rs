@@ -0,0 +1,751 @@
+use berry_api_api::app::{AppState, create_app};
+use berry_api_api::config::model::{AuditLogSettings, AuditSink, Config, Provider, Backend, Tenant};
+use berry_api_api::loadbalance::{LoadBalanceService, TokenUsage};
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum_test::TestServer;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+mod common;
+use common::{ConfigBuilder, admin, app_state, backend, mapping, provider, serve, user};
+
+/// 包含一个管理员用户和一个普通用户的应用状态
+fn create_test_state() -> AppState {
+    create_test_state_with(ConfigBuilder::new()
+        .provider("test-provider", Provider {
+            models: vec!["test-model".to_string()],
+            ..provider("https://api.test.com")
+        })
+        .model("test-model", mapping("test-model", vec![Backend {
+            model: "test-model".to_string(),
+            ..backend("test-provider", 1)
+        }]))
+        .user("admin", admin("Administrator", "admin-token"))
+        .user("user", user("User", "user-token"))
+        .build())
+}
+
+fn create_test_state_with(config: Config) -> AppState {
+    app_state(Arc::new(LoadBalanceService::new(config).unwrap()))
+}
+
+fn bearer(token: &str) -> (HeaderName, HeaderValue) {
+    (
+        axum::http::header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", token)).unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn test_admin_api_requires_admin_tag() {
+    let server = TestServer::new(create_app(create_test_state())).unwrap();
+
+    let (name, value) = bearer("user-token");
+    let response = server.get("/admin/backends").add_header(name, value).await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let (name, value) = bearer("invalid-token");
+    let response = server.get("/admin/backends").add_header(name, value).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_admin_log_filter() {
+    // 日志过滤器在初始化日志时创建，测试进程中只有这个测试初始化日志
+    let _ = berry_api_api::telemetry::init_tracing();
+    let server = TestServer::new(create_app(create_test_state())).unwrap();
+    let (name, value) = bearer("admin-token");
+
+    let (user_name, user_value) = bearer("user-token");
+    let response = server.get("/admin/log-filter").add_header(user_name, user_value).await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let initial: Value = server.get("/admin/log-filter").add_header(name.clone(), value.clone()).await.json();
+    assert_eq!(initial["filter"], initial["default"]);
+
+    let response = server
+        .put("/admin/log-filter")
+        .add_header(name.clone(), value.clone())
+        .json(&json!({ "filter": "berry_api_api::relay=loud" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let response = server
+        .put("/admin/log-filter")
+        .add_header(name.clone(), value.clone())
+        .json(&json!({ "filter": "info,berry_api_api::relay=trace", "duration_seconds": 600 }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let changed: Value = response.json();
+    assert_eq!(changed["filter"], "berry_api_api::relay=trace,info");
+    assert!(changed["expires_at"].is_string());
+
+    let reset: Value = server.delete("/admin/log-filter").add_header(name, value).await.json();
+    assert_eq!(reset["filter"], initial["default"]);
+    assert!(reset["expires_at"].is_null());
+}
+
+#[tokio::test]
+async fn test_admin_disable_and_enable_backend() {
+    let state = create_test_state();
+    let metrics = state.load_balancer.get_metrics();
+    let server = TestServer::new(create_app(state)).unwrap();
+    let (name, value) = bearer("admin-token");
+
+    let response = server
+        .post("/admin/backends/disable")
+        .add_header(name.clone(), value.clone())
+        .json(&json!({ "provider": "test-provider", "model": "test-model" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert!(metrics.is_backend_admin_disabled("test-provider", "test-model"));
+
+    let backends: Value = server
+        .get("/admin/backends")
+        .add_header(name.clone(), value.clone())
+        .await
+        .json();
+    assert_eq!(backends["backends"][0]["admin_disabled"], json!(true));
+
+    let response = server
+        .post("/admin/backends/enable")
+        .add_header(name.clone(), value.clone())
+        .json(&json!({ "provider": "test-provider", "model": "test-model" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert!(!metrics.is_backend_admin_disabled("test-provider", "test-model"));
+
+    let response = server
+        .post("/admin/backends/disable")
+        .add_header(name, value)
+        .json(&json!({ "provider": "test-provider", "model": "unknown" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_admin_route_preview() {
+    let mut config = ConfigBuilder::new()
+        .provider("test-provider", Provider {
+            models: vec!["test-model".to_string()],
+            ..provider("https://api.test.com")
+        })
+        .model("test-model", mapping("test-model", vec![Backend {
+            model: "test-model".to_string(),
+            ..backend("test-provider", 1)
+        }]))
+        .user("admin", admin("Administrator", "admin-token"))
+        .user("user", user("User", "user-token"))
+        .build();
+    config.providers.get_mut("test-provider").unwrap().models.push("large-model".to_string());
+    let mapping = config.models.get_mut("test-model").unwrap();
+    let mut large = mapping.backends[0].clone();
+    large.model = "large-model".to_string();
+    large.weight = 3.0;
+    mapping.backends.push(large);
+
+    let server = TestServer::new(create_app(create_test_state_with(config))).unwrap();
+    let (name, value) = bearer("admin-token");
+
+    let preview: Value = server
+        .get("/admin/route-preview?model=test-model&n=2000")
+        .add_header(name.clone(), value.clone())
+        .await
+        .json();
+    assert_eq!(preview["strategy"], json!("weighted_random"));
+    assert_eq!(preview["completed"], json!(2000));
+    let share = preview["backends"][1]["share"].as_f64().unwrap();
+    assert!((0.65..0.85).contains(&share), "unexpected share {}", share);
+    assert_eq!(preview["backends"][1]["effective_weight"], json!(3.0));
+
+    // 预演其他策略：轮询时两个后端平分流量
+    let preview: Value = server
+        .get("/admin/route-preview?model=test-model&n=10&strategy=round_robin")
+        .add_header(name.clone(), value.clone())
+        .await
+        .json();
+    assert_eq!(preview["backends"][0]["selected"], json!(5));
+    assert_eq!(preview["backends"][1]["selected"], json!(5));
+
+    let response = server
+        .get("/admin/route-preview?model=missing")
+        .add_header(name, value)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_admin_create_key() {
+    let state = create_test_state();
+    let server = TestServer::new(create_app(state.clone())).unwrap();
+    let (name, value) = bearer("admin-token");
+
+    let created: Value = server
+        .post("/admin/keys")
+        .add_header(name.clone(), value.clone())
+        .json(&json!({ "name": "ci-bot", "allowed_models": ["test-model"] }))
+        .await
+        .json();
+    let token = created["token"].as_str().unwrap();
+    let user = state.authenticate(token).await.unwrap();
+    assert_eq!(user.name, "ci-bot");
+    assert_eq!(user.allowed_models, vec!["test-model".to_string()]);
+
+    // 重名与未知模型都会被拒绝
+    let response = server
+        .post("/admin/keys")
+        .add_header(name.clone(), value.clone())
+        .json(&json!({ "name": "ci-bot" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let response = server
+        .post("/admin/keys")
+        .add_header(name, value)
+        .json(&json!({ "name": "other", "allowed_models": ["missing"] }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_admin_patch_config() {
+    let state = create_test_state();
+    let server = TestServer::new(create_app(state.clone())).unwrap();
+    let (name, value) = bearer("admin-token");
+
+    // 查看配置时隐藏密钥
+    let current: Value = server.get("/admin/config").add_header(name.clone(), value.clone()).await.json();
+    assert_eq!(current["providers"]["test-provider"]["api_key"], "********");
+    assert_eq!(current["users"]["admin"]["token"], "********");
+
+    let response = server
+        .patch("/admin/config")
+        .add_header(name.clone(), value.clone())
+        .json(&json!({
+            "providers": {
+                "second-provider": {
+                    "name": "Second Provider",
+                    "base_url": "https://api.second.com",
+                    "api_key": "second-key",
+                    "models": ["test-model"]
+                }
+            },
+            "models": {
+                "test-model": {
+                    "backends": { "test-provider:test-model": { "weight": 3.0 } }
+                }
+            }
+        }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let body: Value = response.json();
+    assert_eq!(body["providers"], 2);
+    assert_eq!(body["persisted"], false);
+
+    let config = state.load_balancer.get_config();
+    assert_eq!(config.providers["second-provider"].api_key, "second-key");
+    assert_eq!(config.models["test-model"].backends[0].weight, 3.0);
+
+    // 合并后无法通过校验的修改不会生效
+    let response = server
+        .patch("/admin/config")
+        .add_header(name.clone(), value.clone())
+        .json(&json!({
+            "models": {
+                "test-model": { "backends": [{ "provider": "missing", "model": "test-model" }] }
+            }
+        }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    assert_eq!(state.load_balancer.get_config().models["test-model"].backends[0].provider, "test-provider");
+
+    // 禁用模型映射
+    let response = server
+        .patch("/admin/config")
+        .add_header(name, value)
+        .json(&json!({ "models": { "test-model": { "enabled": false } } }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert!(!state.load_balancer.get_config().models["test-model"].enabled);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_config_patches_are_all_applied() {
+    let state = create_test_state();
+    let load_balancer = state.load_balancer.clone();
+    let url = serve(create_app(state)).await;
+
+    // 每个修改都基于前一个修改生效后的配置，不会被并发的修改覆盖
+    let patches = (0..16).map(|i| {
+        let user = format!("user-{}", i);
+        let request = reqwest::Client::new()
+            .patch(format!("{}/admin/config", url))
+            .bearer_auth("admin-token")
+            .json(&json!({ "users": { &user: { "name": &user, "token": format!("{}-token", user) } } }));
+        tokio::spawn(request.send())
+    });
+    for response in futures::future::join_all(patches).await {
+        assert_eq!(response.unwrap().unwrap().status(), reqwest::StatusCode::OK);
+    }
+
+    let config = load_balancer.source_config();
+    assert!((0..16).all(|i| config.users.contains_key(&format!("user-{}", i))));
+    assert_eq!(load_balancer.config_history()[0].version, 17);
+}
+
+#[tokio::test]
+async fn test_admin_config_history_and_rollback() {
+    let state = create_test_state();
+    let server = TestServer::new(create_app(state.clone())).unwrap();
+    let (name, value) = bearer("admin-token");
+
+    for weight in [2.0, 5.0] {
+        let response = server
+            .patch("/admin/config")
+            .add_header(name.clone(), value.clone())
+            .json(&json!({ "models": { "test-model": { "backends": { "test-provider:test-model": { "weight": weight } } } } }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+    }
+
+    let history: Value = server.get("/admin/config/history").add_header(name.clone(), value.clone()).await.json();
+    let versions = history["versions"].as_array().unwrap();
+    assert_eq!(versions.iter().map(|v| v["version"].as_u64().unwrap()).collect::<Vec<_>>(), vec![3, 2, 1]);
+    assert_eq!(versions[0]["source"], "patch");
+    assert_eq!(versions[0]["current"], true);
+    assert_eq!(versions[2]["source"], "startup");
+
+    let version: Value = server.get("/admin/config/history/2").add_header(name.clone(), value.clone()).await.json();
+    assert_eq!(version["config"]["models"]["test-model"]["backends"][0]["weight"], 2.0);
+    assert_eq!(version["config"]["providers"]["test-provider"]["api_key"], "********");
+
+    // 未指定版本时回滚到上一个版本
+    let response = server.post("/admin/config/rollback").add_header(name.clone(), value.clone()).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let body: Value = response.json();
+    assert_eq!(body["rolled_back_to"], 2);
+    assert_eq!(body["version"], 4);
+    assert_eq!(state.load_balancer.get_config().models["test-model"].backends[0].weight, 2.0);
+
+    let response = server
+        .post("/admin/config/rollback")
+        .add_header(name.clone(), value.clone())
+        .json(&json!({ "version": 1 }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(state.load_balancer.get_config().models["test-model"].backends[0].weight, 1.0);
+    assert_eq!(state.load_balancer.config_history()[0].source, "rollback:1");
+
+    let response = server
+        .post("/admin/config/rollback")
+        .add_header(name, value)
+        .json(&json!({ "version": 42 }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_dashboard_stats() {
+    let state = create_test_state();
+    let metrics = state.load_balancer.get_metrics();
+    let server = TestServer::new(create_app(state)).unwrap();
+
+    {
+        let _request = metrics.begin_request("test-provider:test-model");
+        let stats: Value = server.get("/dashboard/api/stats").await.json();
+        let backend = &stats["models"][0]["backends"][0];
+        assert_eq!(stats["models"][0]["id"], json!("test-model"));
+        assert_eq!(backend["in_flight"], json!(1));
+        assert_eq!(backend["total_requests"], json!(1));
+        assert_eq!(backend["effective_weight"], json!(1.0));
+    }
+
+    let stats: Value = server.get("/dashboard/api/stats").await.json();
+    assert_eq!(stats["models"][0]["backends"][0]["in_flight"], json!(0));
+
+    let page = server.get("/dashboard").await;
+    assert_eq!(page.status_code(), StatusCode::OK);
+    assert!(page.text().contains("/dashboard/api/stats"));
+}
+
+#[tokio::test]
+async fn test_budget_exceeded_returns_402() {
+    let server = TestServer::new(create_app(create_test_state())).unwrap();
+    let (name, value) = bearer("admin-token");
+
+    let created: Value = server
+        .post("/admin/keys")
+        .add_header(name, value)
+        .json(&json!({ "name": "no-budget", "budget": 0.0 }))
+        .await
+        .json();
+    assert_eq!(created["budget"], json!(0.0));
+
+    let (name, value) = bearer(created["token"].as_str().unwrap());
+    let response = server
+        .post("/v1/chat/completions")
+        .add_header(name, value)
+        .json(&json!({ "model": "test-model", "messages": [{ "role": "user", "content": "hi" }] }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::PAYMENT_REQUIRED);
+    let body: Value = response.json();
+    assert_eq!(body["error"]["type"], json!("insufficient_quota"));
+}
+
+#[tokio::test]
+async fn test_audit_log_records_rejected_request() {
+    let path = std::env::temp_dir().join(format!("berry-audit-test-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut config = ConfigBuilder::new()
+        .provider("test-provider", Provider {
+            models: vec!["test-model".to_string()],
+            ..provider("https://api.test.com")
+        })
+        .model("test-model", mapping("test-model", vec![Backend {
+            model: "test-model".to_string(),
+            ..backend("test-provider", 1)
+        }]))
+        .user("admin", admin("Administrator", "admin-token"))
+        .user("user", user("User", "user-token"))
+        .build();
+    config.users.get_mut("user").unwrap().budget = Some(0.0);
+    config.settings.audit_log = Some(AuditLogSettings {
+        sink: AuditSink::File {
+            path: path.to_string_lossy().to_string(),
+            max_size_mb: 10,
+            max_files: 1,
+        },
+        redact_content: true,
+    });
+    let server = TestServer::new(create_app(create_test_state_with(config))).unwrap();
+
+    let (name, value) = bearer("user-token");
+    let response = server
+        .post("/v1/chat/completions")
+        .add_header(name, value)
+        .json(&json!({ "model": "test-model", "messages": [{ "role": "user", "content": "secret" }] }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::PAYMENT_REQUIRED);
+
+    // 审计记录由后台任务异步写出
+    let mut content = String::new();
+    for _ in 0..50 {
+        content = std::fs::read_to_string(&path).unwrap_or_default();
+        if !content.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let record: Value = serde_json::from_str(content.trim()).unwrap();
+    assert_eq!(record["user"], json!("User"));
+    assert_eq!(record["model"], json!("test-model"));
+    assert_eq!(record["status"], json!(402));
+    assert_eq!(record["backend"], Value::Null);
+    assert!(record["error"].as_str().unwrap().contains("Budget exceeded"));
+    assert_eq!(record["messages"][0]["content"], json!("[redacted]"));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_admin_revoke_key_persists_hashes() {
+    let path = std::env::temp_dir().join(format!("berry-keys-test-{}.toml", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut config = ConfigBuilder::new()
+        .provider("test-provider", Provider {
+            models: vec!["test-model".to_string()],
+            ..provider("https://api.test.com")
+        })
+        .model("test-model", mapping("test-model", vec![Backend {
+            model: "test-model".to_string(),
+            ..backend("test-provider", 1)
+        }]))
+        .user("admin", admin("Administrator", "admin-token"))
+        .user("user", user("User", "user-token"))
+        .build();
+    config.settings.keys_file = Some(path.to_string_lossy().to_string());
+    let state = create_test_state_with(config);
+    let server = TestServer::new(create_app(state.clone())).unwrap();
+    let (name, value) = bearer("admin-token");
+
+    let created: Value = server
+        .post("/admin/keys")
+        .add_header(name.clone(), value.clone())
+        .json(&json!({ "name": "ci-bot" }))
+        .await
+        .json();
+    assert_eq!(created["persisted"], json!(true));
+    let token = created["token"].as_str().unwrap();
+    assert!(state.authenticate(token).await.is_some());
+
+    // 密钥文件只保存哈希
+    let keys = berry_api_api::auth::keys::load_keys_file(path.to_str().unwrap()).unwrap();
+    assert!(keys["ci-bot"].token.is_empty());
+    assert!(keys["ci-bot"].matches_token(token));
+
+    // 配置文件中的用户不能通过管理接口吊销
+    let response = server.delete("/admin/keys/user").add_header(name.clone(), value.clone()).await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    let response = server.delete("/admin/keys/ci-bot").add_header(name, value).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert!(state.authenticate(token).await.is_none());
+    assert!(berry_api_api::auth::keys::load_keys_file(path.to_str().unwrap()).unwrap().is_empty());
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_user_model_alias() {
+    let mut config = ConfigBuilder::new()
+        .provider("test-provider", Provider {
+            models: vec!["test-model".to_string()],
+            ..provider("https://api.test.com")
+        })
+        .model("test-model", mapping("test-model", vec![Backend {
+            model: "test-model".to_string(),
+            ..backend("test-provider", 1)
+        }]))
+        .user("admin", admin("Administrator", "admin-token"))
+        .user("user", user("User", "user-token"))
+        .build();
+    let user = config.users.get_mut("user").unwrap();
+    user.allowed_models = vec!["test-model".to_string()];
+    user.model_aliases.insert("gpt-4".to_string(), "test-model".to_string());
+    user.budget = Some(0.0);
+    config.validate().unwrap();
+    let server = TestServer::new(create_app(create_test_state_with(config))).unwrap();
+    let (name, value) = bearer("user-token");
+
+    let models: Value = server.get("/v1/models").add_header(name.clone(), value.clone()).await.json();
+    let ids: Vec<&str> = models["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, vec!["test-model", "gpt-4"]);
+
+    // 别名解析为test-model后通过权限检查，随后因预算为0被拒绝
+    let response = server
+        .post("/v1/chat/completions")
+        .add_header(name.clone(), value.clone())
+        .json(&json!({ "model": "gpt-4", "messages": [{ "role": "user", "content": "hi" }] }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::PAYMENT_REQUIRED);
+}
+
+#[tokio::test]
+async fn test_models_endpoint_metadata() {
+    let mut config = ConfigBuilder::new()
+        .provider("test-provider", Provider {
+            models: vec!["test-model".to_string()],
+            ..provider("https://api.test.com")
+        })
+        .model("test-model", mapping("test-model", vec![Backend {
+            model: "test-model".to_string(),
+            ..backend("test-provider", 1)
+        }]))
+        .user("admin", admin("Administrator", "admin-token"))
+        .user("user", user("User", "user-token"))
+        .build();
+    let mut disabled = config.models["test-model"].clone();
+    disabled.name = "disabled-model".to_string();
+    disabled.backends[0].enabled = false;
+    config.models.insert("disabled-model".to_string(), disabled);
+    let user = config.users.get_mut("user").unwrap();
+    user.model_aliases.insert("gpt-4".to_string(), "test-model".to_string());
+    config.validate().unwrap();
+    let server = TestServer::new(create_app(create_test_state_with(config))).unwrap();
+    let (name, value) = bearer("user-token");
+
+    // 后端全部禁用的模型不出现在列表中
+    let models: Value = server.get("/v1/models").add_header(name.clone(), value.clone()).await.json();
+    let data = models["data"].as_array().unwrap();
+    assert_eq!(data.len(), 2);
+    assert!(data.iter().all(|m| m["id"] != "disabled-model"));
+
+    let model: Value = server.get("/v1/models/gpt-4").add_header(name.clone(), value.clone()).await.json();
+    assert_eq!(model["id"], "gpt-4");
+    assert_eq!(model["owned_by"], "test-model");
+    assert_eq!(model["berry"]["strategy"], "weighted_random");
+    assert_eq!(model["berry"]["total_backends"], json!(1));
+    assert_eq!(model["berry"]["healthy_backends"], json!(1));
+
+    let response = server.get("/v1/models/disabled-model").add_header(name, value).await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    let body: Value = response.json();
+    assert_eq!(body["error"]["code"], "model_not_found");
+}
+
+#[tokio::test]
+async fn test_admin_usage_report() {
+    let state = create_test_state();
+    let config = state.load_balancer.get_config();
+    let backend = &config.models["test-model"].backends[0];
+    let tracker = state.load_balancer.get_cost_tracker();
+    let usage = TokenUsage { prompt_tokens: 100, completion_tokens: 20 };
+    tracker.record("alice", "test-model", backend, usage);
+    tracker.record("alice", "test-model", backend, usage);
+    tracker.record("bob", "test-model", backend, usage);
+    let server = TestServer::new(create_app(state)).unwrap();
+
+    let (name, value) = bearer("admin-token");
+    let report: Value = server
+        .get("/admin/usage?bucket=total&group_by=user,provider")
+        .add_header(name, value)
+        .await
+        .json();
+    let rows = report["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0]["user"], "alice");
+    assert_eq!(rows[0]["provider"], "test-provider");
+    assert_eq!(rows[0]["requests"], 2);
+    assert!(rows[0]["model"].is_null());
+    assert_eq!(report["total"]["prompt_tokens"], 300);
+
+    // 结束时间早于记录时间时没有数据
+    let (name, value) = bearer("admin-token");
+    let report: Value = server
+        .get("/admin/usage?end=2000-01-01&user=alice")
+        .add_header(name, value)
+        .await
+        .json();
+    assert!(report["rows"].as_array().unwrap().is_empty());
+
+    let (name, value) = bearer("admin-token");
+    let csv = server
+        .get("/admin/usage?group_by=user&format=csv")
+        .add_header(name, value)
+        .await
+        .text();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "bucket_start,user,model,provider,requests,prompt_tokens,completion_tokens,cost");
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].contains(",alice,,,2,200,40,"));
+
+    let (name, value) = bearer("admin-token");
+    let response = server.get("/admin/usage?group_by=team").add_header(name, value).await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+/// 两个租户：acme使用共享的全局provider，beta使用自己的provider，各有一个租户管理员
+fn create_tenant_config() -> Config {
+    let mut config = ConfigBuilder::new()
+        .provider("test-provider", Provider {
+            models: vec!["test-model".to_string()],
+            ..provider("https://api.test.com")
+        })
+        .model("test-model", mapping("test-model", vec![Backend {
+            model: "test-model".to_string(),
+            ..backend("test-provider", 1)
+        }]))
+        .user("admin", admin("Administrator", "admin-token"))
+        .user("user", user("User", "user-token"))
+        .build();
+    let acme: Tenant = serde_json::from_value(json!({
+        "shared_providers": ["test-provider"],
+        "models": {
+            "chat": {
+                "name": "chat",
+                "backends": [{ "provider": "test-provider", "model": "test-model" }]
+            }
+        },
+        "users": { "ops": { "name": "ops", "token": "acme-admin", "tags": ["admin"] } }
+    }))
+    .unwrap();
+    let beta: Tenant = serde_json::from_value(json!({
+        "providers": {
+            "secret": {
+                "name": "Beta Provider",
+                "base_url": "https://api.beta.com",
+                "api_key": "beta-key",
+                "models": ["test-model"]
+            }
+        },
+        "models": {
+            "chat": {
+                "name": "chat",
+                "backends": [{ "provider": "secret", "model": "test-model" }]
+            }
+        },
+        "users": { "ops": { "name": "ops", "token": "beta-admin", "tags": ["admin"] } }
+    }))
+    .unwrap();
+    config.tenants = HashMap::from([("acme".to_string(), acme), ("beta".to_string(), beta)]);
+    config
+}
+
+#[tokio::test]
+async fn test_tenant_admin_delegation() {
+    let state = create_test_state_with(create_tenant_config());
+    let server = TestServer::new(create_app(state.clone())).unwrap();
+    let (name, acme_admin) = bearer("acme-admin");
+    let (_, admin) = bearer("admin-token");
+
+    // 租户管理员不能访问平台管理接口和其他租户
+    for path in ["/admin/config", "/admin/status", "/admin/tenants/beta/config"] {
+        let response = server.get(path).add_header(name.clone(), acme_admin.clone()).await;
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN, "{}", path);
+    }
+
+    let tenant: Value = server
+        .get("/admin/tenants/acme/config")
+        .add_header(name.clone(), acme_admin.clone())
+        .await
+        .json();
+    assert_eq!(tenant["models"]["chat"]["backends"][0]["provider"], "test-provider");
+    assert_eq!(tenant["users"]["ops"]["token"], "********");
+    assert!(tenant.get("tenants").is_none());
+
+    // 共享provider由平台管理员分配
+    let response = server
+        .patch("/admin/tenants/acme/config")
+        .add_header(name.clone(), acme_admin.clone())
+        .json(&json!({ "shared_providers": [] }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = server
+        .patch("/admin/tenants/acme/config")
+        .add_header(name.clone(), acme_admin.clone())
+        .json(&json!({ "models": { "chat": { "backends": { "test-provider:test-model": { "weight": 2.0 } } } } }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(state.load_balancer.get_config().models["acme/chat"].backends[0].weight, 2.0);
+
+    // 租户密钥的名称和模型都在租户内解析
+    let created: Value = server
+        .post("/admin/tenants/acme/keys")
+        .add_header(name.clone(), acme_admin.clone())
+        .json(&json!({ "name": "bot", "allowed_models": ["chat"] }))
+        .await
+        .json();
+    let user = state.authenticate(created["token"].as_str().unwrap()).await.unwrap();
+    assert_eq!(user.name, "acme/bot");
+    assert_eq!(user.tenant.as_deref(), Some("acme"));
+    assert_eq!(user.allowed_models, vec!["acme/chat".to_string()]);
+
+    let response = server
+        .post("/admin/tenants/acme/keys")
+        .add_header(name.clone(), acme_admin.clone())
+        .json(&json!({ "name": "other", "allowed_models": ["test-model"] }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    // 平台管理员可以管理所有租户，但只能按所属租户吊销密钥
+    let beta: Value = server
+        .get("/admin/tenants/beta/config")
+        .add_header(name.clone(), admin.clone())
+        .await
+        .json();
+    assert_eq!(beta["providers"]["secret"]["api_key"], "********");
+    let response = server.get("/admin/tenants/missing/config").add_header(name.clone(), admin.clone()).await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    let response = server.delete("/admin/tenants/beta/keys/bot").add_header(name.clone(), admin).await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    let response = server.delete("/admin/tenants/acme/keys/bot").add_header(name, acme_admin).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert!(state.authenticate(created["token"].as_str().unwrap()).await.is_none());
+}
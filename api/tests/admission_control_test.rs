@@ -0,0 +1,241 @@
+use berry_api_api::config::model::{FairQueueSettings, LoadBalanceStrategy, ModelMapping, PrioritySchedulingSettings, RequestPriority};
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, serve, start_gateway, user};
+
+/// 启动一个延迟指定时间后返回非流式回复的本地上游，回复内容为上游名称
+async fn spawn_upstream(name: &'static str, delay: Duration) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || async move {
+            tokio::time::sleep(delay).await;
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": name},
+                    "finish_reason": "stop"
+                }]
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+/// 占用一个在途名额，等待慢上游开始处理后再发送后续请求
+async fn occupy(url: &str) -> tokio::task::JoinHandle<String> {
+    let url = url.to_string();
+    let first = tokio::spawn(async move { chat(&url, "user-token", chat_body("gpt-4", "first")).await.text().await.unwrap() });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    first
+}
+
+#[tokio::test]
+async fn test_global_in_flight_limit_sheds_load() {
+    let slow_url = spawn_upstream("local", Duration::from_millis(500)).await;
+    let mut config = ConfigBuilder::new()
+        .provider("local", provider(&slow_url))
+        .provider("cloud", provider(&slow_url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    config.settings.max_in_flight_requests = Some(1);
+    let url = start_gateway(config).await.0;
+
+    let first = occupy(&url).await;
+    let started = std::time::Instant::now();
+    let rejected = chat(&url, "user-token", chat_body("gpt-4", "second")).await;
+    assert_eq!(rejected.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    // 超出上限时立即拒绝，不等待在途请求完成
+    assert!(started.elapsed() < Duration::from_millis(300));
+    let body: Value = rejected.json().await.unwrap();
+    assert!(body["error"]["details"].as_str().unwrap().contains("at capacity"));
+
+    // 在途请求完成后名额归还
+    assert!(first.await.unwrap().contains("local"));
+    assert!(chat(&url, "user-token", chat_body("gpt-4", "third")).await.status().is_success());
+}
+
+#[tokio::test]
+async fn test_model_in_flight_limit_sheds_load() {
+    let slow_url = spawn_upstream("local", Duration::from_millis(500)).await;
+    let mut config = ConfigBuilder::new()
+        .provider("local", provider(&slow_url))
+        .provider("cloud", provider(&slow_url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    config.models.get_mut("gpt-4").unwrap().max_in_flight_requests = Some(1);
+    let url = start_gateway(config).await.0;
+
+    let first = occupy(&url).await;
+    assert_eq!(chat(&url, "user-token", chat_body("gpt-4", "second")).await.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    assert!(first.await.unwrap().contains("local"));
+}
+
+#[tokio::test]
+async fn test_batch_traffic_preempted_under_pressure() {
+    let slow_url = spawn_upstream("local", Duration::from_millis(500)).await;
+    let mut config = ConfigBuilder::new()
+        .provider("local", provider(&slow_url))
+        .provider("cloud", provider(&slow_url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    config.settings.max_in_flight_requests = Some(2);
+    config.settings.priority_scheduling = Some(PrioritySchedulingSettings {
+        normal_share_percent: 50.0,
+        batch_share_percent: 50.0,
+    });
+    for (name, priority) in [("batch", RequestPriority::Batch), ("high", RequestPriority::High)] {
+        let mut user = config.users["user"].clone();
+        user.name = name.to_string();
+        user.token = format!("{}-token", name);
+        user.priority = priority;
+        config.users.insert(name.to_string(), user);
+    }
+    let url = start_gateway(config).await.0;
+
+    let batch_url = url.clone();
+    let first = tokio::spawn(async move { chat(&batch_url, "batch-token", chat_body("gpt-4", "first")).await.text().await.unwrap() });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // 批量请求超出份额时以429拒绝，普通请求的份额同样已用完
+    let preempted = chat(&url, "batch-token", chat_body("gpt-4", "second")).await;
+    assert_eq!(preempted.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    let body: Value = preempted.json().await.unwrap();
+    assert!(body["error"]["details"].as_str().unwrap().contains("preempted"));
+    assert_eq!(chat(&url, "user-token", chat_body("gpt-4", "normal")).await.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+
+    // 保留的名额留给高优先级请求
+    assert!(chat(&url, "high-token", chat_body("gpt-4", "high")).await.status().is_success());
+    assert!(first.await.unwrap().contains("local"));
+}
+
+#[tokio::test]
+async fn test_fair_queue_lets_light_user_cut_ahead() {
+    let slow_url = spawn_upstream("local", Duration::from_millis(200)).await;
+    let mut config = ConfigBuilder::new()
+        .provider("local", provider(&slow_url))
+        .provider("cloud", provider(&slow_url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let model = config.models.get_mut("gpt-4").unwrap();
+    model.max_in_flight_requests = Some(1);
+    model.fair_queue = Some(FairQueueSettings {
+        quantum_tokens: 1,
+        ..Default::default()
+    });
+    let mut light = config.users["user"].clone();
+    light.name = "light".to_string();
+    light.token = "light-token".to_string();
+    config.users.insert("light".to_string(), light);
+    let url = start_gateway(config).await.0;
+
+    let (done_tx, mut done_rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut requests = Vec::new();
+    for (token, label) in [("user-token", "heavy-1"), ("user-token", "heavy-2"), ("user-token", "heavy-3"), ("light-token", "light")] {
+        let done_tx = done_tx.clone();
+        let url = url.clone();
+        requests.push(tokio::spawn(async move {
+            let response = chat(&url, token, chat_body("gpt-4", label)).await;
+            assert!(response.status().is_success());
+            assert!(response.text().await.unwrap().contains("local"));
+            done_tx.send(label).unwrap();
+        }));
+        tokio::time::sleep(Duration::from_millis(30)).await;
+    }
+
+    // 排队期间可以在指标中看到等待的用户
+    let metrics: Value = reqwest::get(format!("{}/metrics", url)).await.unwrap().json().await.unwrap();
+    assert_eq!(metrics["fair_queue"]["gpt-4"]["in_flight"], 1);
+    assert_eq!(metrics["fair_queue"]["gpt-4"]["queued"], 3);
+    assert_eq!(metrics["fair_queue"]["gpt-4"]["waiting_users"], 2);
+
+    // 轻量用户轮流获得名额，不必等待重度用户排在前面的全部请求
+    let mut order = Vec::new();
+    for _ in 0..4 {
+        order.push(done_rx.recv().await.unwrap());
+    }
+    assert_eq!(order[0], "heavy-1");
+    let position = |label| order.iter().position(|done| *done == label).unwrap();
+    assert!(position("light") < position("heavy-3"), "unexpected order: {:?}", order);
+    for request in requests {
+        request.await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_fair_queue_times_out() {
+    let slow_url = spawn_upstream("local", Duration::from_millis(500)).await;
+    let mut config = ConfigBuilder::new()
+        .provider("local", provider(&slow_url))
+        .provider("cloud", provider(&slow_url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let model = config.models.get_mut("gpt-4").unwrap();
+    model.max_in_flight_requests = Some(1);
+    model.fair_queue = Some(FairQueueSettings {
+        queue_timeout_ms: 100,
+        ..Default::default()
+    });
+    let url = start_gateway(config).await.0;
+
+    let first = occupy(&url).await;
+    let response = chat(&url, "user-token", chat_body("gpt-4", "second")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["error"]["details"].as_str().unwrap().contains("in queue"));
+    assert!(first.await.unwrap().contains("local"));
+}
+
+#[test]
+fn test_in_flight_limit_validation() {
+    let mut config = ConfigBuilder::new()
+        .provider("local", provider("http://127.0.0.1:1/v1"))
+        .provider("cloud", provider("http://127.0.0.1:2/v1"))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    config.settings.max_in_flight_requests = Some(0);
+    assert!(config.validate().is_err());
+
+    config.settings.max_in_flight_requests = None;
+    config.models.get_mut("gpt-4").unwrap().max_in_flight_requests = Some(0);
+    assert!(config.validate().is_err());
+
+    config.models.get_mut("gpt-4").unwrap().max_in_flight_requests = None;
+    config.settings.priority_scheduling = Some(PrioritySchedulingSettings {
+        normal_share_percent: 50.0,
+        batch_share_percent: 80.0,
+    });
+    assert!(config.validate().is_err());
+
+    config.settings.priority_scheduling = None;
+    config.models.get_mut("gpt-4").unwrap().fair_queue = Some(FairQueueSettings::default());
+    assert!(config.validate().is_err());
+}
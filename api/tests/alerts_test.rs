@@ -0,0 +1,148 @@
+use berry_api_api::config::model::{AlertSettings, AlertSeverity, GlobalSettings, LoadBalanceStrategy, ModelMapping, PagerDutySettings, Provider, SmtpSettings, SmtpTls};
+use berry_api_api::loadbalance::LoadBalanceService;
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+mod common;
+use common::{ConfigBuilder, backend, mapping, provider};
+
+/// 启动模拟的PagerDuty Events API
+async fn spawn_pagerduty() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = axum::Router::new().route(
+        "/v2/enqueue",
+        post(move |axum::Json(body): axum::Json<Value>| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(body);
+                (axum::http::StatusCode::ACCEPTED, axum::Json(json!({ "status": "success" })))
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{}/v2/enqueue", addr), rx)
+}
+
+/// 启动最简单的SMTP服务器，每封邮件的内容通过通道返回
+async fn spawn_smtp() -> (u16, mpsc::UnboundedReceiver<String>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let command = line.to_ascii_uppercase();
+                    if command.starts_with("DATA") {
+                        writer.write_all(b"354 go ahead\r\n").await.unwrap();
+                        let mut data = String::new();
+                        while let Ok(Some(line)) = lines.next_line().await {
+                            if line == "." {
+                                break;
+                            }
+                            data.push_str(&line);
+                            data.push('\n');
+                        }
+                        let _ = tx.send(data);
+                        writer.write_all(b"250 queued\r\n").await.unwrap();
+                    } else if command.starts_with("QUIT") {
+                        writer.write_all(b"221 bye\r\n").await.unwrap();
+                        return;
+                    } else {
+                        writer.write_all(b"250 ok\r\n").await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+    (port, rx)
+}
+
+async fn next<T>(receiver: &mut mpsc::UnboundedReceiver<T>) -> T {
+    tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("alert not sent")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_alerts_are_routed_by_severity() {
+    let (events_url, mut pagerduty) = spawn_pagerduty().await;
+    let (smtp_port, mut emails) = spawn_smtp().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            ..provider("http://127.0.0.1:9/v1")
+        })
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::RoundRobin,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .settings(GlobalSettings {
+            alerts: Some(AlertSettings {
+                flapping_threshold: 1,
+                flapping_window_seconds: 60,
+                pagerduty: Some(PagerDutySettings {
+                    routing_key: "routing-key".to_string(),
+                    events_url: events_url.to_string(),
+                    severities: vec![AlertSeverity::Critical],
+                    timeout_seconds: 2,
+                }),
+                smtp: Some(SmtpSettings {
+                    host: "127.0.0.1".to_string(),
+                    port: smtp_port,
+                    tls: SmtpTls::None,
+                    username: None,
+                    password: None,
+                    from: "Berry API <berry@example.com>".to_string(),
+                    to: vec!["oncall@example.com".to_string()],
+                    severities: vec![AlertSeverity::Warning, AlertSeverity::Critical],
+                    timeout_seconds: 2,
+                }),
+            }),
+            ..GlobalSettings::default()
+        })
+        .build();
+    config.validate().unwrap();
+    let service = LoadBalanceService::new(config).unwrap();
+    service.start().await.unwrap();
+    // 等待初始健康检查完成
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    service.get_model_health();
+    service.get_metrics().record_failure("upstream:gpt-4");
+    service.get_model_health();
+
+    // 抖动告警（warning）只发邮件，模型不可用（critical）同时发送到PagerDuty和邮件
+    let mails = [next(&mut emails).await, next(&mut emails).await];
+    assert!(mails.iter().any(|mail| mail.contains("[Berry API][CRITICAL] All backends of model gpt-4 are down")));
+    assert!(mails.iter().any(|mail| mail.contains("[Berry API][WARNING] Backend upstream:gpt-4 became unhealthy")));
+
+    let trigger = next(&mut pagerduty).await;
+    assert_eq!(trigger["event_action"], "trigger");
+    assert_eq!(trigger["routing_key"], "routing-key");
+    assert_eq!(trigger["payload"]["severity"], "critical");
+
+    // 恢复后按相同的dedup_key解除PagerDuty事件
+    service.get_metrics().record_success("upstream:gpt-4");
+    service.get_model_health();
+    let resolve = next(&mut pagerduty).await;
+    assert_eq!(resolve["event_action"], "resolve");
+    assert_eq!(resolve["dedup_key"], trigger["dedup_key"]);
+    assert!(next(&mut emails).await.contains("[Berry API][RESOLVED] Model gpt-4 has healthy backends again"));
+    assert!(pagerduty.try_recv().is_err());
+
+    service.stop().await;
+}
@@ -0,0 +1,243 @@
+use berry_api_api::config::model::{ModelMapping, UserToken};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+mod common;
+use common::{ConfigBuilder, backend, mapping, provider, serve, start_gateway, user};
+
+/// 启动一个OpenAI格式的本地上游：带tools的请求返回工具调用，stream请求返回SSE流，并记录最后一次请求体
+async fn spawn_upstream() -> (String, Arc<Mutex<Value>>) {
+    let last_request = Arc::new(Mutex::new(Value::Null));
+    let recorder = last_request.clone();
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move |axum::Json(body): axum::Json<Value>| {
+            let recorder = recorder.clone();
+            async move {
+                *recorder.lock().unwrap() = body.clone();
+                if body["stream"] == true {
+                    let chunks = [
+                        json!({"id": "chatcmpl-s", "choices": [{"index": 0, "delta": {"role": "assistant", "content": "Hel"}}]}),
+                        json!({"id": "chatcmpl-s", "choices": [{"index": 0, "delta": {"content": "lo"}, "finish_reason": "stop"}]}),
+                        json!({"id": "chatcmpl-s", "choices": [], "usage": {"prompt_tokens": 4, "completion_tokens": 2, "total_tokens": 6}}),
+                    ];
+                    let mut sse: String = chunks.iter().map(|chunk| format!("data: {}\n\n", chunk)).collect();
+                    sse.push_str("data: [DONE]\n\n");
+                    return ([("content-type", "text/event-stream")], sse).into_response();
+                }
+                let message = if body.get("tools").is_some() {
+                    json!({"role": "assistant", "content": null, "tool_calls": [{
+                        "id": "call_1", "type": "function",
+                        "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+                    }]})
+                } else {
+                    json!({"role": "assistant", "content": "Hello there"})
+                };
+                let finish_reason = if body.get("tools").is_some() { "tool_calls" } else { "stop" };
+                axum::Json(json!({
+                    "id": "chatcmpl-test",
+                    "object": "chat.completion",
+                    "choices": [{"index": 0, "message": message, "finish_reason": finish_reason}],
+                    "usage": {"prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15}
+                }))
+                .into_response()
+            }
+        }),
+    );
+    (format!("{}/v1", serve(app).await), last_request)
+}
+
+async fn send(url: &str, api_key: &str, body: Value) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/v1/messages", url))
+        .header("x-api-key", api_key)
+        .header("anthropic-version", "2023-06-01")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_messages_text_response() {
+    let (base_url, last_request) = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", provider(&base_url))
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", UserToken {
+            model_aliases: HashMap::from([("claude-sonnet".to_string(), "gpt-4".to_string())]),
+            ..user("User", "user-token")
+        })
+        .build();
+    let url = start_gateway(config).await.0;
+
+    let response = send(&url, "user-token", json!({
+        "model": "claude-sonnet",
+        "max_tokens": 64,
+        "system": "Be brief.",
+        "messages": [{"role": "user", "content": "Hi"}]
+    }))
+    .await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["type"], "message");
+    assert_eq!(body["model"], "claude-sonnet");
+    assert_eq!(body["content"], json!([{"type": "text", "text": "Hello there"}]));
+    assert_eq!(body["stop_reason"], "end_turn");
+    assert_eq!(body["usage"], json!({"input_tokens": 12, "output_tokens": 3}));
+
+    let upstream_request = last_request.lock().unwrap().clone();
+    assert_eq!(upstream_request["model"], "gpt-4");
+    assert_eq!(upstream_request["max_tokens"], 64);
+    assert_eq!(upstream_request["messages"][0], json!({"role": "system", "content": "Be brief."}));
+}
+
+#[tokio::test]
+async fn test_messages_tool_use_response() {
+    let (base_url, last_request) = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", provider(&base_url))
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", UserToken {
+            model_aliases: HashMap::from([("claude-sonnet".to_string(), "gpt-4".to_string())]),
+            ..user("User", "user-token")
+        })
+        .build();
+    let url = start_gateway(config).await.0;
+
+    let body: Value = send(&url, "user-token", json!({
+        "model": "claude-sonnet",
+        "max_tokens": 64,
+        "tools": [{"name": "get_weather", "input_schema": {"type": "object", "properties": {"city": {"type": "string"}}}}],
+        "tool_choice": {"type": "auto"},
+        "messages": [{"role": "user", "content": [{"type": "text", "text": "Weather in Paris?"}]}]
+    }))
+    .await
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(body["stop_reason"], "tool_use");
+    assert_eq!(body["content"][0]["type"], "tool_use");
+    assert_eq!(body["content"][0]["name"], "get_weather");
+    assert_eq!(body["content"][0]["input"], json!({"city": "Paris"}));
+
+    let upstream_request = last_request.lock().unwrap().clone();
+    assert_eq!(upstream_request["tools"][0]["function"]["name"], "get_weather");
+    assert_eq!(upstream_request["tool_choice"], "auto");
+}
+
+#[tokio::test]
+async fn test_messages_streaming_events() {
+    let (base_url, _) = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", provider(&base_url))
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", UserToken {
+            model_aliases: HashMap::from([("claude-sonnet".to_string(), "gpt-4".to_string())]),
+            ..user("User", "user-token")
+        })
+        .build();
+    let url = start_gateway(config).await.0;
+
+    let response = send(&url, "user-token", json!({
+        "model": "claude-sonnet",
+        "max_tokens": 64,
+        "stream": true,
+        "messages": [{"role": "user", "content": "Hi"}]
+    }))
+    .await;
+    assert_eq!(response.status(), 200);
+    let text = response.text().await.unwrap();
+
+    let events: Vec<&str> = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("event: "))
+        .filter(|event| *event != "ping")
+        .collect();
+    assert_eq!(
+        events,
+        [
+            "message_start",
+            "content_block_start",
+            "content_block_delta",
+            "content_block_delta",
+            "content_block_stop",
+            "message_delta",
+            "message_stop"
+        ]
+    );
+    let data: Vec<Value> = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect();
+    let message_start = data.iter().find(|event| event["type"] == "message_start").unwrap();
+    assert_eq!(message_start["message"]["model"], "claude-sonnet");
+    let text: String = data
+        .iter()
+        .filter_map(|event| event["delta"]["text"].as_str())
+        .collect();
+    assert_eq!(text, "Hello");
+    let message_delta = data.iter().find(|event| event["type"] == "message_delta").unwrap();
+    assert_eq!(message_delta["delta"]["stop_reason"], "end_turn");
+    assert_eq!(message_delta["usage"]["output_tokens"], 2);
+}
+
+#[tokio::test]
+async fn test_messages_errors_use_anthropic_format() {
+    let (base_url, _) = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", provider(&base_url))
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", UserToken {
+            model_aliases: HashMap::from([("claude-sonnet".to_string(), "gpt-4".to_string())]),
+            ..user("User", "user-token")
+        })
+        .build();
+    let url = start_gateway(config).await.0;
+    let request = json!({
+        "model": "claude-sonnet",
+        "max_tokens": 64,
+        "messages": [{"role": "user", "content": "Hi"}]
+    });
+
+    let response = send(&url, "wrong-token", request.clone()).await;
+    assert_eq!(response.status(), 401);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["type"], "error");
+    assert_eq!(body["error"]["type"], "authentication_error");
+
+    let mut missing_max_tokens = request.clone();
+    missing_max_tokens.as_object_mut().unwrap().remove("max_tokens");
+    let response = send(&url, "user-token", missing_max_tokens).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+
+    let count: Value = reqwest::Client::new()
+        .post(format!("{}/v1/messages/count_tokens", url))
+        .header("x-api-key", "user-token")
+        .json(&json!({"model": "claude-sonnet", "messages": [{"role": "user", "content": "Hi"}]}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(count["input_tokens"].as_u64().unwrap() > 0);
+}
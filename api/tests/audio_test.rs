@@ -0,0 +1,132 @@
+use berry_api_api::app::create_app;
+use berry_api_api::config::model::{Backend, BillingMode, LoadBalanceStrategy, ModelMapping};
+use axum::extract::Multipart;
+use axum::http::{HeaderValue, StatusCode};
+use axum::routing::post;
+use axum_test::TestServer;
+use axum_test::multipart::{MultipartForm, Part};
+use serde_json::{Value, json};
+
+mod common;
+use common::{ConfigBuilder, create_state, provider_with_models, serve, user};
+
+/// 启动一个提供语音转文字和语音合成接口的本地上游
+async fn spawn_upstream() -> String {
+    let app = axum::Router::new()
+        .route(
+            "/v1/audio/transcriptions",
+            post(|mut multipart: Multipart| async move {
+                let mut model = String::new();
+                let mut file = String::new();
+                while let Some(field) = multipart.next_field().await.unwrap() {
+                    let name = field.name().unwrap_or_default().to_string();
+                    let file_name = field.file_name().map(str::to_string);
+                    let data = field.text().await.unwrap();
+                    match name.as_str() {
+                        "model" => model = data,
+                        "file" => file = format!("{}:{}", file_name.unwrap_or_default(), data),
+                        _ => {}
+                    }
+                }
+                axum::Json(json!({ "text": format!("{}|{}", model, file) }))
+            }),
+        )
+        .route(
+            "/v1/audio/speech",
+            post(|| async { ([("content-type", "audio/mpeg")], vec![0xffu8, 0xfb, 0x90, 0x00]) }),
+        );
+    format!("{}/v1", serve(app).await)
+}
+
+fn mapping(name: &str, model: &str) -> ModelMapping {
+    let backend = |provider: &str| Backend {
+        provider: provider.to_string(),
+        model: model.to_string(),
+        priority: 1,
+        billing_mode: BillingMode::PerRequest,
+        ..Default::default()
+    };
+    ModelMapping {
+        name: name.to_string(),
+        backends: vec![backend("broken"), backend("working")],
+        strategy: LoadBalanceStrategy::RoundRobin,
+        semantic_cache: false,
+        ..Default::default()
+    }
+}
+
+async fn create_server(upstream: &str) -> TestServer {
+    let config = ConfigBuilder::new()
+        // 端口1上没有服务，连接会立即被拒绝
+        .provider("broken", provider_with_models("http://127.0.0.1:1/v1", &["whisper-1", "tts-1"]))
+        .provider("working", provider_with_models(upstream, &["whisper-1", "tts-1"]))
+        .model("whisper", mapping("whisper", "whisper-1"))
+        .model("tts", mapping("tts", "tts-1"))
+        .user("user", user("User", "user-token"))
+        .build();
+    let state = create_state(config).await;
+    TestServer::new(create_app(state)).unwrap()
+}
+
+fn auth() -> HeaderValue {
+    HeaderValue::from_static("Bearer user-token")
+}
+
+fn transcription_form() -> MultipartForm {
+    MultipartForm::new()
+        .add_text("model", "whisper")
+        .add_part(
+            "file",
+            Part::bytes(b"RIFF".to_vec())
+                .file_name("speech.wav")
+                .mime_type("audio/wav"),
+        )
+}
+
+#[tokio::test]
+async fn test_transcription_form_forwarded_with_retry() {
+    let upstream = spawn_upstream().await;
+    let server = create_server(&upstream).await;
+
+    // 轮询会选到无法连接的后端，重试时需要重新构建表单
+    for _ in 0..2 {
+        let response = server
+            .post("/v1/audio/transcriptions")
+            .add_header(axum::http::header::AUTHORIZATION, auth())
+            .multipart(transcription_form())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: Value = response.json();
+        assert_eq!(body["text"], "whisper-1|speech.wav:RIFF");
+    }
+}
+
+#[tokio::test]
+async fn test_transcription_requires_file() {
+    let upstream = spawn_upstream().await;
+    let server = create_server(&upstream).await;
+
+    let response = server
+        .post("/v1/audio/transcriptions")
+        .add_header(axum::http::header::AUTHORIZATION, auth())
+        .multipart(MultipartForm::new().add_text("model", "whisper"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_speech_returns_audio() {
+    let upstream = spawn_upstream().await;
+    let server = create_server(&upstream).await;
+
+    for _ in 0..2 {
+        let response = server
+            .post("/v1/audio/speech")
+            .add_header(axum::http::header::AUTHORIZATION, auth())
+            .json(&json!({ "model": "tts", "input": "hello", "voice": "alloy" }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(response.header("content-type"), "audio/mpeg");
+        assert_eq!(response.as_bytes().as_ref(), &[0xff, 0xfb, 0x90, 0x00]);
+    }
+}
@@ -13,10 +13,9 @@ fn create_auto_recovery_test_config() -> Config {
         base_url: "https://httpbin.org".to_string(),
         api_key: "test-api-key".to_string(),
         models: vec!["test-model".to_string()],
-        headers: HashMap::new(),
-        enabled: true,
         timeout_seconds: 10,
         max_retries: 2,
+        ..Default::default()
     });
 
     providers.insert("backup-provider".to_string(), Provider {
@@ -24,10 +23,9 @@ fn create_auto_recovery_test_config() -> Config {
         base_url: "https://httpbin.org".to_string(),
         api_key: "backup-api-key".to_string(),
         models: vec!["backup-model".to_string()],
-        headers: HashMap::new(),
-        enabled: true,
         timeout_seconds: 10,
         max_retries: 2,
+        ..Default::default()
     });
 
     let mut models = HashMap::new();
@@ -39,20 +37,18 @@ fn create_auto_recovery_test_config() -> Config {
                 model: "test-model".to_string(),
                 weight: 0.7,
                 priority: 1,
-                enabled: true,
-                tags: vec![],
+                ..Default::default()
             },
             Backend {
                 provider: "backup-provider".to_string(),
                 model: "backup-model".to_string(),
                 weight: 0.3,
                 priority: 2,
-                enabled: true,
-                tags: vec![],
+                ..Default::default()
             },
         ],
         strategy: LoadBalanceStrategy::WeightedFailover,
-        enabled: true,
+        ..Default::default()
     });
 
     Config {
@@ -68,7 +64,51 @@ fn create_auto_recovery_test_config() -> Config {
             recovery_check_interval_seconds: 60,
             max_internal_retries: 2,
             health_check_timeout_seconds: 10,
+            failover_memory_ttl_seconds: 300,
+            model_degraded_ratio: 0.75,
+            model_down_ratio: 0.25,
+            model_health_hysteresis: 0.1,
+            health_webhook_urls: vec![],
+            readiness_required_models: Vec::new(),
+            config_hot_reload: true,
+            peer_max_hops: 1,
+            response_cache_enabled: false,
+            response_cache_ttl_seconds: 300,
+            response_cache_max_entries: 1000,
+            semantic_cache: None,
+            latency_ewma_alpha: 0.3,
+            audit_log: None,
+            keys_file: None,
+            max_embedding_batch_size: 2048,
+            max_request_body_mb: 32,
+            slow_start: Default::default(),
+            outlier_detection: None,
+            max_in_flight_requests: None,
+            priority_scheduling: None,
+            shared_state: None,
+            secrets: None,
+            config_history: Default::default(),
+            health_scoring: None,
+            region_routing: None,
+            content_filters: HashMap::new(),
+            moderation: None,
+            wasm_plugins: None,
+            notifications: None,
+            alerts: None,
+            slo: None,
+            request_log: None,
+            jwt: None,
+            network_acl: None,
+            cors: None,
+            grpc: None,
+            job_queue: None,
+            files: None,
+            stream_heartbeat: None,
+            stream_resumption: None,
+            request_capture: None,
+            fault_injection: false,
         },
+        ..Default::default()
     }
 }
 
@@ -279,7 +319,29 @@ async fn test_backend_selection_after_auto_recovery() {
     ).await;
     
     println!("✅ test-provider recovered");
-    
+
+    // 恢复后先进入慢启动，以降低的权重重新接收流量
+    assert_eq!(metrics.get_recovery_stage("test-provider", "test-model"), Some(1));
+    let mut slow_start_count = 0;
+    for _ in 0..100 {
+        if let Ok(backend) = service.select_backend("demo-model").await
+            && backend.backend.provider == "test-provider"
+        {
+            slow_start_count += 1;
+        }
+    }
+    assert!(slow_start_count > 0, "recovered backend should receive traffic during slow start");
+
+    // 继续成功直到走完慢启动
+    for _ in 0..4 {
+        service.record_request_result(
+            "test-provider",
+            "test-model",
+            RequestResult::Success { latency: Duration::from_millis(100) }
+        ).await;
+    }
+    assert_eq!(metrics.get_recovery_stage("test-provider", "test-model"), None);
+
     // 再次选择，现在应该根据权重分配
     let mut selections_after = HashMap::new();
     for _ in 0..100 {
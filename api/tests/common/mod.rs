@@ -0,0 +1,193 @@
+//! 集成测试共用的配置构造和服务启动函数，测试文件通过 `mod common;` 引入
+#![allow(dead_code)]
+
+use berry_api_api::app::{AppState, create_app};
+use berry_api_api::config::model::{
+    Backend, Config, GlobalSettings, ModelMapping, ProbeType, Provider, ProviderHealthCheck, UserToken,
+};
+use berry_api_api::loadbalance::LoadBalanceService;
+use berry_api_api::relay::handler::LoadBalancedHandler;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// 只做被动健康判断，避免启动时的主动探测影响测试
+pub fn passive_health_check() -> ProviderHealthCheck {
+    ProviderHealthCheck {
+        probe: ProbeType::None,
+        interval_seconds: None,
+        timeout_seconds: None,
+    }
+}
+
+/// 提供gpt-4的provider，以base_url为名称，超时5秒，不重试，不做主动健康检查
+pub fn provider(base_url: &str) -> Provider {
+    Provider {
+        name: base_url.to_string(),
+        base_url: base_url.to_string(),
+        api_key: "test-api-key".to_string(),
+        models: vec!["gpt-4".to_string()],
+        timeout_seconds: 5,
+        max_retries: 0,
+        health_check: Some(passive_health_check()),
+        ..Default::default()
+    }
+}
+
+/// 提供指定模型的provider，其他设置与provider相同
+pub fn provider_with_models(base_url: &str, models: &[&str]) -> Provider {
+    Provider {
+        models: models.iter().map(|model| model.to_string()).collect(),
+        ..provider(base_url)
+    }
+}
+
+/// 指向provider的gpt-4后端
+pub fn backend(provider: &str, priority: u8) -> Backend {
+    Backend {
+        provider: provider.to_string(),
+        model: "gpt-4".to_string(),
+        priority,
+        ..Default::default()
+    }
+}
+
+/// 使用给定后端的模型映射，其他设置使用默认值
+pub fn mapping(name: &str, backends: Vec<Backend>) -> ModelMapping {
+    ModelMapping {
+        name: name.to_string(),
+        backends,
+        ..Default::default()
+    }
+}
+
+/// 可以访问所有模型的用户
+pub fn user(name: &str, token: &str) -> UserToken {
+    UserToken {
+        name: name.to_string(),
+        token: token.to_string(),
+        ..Default::default()
+    }
+}
+
+/// 带admin标签的用户
+pub fn admin(name: &str, token: &str) -> UserToken {
+    UserToken {
+        tags: vec!["admin".to_string()],
+        ..user(name, token)
+    }
+}
+
+/// 逐项组装测试配置，未设置的部分使用默认值
+#[derive(Default)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 以key登记provider，后端通过key引用它
+    pub fn provider(mut self, key: &str, provider: Provider) -> Self {
+        self.config.providers.insert(key.to_string(), provider);
+        self
+    }
+
+    /// 以key登记模型映射，用量统计按key汇总
+    pub fn model(mut self, key: &str, mapping: ModelMapping) -> Self {
+        self.config.models.insert(key.to_string(), mapping);
+        self
+    }
+
+    pub fn user(mut self, key: &str, user: UserToken) -> Self {
+        self.config.users.insert(key.to_string(), user);
+        self
+    }
+
+    pub fn settings(mut self, settings: GlobalSettings) -> Self {
+        self.config.settings = settings;
+        self
+    }
+
+    pub fn build(self) -> Config {
+        self.config
+    }
+}
+
+/// 只有一条用户消息的chat completions请求体
+pub fn chat_body(model: &str, content: &str) -> Value {
+    json!({
+        "model": model,
+        "messages": [{"role": "user", "content": content}]
+    })
+}
+
+/// 以token身份构造发往网关的chat completions请求，调用方可以继续添加请求头
+pub fn chat_request(base_url: &str, token: &str, body: &Value) -> reqwest::RequestBuilder {
+    reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", base_url))
+        .bearer_auth(token)
+        .json(body)
+}
+
+/// 以token身份发送chat completions请求
+pub async fn chat(base_url: &str, token: &str, body: Value) -> reqwest::Response {
+    chat_request(base_url, token, &body).send().await.unwrap()
+}
+
+/// 断言请求成功并取出非流式回复的内容，回复前可能带有保活空白
+pub async fn reply_content(response: reqwest::Response) -> String {
+    assert!(response.status().is_success());
+    let body: Value = serde_json::from_str(response.text().await.unwrap().trim()).unwrap();
+    body["choices"][0]["message"]["content"].as_str().unwrap_or_default().to_string()
+}
+
+/// 在随机端口上运行服务，返回 http://地址
+pub async fn serve(app: axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+/// 启动负载均衡服务
+pub async fn start_load_balancer(config: Config) -> Arc<LoadBalanceService> {
+    let load_balancer = Arc::new(LoadBalanceService::new(config).unwrap());
+    load_balancer.start().await.unwrap();
+    load_balancer
+}
+
+/// 基于负载均衡服务的应用状态，没有运行时密钥
+pub fn app_state(load_balancer: Arc<LoadBalanceService>) -> AppState {
+    let handler = Arc::new(LoadBalancedHandler::new(load_balancer.clone()));
+    AppState {
+        load_balancer,
+        handler,
+        runtime_users: Arc::new(std::sync::RwLock::new(HashMap::new())),
+    }
+}
+
+/// 校验配置并启动负载均衡服务，返回应用状态
+pub async fn create_state(config: Config) -> AppState {
+    config.validate().unwrap();
+    app_state(start_load_balancer(config).await)
+}
+
+/// 校验配置并启动网关，返回 http://地址 和负载均衡服务；请求的来源地址可以通过ConnectInfo获取
+pub async fn start_gateway(config: Config) -> (String, Arc<LoadBalanceService>) {
+    let state = create_state(config).await;
+    let load_balancer = state.load_balancer.clone();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, create_app(state).into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .unwrap();
+    });
+    (format!("http://{}", addr), load_balancer)
+}
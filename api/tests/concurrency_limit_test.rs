@@ -0,0 +1,89 @@
+use berry_api_api::config::model::{ConcurrencyLimit, LoadBalanceStrategy, ModelMapping};
+use axum::routing::post;
+use serde_json::json;
+use std::time::Duration;
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, chat_request, mapping, provider, reply_content, serve, start_gateway, user};
+
+/// 启动一个延迟指定时间后返回非流式回复的本地上游，回复内容为上游名称
+async fn spawn_upstream(name: &'static str, delay: Duration) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || async move {
+            tokio::time::sleep(delay).await;
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": name},
+                    "finish_reason": "stop"
+                }]
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+#[tokio::test]
+async fn test_request_overflows_when_backend_at_capacity() {
+    let local_url = spawn_upstream("local", Duration::from_millis(500)).await;
+    let cloud_url = spawn_upstream("cloud", Duration::ZERO).await;
+    // 本地后端优先，但同时只处理一个请求且不排队
+    let mut local = backend("local", 1);
+    local.concurrency = Some(ConcurrencyLimit {
+        max_concurrent_requests: 1,
+        max_queue: 0,
+        queue_timeout_ms: 1000,
+    });
+    let config = ConfigBuilder::new()
+        .provider("local", provider(&local_url))
+        .provider("cloud", provider(&cloud_url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![local, backend("cloud", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let (url, load_balancer) = start_gateway(config).await;
+
+    let first = tokio::spawn(chat_request(&url, "user-token", &chat_body("gpt-4", "first")).send());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // 本地后端的唯一名额被占用，第二个请求转给云端后端
+    assert_eq!(reply_content(chat(&url, "user-token", chat_body("gpt-4", "second")).await).await, "cloud");
+    assert_eq!(reply_content(first.await.unwrap().unwrap()).await, "local");
+
+    // 名额不足不计为后端失败，请求结束后名额归还
+    let metrics = load_balancer.get_metrics();
+    assert!(metrics.is_healthy("local", "gpt-4"));
+    assert_eq!(metrics.get_failure_count("local", "gpt-4"), 0);
+    assert_eq!(reply_content(chat(&url, "user-token", chat_body("gpt-4", "third")).await).await, "local");
+}
+
+#[test]
+fn test_concurrency_limit_validation() {
+    let mut config = ConfigBuilder::new()
+        .provider("local", provider("http://127.0.0.1:1/v1"))
+        .provider("cloud", provider("http://127.0.0.1:2/v1"))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    config.models.get_mut("gpt-4").unwrap().backends[0].concurrency = Some(ConcurrencyLimit {
+        max_concurrent_requests: 0,
+        max_queue: 0,
+        queue_timeout_ms: 1000,
+    });
+    assert!(config.validate().is_err());
+
+    config.models.get_mut("gpt-4").unwrap().backends[0].concurrency = None;
+    config.providers.get_mut("cloud").unwrap().concurrency = Some(ConcurrencyLimit {
+        max_concurrent_requests: 0,
+        max_queue: 0,
+        queue_timeout_ms: 1000,
+    });
+    assert!(config.validate().is_err());
+}
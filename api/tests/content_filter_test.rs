@@ -0,0 +1,156 @@
+use berry_api_api::config::model::{AuditLogSettings, AuditSink, BuiltinFilter, ContentFilterSet, GlobalSettings, LoadBalanceStrategy, ModelMapping, Provider, UserToken};
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, serve, start_gateway, user};
+
+/// 启动一个回显最后一条消息内容的本地上游，返回地址和收到的消息内容
+async fn spawn_echo_upstream() -> (String, Arc<Mutex<Vec<String>>>) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = received.clone();
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move |axum::Json(body): axum::Json<Value>| {
+            let log = log.clone();
+            async move {
+                let content = body["messages"][0]["content"].as_str().unwrap_or_default().to_string();
+                log.lock().unwrap().push(content.clone());
+                axum::Json(json!({
+                    "id": "chatcmpl-test",
+                    "object": "chat.completion",
+                    "choices": [{
+                        "index": 0,
+                        "message": {"role": "assistant", "content": format!("echo: {}", content)},
+                        "finish_reason": "stop"
+                    }]
+                }))
+            }
+        }),
+    );
+    (format!("{}/v1", serve(app).await), received)
+}
+
+/// 用户的PII规则在转发前脱敏，模型的代号规则只在日志和响应中脱敏；审计日志写入audit_path
+fn filter_settings(audit_path: &str) -> GlobalSettings {
+    let content_filters = HashMap::from([
+        ("pii".to_string(), ContentFilterSet {
+            builtin: vec![BuiltinFilter::Email],
+            redact_upstream: true,
+            replacement: "[EMAIL]".to_string(),
+            ..Default::default()
+        }),
+        ("codenames".to_string(), ContentFilterSet {
+            keywords: vec!["falcon".to_string()],
+            redact_responses: true,
+            replacement: "[CODENAME]".to_string(),
+            ..Default::default()
+        }),
+    ]);
+
+    GlobalSettings {
+        audit_log: Some(AuditLogSettings {
+            sink: AuditSink::File {
+                path: audit_path.to_string(),
+                max_size_mb: 10,
+                max_files: 1,
+            },
+            redact_content: false,
+        }),
+        content_filters,
+        ..GlobalSettings::default()
+    }
+}
+
+#[tokio::test]
+async fn test_filters_redact_upstream_responses_and_audit_log() {
+    let path = std::env::temp_dir().join(format!("berry-content-filter-test-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let (base_url, received) = spawn_echo_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            ..provider(&base_url)
+        })
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::RoundRobin,
+            content_filters: vec!["codenames".to_string()],
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", UserToken {
+            content_filters: vec!["pii".to_string()],
+            ..user("User", "user-token")
+        })
+        .settings(filter_settings(&path.to_string_lossy()))
+        .build();
+    let url = start_gateway(config).await.0;
+
+    let response: Value = chat(&url, "user-token", chat_body("gpt-4", "mail bob@example.com about Falcon"))
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(received.lock().unwrap().as_slice(), ["mail [EMAIL] about Falcon"]);
+    assert_eq!(
+        response["choices"][0]["message"]["content"],
+        "echo: mail [EMAIL] about [CODENAME]"
+    );
+
+    // 审计记录由后台任务异步写出
+    let mut content = String::new();
+    for _ in 0..50 {
+        content = std::fs::read_to_string(&path).unwrap_or_default();
+        if !content.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let record: Value = serde_json::from_str(content.trim()).unwrap();
+    assert_eq!(record["messages"][0]["content"], "mail [EMAIL] about [CODENAME]");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_unknown_filter_reference_is_rejected() {
+    let mut config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            ..provider("http://127.0.0.1:1/v1")
+        })
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::RoundRobin,
+            content_filters: vec!["codenames".to_string()],
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", UserToken {
+            content_filters: vec!["pii".to_string()],
+            ..user("User", "user-token")
+        })
+        .settings(filter_settings("/dev/null"))
+        .build();
+    config.users.get_mut("user").unwrap().content_filters = vec!["missing".to_string()];
+    let error = config.validate().unwrap_err();
+    assert!(error.to_string().contains("unknown content filter 'missing'"));
+
+    let mut config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            ..provider("http://127.0.0.1:1/v1")
+        })
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::RoundRobin,
+            content_filters: vec!["codenames".to_string()],
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", UserToken {
+            content_filters: vec!["pii".to_string()],
+            ..user("User", "user-token")
+        })
+        .settings(filter_settings("/dev/null"))
+        .build();
+    config.settings.content_filters.get_mut("pii").unwrap().patterns = vec!["(".to_string()];
+    assert!(config.validate().unwrap_err().to_string().contains("invalid pattern"));
+}
@@ -0,0 +1,142 @@
+use berry_api_api::app::create_app;
+use berry_api_api::config::model::{Backend, CorsSettings, GlobalSettings, ModelMapping, Provider};
+use berry_api_api::loadbalance::LoadBalanceService;
+use axum::http::{HeaderName, HeaderValue, Method, StatusCode, header};
+use axum_test::TestServer;
+use std::sync::Arc;
+
+mod common;
+use common::{ConfigBuilder, admin, app_state, backend, mapping, provider};
+
+fn cors_settings() -> CorsSettings {
+    CorsSettings {
+        allowed_origins: vec!["https://app.example.com".to_string(), "https://*.tools.example.com".to_string()],
+        allowed_headers: vec!["authorization".to_string(), "content-type".to_string()],
+        exposed_headers: vec!["x-request-id".to_string()],
+        max_age_seconds: 300,
+        allow_credentials: false,
+    }
+}
+
+/// 只有一个管理员用户并启用CORS的服务
+fn create_test_server() -> TestServer {
+    let config = ConfigBuilder::new()
+        .provider("test-provider", Provider {
+            models: vec!["test-model".to_string()],
+            ..provider("https://api.test.com")
+        })
+        .model("test-model", ModelMapping {
+            semantic_cache: false,
+            ..mapping("test-model", vec![Backend {
+                model: "test-model".to_string(),
+                ..backend("test-provider", 1)
+            }])
+        })
+        .user("admin", admin("admin", "admin-token"))
+        .settings(GlobalSettings {
+            cors: Some(cors_settings()),
+            ..GlobalSettings::default()
+        })
+        .build();
+    TestServer::new(create_app(app_state(Arc::new(LoadBalanceService::new(config).unwrap()))))
+    .unwrap()
+}
+
+fn origin(value: &'static str) -> (HeaderName, HeaderValue) {
+    (header::ORIGIN, HeaderValue::from_static(value))
+}
+
+#[tokio::test]
+async fn test_preflight_for_allowed_origins() {
+    let server = create_test_server();
+
+    for allowed in ["https://app.example.com", "https://chat.tools.example.com"] {
+        let (name, value) = origin(allowed);
+        let response = server
+            .method(Method::OPTIONS, "/v1/chat/completions")
+            .add_header(name, value)
+            .add_header(header::ACCESS_CONTROL_REQUEST_METHOD, HeaderValue::from_static("POST"))
+            .add_header(header::ACCESS_CONTROL_REQUEST_HEADERS, HeaderValue::from_static("authorization, content-type"))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+        assert_eq!(response.header(header::ACCESS_CONTROL_ALLOW_ORIGIN), allowed);
+        assert_eq!(response.header(header::ACCESS_CONTROL_ALLOW_HEADERS), "authorization, content-type");
+        assert_eq!(response.header(header::ACCESS_CONTROL_MAX_AGE), "300");
+        assert!(response.maybe_header(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+    }
+
+    // 不允许的来源和通配符不匹配的域名不返回CORS响应头
+    for denied in ["https://evil.example.com", "https://tools.example.com", "http://app.example.com"] {
+        let (name, value) = origin(denied);
+        let response = server
+            .method(Method::OPTIONS, "/v1/chat/completions")
+            .add_header(name, value)
+            .add_header(header::ACCESS_CONTROL_REQUEST_METHOD, HeaderValue::from_static("POST"))
+            .await;
+        assert!(response.maybe_header(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none(), "{}", denied);
+    }
+}
+
+#[tokio::test]
+async fn test_cors_headers_on_public_routes_only() {
+    let server = create_test_server();
+
+    let (name, value) = origin("https://app.example.com");
+    let response = server
+        .get("/v1/models")
+        .add_header(name, value)
+        .add_header(header::AUTHORIZATION, HeaderValue::from_static("Bearer admin-token"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.header(header::ACCESS_CONTROL_ALLOW_ORIGIN), "https://app.example.com");
+    assert_eq!(response.header(header::ACCESS_CONTROL_EXPOSE_HEADERS), "x-request-id");
+    assert_eq!(response.header(header::VARY), "origin");
+
+    // 认证失败的响应同样带有CORS响应头，浏览器应用可以读取错误信息
+    let (name, value) = origin("https://app.example.com");
+    let response = server
+        .get("/v1/models")
+        .add_header(name, value)
+        .add_header(header::AUTHORIZATION, HeaderValue::from_static("Bearer invalid-token"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.header(header::ACCESS_CONTROL_ALLOW_ORIGIN), "https://app.example.com");
+
+    let (name, value) = origin("https://app.example.com");
+    let response = server
+        .get("/admin/status")
+        .add_header(name, value)
+        .add_header(header::AUTHORIZATION, HeaderValue::from_static("Bearer admin-token"))
+        .await;
+    assert!(response.maybe_header(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+}
+
+#[tokio::test]
+async fn test_cors_validation() {
+    let mut config = ConfigBuilder::new()
+        .provider("test-provider", Provider {
+            models: vec!["test-model".to_string()],
+            ..provider("https://api.test.com")
+        })
+        .model("test-model", ModelMapping {
+            semantic_cache: false,
+            ..mapping("test-model", vec![Backend {
+                model: "test-model".to_string(),
+                ..backend("test-provider", 1)
+            }])
+        })
+        .user("admin", admin("admin", "admin-token"))
+        .build();
+    config.settings.cors = Some(CorsSettings {
+        allowed_origins: vec!["*".to_string()],
+        allow_credentials: true,
+        ..cors_settings()
+    });
+    assert!(config.validate().is_err());
+
+    config.settings.cors = Some(CorsSettings {
+        allowed_origins: vec!["https://app.example.com/".to_string()],
+        ..cors_settings()
+    });
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,153 @@
+use berry_api_api::app::create_app;
+use berry_api_api::config::model::{Backend, BackendPricing, GlobalSettings, LoadBalanceStrategy, ModelMapping};
+use axum::http::{HeaderValue, StatusCode};
+use axum::routing::post;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+mod common;
+use common::{ConfigBuilder, create_state, provider_with_models, serve, user};
+
+/// 启动一个返回固定向量和usage的本地embedding上游
+async fn spawn_upstream() -> String {
+    let app = axum::Router::new().route(
+        "/v1/embeddings",
+        post(|axum::Json(body): axum::Json<Value>| async move {
+            let count = body["input"].as_array().map(|items| items.len()).unwrap_or(1);
+            let data: Vec<Value> = (0..count)
+                .map(|index| json!({"object": "embedding", "index": index, "embedding": [0.1, 0.2]}))
+                .collect();
+            axum::Json(json!({
+                "object": "list",
+                "model": body["model"],
+                "data": data,
+                "usage": {"prompt_tokens": 1000, "total_tokens": 1000}
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+fn backend(provider: &str, pricing: Option<BackendPricing>) -> Backend {
+    Backend {
+        provider: provider.to_string(),
+        model: "text-embedding-3-small".to_string(),
+        priority: 1,
+        pricing,
+        ..Default::default()
+    }
+}
+
+/// 轮询无法连接和可用的两个后端，只有可用后端计费
+fn embedding_mapping() -> ModelMapping {
+    ModelMapping {
+        name: "text-embedding".to_string(),
+        backends: vec![
+            backend("broken", None),
+            backend(
+                "working",
+                Some(BackendPricing {
+                    prompt_per_1k: 0.02,
+                    completion_per_1k: 0.0,
+                }),
+            ),
+        ],
+        strategy: LoadBalanceStrategy::RoundRobin,
+        ..Default::default()
+    }
+}
+
+async fn embed(server: &TestServer, input: Value) -> axum_test::TestResponse {
+    server
+        .post("/v1/embeddings")
+        .add_header(
+            axum::http::header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer user-token"),
+        )
+        .json(&json!({ "model": "text-embedding", "input": input }))
+        .await
+}
+
+#[tokio::test]
+async fn test_embeddings_retry_and_usage_accounting() {
+    let upstream = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        // 端口1上没有服务，连接会立即被拒绝
+        .provider("broken", provider_with_models("http://127.0.0.1:1/v1", &["text-embedding-3-small"]))
+        .provider("working", provider_with_models(&upstream, &["text-embedding-3-small"]))
+        .model("embedding", embedding_mapping())
+        .user("user", user("User", "user-token"))
+        .settings(GlobalSettings {
+            max_embedding_batch_size: 4,
+            ..GlobalSettings::default()
+        })
+        .build();
+    let state = create_state(config).await;
+    let load_balancer = state.load_balancer.clone();
+    let server = TestServer::new(create_app(state)).unwrap();
+
+    // 轮询会先后选到无法连接的后端，失败后在重试中转到可用后端
+    for _ in 0..2 {
+        let response = embed(&server, json!(["hello", "world"])).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: Value = response.json();
+        assert_eq!(body["data"].as_array().unwrap().len(), 2);
+        assert_eq!(body["model"], "text-embedding-3-small");
+    }
+
+    let report = load_balancer.get_cost_tracker().report();
+    let user = &report.users["User"];
+    assert_eq!(user.requests, 2);
+    assert_eq!(user.prompt_tokens, 2000);
+    assert!((user.cost - 0.04).abs() < 1e-9);
+    assert_eq!(report.models["embedding"].requests, 2);
+}
+
+#[tokio::test]
+async fn test_embeddings_batch_limit() {
+    let upstream = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        // 端口1上没有服务，连接会立即被拒绝
+        .provider("broken", provider_with_models("http://127.0.0.1:1/v1", &["text-embedding-3-small"]))
+        .provider("working", provider_with_models(&upstream, &["text-embedding-3-small"]))
+        .model("embedding", embedding_mapping())
+        .user("user", user("User", "user-token"))
+        .settings(GlobalSettings {
+            max_embedding_batch_size: 4,
+            ..GlobalSettings::default()
+        })
+        .build();
+    let server = TestServer::new(create_app(create_state(config).await)).unwrap();
+
+    let response = embed(&server, json!(["a", "b", "c", "d", "e"])).await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    // token数组表示单条输入
+    let response = embed(&server, json!([1, 2, 3, 4, 5, 6])).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_embeddings_requires_model_access() {
+    let upstream = spawn_upstream().await;
+    let mut config = ConfigBuilder::new()
+        // 端口1上没有服务，连接会立即被拒绝
+        .provider("broken", provider_with_models("http://127.0.0.1:1/v1", &["text-embedding-3-small"]))
+        .provider("working", provider_with_models(&upstream, &["text-embedding-3-small"]))
+        .model("embedding", embedding_mapping())
+        .user("user", user("User", "user-token"))
+        .settings(GlobalSettings {
+            max_embedding_batch_size: 4,
+            ..GlobalSettings::default()
+        })
+        .build();
+    config.models.insert("other".to_string(), ModelMapping {
+        name: "other".to_string(),
+        ..config.models["embedding"].clone()
+    });
+    config.users.get_mut("user").unwrap().allowed_models = vec!["other".to_string()];
+    let server = TestServer::new(create_app(create_state(config).await)).unwrap();
+
+    let response = embed(&server, json!("hello")).await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,73 @@
+use berry_api_api::config::model::Provider;
+use axum::routing::post;
+use serde_json::{Value, json};
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, serve, start_gateway, user};
+
+/// 启动一个总是返回429的本地上游
+async fn spawn_rate_limited_upstream() -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(|| async {
+            (
+                axum::http::StatusCode::TOO_MANY_REQUESTS,
+                axum::Json(json!({"error": {"message": "slow down"}})),
+            )
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+#[tokio::test]
+async fn test_client_errors_use_openai_error_format() {
+    let upstream = spawn_rate_limited_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "upstream".to_string(),
+            ..provider(&upstream)
+        })
+        .model("gpt-4", mapping("gpt-4", vec![backend("upstream", 1)]))
+        .user("user", user("User", "user-token"))
+        .build();
+    let url = start_gateway(config).await.0;
+
+    let response = chat(&url, "wrong-token", chat_body("gpt-4", "hi")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["type"], "authentication_error");
+    assert_eq!(body["error"]["code"], "invalid_api_key");
+    assert!(body["error"]["param"].is_null());
+
+    let response = chat(&url, "user-token", chat_body("no-such-model", "hi")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    assert_eq!(body["error"]["code"], "model_not_found");
+    assert_eq!(body["error"]["message"], "Model 'no-such-model' not found");
+}
+
+#[tokio::test]
+async fn test_upstream_rate_limit_is_surfaced_as_429() {
+    let upstream = spawn_rate_limited_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "upstream".to_string(),
+            ..provider(&upstream)
+        })
+        .model("gpt-4", mapping("gpt-4", vec![backend("upstream", 1)]))
+        .user("user", user("User", "user-token"))
+        .build();
+    let url = start_gateway(config).await.0;
+
+    let response = chat(&url, "user-token", json!({"model": "gpt-4", "stream": true, "messages": [{"role": "user", "content": "hi"}]})).await;
+    assert_eq!(response.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["type"], "rate_limit_error");
+    assert_eq!(body["error"]["code"], "upstream_rate_limited");
+
+    // 非流式请求的响应头已提前发出，错误码在响应体中返回
+    let response = chat(&url, "user-token", chat_body("gpt-4", "hi")).await;
+    let body: Value = serde_json::from_str(response.text().await.unwrap().trim()).unwrap();
+    assert_eq!(body["error"]["code"], "upstream_rate_limited");
+}
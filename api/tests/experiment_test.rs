@@ -0,0 +1,109 @@
+use berry_api_api::config::model::{Backend, ExperimentSettings, ModelMapping};
+use berry_api_api::loadbalance::ExperimentArm;
+use axum::routing::post;
+use serde_json::{Value, json};
+
+mod common;
+use common::{ConfigBuilder, chat, chat_body, provider, reply_content, serve, start_gateway, user};
+
+/// 启动一个本地上游，返回的内容为上游名称并附带usage
+async fn spawn_upstream(name: &'static str) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || async move {
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "model": "gpt-4",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": name}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+fn backend(provider: &str, tag: &str) -> Backend {
+    Backend {
+        provider: provider.to_string(),
+        model: "gpt-4".to_string(),
+        priority: 1,
+        tags: vec![tag.to_string()],
+        ..Default::default()
+    }
+}
+
+/// 稳定版和候选版后端分别作为对照组和实验组，treatment_percent的流量进入实验组
+fn experiment_mapping(treatment_percent: f64) -> ModelMapping {
+    ModelMapping {
+        name: "gpt-4".to_string(),
+        backends: vec![backend("stable", "stable"), backend("candidate", "candidate")],
+        semantic_cache: false,
+        experiment: Some(ExperimentSettings {
+            name: "candidate-eval".to_string(),
+            control_tags: vec!["stable".to_string()],
+            treatment_tags: vec!["candidate".to_string()],
+            treatment_percent,
+        }),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_experiment_routes_to_arm_and_records_metrics() {
+    let config = ConfigBuilder::new()
+        .provider("stable", provider(&spawn_upstream("stable").await))
+        .provider("candidate", provider(&spawn_upstream("candidate").await))
+        .model("gpt-4", experiment_mapping(100.0))
+        .user("user", user("User", "user-token"))
+        .build();
+    let (gateway, load_balancer) = start_gateway(config).await;
+
+    for _ in 0..3 {
+        let response = chat(&gateway, "user-token", chat_body("gpt-4", "hi")).await;
+        assert_eq!(response.headers()["x-berry-experiment"], "candidate-eval:treatment");
+        assert_eq!(reply_content(response).await, "candidate");
+    }
+
+    let report = load_balancer.get_experiment_tracker().report();
+    assert_eq!(report.len(), 1);
+    assert_eq!(report[0].experiment, "candidate-eval");
+    let arm = &report[0].arms[&ExperimentArm::Treatment];
+    assert_eq!(arm.requests, 3);
+    assert_eq!(arm.errors, 0);
+    assert_eq!(arm.completion_tokens, 15);
+
+    let metrics: Value = reqwest::get(format!("{}/metrics", gateway)).await.unwrap().json().await.unwrap();
+    assert_eq!(metrics["experiments"][0]["arms"]["treatment"]["requests"], json!(3));
+}
+
+#[tokio::test]
+async fn test_control_arm_uses_control_backends() {
+    let config = ConfigBuilder::new()
+        .provider("stable", provider(&spawn_upstream("stable").await))
+        .provider("candidate", provider(&spawn_upstream("candidate").await))
+        .model("gpt-4", experiment_mapping(0.0))
+        .user("user", user("User", "user-token"))
+        .build();
+    let (gateway, _) = start_gateway(config).await;
+
+    for _ in 0..3 {
+        let response = chat(&gateway, "user-token", chat_body("gpt-4", "hi")).await;
+        assert_eq!(response.headers()["x-berry-experiment"], "candidate-eval:control");
+        assert_eq!(reply_content(response).await, "stable");
+    }
+}
+
+#[tokio::test]
+async fn test_experiment_validation() {
+    let mut config = ConfigBuilder::new()
+        .provider("stable", provider(&spawn_upstream("stable").await))
+        .provider("candidate", provider(&spawn_upstream("candidate").await))
+        .model("gpt-4", experiment_mapping(50.0))
+        .user("user", user("User", "user-token"))
+        .build();
+    let experiment = config.models.get_mut("gpt-4").unwrap().experiment.as_mut().unwrap();
+    experiment.treatment_tags = vec!["missing".to_string()];
+    let error = config.validate().unwrap_err();
+    assert!(error.to_string().contains("treatment tags [missing]"));
+}
@@ -0,0 +1,133 @@
+use berry_api_api::app::create_app;
+use berry_api_api::config::model::{Backend, ModelMapping, UserToken};
+use axum::http::{HeaderValue, StatusCode};
+use axum::routing::post;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+mod common;
+use common::{ConfigBuilder, chat, chat_body, create_state, provider_with_models, serve, user};
+
+/// 启动一个总是返回固定聊天结果的本地上游
+async fn spawn_upstream() -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(|axum::Json(body): axum::Json<Value>| async move {
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "model": body["model"],
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "ok"}, "finish_reason": "stop"}]
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+fn mapping(name: &str, provider: &str, fallback_models: Vec<String>) -> ModelMapping {
+    ModelMapping {
+        name: name.to_string(),
+        backends: vec![Backend {
+            provider: provider.to_string(),
+            model: name.to_string(),
+            priority: 1,
+            ..Default::default()
+        }],
+        fallback_models,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_falls_back_when_retries_fail() {
+    let upstream = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        // 端口1上没有服务，连接会立即被拒绝
+        .provider("broken", provider_with_models("http://127.0.0.1:1/v1", &["gpt-4o"]))
+        .provider("working", provider_with_models(&upstream, &["gpt-4o-mini"]))
+        .model("gpt-4o", mapping("gpt-4o", "broken", vec!["gpt-4o-mini".to_string()]))
+        .model("gpt-4o-mini", mapping("gpt-4o-mini", "working", vec![]))
+        .user("user", user("User", "user-token"))
+        .build();
+    let state = create_state(config).await;
+    let gateway = serve(create_app(state)).await;
+
+    // 流式响应带有保活，不会自行结束，只检查响应头
+    let response = chat(&gateway, "user-token", json!({
+        "model": "gpt-4o",
+        "stream": true,
+        "messages": [{ "role": "user", "content": "hi" }]
+    }))
+    .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["x-berry-fallback-model"], "gpt-4o-mini");
+}
+
+#[tokio::test]
+async fn test_falls_back_when_all_backends_unhealthy() {
+    let upstream = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        // 端口1上没有服务，连接会立即被拒绝
+        .provider("broken", provider_with_models("http://127.0.0.1:1/v1", &["gpt-4o"]))
+        .provider("working", provider_with_models(&upstream, &["gpt-4o-mini"]))
+        .model("gpt-4o", mapping("gpt-4o", "broken", vec!["gpt-4o-mini".to_string()]))
+        .model("gpt-4o-mini", mapping("gpt-4o-mini", "working", vec![]))
+        .user("user", user("User", "user-token"))
+        .build();
+    let state = create_state(config).await;
+    state.load_balancer.get_metrics().record_failure("broken:gpt-4o");
+    let server = TestServer::new(create_app(state)).unwrap();
+
+    let response = server
+        .post("/v1/chat/completions")
+        .add_header(axum::http::header::AUTHORIZATION, HeaderValue::from_static("Bearer user-token"))
+        .json(&chat_body("gpt-4o", "hi"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.header("x-berry-fallback-model"), "gpt-4o-mini");
+    assert_eq!(response.json::<Value>()["model"], "gpt-4o-mini");
+}
+
+#[tokio::test]
+async fn test_fallback_respects_allowed_models() {
+    let upstream = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        // 端口1上没有服务，连接会立即被拒绝
+        .provider("broken", provider_with_models("http://127.0.0.1:1/v1", &["gpt-4o"]))
+        .provider("working", provider_with_models(&upstream, &["gpt-4o-mini"]))
+        .model("gpt-4o", mapping("gpt-4o", "broken", vec!["gpt-4o-mini".to_string()]))
+        .model("gpt-4o-mini", mapping("gpt-4o-mini", "working", vec![]))
+        .user("user", UserToken {
+            allowed_models: vec!["gpt-4o".to_string()],
+            ..user("User", "user-token")
+        })
+        .build();
+    let state = create_state(config).await;
+    state.load_balancer.get_metrics().record_failure("broken:gpt-4o");
+    let server = TestServer::new(create_app(state)).unwrap();
+
+    let response = server
+        .post("/v1/chat/completions")
+        .add_header(axum::http::header::AUTHORIZATION, HeaderValue::from_static("Bearer user-token"))
+        .json(&chat_body("gpt-4o", "hi"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(response.maybe_header("x-berry-fallback-model").is_none());
+}
+
+#[test]
+fn test_unknown_fallback_model_rejected() {
+    let mut config = ConfigBuilder::new()
+        // 端口1上没有服务，连接会立即被拒绝
+        .provider("broken", provider_with_models("http://127.0.0.1:1/v1", &["gpt-4o"]))
+        .provider("working", provider_with_models("http://127.0.0.1:1/v1", &["gpt-4o-mini"]))
+        .model("gpt-4o", mapping("gpt-4o", "broken", vec!["gpt-4o-mini".to_string()]))
+        .model("gpt-4o-mini", mapping("gpt-4o-mini", "working", vec![]))
+        .user("user", user("User", "user-token"))
+        .build();
+    config.models.get_mut("gpt-4o-mini").unwrap().fallback_models = vec!["missing".to_string()];
+    assert!(config.validate().is_err());
+
+    config.models.get_mut("gpt-4o-mini").unwrap().fallback_models = vec!["gpt-4o-mini".to_string()];
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,175 @@
+use berry_api_api::config::model::{GlobalSettings, LoadBalanceStrategy, ModelMapping};
+use axum::body::{Body, Bytes};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::time::{Duration, Instant};
+
+mod common;
+use common::{ConfigBuilder, admin, backend, chat, chat_body, mapping, provider, serve, start_gateway};
+
+/// 启动一个本地上游，回复内容为上游名称，流式请求返回两个数据块
+async fn spawn_upstream(name: &'static str) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move |axum::Json(body): axum::Json<Value>| async move {
+            if body["stream"] != true {
+                return axum::Json(json!({
+                    "id": "chatcmpl-test",
+                    "object": "chat.completion",
+                    "model": "gpt-4",
+                    "choices": [{"index": 0, "message": {"role": "assistant", "content": name}, "finish_reason": "stop"}]
+                }))
+                .into_response();
+            }
+            let chunk = |content: &str| {
+                let chunk = json!({"id": "chatcmpl-s", "choices": [{"index": 0, "delta": {"content": content}}]});
+                Ok::<_, std::convert::Infallible>(Bytes::from(format!("data: {}\n\n", chunk)))
+            };
+            let chunks = vec![chunk(name), chunk("more"), Ok(Bytes::from_static(b"data: [DONE]\n\n"))];
+            ([("content-type", "text/event-stream")], Body::from_stream(futures::stream::iter(chunks))).into_response()
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+/// 读取收到的响应内容；流式响应读到[DONE]或错误事件为止
+async fn read_reply(mut response: reqwest::Response) -> String {
+    let mut text = String::new();
+    while !text.contains("[DONE]") && !text.contains("\"error\"") {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .expect("response stalled")
+            .unwrap();
+        match chunk {
+            Some(chunk) => text.push_str(&String::from_utf8_lossy(&chunk)),
+            None => break,
+        }
+    }
+    text
+}
+
+async fn set_fault(url: &str, fault: Value) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/admin/faults", url))
+        .bearer_auth("admin-token")
+        .json(&fault)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn list_faults(url: &str) -> Value {
+    reqwest::Client::new()
+        .get(format!("{}/admin/faults", url))
+        .bearer_auth("admin-token")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_fault_injection_requires_setting() {
+    let config = ConfigBuilder::new()
+        .provider("primary", provider(&spawn_upstream("primary").await))
+        .provider("secondary", provider(&spawn_upstream("secondary").await))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("primary", 1), backend("secondary", 2)])
+        })
+        .user("admin", admin("Admin", "admin-token"))
+        .settings(GlobalSettings {
+            fault_injection: false,
+            ..GlobalSettings::default()
+        })
+        .build();
+    let (url, _) = start_gateway(config).await;
+
+    let response = set_fault(&url, json!({"provider": "primary", "model": "gpt-4", "error_percent": 100})).await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    assert_eq!(list_faults(&url).await["enabled"], false);
+}
+
+#[tokio::test]
+async fn test_injected_errors_trigger_failover() {
+    let config = ConfigBuilder::new()
+        .provider("primary", provider(&spawn_upstream("primary").await))
+        .provider("secondary", provider(&spawn_upstream("secondary").await))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("primary", 1), backend("secondary", 2)])
+        })
+        .user("admin", admin("Admin", "admin-token"))
+        .settings(GlobalSettings {
+            fault_injection: true,
+            ..GlobalSettings::default()
+        })
+        .build();
+    let (url, load_balancer) = start_gateway(config).await;
+
+    let response = set_fault(&url, json!({"provider": "primary", "model": "gpt-4", "error_percent": 100, "error_status": 503})).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = set_fault(&url, json!({"provider": "unknown", "model": "gpt-4", "error_percent": 100})).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // 流式请求在主后端注入错误后重试到备用后端，主后端被标记为不健康
+    let text = read_reply(chat(&url, "admin-token", json!({"model": "gpt-4", "stream": true, "messages": [{"role": "user", "content": "hi"}]})).await).await;
+    assert!(text.contains("secondary"), "{}", text);
+    assert!(!load_balancer.get_metrics().is_healthy("primary", "gpt-4"));
+    let faults = list_faults(&url).await;
+    let primary = faults["faults"].as_array().unwrap().iter().find(|f| f["backend"] == "primary:gpt-4").unwrap();
+    assert_eq!(primary["errors"], 1);
+
+    // 清除后不再注入
+    let response = reqwest::Client::new()
+        .delete(format!("{}/admin/faults", url))
+        .bearer_auth("admin-token")
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["cleared"], 1);
+    assert!(list_faults(&url).await["faults"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_injected_latency_and_dropped_stream() {
+    let config = ConfigBuilder::new()
+        .provider("primary", provider(&spawn_upstream("primary").await))
+        .provider("secondary", provider(&spawn_upstream("secondary").await))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("primary", 1), backend("secondary", 2)])
+        })
+        .user("admin", admin("Admin", "admin-token"))
+        .settings(GlobalSettings {
+            fault_injection: true,
+            ..GlobalSettings::default()
+        })
+        .build();
+    let (url, _) = start_gateway(config).await;
+
+    let response = set_fault(&url, json!({"provider": "primary", "model": "gpt-4", "latency_ms": 300, "duration_seconds": 60})).await;
+    let status: Value = response.json().await.unwrap();
+    assert!(status["expires_at"].is_string());
+    let start = Instant::now();
+    assert!(read_reply(chat(&url, "admin-token", chat_body("gpt-4", "hi")).await).await.contains("primary"));
+    assert!(start.elapsed() >= Duration::from_millis(300));
+
+    // 流式响应转发首个数据块后断开
+    set_fault(&url, json!({"provider": "primary", "model": "gpt-4", "drop_stream_percent": 100, "drop_after_chunks": 1})).await;
+    let text = read_reply(chat(&url, "admin-token", json!({"model": "gpt-4", "stream": true, "messages": [{"role": "user", "content": "hi"}]})).await).await;
+    assert!(text.contains("primary"), "{}", text);
+    assert!(!text.contains("more") && !text.contains("[DONE]"), "{}", text);
+    let faults = list_faults(&url).await;
+    assert_eq!(faults["faults"][0]["dropped_streams"], 1);
+
+    let response = set_fault(&url, json!({"provider": "primary", "model": "gpt-4", "error_percent": 50, "error_status": 200})).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,271 @@
+use berry_api_api::app::create_app;
+use berry_api_api::config::model::{FilesSettings, GlobalSettings};
+use axum::extract::{Multipart, Path, State};
+use axum::http::{HeaderValue, StatusCode};
+use axum::routing::{get, post};
+use axum_test::TestServer;
+use axum_test::multipart::{MultipartForm, Part};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+type UpstreamFiles = Arc<Mutex<HashMap<String, (Value, String)>>>;
+
+mod common;
+use common::{ConfigBuilder, create_state, provider_with_models, serve, user};
+
+/// 启动一个提供文件接口的本地上游，文件保存在内存中
+async fn spawn_upstream() -> String {
+    let files: UpstreamFiles = Arc::new(Mutex::new(HashMap::new()));
+    let app = axum::Router::new()
+        .route(
+            "/v1/files",
+            post(|State(files): State<UpstreamFiles>, mut multipart: Multipart| async move {
+                let (mut purpose, mut filename, mut content) = (String::new(), String::new(), String::new());
+                while let Some(field) = multipart.next_field().await.unwrap() {
+                    let name = field.name().unwrap_or_default().to_string();
+                    let file_name = field.file_name().map(str::to_string);
+                    let data = field.text().await.unwrap();
+                    match name.as_str() {
+                        "purpose" => purpose = data,
+                        "file" => (filename, content) = (file_name.unwrap_or_default(), data),
+                        _ => {}
+                    }
+                }
+                if purpose == "invalid" {
+                    return (StatusCode::BAD_REQUEST, axum::Json(json!({"error": {"message": "Invalid purpose"}})));
+                }
+                let mut files = files.lock().unwrap();
+                let object = json!({
+                    "id": format!("file-up-{}", files.len() + 1),
+                    "object": "file",
+                    "bytes": content.len(),
+                    "created_at": 1700000000,
+                    "filename": filename,
+                    "purpose": purpose,
+                    "status": "uploaded",
+                });
+                files.insert(object["id"].as_str().unwrap().to_string(), (object.clone(), content));
+                (StatusCode::OK, axum::Json(object))
+            }),
+        )
+        .route(
+            "/v1/files/{id}",
+            get(|State(files): State<UpstreamFiles>, Path(id): Path<String>| async move {
+                match files.lock().unwrap().get(&id) {
+                    Some((object, _)) => {
+                        let mut object = object.clone();
+                        object["status"] = json!("processed");
+                        (StatusCode::OK, axum::Json(object))
+                    }
+                    None => (StatusCode::NOT_FOUND, axum::Json(json!({"error": {"message": "No such file"}}))),
+                }
+            })
+            .delete(|State(files): State<UpstreamFiles>, Path(id): Path<String>| async move {
+                match files.lock().unwrap().remove(&id) {
+                    Some(_) => (StatusCode::OK, axum::Json(json!({"id": id, "object": "file", "deleted": true}))),
+                    None => (StatusCode::NOT_FOUND, axum::Json(json!({"error": {"message": "No such file"}}))),
+                }
+            }),
+        )
+        .route(
+            "/v1/files/{id}/content",
+            get(|State(files): State<UpstreamFiles>, Path(id): Path<String>| async move {
+                match files.lock().unwrap().get(&id) {
+                    Some((_, content)) => (StatusCode::OK, content.clone()),
+                    None => (StatusCode::NOT_FOUND, String::new()),
+                }
+            }),
+        )
+        .with_state(files);
+    format!("{}/v1", serve(app).await)
+}
+
+fn temp_db(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("berry-files-{}-{}.db", name, std::process::id()));
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+    path.display().to_string()
+}
+
+async fn create_server(files: Option<FilesSettings>) -> TestServer {
+    let upstream = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        // 端口1上没有服务，上传会转给下一个provider
+        .provider("broken", provider_with_models("http://127.0.0.1:1/v1", &["gpt-4o"]))
+        .provider("working", provider_with_models(&upstream, &["gpt-4o"]))
+        .user("alice", user("Alice", "alice-token"))
+        .user("bob", user("Bob", "bob-token"))
+        .settings(GlobalSettings {
+            files,
+            ..GlobalSettings::default()
+        })
+        .build();
+    let state = create_state(config).await;
+    TestServer::new(create_app(state)).unwrap()
+}
+
+fn files_settings(name: &str) -> FilesSettings {
+    FilesSettings {
+        providers: vec!["broken".to_string(), "working".to_string()],
+        database: temp_db(name),
+        default_quota_mb: Some(1),
+        user_quotas_mb: HashMap::new(),
+    }
+}
+
+fn upload_form(purpose: &str, content: impl Into<Vec<u8>>) -> MultipartForm {
+    MultipartForm::new()
+        .add_text("purpose", purpose.to_string())
+        .add_part("file", Part::bytes(content.into()).file_name("batch.jsonl").mime_type("application/jsonl"))
+}
+
+fn auth(token: &str) -> HeaderValue {
+    HeaderValue::from_str(&format!("Bearer {}", token)).unwrap()
+}
+
+#[tokio::test]
+async fn test_file_lifecycle_uses_berry_ids() {
+    let server = create_server(Some(files_settings("lifecycle"))).await;
+
+    let response = server
+        .post("/v1/files")
+        .add_header("authorization", auth("alice-token"))
+        .multipart(upload_form("batch", "{\"custom_id\":\"1\"}\n"))
+        .await;
+    response.assert_status_ok();
+    let file: Value = response.json();
+    let id = file["id"].as_str().unwrap().to_string();
+    assert!(id.starts_with("file-berry-"));
+    assert_eq!(file["filename"], "batch.jsonl");
+    assert_eq!(file["purpose"], "batch");
+    assert_eq!(file["bytes"], 18);
+
+    let list: Value = server
+        .get("/v1/files")
+        .add_header("authorization", auth("alice-token"))
+        .await
+        .json();
+    assert_eq!(list["object"], "list");
+    assert_eq!(list["data"].as_array().unwrap().len(), 1);
+    assert_eq!(list["data"][0]["id"], id.as_str());
+    let filtered: Value = server
+        .get("/v1/files?purpose=assistants")
+        .add_header("authorization", auth("alice-token"))
+        .await
+        .json();
+    assert!(filtered["data"].as_array().unwrap().is_empty());
+
+    // 查询时从provider获取最新状态
+    let retrieved: Value = server
+        .get(&format!("/v1/files/{}", id))
+        .add_header("authorization", auth("alice-token"))
+        .await
+        .json();
+    assert_eq!(retrieved["id"], id.as_str());
+    assert_eq!(retrieved["status"], "processed");
+
+    let content = server
+        .get(&format!("/v1/files/{}/content", id))
+        .add_header("authorization", auth("alice-token"))
+        .await;
+    content.assert_status_ok();
+    assert_eq!(content.text(), "{\"custom_id\":\"1\"}\n");
+
+    let deleted: Value = server
+        .delete(&format!("/v1/files/{}", id))
+        .add_header("authorization", auth("alice-token"))
+        .await
+        .json();
+    assert_eq!(deleted, json!({"id": id, "object": "file", "deleted": true}));
+    server
+        .get(&format!("/v1/files/{}", id))
+        .add_header("authorization", auth("alice-token"))
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_files_are_private_to_their_owner() {
+    let server = create_server(Some(files_settings("owner"))).await;
+    let file: Value = server
+        .post("/v1/files")
+        .add_header("authorization", auth("alice-token"))
+        .multipart(upload_form("assistants", "secret"))
+        .await
+        .json();
+    let id = file["id"].as_str().unwrap();
+
+    let response = server
+        .get(&format!("/v1/files/{}/content", id))
+        .add_header("authorization", auth("bob-token"))
+        .await;
+    response.assert_status(StatusCode::NOT_FOUND);
+    assert_eq!(response.json::<Value>()["error"]["code"], "file_not_found");
+    server
+        .delete(&format!("/v1/files/{}", id))
+        .add_header("authorization", auth("bob-token"))
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    let list: Value = server
+        .get("/v1/files")
+        .add_header("authorization", auth("bob-token"))
+        .await
+        .json();
+    assert!(list["data"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_upload_rejected_over_quota() {
+    let server = create_server(Some(files_settings("quota"))).await;
+    let chunk = vec![b'x'; 600 * 1024];
+
+    server
+        .post("/v1/files")
+        .add_header("authorization", auth("alice-token"))
+        .multipart(upload_form("batch", chunk.clone()))
+        .await
+        .assert_status_ok();
+    let response = server
+        .post("/v1/files")
+        .add_header("authorization", auth("alice-token"))
+        .multipart(upload_form("batch", chunk.clone()))
+        .await;
+    response.assert_status(StatusCode::FORBIDDEN);
+    assert_eq!(response.json::<Value>()["error"]["code"], "storage_quota_exceeded");
+
+    // 配额按用户统计
+    server
+        .post("/v1/files")
+        .add_header("authorization", auth("bob-token"))
+        .multipart(upload_form("batch", chunk))
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+async fn test_upstream_client_errors_and_disabled_files() {
+    let server = create_server(Some(files_settings("errors"))).await;
+    let response = server
+        .post("/v1/files")
+        .add_header("authorization", auth("alice-token"))
+        .multipart(upload_form("invalid", "data"))
+        .await;
+    response.assert_status(StatusCode::BAD_REQUEST);
+    assert_eq!(response.json::<Value>()["error"]["message"], "Invalid purpose");
+
+    server
+        .post("/v1/files")
+        .add_header("authorization", auth("unknown-token"))
+        .multipart(upload_form("batch", "data"))
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    let server = create_server(None).await;
+    server
+        .get("/v1/files")
+        .add_header("authorization", auth("alice-token"))
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
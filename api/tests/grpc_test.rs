@@ -0,0 +1,121 @@
+#![cfg(feature = "grpc")]
+
+use berry_api_api::config::model::ModelMapping;
+use berry_api_api::grpc::proto::chat_completion_client::ChatCompletionClient;
+use berry_api_api::grpc::proto::{ChatCompletionRequest, Message};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use serde_json::{Value, json};
+
+mod common;
+use common::{ConfigBuilder, backend, create_state, mapping, provider, serve, user};
+
+/// 启动一个OpenAI格式的本地上游，stream请求返回SSE流
+async fn spawn_upstream() -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(|axum::Json(body): axum::Json<Value>| async move {
+            if body["stream"] == true {
+                let chunks = [
+                    json!({"id": "chatcmpl-s", "model": "gpt-4", "choices": [{"index": 0, "delta": {"role": "assistant", "content": "Hel"}}]}),
+                    json!({"id": "chatcmpl-s", "model": "gpt-4", "choices": [{"index": 0, "delta": {"content": "lo"}, "finish_reason": "stop"}]}),
+                    json!({"id": "chatcmpl-s", "model": "gpt-4", "choices": [], "usage": {"prompt_tokens": 4, "completion_tokens": 2, "total_tokens": 6}}),
+                ];
+                let mut sse: String = chunks.iter().map(|chunk| format!("data: {}\n\n", chunk)).collect();
+                sse.push_str("data: [DONE]\n\n");
+                return ([("content-type", "text/event-stream")], sse).into_response();
+            }
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "model": "gpt-4",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": format!("max_tokens={}", body["max_tokens"])}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15}
+            }))
+            .into_response()
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+/// 启动转发到本地上游的gRPC服务，返回客户端
+async fn start_grpc() -> ChatCompletionClient<tonic::transport::Channel> {
+    let config = ConfigBuilder::new()
+        .provider("upstream", provider(&spawn_upstream().await))
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let state = create_state(config).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(berry_api_api::grpc::serve_with_listener(state, listener));
+    ChatCompletionClient::connect(format!("http://{}", addr)).await.unwrap()
+}
+
+fn chat_request(token: &str) -> tonic::Request<ChatCompletionRequest> {
+    let mut request = tonic::Request::new(ChatCompletionRequest {
+        model: "gpt-4".to_string(),
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "Hi".to_string(),
+            ..Default::default()
+        }],
+        max_tokens: Some(32),
+        ..Default::default()
+    });
+    request
+        .metadata_mut()
+        .insert("authorization", format!("Bearer {}", token).parse().unwrap());
+    request
+}
+
+#[tokio::test]
+async fn test_grpc_create() {
+    let mut client = start_grpc().await;
+
+    let response = client.create(chat_request("user-token")).await.unwrap().into_inner();
+    assert_eq!(response.content, "max_tokens=32");
+    assert_eq!(response.finish_reason, "stop");
+    assert_eq!(response.usage.unwrap().total_tokens, 15);
+}
+
+#[tokio::test]
+async fn test_grpc_create_stream() {
+    let mut client = start_grpc().await;
+
+    let mut stream = client.create_stream(chat_request("user-token")).await.unwrap().into_inner();
+    let mut content = String::new();
+    let mut finish_reason = String::new();
+    let mut usage = None;
+    while let Some(chunk) = stream.message().await.unwrap() {
+        content.push_str(&chunk.content);
+        if !chunk.finish_reason.is_empty() {
+            finish_reason = chunk.finish_reason;
+        }
+        usage = chunk.usage.or(usage);
+    }
+    assert_eq!(content, "Hello");
+    assert_eq!(finish_reason, "stop");
+    assert_eq!(usage.unwrap().completion_tokens, 2);
+}
+
+#[tokio::test]
+async fn test_grpc_errors_map_to_status_codes() {
+    let mut client = start_grpc().await;
+
+    let status = client.create(chat_request("wrong-token")).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+    let mut request = chat_request("user-token");
+    request.get_mut().model = "unknown-model".to_string();
+    let status = client.create_stream(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+
+    let mut request = chat_request("user-token");
+    request.get_mut().messages.clear();
+    let status = client.create(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
@@ -1,84 +1,69 @@
-use berry_api_api::config::model::{Config, Provider, ModelMapping, Backend, LoadBalanceStrategy, GlobalSettings};
+use berry_api_api::config::model::{Provider, ModelMapping, Backend, LoadBalanceStrategy, GlobalSettings};
 use berry_api_api::loadbalance::{LoadBalanceService, MetricsCollector};
-use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
 
-/// 创建测试配置
-fn create_test_config() -> Config {
-    let mut providers = HashMap::new();
-    
-    // 添加一个测试provider（使用httpbin）
-    providers.insert("test-provider".to_string(), Provider {
-        name: "Test Provider".to_string(),
-        base_url: "https://httpbin.org".to_string(),
-        api_key: "test-api-key".to_string(),
-        models: vec!["test-model".to_string()],
-        headers: HashMap::new(),
-        enabled: true,
-        timeout_seconds: 10,
-        max_retries: 2,
-    });
+mod common;
+use common::{ConfigBuilder, mapping};
 
-    // 添加一个模拟的OpenAI provider
-    providers.insert("openai-mock".to_string(), Provider {
-        name: "OpenAI Mock".to_string(),
-        base_url: "https://api.openai.com/v1".to_string(),
-        api_key: "sk-test-key".to_string(),
-        models: vec!["gpt-3.5-turbo".to_string()],
-        headers: HashMap::new(),
-        enabled: true,
-        timeout_seconds: 10,
-        max_retries: 2,
-    });
-
-    let mut models = HashMap::new();
-    models.insert("test-model".to_string(), ModelMapping {
-        name: "test-model".to_string(),
-        backends: vec![
-            Backend {
-                provider: "test-provider".to_string(),
-                model: "test-model".to_string(),
-                weight: 0.7,
-                priority: 1,
-                enabled: true,
-                tags: vec![],
-            },
-            Backend {
-                provider: "openai-mock".to_string(),
-                model: "gpt-3.5-turbo".to_string(),
-                weight: 0.3,
-                priority: 2,
-                enabled: true,
-                tags: vec![],
-            },
-        ],
-        strategy: LoadBalanceStrategy::WeightedFailover,
-        enabled: true,
-    });
-
-    Config {
-        providers,
-        models,
-        users: HashMap::new(),
-        settings: GlobalSettings {
+/// 一个测试provider（使用httpbin）和一个模拟OpenAI provider的服务
+fn create_test_service() -> LoadBalanceService {
+    let config = ConfigBuilder::new()
+        .provider("test-provider", Provider {
+            name: "Test Provider".to_string(),
+            base_url: "https://httpbin.org".to_string(),
+            api_key: "test-api-key".to_string(),
+            models: vec!["test-model".to_string()],
+            timeout_seconds: 10,
+            max_retries: 2,
+            ..Default::default()
+        })
+        .provider("openai-mock", Provider {
+            name: "OpenAI Mock".to_string(),
+            base_url: "https://api.openai.com/v1".to_string(),
+            api_key: "sk-test-key".to_string(),
+            models: vec!["gpt-3.5-turbo".to_string()],
+            timeout_seconds: 10,
+            max_retries: 2,
+            ..Default::default()
+        })
+        .model("test-model", ModelMapping {
+            strategy: LoadBalanceStrategy::WeightedFailover,
+            ..mapping("test-model", vec![
+                Backend {
+                    provider: "test-provider".to_string(),
+                    model: "test-model".to_string(),
+                    weight: 0.7,
+                    priority: 1,
+                    ..Default::default()
+                },
+                Backend {
+                    provider: "openai-mock".to_string(),
+                    model: "gpt-3.5-turbo".to_string(),
+                    weight: 0.3,
+                    priority: 2,
+                    ..Default::default()
+                },
+            ])
+        })
+        .settings(GlobalSettings {
             health_check_interval_seconds: 5,
             request_timeout_seconds: 10,
             max_retries: 2,
             circuit_breaker_failure_threshold: 3,
             circuit_breaker_timeout_seconds: 30,
             recovery_check_interval_seconds: 10,
-            max_internal_retries: 2,
             health_check_timeout_seconds: 5,
-        },
-    }
+            ..GlobalSettings::default()
+        })
+        .build();
+    LoadBalanceService::new(config).unwrap()
 }
 
 #[tokio::test]
 async fn test_health_check_basic_functionality() {
-    let config = create_test_config();
-    let service = LoadBalanceService::new(config).unwrap();
+    let service = create_test_service();
     
     // 启动服务
     service.start().await.unwrap();
@@ -152,8 +137,7 @@ async fn test_recovery_check_timing() {
 
 #[tokio::test]
 async fn test_smart_backend_selection() {
-    let config = create_test_config();
-    let service = LoadBalanceService::new(config).unwrap();
+    let service = create_test_service();
     
     // 启动服务
     service.start().await.unwrap();
@@ -0,0 +1,81 @@
+use berry_api_api::app::create_app;
+use berry_api_api::config::model::{Backend, GlobalSettings, ModelMapping};
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use serde_json::Value;
+
+mod common;
+use common::{ConfigBuilder, create_state, provider_with_models};
+
+fn mapping(name: &str, provider: &str) -> ModelMapping {
+    ModelMapping {
+        name: name.to_string(),
+        backends: vec![Backend {
+            provider: provider.to_string(),
+            model: name.to_string(),
+            priority: 1,
+            ..Default::default()
+        }],
+        semantic_cache: false,
+        ..Default::default()
+    }
+}
+
+/// gpt-4的后端健康、claude的后端被标记为不健康的服务，required_models为就绪检查要求的模型
+async fn start_server(required_models: &[&str]) -> TestServer {
+    let config = ConfigBuilder::new()
+        .provider("test-provider", provider_with_models("http://127.0.0.1:1/v1", &["gpt-4", "claude"]))
+        .model("gpt-4", mapping("gpt-4", "test-provider"))
+        .model("claude", mapping("claude", "test-provider"))
+        .settings(GlobalSettings {
+            readiness_required_models: required_models.iter().map(|model| model.to_string()).collect(),
+            ..GlobalSettings::default()
+        })
+        .build();
+    let state = create_state(config).await;
+    state.load_balancer.get_metrics().record_failure("test-provider:claude");
+    TestServer::new(create_app(state)).unwrap()
+}
+
+#[tokio::test]
+async fn test_healthz_reports_liveness() {
+    let server = start_server(&[]).await;
+    let response = server.get("/healthz").await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.json::<Value>()["status"], "alive");
+}
+
+#[tokio::test]
+async fn test_readyz_checks_required_models() {
+    // 未配置要求的模型时检查所有模型，claude没有健康后端
+    let server = start_server(&[]).await;
+    let response = server.get("/readyz").await;
+    assert_eq!(response.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = response.json();
+    assert_eq!(body["not_ready_models"], serde_json::json!(["claude"]));
+
+    let response = server.get("/readyz?models=gpt-4").await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    // 查询不存在的模型视为未就绪
+    let response = server.get("/readyz?models=gpt-4,missing").await;
+    assert_eq!(response.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // 配置了要求的模型时只检查这些模型
+    let server = start_server(&["gpt-4"]).await;
+    let response = server.get("/readyz").await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.json::<Value>()["config_loaded"], true);
+}
+
+#[test]
+fn test_readiness_required_models_must_exist() {
+    let mut config = ConfigBuilder::new()
+        .provider("test-provider", provider_with_models("http://127.0.0.1:1/v1", &["gpt-4", "claude"]))
+        .model("gpt-4", mapping("gpt-4", "test-provider"))
+        .model("claude", mapping("claude", "test-provider"))
+        .build();
+    config.settings.readiness_required_models = vec!["missing".to_string()];
+    let error = config.validate().unwrap_err();
+    assert!(error.to_string().contains("'missing'"));
+}
@@ -0,0 +1,96 @@
+use berry_api_api::config::model::{GlobalSettings, LoadBalanceStrategy, ModelMapping, NotificationEvent, NotificationSettings, NotificationWebhook, Provider, WebhookFormat};
+use berry_api_api::loadbalance::LoadBalanceService;
+use axum::routing::post;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+mod common;
+use common::{ConfigBuilder, backend, mapping, provider, serve};
+
+/// 启动本地Webhook接收端，收到的消息体通过通道返回
+async fn spawn_webhook() -> (String, mpsc::UnboundedReceiver<(String, Value)>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = axum::Router::new().route(
+        "/{format}",
+        post(
+            move |axum::extract::Path(format): axum::extract::Path<String>, axum::Json(body): axum::Json<Value>| {
+                let tx = tx.clone();
+                async move {
+                    let _ = tx.send((format, body));
+                }
+            },
+        ),
+    );
+    (serve(app).await, rx)
+}
+
+fn webhook(url: String, format: WebhookFormat, events: Vec<NotificationEvent>) -> NotificationWebhook {
+    NotificationWebhook {
+        url,
+        format,
+        events,
+        timeout_seconds: 2,
+    }
+}
+
+#[tokio::test]
+async fn test_backend_failure_notifies_webhooks() {
+    let (webhook_base, mut received) = spawn_webhook().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            ..provider("http://127.0.0.1:9/v1")
+        })
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::RoundRobin,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .settings(GlobalSettings {
+            notifications: Some(NotificationSettings {
+                debounce_seconds: 0,
+                webhooks: vec![
+                    webhook(format!("{}/json", webhook_base), WebhookFormat::Json, Vec::new()),
+                    webhook(format!("{}/slack", webhook_base), WebhookFormat::Slack, vec![NotificationEvent::ModelDown]),
+                ],
+            }),
+            ..GlobalSettings::default()
+        })
+        .build();
+    config.validate().unwrap();
+    let service = LoadBalanceService::new(config).unwrap();
+    service.start().await.unwrap();
+    // 等待初始健康检查完成
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // 初始状态健康，不发送通知
+    service.get_model_health();
+    service.get_metrics().record_failure("upstream:gpt-4");
+    service.get_model_health();
+
+    let mut payloads = HashMap::new();
+    for _ in 0..2 {
+        let (format, body) = tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .expect("webhook not called")
+            .unwrap();
+        payloads.insert(format, body);
+    }
+
+    let events: Vec<&str> = payloads["json"]["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["event"].as_str().unwrap())
+        .collect();
+    assert!(events.contains(&"backend_unhealthy"));
+    assert!(events.contains(&"model_down"));
+
+    // Slack只订阅了model_down
+    let text = payloads["slack"]["text"].as_str().unwrap();
+    assert!(text.contains("All backends of model gpt-4 are down"));
+    assert!(!text.contains("upstream:gpt-4 is unhealthy"));
+
+    service.stop().await;
+}
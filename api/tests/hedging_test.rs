@@ -0,0 +1,95 @@
+use berry_api_api::config::model::{HedgingSettings, LoadBalanceStrategy, ModelMapping};
+use axum::routing::post;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+mod common;
+use common::{ConfigBuilder, backend, chat, mapping, provider, serve, start_gateway, user};
+
+/// 启动一个延迟指定时间后返回流式回复的本地上游，回复内容为上游名称
+async fn spawn_sse_upstream(name: &'static str, delay: Duration) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || async move {
+            tokio::time::sleep(delay).await;
+            let chunk = json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion.chunk",
+                "choices": [{"index": 0, "delta": {"content": name}, "finish_reason": null}]
+            });
+            (
+                [(axum::http::header::CONTENT_TYPE, "text/event-stream")],
+                format!("data: {}\n\ndata: [DONE]\n\n", chunk),
+            )
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+#[tokio::test]
+async fn test_hedged_request_served_by_faster_backend() {
+    let slow_url = spawn_sse_upstream("slow", Duration::from_secs(3)).await;
+    let fast_url = spawn_sse_upstream("fast", Duration::ZERO).await;
+    let config = ConfigBuilder::new()
+        .provider("slow", provider(&slow_url))
+        .provider("fast", provider(&fast_url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            hedging: Some(HedgingSettings { delay_ms: 100 }),
+            // 故障转移策略总是先选择慢后端
+            ..mapping("gpt-4", vec![backend("slow", 1), backend("fast", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let (url, load_balancer) = start_gateway(config).await;
+
+    let started = Instant::now();
+    let mut response = chat(&url, "user-token", json!({
+        "model": "gpt-4",
+        "stream": true,
+        "messages": [{"role": "user", "content": "hi"}]
+    }))
+    .await;
+    assert!(response.status().is_success());
+
+    // 读取到首个数据事件即可，网关的保活流不会主动结束
+    let mut received = String::new();
+    while !received.contains("data:") {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    assert!(received.contains("fast"), "unexpected response: {}", received);
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    // 被取消的慢后端请求不计为失败
+    let metrics = load_balancer.get_metrics();
+    assert!(metrics.is_healthy("slow", "gpt-4"));
+    assert_eq!(metrics.get_failure_count("slow", "gpt-4"), 0);
+}
+
+#[test]
+fn test_hedging_validation() {
+    let mut config = ConfigBuilder::new()
+        .provider("slow", provider("http://127.0.0.1:1/v1"))
+        .provider("fast", provider("http://127.0.0.1:2/v1"))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            hedging: Some(HedgingSettings { delay_ms: 100 }),
+            // 故障转移策略总是先选择慢后端
+            ..mapping("gpt-4", vec![backend("slow", 1), backend("fast", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    config.models.get_mut("gpt-4").unwrap().hedging = Some(HedgingSettings { delay_ms: 0 });
+    assert!(config.validate().is_err());
+
+    let mapping = config.models.get_mut("gpt-4").unwrap();
+    mapping.hedging = Some(HedgingSettings { delay_ms: 100 });
+    mapping.backends.truncate(1);
+    let error = config.validate().unwrap_err();
+    assert!(error.to_string().contains("at least 2 backends"));
+}
@@ -14,10 +14,9 @@ fn create_initial_health_test_config() -> Config {
         base_url: "https://httpbin.org".to_string(),
         api_key: "test-api-key".to_string(),
         models: vec!["test-model".to_string()],
-        headers: HashMap::new(),
-        enabled: true,
         timeout_seconds: 10,
         max_retries: 2,
+        ..Default::default()
     });
 
     // 添加一个会失败的provider
@@ -26,10 +25,9 @@ fn create_initial_health_test_config() -> Config {
         base_url: "https://invalid-url-for-test.example.com".to_string(),
         api_key: "invalid-key".to_string(),
         models: vec!["failing-model".to_string()],
-        headers: HashMap::new(),
-        enabled: true,
         timeout_seconds: 5,
         max_retries: 1,
+        ..Default::default()
     });
 
     let mut models = HashMap::new();
@@ -41,12 +39,11 @@ fn create_initial_health_test_config() -> Config {
                 model: "test-model".to_string(),
                 weight: 0.7,
                 priority: 1,
-                enabled: true,
-                tags: vec![],
+                ..Default::default()
             },
         ],
         strategy: LoadBalanceStrategy::WeightedFailover,
-        enabled: true,
+        ..Default::default()
     });
 
     models.insert("failing-model".to_string(), ModelMapping {
@@ -55,14 +52,12 @@ fn create_initial_health_test_config() -> Config {
             Backend {
                 provider: "failing-provider".to_string(),
                 model: "failing-model".to_string(),
-                weight: 1.0,
                 priority: 1,
-                enabled: true,
-                tags: vec![],
+                ..Default::default()
             },
         ],
         strategy: LoadBalanceStrategy::WeightedFailover,
-        enabled: true,
+        ..Default::default()
     });
 
     Config {
@@ -78,7 +73,51 @@ fn create_initial_health_test_config() -> Config {
             recovery_check_interval_seconds: 30,
             max_internal_retries: 2,
             health_check_timeout_seconds: 10,
+            failover_memory_ttl_seconds: 300,
+            model_degraded_ratio: 0.75,
+            model_down_ratio: 0.25,
+            model_health_hysteresis: 0.1,
+            health_webhook_urls: vec![],
+            readiness_required_models: Vec::new(),
+            config_hot_reload: true,
+            peer_max_hops: 1,
+            response_cache_enabled: false,
+            response_cache_ttl_seconds: 300,
+            response_cache_max_entries: 1000,
+            semantic_cache: None,
+            latency_ewma_alpha: 0.3,
+            audit_log: None,
+            keys_file: None,
+            max_embedding_batch_size: 2048,
+            max_request_body_mb: 32,
+            slow_start: Default::default(),
+            outlier_detection: None,
+            max_in_flight_requests: None,
+            priority_scheduling: None,
+            shared_state: None,
+            secrets: None,
+            config_history: Default::default(),
+            health_scoring: None,
+            region_routing: None,
+            content_filters: HashMap::new(),
+            moderation: None,
+            wasm_plugins: None,
+            notifications: None,
+            alerts: None,
+            slo: None,
+            request_log: None,
+            jwt: None,
+            network_acl: None,
+            cors: None,
+            grpc: None,
+            job_queue: None,
+            files: None,
+            stream_heartbeat: None,
+            stream_resumption: None,
+            request_capture: None,
+            fault_injection: false,
         },
+        ..Default::default()
     }
 }
 
@@ -109,7 +148,6 @@ async fn test_initial_health_check_marks_all_healthy() {
     
     // 注意：由于网络条件和实际API响应的不确定性，
     // 这个测试主要验证逻辑不会崩溃，而不是具体的健康状态
-    assert!(true, "Initial health check completed without errors");
 }
 
 #[tokio::test]
@@ -14,10 +14,9 @@ fn create_retry_test_config() -> Config {
         base_url: "https://httpbin.org".to_string(),
         api_key: "healthy-api-key".to_string(),
         models: vec!["healthy-model".to_string()],
-        headers: HashMap::new(),
-        enabled: true,
         timeout_seconds: 10,
         max_retries: 2,
+        ..Default::default()
     });
 
     // 不健康的provider（无效URL）
@@ -26,10 +25,9 @@ fn create_retry_test_config() -> Config {
         base_url: "https://invalid-url-for-retry-test.example.com".to_string(),
         api_key: "invalid-api-key".to_string(),
         models: vec!["unhealthy-model".to_string()],
-        headers: HashMap::new(),
-        enabled: true,
         timeout_seconds: 5,
         max_retries: 1,
+        ..Default::default()
     });
 
     let mut models = HashMap::new();
@@ -42,8 +40,7 @@ fn create_retry_test_config() -> Config {
                 model: "unhealthy-model".to_string(),
                 weight: 0.8,
                 priority: 1,
-                enabled: true,
-                tags: vec![],
+                ..Default::default()
             },
             // 健康的provider作为备选
             Backend {
@@ -51,12 +48,11 @@ fn create_retry_test_config() -> Config {
                 model: "healthy-model".to_string(),
                 weight: 0.2,
                 priority: 2,
-                enabled: true,
-                tags: vec![],
+                ..Default::default()
             },
         ],
         strategy: LoadBalanceStrategy::WeightedFailover,
-        enabled: true,
+        ..Default::default()
     });
 
     Config {
@@ -72,7 +68,51 @@ fn create_retry_test_config() -> Config {
             recovery_check_interval_seconds: 60,
             max_internal_retries: 3, // 设置较高的重试次数
             health_check_timeout_seconds: 10,
+            failover_memory_ttl_seconds: 300,
+            model_degraded_ratio: 0.75,
+            model_down_ratio: 0.25,
+            model_health_hysteresis: 0.1,
+            health_webhook_urls: vec![],
+            readiness_required_models: Vec::new(),
+            config_hot_reload: true,
+            peer_max_hops: 1,
+            response_cache_enabled: false,
+            response_cache_ttl_seconds: 300,
+            response_cache_max_entries: 1000,
+            semantic_cache: None,
+            latency_ewma_alpha: 0.3,
+            audit_log: None,
+            keys_file: None,
+            max_embedding_batch_size: 2048,
+            max_request_body_mb: 32,
+            slow_start: Default::default(),
+            outlier_detection: None,
+            max_in_flight_requests: None,
+            priority_scheduling: None,
+            shared_state: None,
+            secrets: None,
+            config_history: Default::default(),
+            health_scoring: None,
+            region_routing: None,
+            content_filters: HashMap::new(),
+            moderation: None,
+            wasm_plugins: None,
+            notifications: None,
+            alerts: None,
+            slo: None,
+            request_log: None,
+            jwt: None,
+            network_acl: None,
+            cors: None,
+            grpc: None,
+            job_queue: None,
+            files: None,
+            stream_heartbeat: None,
+            stream_resumption: None,
+            request_capture: None,
+            fault_injection: false,
         },
+        ..Default::default()
     }
 }
 
@@ -0,0 +1,114 @@
+use berry_api_api::config::model::JobQueueSettings;
+use berry_api_api::jobs::{Job, JobQueue, JobStatus, JobStore};
+use serde_json::json;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+fn settings(path: &str) -> JobQueueSettings {
+    JobQueueSettings {
+        path: path.to_string(),
+        workers: 2,
+        max_attempts: 3,
+        initial_backoff_ms: 20,
+        max_backoff_ms: 50,
+        poll_interval_ms: 10,
+        retention_hours: 24,
+    }
+}
+
+fn temp_db(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("berry-jobs-{}-{}.db", name, std::process::id()));
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+    path.display().to_string()
+}
+
+/// 等待任务进入最终状态
+async fn wait_for(queue: &JobQueue, id: i64, status: JobStatus) -> Job {
+    for _ in 0..200 {
+        let job = queue.job(id).await.unwrap().unwrap();
+        if job.status == status {
+            return job;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("job {} did not reach {:?}", id, status);
+}
+
+#[tokio::test]
+async fn test_failed_jobs_retry_with_backoff() {
+    let path = temp_db("retry");
+    let queue = Arc::new(JobQueue::open(&settings(&path)).unwrap());
+    let calls = Arc::new(AtomicU32::new(0));
+    let counter = calls.clone();
+    queue.register("flaky", move |job: Job| {
+        let counter = counter.clone();
+        async move {
+            // 前两次失败，第三次成功
+            if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                anyhow::bail!("temporary failure for {}", job.payload["name"]);
+            }
+            Ok(())
+        }
+    });
+    queue.register("broken", |_job: Job| async { anyhow::bail!("always fails") });
+    queue.start().await.unwrap();
+
+    let flaky = queue.enqueue("flaky", json!({"name": "a"})).await.unwrap();
+    let broken = queue.enqueue("broken", json!({})).await.unwrap();
+
+    let job = wait_for(&queue, flaky, JobStatus::Succeeded).await;
+    assert_eq!(job.attempts, 3);
+    assert_eq!(job.last_error, None);
+    let job = wait_for(&queue, broken, JobStatus::Failed).await;
+    assert_eq!(job.attempts, 3);
+    assert_eq!(job.last_error.as_deref(), Some("always fails"));
+
+    assert_eq!(queue.backoff(1), Duration::from_millis(20));
+    assert_eq!(queue.backoff(2), Duration::from_millis(40));
+    assert_eq!(queue.backoff(10), Duration::from_millis(50));
+    queue.stop();
+}
+
+#[tokio::test]
+async fn test_jobs_survive_restart() {
+    let path = temp_db("restart");
+
+    // 模拟崩溃：任务已被领取但没有完成，另一个任务还在排队
+    let (interrupted, pending) = {
+        let store = JobStore::open(&path).unwrap();
+        let now = chrono::Utc::now().timestamp_millis();
+        let interrupted = store.enqueue("work", &json!({"step": 1}), 3, now).unwrap();
+        let pending = store.enqueue("work", &json!({"step": 2}), 3, now).unwrap();
+        store.claim(&["work".to_string()], now).unwrap().unwrap();
+        (interrupted, pending)
+    };
+
+    let queue = Arc::new(JobQueue::open(&settings(&path)).unwrap());
+    queue.register("work", |_job: Job| async { Ok(()) });
+    queue.start().await.unwrap();
+
+    let job = wait_for(&queue, interrupted, JobStatus::Succeeded).await;
+    assert_eq!(job.attempts, 2);
+    wait_for(&queue, pending, JobStatus::Succeeded).await;
+    assert_eq!(queue.counts().await.unwrap().get(&JobStatus::Succeeded), Some(&2));
+    queue.stop();
+}
+
+#[tokio::test]
+async fn test_jobs_without_handler_stay_pending() {
+    let path = temp_db("unhandled");
+    let queue = Arc::new(JobQueue::open(&settings(&path)).unwrap());
+    queue.start().await.unwrap();
+
+    let id = queue.enqueue("later", json!({})).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(queue.job(id).await.unwrap().unwrap().status, JobStatus::Pending);
+
+    // 注册处理器后开始执行
+    queue.register("later", |_job: Job| async { Ok(()) });
+    wait_for(&queue, id, JobStatus::Succeeded).await;
+    queue.stop();
+}
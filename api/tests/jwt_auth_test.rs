@@ -0,0 +1,165 @@
+use berry_api_api::config::model::{GlobalSettings, JwtProfile, JwtRole, JwtSettings, ModelMapping, Provider};
+use axum::routing::{get, post};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde_json::{Value, json};
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, provider, start_gateway};
+
+/// 启动一个本地上游和OIDC身份提供方，返回上游地址、签发者地址和签名私钥
+async fn spawn_upstream_and_issuer() -> (String, String, EncodingKey) {
+    let key_pair = rcgen::KeyPair::generate().unwrap();
+    // P-256公钥为未压缩的点：0x04 || x || y
+    let point = key_pair.public_key_raw();
+    let jwks = json!({
+        "keys": [{
+            "kty": "EC",
+            "crv": "P-256",
+            "kid": "test-key",
+            "alg": "ES256",
+            "use": "sig",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+        }]
+    });
+    let encoding_key = EncodingKey::from_ec_pem(key_pair.serialize_pem().as_bytes()).unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let issuer = format!("http://{}", addr);
+    let jwks_uri = format!("{}/jwks", issuer);
+    let app = axum::Router::new()
+        .route(
+            "/v1/chat/completions",
+            post(|| async {
+                axum::Json(json!({
+                    "id": "chatcmpl-test",
+                    "object": "chat.completion",
+                    "model": "gpt-4",
+                    "choices": [{"index": 0, "message": {"role": "assistant", "content": "ok"}, "finish_reason": "stop"}]
+                }))
+            }),
+        )
+        .route(
+            "/.well-known/openid-configuration",
+            get(move || async move { axum::Json(json!({ "jwks_uri": jwks_uri })) }),
+        )
+        .route("/jwks", get(move || async move { axum::Json(jwks) }));
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("{}/v1", issuer), issuer, encoding_key)
+}
+
+fn model(name: &str) -> ModelMapping {
+    ModelMapping {
+        name: name.to_string(),
+        backends: vec![backend("openai", 1)],
+        semantic_cache: false,
+        ..Default::default()
+    }
+}
+
+/// ml角色可以使用所有模型，其他已登录用户只能使用gpt-4
+fn jwt_settings(issuer: &str) -> GlobalSettings {
+    GlobalSettings {
+        jwt: Some(JwtSettings {
+            issuer: issuer.to_string(),
+            audience: vec!["berry".to_string()],
+            jwks_url: None,
+            jwks_refresh_seconds: 3600,
+            leeway_seconds: 0,
+            user_claim: "email".to_string(),
+            roles_claim: "roles".to_string(),
+            roles: vec![JwtRole {
+                role: "ml".to_string(),
+                profile: JwtProfile::default(),
+            }],
+            default_profile: Some(JwtProfile {
+                allowed_models: vec!["gpt-4".to_string()],
+                ..Default::default()
+            }),
+        }),
+        ..GlobalSettings::default()
+    }
+}
+
+fn sign(key: &EncodingKey, claims: Value) -> String {
+    let mut header = Header::new(Algorithm::ES256);
+    header.kid = Some("test-key".to_string());
+    jsonwebtoken::encode(&header, &claims, key).unwrap()
+}
+
+fn claims(issuer: &str, audience: &str, roles: &[&str]) -> Value {
+    json!({
+        "iss": issuer,
+        "aud": audience,
+        "email": "alice@example.com",
+        "roles": roles,
+        "exp": chrono::Utc::now().timestamp() + 300,
+    })
+}
+
+#[tokio::test]
+async fn test_jwt_roles_map_to_user_profiles() {
+    let (base_url, issuer, key) = spawn_upstream_and_issuer().await;
+    let config = ConfigBuilder::new()
+        .provider("openai", Provider {
+            name: "openai".to_string(),
+            ..provider(&base_url)
+        })
+        .model("gpt-4", model("gpt-4"))
+        .model("gpt-4-large", model("gpt-4-large"))
+        .settings(jwt_settings(&issuer))
+        .build();
+    let url = start_gateway(config).await.0;
+
+    let ml = sign(&key, claims(&issuer, "berry", &["ml"]));
+    assert!(chat(&url, &ml, chat_body("gpt-4-large", "hi")).await.status().is_success());
+
+    // 没有匹配角色的用户使用default_profile
+    let staff = sign(&key, claims(&issuer, "berry", &["staff"]));
+    assert!(chat(&url, &staff, chat_body("gpt-4", "hi")).await.status().is_success());
+    assert_eq!(chat(&url, &staff, chat_body("gpt-4-large", "hi")).await.status(), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_invalid_jwts_are_rejected() {
+    let (base_url, issuer, key) = spawn_upstream_and_issuer().await;
+    let config = ConfigBuilder::new()
+        .provider("openai", Provider {
+            name: "openai".to_string(),
+            ..provider(&base_url)
+        })
+        .model("gpt-4", model("gpt-4"))
+        .model("gpt-4-large", model("gpt-4-large"))
+        .settings(jwt_settings(&issuer))
+        .build();
+    let url = start_gateway(config).await.0;
+
+    let wrong_audience = sign(&key, claims(&issuer, "other", &["ml"]));
+    assert_eq!(chat(&url, &wrong_audience, chat_body("gpt-4", "hi")).await.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let wrong_issuer = sign(&key, claims("https://evil.example.com", "berry", &["ml"]));
+    assert_eq!(chat(&url, &wrong_issuer, chat_body("gpt-4", "hi")).await.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let mut expired = claims(&issuer, "berry", &["ml"]);
+    expired["exp"] = json!(chrono::Utc::now().timestamp() - 60);
+    assert_eq!(chat(&url, &sign(&key, expired), chat_body("gpt-4", "hi")).await.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // 其他密钥签名的令牌
+    let other_key = EncodingKey::from_ec_pem(rcgen::KeyPair::generate().unwrap().serialize_pem().as_bytes()).unwrap();
+    let forged = sign(&other_key, claims(&issuer, "berry", &["ml"]));
+    assert_eq!(chat(&url, &forged, chat_body("gpt-4", "hi")).await.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // 对称签名的令牌
+    let hmac = jsonwebtoken::encode(
+        &Header::new(Algorithm::HS256),
+        &claims(&issuer, "berry", &["ml"]),
+        &EncodingKey::from_secret(b"secret"),
+    )
+    .unwrap();
+    assert_eq!(chat(&url, &hmac, chat_body("gpt-4", "hi")).await.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
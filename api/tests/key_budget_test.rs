@@ -0,0 +1,127 @@
+use berry_api_api::config::model::{KeyBudget, LoadBalanceStrategy, ModelMapping};
+use axum::routing::post;
+use serde_json::{Value, json};
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, reply_content, serve, start_gateway, user};
+
+/// 启动一个本地上游，返回的内容为上游名称并附带usage
+async fn spawn_upstream(name: &'static str) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || async move {
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "model": "gpt-4",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": name}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+#[tokio::test]
+async fn test_exhausted_key_is_excluded_and_alerted() {
+    let mut primary = provider(&spawn_upstream("primary").await);
+    primary.key_budget = Some(KeyBudget {
+        monthly_cost: None,
+        monthly_tokens: Some(20),
+        alert_percent: 50.0,
+    });
+    let config = ConfigBuilder::new()
+        .provider("primary", primary)
+        .provider("backup", provider(&spawn_upstream("backup").await))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("primary", 1), backend("backup", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let (gateway, load_balancer) = start_gateway(config).await;
+    let mut events = load_balancer.get_events().subscribe();
+
+    // 每次请求消耗15个token，上限为20
+    assert_eq!(reply_content(chat(&gateway, "user-token", chat_body("gpt-4", "hi")).await).await, "primary");
+    assert_eq!(reply_content(chat(&gateway, "user-token", chat_body("gpt-4", "hi")).await).await, "primary");
+    assert!(load_balancer.get_metrics().is_key_exhausted("primary"));
+    assert_eq!(reply_content(chat(&gateway, "user-token", chat_body("gpt-4", "hi")).await).await, "backup");
+
+    let mut alerts = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if event.event == "key_budget_alert" {
+            alerts.push(event.details);
+        }
+    }
+    assert_eq!(alerts.len(), 2);
+    assert_eq!(alerts[0]["exhausted"], json!(false));
+    assert_eq!(alerts[1]["exhausted"], json!(true));
+    assert_eq!(alerts[1]["provider"], json!("primary"));
+
+    let metrics: Value = reqwest::get(format!("{}/metrics", gateway)).await.unwrap().json().await.unwrap();
+    let key_spend = metrics["key_spend"].as_array().unwrap();
+    assert_eq!(key_spend.len(), 2);
+    assert_eq!(key_spend[1]["provider"], json!("primary"));
+    assert_eq!(key_spend[1]["requests"], json!(2));
+    assert_eq!(key_spend[1]["monthly_token_limit"], json!(20));
+    assert_eq!(key_spend[1]["exhausted"], json!(true));
+    assert_eq!(key_spend[0]["exhausted"], json!(false));
+}
+
+#[tokio::test]
+async fn test_raising_budget_reenables_key() {
+    let mut primary = provider(&spawn_upstream("primary").await);
+    primary.key_budget = Some(KeyBudget {
+        monthly_cost: None,
+        monthly_tokens: Some(20),
+        alert_percent: 50.0,
+    });
+    let config = ConfigBuilder::new()
+        .provider("primary", primary)
+        .provider("backup", provider(&spawn_upstream("backup").await))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("primary", 1), backend("backup", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let (gateway, load_balancer) = start_gateway(config.clone()).await;
+    reply_content(chat(&gateway, "user-token", chat_body("gpt-4", "hi")).await).await;
+    reply_content(chat(&gateway, "user-token", chat_body("gpt-4", "hi")).await).await;
+    assert_eq!(reply_content(chat(&gateway, "user-token", chat_body("gpt-4", "hi")).await).await, "backup");
+
+    let mut raised = config;
+    raised.providers.get_mut("primary").unwrap().key_budget.as_mut().unwrap().monthly_tokens = Some(1000);
+    load_balancer.reload_config(raised).await.unwrap();
+    assert_eq!(reply_content(chat(&gateway, "user-token", chat_body("gpt-4", "hi")).await).await, "primary");
+}
+
+#[tokio::test]
+async fn test_key_budget_validation() {
+    let mut primary = provider(&spawn_upstream("primary").await);
+    primary.key_budget = Some(KeyBudget {
+        monthly_cost: None,
+        monthly_tokens: Some(20),
+        alert_percent: 50.0,
+    });
+    let mut config = ConfigBuilder::new()
+        .provider("primary", primary)
+        .provider("backup", provider(&spawn_upstream("backup").await))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("primary", 1), backend("backup", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    config.providers.get_mut("primary").unwrap().key_budget = Some(KeyBudget {
+        monthly_cost: None,
+        monthly_tokens: None,
+        alert_percent: 80.0,
+    });
+    let error = config.validate().unwrap_err();
+    assert!(error.to_string().contains("key_budget must set monthly_cost or monthly_tokens"));
+}
@@ -0,0 +1,112 @@
+use berry_api_api::app::{AppState, create_app};
+use berry_api_api::config::model::{Backend, LoadBalanceStrategy, ModelMapping};
+use axum::http::{HeaderValue, StatusCode};
+use axum::routing::post;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+mod common;
+use common::{ConfigBuilder, create_state, provider_with_models, serve, user};
+
+/// 启动一个提供文本补全和内容审核接口的本地上游
+async fn spawn_upstream() -> String {
+    let app = axum::Router::new()
+        .route(
+            "/v1/completions",
+            post(|axum::Json(body): axum::Json<Value>| async move {
+                axum::Json(json!({
+                    "object": "text_completion",
+                    "model": body["model"],
+                    "choices": [{"index": 0, "text": "ok", "finish_reason": "stop"}],
+                    "usage": {"prompt_tokens": 3, "completion_tokens": 1, "total_tokens": 4}
+                }))
+            }),
+        )
+        .route(
+            "/v1/moderations",
+            post(|axum::Json(body): axum::Json<Value>| async move {
+                axum::Json(json!({
+                    "id": "modr-test",
+                    "model": body["model"],
+                    "results": [{"flagged": false}]
+                }))
+            }),
+        );
+    format!("{}/v1", serve(app).await)
+}
+
+fn mapping(model: &str) -> ModelMapping {
+    let backend = |provider: &str| Backend {
+        provider: provider.to_string(),
+        model: model.to_string(),
+        priority: 1,
+        ..Default::default()
+    };
+    ModelMapping {
+        name: model.to_string(),
+        backends: vec![backend("broken"), backend("working")],
+        strategy: LoadBalanceStrategy::RoundRobin,
+        ..Default::default()
+    }
+}
+
+/// 两个provider都提供文本补全和内容审核模型，其中一个无法连接
+async fn create_test_state(upstream: &str) -> AppState {
+    let models_served = ["gpt-3.5-turbo-instruct", "omni-moderation-latest"];
+    let config = ConfigBuilder::new()
+        // 端口1上没有服务，连接会立即被拒绝
+        .provider("broken", provider_with_models("http://127.0.0.1:1/v1", &models_served))
+        .provider("working", provider_with_models(upstream, &models_served))
+        .model(models_served[0], mapping(models_served[0]))
+        .model(models_served[1], mapping(models_served[1]))
+        .user("user", user("User", "user-token"))
+        .build();
+    create_state(config).await
+}
+
+fn auth() -> HeaderValue {
+    HeaderValue::from_static("Bearer user-token")
+}
+
+#[tokio::test]
+async fn test_legacy_completions_routed_to_healthy_backend() {
+    let upstream = spawn_upstream().await;
+    let state = create_test_state(&upstream).await;
+    // 无法连接的后端已被标记为不健康，请求应转到可用后端
+    state.load_balancer.get_metrics().record_failure("broken:gpt-3.5-turbo-instruct");
+    let server = TestServer::new(create_app(state)).unwrap();
+
+    let response = server
+        .post("/v1/completions")
+        .add_header(axum::http::header::AUTHORIZATION, auth())
+        .json(&json!({ "model": "gpt-3.5-turbo-instruct", "prompt": "Say ok" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let body: Value = response.json();
+    assert_eq!(body["object"], "text_completion");
+    assert_eq!(body["choices"][0]["text"], "ok");
+}
+
+#[tokio::test]
+async fn test_moderations_passthrough() {
+    let upstream = spawn_upstream().await;
+    let server = TestServer::new(create_app(create_test_state(&upstream).await)).unwrap();
+
+    for _ in 0..2 {
+        let response = server
+            .post("/v1/moderations")
+            .add_header(axum::http::header::AUTHORIZATION, auth())
+            .json(&json!({ "model": "omni-moderation-latest", "input": "hello" }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: Value = response.json();
+        assert_eq!(body["results"][0]["flagged"], false);
+    }
+
+    let response = server
+        .post("/v1/moderations")
+        .add_header(axum::http::header::AUTHORIZATION, auth())
+        .json(&json!({ "input": "hello" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,158 @@
+use berry_api_api::app::create_app_with_middleware;
+use berry_api_api::config::model::{LoadBalanceStrategy, ModelMapping, Provider};
+use berry_api_api::middleware::{BerryMiddleware, MiddlewareRequest, MiddlewareStack, RequestInfo};
+use axum::body::Bytes;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use futures::future::BoxFuture;
+use serde_json::{Value, json};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+mod common;
+use common::{ConfigBuilder, app_state, backend, chat_body, mapping, provider, serve, start_load_balancer, user};
+
+/// 启动一个本地上游，回复内容为请求体中的user字段
+async fn spawn_upstream() -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(|axum::Json(body): axum::Json<Value>| async move {
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": body["user"]},
+                    "finish_reason": "stop"
+                }]
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+/// 按x-tenant请求头认证：换成网关的API密钥，并把租户写入请求体的user字段
+struct TenantAuth;
+
+impl BerryMiddleware for TenantAuth {
+    fn on_request<'a>(&'a self, request: &'a mut MiddlewareRequest) -> BoxFuture<'a, Option<Response>> {
+        Box::pin(async move {
+            let tenant = request.parts.headers.remove("x-tenant");
+            if tenant.as_ref().and_then(|t| t.to_str().ok()) != Some("acme") {
+                return Some((axum::http::StatusCode::UNAUTHORIZED, "unknown tenant").into_response());
+            }
+            request
+                .parts
+                .headers
+                .insert("authorization", "Bearer user-token".parse().unwrap());
+            let mut body: Value = serde_json::from_slice(&request.body).unwrap();
+            body["user"] = json!("acme");
+            request.body = Bytes::from(body.to_string());
+            None
+        })
+    }
+}
+
+/// 记录每个请求的路径、状态码和响应字节数，并注入响应头
+#[derive(Default)]
+struct AccessLog {
+    statuses: Mutex<Vec<(String, u16)>>,
+    bytes: AtomicUsize,
+}
+
+impl BerryMiddleware for AccessLog {
+    fn on_response(&self, request: &RequestInfo, response: &mut axum::http::response::Parts) {
+        self.statuses
+            .lock()
+            .unwrap()
+            .push((request.uri.path().to_string(), response.status.as_u16()));
+        response.headers.insert("x-access-log", "recorded".parse().unwrap());
+    }
+
+    fn on_chunk(&self, _request: &RequestInfo, chunk: Bytes) -> Bytes {
+        self.bytes.fetch_add(chunk.len(), Ordering::SeqCst);
+        chunk
+    }
+}
+
+#[tokio::test]
+async fn test_middleware_authenticates_and_rewrites_requests() {
+    let base_url = spawn_upstream().await;
+    let log = Arc::new(AccessLog::default());
+    let mut middleware = MiddlewareStack::new();
+    middleware.push(log.clone());
+    let middleware = middleware.with(TenantAuth);
+    let config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            ..provider(&base_url)
+        })
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::RoundRobin,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let server = serve(create_app_with_middleware(app_state(start_load_balancer(config).await), middleware)).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", server))
+        .header("x-tenant", "acme")
+        .json(&chat_body("gpt-4", "hello"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-access-log"], "recorded");
+    let text = response.text().await.unwrap();
+    let body: Value = serde_json::from_str(text.trim()).unwrap();
+    assert_eq!(body["choices"][0]["message"]["content"], "acme");
+    assert_eq!(log.bytes.load(Ordering::SeqCst), text.len());
+
+    // 认证失败的响应同样经过响应钩子
+    let response = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", server))
+        .header("x-tenant", "other")
+        .json(&chat_body("gpt-4", "hello"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.headers()["x-access-log"], "recorded");
+
+    assert_eq!(
+        log.statuses.lock().unwrap().as_slice(),
+        [
+            ("/v1/chat/completions".to_string(), 200),
+            ("/v1/chat/completions".to_string(), 401)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_empty_middleware_stack_keeps_default_behavior() {
+    let base_url = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            ..provider(&base_url)
+        })
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::RoundRobin,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let server = serve(create_app_with_middleware(app_state(start_load_balancer(config).await), MiddlewareStack::new())).await;
+
+    // 没有注册认证中间件时缺少Authorization请求头
+    let response = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", server))
+        .header("x-tenant", "acme")
+        .json(&chat_body("gpt-4", "hello"))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_client_error());
+    assert!(response.headers().get("x-access-log").is_none());
+}
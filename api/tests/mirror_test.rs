@@ -0,0 +1,105 @@
+use berry_api_api::config::model::{MirrorSettings, ModelMapping};
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, provider_with_models, reply_content, serve, start_gateway, user};
+
+/// 启动一个正常返回回复的本地上游
+async fn spawn_primary_upstream() -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(|| async {
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "model": "gpt-4",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "primary"}, "finish_reason": "stop"}]
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+/// 启动一个记录收到的请求体并总是返回500的镜像上游
+async fn spawn_mirror_upstream(received: Arc<Mutex<Vec<Value>>>) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move |axum::Json(body): axum::Json<Value>| async move {
+            received.lock().unwrap().push(body);
+            (axum::http::StatusCode::INTERNAL_SERVER_ERROR, "mirror failure")
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+#[tokio::test]
+async fn test_requests_are_mirrored_without_affecting_clients() {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let primary_url = spawn_primary_upstream().await;
+    let mirror_url = spawn_mirror_upstream(received.clone()).await;
+    let config = ConfigBuilder::new()
+        .provider("primary", provider(&primary_url))
+        .provider("candidate", provider_with_models(&mirror_url, &["candidate-model"]))
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            mirror: Some(MirrorSettings {
+                provider: "candidate".to_string(),
+                model: "candidate-model".to_string(),
+                percent: 100.0,
+            }),
+            ..mapping("gpt-4", vec![backend("primary", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let (gateway, load_balancer) = start_gateway(config).await;
+
+    for i in 0..3 {
+        let response = chat(&gateway, "user-token", chat_body("gpt-4", &format!("question {}", i))).await;
+        assert_eq!(reply_content(response).await, "primary");
+    }
+
+    // 镜像请求在后台发送，等待其完成
+    for _ in 0..50 {
+        if received.lock().unwrap().len() == 3 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 3);
+    assert!(received.iter().all(|body| body["model"] == "candidate-model"));
+
+    // 镜像失败不影响主后端的健康状态
+    let metrics = load_balancer.get_metrics();
+    assert!(metrics.is_healthy("primary", "gpt-4"));
+    assert_eq!(metrics.get_failure_count("candidate", "candidate-model"), 0);
+}
+
+#[test]
+fn test_mirror_validation() {
+    let mut config = ConfigBuilder::new()
+        .provider("primary", provider("http://127.0.0.1:1/v1"))
+        .provider("candidate", provider_with_models("http://127.0.0.1:2/v1", &["candidate-model"]))
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            mirror: Some(MirrorSettings {
+                provider: "candidate".to_string(),
+                model: "candidate-model".to_string(),
+                percent: 0.0,
+            }),
+            ..mapping("gpt-4", vec![backend("primary", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let error = config.validate().unwrap_err();
+    assert!(error.to_string().contains("mirror percent"));
+
+    let mirror = config.models.get_mut("gpt-4").unwrap().mirror.as_mut().unwrap();
+    mirror.percent = 10.0;
+    mirror.provider = "missing".to_string();
+    let error = config.validate().unwrap_err();
+    assert!(error.to_string().contains("unknown provider 'missing'"));
+}
@@ -0,0 +1,172 @@
+use berry_api_api::app::create_app;
+use berry_api_api::config::model::{AuditLogSettings, AuditSink, GlobalSettings, LoadBalanceStrategy, ModelMapping, ModerationAction, ModerationService, ModerationSettings, Provider};
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, create_state, mapping, provider, serve, user};
+
+/// 启动本地上游：审核接口对包含"attack"的输入返回violence命中，聊天接口统计调用次数
+async fn spawn_upstream() -> (String, Arc<AtomicUsize>) {
+    let completions = Arc::new(AtomicUsize::new(0));
+    let counter = completions.clone();
+    let app = axum::Router::new()
+        .route(
+            "/v1/moderations",
+            post(|axum::Json(body): axum::Json<Value>| async move {
+                let results: Vec<Value> = body["input"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|input| {
+                        let violent = input.as_str().unwrap().contains("attack");
+                        json!({"flagged": violent, "categories": {"violence": violent, "hate": false}})
+                    })
+                    .collect();
+                axum::Json(json!({"id": "modr-test", "results": results}))
+            }),
+        )
+        .route(
+            "/v1/chat/completions",
+            post(move || {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    axum::Json(json!({
+                        "id": "chatcmpl-test",
+                        "object": "chat.completion",
+                        "choices": [{
+                            "index": 0,
+                            "message": {"role": "assistant", "content": "ok"},
+                            "finish_reason": "stop"
+                        }]
+                    }))
+                }
+            }),
+        );
+    (format!("{}/v1", serve(app).await), completions)
+}
+
+fn moderation(service: ModerationService, action: ModerationAction, fail_open: bool) -> ModerationSettings {
+    ModerationSettings {
+        service,
+        model: None,
+        action,
+        categories: Vec::new(),
+        timeout_seconds: 2,
+        fail_open,
+    }
+}
+
+/// 启动按moderation审核请求的网关，审计日志写入audit_path
+async fn start_server(base_url: &str, audit_path: &str, moderation: ModerationSettings) -> String {
+    let config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            ..provider(base_url)
+        })
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::RoundRobin,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .settings(GlobalSettings {
+            audit_log: Some(AuditLogSettings {
+                sink: AuditSink::File {
+                    path: audit_path.to_string(),
+                    max_size_mb: 10,
+                    max_files: 1,
+                },
+                redact_content: false,
+            }),
+            moderation: Some(moderation),
+            ..GlobalSettings::default()
+        })
+        .build();
+    serve(create_app(create_state(config).await)).await
+}
+
+/// 等待后台任务写出审计记录
+async fn read_audit_records(path: &std::path::Path, count: usize) -> Vec<Value> {
+    for _ in 0..50 {
+        let content = std::fs::read_to_string(path).unwrap_or_default();
+        if content.lines().count() >= count {
+            return content.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("audit log did not receive {} records", count);
+}
+
+#[tokio::test]
+async fn test_flagged_request_is_blocked_and_audited() {
+    let path = std::env::temp_dir().join(format!("berry-moderation-block-test-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let (base_url, completions) = spawn_upstream().await;
+    let service = ModerationService::Provider { provider: "upstream".to_string() };
+    let server = start_server(&base_url, &path.to_string_lossy(), moderation(service, ModerationAction::Block, true)).await;
+
+    let response = chat(&server, "user-token", chat_body("gpt-4", "plan an attack")).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "content_policy_violation");
+    assert!(body["error"]["details"].as_str().unwrap().contains("violence"));
+    assert_eq!(completions.load(Ordering::SeqCst), 0);
+
+    let response = chat(&server, "user-token", chat_body("gpt-4", "hello")).await;
+    assert_eq!(response.status(), 200);
+    response.text().await.unwrap();
+    assert_eq!(completions.load(Ordering::SeqCst), 1);
+
+    let records = read_audit_records(&path, 2).await;
+    let blocked = records.iter().find(|r| r["status"] == 400).unwrap();
+    assert_eq!(blocked["moderation"], json!({"flagged": true, "categories": ["violence"], "blocked": true}));
+    let allowed = records.iter().find(|r| r["status"] == 200).unwrap();
+    assert_eq!(allowed["moderation"]["flagged"], false);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_flag_action_forwards_request() {
+    let path = std::env::temp_dir().join(format!("berry-moderation-flag-test-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let (base_url, completions) = spawn_upstream().await;
+    let service = ModerationService::Endpoint {
+        url: format!("{}/moderations", base_url),
+        api_key: Some("moderation-key".to_string()),
+    };
+    let server = start_server(&base_url, &path.to_string_lossy(), moderation(service, ModerationAction::Flag, true)).await;
+
+    let response = chat(&server, "user-token", chat_body("gpt-4", "plan an attack")).await;
+    assert_eq!(response.status(), 200);
+    response.text().await.unwrap();
+    assert_eq!(completions.load(Ordering::SeqCst), 1);
+
+    let records = read_audit_records(&path, 1).await;
+    assert_eq!(records[0]["moderation"], json!({"flagged": true, "categories": ["violence"], "blocked": false}));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_unavailable_moderation_respects_fail_open() {
+    let (base_url, completions) = spawn_upstream().await;
+    let unreachable = || ModerationService::Endpoint {
+        url: "http://127.0.0.1:1/v1/moderations".to_string(),
+        api_key: None,
+    };
+
+    let server = start_server(&base_url, "/dev/null", moderation(unreachable(), ModerationAction::Block, false)).await;
+    let response = chat(&server, "user-token", chat_body("gpt-4", "hello")).await;
+    assert_eq!(response.status(), 503);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "moderation_unavailable");
+    assert_eq!(completions.load(Ordering::SeqCst), 0);
+
+    let server = start_server(&base_url, "/dev/null", moderation(unreachable(), ModerationAction::Block, true)).await;
+    let response = chat(&server, "user-token", chat_body("gpt-4", "hello")).await;
+    assert_eq!(response.status(), 200);
+    response.text().await.unwrap();
+    assert_eq!(completions.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,156 @@
+use berry_api_api::config::model::{AuditLogSettings, AuditSink, GlobalSettings, ModelMapping, NetworkAclSettings, Provider, UserToken, parse_ip_net};
+use serde_json::{Value, json};
+
+mod common;
+use common::{ConfigBuilder, backend, mapping, provider, start_gateway};
+
+fn user(name: &str, token: &str, tags: Vec<String>, allowed_ips: &[&str]) -> UserToken {
+    UserToken {
+        tags,
+        allowed_ips: allowed_ips.iter().map(|ip| parse_ip_net(ip).unwrap()).collect(),
+        ..common::user(name, token)
+    }
+}
+
+async fn list_models(url: &str, token: &str, forwarded_for: Option<&str>) -> reqwest::StatusCode {
+    let mut request = reqwest::Client::new()
+        .get(format!("{}/v1/models", url))
+        .bearer_auth(token);
+    if let Some(forwarded_for) = forwarded_for {
+        request = request.header("x-forwarded-for", forwarded_for);
+    }
+    request.send().await.unwrap().status()
+}
+
+#[tokio::test]
+async fn test_global_denylist_is_audited() {
+    let audit_path = std::env::temp_dir().join(format!("berry-network-acl-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&audit_path);
+    let mut config = ConfigBuilder::new()
+        .provider("openai", Provider {
+            name: "openai".to_string(),
+            ..provider("http://127.0.0.1:9/v1")
+        })
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("openai", 1)])
+        })
+        .user("admin", user("admin", "admin-token", vec!["admin".to_string()], &[]))
+        .user("office", user("office", "office-token", vec![], &["10.0.0.0/8"]))
+        .user("local", user("local", "local-token", vec![], &["127.0.0.1"]))
+        .settings(GlobalSettings {
+            network_acl: Some(NetworkAclSettings {
+                deny: vec!["127.0.0.0/8".parse().unwrap()],
+                ..Default::default()
+            }),
+            ..GlobalSettings::default()
+        })
+        .build();
+    config.settings.audit_log = Some(AuditLogSettings {
+        sink: AuditSink::File {
+            path: audit_path.to_string_lossy().to_string(),
+            max_size_mb: 10,
+            max_files: 1,
+        },
+        redact_content: true,
+    });
+    let url = start_gateway(config).await.0;
+
+    assert_eq!(list_models(&url, "admin-token", None).await, reqwest::StatusCode::FORBIDDEN);
+    // 健康探针不受访问控制
+    let response = reqwest::get(format!("{}/healthz", url)).await.unwrap();
+    assert!(response.status().is_success());
+
+    let mut record = None;
+    for _ in 0..50 {
+        if let Ok(content) = std::fs::read_to_string(&audit_path)
+            && let Some(line) = content.lines().next()
+        {
+            record = Some(serde_json::from_str::<Value>(line).unwrap());
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let _ = std::fs::remove_file(&audit_path);
+    let record = record.expect("rejection was not audited");
+    assert_eq!(record["user"], "admin");
+    assert_eq!(record["status"], 403);
+    assert_eq!(record["client_ip"], "127.0.0.1");
+}
+
+#[tokio::test]
+async fn test_allowlist_uses_forwarded_for_from_trusted_proxies() {
+    let config = ConfigBuilder::new()
+        .provider("openai", Provider {
+            name: "openai".to_string(),
+            ..provider("http://127.0.0.1:9/v1")
+        })
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("openai", 1)])
+        })
+        .user("admin", user("admin", "admin-token", vec!["admin".to_string()], &[]))
+        .user("office", user("office", "office-token", vec![], &["10.0.0.0/8"]))
+        .user("local", user("local", "local-token", vec![], &["127.0.0.1"]))
+        .settings(GlobalSettings {
+            network_acl: Some(NetworkAclSettings {
+                allow: vec!["10.0.0.0/8".parse().unwrap()],
+                trusted_proxies: vec!["127.0.0.1/32".parse().unwrap()],
+                ..Default::default()
+            }),
+            ..GlobalSettings::default()
+        })
+        .build();
+    let url = start_gateway(config).await.0;
+
+    assert_eq!(list_models(&url, "admin-token", None).await, reqwest::StatusCode::FORBIDDEN);
+    assert!(list_models(&url, "admin-token", Some("10.1.2.3")).await.is_success());
+    // 客户端可以伪造最左侧的地址，只信任代理追加的部分
+    assert_eq!(
+        list_models(&url, "admin-token", Some("10.1.2.3, 192.168.1.1")).await,
+        reqwest::StatusCode::FORBIDDEN
+    );
+    assert!(list_models(&url, "office-token", Some("192.168.1.1, 10.1.2.3")).await.is_success());
+}
+
+#[tokio::test]
+async fn test_api_keys_bound_to_source_ips() {
+    let config = ConfigBuilder::new()
+        .provider("openai", Provider {
+            name: "openai".to_string(),
+            ..provider("http://127.0.0.1:9/v1")
+        })
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("openai", 1)])
+        })
+        .user("admin", user("admin", "admin-token", vec!["admin".to_string()], &[]))
+        .user("office", user("office", "office-token", vec![], &["10.0.0.0/8"]))
+        .user("local", user("local", "local-token", vec![], &["127.0.0.1"]))
+        .settings(GlobalSettings {
+            network_acl: None,
+            ..GlobalSettings::default()
+        })
+        .build();
+    let url = start_gateway(config).await.0;
+
+    assert!(list_models(&url, "local-token", None).await.is_success());
+    assert_eq!(list_models(&url, "office-token", None).await, reqwest::StatusCode::FORBIDDEN);
+    // 未配置可信代理时忽略X-Forwarded-For
+    assert_eq!(list_models(&url, "office-token", Some("10.1.2.3")).await, reqwest::StatusCode::FORBIDDEN);
+
+    let client = reqwest::Client::new();
+    let created: Value = client
+        .post(format!("{}/admin/keys", url))
+        .bearer_auth("admin-token")
+        .json(&json!({ "name": "ci", "allowed_ips": ["10.0.0.0/8"] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(created["allowed_ips"], json!(["10.0.0.0/8"]));
+    let token = created["token"].as_str().unwrap();
+    assert_eq!(list_models(&url, token, None).await, reqwest::StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,128 @@
+use berry_api_api::config::model::{Config, LoadBalanceStrategy, ModelMapping, RateLimit, SharedStateSettings, UserToken};
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, serve, start_gateway, user};
+
+/// 启动一个返回非流式回复的本地上游，每次回复消耗600个token
+async fn spawn_upstream(name: &'static str) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || async move {
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": name},
+                    "finish_reason": "stop"
+                }],
+                "usage": {"prompt_tokens": 500, "completion_tokens": 100, "total_tokens": 600}
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+fn rate_limit(config: &mut Config) -> &mut RateLimit {
+    config.users.get_mut("user").unwrap().rate_limit.as_mut().unwrap()
+}
+
+#[tokio::test]
+async fn test_requests_per_minute_limit() {
+    let url = spawn_upstream("local").await;
+    let mut config = ConfigBuilder::new()
+        .provider("local", provider(&url))
+        .provider("cloud", provider(&url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", UserToken {
+            rate_limit: Some(RateLimit {
+                requests_per_minute: 0,
+                requests_per_hour: 0,
+                requests_per_day: 0,
+                tokens_per_minute: None,
+            }),
+            ..user("User", "user-token")
+        })
+        .build();
+    rate_limit(&mut config).requests_per_minute = 2;
+    let gateway = start_gateway(config).await.0;
+
+    assert!(chat(&gateway, "user-token", chat_body("gpt-4", "first")).await.status().is_success());
+    assert!(chat(&gateway, "user-token", chat_body("gpt-4", "second")).await.status().is_success());
+    let limited = chat(&gateway, "user-token", chat_body("gpt-4", "third")).await;
+    assert_eq!(limited.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    let body: Value = limited.json().await.unwrap();
+    assert!(body["error"]["details"].as_str().unwrap().contains("2 requests per minute"));
+}
+
+#[tokio::test]
+async fn test_tokens_per_minute_limit_uses_upstream_usage() {
+    let url = spawn_upstream("local").await;
+    let mut config = ConfigBuilder::new()
+        .provider("local", provider(&url))
+        .provider("cloud", provider(&url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", UserToken {
+            rate_limit: Some(RateLimit {
+                requests_per_minute: 0,
+                requests_per_hour: 0,
+                requests_per_day: 0,
+                tokens_per_minute: None,
+            }),
+            ..user("User", "user-token")
+        })
+        .build();
+    rate_limit(&mut config).tokens_per_minute = Some(1000);
+    let gateway = start_gateway(config).await.0;
+
+    // 每次回复消耗600个token，第二个请求完成后额度耗尽
+    for content in ["first", "second"] {
+        let response = chat(&gateway, "user-token", chat_body("gpt-4", content)).await;
+        assert!(response.status().is_success());
+        response.text().await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let limited = chat(&gateway, "user-token", chat_body("gpt-4", "third")).await;
+    assert_eq!(limited.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    let body: Value = limited.json().await.unwrap();
+    assert!(body["error"]["details"].as_str().unwrap().contains("1000 tokens per minute"));
+}
+
+#[test]
+fn test_shared_state_validation() {
+    let mut config = ConfigBuilder::new()
+        .provider("local", provider("http://127.0.0.1:1/v1"))
+        .provider("cloud", provider("http://127.0.0.1:2/v1"))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", UserToken {
+            rate_limit: Some(RateLimit {
+                requests_per_minute: 0,
+                requests_per_hour: 0,
+                requests_per_day: 0,
+                tokens_per_minute: None,
+            }),
+            ..user("User", "user-token")
+        })
+        .build();
+    config.settings.shared_state = Some(SharedStateSettings {
+        redis_url: "127.0.0.1:6379".to_string(),
+        key_prefix: "berry".to_string(),
+    });
+    assert!(config.validate().is_err());
+
+    config.settings.shared_state.as_mut().unwrap().redis_url = "redis://127.0.0.1:6379/0".to_string();
+    assert!(config.validate().is_ok());
+}
@@ -0,0 +1,168 @@
+use berry_api_api::config::model::{Backend, ModelMapping, Provider};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::routing::get;
+use futures::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest, protocol::frame::coding::CloseCode};
+
+mod common;
+use common::{ConfigBuilder, backend, mapping, provider, serve, start_gateway, user};
+
+/// 模拟的Realtime后端：回显文本消息，收到usage时返回response.done，收到fail时直接断开连接
+async fn upstream_session(mut socket: WebSocket) {
+    while let Some(Ok(message)) = socket.recv().await {
+        match message {
+            Message::Text(text) if text.as_str() == "usage" => {
+                let done = json!({
+                    "type": "response.done",
+                    "response": {"usage": {"input_tokens": 100, "output_tokens": 40}}
+                });
+                let _ = socket.send(Message::Text(done.to_string().into())).await;
+            }
+            Message::Text(text) if text.as_str() == "fail" => return,
+            Message::Text(text) => {
+                let _ = socket.send(Message::Text(text)).await;
+            }
+            Message::Close(_) => return,
+            _ => {}
+        }
+    }
+}
+
+async fn spawn_upstream() -> String {
+    let app = axum::Router::new().route(
+        "/v1/realtime",
+        get(|upgrade: WebSocketUpgrade| async move { upgrade.on_upgrade(upstream_session) }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+async fn connect(
+    url: &str,
+    header: (&'static str, &str),
+) -> Result<tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>, tungstenite::Error> {
+    let mut request = format!("{}/v1/realtime?model=gpt-4o-realtime", url.replacen("http", "ws", 1))
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert(header.0, header.1.parse().unwrap());
+    tokio_tungstenite::connect_async(request).await.map(|(socket, _)| socket)
+}
+
+async fn next_text(
+    socket: &mut tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
+) -> String {
+    match socket.next().await.unwrap().unwrap() {
+        tungstenite::Message::Text(text) => text.to_string(),
+        other => panic!("unexpected message: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_realtime_session_is_proxied_and_billed() {
+    let config = ConfigBuilder::new()
+        .provider("openai", Provider {
+            name: "openai".to_string(),
+            models: vec!["gpt-4o-realtime".to_string()],
+            ..provider(&spawn_upstream().await)
+        })
+        .model("gpt-4o-realtime", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4o-realtime", vec![Backend {
+                model: "gpt-4o-realtime".to_string(),
+                ..backend("openai", 1)
+            }])
+        })
+        .user("alice", user("alice", "alice-token"))
+        .build();
+    let (url, load_balancer) = start_gateway(config).await;
+
+    let mut socket = connect(&url, ("authorization", "Bearer alice-token")).await.unwrap();
+    socket.send(tungstenite::Message::Text("hello".into())).await.unwrap();
+    assert_eq!(next_text(&mut socket).await, "hello");
+    socket.send(tungstenite::Message::Text("usage".into())).await.unwrap();
+    let done: Value = serde_json::from_str(&next_text(&mut socket).await).unwrap();
+    assert_eq!(done["type"], "response.done");
+    socket.close(None).await.unwrap();
+
+    // 会话结束后按response.done中的用量统计
+    let mut usage = None;
+    for _ in 0..50 {
+        if let Some(summary) = load_balancer.get_cost_tracker().report().users.get("alice") {
+            usage = Some((summary.prompt_tokens, summary.completion_tokens));
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(usage, Some((100, 40)));
+    assert_eq!(load_balancer.get_metrics().get_failure_count("openai", "gpt-4o-realtime"), 0);
+
+    // 浏览器通过子协议传递密钥
+    let mut socket = connect(&url, ("sec-websocket-protocol", "realtime, openai-insecure-api-key.alice-token"))
+        .await
+        .unwrap();
+    socket.send(tungstenite::Message::Text("ping".into())).await.unwrap();
+    assert_eq!(next_text(&mut socket).await, "ping");
+}
+
+#[tokio::test]
+async fn test_realtime_rejects_invalid_keys() {
+    let config = ConfigBuilder::new()
+        .provider("openai", Provider {
+            name: "openai".to_string(),
+            models: vec!["gpt-4o-realtime".to_string()],
+            ..provider(&spawn_upstream().await)
+        })
+        .model("gpt-4o-realtime", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4o-realtime", vec![Backend {
+                model: "gpt-4o-realtime".to_string(),
+                ..backend("openai", 1)
+            }])
+        })
+        .user("alice", user("alice", "alice-token"))
+        .build();
+    let (url, _) = start_gateway(config).await;
+
+    match connect(&url, ("authorization", "Bearer invalid-token")).await {
+        Err(tungstenite::Error::Http(response)) => assert_eq!(response.status(), 401),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[tokio::test]
+async fn test_upstream_failure_closes_session() {
+    let config = ConfigBuilder::new()
+        .provider("openai", Provider {
+            name: "openai".to_string(),
+            models: vec!["gpt-4o-realtime".to_string()],
+            ..provider(&spawn_upstream().await)
+        })
+        .model("gpt-4o-realtime", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4o-realtime", vec![Backend {
+                model: "gpt-4o-realtime".to_string(),
+                ..backend("openai", 1)
+            }])
+        })
+        .user("alice", user("alice", "alice-token"))
+        .build();
+    let (url, load_balancer) = start_gateway(config).await;
+
+    let mut socket = connect(&url, ("authorization", "Bearer alice-token")).await.unwrap();
+    socket.send(tungstenite::Message::Text("fail".into())).await.unwrap();
+    match socket.next().await.unwrap().unwrap() {
+        tungstenite::Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Error),
+        other => panic!("unexpected message: {:?}", other),
+    }
+
+    let mut failures = 0;
+    for _ in 0..50 {
+        failures = load_balancer.get_metrics().get_failure_count("openai", "gpt-4o-realtime");
+        if failures > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(failures, 1);
+}
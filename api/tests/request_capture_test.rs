@@ -0,0 +1,104 @@
+use berry_api_api::config::model::{GlobalSettings, LoadBalanceStrategy, ModelMapping, RequestCaptureSettings};
+use axum::routing::post;
+use serde_json::{Value, json};
+
+mod common;
+use common::{ConfigBuilder, admin, backend, chat, chat_body, chat_request, mapping, provider, serve, start_gateway, user};
+
+/// 启动一个本地上游，返回的内容为上游名称
+async fn spawn_upstream(name: &'static str) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || async move {
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "model": "gpt-4",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": name}, "finish_reason": "stop"}]
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+/// 等待抓取文件中出现指定数量的记录
+async fn read_captures(path: &str, count: usize) -> Vec<Value> {
+    for _ in 0..100 {
+        let content = tokio::fs::read_to_string(path).await.unwrap_or_default();
+        let captures: Vec<Value> = content.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        if captures.len() >= count {
+            return captures;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("expected {} captures in {}", count, path);
+}
+
+#[tokio::test]
+async fn test_captures_selected_requests() {
+    let dir = std::env::temp_dir().join(format!("berry-capture-test-{}", std::process::id()));
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    let path = dir.join("captures.jsonl").to_string_lossy().to_string();
+    let config = ConfigBuilder::new()
+        .provider("primary", provider(&spawn_upstream("primary").await))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("primary", 1)])
+        })
+        .user("alice", user("alice", "alice-token"))
+        .user("bob", user("bob", "bob-token"))
+        .user("carol", admin("carol", "carol-token"))
+        .settings(GlobalSettings {
+            request_capture: Some(RequestCaptureSettings {
+                path: path.clone(),
+                max_size_mb: 100,
+                max_files: 1,
+                sample_percent: 0.0,
+                users: vec!["alice".to_string()],
+                max_response_kb: 256,
+            }),
+            ..GlobalSettings::default()
+        })
+        .build();
+    let (url, _) = start_gateway(config).await;
+
+    // 指定用户的请求和管理员携带抓取请求头的请求被抓取，其他用户的抓取请求头被忽略
+    chat(&url, "alice-token", chat_body("gpt-4", "hi")).await.text().await.unwrap();
+    chat(&url, "bob-token", chat_body("gpt-4", "hi")).await.text().await.unwrap();
+    chat_request(&url, "bob-token", &chat_body("gpt-4", "hi"))
+        .header("x-berry-capture", "1")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    chat_request(&url, "carol-token", &chat_body("gpt-4", "hi"))
+        .header("x-berry-capture", "1")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let captures = read_captures(&path, 2).await;
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(read_captures(&path, 2).await.len(), 2);
+    let users: Vec<&str> = captures.iter().map(|c| c["user"].as_str().unwrap()).collect();
+    assert!(users.contains(&"alice") && users.contains(&"carol"));
+
+    let capture = &captures[0];
+    assert_eq!(capture["path"], "/v1/chat/completions");
+    assert_eq!(capture["request_body"]["messages"][0]["content"], "hi");
+    assert_eq!(capture["status"], 200);
+    assert_eq!(capture["backend"], "primary:gpt-4");
+    let response: Value = serde_json::from_str(capture["response_body"].as_str().unwrap().trim()).unwrap();
+    assert_eq!(response["choices"][0]["message"]["content"], "primary");
+    // 抓取记录中不包含API密钥
+    let content = tokio::fs::read_to_string(&path).await.unwrap();
+    assert!(!content.contains("alice-token") && !content.contains("carol-token"));
+
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+}
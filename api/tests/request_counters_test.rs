@@ -0,0 +1,68 @@
+use berry_api_api::config::model::ModelMapping;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::post;
+use serde_json::{Value, json};
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, serve, start_gateway, user};
+
+/// 启动一个本地上游，healthy为false时始终返回500
+async fn spawn_upstream(healthy: bool) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || async move {
+            if !healthy {
+                return (StatusCode::INTERNAL_SERVER_ERROR, "upstream failure").into_response();
+            }
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "model": "gpt-4",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "ok"}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+            }))
+            .into_response()
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+#[tokio::test]
+async fn test_service_health_counts_requests() {
+    let config = ConfigBuilder::new()
+        .provider("good", provider(&spawn_upstream(true).await))
+        .provider("bad", provider(&spawn_upstream(false).await))
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("good", 1)])
+        })
+        .model("broken", ModelMapping {
+            semantic_cache: false,
+            ..mapping("broken", vec![backend("bad", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let (gateway, load_balancer) = start_gateway(config).await;
+
+    for model in ["gpt-4", "gpt-4", "broken", "unknown-model"] {
+        chat(&gateway, "user-token", chat_body(model, "hi")).await.text().await.unwrap();
+    }
+
+    let health = load_balancer.get_service_health().await;
+    assert_eq!(health.total_requests, 4);
+    assert_eq!(health.successful_requests, 2);
+    assert_eq!(health.failed_requests, 2);
+    assert_eq!(health.success_rate(), 0.5);
+    assert_eq!(health.request_rates.one_minute.requests, 4);
+    assert_eq!(health.model_requests["gpt-4"].successful_requests, 2);
+    assert_eq!(health.model_requests["broken"].failed_requests, 1);
+    // 未配置的模型只计入全局计数
+    assert!(!health.model_requests.contains_key("unknown-model"));
+
+    let metrics: Value = reqwest::get(format!("{}/metrics", gateway)).await.unwrap().json().await.unwrap();
+    assert_eq!(metrics["service"]["total_requests"], json!(4));
+    assert_eq!(metrics["service"]["failed_requests"], json!(2));
+    assert_eq!(metrics["service"]["rates"]["1m"]["requests"], json!(4));
+    assert_eq!(metrics["service"]["models"]["broken"]["rates"]["5m"]["success_rate"], json!(0.0));
+}
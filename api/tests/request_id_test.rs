@@ -0,0 +1,106 @@
+use berry_api_api::config::model::{LoadBalanceStrategy, ModelMapping};
+use axum::routing::post;
+use serde_json::json;
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, chat_request, mapping, provider, reply_content, serve, start_gateway, user};
+
+/// 启动一个本地上游，回复内容为收到的x-request-id请求头
+async fn spawn_upstream() -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(|headers: axum::http::HeaderMap| async move {
+            let request_id = headers
+                .get("x-request-id")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": request_id},
+                    "finish_reason": "stop"
+                }]
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+#[tokio::test]
+async fn test_client_request_id_is_returned_and_forwarded() {
+    let url = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("local", provider(&url))
+        .provider("cloud", provider(&url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let gateway = start_gateway(config).await.0;
+
+    let response = chat_request(&gateway, "user-token", &chat_body("gpt-4", "hi"))
+        .header("x-request-id", "client-req-42")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-request-id"], "client-req-42");
+    // 回复内容为上游收到的请求ID
+    assert_eq!(reply_content(response).await, "client-req-42");
+}
+
+#[tokio::test]
+async fn test_request_id_generated_when_missing() {
+    let url = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("local", provider(&url))
+        .provider("cloud", provider(&url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let gateway = start_gateway(config).await.0;
+
+    let response = chat(&gateway, "user-token", chat_body("gpt-4", "hi")).await;
+    let header = response.headers()["x-request-id"].to_str().unwrap().to_string();
+    assert_eq!(header.len(), 32);
+    assert_eq!(reply_content(response).await, header);
+    // 每个请求使用不同的ID，不合法的客户端ID被替换
+    let response = chat_request(&gateway, "user-token", &chat_body("gpt-4", "hi"))
+        .header("x-request-id", "bad id")
+        .send()
+        .await
+        .unwrap();
+    let second = response.headers()["x-request-id"].to_str().unwrap().to_string();
+    assert_ne!(second, header);
+    assert_ne!(second, "bad id");
+}
+
+#[tokio::test]
+async fn test_error_responses_carry_request_id() {
+    let url = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("local", provider(&url))
+        .provider("cloud", provider(&url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("local", 1), backend("cloud", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let gateway = start_gateway(config).await.0;
+
+    let response = chat_request(&gateway, "wrong-token", &chat_body("gpt-4", "hi"))
+        .header("x-request-id", "req-unauthorized")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()["x-request-id"], "req-unauthorized");
+}
@@ -0,0 +1,129 @@
+use berry_api_api::app::create_app;
+use berry_api_api::config::model::{Config, GlobalSettings, LoadBalanceStrategy, ModelMapping, Provider, RequestLogSettings, RequestLogSink};
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, create_state, mapping, provider, serve, user};
+
+/// 启动本地上游，聊天接口返回带usage的响应
+async fn spawn_upstream() -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(|| async {
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "ok"},
+                    "finish_reason": "stop"
+                }],
+                "usage": {"prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15}
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+/// 模拟ClickHouse HTTP接口，收到的（语句, 请求体, 用户）通过通道返回
+async fn spawn_clickhouse() -> (String, mpsc::UnboundedReceiver<(String, String, Option<String>)>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = axum::Router::new().route(
+        "/",
+        post(
+            move |axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
+                  headers: axum::http::HeaderMap,
+                  body: String| {
+                let tx = tx.clone();
+                async move {
+                    let user = headers
+                        .get("X-ClickHouse-User")
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string);
+                    let _ = tx.send((params.get("query").cloned().unwrap_or_default(), body, user));
+                }
+            },
+        ),
+    );
+    (serve(app).await, rx)
+}
+
+/// 写入ClickHouse的请求日志，两条记录一批
+fn request_log_settings(clickhouse_url: &str) -> GlobalSettings {
+    GlobalSettings {
+        request_log: Some(RequestLogSettings {
+            sink: RequestLogSink::Clickhouse {
+                url: clickhouse_url.to_string(),
+                database: "analytics".to_string(),
+                table: "requests".to_string(),
+                username: Some("berry".to_string()),
+                password: Some("secret".to_string()),
+                timeout_seconds: 2,
+            },
+            batch_size: 2,
+            flush_interval_ms: 5000,
+            queue_capacity: 100,
+            max_retries: 0,
+            create_table: true,
+        }),
+        ..GlobalSettings::default()
+    }
+}
+
+async fn start_server(config: Config) -> String {
+    let app = create_app(create_state(config).await);
+    serve(app).await
+}
+
+#[tokio::test]
+async fn test_requests_are_batched_into_clickhouse() {
+    let (clickhouse_url, mut received) = spawn_clickhouse().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            ..provider(&spawn_upstream().await)
+        })
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::RoundRobin,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .settings(request_log_settings(&clickhouse_url))
+        .build();
+    let server = start_server(config).await;
+
+    for _ in 0..2 {
+        let response = chat(&server, "user-token", chat_body("gpt-4", "hello")).await;
+        assert_eq!(response.status(), 200);
+        // 记录在响应体传输完成后生成
+        response.text().await.unwrap();
+    }
+
+    let mut next = async || {
+        tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .expect("clickhouse not called")
+            .unwrap()
+    };
+    let (create, _, user) = next().await;
+    assert!(create.starts_with("CREATE TABLE IF NOT EXISTS analytics.requests"));
+    assert_eq!(user.as_deref(), Some("berry"));
+
+    // batch_size为2：两条记录在一次INSERT中写入，不等待flush_interval
+    let (insert, body, _) = next().await;
+    assert_eq!(insert, "INSERT INTO analytics.requests FORMAT JSONEachRow");
+    let rows: Vec<Value> = body.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(rows.len(), 2);
+    for row in rows {
+        assert_eq!(row["user"], "User");
+        assert_eq!(row["model"], "gpt-4");
+        assert_eq!(row["backend"], "upstream:gpt-4");
+        assert_eq!(row["status"], 200);
+        assert_eq!((row["prompt_tokens"].as_u64(), row["completion_tokens"].as_u64()), (Some(12), Some(3)));
+        assert!(row["error"].is_null());
+    }
+}
@@ -0,0 +1,160 @@
+use berry_api_api::app::create_app;
+use berry_api_api::config::model::{Backend, Config, LoadBalanceStrategy, ModelMapping, ParamOverride, Provider, RequestTransform};
+use axum::http::{HeaderValue, StatusCode};
+use axum::routing::post;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+mod common;
+use common::{ConfigBuilder, create_state, mapping, provider_with_models, serve, user};
+
+/// 启动一个把收到的请求体作为回复内容返回的本地上游
+async fn spawn_echo_upstream() -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(|axum::Json(body): axum::Json<Value>| async move {
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "model": body["model"],
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": body.to_string()}, "finish_reason": "stop"}]
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+/// 把gpt-4转发到azure的gpt-4-0613，按顺序应用transforms，再应用后端的overrides
+fn transform_mapping(transforms: Vec<RequestTransform>, overrides: HashMap<String, ParamOverride>) -> ModelMapping {
+    ModelMapping {
+        strategy: LoadBalanceStrategy::RoundRobin,
+        transforms,
+        ..mapping("gpt-4", vec![Backend {
+            provider: "azure".to_string(),
+            model: "gpt-4-0613".to_string(),
+            priority: 1,
+            overrides,
+            ..Default::default()
+        }])
+    }
+}
+
+/// azure和other两个provider指向同一个上游
+fn transform_config(upstream: &str, mapping: ModelMapping) -> Config {
+    let upstream = Provider {
+        timeout_seconds: 2,
+        ..provider_with_models(upstream, &["gpt-4-0613"])
+    };
+    ConfigBuilder::new()
+        .provider("azure", upstream.clone())
+        .provider("other", upstream)
+        .model("gpt-4", mapping)
+        .user("user", user("User", "user-token"))
+        .build()
+}
+
+async fn create_server(config: Config) -> TestServer {
+    TestServer::new(create_app(create_state(config).await))
+    .unwrap()
+}
+
+/// 发送聊天请求，返回上游收到的请求体
+async fn forwarded_body(server: &TestServer, body: Value) -> Value {
+    let response = server
+        .post("/v1/chat/completions")
+        .add_header(
+            axum::http::header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer user-token"),
+        )
+        .json(&body)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let body: Value = response.json();
+    serde_json::from_str(body["choices"][0]["message"]["content"].as_str().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_transforms_applied_before_forwarding() {
+    let upstream = spawn_echo_upstream().await;
+    let config = transform_config(&upstream, transform_mapping(vec![
+        RequestTransform {
+            max_tokens: Some(4096),
+            defaults: HashMap::from([("temperature".to_string(), json!(0.3))]),
+            ..Default::default()
+        },
+        RequestTransform {
+            providers: vec!["azure".to_string()],
+            strip_params: vec!["logit_bias".to_string()],
+            ..Default::default()
+        },
+        RequestTransform {
+            providers: vec!["other".to_string()],
+            strip_params: vec!["user".to_string()],
+            ..Default::default()
+        },
+    ], HashMap::new()));
+    let server = create_server(config).await;
+
+    let forwarded = forwarded_body(&server, json!({
+        "model": "gpt-4",
+        "messages": [{"role": "user", "content": "hi"}],
+        "max_tokens": 100000,
+        "logit_bias": {"50256": -100},
+        "user": "end-user-1"
+    }))
+    .await;
+    assert_eq!(forwarded["model"], "gpt-4-0613");
+    assert_eq!(forwarded["max_tokens"], 4096);
+    assert_eq!(forwarded["temperature"], 0.3);
+    assert!(forwarded.get("logit_bias").is_none());
+    assert_eq!(forwarded["user"], "end-user-1");
+}
+
+#[tokio::test]
+async fn test_backend_overrides_applied_after_transforms() {
+    let upstream = spawn_echo_upstream().await;
+    let config = transform_config(&upstream, transform_mapping(
+        vec![RequestTransform {
+            max_tokens: Some(4096),
+            ..Default::default()
+        }],
+        HashMap::from([
+            ("max_tokens".to_string(), ParamOverride::Max(2048.0)),
+            ("top_p".to_string(), ParamOverride::Remove),
+        ]),
+    ));
+    let server = create_server(config).await;
+
+    let forwarded = forwarded_body(&server, json!({
+        "model": "gpt-4",
+        "messages": [{"role": "user", "content": "hi"}],
+        "max_tokens": 100000,
+        "top_p": 0.5
+    }))
+    .await;
+    assert_eq!(forwarded["max_tokens"], 2048);
+    assert!(forwarded.get("top_p").is_none());
+}
+
+#[test]
+fn test_transform_validation() {
+    let transform = RequestTransform {
+        providers: vec!["missing".to_string()],
+        ..Default::default()
+    };
+    let config = transform_config("http://localhost/v1", transform_mapping(vec![transform], HashMap::new()));
+    assert!(config.validate().is_err());
+
+    let transform = RequestTransform {
+        strip_params: vec!["model".to_string()],
+        ..Default::default()
+    };
+    let config = transform_config("http://localhost/v1", transform_mapping(vec![transform], HashMap::new()));
+    assert!(config.validate().is_err());
+
+    let overrides = HashMap::from([("model".to_string(), ParamOverride::Remove)]);
+    let config = transform_config("http://localhost/v1", transform_mapping(vec![], overrides));
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,112 @@
+use berry_api_api::app::create_app;
+use berry_api_api::config::model::{Backend, GlobalSettings, LoadBalanceStrategy, ModelMapping, Provider};
+use berry_api_api::loadbalance::LoadBalanceService;
+use axum::http::{HeaderValue, StatusCode};
+use axum_test::TestServer;
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+mod common;
+use common::{ConfigBuilder, app_state, mapping, provider, start_load_balancer, user};
+
+async fn create_server() -> (TestServer, Arc<LoadBalanceService>) {
+    let config = ConfigBuilder::new()
+        // 端口1上没有服务：校验失败的请求不应被转发
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            timeout_seconds: 2,
+            ..provider("http://127.0.0.1:1/v1")
+        })
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::RoundRobin,
+            ..mapping("gpt-4", vec![Backend {
+                provider: "upstream".to_string(),
+                model: "gpt-4".to_string(),
+                priority: 1,
+                max_context: Some(4096),
+                ..Default::default()
+            }])
+        })
+        .user("user", user("User", "user-token"))
+        .settings(GlobalSettings {
+            max_request_body_mb: 1,
+            ..GlobalSettings::default()
+        })
+        .build();
+    config.validate().unwrap();
+    let load_balancer = start_load_balancer(config).await;
+    let state = app_state(load_balancer.clone());
+    (TestServer::new(create_app(state)).unwrap(), load_balancer)
+}
+
+fn auth() -> HeaderValue {
+    HeaderValue::from_static("Bearer user-token")
+}
+
+#[tokio::test]
+async fn test_invalid_chat_request_rejected() {
+    let (server, load_balancer) = create_server().await;
+
+    let invalid_bodies = [
+        json!({ "model": "gpt-4" }),
+        json!({ "model": "gpt-4", "messages": [] }),
+        json!({ "model": "gpt-4", "messages": [{"role": "robot", "content": "hi"}] }),
+    ];
+    for body in invalid_bodies {
+        let response = server
+            .post("/v1/chat/completions")
+            .add_header(axum::http::header::AUTHORIZATION, auth())
+            .json(&body)
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+        let error: Value = response.json();
+        assert_eq!(error["error"]["message"], "Invalid chat completion request");
+    }
+
+    // 后端没有被请求，也没有记录失败
+    assert_eq!(
+        load_balancer.get_metrics().get_failure_count("upstream", "gpt-4"),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_oversized_body_rejected() {
+    let (server, _load_balancer) = create_server().await;
+
+    let response = server
+        .post("/v1/chat/completions")
+        .add_header(axum::http::header::AUTHORIZATION, auth())
+        .json(&json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "a".repeat(2 * 1024 * 1024)}]
+        }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+    let error: Value = response.json();
+    assert_eq!(error["error"]["message"], "Request body too large");
+}
+
+#[tokio::test]
+async fn test_request_exceeding_context_length_rejected() {
+    let (server, load_balancer) = create_server().await;
+
+    let response = server
+        .post("/v1/chat/completions")
+        .add_header(axum::http::header::AUTHORIZATION, auth())
+        .json(&json!({
+            "model": "gpt-4",
+            "max_tokens": 8000,
+            "messages": [{"role": "user", "content": "hello"}]
+        }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    let error: Value = response.json();
+    assert_eq!(error["error"]["code"], "context_length_exceeded");
+    assert!(error["error"]["details"].as_str().unwrap().contains("4096 tokens"));
+
+    assert_eq!(
+        load_balancer.get_metrics().get_failure_count("upstream", "gpt-4"),
+        0
+    );
+}
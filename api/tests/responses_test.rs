@@ -0,0 +1,241 @@
+use berry_api_api::config::model::{Backend, ModelMapping};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+
+mod common;
+use common::{ConfigBuilder, provider, serve, start_gateway, user};
+
+/// 最后一次发往上游的请求：(路径, 请求体)
+type LastRequest = Arc<Mutex<(String, Value)>>;
+
+fn sse(events: &[Value], done: bool) -> axum::response::Response {
+    let mut body: String = events.iter().map(|event| format!("data: {}\n\n", event)).collect();
+    if done {
+        body.push_str("data: [DONE]\n\n");
+    }
+    ([("content-type", "text/event-stream")], body).into_response()
+}
+
+/// 启动一个本地上游，同时提供聊天接口和Responses接口，并记录最后一次请求
+async fn spawn_upstream() -> (String, LastRequest) {
+    let last_request: LastRequest = Arc::new(Mutex::new((String::new(), Value::Null)));
+    let chat_recorder = last_request.clone();
+    let responses_recorder = last_request.clone();
+    let app = axum::Router::new()
+        .route(
+            "/v1/chat/completions",
+            post(move |axum::Json(body): axum::Json<Value>| {
+                let recorder = chat_recorder.clone();
+                async move {
+                    *recorder.lock().unwrap() = ("/chat/completions".to_string(), body.clone());
+                    if body["stream"] == true {
+                        return sse(&[
+                            json!({"id": "chatcmpl-s", "choices": [{"index": 0, "delta": {"role": "assistant", "content": "Hel"}}]}),
+                            json!({"id": "chatcmpl-s", "choices": [{"index": 0, "delta": {"content": "lo"}, "finish_reason": "stop"}]}),
+                            json!({"id": "chatcmpl-s", "choices": [], "usage": {"prompt_tokens": 4, "completion_tokens": 2, "total_tokens": 6}}),
+                        ], true);
+                    }
+                    axum::Json(json!({
+                        "id": "chatcmpl-test",
+                        "object": "chat.completion",
+                        "created": 1700000000,
+                        "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hello there"}, "finish_reason": "stop"}],
+                        "usage": {"prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15}
+                    }))
+                    .into_response()
+                }
+            }),
+        )
+        .route(
+            "/v1/responses",
+            post(move |axum::Json(body): axum::Json<Value>| {
+                let recorder = responses_recorder.clone();
+                async move {
+                    *recorder.lock().unwrap() = ("/responses".to_string(), body.clone());
+                    let response = json!({
+                        "id": "resp_native",
+                        "object": "response",
+                        "status": "completed",
+                        "model": body["model"],
+                        "output": [{"type": "message", "id": "msg_1", "role": "assistant", "status": "completed",
+                                    "content": [{"type": "output_text", "text": "Native", "annotations": []}]}],
+                        "usage": {"input_tokens": 5, "output_tokens": 1, "total_tokens": 6}
+                    });
+                    if body["stream"] != true {
+                        return axum::Json(response).into_response();
+                    }
+                    let mut events = vec![json!({"type": "response.created", "sequence_number": 0, "response": {"id": "resp_native", "status": "in_progress"}})];
+                    // 模拟上游在响应完成前断开
+                    if body["metadata"]["truncate"] != "yes" {
+                        events.push(json!({"type": "response.completed", "sequence_number": 1, "response": response}));
+                    }
+                    sse(&events, false)
+                }
+            }),
+        );
+    (format!("{}/v1", serve(app).await), last_request)
+}
+
+fn backend(provider: &str, supports_responses: bool) -> Backend {
+    Backend {
+        provider: provider.to_string(),
+        model: "gpt-4".to_string(),
+        priority: 1,
+        supports_responses,
+        ..Default::default()
+    }
+}
+
+fn mapping(name: &str, supports_responses: bool) -> ModelMapping {
+    ModelMapping {
+        name: name.to_string(),
+        backends: vec![backend("upstream", supports_responses)],
+        semantic_cache: false,
+        ..Default::default()
+    }
+}
+
+/// 启动上游和网关：chat-model只能翻译成聊天请求，native-model的后端支持Responses接口
+async fn start_server() -> (String, LastRequest) {
+    let (base_url, last_request) = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", provider(&base_url))
+        .model("chat-model", mapping("chat-model", false))
+        .model("native-model", mapping("native-model", true))
+        .user("user", user("User", "user-token"))
+        .build();
+    (start_gateway(config).await.0, last_request)
+}
+
+async fn send(url: &str, body: Value) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/v1/responses", url))
+        .bearer_auth("user-token")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+/// 解析SSE响应中的事件名和数据
+fn parse_events(text: &str) -> Vec<(String, Value)> {
+    text.split("\n\n")
+        .filter_map(|block| {
+            let name = block.lines().find_map(|line| line.strip_prefix("event: "))?;
+            let data = block.lines().find_map(|line| line.strip_prefix("data: "))?;
+            Some((name.to_string(), serde_json::from_str(data).unwrap()))
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_responses_translated_to_chat() {
+    let (url, last_request) = start_server().await;
+
+    let response = send(&url, json!({
+        "model": "chat-model",
+        "instructions": "Be brief.",
+        "max_output_tokens": 64,
+        "input": "Hi"
+    }))
+    .await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["object"], "response");
+    assert_eq!(body["status"], "completed");
+    assert_eq!(body["model"], "chat-model");
+    assert_eq!(body["output"][0]["content"][0]["text"], "Hello there");
+    assert_eq!(body["usage"], json!({"input_tokens": 12, "output_tokens": 3, "total_tokens": 15}));
+
+    let (path, upstream_request) = last_request.lock().unwrap().clone();
+    assert_eq!(path, "/chat/completions");
+    assert_eq!(upstream_request["max_tokens"], 64);
+    assert_eq!(upstream_request["messages"], json!([
+        {"role": "system", "content": "Be brief."},
+        {"role": "user", "content": "Hi"}
+    ]));
+}
+
+#[tokio::test]
+async fn test_responses_translated_streaming_events() {
+    let (url, _) = start_server().await;
+
+    let response = send(&url, json!({"model": "chat-model", "input": "Hi", "stream": true})).await;
+    assert_eq!(response.status(), 200);
+    let events = parse_events(&response.text().await.unwrap());
+    let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "response.created",
+            "response.in_progress",
+            "response.output_item.added",
+            "response.content_part.added",
+            "response.output_text.delta",
+            "response.output_text.delta",
+            "response.output_text.done",
+            "response.content_part.done",
+            "response.output_item.done",
+            "response.completed"
+        ]
+    );
+    assert_eq!(events[6].1["text"], "Hello");
+    let completed = &events[9].1["response"];
+    assert_eq!(completed["model"], "chat-model");
+    assert_eq!(completed["usage"]["output_tokens"], 2);
+}
+
+#[tokio::test]
+async fn test_responses_passthrough_for_native_backends() {
+    let (url, last_request) = start_server().await;
+
+    let body: Value = send(&url, json!({"model": "native-model", "input": "Hi", "previous_response_id": "resp_0"}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["id"], "resp_native");
+    assert_eq!(body["output"][0]["content"][0]["text"], "Native");
+
+    let (path, upstream_request) = last_request.lock().unwrap().clone();
+    assert_eq!(path, "/responses");
+    assert_eq!(upstream_request["model"], "gpt-4");
+    assert_eq!(upstream_request["previous_response_id"], "resp_0");
+
+    let response = send(&url, json!({"model": "native-model", "input": "Hi", "stream": true})).await;
+    let names: Vec<String> = parse_events(&response.text().await.unwrap()).into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, ["response.created", "response.completed"]);
+}
+
+#[tokio::test]
+async fn test_responses_native_stream_ending_early_gets_error_event() {
+    let (url, _) = start_server().await;
+
+    let response = send(&url, json!({
+        "model": "native-model",
+        "input": "Hi",
+        "stream": true,
+        "metadata": {"truncate": "yes"}
+    }))
+    .await;
+    let events = parse_events(&response.text().await.unwrap());
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[1].0, "error");
+    assert_eq!(events[1].1["code"], "upstream_error");
+}
+
+#[tokio::test]
+async fn test_responses_features_requiring_native_backends_are_rejected() {
+    let (url, _) = start_server().await;
+
+    let response = send(&url, json!({"model": "chat-model", "input": "Hi", "previous_response_id": "resp_0"})).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_request");
+    assert!(body["error"]["details"].as_str().unwrap().contains("previous_response_id"));
+
+    let response = send(&url, json!({"model": "unknown-model", "input": "Hi"})).await;
+    assert_eq!(response.status(), 404);
+}
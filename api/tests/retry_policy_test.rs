@@ -0,0 +1,158 @@
+use berry_api_api::config::model::{Config, LoadBalanceStrategy, ModelMapping, RetryPolicy};
+use axum::routing::post;
+use serde_json::json;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+mod common;
+use common::{ConfigBuilder, backend, chat, mapping, provider, serve, start_gateway, user};
+
+/// 本地上游的行为
+#[derive(Clone, Copy)]
+enum Upstream {
+    /// 正常返回流式响应
+    Ok,
+    /// 返回指定的错误状态码
+    Status(u16),
+    /// 返回200但流在首个事件前结束
+    EmptyStream,
+}
+
+/// 启动一个本地上游，返回地址和请求计数
+async fn spawn_upstream(name: &'static str, behavior: Upstream) -> (String, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let (status, body) = match behavior {
+                    Upstream::Ok => {
+                        let chunk = json!({
+                            "id": "chatcmpl-test",
+                            "object": "chat.completion.chunk",
+                            "choices": [{"index": 0, "delta": {"content": name}, "finish_reason": null}]
+                        });
+                        (200, format!("data: {}\n\ndata: [DONE]\n\n", chunk))
+                    }
+                    Upstream::Status(status) => (status, json!({"error": {"message": "failed"}}).to_string()),
+                    Upstream::EmptyStream => (200, String::new()),
+                };
+                (
+                    axum::http::StatusCode::from_u16(status).unwrap(),
+                    [("content-type", "text/event-stream")],
+                    body,
+                )
+            }
+        }),
+    );
+    (format!("{}/v1", serve(app).await), hits)
+}
+
+/// primary优先、secondary兜底的故障转移配置
+fn retry_config(primary_url: &str, secondary_url: &str, retry: Option<RetryPolicy>) -> Config {
+    ConfigBuilder::new()
+        .provider("primary", provider(primary_url))
+        .provider("secondary", provider(secondary_url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            retry,
+            ..mapping("gpt-4", vec![backend("primary", 1), backend("secondary", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build()
+}
+
+/// 发送流式请求，成功时读取到首个数据事件即返回（网关的保活流不会主动结束）
+async fn stream_chat(url: &str) -> (u16, String) {
+    let body = json!({"model": "gpt-4", "stream": true, "messages": [{"role": "user", "content": "hi"}]});
+    let mut response = chat(url, "user-token", body).await;
+    let status = response.status().as_u16();
+    if status != 200 {
+        return (status, response.text().await.unwrap());
+    }
+    let mut received = String::new();
+    while !received.contains("data:") {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    (status, received)
+}
+
+#[tokio::test]
+async fn test_default_policy_retries_any_upstream_error() {
+    let (primary_url, primary_hits) = spawn_upstream("primary", Upstream::Status(400)).await;
+    let (secondary_url, secondary_hits) = spawn_upstream("secondary", Upstream::Ok).await;
+    let url = start_gateway(retry_config(&primary_url, &secondary_url, None)).await.0;
+
+    let (status, body) = stream_chat(&url).await;
+    assert_eq!(status, 200);
+    assert!(body.contains("secondary"));
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(secondary_hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_non_retryable_status_is_returned_without_retry() {
+    let (primary_url, primary_hits) = spawn_upstream("primary", Upstream::Status(400)).await;
+    let (secondary_url, secondary_hits) = spawn_upstream("secondary", Upstream::Ok).await;
+    let retry = RetryPolicy {
+        retry_on_status: vec![429, 503],
+        ..Default::default()
+    };
+    let url = start_gateway(retry_config(&primary_url, &secondary_url, Some(retry))).await.0;
+
+    let (status, body) = stream_chat(&url).await;
+    assert_eq!(status, 502);
+    assert!(body.contains("after 1 attempts"));
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(secondary_hits.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_max_attempts_limits_retries() {
+    let (primary_url, primary_hits) = spawn_upstream("primary", Upstream::Status(503)).await;
+    let (secondary_url, secondary_hits) = spawn_upstream("secondary", Upstream::Ok).await;
+    let retry = RetryPolicy {
+        max_attempts: 1,
+        ..Default::default()
+    };
+    let url = start_gateway(retry_config(&primary_url, &secondary_url, Some(retry))).await.0;
+
+    let (status, _) = stream_chat(&url).await;
+    assert_eq!(status, 502);
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(secondary_hits.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_stream_interrupted_before_first_event_is_retried() {
+    let (primary_url, primary_hits) = spawn_upstream("primary", Upstream::EmptyStream).await;
+    let (secondary_url, secondary_hits) = spawn_upstream("secondary", Upstream::Ok).await;
+    let retry = RetryPolicy {
+        retry_partial_stream: true,
+        ..Default::default()
+    };
+    let url = start_gateway(retry_config(&primary_url, &secondary_url, Some(retry))).await.0;
+
+    let (status, body) = stream_chat(&url).await;
+    assert_eq!(status, 200);
+    assert!(body.contains("secondary"));
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(secondary_hits.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_retry_policy_validation() {
+    let retry = |policy: RetryPolicy| retry_config("http://127.0.0.1:1/v1", "http://127.0.0.1:2/v1", Some(policy));
+    assert!(retry(RetryPolicy { max_attempts: 0, ..Default::default() }).validate().is_err());
+    assert!(retry(RetryPolicy { retry_on_status: vec![200], ..Default::default() }).validate().is_err());
+    assert!(retry(RetryPolicy { retry_on_status: vec![502], ..Default::default() }).validate().is_ok());
+}
@@ -0,0 +1,204 @@
+use berry_api_api::config::model::{Config, LoadBalanceStrategy, ModelMapping, Provider};
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::collections::HashSet;
+
+mod common;
+use common::{ConfigBuilder, admin, backend, chat, chat_body, chat_request, mapping, provider, reply_content, serve, start_gateway, user};
+
+/// 启动一个本地上游，返回的内容为上游名称
+async fn spawn_upstream(name: &'static str) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || async move {
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "model": "gpt-4",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": name}, "finish_reason": "stop"}]
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+/// primary优先、secondary兜底，两个上游分别回复自己的名称
+async fn failover_config() -> Config {
+    ConfigBuilder::new()
+        .provider("primary", Provider {
+            name: "primary".to_string(),
+            ..provider(&spawn_upstream("primary").await)
+        })
+        .provider("secondary", Provider {
+            name: "secondary".to_string(),
+            ..provider(&spawn_upstream("secondary").await)
+        })
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("primary", 1), backend("secondary", 2)])
+        })
+        .user("admin", admin("Admin", "admin-token"))
+        .user("user", user("User", "user-token"))
+        .build()
+}
+
+#[tokio::test]
+async fn test_admin_can_override_strategy() {
+    let (url, _) = start_gateway(failover_config().await).await;
+    let request = chat_body("gpt-4", "hi");
+
+    // 故障转移策略始终选择优先级最高的后端
+    for _ in 0..4 {
+        assert_eq!(reply_content(chat(&url, "admin-token", request.clone()).await).await, "primary");
+    }
+
+    // 轮询覆盖后两个后端都会收到请求
+    let mut served = HashSet::new();
+    for _ in 0..4 {
+        let response = chat_request(&url, "admin-token", &request)
+            .header("x-berry-strategy", "round_robin")
+            .send()
+            .await
+            .unwrap();
+        served.insert(reply_content(response).await);
+    }
+    assert_eq!(served.len(), 2);
+}
+
+#[tokio::test]
+async fn test_strategy_override_is_rejected_for_non_admins() {
+    let (url, _) = start_gateway(failover_config().await).await;
+    let request = chat_body("gpt-4", "hi");
+
+    let response = chat_request(&url, "user-token", &request)
+        .header("x-berry-strategy", "round_robin")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "permission_denied");
+
+    let response = chat_request(&url, "admin-token", &request)
+        .header("x-berry-strategy", "fastest")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["error"]["details"].as_str().unwrap().contains("fastest"));
+}
+
+#[tokio::test]
+async fn test_admin_can_pin_backend() {
+    let (url, load_balancer) = start_gateway(failover_config().await).await;
+    let request = chat_body("gpt-4", "hi");
+
+    for _ in 0..3 {
+        let response = chat_request(&url, "admin-token", &request)
+            .header("x-berry-backend", "secondary:gpt-4")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(reply_content(response).await, "secondary");
+    }
+
+    // 指定后端的请求同样计入后端指标
+    let metrics = load_balancer.get_metrics();
+    assert_eq!(metrics.get_request_total("secondary", "gpt-4"), 3);
+    assert_eq!(metrics.get_request_total("primary", "gpt-4"), 0);
+}
+
+#[tokio::test]
+async fn test_backend_pinning_is_validated() {
+    let (url, _) = start_gateway(failover_config().await).await;
+    let request = chat_body("gpt-4", "hi");
+
+    let response = chat_request(&url, "user-token", &request)
+        .header("x-berry-backend", "secondary:gpt-4")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let response = chat_request(&url, "admin-token", &request)
+        .header("x-berry-backend", "secondary")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // 不属于该模型的后端无法被选中
+    let response = chat_request(&url, "admin-token", &request)
+        .header("x-berry-backend", "secondary:gpt-3.5")
+        .send()
+        .await
+        .unwrap();
+    let body: Value = serde_json::from_str(response.text().await.unwrap().trim()).unwrap();
+    assert!(body["error"]["details"].as_str().unwrap().contains("secondary:gpt-3.5"));
+}
+
+#[tokio::test]
+async fn test_admin_debug_headers_explain_selection() {
+    let mut config = failover_config().await;
+    // 主后端无法连接
+    config.providers.get_mut("primary").unwrap().base_url = "http://127.0.0.1:1/v1".to_string();
+    let (url, _) = start_gateway(config).await;
+    let request = chat_body("gpt-4", "hi");
+
+    let attempts = |response: &reqwest::Response| -> Vec<Value> {
+        serde_json::from_str(response.headers()["x-berry-debug-attempts"].to_str().unwrap()).unwrap()
+    };
+
+    // 保活响应的上游请求在响应头发出后才失败，尝试结果仍然是真实结果
+    let response = chat_request(&url, "admin-token", &request)
+        .header("x-berry-debug", "1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-berry-debug-strategy"], "failover");
+    let first = attempts(&response);
+    assert_eq!(first.len(), 1);
+    assert_eq!(first[0]["model"], "gpt-4");
+    assert_eq!(first[0]["backend"], "primary:gpt-4");
+    assert_ne!(first[0]["outcome"], "success");
+
+    // 失败的后端在之后的请求中因不健康被排除
+    let response = chat_request(&url, "admin-token", &request)
+        .header("x-berry-debug", "true")
+        .send()
+        .await
+        .unwrap();
+    let candidates: Vec<Value> =
+        serde_json::from_str(response.headers()["x-berry-debug-candidates"].to_str().unwrap()).unwrap();
+    let primary = candidates.iter().find(|c| c["backend"] == "primary:gpt-4").unwrap();
+    assert_eq!(primary["model"], "gpt-4");
+    assert_eq!(primary["healthy"], false);
+    assert_eq!(primary["excluded"], "unhealthy");
+    let secondary = candidates.iter().find(|c| c["backend"] == "secondary:gpt-4").unwrap();
+    assert!(secondary.get("excluded").is_none());
+    let second = attempts(&response);
+    assert_eq!(second.last().unwrap()["backend"], "secondary:gpt-4");
+    assert_eq!(second.last().unwrap()["outcome"], "success");
+    assert_eq!(reply_content(response).await, "secondary");
+
+    // 未开启时不返回调试响应头
+    let response = chat(&url, "admin-token", request.clone()).await;
+    assert!(response.headers().get("x-berry-debug-attempts").is_none());
+}
+
+#[tokio::test]
+async fn test_debug_headers_are_rejected_for_non_admins() {
+    let (url, _) = start_gateway(failover_config().await).await;
+    let request = chat_body("gpt-4", "hi");
+
+    let response = chat_request(&url, "user-token", &request)
+        .header("x-berry-debug", "1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "permission_denied");
+}
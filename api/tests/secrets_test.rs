@@ -0,0 +1,148 @@
+use berry_api_api::config::model::{AwsSecretsSettings, Config, GlobalSettings, ModelMapping, Provider, SecretsSettings, VaultSettings};
+use berry_api_api::config::secrets::SecretResolver;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, reply_content, serve, start_gateway, user};
+
+/// 模拟Vault KV v2，返回当前保存的密钥，值为None时返回500
+async fn spawn_vault(secret: Arc<Mutex<Option<String>>>) -> String {
+    let app = axum::Router::new().route(
+        "/v1/secret/data/openai",
+        get(move |headers: HeaderMap| async move {
+            if headers.get("x-vault-token").and_then(|v| v.to_str().ok()) != Some("vault-token") {
+                return StatusCode::FORBIDDEN.into_response();
+            }
+            match secret.lock().unwrap().clone() {
+                Some(key) => axum::Json(json!({ "data": { "data": { "api_key": key } } })).into_response(),
+                None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            }
+        }),
+    );
+    serve(app).await
+}
+
+/// 启动一个本地上游，把收到的Authorization头作为回复内容
+async fn spawn_upstream() -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(|headers: HeaderMap| async move {
+            let authorization = headers
+                .get("authorization")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "model": "gpt-4",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": authorization}, "finish_reason": "stop"}]
+            }))
+        }),
+    );
+    serve(app).await
+}
+
+/// openai使用给定的api_key（可以是密钥引用）转发gpt-4
+fn secrets_config(upstream: &str, api_key: &str, secrets: Option<SecretsSettings>) -> Config {
+    ConfigBuilder::new()
+        .provider("openai", Provider {
+            name: upstream.to_string(),
+            api_key: api_key.to_string(),
+            ..provider(&format!("{}/v1", upstream))
+        })
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("openai", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .settings(GlobalSettings {
+            secrets,
+            ..Default::default()
+        })
+        .build()
+}
+
+fn vault_settings(address: &str) -> SecretsSettings {
+    SecretsSettings {
+        refresh_interval_seconds: 300,
+        vault: Some(VaultSettings {
+            address: address.to_string(),
+            token_env: "BERRY_TEST_VAULT_TOKEN".to_string(),
+            mount: "secret".to_string(),
+            namespace: None,
+        }),
+        aws: None,
+    }
+}
+
+#[tokio::test]
+async fn test_vault_secret_is_resolved_and_rotated() {
+    unsafe { std::env::set_var("BERRY_TEST_VAULT_TOKEN", "vault-token") };
+    let secret = Arc::new(Mutex::new(Some("key-1".to_string())));
+    let vault = spawn_vault(secret.clone()).await;
+    let config = secrets_config(&spawn_upstream().await, "vault://openai", Some(vault_settings(&vault)));
+    let (gateway, load_balancer) = start_gateway(config).await;
+    let request = chat_body("gpt-4", "hi");
+
+    assert_eq!(reply_content(chat(&gateway, "user-token", request.clone()).await).await, "Bearer key-1");
+    // 生效配置中是实际密钥，原始引用不会发往上游
+    assert_eq!(load_balancer.get_config().providers["openai"].api_key, "key-1");
+
+    *secret.lock().unwrap() = Some("key-2".to_string());
+    assert_eq!(load_balancer.refresh_secrets().await.unwrap(), vec!["openai".to_string()]);
+    assert_eq!(reply_content(chat(&gateway, "user-token", request.clone()).await).await, "Bearer key-2");
+    assert!(load_balancer.refresh_secrets().await.unwrap().is_empty());
+
+    // 拉取失败时继续使用之前的密钥
+    *secret.lock().unwrap() = None;
+    assert!(load_balancer.refresh_secrets().await.is_err());
+    assert_eq!(reply_content(chat(&gateway, "user-token", request.clone()).await).await, "Bearer key-2");
+}
+
+#[tokio::test]
+async fn test_aws_secrets_manager_request_is_signed() {
+    unsafe {
+        std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDTEST");
+        std::env::set_var("AWS_SECRET_ACCESS_KEY", "secret");
+    }
+    let app = axum::Router::new().route(
+        "/",
+        post(|headers: HeaderMap, body: String| async move {
+            let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+            let request: Value = serde_json::from_str(&body).unwrap();
+            if header("x-amz-target") != "secretsmanager.GetSecretValue"
+                || !header("authorization").starts_with("AWS4-HMAC-SHA256 Credential=AKIDTEST/")
+                || request["SecretId"] != json!("prod/openai")
+            {
+                return StatusCode::BAD_REQUEST.into_response();
+            }
+            axum::Json(json!({ "SecretString": "{\"key\":\"aws-key\"}" })).into_response()
+        }),
+    );
+    let endpoint = serve(app).await;
+    let secrets = SecretsSettings {
+        refresh_interval_seconds: 300,
+        vault: None,
+        aws: Some(AwsSecretsSettings {
+            region: "us-east-1".to_string(),
+            endpoint: Some(endpoint),
+        }),
+    };
+    let config = secrets_config("http://127.0.0.1:1", "aws-sm://prod/openai#key", Some(secrets));
+    config.validate().unwrap();
+
+    let resolved = SecretResolver::new().resolve(&config).await.unwrap();
+    assert_eq!(resolved.providers["openai"].api_key, "aws-key");
+}
+
+#[tokio::test]
+async fn test_secret_reference_requires_configured_backend() {
+    let config = secrets_config("http://127.0.0.1:1", "vault://openai", None);
+    let error = config.validate().unwrap_err();
+    assert!(error.to_string().contains("settings.secrets.vault is not configured"));
+}
@@ -0,0 +1,122 @@
+use berry_api_api::config::model::{
+    Config, GlobalSettings, ModelMapping,
+    StreamHeartbeatSettings,
+};
+use axum::body::{Body, Bytes};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, reply_content, serve, start_gateway, user};
+
+/// 启动一个慢速上游：返回响应头前等待header_delay，流式响应在首个数据块前再等待first_token_delay
+async fn spawn_slow_upstream(header_delay: Duration, first_token_delay: Duration) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move |axum::Json(body): axum::Json<Value>| async move {
+            tokio::time::sleep(header_delay).await;
+            if body["stream"] != true {
+                return axum::Json(json!({
+                    "id": "chatcmpl-test",
+                    "object": "chat.completion",
+                    "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hello"}, "finish_reason": "stop"}],
+                    "usage": {"prompt_tokens": 4, "completion_tokens": 1, "total_tokens": 5}
+                }))
+                .into_response();
+            }
+            let chunks = futures::stream::once(async move {
+                tokio::time::sleep(first_token_delay).await;
+                let chunk = json!({"id": "chatcmpl-s", "choices": [{"index": 0, "delta": {"content": "Hello"}, "finish_reason": "stop"}]});
+                Ok::<_, std::convert::Infallible>(Bytes::from(format!("data: {}\n\ndata: [DONE]\n\n", chunk)))
+            });
+            ([("content-type", "text/event-stream")], Body::from_stream(chunks)).into_response()
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+/// 流式保活间隔为heartbeat_ms，None表示不开启
+fn heartbeat_config(base_url: &str, heartbeat_ms: Option<u64>) -> Config {
+    ConfigBuilder::new()
+        .provider("upstream", provider(base_url))
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .settings(GlobalSettings {
+            stream_heartbeat: heartbeat_ms.map(|interval_ms| StreamHeartbeatSettings { interval_ms }),
+            ..GlobalSettings::default()
+        })
+        .build()
+}
+
+/// 流式的chat completions请求体
+fn stream_body() -> Value {
+    json!({"model": "gpt-4", "stream": true, "messages": [{"role": "user", "content": "Hi"}]})
+}
+
+/// 聊天流带有保活定时器不会自行结束，读到[DONE]为止
+async fn read_stream(mut response: reqwest::Response) -> String {
+    let mut received = String::new();
+    while !received.contains("data: [DONE]") {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    received
+}
+
+fn content(text: &str) -> String {
+    text.lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter(|data| *data != "[DONE]")
+        .filter_map(|data| serde_json::from_str::<Value>(data).ok())
+        .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str().map(str::to_string))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_heartbeat_while_waiting_for_response_headers() {
+    let base_url = spawn_slow_upstream(Duration::from_millis(400), Duration::ZERO).await;
+    let url = start_gateway(heartbeat_config(&base_url, Some(50))).await.0;
+
+    let response = chat(&url, "user-token", stream_body()).await;
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/event-stream"));
+    let text = read_stream(response).await;
+    assert!(text.starts_with(": ping\n\n"), "expected heartbeat before data: {}", text);
+    assert!(text.matches(": ping").count() >= 3);
+    assert_eq!(content(&text), "Hello");
+}
+
+#[tokio::test]
+async fn test_heartbeat_until_first_token() {
+    let base_url = spawn_slow_upstream(Duration::ZERO, Duration::from_millis(400)).await;
+    let url = start_gateway(heartbeat_config(&base_url, Some(50))).await.0;
+
+    let text = read_stream(chat(&url, "user-token", stream_body()).await).await;
+    let first_data = text.find("data: ").unwrap();
+    assert!(text[..first_data].matches(": ping").count() >= 3, "expected heartbeats before first token: {}", text);
+    assert!(!text[first_data..].contains(": ping"));
+    assert_eq!(content(&text), "Hello");
+}
+
+#[tokio::test]
+async fn test_no_heartbeat_when_disabled_or_not_streaming() {
+    let base_url = spawn_slow_upstream(Duration::from_millis(200), Duration::from_millis(200)).await;
+
+    let url = start_gateway(heartbeat_config(&base_url, None)).await.0;
+    let text = read_stream(chat(&url, "user-token", stream_body()).await).await;
+    assert!(!text.contains(": ping"));
+    assert_eq!(content(&text), "Hello");
+
+    let url = start_gateway(heartbeat_config(&base_url, Some(50))).await.0;
+    let response = chat(&url, "user-token", chat_body("gpt-4", "Hi")).await;
+    assert_eq!(reply_content(response).await, "Hello");
+}
@@ -0,0 +1,129 @@
+use berry_api_api::config::model::{
+    GlobalSettings, ModelMapping,
+    StreamResumptionSettings,
+};
+use axum::body::{Body, Bytes};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use futures::StreamExt;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+mod common;
+use common::{ConfigBuilder, backend, chat_request, mapping, provider, serve, start_gateway, user};
+
+/// 启动一个流式上游：先返回"Hel"，间隔一段时间后返回"lo"和[DONE]，并统计请求次数
+async fn spawn_upstream() -> (String, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                let chunk = |content: &str| {
+                    json!({"id": "chatcmpl-s", "choices": [{"index": 0, "delta": {"content": content}}]}).to_string()
+                };
+                let first = format!("data: {}\n\n", chunk("Hel"));
+                let rest = format!("data: {}\n\ndata: [DONE]\n\n", chunk("lo"));
+                let chunks = futures::stream::iter([(Duration::ZERO, first), (Duration::from_millis(300), rest)]).then(
+                    |(delay, data)| async move {
+                        tokio::time::sleep(delay).await;
+                        Ok::<_, std::convert::Infallible>(Bytes::from(data))
+                    },
+                );
+                ([("content-type", "text/event-stream")], Body::from_stream(chunks)).into_response()
+            }
+        }),
+    );
+    (format!("{}/v1", serve(app).await), hits)
+}
+
+/// 启动上游和开启流恢复的网关，返回网关地址和上游请求计数
+async fn start_server() -> (String, Arc<AtomicUsize>) {
+    let (base_url, hits) = spawn_upstream().await;
+    let config = ConfigBuilder::new()
+        .provider("upstream", provider(&base_url))
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .settings(GlobalSettings {
+            stream_resumption: Some(StreamResumptionSettings {
+                ttl_seconds: 60,
+                max_streams: 100,
+            }),
+            ..GlobalSettings::default()
+        })
+        .build();
+    (start_gateway(config).await.0, hits)
+}
+
+/// 以request_id发送流式请求，带有last_event_id时恢复该请求的流
+async fn send(url: &str, request_id: &str, last_event_id: Option<&str>) -> reqwest::Response {
+    let body = json!({"model": "gpt-4", "stream": true, "messages": [{"role": "user", "content": "Hi"}]});
+    let mut request = chat_request(url, "user-token", &body).header("x-request-id", request_id);
+    if let Some(id) = last_event_id {
+        request = request.header("last-event-id", id);
+    }
+    request.send().await.unwrap()
+}
+
+fn content(text: &str) -> String {
+    text.lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter_map(|data| serde_json::from_str::<Value>(data).ok())
+        .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str().map(str::to_string))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_resume_after_disconnect_without_new_upstream_request() {
+    let (url, hits) = start_server().await;
+
+    // 读到第一个事件后断开连接
+    let mut response = send(&url, "resume-1", None).await;
+    assert_eq!(response.status(), 200);
+    let mut received = String::new();
+    while !received.contains("data: ") {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    drop(response);
+    assert!(received.contains("id: 1\n"));
+    assert_eq!(content(&received), "Hel");
+
+    let resumed = send(&url, "resume-1", Some("1")).await;
+    assert_eq!(resumed.status(), 200);
+    let text = tokio::time::timeout(Duration::from_secs(5), resumed.text()).await.unwrap().unwrap();
+    assert!(text.starts_with("id: 2\n"), "unexpected resumed stream: {}", text);
+    assert_eq!(content(&text), "lo");
+    assert!(text.contains("data: [DONE]"));
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    // 流结束后仍可以从头重放
+    let replay = send(&url, "resume-1", Some("0")).await.text().await.unwrap();
+    assert_eq!(content(&replay), "Hello");
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_resume_unknown_stream_returns_not_found() {
+    let (url, hits) = start_server().await;
+
+    let response = send(&url, "never-started", Some("3")).await;
+    assert_eq!(response.status(), 404);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "stream_not_found");
+
+    let response = send(&url, "never-started", Some("abc")).await;
+    assert_eq!(response.status(), 400);
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,106 @@
+use berry_api_api::app::create_app;
+use berry_api_api::config::model::{LoadBalanceStrategy, ModelMapping, StructuredOutputSettings};
+use berry_api_api::relay::handler::LoadBalancedHandler;
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, create_state, mapping, provider, reply_content, serve, user};
+
+/// 启动一个总是返回指定消息内容的本地上游，返回地址和请求计数
+async fn spawn_upstream(content: &'static str) -> (String, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                axum::Json(json!({
+                    "id": "chatcmpl-test",
+                    "object": "chat.completion",
+                    "choices": [{
+                        "index": 0,
+                        "message": {"role": "assistant", "content": content},
+                        "finish_reason": "stop"
+                    }]
+                }))
+            }
+        }),
+    );
+    (format!("{}/v1", serve(app).await), hits)
+}
+
+/// 启动primary优先、secondary兜底的网关，返回网关地址和请求处理器
+async fn start_server(
+    primary_url: &str,
+    secondary_url: &str,
+    structured_output: StructuredOutputSettings,
+) -> (String, Arc<LoadBalancedHandler>) {
+    let config = ConfigBuilder::new()
+        .provider("primary", provider(primary_url))
+        .provider("secondary", provider(secondary_url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            structured_output: Some(structured_output),
+            ..mapping("gpt-4", vec![backend("primary", 1), backend("secondary", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let state = create_state(config).await;
+    let handler = state.handler.clone();
+    (serve(create_app(state)).await, handler)
+}
+
+/// 要求JSON输出的请求体
+fn json_mode_body() -> Value {
+    let mut body = chat_body("gpt-4", "hi");
+    body["response_format"] = json!({"type": "json_object"});
+    body
+}
+
+#[tokio::test]
+async fn test_invalid_json_retries_on_another_backend() {
+    let (primary_url, primary_hits) = spawn_upstream("Sure! Here you go").await;
+    let (secondary_url, secondary_hits) = spawn_upstream("{\"ok\": true}").await;
+    let (gateway, handler) = start_server(
+        &primary_url,
+        &secondary_url,
+        StructuredOutputSettings { retry: true, repair: true },
+    )
+    .await;
+
+    let response = chat(&gateway, "user-token", json_mode_body()).await;
+    assert_eq!(reply_content(response).await, "{\"ok\": true}");
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(secondary_hits.load(Ordering::SeqCst), 1);
+
+    let stats = handler.structured_output().stats();
+    assert_eq!((stats.invalid, stats.retried, stats.repaired), (1, 1, 0));
+}
+
+#[tokio::test]
+async fn test_invalid_json_is_repaired_without_retry() {
+    let (primary_url, primary_hits) = spawn_upstream("```json\n{\"ok\": true,}\n```").await;
+    let (secondary_url, secondary_hits) = spawn_upstream("{\"ok\": true}").await;
+    let (gateway, handler) = start_server(
+        &primary_url,
+        &secondary_url,
+        StructuredOutputSettings { retry: false, repair: true },
+    )
+    .await;
+
+    let response = chat(&gateway, "user-token", json_mode_body()).await;
+    assert_eq!(reply_content(response).await, "{\"ok\": true}");
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(secondary_hits.load(Ordering::SeqCst), 0);
+    assert_eq!(handler.structured_output().stats().repaired, 1);
+
+    // 没有要求JSON输出的请求不校验
+    let response = chat(&gateway, "user-token", chat_body("gpt-4", "hi")).await;
+    assert_eq!(reply_content(response).await, "```json\n{\"ok\": true,}\n```");
+    assert_eq!(handler.structured_output().stats().invalid, 1);
+}
@@ -0,0 +1,144 @@
+use berry_api_api::config::model::{Config, ModelMapping, Provider, Tenant};
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, reply_content, serve, start_gateway, user};
+
+/// 启动一个本地上游，返回的内容为上游名称
+async fn spawn_upstream(name: &'static str) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || async move {
+            axum::Json(json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "model": "gpt-4",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": name}, "finish_reason": "stop"}]
+            }))
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+fn model(provider: &str) -> ModelMapping {
+    ModelMapping {
+        semantic_cache: false,
+        ..mapping("gpt-4", vec![backend(provider, 1)])
+    }
+}
+
+fn tenant(providers: HashMap<String, Provider>, shared_providers: Vec<String>, token: &str) -> Tenant {
+    Tenant {
+        name: String::new(),
+        enabled: true,
+        providers,
+        models: HashMap::from([("gpt-4".to_string(), model("openai"))]),
+        users: HashMap::from([("alice".to_string(), user("alice", token))]),
+        shared_providers,
+    }
+}
+
+/// 全局和两个租户都定义了gpt-4和用户alice：acme使用自己的provider，beta使用共享的全局provider
+async fn tenant_config() -> Config {
+    let openai = |base_url: &str| Provider {
+        name: "openai".to_string(),
+        ..provider(base_url)
+    };
+    let platform = openai(&spawn_upstream("platform").await);
+    let acme = openai(&spawn_upstream("acme").await);
+
+    let config = ConfigBuilder::new()
+        .provider("openai", platform)
+        .model("gpt-4", model("openai"))
+        .user("alice", user("alice", "global-token"))
+        .build();
+    Config {
+        tenants: HashMap::from([
+            ("acme".to_string(), tenant(HashMap::from([("openai".to_string(), acme)]), vec![], "acme-token")),
+            ("beta".to_string(), tenant(HashMap::new(), vec!["openai".to_string()], "beta-token")),
+        ]),
+        ..config
+    }
+}
+
+async fn model_ids(response: reqwest::Response) -> Vec<String> {
+    let body: Value = response.json().await.unwrap();
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|model| model["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_api_key_selects_tenant_pool() {
+    let url = start_gateway(tenant_config().await).await.0;
+
+    for (token, served_by) in [("global-token", "platform"), ("acme-token", "acme"), ("beta-token", "platform")] {
+        let response = chat(&url, token, chat_body("gpt-4", "hi")).await;
+        assert_eq!(reply_content(response).await, served_by);
+    }
+}
+
+#[tokio::test]
+async fn test_tenant_models_are_isolated() {
+    let url = start_gateway(tenant_config().await).await.0;
+
+    // 全局用户不能通过内部名称访问租户的模型
+    let response = chat(&url, "global-token", chat_body("acme/gpt-4", "hi")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    // 租户用户只能解析到本租户的模型
+    let response = chat(&url, "acme-token", chat_body("beta/gpt-4", "hi")).await;
+    assert!(!response.status().is_success());
+
+    let client = reqwest::Client::new();
+    let list = |token: &str| {
+        client
+            .get(format!("{}/v1/models", url))
+            .bearer_auth(token)
+            .send()
+    };
+    assert_eq!(model_ids(list("acme-token").await.unwrap()).await, vec!["gpt-4"]);
+    assert_eq!(model_ids(list("global-token").await.unwrap()).await, vec!["gpt-4"]);
+
+    let public = client.get(format!("{}/models", url)).send().await.unwrap();
+    assert_eq!(model_ids(public).await, vec!["gpt-4"]);
+
+    let response = client
+        .get(format!("{}/v1/models/gpt-4", url))
+        .bearer_auth("acme-token")
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["owned_by"], "gpt-4");
+    assert_eq!(body["berry"]["model"], "gpt-4");
+}
+
+#[tokio::test]
+async fn test_tenant_validation() {
+    let mut config = tenant_config().await;
+    config.tenants.get_mut("beta").unwrap().shared_providers = vec!["missing".to_string()];
+    assert!(config.validate().is_err());
+
+    // 租户前缀保留给租户使用
+    let mut config = tenant_config().await;
+    config.models.get_mut("gpt-4").unwrap().name = "acme/gpt-4".to_string();
+    assert!(config.validate().is_err());
+
+    // 引用既不属于租户也未共享的provider
+    let mut config = tenant_config().await;
+    config.tenants.get_mut("beta").unwrap().shared_providers.clear();
+    assert!(config.validate().is_err());
+
+    let flattened = tenant_config().await.flatten_tenants();
+    assert_eq!(flattened.users["acme/alice"].name, "acme/alice");
+    assert_eq!(flattened.users["acme/alice"].tenant.as_deref(), Some("acme"));
+    assert_eq!(flattened.models["acme/gpt-4"].backends[0].provider, "acme/openai");
+    assert_eq!(flattened.models["beta/gpt-4"].backends[0].provider, "openai");
+    assert_eq!(flattened.model_tenant("beta/gpt-4"), Some("beta"));
+    assert!(flattened.validate().is_ok());
+}
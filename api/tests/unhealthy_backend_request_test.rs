@@ -14,10 +14,9 @@ fn create_weighted_test_config() -> Config {
         base_url: "https://httpbin.org".to_string(),
         api_key: "key1".to_string(),
         models: vec!["model1".to_string()],
-        headers: HashMap::new(),
-        enabled: true,
         timeout_seconds: 10,
         max_retries: 2,
+        ..Default::default()
     });
 
     providers.insert("provider2".to_string(), Provider {
@@ -25,10 +24,9 @@ fn create_weighted_test_config() -> Config {
         base_url: "https://httpbin.org".to_string(),
         api_key: "key2".to_string(),
         models: vec!["model2".to_string()],
-        headers: HashMap::new(),
-        enabled: true,
         timeout_seconds: 10,
         max_retries: 2,
+        ..Default::default()
     });
 
     providers.insert("provider3".to_string(), Provider {
@@ -36,10 +34,9 @@ fn create_weighted_test_config() -> Config {
         base_url: "https://httpbin.org".to_string(),
         api_key: "key3".to_string(),
         models: vec!["model3".to_string()],
-        headers: HashMap::new(),
-        enabled: true,
         timeout_seconds: 10,
         max_retries: 2,
+        ..Default::default()
     });
 
     let mut models = HashMap::new();
@@ -49,30 +46,30 @@ fn create_weighted_test_config() -> Config {
             Backend {
                 provider: "provider1".to_string(),
                 model: "model1".to_string(),
-                weight: 0.5,  // 高权重
+                weight: 0.5,
+  // 高权重
                 priority: 2,
-                enabled: true,
-                tags: vec![],
+                ..Default::default()
             },
             Backend {
                 provider: "provider2".to_string(),
                 model: "model2".to_string(),
-                weight: 0.3,  // 中权重
+                weight: 0.3,
+  // 中权重
                 priority: 1,
-                enabled: true,
-                tags: vec![],
+                ..Default::default()
             },
             Backend {
                 provider: "provider3".to_string(),
                 model: "model3".to_string(),
-                weight: 0.2,  // 低权重
+                weight: 0.2,
+  // 低权重
                 priority: 3,
-                enabled: true,
-                tags: vec![],
+                ..Default::default()
             },
         ],
         strategy: LoadBalanceStrategy::WeightedFailover,
-        enabled: true,
+        ..Default::default()
     });
 
     Config {
@@ -88,7 +85,51 @@ fn create_weighted_test_config() -> Config {
             recovery_check_interval_seconds: 60,
             max_internal_retries: 2,
             health_check_timeout_seconds: 10,
+            failover_memory_ttl_seconds: 300,
+            model_degraded_ratio: 0.75,
+            model_down_ratio: 0.25,
+            model_health_hysteresis: 0.1,
+            health_webhook_urls: vec![],
+            readiness_required_models: Vec::new(),
+            config_hot_reload: true,
+            peer_max_hops: 1,
+            response_cache_enabled: false,
+            response_cache_ttl_seconds: 300,
+            response_cache_max_entries: 1000,
+            semantic_cache: None,
+            latency_ewma_alpha: 0.3,
+            audit_log: None,
+            keys_file: None,
+            max_embedding_batch_size: 2048,
+            max_request_body_mb: 32,
+            slow_start: Default::default(),
+            outlier_detection: None,
+            max_in_flight_requests: None,
+            priority_scheduling: None,
+            shared_state: None,
+            secrets: None,
+            config_history: Default::default(),
+            health_scoring: None,
+            region_routing: None,
+            content_filters: HashMap::new(),
+            moderation: None,
+            wasm_plugins: None,
+            notifications: None,
+            alerts: None,
+            slo: None,
+            request_log: None,
+            jwt: None,
+            network_acl: None,
+            cors: None,
+            grpc: None,
+            job_queue: None,
+            files: None,
+            stream_heartbeat: None,
+            stream_resumption: None,
+            request_capture: None,
+            fault_injection: false,
         },
+        ..Default::default()
     }
 }
 
@@ -0,0 +1,79 @@
+use berry_api_api::config::model::{LoadBalanceStrategy, ModelMapping};
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, reply_content, serve, start_gateway, user};
+
+/// 启动一个本地上游：rate_limited为true时返回带Retry-After的429，否则正常回复；返回地址和请求计数
+async fn spawn_upstream(name: &'static str, rate_limited: bool) -> (String, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                if rate_limited {
+                    return (
+                        axum::http::StatusCode::TOO_MANY_REQUESTS,
+                        [("retry-after", "30")],
+                        axum::Json(json!({"error": {"message": "slow down"}})),
+                    );
+                }
+                (
+                    axum::http::StatusCode::OK,
+                    [("x-upstream", name)],
+                    axum::Json(json!({
+                        "id": "chatcmpl-test",
+                        "object": "chat.completion",
+                        "choices": [{
+                            "index": 0,
+                            "message": {"role": "assistant", "content": name},
+                            "finish_reason": "stop"
+                        }]
+                    })),
+                )
+            }
+        }),
+    );
+    (format!("{}/v1", serve(app).await), hits)
+}
+
+#[tokio::test]
+async fn test_rate_limited_backend_cools_down_without_being_marked_unhealthy() {
+    let (primary_url, primary_hits) = spawn_upstream("primary", true).await;
+    let (secondary_url, secondary_hits) = spawn_upstream("secondary", false).await;
+    let config = ConfigBuilder::new()
+        .provider("primary", provider(&primary_url))
+        .provider("secondary", provider(&secondary_url))
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::Failover,
+            ..mapping("gpt-4", vec![backend("primary", 1), backend("secondary", 2)])
+        })
+        .user("user", user("User", "user-token"))
+        .build();
+    let (url, load_balancer) = start_gateway(config).await;
+
+    // 非流式响应先发出保活空白，错误在响应体中返回
+    let response = chat(&url, "user-token", chat_body("gpt-4", "hi")).await;
+    let body: Value = serde_json::from_str(response.text().await.unwrap().trim()).unwrap();
+    assert_eq!(body["error"]["code"], "upstream_rate_limited");
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 1);
+
+    let metrics = load_balancer.get_metrics();
+    assert!(metrics.is_healthy("primary", "gpt-4"));
+    let remaining = metrics.cooldown_remaining("primary", "gpt-4").unwrap();
+    assert!(remaining.as_secs() >= 29);
+
+    // 冷却期内故障转移策略跳过优先级更高的限流后端
+    for _ in 0..3 {
+        let response = chat(&url, "user-token", chat_body("gpt-4", "hi")).await;
+        assert_eq!(reply_content(response).await, "secondary");
+    }
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(secondary_hits.load(Ordering::SeqCst), 3);
+}
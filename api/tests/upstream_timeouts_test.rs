@@ -0,0 +1,168 @@
+use berry_api_api::config::model::{
+    Config, GlobalSettings, ModelMapping, Provider,
+    TimeoutSettings,
+};
+use axum::body::{Body, Bytes};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use futures::StreamExt;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, serve, start_gateway, user};
+
+/// 启动一个慢速上游：返回响应头前等待header_delay，流式响应在首个数据块前等待first_token_delay，
+/// 两个数据块之间再等待chunk_gap
+async fn spawn_slow_upstream(header_delay: Duration, first_token_delay: Duration, chunk_gap: Duration) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move |axum::Json(body): axum::Json<Value>| async move {
+            tokio::time::sleep(header_delay).await;
+            if body["stream"] != true {
+                return axum::Json(json!({
+                    "id": "chatcmpl-test",
+                    "object": "chat.completion",
+                    "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hello"}, "finish_reason": "stop"}],
+                    "usage": {"prompt_tokens": 4, "completion_tokens": 1, "total_tokens": 5}
+                }))
+                .into_response();
+            }
+            let chunk = |content: &str| {
+                let chunk = json!({"id": "chatcmpl-s", "choices": [{"index": 0, "delta": {"content": content}}]});
+                Bytes::from(format!("data: {}\n\n", chunk))
+            };
+            let chunks = futures::stream::iter([
+                (first_token_delay, chunk("Hel")),
+                (chunk_gap, chunk("lo")),
+                (Duration::ZERO, Bytes::from_static(b"data: [DONE]\n\n")),
+            ])
+            .then(|(delay, bytes)| async move {
+                tokio::time::sleep(delay).await;
+                Ok::<_, std::convert::Infallible>(bytes)
+            });
+            ([("content-type", "text/event-stream")], Body::from_stream(chunks)).into_response()
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+fn timeouts(first_token: Option<u64>, idle: Option<u64>, total: Option<u64>) -> TimeoutSettings {
+    TimeoutSettings {
+        connect_seconds: None,
+        first_token_seconds: first_token,
+        idle_seconds: idle,
+        total_seconds: total,
+    }
+}
+
+/// 超时设置可以同时在provider和模型上配置，内部重试只有一次
+fn timeout_config(
+    base_url: &str,
+    provider_timeouts: Option<TimeoutSettings>,
+    model_timeouts: Option<TimeoutSettings>,
+) -> Config {
+    ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            timeouts: provider_timeouts,
+            ..provider(base_url)
+        })
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            timeouts: model_timeouts,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .settings(GlobalSettings {
+            max_internal_retries: 1,
+            ..GlobalSettings::default()
+        })
+        .build()
+}
+
+/// 流式的chat completions请求体
+fn stream_body() -> Value {
+    json!({"model": "gpt-4", "stream": true, "messages": [{"role": "user", "content": "Hi"}]})
+}
+
+/// 聊天流带有保活定时器不会自行结束，读到[DONE]或错误事件为止
+async fn read_stream(mut response: reqwest::Response) -> String {
+    let mut received = String::new();
+    while !received.contains("data: [DONE]") && !received.contains("\"error\"") {
+        let chunk = tokio::time::timeout(Duration::from_secs(10), response.chunk())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    received
+}
+
+fn events(text: &str) -> Vec<Value> {
+    text.lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter_map(|data| serde_json::from_str::<Value>(data).ok())
+        .collect()
+}
+
+async fn upstream_timeouts(url: &str) -> Value {
+    let metrics: Value = reqwest::get(format!("{}/metrics", url)).await.unwrap().json().await.unwrap();
+    metrics["upstream_timeouts"]["upstream:gpt-4"].clone()
+}
+
+#[tokio::test]
+async fn test_first_token_timeout_ends_stream_with_error() {
+    let base_url = spawn_slow_upstream(Duration::ZERO, Duration::from_secs(3), Duration::ZERO).await;
+    let url = start_gateway(timeout_config(&base_url, Some(timeouts(Some(1), None, None)), None)).await.0;
+
+    let text = read_stream(chat(&url, "user-token", stream_body()).await).await;
+    let events = events(&text);
+    assert_eq!(events.len(), 1, "unexpected events: {}", text);
+    assert_eq!(events[0]["error"]["code"], "upstream_first_token_timeout");
+    assert_eq!(upstream_timeouts(&url).await, json!({"first_token": 1}));
+}
+
+#[tokio::test]
+async fn test_idle_timeout_between_chunks() {
+    let base_url = spawn_slow_upstream(Duration::ZERO, Duration::ZERO, Duration::from_secs(3)).await;
+    let url = start_gateway(timeout_config(&base_url, Some(timeouts(Some(1), Some(1), None)), None)).await.0;
+
+    let text = read_stream(chat(&url, "user-token", stream_body()).await).await;
+    let events = events(&text);
+    assert_eq!(events.len(), 2, "unexpected events: {}", text);
+    assert_eq!(events[0]["choices"][0]["delta"]["content"], "Hel");
+    assert_eq!(events[1]["error"]["code"], "upstream_idle_timeout");
+    assert_eq!(upstream_timeouts(&url).await, json!({"idle": 1}));
+}
+
+#[tokio::test]
+async fn test_total_timeout_for_non_streaming_request() {
+    let base_url = spawn_slow_upstream(Duration::from_secs(3), Duration::ZERO, Duration::ZERO).await;
+    let url = start_gateway(timeout_config(&base_url, Some(timeouts(None, None, Some(1))), None)).await.0;
+
+    let body: Value = chat(&url, "user-token", chat_body("gpt-4", "Hi")).await.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "upstream_timeout");
+    assert_eq!(body["error"]["status"], 504);
+    assert_eq!(upstream_timeouts(&url).await, json!({"total": 1}));
+}
+
+#[tokio::test]
+async fn test_model_timeouts_override_provider_defaults() {
+    let base_url = spawn_slow_upstream(Duration::ZERO, Duration::from_secs(2), Duration::ZERO).await;
+    let config = timeout_config(
+        &base_url,
+        Some(timeouts(Some(1), Some(1), None)),
+        Some(timeouts(Some(5), None, None)),
+    );
+    let url = start_gateway(config).await.0;
+
+    let text = read_stream(chat(&url, "user-token", stream_body()).await).await;
+    let content: String = events(&text)
+        .iter()
+        .filter_map(|event| event["choices"][0]["delta"]["content"].as_str().map(str::to_string))
+        .collect();
+    assert_eq!(content, "Hello", "unexpected stream: {}", text);
+    assert!(upstream_timeouts(&url).await.is_null());
+}
@@ -0,0 +1,205 @@
+use berry_api_api::config::model::{Provider, ProviderTls};
+use berry_api_api::loadbalance::MetricsCollector;
+use berry_api_api::relay::client::openai::OpenAIClient;
+use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+mod common;
+use common::provider_with_models;
+
+/// 测试用的内部PKI：一个CA签发的服务端证书和客户端证书，PEM写入临时目录
+struct TestPki {
+    dir: PathBuf,
+    server_chain: Vec<CertificateDer<'static>>,
+    server_key: PrivateKeyDer<'static>,
+    ca_cert: CertificateDer<'static>,
+}
+
+impl TestPki {
+    fn generate(name: &str) -> Self {
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let server_key = KeyPair::generate().unwrap();
+        let server = CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .signed_by(&server_key, &ca, &ca_key)
+            .unwrap();
+
+        let client_key = KeyPair::generate().unwrap();
+        let client = CertificateParams::new(vec!["berry".to_string()])
+            .unwrap()
+            .signed_by(&client_key, &ca, &ca_key)
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("berry-tls-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+        std::fs::write(dir.join("client.pem"), client.pem()).unwrap();
+        std::fs::write(dir.join("client-key.pem"), client_key.serialize_pem()).unwrap();
+
+        Self {
+            dir,
+            server_chain: vec![server.der().clone()],
+            server_key: PrivatePkcs8KeyDer::from(server_key.serialize_der()).into(),
+            ca_cert: ca.der().clone(),
+        }
+    }
+
+    fn path(&self, file: &str) -> Option<String> {
+        Some(self.dir.join(file).to_string_lossy().to_string())
+    }
+
+    /// 启动要求客户端证书的HTTPS上游，返回base_url
+    async fn spawn_mtls_upstream(&self) -> String {
+        let crypto_provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut client_roots = rustls::RootCertStore::empty();
+        client_roots.add(self.ca_cert.clone()).unwrap();
+        let client_verifier = rustls::server::WebPkiClientVerifier::builder_with_provider(
+            Arc::new(client_roots),
+            crypto_provider.clone(),
+        )
+        .build()
+        .unwrap();
+        let config = rustls::ServerConfig::builder_with_provider(crypto_provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(self.server_chain.clone(), self.server_key.clone_key())
+            .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let Ok(mut stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let mut buffer = [0u8; 4096];
+                    let _ = stream.read(&mut buffer).await;
+                    let _ = stream
+                        .write_all(b"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 11\r\nconnection: close\r\n\r\n{\"ok\":true}")
+                        .await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        format!("https://localhost:{}/v1", port)
+    }
+}
+
+impl Drop for TestPki {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// 使用给定TLS设置的provider直接发送一次请求
+async fn send(base_url: &str, tls: ProviderTls) -> Result<reqwest::Response, String> {
+    let provider = Provider {
+        name: "Internal vLLM".to_string(),
+        tls: Some(tls),
+        ..provider_with_models(base_url, &["llama"])
+    };
+    let client = OpenAIClient::for_provider(
+        "internal",
+        &provider,
+        Duration::from_secs(5),
+        Arc::new(MetricsCollector::new()),
+    )
+    .map_err(|e| e.to_string())?;
+    client
+        .chat_completions(reqwest::header::HeaderMap::new(), &serde_json::json!({}))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tokio::test]
+async fn test_mutual_tls_with_custom_ca() {
+    let pki = TestPki::generate("mtls");
+    let base_url = pki.spawn_mtls_upstream().await;
+
+    let tls = ProviderTls {
+        ca_file: pki.path("ca.pem"),
+        client_cert_file: pki.path("client.pem"),
+        client_key_file: pki.path("client-key.pem"),
+        insecure_skip_verify: false,
+    };
+    let response = send(&base_url, tls.clone()).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 不提供客户端证书时上游拒绝握手
+    let without_client_cert = ProviderTls {
+        client_cert_file: None,
+        client_key_file: None,
+        ..tls.clone()
+    };
+    assert!(send(&base_url, without_client_cert).await.is_err());
+
+    // 不信任内部CA时无法校验上游证书，跳过校验后可以连接
+    let without_ca = ProviderTls {
+        ca_file: None,
+        ..tls.clone()
+    };
+    assert!(send(&base_url, without_ca.clone()).await.is_err());
+    let skip_verify = ProviderTls {
+        insecure_skip_verify: true,
+        ..without_ca
+    };
+    let response = send(&base_url, skip_verify).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[test]
+fn test_tls_config_validation() {
+    let pki = TestPki::generate("validation");
+    let config: berry_api_api::config::model::Config = toml::from_str(
+        r#"
+        [providers]
+        [users]
+        [models.llama]
+        name = "llama"
+        [[models.llama.backends]]
+        provider = "internal"
+        model = "llama"
+        "#,
+    )
+    .unwrap();
+    let internal = provider_with_models("https://vllm.internal/v1", &["llama"]);
+    let validate = |tls: ProviderTls| {
+        let mut config = config.clone();
+        let provider = Provider {
+            tls: Some(tls),
+            ..internal.clone()
+        };
+        config.providers.insert("internal".to_string(), provider);
+        config.validate()
+    };
+
+    let tls = ProviderTls {
+        client_cert_file: pki.path("client.pem"),
+        ..Default::default()
+    };
+    assert!(validate(tls).is_err());
+
+    let tls = ProviderTls {
+        ca_file: pki.path("missing.pem"),
+        ..Default::default()
+    };
+    assert!(validate(tls).is_err());
+
+    let tls = ProviderTls {
+        ca_file: pki.path("ca.pem"),
+        ..Default::default()
+    };
+    validate(tls).unwrap();
+}
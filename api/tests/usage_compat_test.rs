@@ -0,0 +1,135 @@
+use berry_api_api::app::create_app;
+use berry_api_api::config::model::{Backend, BackendPricing, Provider, UserToken};
+use berry_api_api::loadbalance::{LoadBalanceService, TokenUsage};
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum_test::TestServer;
+use serde_json::Value;
+use std::sync::Arc;
+
+mod common;
+use common::{ConfigBuilder, admin, app_state, mapping, provider_with_models, user};
+
+/// 启动服务并为alice记录两次、为bob记录一次用量（每次1000+500 tokens，花费$0.025）
+fn create_test_server() -> TestServer {
+    let config = ConfigBuilder::new()
+        .provider("test-provider", Provider {
+            name: "Test Provider".to_string(),
+            timeout_seconds: 10,
+            health_check: None,
+            ..provider_with_models("https://api.test.com", &["test-model"])
+        })
+        .model("test-model", mapping("test-model", vec![Backend {
+            provider: "test-provider".to_string(),
+            model: "test-model".to_string(),
+            priority: 1,
+            pricing: Some(BackendPricing {
+                prompt_per_1k: 0.01,
+                completion_per_1k: 0.03,
+            }),
+            ..Default::default()
+        }]))
+        .user("admin", admin("Administrator", "admin-token"))
+        .user("alice", UserToken {
+            budget: Some(20.0),
+            ..user("alice", "alice-token")
+        })
+        .user("bob", user("bob", "bob-token"))
+        .build();
+    let load_balancer = Arc::new(LoadBalanceService::new(config).unwrap());
+    let config = load_balancer.get_config();
+    let backend = &config.models["test-model"].backends[0];
+    let tracker = load_balancer.get_cost_tracker();
+    let usage = TokenUsage { prompt_tokens: 1000, completion_tokens: 500 };
+    for name in ["alice", "alice", "bob"] {
+        tracker.record(name, "test-model", backend, usage);
+    }
+
+    TestServer::new(create_app(app_state(load_balancer))).unwrap()
+}
+
+fn bearer(token: &str) -> (HeaderName, HeaderValue) {
+    (
+        axum::http::header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", token)).unwrap(),
+    )
+}
+
+async fn get(server: &TestServer, path: &str, token: &str) -> Value {
+    let (name, value) = bearer(token);
+    let response = server.get(path).add_header(name, value).await;
+    assert_eq!(response.status_code(), StatusCode::OK, "{}", response.text());
+    response.json()
+}
+
+#[tokio::test]
+async fn test_billing_endpoints_report_own_usage() {
+    let server = create_test_server();
+    let today = chrono::Utc::now().date_naive();
+    let tomorrow = today.succ_opt().unwrap();
+
+    let subscription = get(&server, "/v1/dashboard/billing/subscription", "alice-token").await;
+    assert_eq!(subscription["hard_limit_usd"], 20.0);
+
+    let path = format!("/v1/dashboard/billing/usage?start_date={}&end_date={}", today, tomorrow);
+    let usage = get(&server, &path, "alice-token").await;
+    assert!((usage["total_usage"].as_f64().unwrap() - 5.0).abs() < 1e-9);
+    let line_items = usage["daily_costs"][0]["line_items"].as_array().unwrap();
+    assert_eq!(line_items[0]["name"], "test-model");
+
+    let legacy = get(&server, &format!("/v1/usage?date={}", today), "bob-token").await;
+    assert_eq!(legacy["data"][0]["n_requests"], 1);
+    assert_eq!(legacy["data"][0]["n_context_tokens_total"], 1000);
+
+    let (name, value) = bearer("alice-token");
+    let response = server.get("/v1/usage?date=yesterday").add_header(name, value).await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let (name, value) = bearer("invalid-token");
+    let response = server.get("/v1/dashboard/billing/subscription").add_header(name, value).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_organization_usage_buckets() {
+    let server = create_test_server();
+    let now = chrono::Utc::now().timestamp();
+    let start_time = now - 3 * 86400;
+
+    // 管理员按用户分组可以看到所有用户，空的时间桶也会返回
+    let path = format!("/v1/organization/usage/completions?start_time={}&group_by[]=user_id&group_by[]=model", start_time);
+    let page = get(&server, &path, "admin-token").await;
+    assert_eq!(page["object"], "page");
+    let buckets = page["data"].as_array().unwrap();
+    assert_eq!(buckets.len(), 4);
+    assert!(buckets[0]["results"].as_array().unwrap().is_empty());
+    let results = buckets[3]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["user_id"], "alice");
+    assert_eq!(results[0]["num_model_requests"], 2);
+    assert_eq!(results[0]["input_tokens"], 2000);
+    assert_eq!(results[1]["user_id"], "bob");
+
+    // 普通用户只能看到自己的用量；limit分页
+    let path = format!("/v1/organization/usage/completions?start_time={}&user_ids=bob&limit=2", start_time);
+    let page = get(&server, &path, "alice-token").await;
+    assert_eq!(page["has_more"], true);
+    let next = page["next_page"].as_str().unwrap().to_string();
+    let page = get(&server, &format!("/v1/organization/usage/completions?start_time={}&page={}", start_time, next), "alice-token").await;
+    let results = page["data"][1]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["num_model_requests"], 2);
+    assert!(results[0]["user_id"].is_null());
+
+    let path = format!("/v1/organization/costs?start_time={}&group_by=line_item", now);
+    let costs = get(&server, &path, "admin-token").await;
+    let result = &costs["data"][0]["results"][0];
+    assert_eq!(result["line_item"], "test-model");
+    assert!((result["amount"]["value"].as_f64().unwrap() - 0.075).abs() < 1e-9);
+
+    let (name, value) = bearer("admin-token");
+    let response = server
+        .get(&format!("/v1/organization/costs?start_time={}&bucket_width=1m", now))
+        .add_header(name, value)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
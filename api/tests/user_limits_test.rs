@@ -0,0 +1,197 @@
+use berry_api_api::config::model::{
+    ConcurrencyLimit, MaxTokensAction, ModelMapping, Provider, UserRequestLimits, UserToken,
+};
+use axum::body::{Body, Bytes};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use futures::StreamExt;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, chat_request, mapping, provider, reply_content, serve, start_gateway};
+
+/// 启动一个模拟上游：回复内容为收到的max_tokens，非流式响应等待delay后返回，流式响应的两个数据块之间等待delay
+async fn spawn_upstream(delay: Duration) -> String {
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move |axum::Json(body): axum::Json<Value>| async move {
+            let content = body["max_tokens"].to_string();
+            if body["stream"] != true {
+                tokio::time::sleep(delay).await;
+                return axum::Json(json!({
+                    "id": "chatcmpl-test",
+                    "object": "chat.completion",
+                    "choices": [{"index": 0, "message": {"role": "assistant", "content": content}, "finish_reason": "stop"}],
+                    "usage": {"prompt_tokens": 4, "completion_tokens": 1, "total_tokens": 5}
+                }))
+                .into_response();
+            }
+            let chunk = |content: &str| {
+                let chunk = json!({"id": "chatcmpl-s", "choices": [{"index": 0, "delta": {"content": content}}]});
+                Bytes::from(format!("data: {}\n\n", chunk))
+            };
+            let chunks = futures::stream::iter([
+                (Duration::ZERO, chunk(&content)),
+                (delay, chunk("more")),
+                (Duration::ZERO, Bytes::from_static(b"data: [DONE]\n\n")),
+            ])
+            .then(|(delay, bytes)| async move {
+                tokio::time::sleep(delay).await;
+                Ok::<_, std::convert::Infallible>(bytes)
+            });
+            ([("content-type", "text/event-stream")], Body::from_stream(chunks)).into_response()
+        }),
+    );
+    format!("{}/v1", serve(app).await)
+}
+
+fn user(name: &str, limits: UserRequestLimits) -> UserToken {
+    UserToken {
+        limits: Some(limits),
+        ..common::user(name, &format!("{}-token", name))
+    }
+}
+
+/// 启动上游和网关，每个用户的token为"用户名-token"，各自带有一种请求限制
+async fn start_server(delay: Duration) -> String {
+    let config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            ..provider(&spawn_upstream(delay).await)
+        })
+        .model("gpt-4", ModelMapping {
+            semantic_cache: false,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("clamped", user("clamped", UserRequestLimits {
+            max_tokens: Some(1000),
+            ..Default::default()
+        }))
+        .user("strict", user("strict", UserRequestLimits {
+            max_tokens: Some(1000),
+            on_max_tokens: MaxTokensAction::Reject,
+            max_duration_seconds: None,
+            concurrency: None,
+        }))
+        .user("hasty", user("hasty", UserRequestLimits {
+            max_duration_seconds: Some(1),
+            ..Default::default()
+        }))
+        .user("busy", user("busy", UserRequestLimits {
+            concurrency: Some(ConcurrencyLimit {
+                max_concurrent_requests: 1,
+                max_queue: 0,
+                queue_timeout_ms: 5000,
+            }),
+            ..Default::default()
+        }))
+        .user("patient", user("patient", UserRequestLimits {
+            concurrency: Some(ConcurrencyLimit {
+                max_concurrent_requests: 1,
+                max_queue: 1,
+                queue_timeout_ms: 5000,
+            }),
+            ..Default::default()
+        }))
+        .build();
+    start_gateway(config).await.0
+}
+
+/// 带有额外参数的请求体
+fn request(params: Value) -> Value {
+    let mut request = chat_body("gpt-4", "Hi");
+    request.as_object_mut().unwrap().extend(params.as_object().unwrap().clone());
+    request
+}
+
+#[tokio::test]
+async fn test_max_tokens_clamped_to_user_limit() {
+    let url = start_server(Duration::ZERO).await;
+
+    let response = chat(&url, "clamped-token", request(json!({"max_tokens": 100000}))).await;
+    assert_eq!(reply_content(response).await, "1000");
+    let response = chat(&url, "clamped-token", request(json!({"max_tokens": 50}))).await;
+    assert_eq!(reply_content(response).await, "50");
+    // 未指定时使用上限，避免不受限制的生成
+    let response = chat(&url, "clamped-token", chat_body("gpt-4", "Hi")).await;
+    assert_eq!(reply_content(response).await, "1000");
+}
+
+#[tokio::test]
+async fn test_max_tokens_over_limit_rejected() {
+    let url = start_server(Duration::ZERO).await;
+
+    let response = chat(&url, "strict-token", request(json!({"max_tokens": 5000}))).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "max_tokens_exceeded");
+
+    let response = chat(&url, "strict-token", request(json!({"max_tokens": 1000}))).await;
+    assert_eq!(reply_content(response).await, "1000");
+}
+
+#[tokio::test]
+async fn test_max_duration_cuts_off_stream() {
+    let url = start_server(Duration::from_secs(3)).await;
+
+    // 非流式请求通过保活立即开始响应，到期后在响应体中写入错误
+    let body: Value = chat(&url, "hasty-token", chat_body("gpt-4", "Hi")).await.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "max_duration_exceeded");
+    assert_eq!(body["error"]["status"], 408);
+
+    let mut response = chat(&url, "hasty-token", request(json!({"stream": true}))).await;
+    let mut text = String::new();
+    while !text.contains("max_duration_exceeded") {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .unwrap()
+            .unwrap()
+            .expect("stream ended without a duration error");
+        text.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    assert!(!text.contains("more"), "unexpected stream: {}", text);
+    // 截断后响应结束，不再发送保活
+    assert!(response.chunk().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_concurrent_requests_capped_per_user() {
+    let url = start_server(Duration::from_millis(500)).await;
+    let body = chat_body("gpt-4", "Hi");
+    let usage = async |token: &str| {
+        let usage: Value = reqwest::Client::new()
+            .get(format!("{}/v1/usage?date=2024-01-01", url))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        usage["in_flight_requests"].clone()
+    };
+
+    let first = tokio::spawn(chat_request(&url, "busy-token", &body).send());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(usage("busy-token").await, 1);
+
+    // 超出并发上限且不排队时立即返回429，其他用户不受影响
+    let response = chat(&url, "busy-token", body.clone()).await;
+    assert_eq!(response.status(), 429);
+    let error: Value = response.json().await.unwrap();
+    assert_eq!(error["error"]["code"], "rate_limit_exceeded");
+    assert!(error["error"]["details"].as_str().unwrap().contains("concurrent requests"));
+    assert_eq!(reply_content(chat(&url, "clamped-token", body.clone()).await).await, "1000");
+
+    assert_eq!(reply_content(first.await.unwrap().unwrap()).await, "null");
+    assert_eq!(usage("busy-token").await, 0);
+
+    // 允许排队时等待在途请求完成后继续处理
+    let first = tokio::spawn(chat_request(&url, "patient-token", &body).send());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let started = std::time::Instant::now();
+    assert_eq!(reply_content(chat(&url, "patient-token", body.clone()).await).await, "null");
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert_eq!(reply_content(first.await.unwrap().unwrap()).await, "null");
+}
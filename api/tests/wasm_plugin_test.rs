@@ -0,0 +1,112 @@
+#![cfg(feature = "wasm-plugins")]
+
+use berry_api_api::config::model::{GlobalSettings, LoadBalanceStrategy, ModelMapping, Provider, WasmPluginSettings};
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+mod common;
+use common::{ConfigBuilder, backend, chat, chat_body, mapping, provider, reply_content, serve, start_gateway, user};
+
+/// 把请求中的消息替换为固定内容
+const REWRITE_REQUEST_PLUGIN: &str = r#"(module
+    (memory (export "memory") 1)
+    (data (i32.const 0) "{\"model\":\"gpt-4\",\"messages\":[{\"role\":\"user\",\"content\":\"rewritten\"}]}")
+    (func (export "berry_alloc") (param i32) (result i32) (i32.const 1024))
+    (func (export "berry_on_request") (param i32 i32) (result i64) (i64.const 68)))"#;
+
+/// 把响应替换为固定内容
+const REWRITE_RESPONSE_PLUGIN: &str = r#"(module
+    (memory (export "memory") 1)
+    (data (i32.const 0) "{\"plugin\":\"response\"}")
+    (func (export "berry_alloc") (param i32) (result i32) (i32.const 1024))
+    (func (export "berry_on_response") (param i32 i32) (result i64) (i64.const 21)))"#;
+
+/// 启动一个回显最后一条消息内容的本地上游，返回地址和收到的消息内容
+async fn spawn_echo_upstream() -> (String, Arc<Mutex<Vec<String>>>) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = received.clone();
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        post(move |axum::Json(body): axum::Json<Value>| {
+            let log = log.clone();
+            async move {
+                let content = body["messages"][0]["content"].as_str().unwrap_or_default().to_string();
+                log.lock().unwrap().push(content.clone());
+                axum::Json(json!({
+                    "id": "chatcmpl-test",
+                    "object": "chat.completion",
+                    "choices": [{
+                        "index": 0,
+                        "message": {"role": "assistant", "content": format!("echo: {}", content)},
+                        "finish_reason": "stop"
+                    }]
+                }))
+            }
+        }),
+    );
+    (format!("{}/v1", serve(app).await), received)
+}
+
+/// 启动加载plugin_directory中插件的网关
+async fn start_server(base_url: &str, plugin_directory: &str) -> String {
+    let config = ConfigBuilder::new()
+        .provider("upstream", Provider {
+            name: "Upstream".to_string(),
+            ..provider(base_url)
+        })
+        .model("gpt-4", ModelMapping {
+            strategy: LoadBalanceStrategy::RoundRobin,
+            ..mapping("gpt-4", vec![backend("upstream", 1)])
+        })
+        .user("user", user("User", "user-token"))
+        .settings(GlobalSettings {
+            wasm_plugins: Some(WasmPluginSettings {
+                directory: plugin_directory.to_string(),
+                timeout_ms: 1000,
+                plugin_timeouts_ms: HashMap::new(),
+                max_memory_mb: 16,
+            }),
+            ..GlobalSettings::default()
+        })
+        .build();
+    start_gateway(config).await.0
+}
+
+fn plugin_directory(name: &str, plugins: &[(&str, &str)]) -> std::path::PathBuf {
+    let directory = std::env::temp_dir().join(format!("berry-wasm-test-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    for (file, source) in plugins {
+        std::fs::write(directory.join(file), source).unwrap();
+    }
+    directory
+}
+
+#[tokio::test]
+async fn test_request_plugin_rewrites_forwarded_body() {
+    let directory = plugin_directory("request", &[("rewrite.wat", REWRITE_REQUEST_PLUGIN)]);
+    let (base_url, received) = spawn_echo_upstream().await;
+
+    let url = start_server(&base_url, &directory.to_string_lossy()).await;
+
+    let response = chat(&url, "user-token", chat_body("gpt-4", "original")).await;
+    assert_eq!(reply_content(response).await, "echo: rewritten");
+    assert_eq!(received.lock().unwrap().as_slice(), ["rewritten"]);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[tokio::test]
+async fn test_response_plugin_rewrites_client_response() {
+    let directory = plugin_directory("response", &[("rewrite.wat", REWRITE_RESPONSE_PLUGIN)]);
+    let (base_url, received) = spawn_echo_upstream().await;
+
+    let url = start_server(&base_url, &directory.to_string_lossy()).await;
+
+    let response = chat(&url, "user-token", chat_body("gpt-4", "original")).await;
+    let body: Value = serde_json::from_str(response.text().await.unwrap().trim()).unwrap();
+    assert_eq!(received.lock().unwrap().as_slice(), ["original"]);
+    assert_eq!(body, json!({"plugin": "response"}));
+    let _ = std::fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,1511 @@
+use anyhow::{Context, Result, bail};
+use berry_api_api::config::check::check_config;
+use berry_api_api::config::loader::{ConfigFormat, config_path, load_config_with_format, serialize_config};
+use clap::{Parser, Subcommand, ValueEnum};
+use eventsource_stream::Eventsource;
+use futures::StreamExt;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+/// Berry API 远程管理工具
+#[derive(Parser)]
+#[command(name = "berryctl", version, about = "Berry API 远程管理工具")]
+struct Cli {
+    /// Berry API 服务地址
+    #[arg(long, env = "BERRY_URL", default_value = "http://127.0.0.1:3000")]
+    url: String,
+
+    /// 管理员令牌（所属用户需带有 admin 标签，租户管理员只能使用带 --tenant 的命令），本地命令不需要
+    #[arg(long, env = "BERRY_ADMIN_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// 输出格式
+    #[arg(long, short, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 查看服务状态
+    Status,
+    /// 管理后端
+    Backends {
+        #[command(subcommand)]
+        command: BackendsCommand,
+    },
+    /// 预演后端选择（不发送请求），查看各后端的选中分布
+    RoutePreview {
+        /// 模型ID或名称
+        model: String,
+        /// 选择次数
+        #[arg(short, default_value_t = 1000)]
+        n: usize,
+        /// 按该策略预演，默认使用模型配置的策略
+        #[arg(long)]
+        strategy: Option<String>,
+        /// 按来自该区域的请求预演
+        #[arg(long)]
+        region: Option<String>,
+    },
+    /// 从配置文件重新加载配置
+    Reload,
+    /// 管理API密钥
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommand,
+    },
+    /// 实时查看服务事件
+    TailEvents,
+    /// 查看或临时修改服务的日志过滤规则（EnvFilter语法），无需修改RUST_LOG重启服务
+    LogFilter {
+        /// 新的过滤规则，如 info,berry_api_api::relay=trace；省略时查看当前规则
+        filter: Option<String>,
+        /// 规则生效的时长（秒），到期后恢复启动时的规则
+        #[arg(long)]
+        duration: Option<u64>,
+        /// 恢复启动时的规则
+        #[arg(long, conflicts_with_all = ["filter", "duration"])]
+        reset: bool,
+    },
+    /// 查看按时间和维度聚合的用量报告（请求数、token数、估算花费）
+    Usage {
+        /// 起始时间（含），YYYY-MM-DD或RFC3339，UTC
+        #[arg(long)]
+        start: Option<String>,
+        /// 结束时间（不含），格式同start
+        #[arg(long)]
+        end: Option<String>,
+        /// 时间分桶：hour、day、month、total
+        #[arg(long, default_value = "day")]
+        bucket: String,
+        /// 分组维度，逗号分隔：user、model、provider
+        #[arg(long, value_delimiter = ',')]
+        group_by: Vec<String>,
+        /// 只统计该用户
+        #[arg(long)]
+        user: Option<String>,
+        /// 只统计该模型
+        #[arg(long)]
+        model: Option<String>,
+        /// 只统计该provider
+        #[arg(long)]
+        provider: Option<String>,
+    },
+    /// 对后端注入延迟、错误和断流，验证故障转移和恢复（服务需开启settings.fault_injection）
+    Faults {
+        #[command(subcommand)]
+        command: FaultsCommand,
+    },
+    /// 把抓取的请求重新发送给服务（重新经过后端选择），对比响应状态码，有不一致时以非零状态退出
+    Replay {
+        /// 抓取文件（request_capture写出的JSON Lines）
+        file: String,
+        /// 只回放该用户的请求
+        #[arg(long)]
+        user: Option<String>,
+        /// 改用该模型回放
+        #[arg(long)]
+        model: Option<String>,
+        /// 最多回放的请求数
+        #[arg(long)]
+        limit: Option<usize>,
+        /// 回放使用的API密钥，默认使用管理员令牌（此时响应附带选择过程说明）
+        #[arg(long, env = "BERRY_API_KEY", hide_env_values = true)]
+        api_key: Option<String>,
+    },
+    /// 以固定速率向服务发送合成请求，统计选中后端的分布、延迟分位数和错误率，用于容量规划
+    Bench {
+        /// 模型ID或名称
+        #[arg(long)]
+        model: String,
+        /// 每秒发送的请求数
+        #[arg(long, default_value_t = 10.0)]
+        rps: f64,
+        /// 压测时长，如 60s、5m，不带单位时为秒
+        #[arg(long, default_value = "60s", value_parser = parse_duration)]
+        duration: std::time::Duration,
+        /// 使用流式请求，同时统计首个数据块的延迟
+        #[arg(long)]
+        stream: bool,
+        /// 每个请求的max_tokens
+        #[arg(long, default_value_t = 16)]
+        max_tokens: u32,
+        /// 最多同时进行的请求数，达到上限时跳过本次发送
+        #[arg(long, default_value_t = 256)]
+        concurrency: usize,
+        /// 压测使用的API密钥，默认使用管理员令牌（此时可以统计选中的后端）
+        #[arg(long, env = "BERRY_API_KEY", hide_env_values = true)]
+        api_key: Option<String>,
+    },
+    /// 查看、修改运行中的配置，以及本地配置文件工具
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// 查看服务当前的配置（密钥已隐藏）
+    Show {
+        /// 只查看该租户的配置，租户管理员必须指定
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+    /// 按JSON Merge Patch修改服务配置，校验通过后立即生效
+    Patch {
+        /// JSON格式的修改内容，以@开头时从文件读取，如 @patch.json
+        patch: String,
+        /// 修改该租户的配置（内容相对于租户，租户管理员只能修改providers、models和users）
+        #[arg(long)]
+        tenant: Option<String>,
+        /// 同时写回服务端配置文件
+        #[arg(long)]
+        persist: bool,
+    },
+    /// 查看已应用配置的历史版本
+    History,
+    /// 回滚到历史版本的配置
+    Rollback {
+        /// 目标版本，省略时回滚到上一个版本
+        version: Option<u64>,
+        /// 同时写回服务端配置文件
+        #[arg(long)]
+        persist: bool,
+    },
+    /// 在TOML和JSON格式之间转换配置文件，格式默认按扩展名判断
+    Convert {
+        /// 输入配置文件
+        input: String,
+        /// 输出文件，省略时输出到标准输出
+        output: Option<String>,
+        /// 输入格式：toml、json
+        #[arg(long)]
+        from: Option<String>,
+        /// 输出格式：toml、json，省略且未指定输出文件时转换为另一种格式
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// 校验配置文件并解析外部密钥引用，有错误时以非零状态退出，适用于CI
+    Validate {
+        /// 配置文件，省略时使用CONFIG_PATH环境变量或config.toml
+        path: Option<String>,
+        /// 输入格式：toml、json，默认按扩展名判断
+        #[arg(long)]
+        format: Option<String>,
+        /// 请求每个启用provider的models接口，检查连通性和密钥
+        #[arg(long)]
+        probe: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackendsCommand {
+    /// 列出所有后端
+    List,
+    /// 运行时禁用后端
+    Disable { provider: String, model: String },
+    /// 重新启用后端
+    Enable { provider: String, model: String },
+}
+
+#[derive(Subcommand)]
+enum FaultsCommand {
+    /// 列出注入的故障和注入次数
+    List,
+    /// 注入故障，替换该后端已有的故障
+    Set {
+        provider: String,
+        model: String,
+        /// 发送上游请求前额外等待的毫秒数
+        #[arg(long, default_value_t = 0)]
+        latency_ms: u64,
+        /// 直接返回错误的请求比例（百分比）
+        #[arg(long, default_value_t = 0.0)]
+        error_percent: f64,
+        /// 注入错误的状态码
+        #[arg(long, default_value_t = 503)]
+        error_status: u16,
+        /// 流式响应中途断开的比例（百分比）
+        #[arg(long, default_value_t = 0.0)]
+        drop_stream_percent: f64,
+        /// 断开前转发的数据块数
+        #[arg(long, default_value_t = 1)]
+        drop_after_chunks: usize,
+        /// 故障生效的时长（秒），到期后自动清除
+        #[arg(long)]
+        duration: Option<u64>,
+    },
+    /// 清除后端的故障，省略后端时清除所有故障
+    Clear {
+        #[arg(requires = "model")]
+        provider: Option<String>,
+        model: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// 创建运行时API密钥（服务端配置了keys_file时持久化，否则重启后失效）
+    Create {
+        /// 密钥名称
+        #[arg(long)]
+        name: String,
+        /// 允许访问的模型ID，可重复；不指定表示允许所有模型
+        #[arg(long = "model")]
+        models: Vec<String>,
+        /// 用户标签，可重复
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// 累计花费上限（美元）
+        #[arg(long)]
+        budget: Option<f64>,
+        /// 请求优先级：high、normal、batch
+        #[arg(long)]
+        priority: Option<String>,
+        /// 只允许从该IP或CIDR使用密钥，可重复
+        #[arg(long = "allow-ip")]
+        allowed_ips: Vec<String>,
+        /// 为该租户创建密钥，模型ID为租户内的ID
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+    /// 吊销运行时API密钥
+    Revoke {
+        name: String,
+        /// 吊销该租户的密钥，名称不带租户前缀
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+    /// 计算密钥的SHA-256哈希，用于在配置文件中以token_hash代替明文token
+    Hash { token: String },
+}
+
+/// 管理API客户端
+struct AdminClient {
+    base_url: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl AdminClient {
+    fn new(base_url: &str, token: Option<&str>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: token.map(str::to_string),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// 管理员令牌，只有访问服务的命令需要
+    fn token(&self) -> Result<&str> {
+        self.token
+            .as_deref()
+            .context("Admin token is required: pass --token or set BERRY_ADMIN_TOKEN")
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/admin/{}", self.base_url, path)
+    }
+
+    async fn get(&self, path: &str) -> Result<Value> {
+        self.get_with_query(path, &[]).await
+    }
+
+    async fn get_with_query(&self, path: &str, query: &[(&str, String)]) -> Result<Value> {
+        let response = self
+            .http
+            .get(self.url(path))
+            .query(query)
+            .bearer_auth(self.token()?)
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.base_url))?;
+        Self::parse(response).await
+    }
+
+    async fn post(&self, path: &str, body: Value) -> Result<Value> {
+        let response = self
+            .http
+            .post(self.url(path))
+            .bearer_auth(self.token()?)
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.base_url))?;
+        Self::parse(response).await
+    }
+
+    async fn post_with_query(&self, path: &str, query: &[(&str, String)], body: Value) -> Result<Value> {
+        let response = self
+            .http
+            .post(self.url(path))
+            .query(query)
+            .bearer_auth(self.token()?)
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.base_url))?;
+        Self::parse(response).await
+    }
+
+    async fn patch(&self, path: &str, query: &[(&str, String)], body: Value) -> Result<Value> {
+        let response = self
+            .http
+            .patch(self.url(path))
+            .query(query)
+            .bearer_auth(self.token()?)
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.base_url))?;
+        Self::parse(response).await
+    }
+
+    async fn put(&self, path: &str, body: Value) -> Result<Value> {
+        let response = self
+            .http
+            .put(self.url(path))
+            .bearer_auth(self.token()?)
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.base_url))?;
+        Self::parse(response).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<Value> {
+        self.delete_with_query(path, &[]).await
+    }
+
+    async fn delete_with_query(&self, path: &str, query: &[(&str, String)]) -> Result<Value> {
+        let response = self
+            .http
+            .delete(self.url(path))
+            .query(query)
+            .bearer_auth(self.token()?)
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.base_url))?;
+        Self::parse(response).await
+    }
+
+    async fn parse(response: reqwest::Response) -> Result<Value> {
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            let message = body["error"]["message"].as_str().unwrap_or("request failed");
+            match body["error"]["details"].as_str() {
+                Some(details) => bail!("{} ({}): {}", message, status, details),
+                None => bail!("{} ({})", message, status),
+            }
+        }
+        Ok(body)
+    }
+}
+
+/// 以对齐的列打印表格
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<String>| {
+        cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| format!("{:<width$}", cell, width = widths[i]))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", format_row(headers.iter().map(|h| h.to_string()).collect()));
+    for row in rows {
+        println!("{}", format_row(row.clone()));
+    }
+}
+
+/// 将JSON值转换为表格单元格文本
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn print_json(value: &Value) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// 转换配置文件格式，转换前按服务端规则校验配置，include 的文件会合并到输出中
+fn convert_config(input: &str, output: Option<&str>, from: Option<&str>, to: Option<&str>) -> Result<()> {
+    let from = match from {
+        Some(name) => ConfigFormat::from_name(name)?,
+        None => ConfigFormat::from_path(input),
+    };
+    let to = match (to, output) {
+        (Some(name), _) => ConfigFormat::from_name(name)?,
+        (None, Some(path)) => ConfigFormat::from_path(path),
+        (None, None) if from == ConfigFormat::Toml => ConfigFormat::Json,
+        (None, None) => ConfigFormat::Toml,
+    };
+
+    let config = load_config_with_format(input, from).with_context(|| format!("Failed to load {}", input))?;
+    config.validate().with_context(|| format!("Invalid configuration in {}", input))?;
+    let converted = serialize_config(&config, to)?;
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, converted).with_context(|| format!("Failed to write {}", path))?;
+            eprintln!("Converted {} to {}", input, path);
+        }
+        None => print!("{}", converted),
+    }
+    Ok(())
+}
+
+/// 校验配置文件并打印各provider的检查结果，任一检查失败时返回错误
+async fn validate_config(path: &str, format: Option<&str>, probe: bool, output: OutputFormat) -> Result<()> {
+    let format = match format {
+        Some(name) => ConfigFormat::from_name(name)?,
+        None => ConfigFormat::for_path(path)?,
+    };
+    let config = load_config_with_format(path, format).with_context(|| format!("Failed to load {}", path))?;
+    let report = check_config(&config, probe)
+        .await
+        .with_context(|| format!("Invalid configuration in {}", path))?;
+
+    if output == OutputFormat::Json {
+        let providers: Vec<Value> = report
+            .providers
+            .iter()
+            .map(|p| {
+                json!({
+                    "provider": p.provider_id,
+                    "enabled": p.enabled,
+                    "models": p.models,
+                    "key_source": p.key_source,
+                    "probe_status": p.probe.as_ref().and_then(|probe| probe.status),
+                    "probe_latency_ms": p.probe.as_ref().map(|probe| probe.latency.as_millis() as u64),
+                    "error": p.error,
+                })
+            })
+            .collect();
+        print_json(&json!({
+            "valid": report.is_ok(),
+            "models": report.models,
+            "users": report.users,
+            "providers": providers,
+        }))?;
+    } else {
+        let rows: Vec<Vec<String>> = report
+            .providers
+            .iter()
+            .map(|p| {
+                let probe = match &p.probe {
+                    Some(probe) => format!(
+                        "{} ({}ms)",
+                        probe.status.map_or("-".to_string(), |s| s.to_string()),
+                        probe.latency.as_millis()
+                    ),
+                    None if p.enabled => "-".to_string(),
+                    None => "disabled".to_string(),
+                };
+                vec![
+                    p.provider_id.clone(),
+                    p.models.to_string(),
+                    p.key_source.to_string(),
+                    probe,
+                    if p.error.is_none() { "ok".to_string() } else { "FAIL".to_string() },
+                ]
+            })
+            .collect();
+        print_table(&["PROVIDER", "MODELS", "KEY", "PROBE", "RESULT"], &rows);
+        for p in report.providers.iter().filter(|p| p.error.is_some()) {
+            eprintln!("{}: {}", p.provider_id, p.error.as_deref().unwrap_or_default());
+        }
+        println!(
+            "\n{} providers, {} models, {} users",
+            report.providers.len(),
+            report.models,
+            report.users
+        );
+    }
+
+    if !report.is_ok() {
+        bail!("Configuration check failed for {}", path);
+    }
+    Ok(())
+}
+
+/// 只允许管理员使用的路由请求头，网关拒绝非管理员携带它们的请求
+const ADMIN_ONLY_HEADERS: &[&str] = &["x-berry-strategy", "x-berry-backend"];
+
+/// 回放时转发的抓取请求头：抓取和选择说明由回放工具决定，其余请求头由HTTP客户端重新生成；
+/// 不使用管理员令牌回放时不转发只允许管理员使用的请求头
+fn replayed_header(name: &str, admin: bool) -> bool {
+    name.starts_with("x-berry-")
+        && name != "x-berry-capture"
+        && name != "x-berry-debug"
+        && (admin || !ADMIN_ONLY_HEADERS.contains(&name))
+}
+
+/// 逐条回放抓取的请求，打印每条请求的原状态码和回放结果，有状态码不一致时返回错误
+async fn replay_captures(
+    client: &AdminClient,
+    file: &str,
+    user: Option<&str>,
+    model: Option<&str>,
+    limit: Option<usize>,
+    api_key: Option<&str>,
+    output: OutputFormat,
+) -> Result<()> {
+    let content = std::fs::read_to_string(file).with_context(|| format!("Failed to read {}", file))?;
+    let captures: Vec<Value> = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| serde_json::from_str(line).with_context(|| format!("Invalid capture on line {}", i + 1)))
+        .collect::<Result<_>>()?;
+    // 使用管理员令牌时请求选择过程说明，用于显示回放时选中的后端
+    let (token, admin) = match api_key {
+        Some(key) => (key, false),
+        None => (client.token()?, true),
+    };
+
+    let mut results = Vec::new();
+    for capture in captures
+        .iter()
+        .filter(|c| user.is_none_or(|user| c["user"] == user))
+        .take(limit.unwrap_or(usize::MAX))
+    {
+        let mut body = capture["request_body"].clone();
+        if let Some(model) = model {
+            body["model"] = json!(model);
+        }
+        let mut request = client
+            .http
+            .post(format!("{}{}", client.base_url, cell(&capture["path"])))
+            .bearer_auth(token)
+            .json(&body);
+        if let Some(headers) = capture["request_headers"].as_object() {
+            for (name, value) in headers.iter().filter(|(name, _)| replayed_header(name, admin)) {
+                request = request.header(name, cell(value));
+            }
+        }
+        if admin {
+            request = request.header("x-berry-debug", "1");
+        }
+
+        let start = std::time::Instant::now();
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to {}", client.base_url))?;
+        let status = response.status().as_u16();
+        let attempts: Vec<Value> = response
+            .headers()
+            .get("x-berry-debug-attempts")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| serde_json::from_str(v).ok())
+            .unwrap_or_default();
+        // 响应体读完才算回放结束（流式响应和保活响应）
+        let _ = response.bytes().await;
+        results.push(json!({
+            "request_id": capture["request_id"],
+            "path": capture["path"],
+            "model": body["model"],
+            "recorded_status": capture["status"],
+            "recorded_backend": capture["backend"],
+            "status": status,
+            "backend": attempts.last().map(|attempt| attempt["backend"].clone()),
+            "attempts": attempts.len(),
+            "latency_ms": start.elapsed().as_millis() as u64,
+            "matched": capture["status"] == status,
+        }));
+    }
+
+    let mismatched = results.iter().filter(|r| r["matched"] != true).count();
+    if output == OutputFormat::Json {
+        print_json(&json!(results))?;
+    } else {
+        let rows: Vec<Vec<String>> = results
+            .iter()
+            .map(|r| {
+                vec![
+                    cell(&r["request_id"]),
+                    cell(&r["path"]),
+                    cell(&r["model"]),
+                    format!("{} -> {}", cell(&r["recorded_status"]), cell(&r["status"])),
+                    format!("{} -> {}", cell(&r["recorded_backend"]), cell(&r["backend"])),
+                    format!("{}ms", cell(&r["latency_ms"])),
+                    if r["matched"] == true { "ok".to_string() } else { "MISMATCH".to_string() },
+                ]
+            })
+            .collect();
+        print_table(&["REQUEST_ID", "PATH", "MODEL", "STATUS", "BACKEND", "LATENCY", "RESULT"], &rows);
+        println!("\n{} replayed, {} mismatched", results.len(), mismatched);
+    }
+
+    if mismatched > 0 {
+        bail!("{} of {} replayed requests returned a different status", mismatched, results.len());
+    }
+    Ok(())
+}
+
+/// 解析时长参数：60、60s、500ms、5m、1h
+fn parse_duration(value: &str) -> std::result::Result<std::time::Duration, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse().map_err(|_| format!("invalid duration '{}'", value))?;
+    let unit_millis: u64 = match unit {
+        "ms" => 1,
+        "" | "s" => 1000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        _ => return Err(format!("invalid duration unit '{}', expected ms, s, m or h", unit)),
+    };
+    let millis = number
+        .checked_mul(unit_millis)
+        .ok_or_else(|| format!("duration '{}' is too large", value))?;
+    Ok(std::time::Duration::from_millis(millis))
+}
+
+/// 压测的合成提示词，每个请求附带序号，避免命中响应缓存
+const BENCH_PROMPTS: &[&str] = &[
+    "Summarize the main benefits of automated testing in two sentences.",
+    "Write a haiku about load balancers.",
+    "Explain what a circuit breaker does in distributed systems.",
+    "List three ways to reduce API latency.",
+    "Describe the difference between a process and a thread.",
+    "Give a short definition of exponential backoff.",
+    "Translate 'the service is healthy' into French.",
+    "What is the capital of Australia? Answer briefly.",
+];
+
+struct BenchOptions {
+    model: String,
+    rps: f64,
+    duration: std::time::Duration,
+    stream: bool,
+    max_tokens: u32,
+    concurrency: usize,
+    api_key: Option<String>,
+}
+
+/// 一次压测请求的结果
+struct BenchSample {
+    /// 响应状态码，连接失败时为空
+    status: Option<u16>,
+    /// 选中的后端，命中缓存时为cache，没有选择过程说明时为空
+    backend: Option<String>,
+    latency: std::time::Duration,
+    /// 流式请求收到首个数据块的延迟
+    first_chunk: Option<std::time::Duration>,
+    failed: bool,
+}
+
+async fn send_bench_request(
+    http: reqwest::Client,
+    url: String,
+    token: String,
+    debug: bool,
+    body: Value,
+    stream: bool,
+) -> BenchSample {
+    let start = std::time::Instant::now();
+    let mut request = http.post(url).bearer_auth(token).json(&body);
+    if debug {
+        request = request.header("x-berry-debug", "1");
+    }
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(_) => {
+            return BenchSample {
+                status: None,
+                backend: None,
+                latency: start.elapsed(),
+                first_chunk: None,
+                failed: true,
+            };
+        }
+    };
+
+    let status = response.status();
+    let cached = response
+        .headers()
+        .get("x-berry-cache")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.ends_with("hit"));
+    let backend = if cached {
+        Some("cache".to_string())
+    } else {
+        response
+            .headers()
+            .get("x-berry-debug-attempts")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| serde_json::from_str::<Vec<Value>>(v).ok())
+            .and_then(|attempts| attempts.last().and_then(|a| a["backend"].as_str().map(str::to_string)))
+    };
+
+    let mut failed = !status.is_success();
+    let mut first_chunk = None;
+    if stream && status.is_success() {
+        // 服务在流结束后仍会发送心跳，读到[DONE]或错误事件即视为结束
+        let mut events = response.bytes_stream();
+        let mut received = String::new();
+        failed = true;
+        while let Some(chunk) = events.next().await {
+            let Ok(chunk) = chunk else { break };
+            first_chunk.get_or_insert_with(|| start.elapsed());
+            received.push_str(&String::from_utf8_lossy(&chunk));
+            if received.contains("data: [DONE]") {
+                failed = false;
+                break;
+            }
+            if received.contains("\"error\"") {
+                break;
+            }
+        }
+    } else {
+        failed |= response.bytes().await.is_err();
+    }
+
+    BenchSample {
+        status: Some(status.as_u16()),
+        backend,
+        latency: start.elapsed(),
+        first_chunk,
+        failed,
+    }
+}
+
+/// 按最近秩法计算分位数（毫秒），samples需已排序
+fn percentile_ms(samples: &[std::time::Duration], p: f64) -> Option<u64> {
+    if samples.is_empty() {
+        return None;
+    }
+    let rank = ((p / 100.0) * samples.len() as f64).ceil() as usize;
+    Some(samples[rank.clamp(1, samples.len()) - 1].as_millis() as u64)
+}
+
+fn latency_summary(mut samples: Vec<std::time::Duration>) -> Value {
+    samples.sort();
+    json!({
+        "p50_ms": percentile_ms(&samples, 50.0),
+        "p90_ms": percentile_ms(&samples, 90.0),
+        "p99_ms": percentile_ms(&samples, 99.0),
+        "max_ms": samples.last().map(|d| d.as_millis() as u64),
+    })
+}
+
+async fn run_bench(client: &AdminClient, options: BenchOptions, output: OutputFormat) -> Result<()> {
+    if !(options.rps > 0.0 && options.rps.is_finite()) {
+        bail!("--rps must be greater than 0");
+    }
+    if options.duration.is_zero() {
+        bail!("--duration must be greater than 0");
+    }
+    if options.concurrency == 0 {
+        bail!("--concurrency must be greater than 0");
+    }
+    // 使用管理员令牌时请求选择过程说明，用于统计选中的后端
+    let (token, debug) = match options.api_key.as_deref() {
+        Some(key) => (key.to_string(), false),
+        None => (client.token()?.to_string(), true),
+    };
+    let url = format!("{}/v1/chat/completions", client.base_url);
+    if output == OutputFormat::Table {
+        eprintln!(
+            "sending {} req/s to {} for {}s ...",
+            options.rps,
+            options.model,
+            options.duration.as_secs_f64()
+        );
+    }
+
+    let slots = std::sync::Arc::new(tokio::sync::Semaphore::new(options.concurrency));
+    let mut tasks = tokio::task::JoinSet::new();
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs_f64(1.0 / options.rps));
+    let start = std::time::Instant::now();
+    let mut sent: u64 = 0;
+    let mut skipped: u64 = 0;
+    loop {
+        ticker.tick().await;
+        if start.elapsed() >= options.duration {
+            break;
+        }
+        let Ok(permit) = slots.clone().try_acquire_owned() else {
+            skipped += 1;
+            continue;
+        };
+        let prompt = BENCH_PROMPTS[sent as usize % BENCH_PROMPTS.len()];
+        let body = json!({
+            "model": options.model,
+            "messages": [{"role": "user", "content": format!("[bench #{}] {}", sent, prompt)}],
+            "max_tokens": options.max_tokens,
+            "stream": options.stream,
+        });
+        sent += 1;
+        let request = send_bench_request(client.http.clone(), url.clone(), token.clone(), debug, body, options.stream);
+        tasks.spawn(async move {
+            let sample = request.await;
+            drop(permit);
+            sample
+        });
+    }
+    let samples: Vec<BenchSample> = tasks.join_all().await;
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let mut backends: std::collections::BTreeMap<String, (u64, u64)> = std::collections::BTreeMap::new();
+    let mut statuses: std::collections::BTreeMap<String, u64> = std::collections::BTreeMap::new();
+    for sample in &samples {
+        let entry = backends.entry(sample.backend.clone().unwrap_or_else(|| "-".to_string())).or_default();
+        entry.0 += 1;
+        entry.1 += sample.failed as u64;
+        let status = sample.status.map_or_else(|| "connection_error".to_string(), |s| s.to_string());
+        *statuses.entry(status).or_default() += 1;
+    }
+    let completed = samples.len() as u64;
+    let errors = samples.iter().filter(|s| s.failed).count() as u64;
+    let rate = |n: u64, total: u64| if total == 0 { 0.0 } else { n as f64 * 100.0 / total as f64 };
+    let report = json!({
+        "model": options.model,
+        "target_rps": options.rps,
+        "duration_seconds": elapsed,
+        "sent": sent,
+        "skipped": skipped,
+        "achieved_rps": completed as f64 / elapsed,
+        "errors": errors,
+        "error_percent": rate(errors, completed),
+        "statuses": statuses,
+        "latency": latency_summary(samples.iter().filter(|s| !s.failed).map(|s| s.latency).collect()),
+        "first_chunk_latency": options.stream.then(|| latency_summary(samples.iter().filter_map(|s| s.first_chunk).collect())),
+        "backends": backends
+            .iter()
+            .map(|(backend, (requests, errors))| json!({
+                "backend": backend,
+                "requests": requests,
+                "percent": rate(*requests, completed),
+                "errors": errors,
+            }))
+            .collect::<Vec<_>>(),
+    });
+
+    if output == OutputFormat::Json {
+        return print_json(&report);
+    }
+    println!(
+        "sent: {}  skipped: {}  achieved: {:.1} req/s  errors: {} ({:.2}%)",
+        sent,
+        skipped,
+        completed as f64 / elapsed,
+        errors,
+        rate(errors, completed)
+    );
+    println!(
+        "status: {}",
+        statuses.iter().map(|(status, n)| format!("{}={}", status, n)).collect::<Vec<_>>().join(" ")
+    );
+    println!();
+    let mut rows = vec![vec!["total".to_string()]];
+    let mut latencies = vec![&report["latency"]];
+    if options.stream {
+        rows.push(vec!["first_chunk".to_string()]);
+        latencies.push(&report["first_chunk_latency"]);
+    }
+    for (row, latency) in rows.iter_mut().zip(latencies) {
+        for key in ["p50_ms", "p90_ms", "p99_ms", "max_ms"] {
+            row.push(cell(&latency[key]));
+        }
+    }
+    print_table(&["LATENCY", "P50_MS", "P90_MS", "P99_MS", "MAX_MS"], &rows);
+    println!();
+    let rows: Vec<Vec<String>> = report["backends"]
+        .as_array()
+        .map(|backends| {
+            backends
+                .iter()
+                .map(|b| {
+                    vec![
+                        cell(&b["backend"]),
+                        cell(&b["requests"]),
+                        format!("{:.1}%", b["percent"].as_f64().unwrap_or(0.0)),
+                        cell(&b["errors"]),
+                    ]
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    print_table(&["BACKEND", "REQUESTS", "SHARE", "ERRORS"], &rows);
+    Ok(())
+}
+
+/// 指定租户时使用租户管理接口的路径
+fn tenant_path(tenant: Option<&str>, path: &str) -> String {
+    match tenant {
+        Some(tenant) => format!("tenants/{}/{}", tenant, path),
+        None => path.to_string(),
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let client = AdminClient::new(&cli.url, cli.token.as_deref());
+    let output = cli.output;
+
+    match cli.command {
+        Command::Status => {
+            let status = client.get("status").await?;
+            if output == OutputFormat::Json {
+                return print_json(&status);
+            }
+
+            println!(
+                "service: {}  healthy: {}  version: {}",
+                if status["service_running"].as_bool() == Some(true) { "running" } else { "stopped" },
+                cell(&status["healthy"]),
+                cell(&status["version"]["git_sha"])
+            );
+            println!(
+                "providers: {}/{} healthy  runtime keys: {}",
+                cell(&status["providers"]["healthy"]),
+                cell(&status["providers"]["total"]),
+                cell(&status["runtime_keys"])
+            );
+            println!();
+
+            let mut rows: Vec<Vec<String>> = status["models"]
+                .as_object()
+                .map(|models| {
+                    models
+                        .iter()
+                        .map(|(model, health)| {
+                            vec![
+                                model.clone(),
+                                cell(&health["state"]),
+                                format!(
+                                    "{}/{}",
+                                    cell(&health["healthy_backends"]),
+                                    cell(&health["total_backends"])
+                                ),
+                            ]
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            rows.sort();
+            print_table(&["MODEL", "STATE", "HEALTHY"], &rows);
+        }
+        Command::Backends { command } => match command {
+            BackendsCommand::List => {
+                let result = client.get("backends").await?;
+                if output == OutputFormat::Json {
+                    return print_json(&result);
+                }
+
+                let rows: Vec<Vec<String>> = result["backends"]
+                    .as_array()
+                    .map(|backends| {
+                        backends
+                            .iter()
+                            .map(|b| {
+                                let state = if b["admin_disabled"].as_bool() == Some(true) {
+                                    "disabled(admin)"
+                                } else if b["enabled"].as_bool() != Some(true) {
+                                    "disabled"
+                                } else if b["healthy"].as_bool() == Some(true) {
+                                    "healthy"
+                                } else {
+                                    "unhealthy"
+                                };
+                                vec![
+                                    cell(&b["model_id"]),
+                                    format!("{}:{}", cell(&b["provider"]), cell(&b["model"])),
+                                    state.to_string(),
+                                    format!("{:.2}", b["health_score"]["score"].as_f64().unwrap_or(0.0)),
+                                    cell(&b["weight"]),
+                                    cell(&b["priority"]),
+                                    cell(&b["latency_ms"]),
+                                    cell(&b["failure_count"]),
+                                    cell(&b["in_flight"]),
+                                ]
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                print_table(
+                    &["MODEL", "BACKEND", "STATE", "SCORE", "WEIGHT", "PRIORITY", "LATENCY_MS", "FAILURES", "IN_FLIGHT"],
+                    &rows,
+                );
+            }
+            BackendsCommand::Disable { provider, model } => {
+                let result = client
+                    .post("backends/disable", json!({ "provider": provider, "model": model }))
+                    .await?;
+                if output == OutputFormat::Json {
+                    return print_json(&result);
+                }
+                println!("backend {} disabled", cell(&result["backend_key"]));
+            }
+            BackendsCommand::Enable { provider, model } => {
+                let result = client
+                    .post("backends/enable", json!({ "provider": provider, "model": model }))
+                    .await?;
+                if output == OutputFormat::Json {
+                    return print_json(&result);
+                }
+                println!("backend {} enabled", cell(&result["backend_key"]));
+            }
+        },
+        Command::Faults { command } => match command {
+            FaultsCommand::List => {
+                let result = client.get("faults").await?;
+                if output == OutputFormat::Json {
+                    return print_json(&result);
+                }
+
+                if result["enabled"].as_bool() != Some(true) {
+                    println!("fault injection is disabled (settings.fault_injection)");
+                }
+                let rows: Vec<Vec<String>> = result["faults"]
+                    .as_array()
+                    .map(|faults| {
+                        faults
+                            .iter()
+                            .map(|f| {
+                                vec![
+                                    cell(&f["backend"]),
+                                    cell(&f["latency_ms"]),
+                                    format!("{}% ({})", cell(&f["error_percent"]), cell(&f["error_status"])),
+                                    format!("{}% (after {})", cell(&f["drop_stream_percent"]), cell(&f["drop_after_chunks"])),
+                                    cell(&f["expires_at"]),
+                                    format!("{}/{}/{}", cell(&f["delayed"]), cell(&f["errors"]), cell(&f["dropped_streams"])),
+                                ]
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                print_table(
+                    &["BACKEND", "LATENCY_MS", "ERRORS", "DROP_STREAM", "EXPIRES_AT", "INJECTED(D/E/S)"],
+                    &rows,
+                );
+            }
+            FaultsCommand::Set {
+                provider,
+                model,
+                latency_ms,
+                error_percent,
+                error_status,
+                drop_stream_percent,
+                drop_after_chunks,
+                duration,
+            } => {
+                let result = client
+                    .post(
+                        "faults",
+                        json!({
+                            "provider": provider,
+                            "model": model,
+                            "latency_ms": latency_ms,
+                            "error_percent": error_percent,
+                            "error_status": error_status,
+                            "drop_stream_percent": drop_stream_percent,
+                            "drop_after_chunks": drop_after_chunks,
+                            "duration_seconds": duration,
+                        }),
+                    )
+                    .await?;
+                if output == OutputFormat::Json {
+                    return print_json(&result);
+                }
+                match result["expires_at"].as_str() {
+                    Some(expires_at) => println!("fault injected into {} until {}", cell(&result["backend"]), expires_at),
+                    None => println!("fault injected into {}", cell(&result["backend"])),
+                }
+            }
+            FaultsCommand::Clear { provider, model } => {
+                let query: Vec<(&str, String)> = match (provider, model) {
+                    (Some(provider), Some(model)) => vec![("provider", provider), ("model", model)],
+                    _ => Vec::new(),
+                };
+                let result = client.delete_with_query("faults", &query).await?;
+                if output == OutputFormat::Json {
+                    return print_json(&result);
+                }
+                println!("{} fault(s) cleared", cell(&result["cleared"]));
+            }
+        },
+        Command::Replay { file, user, model, limit, api_key } => {
+            replay_captures(
+                &client,
+                &file,
+                user.as_deref(),
+                model.as_deref(),
+                limit,
+                api_key.as_deref(),
+                output,
+            )
+            .await?;
+        }
+        Command::Bench {
+            model,
+            rps,
+            duration,
+            stream,
+            max_tokens,
+            concurrency,
+            api_key,
+        } => {
+            let options = BenchOptions {
+                model,
+                rps,
+                duration,
+                stream,
+                max_tokens,
+                concurrency,
+                api_key,
+            };
+            run_bench(&client, options, output).await?;
+        }
+        Command::RoutePreview { model, n, strategy, region } => {
+            let mut query = vec![("model", model), ("n", n.to_string())];
+            if let Some(strategy) = strategy {
+                query.push(("strategy", strategy));
+            }
+            if let Some(region) = region {
+                query.push(("region", region));
+            }
+            let result = client.get_with_query("route-preview", &query).await?;
+            if output == OutputFormat::Json {
+                return print_json(&result);
+            }
+
+            println!(
+                "model: {}  strategy: {}  samples: {}/{}",
+                cell(&result["model_id"]),
+                cell(&result["strategy"]),
+                cell(&result["completed"]),
+                cell(&result["samples"])
+            );
+            if let Some(error) = result["error"].as_str() {
+                println!("selection failed: {}", error);
+            }
+            println!();
+
+            let rows: Vec<Vec<String>> = result["backends"]
+                .as_array()
+                .map(|backends| {
+                    backends
+                        .iter()
+                        .map(|b| {
+                            vec![
+                                format!("{}:{}", cell(&b["provider"]), cell(&b["model"])),
+                                cell(&b["weight"]),
+                                format!("{:.2}", b["effective_weight"].as_f64().unwrap_or(0.0)),
+                                cell(&b["healthy"]),
+                                cell(&b["selected"]),
+                                format!("{:.1}%", b["share"].as_f64().unwrap_or(0.0) * 100.0),
+                            ]
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            print_table(
+                &["BACKEND", "WEIGHT", "EFFECTIVE", "HEALTHY", "SELECTED", "SHARE"],
+                &rows,
+            );
+        }
+        Command::Reload => {
+            let result = client.post("reload", json!({})).await?;
+            if output == OutputFormat::Json {
+                return print_json(&result);
+            }
+            println!("configuration reloaded ({} models)", cell(&result["models"]));
+        }
+        Command::Keys { command } => match command {
+            KeysCommand::Create { name, models, tags, budget, priority, allowed_ips, tenant } => {
+                let mut body = json!({
+                    "name": name,
+                    "allowed_models": models,
+                    "tags": tags,
+                    "budget": budget,
+                    "allowed_ips": allowed_ips,
+                });
+                if let Some(priority) = priority {
+                    body["priority"] = json!(priority);
+                }
+                let result = client.post(&tenant_path(tenant.as_deref(), "keys"), body).await?;
+                if output == OutputFormat::Json {
+                    return print_json(&result);
+                }
+                print_table(
+                    &["NAME", "TOKEN", "MODELS"],
+                    &[vec![
+                        cell(&result["name"]),
+                        cell(&result["token"]),
+                        cell(&result["allowed_models"]),
+                    ]],
+                );
+                if result["persisted"] != json!(true) {
+                    eprintln!("note: runtime keys are lost on restart; set settings.keys_file to persist them");
+                }
+            }
+            KeysCommand::Revoke { name, tenant } => {
+                let result = client.delete(&tenant_path(tenant.as_deref(), &format!("keys/{}", name))).await?;
+                if output == OutputFormat::Json {
+                    return print_json(&result);
+                }
+                println!("key {} revoked", cell(&result["name"]));
+            }
+            KeysCommand::Hash { token } => {
+                let hash = format!("sha256:{:x}", Sha256::digest(token.as_bytes()));
+                if output == OutputFormat::Json {
+                    return print_json(&json!({ "token_hash": hash }));
+                }
+                println!("{}", hash);
+            }
+        },
+        Command::Config { command } => match command {
+            ConfigCommand::Show { tenant } => {
+                print_json(&client.get(&tenant_path(tenant.as_deref(), "config")).await?)?;
+            }
+            ConfigCommand::Patch { patch, tenant, persist } => {
+                let patch = match patch.strip_prefix('@') {
+                    Some(path) => std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?,
+                    None => patch,
+                };
+                let body: Value = serde_json::from_str(&patch).context("Patch must be valid JSON")?;
+                let path = tenant_path(tenant.as_deref(), "config");
+                let result = client.patch(&path, &[("persist", persist.to_string())], body).await?;
+                if output == OutputFormat::Json {
+                    return print_json(&result);
+                }
+                println!(
+                    "configuration patched ({} providers, {} models)",
+                    cell(&result["providers"]),
+                    cell(&result["models"])
+                );
+                if let Some(error) = result["persist_error"].as_str() {
+                    eprintln!("warning: applied but not persisted: {}", error);
+                }
+            }
+            ConfigCommand::History => {
+                let result = client.get("config/history").await?;
+                if output == OutputFormat::Json {
+                    return print_json(&result);
+                }
+                let rows: Vec<Vec<String>> = result["versions"]
+                    .as_array()
+                    .map(|versions| {
+                        versions
+                            .iter()
+                            .map(|v| {
+                                vec![
+                                    format!("{}{}", cell(&v["version"]), if v["current"] == json!(true) { "*" } else { "" }),
+                                    cell(&v["applied_at"]),
+                                    cell(&v["source"]),
+                                    cell(&v["providers"]),
+                                    cell(&v["models"]),
+                                    cell(&v["users"]),
+                                ]
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                print_table(&["VERSION", "APPLIED", "SOURCE", "PROVIDERS", "MODELS", "USERS"], &rows);
+            }
+            ConfigCommand::Rollback { version, persist } => {
+                let result = client
+                    .post_with_query("config/rollback", &[("persist", persist.to_string())], json!({ "version": version }))
+                    .await?;
+                if output == OutputFormat::Json {
+                    return print_json(&result);
+                }
+                println!(
+                    "rolled back to version {} (now version {})",
+                    cell(&result["rolled_back_to"]),
+                    cell(&result["version"])
+                );
+                if let Some(error) = result["persist_error"].as_str() {
+                    eprintln!("warning: applied but not persisted: {}", error);
+                }
+            }
+            ConfigCommand::Convert { input, output, from, to } => {
+                convert_config(&input, output.as_deref(), from.as_deref(), to.as_deref())?;
+            }
+            ConfigCommand::Validate { path, format, probe } => {
+                let path = path.unwrap_or_else(config_path);
+                validate_config(&path, format.as_deref(), probe, output).await?;
+            }
+        },
+        Command::TailEvents => {
+            let response = client
+                .http
+                .get(client.url("events"))
+                .bearer_auth(client.token()?)
+                .send()
+                .await
+                .with_context(|| format!("Failed to connect to {}", client.base_url))?;
+            if !response.status().is_success() {
+                AdminClient::parse(response).await?;
+                return Ok(());
+            }
+
+            let mut events = response.bytes_stream().eventsource();
+            while let Some(event) = events.next().await {
+                let event = event.context("Event stream error")?;
+                let data: Value = serde_json::from_str(&event.data).unwrap_or(Value::String(event.data));
+                if output == OutputFormat::Json {
+                    println!("{}", data);
+                } else {
+                    println!(
+                        "{}  {:<22}  {}",
+                        cell(&data["timestamp"]),
+                        cell(&data["event"]),
+                        data["details"]
+                    );
+                }
+            }
+        }
+        Command::LogFilter { filter, duration, reset } => {
+            let result = match filter {
+                _ if reset => client.delete("log-filter").await?,
+                Some(filter) => {
+                    client
+                        .put("log-filter", json!({ "filter": filter, "duration_seconds": duration }))
+                        .await?
+                }
+                None if duration.is_some() => bail!("--duration requires a filter"),
+                None => client.get("log-filter").await?,
+            };
+            if output == OutputFormat::Json {
+                return print_json(&result);
+            }
+            println!("filter: {}  default: {}", cell(&result["filter"]), cell(&result["default"]));
+            if let Some(expires_at) = result["expires_at"].as_str() {
+                println!("restores default at {}", expires_at);
+            }
+        }
+        Command::Usage {
+            start,
+            end,
+            bucket,
+            group_by,
+            user,
+            model,
+            provider,
+        } => {
+            let mut query = vec![("bucket", bucket), ("group_by", group_by.join(","))];
+            let filters = [("start", start), ("end", end), ("user", user), ("model", model), ("provider", provider)];
+            query.extend(filters.into_iter().filter_map(|(key, value)| value.map(|v| (key, v))));
+            let report = client.get_with_query("usage", &query).await?;
+            if output == OutputFormat::Json {
+                return print_json(&report);
+            }
+
+            let dimensions: Vec<String> = group_by.iter().map(|d| d.trim().to_string()).collect();
+            let format_row = |label: String, row: &Value| {
+                let mut cells = vec![label];
+                cells.extend(dimensions.iter().map(|d| cell(&row[d.as_str()])));
+                cells.extend([
+                    cell(&row["requests"]),
+                    cell(&row["prompt_tokens"]),
+                    cell(&row["completion_tokens"]),
+                    format!("{:.4}", row["cost"].as_f64().unwrap_or(0.0)),
+                ]);
+                cells
+            };
+            let mut rows: Vec<Vec<String>> = report["rows"]
+                .as_array()
+                .map(|rows| rows.iter().map(|row| format_row(cell(&row["bucket_start"]), row)).collect())
+                .unwrap_or_default();
+            let mut total = format_row("TOTAL".to_string(), &report["total"]);
+            for value in total.iter_mut().skip(1).take(dimensions.len()) {
+                *value = String::new();
+            }
+            rows.push(total);
+
+            let mut headers = vec!["BUCKET".to_string()];
+            headers.extend(dimensions.iter().map(|d| d.to_uppercase()));
+            headers.extend(["REQUESTS", "PROMPT_TOKENS", "COMPLETION_TOKENS", "COST"].map(String::from));
+            print_table(&headers.iter().map(String::as_str).collect::<Vec<_>>(), &rows);
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_replayed_header() {
+        assert!(replayed_header("x-berry-tags", false));
+        assert!(!replayed_header("x-berry-capture", true));
+        assert!(!replayed_header("x-berry-debug", true));
+        assert!(!replayed_header("authorization", true));
+
+        // 只允许管理员使用的请求头只在使用管理员令牌回放时转发
+        for name in ["x-berry-strategy", "x-berry-backend"] {
+            assert!(replayed_header(name, true));
+            assert!(!replayed_header(name, false));
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("60"), Ok(Duration::from_secs(60)));
+        assert_eq!(parse_duration(" 60s "), Ok(Duration::from_secs(60)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+
+        assert!(parse_duration("99999999999999h").unwrap_err().contains("too large"));
+        assert!(parse_duration("10d").unwrap_err().contains("invalid duration unit"));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("s").is_err());
+    }
+
+    #[test]
+    fn test_percentile_ms() {
+        assert_eq!(percentile_ms(&[], 50.0), None);
+
+        let single = [Duration::from_millis(42)];
+        assert_eq!(percentile_ms(&single, 0.0), Some(42));
+        assert_eq!(percentile_ms(&single, 99.0), Some(42));
+
+        // 最近秩向上取整：10个样本的p99是第10个，p90是第9个
+        let samples: Vec<Duration> = (1..=10).map(|i| Duration::from_millis(i * 10)).collect();
+        assert_eq!(percentile_ms(&samples, 99.0), Some(100));
+        assert_eq!(percentile_ms(&samples, 90.0), Some(90));
+        assert_eq!(percentile_ms(&samples, 50.0), Some(50));
+    }
+}
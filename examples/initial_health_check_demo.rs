@@ -18,6 +18,15 @@ fn create_demo_config() -> Config {
         enabled: true,
         timeout_seconds: 10,
         max_retries: 2,
+        tls_pinning: None,
+        tls: None,
+        http_client: None,
+        proxy: None,
+        concurrency: None,
+        quota: None,
+        key_budget: None,
+        timeouts: None,
+        health_check: None,
     });
 
     // 会失败的provider
@@ -30,6 +39,15 @@ fn create_demo_config() -> Config {
         enabled: true,
         timeout_seconds: 5,
         max_retries: 1,
+        tls_pinning: None,
+        tls: None,
+        http_client: None,
+        proxy: None,
+        concurrency: None,
+        quota: None,
+        key_budget: None,
+        timeouts: None,
+        health_check: None,
     });
 
     let mut models = HashMap::new();
@@ -44,10 +62,37 @@ fn create_demo_config() -> Config {
                 enabled: true,
                 tags: vec![],
                 billing_mode: BillingMode::PerToken,
+                pricing: None,
+                overrides: HashMap::new(),
+                slow_start: None,
+                concurrency: None,
+                schedule: None,
+                region: None,
+                max_context: None,
+                supports_vision: true,
+                supports_tools: true,
+                supports_json_mode: true,
+                supports_streaming: true,
+                supports_responses: false,
             },
         ],
         strategy: LoadBalanceStrategy::WeightedFailover,
         enabled: true,
+        virtual_nodes: 100,
+        semantic_cache: true,
+        budget: None,
+        fallback_models: Vec::new(),
+        transforms: Vec::new(),
+        hedging: None,
+        max_in_flight_requests: None,
+        mirror: None,
+        experiment: None,
+        retry: None,
+        latency_slo_ms: None,
+        structured_output: None,
+        fair_queue: None,
+        timeouts: None,
+        content_filters: Vec::new(),
     });
 
     models.insert("failing-demo-model".to_string(), ModelMapping {
@@ -61,16 +106,45 @@ fn create_demo_config() -> Config {
                 enabled: true,
                 tags: vec![],
                 billing_mode: BillingMode::PerToken,
+                pricing: None,
+                overrides: HashMap::new(),
+                slow_start: None,
+                concurrency: None,
+                schedule: None,
+                region: None,
+                max_context: None,
+                supports_vision: true,
+                supports_tools: true,
+                supports_json_mode: true,
+                supports_streaming: true,
+                supports_responses: false,
             },
         ],
         strategy: LoadBalanceStrategy::WeightedFailover,
         enabled: true,
+        virtual_nodes: 100,
+        semantic_cache: true,
+        budget: None,
+        fallback_models: Vec::new(),
+        transforms: Vec::new(),
+        hedging: None,
+        max_in_flight_requests: None,
+        mirror: None,
+        experiment: None,
+        retry: None,
+        latency_slo_ms: None,
+        structured_output: None,
+        fair_queue: None,
+        timeouts: None,
+        content_filters: Vec::new(),
     });
 
     Config {
         providers,
         models,
         users: HashMap::new(),
+        peers: HashMap::new(),
+        tenants: HashMap::new(),
         settings: GlobalSettings {
             health_check_interval_seconds: 15, // 较短的间隔用于演示
             request_timeout_seconds: 10,
@@ -80,6 +154,49 @@ fn create_demo_config() -> Config {
             recovery_check_interval_seconds: 20,
             max_internal_retries: 2,
             health_check_timeout_seconds: 10,
+            failover_memory_ttl_seconds: 300,
+            model_degraded_ratio: 0.75,
+            model_down_ratio: 0.25,
+            model_health_hysteresis: 0.1,
+            health_webhook_urls: vec![],
+            readiness_required_models: Vec::new(),
+            config_hot_reload: true,
+            peer_max_hops: 1,
+            response_cache_enabled: false,
+            response_cache_ttl_seconds: 300,
+            response_cache_max_entries: 1000,
+            semantic_cache: None,
+            latency_ewma_alpha: 0.3,
+            audit_log: None,
+            keys_file: None,
+            max_embedding_batch_size: 2048,
+            max_request_body_mb: 32,
+            slow_start: Default::default(),
+            outlier_detection: None,
+            max_in_flight_requests: None,
+            priority_scheduling: None,
+            shared_state: None,
+            secrets: None,
+            config_history: Default::default(),
+            health_scoring: None,
+            region_routing: None,
+            content_filters: HashMap::new(),
+            moderation: None,
+            wasm_plugins: None,
+            notifications: None,
+            alerts: None,
+            slo: None,
+            request_log: None,
+            jwt: None,
+            network_acl: None,
+            cors: None,
+            grpc: None,
+            job_queue: None,
+            files: None,
+            stream_heartbeat: None,
+            stream_resumption: None,
+            request_capture: None,
+            fault_injection: false,
         },
     }
 }
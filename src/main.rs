@@ -1,5 +1,12 @@
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // --check-config：只检查配置后退出，加上--probe时同时探测各provider
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--check-config") {
+        berry_api_api::check_server_config(args.iter().any(|arg| arg == "--probe")).await?;
+        return Ok(());
+    }
+
     berry_api_api::start_server().await?;
     Ok(())
 }